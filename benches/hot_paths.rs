@@ -0,0 +1,148 @@
+//! Criterion benchmarks for the CPU hot paths, as a baseline for optimization work.
+//! Run with `cargo bench --bench hot_paths` (or `cargo bench --bench hot_paths -- raycast` for one group).
+
+use std::{hint::black_box, time::Duration};
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use glam::{vec3, vec3a, Mat4, Quat};
+use wgpu_learn::{
+    math::{average::AverageBuffer, morton::{morton3_decode, morton3_encode}, ray::Ray3},
+    modeling::modeler::Modeler,
+    rendering::raytrace::RaytraceChunk,
+    voxel::worldgen::generate_hills,
+};
+
+const RAYS: usize = 4096;
+const MAX_DISTANCE: f32 = 200.0;
+
+/// Rays from the same terrain, in four flavors that exercise different parts of the traversal.
+fn rays(chunk: &RaytraceChunk) -> [(&'static str, Vec<Ray3>); 4] {
+    let spread = |i: usize| {
+        let angle = i as f32 * 0.618_034 * std::f32::consts::TAU;
+        (angle.cos(), angle.sin())
+    };
+    // Upwards from above the terrain, through empty cells until the ray leaves the chunk.
+    let miss = (0..RAYS).map(|i| {
+        let (cos, sin) = spread(i);
+        let pos = vec3a(32.0 + cos * 16.0, 60.0, 32.0 + sin * 16.0);
+        Ray3::new(pos, vec3a(cos, 0.3, sin).normalize())
+    }).collect();
+    // Almost level, just above the ground, so the ray skims the hills for a long way.
+    let graze = (0..RAYS).map(|i| {
+        let z = (i % 64) as i32;
+        let ground = chunk.highest_solid_at(0, z).unwrap_or(0) as f32;
+        let (_, sin) = spread(i);
+        let pos = vec3a(0.01, ground + 1.5, z as f32 + 0.5);
+        Ray3::new(pos, vec3a(1.0, -0.02, sin * 0.05).normalize())
+    }).collect();
+    // Steeply down onto the terrain.
+    let hit = (0..RAYS).map(|i| {
+        let (cos, sin) = spread(i);
+        let pos = vec3a(32.0 + cos * 20.0, 63.5, 32.0 + sin * 20.0);
+        Ray3::new(pos, vec3a((i % 97) as f32 / 97.0 - 0.5, -1.0, (i % 89) as f32 / 89.0 - 0.5).normalize())
+    }).collect();
+    // Down at an angle from above the hills, scattered in every direction.
+    let scatter = (0..RAYS).map(|i| {
+        let (cos, sin) = spread(i);
+        let pos = vec3a(32.0 + cos * 20.0, 50.0, 32.0 + sin * 20.0);
+        Ray3::new(pos, vec3a((i % 97) as f32 / 48.5 - 1.0, -0.7, (i % 89) as f32 / 44.5 - 1.0).normalize())
+    }).collect();
+    [("miss", miss), ("graze", graze), ("hit", hit), ("scatter", scatter)]
+}
+
+/// Compares the linear and the Morton ordered block storage on every flavor of [rays].
+fn raycast(c: &mut Criterion) {
+    let mut chunk = generate_hills(7, 1);
+    let rays = rays(&chunk);
+    let mut group = c.benchmark_group("raycast");
+    group.throughput(Throughput::Elements(RAYS as u64));
+    for morton in [false, true] {
+        chunk.set_morton_order(morton);
+        let order = if morton { "morton" } else { "linear" };
+        for (name, rays) in &rays {
+            group.bench_with_input(BenchmarkId::new(*name, order), rays, |b, rays| {
+                b.iter(|| rays.iter().filter(|&&ray| chunk.raycast(black_box(ray), MAX_DISTANCE).is_some()).count())
+            });
+        }
+    }
+    group.finish();
+}
+
+fn morton(c: &mut Criterion) {
+    let mut group = c.benchmark_group("morton");
+    // Every cell of a 64³ chunk.
+    group.throughput(Throughput::Elements(64 * 64 * 64));
+    group.bench_function("encode", |b| {
+        b.iter(|| (0..64 * 64 * 64u32).fold(0, |acc, i| acc ^ morton3_encode(black_box(i & 63), (i >> 6) & 63, i >> 12)))
+    });
+    group.bench_function("decode", |b| {
+        b.iter(|| (0..64 * 64 * 64u32).fold(0, |acc, i| {
+            let (x, y, z) = morton3_decode(black_box(i));
+            acc ^ x ^ y ^ z
+        }))
+    });
+    group.finish();
+}
+
+/// Pushes a quad at the bottom of `depth` nested transforms.
+fn push_nested(modeler: &mut Modeler, depth: u32) {
+    if depth == 0 {
+        modeler.push_unit_quad(0);
+        return;
+    }
+    let rotation = Quat::from_rotation_y(0.1);
+    modeler.transform(Mat4::from_rotation_translation(rotation, vec3(1.0, 0.0, 0.5)), |modeler| {
+        push_nested(modeler, depth - 1);
+    });
+}
+
+fn modeler(c: &mut Criterion) {
+    let mut group = c.benchmark_group("modeler");
+    group.throughput(Throughput::Elements(256));
+    group.bench_function("unit_quads_16x16", |b| {
+        b.iter_batched_ref(Modeler::new, |modeler| {
+            for y in 0..16 {
+                for x in 0..16 {
+                    modeler.translate(vec3(x as f32, 0.0, y as f32), |modeler| {
+                        modeler.push_unit_quad(0);
+                    });
+                }
+            }
+        }, BatchSize::SmallInput)
+    });
+    for depth in [8, 64] {
+        group.bench_with_input(BenchmarkId::new("nested_transforms", depth), &depth, |b, &depth| {
+            b.iter_batched_ref(Modeler::new, |modeler| {
+                for _ in 0..256 {
+                    push_nested(modeler, depth);
+                }
+            }, BatchSize::SmallInput)
+        });
+    }
+    group.finish();
+}
+
+fn average_buffer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("average_buffer");
+    let times = (0..1000u64).map(|i| Duration::from_micros(8000 + (i * 7919) % 4000)).collect::<Vec<_>>();
+    // The frame time buffers are full most of the time, so every push also drops a value.
+    group.bench_function("push_full", |b| {
+        let mut buffer = AverageBuffer::new(100, None);
+        times.iter().take(100).for_each(|&time| { buffer.push(time); });
+        b.iter(|| times.iter().fold(Duration::ZERO, |acc, &time| acc + buffer.push(black_box(time))))
+    });
+    for capacity in [100, 1000] {
+        let mut buffer = AverageBuffer::new(capacity, None);
+        times.iter().take(capacity).for_each(|&time| { buffer.push(time); });
+        group.bench_with_input(BenchmarkId::new("percentile", capacity), &buffer, |b, buffer| {
+            b.iter(|| buffer.percentile(black_box(0.99)))
+        });
+        group.bench_with_input(BenchmarkId::new("min_max", capacity), &buffer, |b, buffer| {
+            b.iter(|| (buffer.min(), buffer.max()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, raycast, morton, modeler, average_buffer);
+criterion_main!(benches);
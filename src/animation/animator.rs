@@ -1,4 +1,268 @@
+use std::time::Duration;
 
-pub struct Animation {
-    
-}
\ No newline at end of file
+use glam::{Vec2, Vec3};
+
+use crate::camera::Camera;
+
+/*
+Animations are driven by the frame's delta time rather than the clock, so that they pause with the game
+and can be stepped in tests.
+
+An [Animator] interpolates a single value with an easing function from [super::tween]. A [Track] applies
+one or more animators to a target (such as the [Camera]), and [Animations] runs any number of tracks at
+once. Each track gets an [AnimationId] that can be used to cancel it, and can have a callback that runs
+when it completes (but not when it's cancelled).
+*/
+
+/// An easing function that maps `0..=1` to `0..=1`, such as those in [super::tween::f32].
+pub type Easing = fn(f32) -> f32;
+
+pub fn linear(t: f32) -> f32 {
+    t
+}
+
+/// Values that can be interpolated by an [Animator].
+pub trait Lerp: Copy {
+    fn lerp(self, end: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(self, end: Self, t: f32) -> Self {
+        self + (end - self) * t
+    }
+}
+
+impl Lerp for Vec2 {
+    fn lerp(self, end: Self, t: f32) -> Self {
+        Vec2::lerp(self, end, t)
+    }
+}
+
+impl Lerp for Vec3 {
+    fn lerp(self, end: Self, t: f32) -> Self {
+        Vec3::lerp(self, end, t)
+    }
+}
+
+/// A tween from `start` to `end` over `duration`.
+#[derive(Debug, Clone, Copy)]
+pub struct Animator<T: Lerp> {
+    start: T,
+    end: T,
+    duration: Duration,
+    elapsed: Duration,
+    easing: Easing,
+}
+
+impl<T: Lerp> Animator<T> {
+    /// A linear tween. Use [Animator::with_easing] to change the easing.
+    pub fn new(start: T, end: T, duration: Duration) -> Self {
+        Self {
+            start,
+            end,
+            duration,
+            elapsed: Duration::ZERO,
+            easing: linear,
+        }
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    pub fn start(&self) -> T {
+        self.start
+    }
+
+    pub fn end(&self) -> T {
+        self.end
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// The progress before easing, in `0..=1`.
+    pub fn alpha(&self) -> f32 {
+        if self.elapsed >= self.duration {
+            1.0
+        } else {
+            self.elapsed.as_secs_f32() / self.duration.as_secs_f32()
+        }
+    }
+
+    /// The current value.
+    pub fn value(&self) -> T {
+        self.start.lerp(self.end, (self.easing)(self.alpha()))
+    }
+
+    /// Advances the animation and returns the new value.
+    pub fn update(&mut self, delta_time: Duration) -> T {
+        self.elapsed = (self.elapsed + delta_time).min(self.duration);
+        self.value()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+/// Something that can be animated by [Animations].
+pub trait Track<C> {
+    /// Advances the track and applies it to the target. Returns `true` once the track is finished.
+    fn update(&mut self, target: &mut C, delta_time: Duration) -> bool;
+}
+
+/// A camera animation. The field of view is set directly, without clamping.
+pub enum CameraTrack {
+    Position(Animator<Vec3>),
+    Rotation(Animator<Vec2>),
+    Fov(Animator<f32>),
+}
+
+impl Track<Camera> for CameraTrack {
+    fn update(&mut self, camera: &mut Camera, delta_time: Duration) -> bool {
+        match self {
+            CameraTrack::Position(animator) => {
+                camera.position = animator.update(delta_time);
+                animator.is_finished()
+            }
+            CameraTrack::Rotation(animator) => {
+                camera.rotation = animator.update(delta_time);
+                animator.is_finished()
+            }
+            CameraTrack::Fov(animator) => {
+                camera.fov = animator.update(delta_time);
+                animator.is_finished()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AnimationId(u64);
+
+type OnComplete<C> = Box<dyn FnOnce(&mut C)>;
+
+struct Animation<C> {
+    id: AnimationId,
+    track: Box<dyn Track<C>>,
+    on_complete: Option<OnComplete<C>>,
+}
+
+/// Concurrent animations over a target of type `C`. Tracks are updated in the order that they were started.
+pub struct Animations<C> {
+    next_id: u64,
+    animations: Vec<Animation<C>>,
+}
+
+impl<C> Animations<C> {
+    pub fn new() -> Self {
+        Self {
+            next_id: 0,
+            animations: Vec::new(),
+        }
+    }
+
+    pub fn start<T: Track<C> + 'static>(&mut self, track: T) -> AnimationId {
+        self.insert(Box::new(track), None)
+    }
+
+    /// Starts the track and calls `on_complete` on the target once it's finished.
+    pub fn start_then<T: Track<C> + 'static, F: FnOnce(&mut C) + 'static>(&mut self, track: T, on_complete: F) -> AnimationId {
+        self.insert(Box::new(track), Some(Box::new(on_complete)))
+    }
+
+    fn insert(&mut self, track: Box<dyn Track<C>>, on_complete: Option<OnComplete<C>>) -> AnimationId {
+        let id = AnimationId(self.next_id);
+        self.next_id += 1;
+        self.animations.push(Animation { id, track, on_complete });
+        id
+    }
+
+    /// Stops the animation without calling its completion callback. Returns `false` if it wasn't running.
+    pub fn cancel(&mut self, id: AnimationId) -> bool {
+        let count = self.animations.len();
+        self.animations.retain(|animation| animation.id != id);
+        self.animations.len() != count
+    }
+
+    /// Stops every animation without calling their completion callbacks.
+    pub fn cancel_all(&mut self) {
+        self.animations.clear();
+    }
+
+    pub fn is_running(&self, id: AnimationId) -> bool {
+        self.animations.iter().any(|animation| animation.id == id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.animations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.animations.is_empty()
+    }
+
+    /// Updates every track, then removes the finished ones and calls their completion callbacks.
+    pub fn update(&mut self, target: &mut C, delta_time: Duration) {
+        let mut finished = Vec::new();
+        self.animations.retain_mut(|animation| {
+            if animation.track.update(target, delta_time) {
+                finished.extend(animation.on_complete.take());
+                false
+            } else {
+                true
+            }
+        });
+        for on_complete in finished {
+            on_complete(target);
+        }
+    }
+}
+
+impl<C> Default for Animations<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn animations_test() {
+        let mut animator = Animator::new(0.0f32, 10.0, Duration::from_secs(2));
+        assert_eq!(animator.update(Duration::from_millis(500)), 2.5);
+        let mut eased = animator.with_easing(super::super::tween::f32::quadratic_in);
+        assert_eq!(eased.value(), 0.625);
+        assert_eq!(eased.update(Duration::from_secs(5)), 10.0);
+        assert!(eased.is_finished());
+
+        struct Counter(Animator<f32>);
+        impl Track<Vec<f32>> for Counter {
+            fn update(&mut self, target: &mut Vec<f32>, delta_time: Duration) -> bool {
+                target.push(self.0.update(delta_time));
+                self.0.is_finished()
+            }
+        }
+        let mut values = Vec::new();
+        let mut animations = Animations::new();
+        let short = animations.start_then(Counter(Animator::new(0.0, 1.0, Duration::from_secs(1))), |values: &mut Vec<f32>| values.push(-1.0));
+        let long = animations.start(Counter(Animator::new(0.0, 4.0, Duration::from_secs(4))));
+        let cancelled = animations.start_then(Counter(Animator::new(0.0, 1.0, Duration::from_secs(1))), |values: &mut Vec<f32>| values.push(-2.0));
+        assert!(animations.cancel(cancelled));
+        assert!(!animations.cancel(cancelled));
+        animations.update(&mut values, Duration::from_secs(1));
+        // The completion callback runs after every track was updated.
+        assert_eq!(values, [1.0, 1.0, -1.0]);
+        assert!(!animations.is_running(short));
+        assert!(animations.is_running(long));
+        animations.update(&mut values, Duration::from_secs(1));
+        assert_eq!(values, [1.0, 1.0, -1.0, 2.0]);
+        animations.cancel_all();
+        assert!(animations.is_empty());
+    }
+}
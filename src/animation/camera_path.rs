@@ -0,0 +1,247 @@
+use std::{io::{BufReader, BufWriter, Read, Write}, path::Path, time::Duration};
+
+use glam::*;
+
+use crate::camera::Camera;
+
+use super::animator::{Animator, Easing, Track};
+
+/*
+Camera paths are a list of evenly spaced keyframes. Positions are interpolated with a Catmull-Rom
+spline (so the camera passes through every keyframe without stopping), and rotations are slerped.
+
+Paths are saved as (big endian):
+
+    magic: b"WLCP", version: u32,
+    keyframe_count: u32,
+    keyframes: [(position: [f32; 3], rotation: [f32; 2])]
+*/
+
+const CAMERA_PATH_MAGIC: [u8; 4] = *b"WLCP";
+const CAMERA_PATH_VERSION: u32 = 1;
+/// The most keyframes that can be read, to reject corrupt files before allocating.
+pub const MAX_CAMERA_PATH_KEYFRAMES: u32 = 65536;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CameraPathError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Not a camera path.")]
+    InvalidMagic,
+    #[error("Unsupported camera path version {0}, expected {CAMERA_PATH_VERSION}.")]
+    UnsupportedVersion(u32),
+    #[error("Too many keyframes ({0}).")]
+    TooManyKeyframes(u32),
+}
+
+/// A camera pose. The rotation is the same as [Camera::rotation].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraKeyframe {
+    pub position: Vec3,
+    pub rotation: Vec2,
+}
+
+impl CameraKeyframe {
+    pub fn from_camera(camera: &Camera) -> Self {
+        Self {
+            position: camera.position,
+            rotation: camera.rotation,
+        }
+    }
+
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.position = self.position;
+        camera.rotation = self.rotation;
+    }
+
+    fn quat(&self) -> Quat {
+        Quat::from_euler(EulerRot::YXZ, self.rotation.y, self.rotation.x, 0.0)
+    }
+}
+
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (
+        2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3
+    )
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CameraPath {
+    keyframes: Vec<CameraKeyframe>,
+}
+
+impl CameraPath {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn keyframes(&self) -> &[CameraKeyframe] {
+        &self.keyframes
+    }
+
+    pub fn push(&mut self, keyframe: CameraKeyframe) {
+        self.keyframes.push(keyframe);
+    }
+
+    pub fn clear(&mut self) {
+        self.keyframes.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.keyframes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keyframes.is_empty()
+    }
+
+    /// The pose at `t` in `0..=1` along the path, or `None` if there are no keyframes.
+    pub fn sample(&self, t: f32) -> Option<CameraKeyframe> {
+        let last = self.keyframes.len().checked_sub(1)?;
+        if last == 0 {
+            return Some(self.keyframes[0]);
+        }
+        let scaled = t.clamp(0.0, 1.0) * last as f32;
+        let index = (scaled as usize).min(last - 1);
+        let local = scaled - index as f32;
+        let key = |index: usize| self.keyframes[index.min(last)];
+        let (k1, k2) = (key(index), key(index + 1));
+        let position = catmull_rom(
+            key(index.saturating_sub(1)).position,
+            k1.position,
+            k2.position,
+            key(index + 2).position,
+            local,
+        );
+        let (yaw, pitch, _) = k1.quat().slerp(k2.quat(), local).to_euler(EulerRot::YXZ);
+        Some(CameraKeyframe {
+            position,
+            rotation: vec2(pitch, yaw.rem_euclid(std::f32::consts::TAU)),
+        })
+    }
+
+    /// A track that moves the camera along the path over `duration`.
+    pub fn playback(&self, duration: Duration) -> CameraPathTrack {
+        CameraPathTrack {
+            path: self.clone(),
+            animator: Animator::new(0.0, 1.0, duration),
+        }
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), CameraPathError> {
+        if self.keyframes.len() > MAX_CAMERA_PATH_KEYFRAMES as usize {
+            return Err(CameraPathError::TooManyKeyframes(self.keyframes.len() as u32));
+        }
+        writer.write_all(&CAMERA_PATH_MAGIC)?;
+        writer.write_all(&CAMERA_PATH_VERSION.to_be_bytes())?;
+        writer.write_all(&(self.keyframes.len() as u32).to_be_bytes())?;
+        for keyframe in self.keyframes.iter() {
+            for value in keyframe.position.to_array().into_iter().chain(keyframe.rotation.to_array()) {
+                writer.write_all(&value.to_be_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self, CameraPathError> {
+        if read_bytes::<_, 4>(reader)? != CAMERA_PATH_MAGIC {
+            return Err(CameraPathError::InvalidMagic);
+        }
+        let version = u32::from_be_bytes(read_bytes(reader)?);
+        if version != CAMERA_PATH_VERSION {
+            return Err(CameraPathError::UnsupportedVersion(version));
+        }
+        let count = u32::from_be_bytes(read_bytes(reader)?);
+        if count > MAX_CAMERA_PATH_KEYFRAMES {
+            return Err(CameraPathError::TooManyKeyframes(count));
+        }
+        let mut read_f32 = || read_bytes(reader).map(f32::from_be_bytes);
+        let keyframes = (0..count).map(|_| Ok(CameraKeyframe {
+            position: vec3(read_f32()?, read_f32()?, read_f32()?),
+            rotation: vec2(read_f32()?, read_f32()?),
+        })).collect::<Result<Vec<_>, CameraPathError>>()?;
+        Ok(Self { keyframes })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), CameraPathError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut writer = BufWriter::new(std::fs::File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, CameraPathError> {
+        let mut reader = BufReader::new(std::fs::File::open(path)?);
+        Self::read(&mut reader)
+    }
+}
+
+fn read_bytes<R: Read, const N: usize>(reader: &mut R) -> std::io::Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Plays a [CameraPath] back, see [CameraPath::playback].
+pub struct CameraPathTrack {
+    path: CameraPath,
+    animator: Animator<f32>,
+}
+
+impl CameraPathTrack {
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.animator = self.animator.with_easing(easing);
+        self
+    }
+}
+
+impl Track<Camera> for CameraPathTrack {
+    fn update(&mut self, camera: &mut Camera, delta_time: Duration) -> bool {
+        if let Some(keyframe) = self.path.sample(self.animator.update(delta_time)) {
+            keyframe.apply(camera);
+        }
+        self.animator.is_finished()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camera_path_test() {
+        let mut path = CameraPath::new();
+        assert_eq!(path.sample(0.5), None);
+        let a = CameraKeyframe { position: vec3(0.0, 0.0, 0.0), rotation: vec2(0.0, 0.5) };
+        let b = CameraKeyframe { position: vec3(10.0, 2.0, 0.0), rotation: vec2(0.0, 1.5) };
+        let c = CameraKeyframe { position: vec3(10.0, 2.0, 10.0), rotation: vec2(-0.25, 3.0) };
+        path.push(a);
+        path.push(b);
+        // With two keyframes the spline is a straight line, and turning around one axis is linear.
+        let mid = path.sample(0.5).unwrap();
+        assert!(mid.position.abs_diff_eq(vec3(5.0, 1.0, 0.0), 1e-5));
+        assert!(mid.rotation.abs_diff_eq(vec2(0.0, 1.0), 1e-4));
+        path.push(c);
+        // The path passes through every keyframe.
+        for (t, keyframe) in [(0.0, a), (0.5, b), (1.0, c)] {
+            let sample = path.sample(t).unwrap();
+            assert!(sample.position.abs_diff_eq(keyframe.position, 1e-5));
+            assert!(sample.rotation.abs_diff_eq(keyframe.rotation, 1e-4));
+        }
+
+        let mut bytes = Vec::new();
+        path.write(&mut bytes).unwrap();
+        assert_eq!(CameraPath::read(&mut bytes.as_slice()).unwrap(), path);
+        assert!(matches!(CameraPath::read(&mut &b"nope"[..]), Err(CameraPathError::InvalidMagic)));
+        assert!(matches!(CameraPath::read(&mut &bytes[..bytes.len() - 1]), Err(CameraPathError::Io(_))));
+    }
+}
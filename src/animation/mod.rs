@@ -1,3 +1,4 @@
 pub mod tween;
 pub mod animator;
-pub mod animtimer;
\ No newline at end of file
+pub mod animtimer;
+pub mod camera_path;
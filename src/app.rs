@@ -0,0 +1,255 @@
+use std::{path::PathBuf, time::{Duration, Instant}};
+
+use winit::{
+    dpi::{PhysicalSize, Size},
+    error::{EventLoopError, OsError},
+    event::{ElementState, Event, KeyEvent, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    keyboard::{KeyCode, PhysicalKey},
+    window::{Window, WindowBuilder},
+};
+
+use crate::{
+    framepace::{FrameLimit, Framepace},
+    math::average::AverageBuffer,
+    state::{State, ASSETS_ROOT},
+    user_config::UserConfig,
+    window_config::{center_window, FullscreenMode, WindowConfig},
+    FrameInfo,
+};
+
+/*
+The App runs the window and the frame loop, so that a binary only has to configure it:
+
+    App::new(settings).run(|window, settings| {
+        let mut state = pollster::block_on(State::with_assets_root(window, settings.assets_root.clone()));
+        state.systems.add(OverlaySystem);
+        state
+    })
+
+The factory creates the State for the window. Everything else is done by the App:
+
+    - Creating the window from `GameSettings::window` and applying its config and present mode to the
+      State.
+    - Dispatching winit and gamepad events to the State.
+    - Every frame: frame pacing (see [Framepace]), the FrameInfo bookkeeping, State::begin_frame, update,
+      render and end_frame, and recording the frame times.
+    - Surface errors, lost devices, focus changes and closing the window.
+*/
+
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("Failed to create the event loop: {0}")]
+    EventLoop(#[from] EventLoopError),
+    #[error("Failed to create the window: {0}")]
+    Window(#[from] OsError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Timer(Instant);
+
+impl Timer {
+    fn start() -> Self {
+        Self(Instant::now())
+    }
+
+    /// Resets the timer and returns the [Duration].
+    fn time(&mut self) -> Duration {
+        let duration = self.0.elapsed();
+        self.0 = Instant::now();
+        duration
+    }
+
+    fn split_time(&mut self) -> Self {
+        let old = *self;
+        self.0 = Instant::now();
+        old
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.0.elapsed()
+    }
+
+    fn framerate(&self) -> f64 {
+        1.0 / self.0.elapsed().as_secs_f64()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GameSettings {
+    pub present_mode: wgpu::PresentMode,
+    pub camera_smoothing_frame_count: Option<usize>,
+    /// The number of frames that the FPS is averaged over.
+    pub framerate_frame_count: usize,
+    pub window: WindowConfig,
+    pub frame_limit: FrameLimit,
+    pub assets_root: PathBuf,
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        Self {
+            present_mode: wgpu::PresentMode::Fifo,
+            camera_smoothing_frame_count: None,
+            framerate_frame_count: 32,
+            window: WindowConfig::default(),
+            frame_limit: FrameLimit::RefreshRate,
+            assets_root: PathBuf::from(ASSETS_ROOT),
+        }
+    }
+}
+
+impl GameSettings {
+    /// Applies the window and present mode from the user config. The rest is applied by [State::apply_user_config].
+    pub fn apply_user_config(&mut self, config: &UserConfig) {
+        self.present_mode = config.present_mode;
+        self.window.size = Size::Physical(PhysicalSize::new(config.window_size.0, config.window_size.1));
+        if config.fullscreen {
+            self.window.fullscreen = FullscreenMode::Borderless;
+        }
+    }
+}
+
+pub struct App {
+    settings: GameSettings,
+}
+
+impl App {
+    pub fn new(settings: GameSettings) -> Self {
+        Self { settings }
+    }
+
+    pub fn settings(&self) -> &GameSettings {
+        &self.settings
+    }
+
+    /// Creates the window, creates the State with `state_factory` and runs the frame loop until the
+    /// window is closed.
+    pub fn run<F>(self, state_factory: F) -> Result<(), AppError>
+    where F: for<'w> FnOnce(&'w Window, &GameSettings) -> State<'w> {
+        let settings = self.settings;
+        let event_loop = EventLoop::new()?;
+        event_loop.set_control_flow(ControlFlow::Poll);
+        let mut window_builder = WindowBuilder::new()
+            .with_inner_size(settings.window.size)
+            .with_title(settings.window.title.as_str());
+        if let Some(min_size) = settings.window.min_size {
+            window_builder = window_builder.with_min_inner_size(min_size);
+        }
+        let window = window_builder.build(&event_loop)?;
+        if let Some(monitor) = settings.window.select_monitor(&window) {
+            center_window(&window, &monitor);
+        }
+        let mut state = state_factory(&window, &settings);
+        state.apply_window_config(settings.window.clone());
+        if settings.present_mode != state.config.present_mode {
+            state.set_present_mode(settings.present_mode);
+        }
+
+        let refresh_rate = state.window().current_monitor()
+            .and_then(|monitor| monitor.refresh_rate_millihertz())
+            .map(|refresh| refresh as f64 / 1000.0);
+        if let Some(refresh_rate) = refresh_rate {
+            log::info!("Refresh rate: {refresh_rate}");
+        }
+        let mut timer = Timer::start();
+        let mut focused = true;
+        let mut framepace = Framepace::new(8, settings.frame_limit, refresh_rate);
+        let mut fps_avgs = AverageBuffer::<f64>::new(settings.framerate_frame_count, None);
+
+        let mut frame = FrameInfo {
+            index: 0,
+            fps: 0.0,
+            last_frame_time: Duration::from_secs(0),
+            delta_time: Duration::from_secs(0),
+        };
+        let mut loop_timer = Timer::start();
+        event_loop.run(move |event, control_flow| {
+            while let Some(event) = state.gamepad.next_event() {
+                state.process_gamepad_event(&event);
+            }
+            state.process_event(&event);
+            match event {
+                Event::WindowEvent {
+                    ref event,
+                    window_id,
+                } if window_id == state.window.id() && !state.process_window_event(event) => match event {
+                    WindowEvent::CloseRequested
+                    | WindowEvent::KeyboardInput {
+                        event:
+                            // Escape key pressed
+                            KeyEvent {
+                                state: ElementState::Pressed,
+                                physical_key: PhysicalKey::Code(KeyCode::Escape),
+                                ..
+                            },
+                        ..
+                    } if state.close_requested() => control_flow.exit(),
+                    WindowEvent::Focused(focus) => {
+                        focused = *focus;
+                        state.focus_changed(focused);
+                    }
+                    WindowEvent::Resized(physical_size) => {
+                        state.resize(*physical_size);
+                    }
+                    WindowEvent::RedrawRequested => {
+                        // Sleep off the remainder of the frame so that update and render
+                        // finish just before the next frame is due.
+                        framepace.wait_for_update();
+                        let frame_time = loop_timer.split_time();
+                        frame.fps = fps_avgs.push(frame_time.framerate());
+
+                        if state.device_lost() {
+                            if let Err(err) = state.recover_lost_device() {
+                                log::error!("Failed to recover from the lost device: {err}");
+                                control_flow.exit();
+                                return;
+                            }
+                        }
+
+                        let frame_elapsed = frame_time.elapsed();
+                        frame.delta_time = frame_elapsed;
+                        state.begin_frame(&mut frame);
+                        framepace.measure_update(|| state.update(&frame));
+
+                        match framepace.measure_render(|| state.render(&frame)) {
+                            Ok(_) => (),
+                            Err(wgpu::SurfaceError::Lost) => state.resize(state.size),
+                            Err(wgpu::SurfaceError::OutOfMemory) => {
+                                log::error!("OutOfMemory");
+                                control_flow.exit()
+                            },
+                            Err(wgpu::SurfaceError::Timeout) => {
+                                log::warn!("Surface timeout");
+                            }
+                            Err(e) => log::error!("Surface error: {e:?}"),
+                        }
+
+                        state.record_frame_times(frame_elapsed, framepace.last_update_time(), framepace.last_render_time());
+                        let time = timer.time();
+                        state.end_frame(&frame);
+                        framepace.end_frame();
+                        frame.last_frame_time = time;
+                        frame.index += 1;
+                        if state.exit_requested() && state.close_requested() {
+                            control_flow.exit();
+                        }
+                    }
+                    _ => {}
+                }
+                Event::LoopExiting => state.save_user_config(),
+                Event::AboutToWait => {
+                    if focused {
+                        control_flow.set_control_flow(ControlFlow::Poll);
+                        state.window().request_redraw();
+                    } else {
+                        // Don't spin while in the background.
+                        control_flow.set_control_flow(ControlFlow::Wait);
+                    }
+                }
+                _ => {}
+            }
+        })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,420 @@
+use std::{collections::HashMap, hash::Hash, marker::PhantomData, path::{Path, PathBuf}};
+
+use image::RgbaImage;
+
+use crate::rendering::compressed::{CompressedError, CompressedImage};
+use crate::rendering::memory::{self, MemoryCategory, Tracked};
+
+/*
+Loads assets relative to a root directory and caches them by path, so loading the same file
+twice returns the same handle. Handles are only valid for the server that created them.
+
+Block-compressed textures (KTX2 or DDS) are cached the same way as images, see
+[crate::rendering::compressed].
+
+Textures created through the server can be reloaded in place: the file is decoded again and
+written into the existing texture, so bind groups that use the texture stay valid.
+*/
+
+#[derive(Debug, thiserror::Error)]
+pub enum AssetError {
+    #[error("Asset not found: {path:?} (looked in {resolved:?})")]
+    NotFound {
+        path: PathBuf,
+        resolved: PathBuf,
+    },
+    #[error("Failed to read {path:?}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Failed to decode image {path:?}: {source}")]
+    Decode {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    #[error("Failed to load compressed texture {path:?}: {source}")]
+    Compressed {
+        path: PathBuf,
+        source: CompressedError,
+    },
+    #[error("{path:?} changed dimensions from {expected:?} to {dimensions:?}, it can't be reloaded in place.")]
+    DimensionsChanged {
+        path: PathBuf,
+        dimensions: (u32, u32),
+        expected: (u32, u32),
+    },
+}
+
+/// A typed index into an [AssetServer].
+pub struct Handle<T> {
+    index: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    const fn new(index: usize) -> Self {
+        Self {
+            index,
+            _marker: PhantomData,
+        }
+    }
+}
+
+// Implemented by hand so that `T` doesn't need to implement these traits.
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+
+impl<T> std::fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Handle<{}>({})", std::any::type_name::<T>(), self.index)
+    }
+}
+
+pub struct TextureAsset {
+    pub texture: Tracked<wgpu::Texture>,
+    pub view: wgpu::TextureView,
+    pub format: wgpu::TextureFormat,
+    pub image: Handle<RgbaImage>,
+}
+
+struct ImageEntry {
+    path: PathBuf,
+    image: RgbaImage,
+}
+
+pub struct AssetServer {
+    root: PathBuf,
+    images: Vec<ImageEntry>,
+    image_lookup: HashMap<PathBuf, Handle<RgbaImage>>,
+    textures: Vec<TextureAsset>,
+    texture_lookup: HashMap<(Handle<RgbaImage>, wgpu::TextureFormat), Handle<TextureAsset>>,
+    compressed: Vec<CompressedImage>,
+    compressed_lookup: HashMap<PathBuf, Handle<CompressedImage>>,
+}
+
+impl AssetServer {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self {
+            root: root.into(),
+            images: Vec::new(),
+            image_lookup: HashMap::new(),
+            textures: Vec::new(),
+            texture_lookup: HashMap::new(),
+            compressed: Vec::new(),
+            compressed_lookup: HashMap::new(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolves a path relative to the root. Absolute paths are returned unchanged.
+    pub fn resolve<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.root.join(path)
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>, AssetError> {
+        let resolved = self.resolve(path);
+        std::fs::read(&resolved).map_err(|source| match source.kind() {
+            std::io::ErrorKind::NotFound => AssetError::NotFound {
+                path: path.to_owned(),
+                resolved: resolved.clone(),
+            },
+            _ => AssetError::Io {
+                path: path.to_owned(),
+                source,
+            },
+        })
+    }
+
+    fn decode<P: AsRef<Path>>(&self, path: P) -> Result<RgbaImage, AssetError> {
+        let path = path.as_ref();
+        let bytes = self.read(path)?;
+        let image = image::load_from_memory(&bytes).map_err(|source| AssetError::Decode {
+            path: path.to_owned(),
+            source,
+        })?;
+        Ok(image.into_rgba8())
+    }
+
+    fn insert_image(&mut self, path: PathBuf, image: RgbaImage) -> Handle<RgbaImage> {
+        let handle = Handle::new(self.images.len());
+        self.images.push(ImageEntry {
+            path: path.clone(),
+            image,
+        });
+        self.image_lookup.insert(path, handle);
+        handle
+    }
+
+    /// Loads and decodes an image, or returns the cached handle if it was already loaded.
+    pub fn load_image<P: AsRef<Path>>(&mut self, path: P) -> Result<Handle<RgbaImage>, AssetError> {
+        let path = path.as_ref();
+        if let Some(&handle) = self.image_lookup.get(path) {
+            return Ok(handle);
+        }
+        let image = self.decode(path)?;
+        Ok(self.insert_image(path.to_owned(), image))
+    }
+
+    /// Like [AssetServer::load_image], but logs the error and uses a [placeholder_image] if the
+    /// image can't be loaded. The placeholder is cached under `path`, so reloading picks up the
+    /// file once it exists.
+    pub fn load_image_or_placeholder<P: AsRef<Path>>(&mut self, path: P, placeholder_size: (u32, u32)) -> Handle<RgbaImage> {
+        let path = path.as_ref();
+        match self.load_image(path) {
+            Ok(handle) => handle,
+            Err(err) => {
+                log::error!("{err}");
+                self.insert_image(path.to_owned(), placeholder_image(placeholder_size.0, placeholder_size.1))
+            }
+        }
+    }
+
+    pub fn image(&self, handle: Handle<RgbaImage>) -> &RgbaImage {
+        &self.images[handle.index].image
+    }
+
+    /// The path that the image was loaded from (relative to the root).
+    pub fn image_path(&self, handle: Handle<RgbaImage>) -> &Path {
+        &self.images[handle.index].path
+    }
+
+    /// Decodes the image again. On failure, the previous image is kept.
+    pub fn reload_image(&mut self, handle: Handle<RgbaImage>) -> Result<(), AssetError> {
+        let image = self.decode(&self.images[handle.index].path)?;
+        self.images[handle.index].image = image;
+        Ok(())
+    }
+
+    /// Decodes an image without caching it. Use this for large images that are only needed
+    /// once (such as skybox faces), so that they don't stay in memory.
+    pub fn read_image<P: AsRef<Path>>(&self, path: P) -> Result<RgbaImage, AssetError> {
+        self.decode(path)
+    }
+
+    /// Loads a `.ktx2` or `.dds` texture, or returns the cached handle if it was already loaded.
+    /// Create the GPU texture with [CompressedImage::create_texture] or
+    /// [crate::rendering::texture_array::TextureArray::from_compressed].
+    pub fn load_compressed<P: AsRef<Path>>(&mut self, path: P) -> Result<Handle<CompressedImage>, AssetError> {
+        let path = path.as_ref();
+        if let Some(&handle) = self.compressed_lookup.get(path) {
+            return Ok(handle);
+        }
+        let ktx2 = CompressedImage::is_ktx2(path).map_err(|source| AssetError::Compressed {
+            path: path.to_owned(),
+            source,
+        })?;
+        let bytes = self.read(path)?;
+        let image = CompressedImage::from_bytes(&bytes, ktx2).map_err(|source| AssetError::Compressed {
+            path: path.to_owned(),
+            source,
+        })?;
+        let handle = Handle::new(self.compressed.len());
+        self.compressed.push(image);
+        self.compressed_lookup.insert(path.to_owned(), handle);
+        Ok(handle)
+    }
+
+    pub fn compressed(&self, handle: Handle<CompressedImage>) -> &CompressedImage {
+        &self.compressed[handle.index]
+    }
+
+    /// Loads an image into a 2D texture, or returns the cached handle if the same image was
+    /// already loaded with the same format.
+    pub fn load_texture<P: AsRef<Path>>(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: P,
+        format: wgpu::TextureFormat,
+    ) -> Result<Handle<TextureAsset>, AssetError> {
+        let image = self.load_image(path)?;
+        Ok(self.texture_from_image(device, queue, image, format))
+    }
+
+    /// Like [AssetServer::load_texture], but uses a placeholder if the image can't be loaded
+    /// (see [AssetServer::load_image_or_placeholder]).
+    pub fn load_texture_or_placeholder<P: AsRef<Path>>(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: P,
+        format: wgpu::TextureFormat,
+        placeholder_size: (u32, u32),
+    ) -> Handle<TextureAsset> {
+        let image = self.load_image_or_placeholder(path, placeholder_size);
+        self.texture_from_image(device, queue, image, format)
+    }
+
+    /// Creates a 2D texture from a loaded image, or returns the cached handle if there already
+    /// is one with the same format.
+    pub fn texture_from_image(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image_handle: Handle<RgbaImage>,
+        format: wgpu::TextureFormat,
+    ) -> Handle<TextureAsset> {
+        if let Some(&handle) = self.texture_lookup.get(&(image_handle, format)) {
+            return handle;
+        }
+        let handle = Handle::new(self.textures.len());
+        self.textures.push(self.create_texture(device, queue, image_handle, format));
+        self.texture_lookup.insert((image_handle, format), handle);
+        handle
+    }
+
+    fn create_texture(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image_handle: Handle<RgbaImage>,
+        format: wgpu::TextureFormat,
+    ) -> TextureAsset {
+        let entry = &self.images[image_handle.index];
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: entry.path.to_str(),
+            format,
+            mip_level_count: 1,
+            sample_count: 1,
+            size: wgpu::Extent3d {
+                width: entry.image.width(),
+                height: entry.image.height(),
+                depth_or_array_layers: 1,
+            },
+            dimension: wgpu::TextureDimension::D2,
+            view_formats: &[],
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
+        write_image(queue, &texture, &entry.image);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        TextureAsset {
+            texture: memory::track(MemoryCategory::Textures, texture),
+            view,
+            format,
+            image: image_handle,
+        }
+    }
+
+    /// Creates every texture again on `device` from the cached images (after the previous device was
+    /// lost). Handles stay valid, but anything that uses the old views has to be rebuilt.
+    pub fn recreate_textures(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.textures = self.textures.iter()
+            .map(|texture| self.create_texture(device, queue, texture.image, texture.format))
+            .collect();
+    }
+
+    pub fn texture(&self, handle: Handle<TextureAsset>) -> &TextureAsset {
+        &self.textures[handle.index]
+    }
+
+    /// Decodes the texture's image again and writes it into the existing texture.
+    /// The image must have the same dimensions as before.
+    pub fn reload_texture(&mut self, queue: &wgpu::Queue, handle: Handle<TextureAsset>) -> Result<(), AssetError> {
+        let image_handle = self.textures[handle.index].image;
+        let path = self.image_path(image_handle).to_owned();
+        let image = self.decode(&path)?;
+        let texture = &self.textures[handle.index].texture;
+        let expected = (texture.width(), texture.height());
+        if image.dimensions() != expected {
+            return Err(AssetError::DimensionsChanged {
+                path,
+                dimensions: image.dimensions(),
+                expected,
+            });
+        }
+        write_image(queue, texture, &image);
+        self.images[image_handle.index].image = image;
+        Ok(())
+    }
+
+    /// Reloads every texture that was loaded through this server. Returns the errors of the
+    /// textures that failed to reload (those keep their previous contents).
+    pub fn reload_textures(&mut self, queue: &wgpu::Queue) -> Vec<AssetError> {
+        (0..self.textures.len())
+            .filter_map(|index| self.reload_texture(queue, Handle::new(index)).err())
+            .collect()
+    }
+}
+
+fn write_image(queue: &wgpu::Queue, texture: &wgpu::Texture, image: &RgbaImage) {
+    let (width, height) = image.dimensions();
+    queue.write_texture(
+        wgpu::TexelCopyTextureInfoBase {
+            texture,
+            aspect: wgpu::TextureAspect::All,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        image,
+        wgpu::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(width * 4),
+            rows_per_image: Some(height),
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+}
+
+/// A magenta and black checkerboard to stand in for missing images.
+pub fn placeholder_image(width: u32, height: u32) -> RgbaImage {
+    const CHECKER_SIZE: u32 = 8;
+    RgbaImage::from_fn(width.max(1), height.max(1), |x, y| {
+        if ((x / CHECKER_SIZE) + (y / CHECKER_SIZE)) & 1 == 0 {
+            image::Rgba([255, 0, 255, 255])
+        } else {
+            image::Rgba([0, 0, 0, 255])
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_cache_test() {
+        let mut assets = AssetServer::new("./assets");
+        let first = assets.load_image("textures/reticles/crosshair118.png").unwrap();
+        let second = assets.load_image("textures/reticles/crosshair118.png").unwrap();
+        assert_eq!(first, second);
+        assert_eq!(assets.image(first).dimensions(), (72, 72));
+        assets.reload_image(first).unwrap();
+
+        let missing = assets.load_image("textures/does_not_exist.png");
+        assert!(matches!(missing, Err(AssetError::NotFound { .. })));
+        let placeholder = assets.load_image_or_placeholder("textures/does_not_exist.png", (16, 16));
+        assert_ne!(placeholder, first);
+        assert_eq!(assets.image(placeholder).dimensions(), (16, 16));
+        // The placeholder is cached under the path.
+        assert_eq!(assets.load_image("textures/does_not_exist.png").unwrap(), placeholder);
+        assert!(matches!(assets.load_compressed("textures/does_not_exist.dds"), Err(AssetError::NotFound { .. })));
+        assert!(matches!(assets.load_compressed("textures/reticles/crosshair118.png"), Err(AssetError::Compressed { .. })));
+    }
+}
@@ -0,0 +1,340 @@
+/*
+Benchmark mode. A benchmark generates a fixed scene (see [worldgen::generate_hills]), flies the camera
+around it on a scripted path for a fixed number of frames and records how long every frame took. The
+report is saved as CSV (one row per frame) so that performance can be compared between commits.
+
+Frames are counted rather than timed, so every run renders the same views no matter how fast it is.
+The first few frames are a warmup and aren't recorded, since pipelines and buffers are still settling.
+GPU times come back a few frames late (see [crate::rendering::timestamps::GpuTimer]), so the GPU time
+of a row is the latest one that arrived during that frame, and is empty if none did.
+*/
+
+use std::{f32::consts::TAU, io::Write, path::{Path, PathBuf}, time::Duration};
+
+use glam::{vec3, Vec3};
+
+use crate::{
+    animation::camera_path::{CameraKeyframe, CameraPath},
+    camera::{rotation_from_look_at, Camera},
+    rendering::raytrace::RaytraceChunk,
+    voxel::worldgen,
+};
+
+/// Ctrl+F9 (or `--bench`) saves benchmark reports into this directory.
+pub const BENCHMARK_DIR: &str = "./sandbox_files/benchmarks";
+pub const DEFAULT_BENCHMARK_FRAMES: u32 = 600;
+const DEFAULT_WARMUP_FRAMES: u32 = 30;
+const DEFAULT_BENCHMARK_SEED: u32 = 1;
+/// The number of keyframes on the orbit around the scene.
+const ORBIT_KEYFRAMES: usize = 16;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BenchmarkError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("The benchmark didn't record any frames.")]
+    NoSamples,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BenchmarkSettings {
+    /// The number of recorded frames.
+    pub frames: u32,
+    /// The number of frames before recording starts.
+    pub warmup_frames: u32,
+    /// The seed of the generated scene.
+    pub seed: u32,
+}
+
+impl Default for BenchmarkSettings {
+    fn default() -> Self {
+        Self {
+            frames: DEFAULT_BENCHMARK_FRAMES,
+            warmup_frames: DEFAULT_WARMUP_FRAMES,
+            seed: DEFAULT_BENCHMARK_SEED,
+        }
+    }
+}
+
+impl BenchmarkSettings {
+    /// Generates the scene that the benchmark runs in.
+    pub fn generate_scene(&self, block_id: u32) -> RaytraceChunk {
+        worldgen::generate_hills(self.seed, block_id)
+    }
+}
+
+/// The times of one frame, see [crate::state::State::record_frame_times].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchmarkSample {
+    /// The time since the previous frame started.
+    pub frame: Duration,
+    pub update: Duration,
+    pub render: Duration,
+    pub raytrace_gpu: Option<Duration>,
+    /// The raytracer's render scale (in percent), which the dynamic render scale may change.
+    pub render_scale: u32,
+}
+
+/// A running benchmark. Call [Benchmark::apply_camera] before rendering and [Benchmark::record] after
+/// every frame until [Benchmark::is_finished].
+#[derive(Debug, Clone)]
+pub struct Benchmark {
+    settings: BenchmarkSettings,
+    path: CameraPath,
+    frame: u32,
+    samples: Vec<BenchmarkSample>,
+}
+
+impl Benchmark {
+    /// Orbits around the top of `chunk`.
+    pub fn new(settings: BenchmarkSettings, chunk: &RaytraceChunk) -> Self {
+        let ground = chunk.highest_solid_at(32, 32).unwrap_or(0) as f32;
+        let center = vec3(32.0, ground, 32.0);
+        Self {
+            settings,
+            path: Self::orbit_path(center, 48.0, 24.0),
+            frame: 0,
+            samples: Vec::with_capacity(settings.frames as usize),
+        }
+    }
+
+    /// A closed loop around `center` at `height` above it, always looking at it. The distance swings
+    /// between `radius` and half of it, so that both near and far (LOD) rays are measured.
+    pub fn orbit_path(center: Vec3, radius: f32, height: f32) -> CameraPath {
+        let mut path = CameraPath::new();
+        for index in 0..=ORBIT_KEYFRAMES {
+            let angle = index as f32 / ORBIT_KEYFRAMES as f32 * TAU;
+            let distance = radius * (0.75 + 0.25 * (angle * 2.0).cos());
+            let (sin, cos) = angle.sin_cos();
+            let position = center + vec3(cos * distance, height, sin * distance);
+            path.push(CameraKeyframe {
+                position,
+                rotation: rotation_from_look_at(position, center),
+            });
+        }
+        path
+    }
+
+    pub fn settings(&self) -> BenchmarkSettings {
+        self.settings
+    }
+
+    /// The frames so far, including the warmup.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn total_frames(&self) -> u32 {
+        self.settings.warmup_frames + self.settings.frames
+    }
+
+    /// How far along the benchmark is, from `0` to `1`.
+    pub fn progress(&self) -> f32 {
+        self.frame as f32 / self.total_frames().max(1) as f32
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.total_frames()
+    }
+
+    /// Moves the camera to where it is on this frame.
+    pub fn apply_camera(&self, camera: &mut Camera) {
+        let t = self.frame as f32 / self.total_frames().saturating_sub(1).max(1) as f32;
+        if let Some(keyframe) = self.path.sample(t) {
+            keyframe.apply(camera);
+        }
+    }
+
+    /// Records the times of the frame (unless it's a warmup frame) and moves on to the next.
+    pub fn record(&mut self, sample: BenchmarkSample) {
+        if self.is_finished() {
+            return;
+        }
+        if self.frame >= self.settings.warmup_frames {
+            self.samples.push(sample);
+        }
+        self.frame += 1;
+    }
+
+    pub fn finish(self) -> BenchmarkReport {
+        BenchmarkReport {
+            settings: self.settings,
+            samples: self.samples,
+        }
+    }
+}
+
+/// Statistics of one column of a report (in milliseconds).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeStats {
+    pub mean: f64,
+    pub median: f64,
+    /// 95% of frames were at least this fast.
+    pub p95: f64,
+    pub max: f64,
+}
+
+impl TimeStats {
+    /// Returns `None` if there are no times.
+    pub fn new<I: IntoIterator<Item = Duration>>(times: I) -> Option<Self> {
+        let mut millis = times.into_iter().map(duration_ms).collect::<Vec<_>>();
+        if millis.is_empty() {
+            return None;
+        }
+        millis.sort_by(f64::total_cmp);
+        let percentile = |p: f64| millis[((millis.len() - 1) as f64 * p).round() as usize];
+        Some(Self {
+            mean: millis.iter().sum::<f64>() / millis.len() as f64,
+            median: percentile(0.5),
+            p95: percentile(0.95),
+            max: millis[millis.len() - 1],
+        })
+    }
+}
+
+impl std::fmt::Display for TimeStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "mean {:.3} ms, median {:.3} ms, p95 {:.3} ms, max {:.3} ms", self.mean, self.median, self.p95, self.max)
+    }
+}
+
+fn duration_ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkReport {
+    pub settings: BenchmarkSettings,
+    pub samples: Vec<BenchmarkSample>,
+}
+
+impl BenchmarkReport {
+    pub fn frame_stats(&self) -> Option<TimeStats> {
+        TimeStats::new(self.samples.iter().map(|sample| sample.frame))
+    }
+
+    pub fn update_stats(&self) -> Option<TimeStats> {
+        TimeStats::new(self.samples.iter().map(|sample| sample.update))
+    }
+
+    pub fn render_stats(&self) -> Option<TimeStats> {
+        TimeStats::new(self.samples.iter().map(|sample| sample.render))
+    }
+
+    /// Only the frames that had a GPU time.
+    pub fn raytrace_gpu_stats(&self) -> Option<TimeStats> {
+        TimeStats::new(self.samples.iter().filter_map(|sample| sample.raytrace_gpu))
+    }
+
+    /// One line per column, for the log.
+    pub fn summary(&self) -> String {
+        let columns = [
+            ("Frame", self.frame_stats()),
+            ("Update", self.update_stats()),
+            ("Render", self.render_stats()),
+            ("Raytrace GPU", self.raytrace_gpu_stats()),
+        ];
+        let mut summary = format!("Benchmark: {} frames (seed {})", self.samples.len(), self.settings.seed);
+        for (name, stats) in columns {
+            match stats {
+                Some(stats) => summary.push_str(&format!("\n{name}: {stats}")),
+                None => summary.push_str(&format!("\n{name}: -")),
+            }
+        }
+        summary
+    }
+
+    /// Writes a header and one row per frame. Times are in milliseconds, and missing GPU times are empty.
+    pub fn write_csv<W: Write>(&self, writer: &mut W) -> Result<(), BenchmarkError> {
+        writeln!(writer, "frame,frame_ms,update_ms,render_ms,raytrace_gpu_ms,render_scale")?;
+        for (index, sample) in self.samples.iter().enumerate() {
+            let raytrace_gpu = sample.raytrace_gpu.map(|time| format!("{:.4}", duration_ms(time))).unwrap_or_default();
+            writeln!(
+                writer,
+                "{index},{:.4},{:.4},{:.4},{raytrace_gpu},{}",
+                duration_ms(sample.frame),
+                duration_ms(sample.update),
+                duration_ms(sample.render),
+                sample.render_scale,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Saves the report to the next free `benchmark_#####.csv` in `dir`, and returns its path.
+    pub fn save_csv<P: AsRef<Path>>(&self, dir: P) -> Result<PathBuf, BenchmarkError> {
+        if self.samples.is_empty() {
+            return Err(BenchmarkError::NoSamples);
+        }
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        let path = (0..)
+            .map(|index| dir.join(format!("benchmark_{index:05}.csv")))
+            .find(|path| !path.exists())
+            .expect("There is an unused index.");
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(&path)?);
+        self.write_csv(&mut writer)?;
+        writer.flush()?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rendering::skybox::Skybox;
+
+    use super::*;
+
+    fn sample(millis: u64, raytrace_gpu: Option<u64>) -> BenchmarkSample {
+        BenchmarkSample {
+            frame: Duration::from_millis(millis),
+            update: Duration::from_millis(1),
+            render: Duration::from_millis(2),
+            raytrace_gpu: raytrace_gpu.map(Duration::from_millis),
+            render_scale: 100,
+        }
+    }
+
+    #[test]
+    fn benchmark_test() {
+        let settings = BenchmarkSettings { frames: 4, warmup_frames: 2, seed: 0 };
+        let mut benchmark = Benchmark::new(settings, &RaytraceChunk::new());
+        let mut camera = Camera::at(Vec3::ZERO, 1.0, 0.1, 100.0, winit::dpi::PhysicalSize::new(1920, 1080), None::<Skybox>);
+        benchmark.apply_camera(&mut camera);
+        let start = camera.position;
+        // The orbit is a closed loop that looks at its center.
+        assert!(start.distance(benchmark.path.keyframes().last().unwrap().position) < 1e-4);
+        assert!((camera.forward().normalize() - (vec3(32.0, 0.0, 32.0) - start).normalize()).length() < 1e-3);
+
+        for millis in 0..6 {
+            assert!(!benchmark.is_finished());
+            benchmark.record(sample(10 + millis, (millis % 2 == 0).then_some(5)));
+        }
+        assert!(benchmark.is_finished());
+        assert_eq!(benchmark.progress(), 1.0);
+        benchmark.record(sample(100, None));
+        let report = benchmark.finish();
+        // The warmup frames aren't recorded.
+        assert_eq!(report.samples.len(), 4);
+        assert_eq!(report.samples[0].frame, Duration::from_millis(12));
+        let frame = report.frame_stats().unwrap();
+        assert_eq!((frame.mean, frame.max), (13.5, 15.0));
+        assert_eq!(report.raytrace_gpu_stats().unwrap().mean, 5.0);
+
+        let mut csv = Vec::new();
+        report.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[1], "0,12.0000,1.0000,2.0000,5.0000,100");
+        assert_eq!(lines[2], "1,13.0000,1.0000,2.0000,,100");
+    }
+
+    #[test]
+    fn time_stats_test() {
+        assert_eq!(TimeStats::new([]), None);
+        let stats = TimeStats::new((1..=100).rev().map(Duration::from_millis)).unwrap();
+        assert_eq!((stats.median, stats.max), (51.0, 100.0));
+        assert_eq!(stats.p95, 95.0);
+    }
+}
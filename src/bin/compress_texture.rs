@@ -0,0 +1,48 @@
+//! Converts images into BC1/BC3 DDS files for [wgpu_learn::rendering::compressed::CompressedImage].
+//!
+//!     cargo run --features texture-conversion --bin compress_texture -- [--linear] <output.dds> <layer images...>
+//!
+//! Each image becomes a layer of the texture (in order), so a texture array can be converted into one
+//! file. Colors are treated as sRGB unless `--linear` is given.
+
+use wgpu_learn::rendering::compressed::encode::encode_dds;
+
+fn main() {
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    let srgb = match args.iter().position(|arg| arg == "--linear") {
+        Some(index) => {
+            args.remove(index);
+            false
+        }
+        None => true,
+    };
+    let [output, inputs @ ..] = args.as_slice() else {
+        eprintln!("Usage: compress_texture [--linear] <output.dds> <layer images...>");
+        std::process::exit(2);
+    };
+    if inputs.is_empty() {
+        eprintln!("No input images.");
+        std::process::exit(2);
+    }
+    let images = match inputs.iter().map(|path| image::open(path).map(|image| image.into_rgba8())).collect::<Result<Vec<_>, _>>() {
+        Ok(images) => images,
+        Err(err) => {
+            eprintln!("Failed to load image: {err}");
+            std::process::exit(1);
+        }
+    };
+    let layers = images.iter().collect::<Vec<_>>();
+    let result = encode_dds(&layers, srgb)
+        .map_err(|err| err.to_string())
+        .and_then(|dds| {
+            let mut file = std::io::BufWriter::new(std::fs::File::create(output).map_err(|err| err.to_string())?);
+            dds.write(&mut file).map_err(|err| err.to_string())
+        });
+    match result {
+        Ok(()) => println!("Wrote {} layer(s) to {output}.", layers.len()),
+        Err(err) => {
+            eprintln!("Failed to write {output}: {err}");
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+/*
+Camera bookmarks, to get back to interesting places in a big world. There are nine numbered slots:
+
+    Ctrl+1..9           Saves the camera's pose to the slot.
+    Alt+1..9            Flies to the slot.
+    Alt+Shift+1..9      Teleports to the slot.
+
+Flights are [CameraPath]s from the current pose to the bookmark, which take longer the further they go.
+The bookmarks are saved in the user config (see [crate::user_config]).
+*/
+
+use std::time::Duration;
+
+use winit::keyboard::KeyCode;
+
+use crate::animation::camera_path::{CameraKeyframe, CameraPath, CameraPathTrack};
+use crate::animation::tween;
+
+pub const BOOKMARK_SLOTS: usize = 9;
+/// The time that a flight takes per unit of distance, between [MIN_FLIGHT_TIME] and [MAX_FLIGHT_TIME].
+pub const FLIGHT_TIME_PER_UNIT: Duration = Duration::from_millis(10);
+pub const MIN_FLIGHT_TIME: Duration = Duration::from_millis(500);
+pub const MAX_FLIGHT_TIME: Duration = Duration::from_secs(4);
+
+const SLOT_KEYS: [KeyCode; BOOKMARK_SLOTS] = [
+    KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3,
+    KeyCode::Digit4, KeyCode::Digit5, KeyCode::Digit6,
+    KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+];
+
+/// The key of the bookmark `slot` (from 1 to [BOOKMARK_SLOTS]).
+pub fn slot_key(slot: usize) -> Option<KeyCode> {
+    SLOT_KEYS.get(slot.checked_sub(1)?).copied()
+}
+
+/// The duration of a flight over `distance` units.
+pub fn flight_time(distance: f32) -> Duration {
+    FLIGHT_TIME_PER_UNIT.mul_f32(distance.max(0.0)).clamp(MIN_FLIGHT_TIME, MAX_FLIGHT_TIME)
+}
+
+/// Camera poses by slot. Slots are numbered from 1, like their keys.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Bookmarks {
+    slots: [Option<CameraKeyframe>; BOOKMARK_SLOTS],
+}
+
+impl Bookmarks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, slot: usize) -> Option<CameraKeyframe> {
+        *self.slots.get(slot.checked_sub(1)?)?
+    }
+
+    /// Saves `pose` to `slot`. Returns `false` if there's no such slot.
+    pub fn set(&mut self, slot: usize, pose: CameraKeyframe) -> bool {
+        match slot.checked_sub(1).and_then(|index| self.slots.get_mut(index)) {
+            Some(bookmark) => {
+                *bookmark = Some(pose);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear(&mut self, slot: usize) {
+        if let Some(bookmark) = slot.checked_sub(1).and_then(|index| self.slots.get_mut(index)) {
+            *bookmark = None;
+        }
+    }
+
+    /// The slots that have a bookmark, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, CameraKeyframe)> + '_ {
+        self.slots.iter().enumerate().filter_map(|(index, bookmark)| Some((index + 1, (*bookmark)?)))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.iter().all(Option::is_none)
+    }
+
+    /// A track that flies the camera from `from` to the bookmark in `slot`.
+    pub fn flight(&self, slot: usize, from: CameraKeyframe) -> Option<CameraPathTrack> {
+        let to = self.get(slot)?;
+        let mut path = CameraPath::new();
+        path.push(from);
+        path.push(to);
+        let duration = flight_time(from.position.distance(to.position));
+        Some(path.playback(duration).with_easing(tween::f32::cubic_in_out))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec2, vec3};
+
+    use super::*;
+
+    #[test]
+    fn bookmarks_test() {
+        let mut bookmarks = Bookmarks::new();
+        let pose = CameraKeyframe { position: vec3(1.0, 2.0, 3.0), rotation: vec2(0.5, 1.0) };
+        assert!(bookmarks.is_empty());
+        assert!(bookmarks.set(3, pose));
+        assert!(bookmarks.set(9, pose));
+        assert!(!bookmarks.set(0, pose));
+        assert!(!bookmarks.set(10, pose));
+        assert_eq!(bookmarks.get(3), Some(pose));
+        assert_eq!(bookmarks.get(1), None);
+        assert_eq!(bookmarks.get(0), None);
+        assert_eq!(bookmarks.iter().map(|(slot, _)| slot).collect::<Vec<_>>(), [3, 9]);
+        bookmarks.clear(9);
+        assert_eq!(bookmarks.iter().count(), 1);
+        assert!(bookmarks.flight(1, pose).is_none());
+        assert!(bookmarks.flight(3, pose).is_some());
+
+        assert_eq!(slot_key(1), Some(KeyCode::Digit1));
+        assert_eq!(slot_key(9), Some(KeyCode::Digit9));
+        assert_eq!(slot_key(0), None);
+        assert_eq!(flight_time(0.0), MIN_FLIGHT_TIME);
+        assert_eq!(flight_time(100.0), Duration::from_secs(1));
+        assert_eq!(flight_time(10000.0), MAX_FLIGHT_TIME);
+    }
+}
@@ -10,8 +10,10 @@ pub fn rotation_from_look_at(position: Vec3, target: Vec3) -> Vec2 {
     rotation_from_direction(dir)
 }
 
+/// The rotation (see [Camera::rotation]) whose [Camera::forward] is `direction`.
 pub fn rotation_from_direction(direction: Vec3) -> Vec2 {
-    let yaw = direction.x.atan2(-direction.z);
+    // The yaw turns -Z towards -X, so forward is (-sin(yaw), 0, -cos(yaw)) on the horizontal plane.
+    let yaw = (-direction.x).atan2(-direction.z);
     let pitch = direction.y.asin();
     vec2(pitch, yaw)
     // let yaw = (-direction.x).atan2(-direction.z);
@@ -22,6 +24,11 @@ pub fn rotation_from_direction(direction: Vec3) -> Vec2 {
     // vec2(pitch, yaw)
 }
 
+/// The smallest allowed field of view (in radians).
+pub const MIN_FOV: f32 = 5.0 * (std::f32::consts::PI / 180.0);
+/// The largest allowed field of view (in radians).
+pub const MAX_FOV: f32 = 120.0 * (std::f32::consts::PI / 180.0);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum MoveType {
     /// Absolute movement. No rotation of the translation vector.
@@ -136,11 +143,33 @@ impl Camera {
         }
     }
 
+    /// The skybox that is drawn behind the scene, if any.
+    pub fn skybox(&self) -> Option<&Skybox> {
+        self.skybox.as_ref()
+    }
+
+    /// Replaces the skybox, such as after it was recreated on a new device.
+    pub fn set_skybox<S: Into<Option<Skybox>>>(&mut self, skybox: S) {
+        self.skybox = skybox.into();
+    }
+
     pub fn resize(&mut self, size: PhysicalSize<u32>) {
         self.screen_size = size;
         self.aspect_ratio = aspect_ratio(size);
     }
 
+    /// Sets the vertical field of view (in radians), clamped to [MIN_FOV]..=[MAX_FOV].
+    /// Returns `true` if the field of view changed. The raytracer must be updated separately
+    /// with [crate::rendering::raytrace::Raytracer::set_fov].
+    pub fn set_fov(&mut self, fov: f32) -> bool {
+        let fov = fov.clamp(MIN_FOV, MAX_FOV);
+        if fov == self.fov {
+            return false;
+        }
+        self.fov = fov;
+        true
+    }
+
     pub fn rotate_vec(&self, v: Vec3) -> Vec3 {
         let rot = self.quat();
         rot * v
@@ -292,9 +321,9 @@ impl Camera {
         Ray3::new(self.position.into(), direction.into())
     }
 
-    pub fn render(&self, render_pass: &mut wgpu::RenderPass, transforms: &TransformsBindGroup) {
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass, transforms: &TransformsBindGroup, sky_tint: Vec4) {
         if let Some(skybox) = &self.skybox {
-            skybox.render(render_pass, transforms, self.position);
+            skybox.render(render_pass, transforms, self.position, sky_tint);
         }
     }
 }
@@ -309,6 +338,18 @@ mod tests {
     fn radians_test() {
         assert_eq!(-90f32.to_radians(), (-90f32).to_radians());
     }
+
+    #[test]
+    fn look_at_test() {
+        // Looking along +X is a quarter turn clockwise from -Z (seen from above).
+        let rotation = rotation_from_direction(Vec3::X);
+        assert!((rotation - vec2(0.0, -std::f32::consts::FRAC_PI_2)).length() < 1e-6, "{rotation}");
+        let mut camera = Camera::at(Vec3::ZERO, 1.0, 0.01, 1000.0, PhysicalSize::new(1280, 720), None);
+        for target in [Vec3::X, Vec3::NEG_X, Vec3::Z, vec3(1.0, 2.0, -3.0)] {
+            camera.look_at(target);
+            assert!((camera.forward() - target.normalize()).length() < 1e-5, "{target}");
+        }
+    }
     
     #[test]
     fn glam_test() {
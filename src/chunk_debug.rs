@@ -0,0 +1,135 @@
+/*
+The chunk debug view (Ctrl+Quote) draws the chunk borders around the camera with the [Gizmo]: a grid of lines
+every [RaytraceChunk::SIZE] units through the chunks next to the camera's, with the camera's chunk outlined on top.
+The overlay lists the camera's chunk coordinate and its voxel inside of that chunk. The second mode adds
+the axes of voxel space at the origin, X in red, Y in green and Z in blue.
+*/
+
+use glam::{vec4, IVec3, Vec3, Vec4};
+
+use crate::gizmo::Gizmo;
+use crate::rendering::raytrace::RaytraceChunk;
+
+/// The number of chunks around the camera's chunk, in every direction, that the grid goes through.
+pub const BORDER_RADIUS: i32 = 1;
+pub const AXIS_LENGTH: f32 = RaytraceChunk::SIZE as f32;
+
+const GRID_COLOR: Vec4 = vec4(0.3, 0.6, 1.0, 0.5);
+const CURRENT_CHUNK_COLOR: Vec4 = vec4(1.0, 0.9, 0.2, 1.0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChunkDebugMode {
+    #[default]
+    Off,
+    Borders,
+    BordersAndAxes,
+}
+
+impl ChunkDebugMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Borders,
+            Self::Borders => Self::BordersAndAxes,
+            Self::BordersAndAxes => Self::Off,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Borders => "Borders",
+            Self::BordersAndAxes => "Borders and Axes",
+        }
+    }
+
+    pub fn shows_borders(self) -> bool {
+        self != Self::Off
+    }
+
+    pub fn shows_axes(self) -> bool {
+        self == Self::BordersAndAxes
+    }
+}
+
+/// The coordinate of the chunk that contains `position`.
+pub fn chunk_coord(position: Vec3) -> IVec3 {
+    (position / RaytraceChunk::SIZE as f32).floor().as_ivec3()
+}
+
+/// The coordinate of the voxel that contains `position`, relative to its chunk.
+pub fn local_coord(position: Vec3) -> IVec3 {
+    position.floor().as_ivec3().rem_euclid(IVec3::splat(RaytraceChunk::SIZE))
+}
+
+/// The lines of the grid through the chunks up to `radius` chunks away from `center`, along every axis.
+pub fn border_lines(center: IVec3, radius: i32) -> Vec<(Vec3, Vec3)> {
+    let size = RaytraceChunk::SIZE as f32;
+    let min = (center - radius).as_vec3() * size;
+    let max = (center + radius + 1).as_vec3() * size;
+    let borders = (-radius..=radius + 1).map(|offset| offset as f32 * size).collect::<Vec<_>>();
+    let mut lines = Vec::with_capacity(borders.len() * borders.len() * 3);
+    for axis in 0..3 {
+        // The other two axes, in order.
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        for &a in &borders {
+            for &b in &borders {
+                let mut start = Vec3::ZERO;
+                start[axis] = min[axis];
+                start[u] = center[u] as f32 * size + a;
+                start[v] = center[v] as f32 * size + b;
+                let mut end = start;
+                end[axis] = max[axis];
+                lines.push((start, end));
+            }
+        }
+    }
+    lines
+}
+
+/// Adds the lines of `mode` around a camera at `camera_position` to the `gizmo` for this frame.
+pub fn draw(gizmo: &mut Gizmo, mode: ChunkDebugMode, camera_position: Vec3) {
+    if mode.shows_borders() {
+        let chunk = chunk_coord(camera_position);
+        for (start, end) in border_lines(chunk, BORDER_RADIUS) {
+            gizmo.line(start, end, GRID_COLOR);
+        }
+        let min = chunk.as_vec3() * RaytraceChunk::SIZE as f32;
+        gizmo.aabb(min, min + RaytraceChunk::SIZE as f32, CURRENT_CHUNK_COLOR);
+    }
+    if mode.shows_axes() {
+        gizmo.line(Vec3::ZERO, Vec3::X * AXIS_LENGTH, vec4(1.0, 0.2, 0.2, 1.0));
+        gizmo.line(Vec3::ZERO, Vec3::Y * AXIS_LENGTH, vec4(0.2, 1.0, 0.2, 1.0));
+        gizmo.line(Vec3::ZERO, Vec3::Z * AXIS_LENGTH, vec4(0.2, 0.4, 1.0, 1.0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{ivec3, vec3};
+
+    use super::*;
+
+    #[test]
+    fn coord_test() {
+        assert_eq!(chunk_coord(vec3(10.0, 63.9, 64.0)), ivec3(0, 0, 1));
+        assert_eq!(chunk_coord(vec3(-0.5, -64.0, -64.5)), ivec3(-1, -1, -2));
+        assert_eq!(local_coord(vec3(10.5, 63.9, 64.0)), ivec3(10, 63, 0));
+        assert_eq!(local_coord(vec3(-0.5, -64.0, -65.5)), ivec3(63, 0, 62));
+    }
+
+    #[test]
+    fn border_lines_test() {
+        // One chunk: its 12 edges.
+        let lines = border_lines(ivec3(1, 0, 0), 0);
+        assert_eq!(lines.len(), 12);
+        assert!(lines.contains(&(vec3(64.0, 0.0, 0.0), vec3(128.0, 0.0, 0.0))));
+        assert!(lines.contains(&(vec3(128.0, 0.0, 64.0), vec3(128.0, 64.0, 64.0))));
+        // Every line is on chunk borders and spans the whole region.
+        let lines = border_lines(IVec3::ZERO, 1);
+        assert_eq!(lines.len(), 4 * 4 * 3);
+        for (start, end) in lines {
+            assert_eq!((end - start).length(), 192.0);
+            assert!((start % 64.0).abs().max_element() == 0.0 && (end % 64.0).abs().max_element() == 0.0);
+        }
+    }
+}
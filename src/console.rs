@@ -0,0 +1,208 @@
+/*
+The in-game console at the bottom of the window, which shows the latest entries of the log buffer (see
+[crate::logging]). Ctrl+` toggles it. While it's open:
+
+    Home                Cycles the lowest level that is shown.
+    End                 Cycles the module that is shown, through the modules that have logged something.
+    PageUp/PageDown     Scrolls through older entries.
+
+The [crate::systems::OverlaySystem] renders it.
+*/
+
+use log::Level;
+
+use crate::logging::{LogBuffer, LogEntry};
+
+/// The number of entries that are shown at once.
+pub const CONSOLE_LINES: usize = 12;
+pub const CONSOLE_FONT_SIZE: f32 = 14.0;
+pub const CONSOLE_LINE_HEIGHT: f32 = 18.0;
+/// The space between the console and the edges of the window.
+pub const CONSOLE_MARGIN: f32 = 8.0;
+
+/// From most to least verbose.
+const LEVELS: [Level; 5] = [Level::Trace, Level::Debug, Level::Info, Level::Warn, Level::Error];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Console {
+    pub visible: bool,
+    /// Entries that are less severe are hidden.
+    min_level: Level,
+    /// Only entries of this target are shown.
+    module: Option<String>,
+    /// The number of entries between the newest one and the bottom line.
+    scroll: usize,
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            min_level: Level::Trace,
+            module: None,
+            scroll: 0,
+        }
+    }
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        self.scroll = 0;
+    }
+
+    pub fn min_level(&self) -> Level {
+        self.min_level
+    }
+
+    pub fn module(&self) -> Option<&str> {
+        self.module.as_deref()
+    }
+
+    /// Shows only entries at the next level and above, wrapping back around to every level.
+    pub fn cycle_level(&mut self) -> Level {
+        let index = LEVELS.iter().position(|&level| level == self.min_level).unwrap_or(0);
+        self.min_level = LEVELS[(index + 1) % LEVELS.len()];
+        self.scroll = 0;
+        self.min_level
+    }
+
+    /// Shows only the next of the `targets` (as returned by [LogBuffer::targets]), or every module after
+    /// the last one.
+    pub fn cycle_module(&mut self, targets: &[String]) -> Option<&str> {
+        self.module = match &self.module {
+            None => targets.first().cloned(),
+            Some(module) => targets.iter()
+                .position(|target| target == module)
+                .and_then(|index| targets.get(index + 1))
+                .cloned(),
+        };
+        self.scroll = 0;
+        self.module()
+    }
+
+    /// Scrolls towards older entries for positive `lines`, and towards newer ones for negative `lines`.
+    pub fn scroll(&mut self, lines: isize) {
+        self.scroll = self.scroll.saturating_add_signed(lines);
+    }
+
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        entry.level <= self.min_level && self.module.as_ref().is_none_or(|module| entry.target == *module)
+    }
+
+    /// The entries on screen, oldest first. The scroll is clamped so that the console is never less than
+    /// full when there are enough entries.
+    pub fn visible_entries(&mut self, buffer: &LogBuffer) -> Vec<LogEntry> {
+        let mut entries = buffer.filtered(|entry| self.matches(entry));
+        self.scroll = self.scroll.min(entries.len().saturating_sub(CONSOLE_LINES));
+        entries.truncate(entries.len() - self.scroll);
+        let start = entries.len().saturating_sub(CONSOLE_LINES);
+        entries.split_off(start)
+    }
+
+    /// The line above the entries.
+    pub fn title(&self) -> String {
+        let level = if self.min_level == Level::Trace {
+            String::from("All Levels")
+        } else {
+            format!("{} and Above", self.min_level)
+        };
+        let module = self.module().unwrap_or("All Modules");
+        if self.scroll > 0 {
+            format!("Console: {level}, {module} ({} newer)", self.scroll)
+        } else {
+            format!("Console: {level}, {module}")
+        }
+    }
+
+    /// The height in pixels of the title and the entries.
+    pub fn height(&self) -> f32 {
+        (CONSOLE_LINES + 1) as f32 * CONSOLE_LINE_HEIGHT
+    }
+}
+
+/// One line of the console. This crate's module paths are shortened.
+pub fn format_entry(entry: &LogEntry) -> String {
+    let target = entry.target.strip_prefix("wgpu_learn::").unwrap_or(&entry.target);
+    format!("{:>8.3} {:<5} {target}: {}", entry.time.as_secs_f64(), entry.level, entry.message)
+}
+
+/// The text color of entries at `level`.
+pub fn level_color(level: Level) -> [u8; 3] {
+    match level {
+        Level::Error => [255, 90, 80],
+        Level::Warn => [255, 200, 60],
+        Level::Info => [220, 220, 220],
+        Level::Debug => [130, 180, 255],
+        Level::Trace => [150, 150, 150],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn entry(level: Level, target: &str, message: &str) -> LogEntry {
+        LogEntry {
+            level,
+            target: target.to_owned(),
+            message: message.to_owned(),
+            time: Duration::from_millis(1500),
+        }
+    }
+
+    #[test]
+    fn filter_test() {
+        let mut console = Console::new();
+        let warning = entry(Level::Warn, "wgpu_core::device", "slow");
+        let info = entry(Level::Info, "wgpu_learn::state", "loaded");
+        assert!(console.matches(&warning) && console.matches(&info));
+        assert_eq!(console.cycle_level(), Level::Debug);
+        assert_eq!(console.cycle_level(), Level::Info);
+        assert_eq!(console.cycle_level(), Level::Warn);
+        assert!(console.matches(&warning) && !console.matches(&info));
+        assert_eq!(console.cycle_level(), Level::Error);
+        assert_eq!(console.cycle_level(), Level::Trace);
+
+        let targets = [String::from("wgpu_core::device"), String::from("wgpu_learn::state")];
+        assert_eq!(console.cycle_module(&targets), Some("wgpu_core::device"));
+        assert!(console.matches(&warning) && !console.matches(&info));
+        assert_eq!(console.cycle_module(&targets), Some("wgpu_learn::state"));
+        assert_eq!(console.cycle_module(&targets), None);
+        assert!(console.matches(&warning) && console.matches(&info));
+    }
+
+    #[test]
+    fn scroll_test() {
+        let buffer = LogBuffer::new(100);
+        for i in 0..20 {
+            buffer.push(entry(Level::Info, "wgpu_learn::state", &i.to_string()));
+        }
+        let messages = |console: &mut Console| console.visible_entries(&buffer)
+            .into_iter()
+            .map(|entry| entry.message.parse::<usize>().unwrap())
+            .collect::<Vec<_>>();
+        let mut console = Console::new();
+        assert_eq!(messages(&mut console), (8..20).collect::<Vec<_>>());
+        console.scroll(3);
+        assert_eq!(messages(&mut console), (5..17).collect::<Vec<_>>());
+        // Clamped to the oldest entries.
+        console.scroll(100);
+        assert_eq!(messages(&mut console), (0..12).collect::<Vec<_>>());
+        console.scroll(-100);
+        assert_eq!(messages(&mut console), (8..20).collect::<Vec<_>>());
+        assert_eq!(console.title(), "Console: All Levels, All Modules");
+    }
+
+    #[test]
+    fn format_test() {
+        assert_eq!(format_entry(&entry(Level::Warn, "wgpu_learn::state", "hi")), "   1.500 WARN  state: hi");
+        assert_eq!(format_entry(&entry(Level::Info, "wgpu_core::device", "hi")), "   1.500 INFO  wgpu_core::device: hi");
+    }
+}
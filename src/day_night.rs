@@ -0,0 +1,205 @@
+use std::{f32::consts::TAU, time::Duration};
+
+use glam::{vec3, vec4, Quat, Vec3, Vec4};
+
+use crate::{animation::tween, rendering::raytrace::{DirectionalLight, GpuRtLighting}};
+
+const DAY_SPEEDS: [f32; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+
+/*
+Time of day is stored as a value in the range [0, 1):
+0.00 = midnight
+0.25 = sunrise
+0.50 = noon
+0.75 = sunset
+When the sun is below the horizon, the directional light switches over to the moon
+(which is always opposite of the sun) so that the shader never lights faces from below.
+*/
+#[derive(Debug, Clone)]
+pub struct DayNightCycle {
+    /// Time of day in the range `[0, 1)`.
+    pub time_of_day: f32,
+    /// Length of a full day/night cycle (at a speed of `1.0`).
+    pub day_length: Duration,
+    pub speed_index: usize,
+    pub paused: bool,
+    /// Rotation of the sun's path around the Y axis (radians).
+    pub azimuth: f32,
+    /// How far the sun's path is tilted away from directly overhead (radians).
+    pub tilt: f32,
+    pub noon_color: Vec3,
+    pub evening_color: Vec3,
+    pub night_color: Vec3,
+    pub noon_intensity: f32,
+    pub evening_intensity: f32,
+    pub night_intensity: f32,
+    pub day_sky_tint: Vec4,
+    pub evening_sky_tint: Vec4,
+    pub night_sky_tint: Vec4,
+}
+
+impl DayNightCycle {
+    /// Creates a cycle using the directional light's color and intensities for the daytime values.
+    pub fn new(directional: &DirectionalLight, time_of_day: f32, day_length: Duration) -> Self {
+        Self {
+            time_of_day: time_of_day.rem_euclid(1.0),
+            day_length,
+            speed_index: 2,
+            paused: false,
+            azimuth: 30f32.to_radians(),
+            tilt: 25f32.to_radians(),
+            noon_color: directional.color,
+            evening_color: vec3(1.0, 0.55, 0.3),
+            night_color: vec3(0.45, 0.55, 0.9),
+            noon_intensity: directional.intensity,
+            evening_intensity: directional.evening_intensity,
+            night_intensity: 0.15,
+            day_sky_tint: Vec4::ONE,
+            evening_sky_tint: vec4(1.0, 0.65, 0.5, 1.0),
+            night_sky_tint: vec4(0.06, 0.07, 0.15, 1.0),
+        }
+    }
+
+    pub fn speed(&self) -> f32 {
+        DAY_SPEEDS[self.speed_index]
+    }
+
+    pub fn speed_up(&mut self) {
+        self.speed_index = (self.speed_index + 1).min(DAY_SPEEDS.len() - 1);
+    }
+
+    pub fn slow_down(&mut self) {
+        self.speed_index = self.speed_index.saturating_sub(1);
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Advances the time of day. Returns `true` if the time changed.
+    pub fn update(&mut self, delta_time: Duration) -> bool {
+        if self.paused || self.day_length.is_zero() {
+            return false;
+        }
+        let advance = delta_time.as_secs_f32() / self.day_length.as_secs_f32() * self.speed();
+        self.time_of_day = (self.time_of_day + advance).rem_euclid(1.0);
+        true
+    }
+
+    /// The position of the sun on the unit sphere.
+    pub fn sun_position(&self) -> Vec3 {
+        let angle = (self.time_of_day - 0.25) * TAU;
+        let rotation = Quat::from_rotation_y(self.azimuth) * Quat::from_rotation_x(self.tilt);
+        rotation * vec3(angle.cos(), angle.sin(), 0.0)
+    }
+
+    /// The sine of the sun's angle above the horizon (`-1.0..=1.0`).
+    pub fn sun_elevation(&self) -> f32 {
+        self.sun_position().y
+    }
+
+    pub fn is_day(&self) -> bool {
+        self.sun_elevation() >= 0.0
+    }
+
+    /// The direction that the light travels. This is the sun during the day, and the moon at night.
+    pub fn light_direction(&self) -> Vec3 {
+        let sun = self.sun_position();
+        if sun.y >= 0.0 {
+            -sun
+        } else {
+            sun
+        }
+    }
+
+    /// `0.0` at the horizon, `1.0` when the sun (or moon) is directly overhead.
+    fn horizon_alpha(&self) -> f32 {
+        tween::f32::circular_out(self.sun_elevation().abs().min(1.0))
+    }
+
+    pub fn light_color(&self) -> Vec3 {
+        let alpha = self.horizon_alpha();
+        if self.is_day() {
+            self.evening_color.lerp(self.noon_color, alpha)
+        } else {
+            self.evening_color.lerp(self.night_color, alpha)
+        }
+    }
+
+    pub fn light_intensity(&self) -> f32 {
+        let alpha = self.horizon_alpha();
+        let target = if self.is_day() {
+            self.noon_intensity
+        } else {
+            self.night_intensity
+        };
+        self.evening_intensity + (target - self.evening_intensity) * alpha
+    }
+
+    pub fn sky_tint(&self) -> Vec4 {
+        let elevation = self.sun_elevation();
+        let alpha = tween::f32::sine_out(elevation.abs().min(1.0));
+        if elevation >= 0.0 {
+            self.evening_sky_tint.lerp(self.day_sky_tint, alpha)
+        } else {
+            self.evening_sky_tint.lerp(self.night_sky_tint, tween::f32::quadratic_out((alpha * 4.0).min(1.0)))
+        }
+    }
+
+    /// Formats the time of day as `HH:MM`.
+    pub fn clock_time(&self) -> String {
+        let minutes = (self.time_of_day * 24.0 * 60.0) as u32;
+        format!("{:02}:{:02}", minutes / 60, minutes % 60)
+    }
+
+    /// Writes the light direction, color, and intensity to the GPU lighting buffer.
+    pub fn apply(&self, lighting: &GpuRtLighting, queue: &wgpu::Queue) {
+        lighting.set_directional_direction(queue, self.light_direction());
+        lighting.set_directional_color(queue, self.light_color());
+        lighting.set_directional_intensity(queue, self.light_intensity());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cycle(time_of_day: f32) -> DayNightCycle {
+        DayNightCycle::new(&DirectionalLight {
+            direction: Vec3::NEG_Y,
+            color: Vec3::ONE,
+            intensity: 1.0,
+            evening_intensity: 0.1,
+            shadow: 0.2,
+            active: true,
+        }, time_of_day, Duration::from_secs(60))
+    }
+
+    #[test]
+    fn light_points_down_test() {
+        for i in 0..100 {
+            let cycle = cycle(i as f32 / 100.0);
+            assert!(cycle.light_direction().y <= 0.0);
+        }
+    }
+
+    #[test]
+    fn noon_midnight_test() {
+        let noon = cycle(0.5);
+        assert!(noon.is_day());
+        assert!((noon.light_intensity() - 1.0).abs() < 1e-2);
+        let midnight = cycle(0.0);
+        assert!(!midnight.is_day());
+        assert!((midnight.light_intensity() - midnight.night_intensity).abs() < 1e-2);
+        assert_eq!(noon.clock_time(), "12:00");
+    }
+
+    #[test]
+    fn update_wraps_test() {
+        let mut cycle = cycle(0.75);
+        assert!(cycle.update(Duration::from_secs(30)));
+        assert!((cycle.time_of_day - 0.25).abs() < 1e-4);
+        cycle.toggle_pause();
+        assert!(!cycle.update(Duration::from_secs(30)));
+    }
+}
@@ -0,0 +1,249 @@
+use std::time::Duration;
+
+use bytemuck::{Pod, Zeroable};
+use glam::*;
+
+/*
+Entities are objects that move freely, unlike the voxels of the chunk. They live on the CPU in [Entities]
+and are uploaded to the raytracer as [GpuEntity]s (see [crate::rendering::raytrace::Raytracer::write_entities]),
+where every primary and shadow ray is intersected with each of them:
+
+    EntityShape::Box        An oriented box, lit like the voxels.
+    EntityShape::Billboard  A round sprite that always faces the camera.
+
+Entities are drawn over voxels that are further away, they cast shadows onto the voxels, and the G-buffer
+stores them with [ENTITY_ID_FLAG] set. They don't show up in reflections or behind translucent blocks.
+There are only a few of them, so the shader tests every entity instead of using an acceleration structure.
+*/
+
+/// The number of entities that the raytracer can draw.
+pub const MAX_ENTITIES: usize = 64;
+/// Set on the G-buffer ID of an entity, the rest of the ID is its [EntityId].
+pub const ENTITY_ID_FLAG: u32 = 0x8000_0000;
+
+/// The values match the `ENTITY_*` constants in raytrace.wgsl.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EntityShape {
+    Box = 1,
+    Billboard = 2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EntityId(u32);
+
+impl EntityId {
+    /// The entity of a G-buffer ID, if it has [ENTITY_ID_FLAG] set.
+    pub fn from_hit_id(id: u32) -> Option<Self> {
+        (id & ENTITY_ID_FLAG != 0).then_some(Self(id & !ENTITY_ID_FLAG))
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Entity {
+    pub position: Vec3,
+    /// Rotates boxes. Billboards always face the camera.
+    pub rotation: Quat,
+    /// Half the size of a box. Billboards use `x` and `y` as their radii.
+    pub half_extents: Vec3,
+    pub shape: EntityShape,
+    /// A linear color.
+    pub color: Vec3,
+    pub emission: f32,
+}
+
+impl Entity {
+    pub fn cube(position: Vec3, size: f32, color: Vec3) -> Self {
+        Self {
+            position,
+            rotation: Quat::IDENTITY,
+            half_extents: Vec3::splat(size * 0.5),
+            shape: EntityShape::Box,
+            color,
+            emission: 0.0,
+        }
+    }
+
+    pub fn billboard(position: Vec3, radius: f32, color: Vec3) -> Self {
+        Self {
+            position,
+            rotation: Quat::IDENTITY,
+            half_extents: Vec3::new(radius, radius, 0.0),
+            shape: EntityShape::Billboard,
+            color,
+            emission: 0.0,
+        }
+    }
+
+    pub fn with_emission(self, emission: f32) -> Self {
+        Self { emission, ..self }
+    }
+
+    pub fn to_gpu(&self) -> GpuEntity {
+        let to_local = Mat3::from_quat(self.rotation.normalize().inverse());
+        GpuEntity {
+            center: self.position.to_array(),
+            shape: self.shape as u32,
+            half_extents: self.half_extents.to_array(),
+            emission: self.emission,
+            color: self.color.to_array(),
+            _padding: 0,
+            to_local: [to_local.x_axis, to_local.y_axis, to_local.z_axis].map(|axis| axis.extend(0.0).to_array()),
+        }
+    }
+}
+
+/// `Entity` in raytrace.wgsl. Empty slots have a shape of `0`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct GpuEntity {
+    center: [f32; 3],
+    shape: u32,
+    half_extents: [f32; 3],
+    emission: f32,
+    color: [f32; 3],
+    _padding: u32,
+    /// The columns of a `mat3x3<f32>`, each padded to 16 bytes.
+    to_local: [[f32; 4]; 3],
+}
+
+/// The entities of the scene. An entity keeps its slot (and [EntityId]) until it is despawned.
+#[derive(Debug, Clone, Default)]
+pub struct Entities {
+    slots: Vec<Option<Entity>>,
+    changed: bool,
+}
+
+impl Entities {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `None` if there are already [MAX_ENTITIES] entities.
+    pub fn spawn(&mut self, entity: Entity) -> Option<EntityId> {
+        let index = match self.slots.iter().position(Option::is_none) {
+            Some(index) => index,
+            None if self.slots.len() < MAX_ENTITIES => {
+                self.slots.push(None);
+                self.slots.len() - 1
+            }
+            None => return None,
+        };
+        self.slots[index] = Some(entity);
+        self.changed = true;
+        Some(EntityId(index as u32))
+    }
+
+    pub fn despawn(&mut self, id: EntityId) -> Option<Entity> {
+        let entity = self.slots.get_mut(id.index())?.take();
+        while matches!(self.slots.last(), Some(None)) {
+            self.slots.pop();
+        }
+        self.changed |= entity.is_some();
+        entity
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&Entity> {
+        self.slots.get(id.index())?.as_ref()
+    }
+
+    /// Marks the entities as changed, so they are uploaded again.
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut Entity> {
+        let entity = self.slots.get_mut(id.index())?.as_mut();
+        self.changed |= entity.is_some();
+        entity
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &Entity)> {
+        self.slots.iter()
+            .enumerate()
+            .filter_map(|(index, entity)| Some((EntityId(index as u32), entity.as_ref()?)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns whether anything changed since the last call.
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    /// The slots up to the last entity, indexed by [EntityId].
+    pub fn to_gpu(&self) -> Vec<GpuEntity> {
+        self.slots.iter()
+            .map(|entity| entity.as_ref().map_or(GpuEntity::zeroed(), Entity::to_gpu))
+            .collect()
+    }
+}
+
+/// Moves in a horizontal circle around `center`, counterclockwise when seen from above.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Orbit {
+    pub center: Vec3,
+    pub radius: f32,
+    /// The time of a full circle.
+    pub period: Duration,
+}
+
+impl Orbit {
+    pub fn position(&self, elapsed: Duration) -> Vec3 {
+        let turns = elapsed.as_secs_f32() / self.period.as_secs_f32().max(f32::EPSILON);
+        let (sin, cos) = (turns.fract() * std::f32::consts::TAU).sin_cos();
+        self.center + vec3(cos, 0.0, -sin) * self.radius
+    }
+
+    /// The rotation that faces along the orbit, for boxes.
+    pub fn rotation(&self, elapsed: Duration) -> Quat {
+        let turns = elapsed.as_secs_f32() / self.period.as_secs_f32().max(f32::EPSILON);
+        Quat::from_rotation_y(turns.fract() * std::f32::consts::TAU)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entities_test() {
+        let mut entities = Entities::new();
+        assert!(!entities.take_changed());
+        let a = entities.spawn(Entity::cube(Vec3::ZERO, 1.0, Vec3::ONE)).unwrap();
+        let b = entities.spawn(Entity::billboard(Vec3::X, 0.5, Vec3::ONE)).unwrap();
+        assert!(entities.take_changed());
+        assert_eq!((a.index(), b.index(), entities.len()), (0, 1, 2));
+
+        // Despawned slots are reused, and the trailing empty slots aren't uploaded.
+        assert!(entities.despawn(a).is_some());
+        assert_eq!(entities.to_gpu().len(), 2);
+        assert_eq!(entities.to_gpu()[0], GpuEntity::zeroed());
+        assert_eq!(entities.spawn(Entity::cube(Vec3::Y, 1.0, Vec3::ONE)), Some(a));
+        entities.despawn(b);
+        assert_eq!(entities.to_gpu().len(), 1);
+        assert_eq!(entities.iter().map(|(id, _)| id).collect::<Vec<_>>(), vec![a]);
+
+        while entities.len() < MAX_ENTITIES {
+            entities.spawn(Entity::cube(Vec3::ZERO, 1.0, Vec3::ONE)).unwrap();
+        }
+        assert_eq!(entities.spawn(Entity::cube(Vec3::ZERO, 1.0, Vec3::ONE)), None);
+
+        assert_eq!(std::mem::size_of::<GpuEntity>(), 96);
+        assert_eq!(EntityId::from_hit_id(ENTITY_ID_FLAG | 3), Some(EntityId(3)));
+        assert_eq!(EntityId::from_hit_id(3), None);
+
+        let orbit = Orbit { center: Vec3::Y, radius: 2.0, period: Duration::from_secs(4) };
+        assert!(orbit.position(Duration::ZERO).abs_diff_eq(vec3(2.0, 1.0, 0.0), 1e-5));
+        assert!(orbit.position(Duration::from_secs(1)).abs_diff_eq(vec3(0.0, 1.0, -2.0), 1e-5));
+        // The box faces along the orbit (its local -Z points along the motion).
+        let forward = orbit.rotation(Duration::from_secs(1)) * Vec3::NEG_Z;
+        assert!(forward.abs_diff_eq(Vec3::NEG_X, 1e-5));
+    }
+}
@@ -1,4 +1,6 @@
-use std::{collections::VecDeque, time::{Duration, Instant}};
+use std::time::{Duration, Instant};
+
+use crate::math::average::AverageBuffer;
 
 
 /*
@@ -11,23 +13,61 @@ use std::{collections::VecDeque, time::{Duration, Instant}};
 | That means that the beginning of update time is `frame_time - (avg_render_time + avg_update_time)`
 | and the beginning of render time is `frame_time - avg_render_time`.
 */
+/// How often frames should be started.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameLimit {
+    /// Start the next frame as soon as the previous one finishes.
+    Uncapped,
+    /// Target a fixed number of frames per second.
+    Fps(f64),
+    /// Target the refresh rate of the monitor (falls back to 60hz if unknown).
+    RefreshRate,
+}
+
 pub struct Framepace {
-    update_average: AverageBuffer,
-    render_average: AverageBuffer,
-    frame_rate_secs: f64,
+    update_average: AverageBuffer<Duration>,
+    render_average: AverageBuffer<Duration>,
+    limit: FrameLimit,
+    refresh_rate: Option<f64>,
     frame_time: Option<Instant>,
 }
 
 impl Framepace {
-    pub fn new(average_capacity: usize, frame_time: f64) -> Self {
+    /// `refresh_rate` is in hertz.
+    pub fn new(average_capacity: usize, limit: FrameLimit, refresh_rate: Option<f64>) -> Self {
         Self {
-            update_average: AverageBuffer::new(average_capacity),
-            render_average: AverageBuffer::new(average_capacity),
-            frame_rate_secs: frame_time,
+            update_average: AverageBuffer::new(average_capacity, None),
+            render_average: AverageBuffer::new(average_capacity, None),
+            limit,
+            refresh_rate,
             frame_time: None,
         }
     }
 
+    pub fn limit(&self) -> FrameLimit {
+        self.limit
+    }
+
+    pub fn set_limit(&mut self, limit: FrameLimit) {
+        self.limit = limit;
+        // Resync so that a lower cap doesn't wait on a stale frame time.
+        self.frame_time = None;
+    }
+
+    pub fn set_refresh_rate(&mut self, refresh_rate: Option<f64>) {
+        self.refresh_rate = refresh_rate;
+    }
+
+    /// The target time between frames, or [None] if uncapped.
+    pub fn frame_interval(&self) -> Option<Duration> {
+        match self.limit {
+            FrameLimit::Uncapped => None,
+            FrameLimit::Fps(fps) if fps > 0.0 => Some(Duration::from_secs_f64(1.0 / fps)),
+            FrameLimit::Fps(_) => None,
+            FrameLimit::RefreshRate => Some(Duration::from_secs_f64(1.0 / self.refresh_rate.unwrap_or(60.0))),
+        }
+    }
+
     fn measure_time<R, F: FnOnce() -> R>(f: F) -> (R, Duration) {
         let start_time = Instant::now();
         let result = f();
@@ -37,85 +77,80 @@ impl Framepace {
 
     pub fn measure_update<R, F: FnOnce() -> R>(&mut self, update: F) -> R {
         let (result, time) = Self::measure_time(update);
-        self.update_average.push(time.as_secs_f64());
+        self.update_average.push(time);
         result
     }
 
     pub fn measure_render<R, F: FnOnce() -> R>(&mut self, render: F) -> R {
         let (result, time) = Self::measure_time(render);
-        self.render_average.push(time.as_secs_f64());
+        self.render_average.push(time);
         result
     }
 
-    // pub fn is_time_to_update(&self) -> bool {
-    //     let Some(ref frame_time) = self.frame_time else {
-    //         return false;
-    //     };
-    //     let update_avg = self.update_average.average();
-    //     let render_avg = self.render_average.average();
-
-    // }
-
-    pub fn end_frame(&mut self) {
-        self.frame_time = Some(Instant::now())
-    }
-}
-
-#[derive(Debug, Clone)]
-pub struct AverageBuffer {
-    pub buffer: VecDeque<f64>,
-}
-
-impl AverageBuffer {
-    pub fn new(capacity: usize) -> Self {
-        assert_ne!(capacity, 0, "Capacity must be greater than 0.");
-        Self {
-            buffer: VecDeque::with_capacity(capacity),
-        }
-    }
-
-    pub fn with_seed(capacity: usize, seed: f64) -> Self {
-        let mut new = Self::new(capacity);
-        new.buffer.push_back(seed);
-        new
+    pub fn average_update_time(&self) -> Duration {
+        self.update_average.average()
     }
 
-    pub fn average(&self) -> f64 {
-        self.buffer.iter().cloned().sum::<f64>() / self.buffer.len() as f64
+    pub fn average_render_time(&self) -> Duration {
+        self.render_average.average()
     }
 
-    pub fn push(&mut self, t: f64) {
-        if self.buffer.len() == self.buffer.capacity() {
-            self.buffer.pop_front();
-        }
-        self.buffer.push_back(t);
+    /// The time of the most recent update, see [Framepace::measure_update].
+    pub fn last_update_time(&self) -> Duration {
+        self.update_average.last().unwrap_or_default()
     }
 
-    /// Pushes value and then gets the resulting average.
-    pub fn push_get(&mut self, t: f64) -> f64 {
-        self.push(t);
-        self.average()
+    pub fn last_render_time(&self) -> Duration {
+        self.render_average.last().unwrap_or_default()
     }
 
-    pub fn len(&self) -> usize {
-        self.buffer.len()
+    /// The time that update should begin so that update and render finish just as the next frame is due.
+    /// Returns [None] if there is nothing to wait for.
+    pub fn update_start_time(&self) -> Option<Instant> {
+        let frame_time = self.frame_time?;
+        let interval = self.frame_interval()?;
+        let work_time = self.average_update_time() + self.average_render_time();
+        let start = frame_time + interval.saturating_sub(work_time);
+        Some(start)
     }
 
-    pub fn capacity(&self) -> usize {
-        self.buffer.capacity()
+    pub fn is_time_to_update(&self) -> bool {
+        self.is_time_to_update_at(Instant::now())
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.buffer.is_empty()
+    /// Like [Framepace::is_time_to_update], at `now` instead of the current time.
+    fn is_time_to_update_at(&self, now: Instant) -> bool {
+        match self.update_start_time() {
+            Some(start) => now >= start,
+            None => true,
+        }
     }
 
-    pub fn clear(&mut self) {
-        self.buffer.clear();
+    /// Sleeps until it is time to begin update.
+    pub fn wait_for_update(&self) {
+        if let Some(start) = self.update_start_time() {
+            spin_sleep::sleep_until(start);
+        }
     }
 
-    pub fn reset(&mut self, new_seed: f64) {
-        self.buffer.clear();
-        self.push(new_seed);
+    pub fn end_frame(&mut self) {
+        self.end_frame_at(Instant::now());
+    }
+
+    /// Like [Framepace::end_frame], at `now` instead of the current time.
+    fn end_frame_at(&mut self, now: Instant) {
+        self.frame_time = Some(match (self.frame_time, self.frame_interval()) {
+            (Some(last), Some(interval)) => {
+                let next = last + interval;
+                // Keep a steady cadence unless we've fallen more than a frame behind.
+                if now.saturating_duration_since(next) > interval {
+                    now
+                } else {
+                    next
+                }
+            }
+            _ => now,
+        });
     }
 }
 
@@ -123,12 +158,29 @@ impl AverageBuffer {
 mod tests {
     use super::*;
     #[test]
-    fn avg_test() {
-        let mut avgs = AverageBuffer::with_seed(10, 5.0);
-        avgs.push(10.0);
-        avgs.push(15.0);
-        println!("{}", avgs.average());
-        avgs.reset(50.0);
-        println!("{}", avgs.average());
+    fn framepace_test() {
+        let mut pace = Framepace::new(4, FrameLimit::Fps(50.0), None);
+        assert_eq!(pace.frame_interval(), Some(Duration::from_millis(20)));
+        assert!(pace.is_time_to_update());
+        let ms = Duration::from_millis;
+        pace.update_average.push(ms(2));
+        pace.render_average.push(ms(3));
+        let start = Instant::now();
+        pace.end_frame_at(start);
+        // Update begins early enough for update and render to finish as the next frame is due.
+        assert_eq!(pace.update_start_time(), Some(start + ms(15)));
+        assert!(!pace.is_time_to_update_at(start + ms(14)));
+        assert!(pace.is_time_to_update_at(start + ms(15)));
+        // A late frame keeps the cadence, unless it is more than a frame behind.
+        pace.end_frame_at(start + ms(25));
+        assert_eq!(pace.update_start_time(), Some(start + ms(35)));
+        pace.end_frame_at(start + ms(70));
+        assert_eq!(pace.update_start_time(), Some(start + ms(85)));
+        pace.set_limit(FrameLimit::Uncapped);
+        assert_eq!(pace.frame_interval(), None);
+        assert!(pace.is_time_to_update());
+        pace.set_limit(FrameLimit::RefreshRate);
+        pace.set_refresh_rate(Some(120.0));
+        assert_eq!(pace.frame_interval(), Some(Duration::from_secs_f64(1.0 / 120.0)));
     }
 }
\ No newline at end of file
@@ -6,6 +6,12 @@ The gizmo uses batches for rendering. Call render functions from anywhere during
 
 use std::{collections::VecDeque, sync::atomic::AtomicU32};
 
+use bytemuck::{Pod, Zeroable};
+use glam::{Vec3, Vec4, Vec4Swizzles};
+
+use crate::camera::Camera;
+use crate::rendering::{msaa::multisample_state, transforms::TransformsBindGroup};
+
 struct Heavy(u32);
 
 fn next_heavy() -> Heavy {
@@ -40,4 +46,160 @@ impl<T: PartialEq> Batcher<T> {
 
         }
     }
-}
\ No newline at end of file
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct GizmoVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+impl GizmoVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Draws lines in world space on top of the scene.
+///
+/// Lines can be added at any point during the frame. [Gizmo::prepare] uploads them (and clears the batch)
+/// and [Gizmo::render] draws them.
+pub struct Gizmo {
+    vertices: Vec<GizmoVertex>,
+    buffer: wgpu::Buffer,
+    // In vertices.
+    capacity: usize,
+    vertex_count: u32,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Gizmo {
+    const INITIAL_CAPACITY: usize = 1024;
+
+    /// `format` and `sample_count` are the format and sample count of the target that the gizmo is rendered to.
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32, transforms: &TransformsBindGroup) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/gizmo.wgsl"));
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Gizmo Pipeline Layout"),
+            bind_group_layouts: &[&transforms.bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Gizmo Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vertex_main"),
+                buffers: &[GizmoVertex::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fragment_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: multisample_state(sample_count),
+            multiview: None,
+            cache: None,
+        });
+        Self {
+            vertices: Vec::new(),
+            buffer: Self::create_buffer(device, Self::INITIAL_CAPACITY),
+            capacity: Self::INITIAL_CAPACITY,
+            vertex_count: 0,
+            pipeline,
+        }
+    }
+
+    fn create_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Gizmo Vertex Buffer"),
+            size: (capacity * std::mem::size_of::<GizmoVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    pub fn line(&mut self, start: Vec3, end: Vec3, color: Vec4) {
+        let color = color.to_array();
+        self.vertices.push(GizmoVertex { position: start.to_array(), color });
+        self.vertices.push(GizmoVertex { position: end.to_array(), color });
+    }
+
+    /// The outline of an axis aligned box.
+    pub fn aabb(&mut self, min: Vec3, max: Vec3, color: Vec4) {
+        let corner = |x: bool, y: bool, z: bool| Vec3::new(
+            if x { max.x } else { min.x },
+            if y { max.y } else { min.y },
+            if z { max.z } else { min.z },
+        );
+        for a in [false, true] {
+            for b in [false, true] {
+                self.line(corner(false, a, b), corner(true, a, b), color);
+                self.line(corner(a, false, b), corner(a, true, b), color);
+                self.line(corner(a, b, false), corner(a, b, true), color);
+            }
+        }
+    }
+
+    /// The edges of the camera's frustum, cut off `max_distance` in front of the camera.
+    pub fn frustum(&mut self, camera: &Camera, max_distance: f32, color: Vec4) {
+        let inverse = camera.projection_view_matrix().inverse();
+        let unproject = |x: f32, y: f32, z: f32| {
+            let point = inverse * Vec4::new(x, y, z, 1.0);
+            point.xyz() / point.w
+        };
+        let scale = max_distance.min(camera.z_far) / camera.z_far;
+        let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].map(|(x, y)| {
+            let far = camera.position + (unproject(x, y, 1.0) - camera.position) * scale;
+            (unproject(x, y, 0.0), far)
+        });
+        for (index, &(near, far)) in corners.iter().enumerate() {
+            let (next_near, next_far) = corners[(index + 1) % corners.len()];
+            self.line(near, far, color);
+            self.line(near, next_near, color);
+            self.line(far, next_far, color);
+        }
+    }
+
+    /// Uploads the lines that were added since the last call and clears them.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if self.vertices.len() > self.capacity {
+            self.capacity = self.vertices.len().next_power_of_two();
+            self.buffer = Self::create_buffer(device, self.capacity);
+        }
+        if !self.vertices.is_empty() {
+            queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&self.vertices));
+        }
+        self.vertex_count = self.vertices.len() as u32;
+        self.vertices.clear();
+    }
+
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass, transforms: &TransformsBindGroup) {
+        if self.vertex_count == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &transforms.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
@@ -1,6 +1,176 @@
+/*
+The grid gizmo draws an endless grid on a horizontal plane. It's a fullscreen pass after the raytrace
+result: every pixel intersects its camera ray with the plane, and the raytracer's G-buffer hides the grid
+behind anything that the raytracer hit first.
 
-// Grid Gizmo
+The spacing follows the height of the camera above the plane in powers of ten (see [grid_spacing]), with
+a major line every ten minor lines. The minor lines fade out as the camera rises towards the next power,
+so there is no pop when the spacing changes.
+*/
+
+use bytemuck::{Pod, Zeroable};
+use glam::Vec4;
+
+use crate::{camera::Camera, rendering::{bindings::{BindGroupBuilder, Bindings}, buffers::UniformBuffer, msaa::multisample_state}};
+
+/// The smallest spacing of the minor lines, one voxel.
+pub const MIN_SPACING: f32 = 1.0;
+
+/// The spacing of the minor lines for a camera `height` above the plane, and how far (0..1) the minor
+/// lines have faded out towards the next power of ten.
+pub fn grid_spacing(height: f32) -> (f32, f32) {
+    // Ten minor lines per camera height below the camera.
+    let level = (height.abs() / 10.0).max(MIN_SPACING).log10();
+    (10f32.powf(level.floor()), level.fract())
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+struct GridParams {
+    inverse_view_projection: [f32; 16],
+    camera_position: [f32; 3],
+    height: f32,
+    spacing: f32,
+    level_blend: f32,
+    fade_distance: f32,
+    line_width: f32,
+    minor_color: [f32; 4],
+    major_color: [f32; 4],
+}
 
 pub struct Gridzmo {
-    
-}
\ No newline at end of file
+    pub visible: bool,
+    /// The Y coordinate of the plane.
+    pub height: f32,
+    /// Linear colors with straight alpha.
+    pub minor_color: Vec4,
+    pub major_color: Vec4,
+    /// In pixels.
+    pub line_width: f32,
+    params: UniformBuffer<GridParams>,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Gridzmo {
+    /// `format` and `sample_count` are those of the scene pass. `hit_layout` is the layout of the raytracer's
+    /// G-buffer (see [crate::rendering::raytrace::Raytracer::hit_bind_group_layout]).
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32, hit_layout: &wgpu::BindGroupLayout) -> Self {
+        let params = UniformBuffer::new(device, Some("Gridzmo Params Buffer"), GridParams::zeroed());
+        let layout = BindGroupBuilder::new()
+            .label("Gridzmo Bind Group Layout")
+            .uniform(0, wgpu::ShaderStages::FRAGMENT)
+            .build(device);
+        let bind_group = Bindings::new()
+            .buffer(0, params.buffer())
+            .build(device, Some("Gridzmo Bind Group"), &layout);
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/gridzmo.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Gridzmo Pipeline Layout"),
+            bind_group_layouts: &[&layout, hit_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Gridzmo Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vertex_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fragment_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: multisample_state(sample_count),
+            multiview: None,
+            cache: None,
+        });
+        Self {
+            visible: false,
+            height: 0.0,
+            minor_color: Vec4::new(0.6, 0.6, 0.6, 0.35),
+            major_color: Vec4::new(0.8, 0.8, 0.8, 0.6),
+            line_width: 1.0,
+            params,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    /// Recreates the pipeline for a new device or sample count. Keeps the settings.
+    pub fn recreate(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32, hit_layout: &wgpu::BindGroupLayout) {
+        *self = Self {
+            visible: self.visible,
+            height: self.height,
+            minor_color: self.minor_color,
+            major_color: self.major_color,
+            line_width: self.line_width,
+            ..Self::new(device, format, sample_count, hit_layout)
+        };
+    }
+
+    /// Writes the camera and the spacing for its height.
+    pub fn prepare(&self, queue: &wgpu::Queue, camera: &Camera) {
+        if !self.visible {
+            return;
+        }
+        let (spacing, level_blend) = grid_spacing(camera.position.y - self.height);
+        let params = GridParams {
+            inverse_view_projection: camera.projection_view_matrix().inverse().to_cols_array(),
+            camera_position: camera.position.to_array(),
+            height: self.height,
+            spacing,
+            level_blend,
+            // The major lines fade out at about a hundred of them.
+            fade_distance: spacing * 10.0 * 100.0,
+            line_width: self.line_width,
+            minor_color: self.minor_color.to_array(),
+            major_color: self.major_color.to_array(),
+        };
+        if self.params.get() != params {
+            self.params.write(queue, params);
+        }
+    }
+
+    /// `hit_bind_group` is the raytracer's G-buffer of the current frame.
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass, hit_bind_group: &wgpu::BindGroup) {
+        if !self.visible {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_bind_group(1, hit_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_spacing_test() {
+        assert_eq!(grid_spacing(0.0), (1.0, 0.0));
+        assert_eq!(grid_spacing(-5.0), (1.0, 0.0));
+        let (spacing, blend) = grid_spacing(500.0);
+        assert!((spacing - 10.0).abs() < 1e-4 && (blend - 0.69897).abs() < 1e-4);
+        let (spacing, blend) = grid_spacing(1000.0);
+        assert!((spacing - 100.0).abs() < 1e-3 && blend.abs() < 1e-4);
+        // Just below the next power, the minor lines have almost faded out.
+        assert!(grid_spacing(999.0).1 > 0.99);
+    }
+}
@@ -1,8 +1,8 @@
 use glam::*;
 use winit::{dpi::PhysicalPosition, event::MouseButton, keyboard::*};
-use std::collections::{HashMap, VecDeque};
+use std::{collections::{HashMap, VecDeque}, io::{BufReader, BufWriter, Read, Write}, path::{Path, PathBuf}, time::Duration};
 
-use crate::{framepace::AverageBuffer, livemouse::LiveMouse, state::Settings, FrameInfo};
+use crate::{livemouse::{ExpMouse, LiveMouse, MouseFilter}, mouse_settings::SmoothingMode, state::Settings, FrameInfo};
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct PressState {
@@ -87,13 +87,30 @@ impl DeltaBuffer {
     }
 }
 
+impl MouseFilter for DeltaBuffer {
+    fn filter(&mut self, delta: (f64, f64), _dt: Duration) -> (f64, f64) {
+        self.push(PhysicalPosition::new(delta.0, delta.1));
+        let average = self.average();
+        (average.x, average.y)
+    }
+
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct MousePosState {
     pub previous: PhysicalPosition<f64>,
     pub current: PhysicalPosition<f64>,
+    /// The movement this frame. After [MousePosState::begin_frame], this is smoothed by the filter of
+    /// the current [SmoothingMode].
     pub delta: PhysicalPosition<f64>,
     pub delta_avg: DeltaBuffer,
     pub live_mouse: LiveMouse,
+    pub exp_mouse: ExpMouse,
+    /// The mode of the last frame. Filters are reset when they are switched to.
+    smoothing_mode: SmoothingMode,
 }
 
 impl Default for MousePosState {
@@ -110,21 +127,34 @@ impl MousePosState {
             delta: PhysicalPosition::new(0., 0.),
             delta_avg: DeltaBuffer::new(6),
             live_mouse: LiveMouse::new(100.0, 100.0, 100.0, true),
+            exp_mouse: ExpMouse::new(30.0, false),
+            smoothing_mode: SmoothingMode::Off,
+        }
+    }
+
+    /// The filter for `mode`, or `None` when smoothing is off.
+    pub fn filter_mut(&mut self, mode: SmoothingMode) -> Option<&mut dyn MouseFilter> {
+        match mode {
+            SmoothingMode::Off => None,
+            SmoothingMode::Average => Some(&mut self.delta_avg),
+            SmoothingMode::Exponential => Some(&mut self.exp_mouse),
+            SmoothingMode::Live => Some(&mut self.live_mouse),
         }
     }
 
     pub fn begin_frame(&mut self, settings: &Settings, frame: &FrameInfo) {
-        // println!("Avg.");
         // Mouse Smoothing
-        self.live_mouse.update(frame.delta_time);
-        if settings.mouse_halting && self.delta.x == 0.0 && self.delta.y == 0.0 {
-            self.delta_avg.clear();
-            self.delta_avg.push(self.delta);
-        } else {
-            self.delta_avg.push(self.delta);
-            if settings.mouse_smoothing {
-                self.delta = self.delta_avg.average();
+        let mode = settings.mouse.smoothing_mode;
+        let switched = mode != self.smoothing_mode;
+        self.smoothing_mode = mode;
+        let delta = (self.delta.x, self.delta.y);
+        let halted = settings.mouse.halting && delta == (0.0, 0.0);
+        if let Some(filter) = self.filter_mut(mode) {
+            if switched || halted {
+                filter.reset();
             }
+            let (x, y) = filter.filter(delta, frame.delta_time);
+            self.delta = PhysicalPosition::new(x, y);
         }
     }
 
@@ -135,11 +165,38 @@ impl MousePosState {
     }
 }
 
+/// The analog state of a gamepad.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct GamepadAxes {
+    pub left_stick: Vec2,
+    pub right_stick: Vec2,
+    pub dpad: Vec2,
+    pub left_trigger: f32,
+    pub right_trigger: f32,
+}
+
+impl GamepadAxes {
+    pub fn set_axis(&mut self, axis: gilrs::Axis, value: f32) {
+        match axis {
+            gilrs::Axis::LeftStickX => self.left_stick.x = value,
+            gilrs::Axis::LeftStickY => self.left_stick.y = value,
+            gilrs::Axis::LeftZ => self.left_trigger = value,
+            gilrs::Axis::RightStickX => self.right_stick.x = value,
+            gilrs::Axis::RightStickY => self.right_stick.y = value,
+            gilrs::Axis::RightZ => self.right_trigger = value,
+            gilrs::Axis::DPadX => self.dpad.x = value,
+            gilrs::Axis::DPadY => self.dpad.y = value,
+            gilrs::Axis::Unknown => {},
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct Input {
     pub(crate) key_states: HashMap<KeyCode, PressState>,
     pub(crate) mouse_states: HashMap<MouseButton, PressState>,
     pub(crate) mouse_pos: MousePosState,
+    pub(crate) gamepad: GamepadAxes,
 }
 
 impl Input {
@@ -196,6 +253,10 @@ impl Input {
         }
     }
 
+    pub fn gamepad(&self) -> &GamepadAxes {
+        &self.gamepad
+    }
+
     pub fn set_key_state(&mut self, key: KeyCode, pressed: bool) {
         self.key_states.entry(key).or_default().current = pressed;
     }
@@ -231,11 +292,7 @@ impl Input {
 
 pub struct GamepadInput {
     gilrs: gilrs::Gilrs,
-    left_stick: Vec2,
-    right_stick: Vec2,
-    dpad: Vec2,
-    left_trigger: f32,
-    right_trigger: f32,
+    axes: GamepadAxes,
 }
 
 impl GamepadInput {
@@ -255,17 +312,7 @@ impl GamepadInput {
 
                 },
                 gilrs::EventType::AxisChanged(axis, value, _) => {
-                    match axis {
-                        gilrs::Axis::LeftStickX => self.left_stick.x = value,
-                        gilrs::Axis::LeftStickY => self.left_stick.y = value,
-                        gilrs::Axis::LeftZ => self.left_trigger = value,
-                        gilrs::Axis::RightStickX => self.right_stick.x = value,
-                        gilrs::Axis::RightStickY => self.right_stick.y = value,
-                        gilrs::Axis::RightZ => self.right_trigger = value,
-                        gilrs::Axis::DPadX => self.dpad.x = value,
-                        gilrs::Axis::DPadY => self.dpad.y = value,
-                        gilrs::Axis::Unknown => {},
-                    }
+                    self.axes.set_axis(axis, value);
                 },
                 gilrs::EventType::Connected => {
 
@@ -285,4 +332,400 @@ impl GamepadInput {
             }
         }
     }
-}
\ No newline at end of file
+}
+/*
+Input recording and playback.
+
+Every frame, the recorder captures the raw input state (before mouse smoothing) along with the frame's
+delta time. During playback the recorded state replaces the live input at the beginning of each frame,
+so `State::update` sees exactly what it saw while recording.
+
+File layout (big endian):
+    magic: b"WLIR", version: u32, frame_count: u64
+    per frame:
+        index: u64, delta_time_nanos: u64,
+        key_count: u16, key ids: [u16],
+        button_count: u16, button ids: [u32],
+        mouse_delta: (f64, f64), mouse_pos: (f64, f64),
+        gamepad: left_stick, right_stick, dpad: (f32, f32), left_trigger: f32, right_trigger: f32
+*/
+
+const RECORDING_MAGIC: [u8; 4] = *b"WLIR";
+const RECORDING_VERSION: u32 = 1;
+
+/// The keys that can be recorded. The index of a key is its ID in recording files, so keys must only
+/// ever be appended to this list.
+const RECORDABLE_KEYS: &[KeyCode] = &[
+    KeyCode::Backquote, KeyCode::Backslash, KeyCode::BracketLeft, KeyCode::BracketRight, KeyCode::Comma,
+    KeyCode::Digit0, KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4,
+    KeyCode::Digit5, KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+    KeyCode::Equal, KeyCode::IntlBackslash, KeyCode::IntlRo, KeyCode::IntlYen,
+    KeyCode::KeyA, KeyCode::KeyB, KeyCode::KeyC, KeyCode::KeyD, KeyCode::KeyE, KeyCode::KeyF,
+    KeyCode::KeyG, KeyCode::KeyH, KeyCode::KeyI, KeyCode::KeyJ, KeyCode::KeyK, KeyCode::KeyL,
+    KeyCode::KeyM, KeyCode::KeyN, KeyCode::KeyO, KeyCode::KeyP, KeyCode::KeyQ, KeyCode::KeyR,
+    KeyCode::KeyS, KeyCode::KeyT, KeyCode::KeyU, KeyCode::KeyV, KeyCode::KeyW, KeyCode::KeyX,
+    KeyCode::KeyY, KeyCode::KeyZ,
+    KeyCode::Minus, KeyCode::Period, KeyCode::Quote, KeyCode::Semicolon, KeyCode::Slash,
+    KeyCode::AltLeft, KeyCode::AltRight, KeyCode::Backspace, KeyCode::CapsLock, KeyCode::ContextMenu,
+    KeyCode::ControlLeft, KeyCode::ControlRight, KeyCode::Enter, KeyCode::SuperLeft, KeyCode::SuperRight,
+    KeyCode::ShiftLeft, KeyCode::ShiftRight, KeyCode::Space, KeyCode::Tab,
+    KeyCode::Delete, KeyCode::End, KeyCode::Help, KeyCode::Home, KeyCode::Insert, KeyCode::PageDown, KeyCode::PageUp,
+    KeyCode::ArrowDown, KeyCode::ArrowLeft, KeyCode::ArrowRight, KeyCode::ArrowUp,
+    KeyCode::NumLock, KeyCode::Numpad0, KeyCode::Numpad1, KeyCode::Numpad2, KeyCode::Numpad3, KeyCode::Numpad4,
+    KeyCode::Numpad5, KeyCode::Numpad6, KeyCode::Numpad7, KeyCode::Numpad8, KeyCode::Numpad9,
+    KeyCode::NumpadAdd, KeyCode::NumpadDecimal, KeyCode::NumpadDivide, KeyCode::NumpadEnter,
+    KeyCode::NumpadMultiply, KeyCode::NumpadSubtract,
+    KeyCode::Escape, KeyCode::PrintScreen, KeyCode::ScrollLock, KeyCode::Pause,
+    KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5, KeyCode::F6,
+    KeyCode::F7, KeyCode::F8, KeyCode::F9, KeyCode::F10, KeyCode::F11, KeyCode::F12,
+];
+
+fn key_id(key: KeyCode) -> Option<u16> {
+    RECORDABLE_KEYS.iter().position(|&recordable| recordable == key).map(|index| index as u16)
+}
+
+fn mouse_button_id(button: MouseButton) -> u32 {
+    match button {
+        MouseButton::Left => 0,
+        MouseButton::Right => 1,
+        MouseButton::Middle => 2,
+        MouseButton::Back => 3,
+        MouseButton::Forward => 4,
+        MouseButton::Other(other) => 5 + other as u32,
+    }
+}
+
+fn mouse_button_from_id(id: u32) -> Result<MouseButton, InputRecordingError> {
+    Ok(match id {
+        0 => MouseButton::Left,
+        1 => MouseButton::Right,
+        2 => MouseButton::Middle,
+        3 => MouseButton::Back,
+        4 => MouseButton::Forward,
+        id => MouseButton::Other(u16::try_from(id - 5).map_err(|_| InputRecordingError::UnknownMouseButton(id))?),
+    })
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InputRecordingError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Not an input recording.")]
+    InvalidMagic,
+    #[error("Unsupported input recording version {0}, expected {RECORDING_VERSION}.")]
+    UnsupportedVersion(u32),
+    #[error("Unknown key ID {0}.")]
+    UnknownKey(u16),
+    #[error("Unknown mouse button ID {0}.")]
+    UnknownMouseButton(u32),
+}
+
+/// The input state of a single frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputFrame {
+    pub index: u64,
+    pub delta_time: Duration,
+    pub keys: Vec<KeyCode>,
+    pub mouse_buttons: Vec<MouseButton>,
+    pub mouse_delta: PhysicalPosition<f64>,
+    pub mouse_pos: PhysicalPosition<f64>,
+    pub gamepad: GamepadAxes,
+}
+
+impl InputFrame {
+    /// Captures the input that is currently held. Keys that can't be recorded are skipped.
+    pub fn capture(frame: &FrameInfo, input: &Input) -> Self {
+        let mut keys = input.key_states.iter()
+            .filter(|(_, state)| state.current)
+            .map(|(&key, _)| key)
+            .filter(|&key| key_id(key).is_some())
+            .collect::<Vec<_>>();
+        keys.sort_by_key(|&key| key_id(key));
+        let mut mouse_buttons = input.mouse_states.iter()
+            .filter(|(_, state)| state.current)
+            .map(|(&button, _)| button)
+            .collect::<Vec<_>>();
+        mouse_buttons.sort_by_key(|&button| mouse_button_id(button));
+        Self {
+            index: frame.index,
+            delta_time: frame.delta_time,
+            keys,
+            mouse_buttons,
+            mouse_delta: input.mouse_pos.delta,
+            mouse_pos: input.mouse_pos.current,
+            gamepad: input.gamepad,
+        }
+    }
+
+    /// Replaces the held keys and buttons, the mouse motion and the gamepad state of `input`.
+    pub fn apply(&self, input: &mut Input) {
+        for state in input.key_states.values_mut().chain(input.mouse_states.values_mut()) {
+            state.current = false;
+        }
+        for &key in self.keys.iter() {
+            input.set_key_state(key, true);
+        }
+        for &button in self.mouse_buttons.iter() {
+            input.set_mouse_state(button, true);
+        }
+        input.mouse_pos.delta = self.mouse_delta;
+        input.mouse_pos.current = self.mouse_pos;
+        input.gamepad = self.gamepad;
+    }
+
+    fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(&self.index.to_be_bytes())?;
+        writer.write_all(&(self.delta_time.as_nanos() as u64).to_be_bytes())?;
+        // Keys without an ID can't be played back, so they're left out (`capture` never records them).
+        let key_ids = self.keys.iter().filter_map(|&key| key_id(key)).collect::<Vec<_>>();
+        writer.write_all(&(key_ids.len() as u16).to_be_bytes())?;
+        for id in key_ids {
+            writer.write_all(&id.to_be_bytes())?;
+        }
+        writer.write_all(&(self.mouse_buttons.len() as u16).to_be_bytes())?;
+        for &button in self.mouse_buttons.iter() {
+            writer.write_all(&mouse_button_id(button).to_be_bytes())?;
+        }
+        for value in [self.mouse_delta.x, self.mouse_delta.y, self.mouse_pos.x, self.mouse_pos.y] {
+            writer.write_all(&value.to_be_bytes())?;
+        }
+        let gamepad = &self.gamepad;
+        for value in [
+            gamepad.left_stick.x, gamepad.left_stick.y,
+            gamepad.right_stick.x, gamepad.right_stick.y,
+            gamepad.dpad.x, gamepad.dpad.y,
+            gamepad.left_trigger, gamepad.right_trigger,
+        ] {
+            writer.write_all(&value.to_be_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn read_from<R: Read>(reader: &mut R) -> Result<Self, InputRecordingError> {
+        let index = read_u64(reader)?;
+        let delta_time = Duration::from_nanos(read_u64(reader)?);
+        let key_count = read_u16(reader)?;
+        let keys = (0..key_count)
+            .map(|_| {
+                let id = read_u16(reader)?;
+                RECORDABLE_KEYS.get(id as usize).copied().ok_or(InputRecordingError::UnknownKey(id))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let button_count = read_u16(reader)?;
+        let mouse_buttons = (0..button_count)
+            .map(|_| mouse_button_from_id(read_u32(reader)?))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mouse_delta = PhysicalPosition::new(read_f64(reader)?, read_f64(reader)?);
+        let mouse_pos = PhysicalPosition::new(read_f64(reader)?, read_f64(reader)?);
+        let mut axes = [0.0f32; 8];
+        for axis in axes.iter_mut() {
+            *axis = f32::from_be_bytes(read_bytes(reader)?);
+        }
+        let gamepad = GamepadAxes {
+            left_stick: vec2(axes[0], axes[1]),
+            right_stick: vec2(axes[2], axes[3]),
+            dpad: vec2(axes[4], axes[5]),
+            left_trigger: axes[6],
+            right_trigger: axes[7],
+        };
+        Ok(Self {
+            index,
+            delta_time,
+            keys,
+            mouse_buttons,
+            mouse_delta,
+            mouse_pos,
+            gamepad,
+        })
+    }
+}
+
+fn read_bytes<R: Read, const N: usize>(reader: &mut R) -> std::io::Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> std::io::Result<u16> {
+    read_bytes(reader).map(u16::from_be_bytes)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> std::io::Result<u32> {
+    read_bytes(reader).map(u32::from_be_bytes)
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> std::io::Result<u64> {
+    read_bytes(reader).map(u64::from_be_bytes)
+}
+
+fn read_f64<R: Read>(reader: &mut R) -> std::io::Result<f64> {
+    read_bytes(reader).map(f64::from_be_bytes)
+}
+
+/// Records the input of every frame until it is saved with [InputRecorder::finish].
+#[derive(Debug, Clone)]
+pub struct InputRecorder {
+    path: PathBuf,
+    frames: Vec<InputFrame>,
+}
+
+impl InputRecorder {
+    /// The recording is written to `path` when it is finished.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_owned(),
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn frames(&self) -> &[InputFrame] {
+        &self.frames
+    }
+
+    /// Call this at the beginning of the frame, before the input is smoothed.
+    pub fn record(&mut self, frame: &FrameInfo, input: &Input) {
+        self.frames.push(InputFrame::capture(frame, input));
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(&RECORDING_MAGIC)?;
+        writer.write_all(&RECORDING_VERSION.to_be_bytes())?;
+        writer.write_all(&(self.frames.len() as u64).to_be_bytes())?;
+        for frame in self.frames.iter() {
+            frame.write_to(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the recording to its path and returns the number of recorded frames.
+    pub fn finish(self) -> Result<usize, InputRecordingError> {
+        let mut writer = BufWriter::new(std::fs::File::create(&self.path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(self.frames.len())
+    }
+}
+
+/// Feeds recorded input back into [Input], one frame at a time.
+#[derive(Debug, Clone)]
+pub struct InputPlayback {
+    frames: Vec<InputFrame>,
+    position: usize,
+}
+
+impl InputPlayback {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, InputRecordingError> {
+        let mut reader = BufReader::new(std::fs::File::open(path)?);
+        Self::read_from(&mut reader)
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Self, InputRecordingError> {
+        if read_bytes::<_, 4>(reader)? != RECORDING_MAGIC {
+            return Err(InputRecordingError::InvalidMagic);
+        }
+        let version = read_u32(reader)?;
+        if version != RECORDING_VERSION {
+            return Err(InputRecordingError::UnsupportedVersion(version));
+        }
+        let frame_count = read_u64(reader)?;
+        let frames = (0..frame_count)
+            .map(|_| InputFrame::read_from(reader))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            frames,
+            position: 0,
+        })
+    }
+
+    pub fn frames(&self) -> &[InputFrame] {
+        &self.frames
+    }
+
+    /// The number of frames that have been played back.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.frames.len()
+    }
+
+    /// Applies the next recorded frame to `input` and replaces the delta time of `frame` with the recorded one.
+    /// Returns `false` once every frame has been played back.
+    pub fn apply_next(&mut self, frame: &mut FrameInfo, input: &mut Input) -> bool {
+        let Some(recorded) = self.frames.get(self.position) else {
+            return false;
+        };
+        recorded.apply(input);
+        frame.delta_time = recorded.delta_time;
+        self.position += 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recording_round_trip() {
+        let mut input = Input::default();
+        let mut frame = FrameInfo {
+            index: 7,
+            fps: 60.0,
+            last_frame_time: Duration::ZERO,
+            delta_time: Duration::from_millis(16),
+        };
+        let mut recorder = InputRecorder::new("unused.rec");
+        input.set_key_state(KeyCode::KeyW, true);
+        input.set_key_state(KeyCode::ShiftLeft, true);
+        input.set_mouse_state(MouseButton::Other(3), true);
+        input.mouse_pos.delta = PhysicalPosition::new(1.5, -2.0);
+        input.gamepad.left_stick = vec2(0.25, -1.0);
+        recorder.record(&frame, &input);
+        input.end_frame();
+        input.set_key_state(KeyCode::KeyW, false);
+        frame.index += 1;
+        frame.delta_time = Duration::from_millis(17);
+        recorder.record(&frame, &input);
+
+        let mut bytes = Vec::new();
+        recorder.write_to(&mut bytes).unwrap();
+        let mut playback = InputPlayback::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(playback.frames(), recorder.frames());
+
+        let mut replayed = Input::default();
+        let mut replay_frame = FrameInfo {
+            index: 0,
+            fps: 0.0,
+            last_frame_time: Duration::ZERO,
+            delta_time: Duration::ZERO,
+        };
+        assert!(playback.apply_next(&mut replay_frame, &mut replayed));
+        assert!(replayed.key_just_pressed(KeyCode::KeyW));
+        assert!(replayed.mouse_pressed(MouseButton::Other(3)));
+        assert_eq!(replayed.gamepad().left_stick, vec2(0.25, -1.0));
+        replayed.end_frame();
+        assert!(playback.apply_next(&mut replay_frame, &mut replayed));
+        assert!(replayed.key_just_released(KeyCode::KeyW));
+        assert!(replayed.key_pressed(KeyCode::ShiftLeft));
+        assert_eq!(replay_frame.delta_time, Duration::from_millis(17));
+        assert!(!playback.apply_next(&mut replay_frame, &mut replayed));
+
+        // A key without an ID is dropped instead of being played back as another key.
+        let mut unrecordable = recorder.frames()[0].clone();
+        unrecordable.keys = vec![KeyCode::F24, KeyCode::KeyW];
+        let mut bytes = Vec::new();
+        unrecordable.write_to(&mut bytes).unwrap();
+        assert_eq!(InputFrame::read_from(&mut bytes.as_slice()).unwrap().keys, [KeyCode::KeyW]);
+    }
+
+    #[test]
+    fn invalid_recording() {
+        assert!(matches!(InputPlayback::read_from(&mut &b"nope"[..]), Err(InputRecordingError::InvalidMagic)));
+    }
+}
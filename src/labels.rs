@@ -0,0 +1,141 @@
+/*
+Text labels in world space, such as block coordinates and chunk IDs. Like the [crate::gizmo::Gizmo], labels
+are added at any point during the frame and drawn on top of the scene. [Labels::prepare] builds one mesh
+for all of them with [crate::modeling::text3d], turned towards the camera, and [Labels::render] draws it.
+*/
+
+use glam::{Quat, Vec3};
+use wgpu::util::DeviceExt;
+
+use crate::modeling::modeler::Modeler;
+use crate::modeling::text3d::{self, TextStyle};
+use crate::rendering::{memory::{self, MemoryCategory, Tracked}, msaa::multisample_state, texture_array::TextureArray, transforms::TransformsBindGroup};
+use crate::voxel::vertex::Vertex;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub position: Vec3,
+    pub text: String,
+    pub style: TextStyle,
+}
+
+pub struct Labels {
+    labels: Vec<Label>,
+    vertex_buffer: Tracked<wgpu::Buffer>,
+    index_buffer: Tracked<wgpu::Buffer>,
+    index_count: u32,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Labels {
+    /// `format` and `sample_count` are the format and sample count of the target that the labels are rendered to.
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32, transforms: &TransformsBindGroup, texture_array: &TextureArray) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/labels.wgsl"));
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Labels Pipeline Layout"),
+            bind_group_layouts: &[&transforms.bind_group_layout, &texture_array.bind_group.bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Labels Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vertex_main"),
+                buffers: &[Vertex::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fragment_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: multisample_state(sample_count),
+            multiview: None,
+            cache: None,
+        });
+        Self {
+            labels: Vec::new(),
+            vertex_buffer: Self::create_buffer(device, "Labels Vertex Buffer", &[], wgpu::BufferUsages::VERTEX),
+            index_buffer: Self::create_buffer(device, "Labels Index Buffer", &[], wgpu::BufferUsages::INDEX),
+            index_count: 0,
+            pipeline,
+        }
+    }
+
+    fn create_buffer(device: &wgpu::Device, label: &str, contents: &[u8], usage: wgpu::BufferUsages) -> Tracked<wgpu::Buffer> {
+        // Empty buffers can't be created with contents, so they get room for a little.
+        let contents = if contents.is_empty() { &[0u8; 64][..] } else { contents };
+        memory::track(MemoryCategory::Text, device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents,
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+        }))
+    }
+
+    /// Shows `text` at `position` for this frame.
+    pub fn label<S: Into<String>>(&mut self, position: Vec3, text: S, style: TextStyle) {
+        self.labels.push(Label {
+            position,
+            text: text.into(),
+            style,
+        });
+    }
+
+    /// Builds the mesh of every label, facing a camera with `camera_rotation`.
+    fn build_mesh(&self, camera_rotation: Quat) -> Modeler {
+        let mut modeler = Modeler::new();
+        for label in &self.labels {
+            modeler.transform(text3d::billboard(label.position, camera_rotation), |modeler| {
+                text3d::push_text(modeler, &label.text, &label.style);
+            });
+        }
+        modeler
+    }
+
+    /// Uploads the labels that were added since the last call and clears them.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, camera_rotation: Quat) {
+        let modeler = self.build_mesh(camera_rotation);
+        self.labels.clear();
+        self.index_count = modeler.indices.len() as u32;
+        if modeler.indices.is_empty() {
+            return;
+        }
+        let vertices: &[u8] = bytemuck::cast_slice(&modeler.vertices);
+        let indices: &[u8] = bytemuck::cast_slice(&modeler.indices);
+        if vertices.len() as u64 > self.vertex_buffer.size() {
+            self.vertex_buffer = Self::create_buffer(device, "Labels Vertex Buffer", vertices, wgpu::BufferUsages::VERTEX);
+        } else {
+            queue.write_buffer(&self.vertex_buffer, 0, vertices);
+        }
+        if indices.len() as u64 > self.index_buffer.size() {
+            self.index_buffer = Self::create_buffer(device, "Labels Index Buffer", indices, wgpu::BufferUsages::INDEX);
+        } else {
+            queue.write_buffer(&self.index_buffer, 0, indices);
+        }
+    }
+
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass, transforms: &TransformsBindGroup, texture_array: &TextureArray) {
+        if self.index_count == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &transforms.bind_group, &[]);
+        render_pass.set_bind_group(1, &texture_array.bind_group.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), Modeler::INDEX_FORMAT);
+        render_pass.draw_indexed(0..self.index_count, 0, 0..1);
+    }
+}
@@ -1,5 +1,6 @@
 use std::time::Duration;
 
+pub mod app;
 pub mod state;
 pub mod model;
 pub mod voxel;
@@ -10,12 +11,29 @@ pub mod input;
 pub mod framepace;
 pub mod modeling;
 pub mod gridzmo;
+pub mod chunk_debug;
 pub mod voxel_fog;
 // pub mod text;
 pub mod animation;
 pub mod livemouse;
 pub mod gizmo;
+pub mod labels;
 pub mod timing;
+pub mod day_night;
+pub mod sun_gizmo;
+pub mod benchmark;
+pub mod tasks;
+pub mod assets;
+pub mod physics;
+pub mod entity;
+pub mod systems;
+pub mod window_config;
+pub mod user_config;
+pub mod mouse_settings;
+pub mod movement;
+pub mod bookmarks;
+pub mod logging;
+pub mod console;
 // mod trie;
 
 pub struct FrameInfo {
@@ -1,5 +1,15 @@
 use std::time::Duration;
 
+/// Smooths the mouse movement of each frame. [crate::input::MousePosState] switches between filters
+/// with [crate::mouse_settings::SmoothingMode].
+pub trait MouseFilter {
+    /// Takes the raw delta of a frame and returns the smoothed delta.
+    fn filter(&mut self, delta: (f64, f64), dt: Duration) -> (f64, f64);
+
+    /// Forgets the previous movement, such as when the mouse halts.
+    fn reset(&mut self);
+}
+
 #[derive(Debug, Clone)]
 pub struct LiveMouse {
     pub velocity: (f64, f64),
@@ -30,7 +40,7 @@ impl LiveMouse {
     pub fn set_target(&mut self, delta_x: f64, delta_y: f64) {
         let mag = (delta_x * delta_x + delta_y * delta_y).sqrt();
         if mag > 0.0001 {
-            let scale = mag.min(self.max_velocity / mag);
+            let scale = (self.max_velocity / mag).min(1.0);
             self.target_velocity = (delta_x * scale, delta_y * scale);
         } else {
             if self.halting {
@@ -78,33 +88,98 @@ impl LiveMouse {
     }
 }
 
+impl MouseFilter for LiveMouse {
+    fn filter(&mut self, delta: (f64, f64), dt: Duration) -> (f64, f64) {
+        self.set_target(delta.0, delta.1);
+        self.update(dt)
+    }
+
+    fn reset(&mut self) {
+        LiveMouse::reset(self);
+    }
+}
+
+/// An exponential moving average of the mouse deltas.
+#[derive(Debug, Clone)]
 pub struct ExpMouse {
     pub delta: (f64, f64),
     pub delta_accum: (f64, f64),
+    /// How quickly the average follows the mouse, per second. Higher is less smooth.
     pub smoothing_factor: f64,
+    /// Drops the average as soon as the mouse stops.
     pub halting: bool,
 }
 
-// impl ExpMouse {
-//     pub fn new(
-//         smoothing_factor: f64,
-//         halting: bool,
-//     ) -> Self {
-//         Self {
-//             delta: (0.0, 0.0),
-//             delta_accum: (0.0, 0.0),
-//             smoothing_factor,
-//             halting,
-//         }
-//     }
-
-//     pub fn accumulate_delta(&mut self, delta: (f64, f64)) {
-//         self.delta_accum.0 += delta.0;
-//         self.delta_accum.1 += delta.1;
-//     }
-
-//     pub fn update(&mut self, dt: Duration) -> (f64, f64) {
-//         let secs = dt.as_secs_f64();
-        
-//     }
-// }
\ No newline at end of file
+impl ExpMouse {
+    pub fn new(
+        smoothing_factor: f64,
+        halting: bool,
+    ) -> Self {
+        Self {
+            delta: (0.0, 0.0),
+            delta_accum: (0.0, 0.0),
+            smoothing_factor,
+            halting,
+        }
+    }
+
+    /// Adds movement to the current frame.
+    pub fn accumulate_delta(&mut self, delta: (f64, f64)) {
+        self.delta_accum.0 += delta.0;
+        self.delta_accum.1 += delta.1;
+    }
+
+    /// Moves the average towards the movement that was accumulated this frame and returns it.
+    pub fn update(&mut self, dt: Duration) -> (f64, f64) {
+        let secs = dt.as_secs_f64();
+        let target = std::mem::take(&mut self.delta_accum);
+        if self.halting && target == (0.0, 0.0) {
+            self.delta = (0.0, 0.0);
+            return self.delta;
+        }
+        let smooth_factor = 1.0 - (-self.smoothing_factor * secs).exp();
+        self.delta.0 += (target.0 - self.delta.0) * smooth_factor;
+        self.delta.1 += (target.1 - self.delta.1) * smooth_factor;
+        self.delta
+    }
+}
+
+impl MouseFilter for ExpMouse {
+    fn filter(&mut self, delta: (f64, f64), dt: Duration) -> (f64, f64) {
+        self.accumulate_delta(delta);
+        self.update(dt)
+    }
+
+    fn reset(&mut self) {
+        self.delta = (0.0, 0.0);
+        self.delta_accum = (0.0, 0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mouse_filter_test() {
+        let frame = Duration::from_millis(16);
+        let mut exp = ExpMouse::new(20.0, true);
+        let first = exp.filter((10.0, 0.0), frame);
+        assert!(first.0 > 0.0 && first.0 < 10.0);
+        // Converges on a steady movement.
+        let steady = (0..100).fold(first, |_, _| exp.filter((10.0, -5.0), frame));
+        assert!((steady.0 - 10.0).abs() < 1e-3 && (steady.1 + 5.0).abs() < 1e-3);
+        // Halting drops the average immediately.
+        assert_eq!(exp.filter((0.0, 0.0), frame), (0.0, 0.0));
+        exp.halting = false;
+        exp.filter((10.0, 0.0), frame);
+        assert!(exp.filter((0.0, 0.0), frame).0 > 0.0);
+
+        let mut live = LiveMouse::new(100.0, 100.0, 50.0, false);
+        let steady = (0..100).fold((0.0, 0.0), |_, _| live.filter((300.0, 0.0), frame));
+        // Clamped to the max velocity.
+        assert!((steady.0 - 50.0).abs() < 1e-3);
+        MouseFilter::reset(&mut live);
+        assert_eq!(live.velocity(), (0.0, 0.0));
+    }
+}
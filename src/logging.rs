@@ -0,0 +1,159 @@
+use std::{collections::VecDeque, sync::{LazyLock, Mutex}, time::{Duration, Instant}};
+
+use log::{Level, Log, Metadata, Record};
+
+/*
+Everything logs through the `log` macros. [init] installs a logger that prints to stdout with env_logger,
+and also keeps the latest records in a ring buffer ([log_buffer]) for the in-game console (see
+[crate::console]).
+
+Levels are set per module with `RUST_LOG`, in env_logger's syntax:
+
+    RUST_LOG=warn,wgpu_learn=info,wgpu_learn::rendering=debug
+
+Without it, [DEFAULT_FILTER] applies. Only records that pass the filter reach the console.
+*/
+
+/// This crate at info, wgpu and the other dependencies at warn.
+pub const DEFAULT_FILTER: &str = "warn,wgpu_learn=info";
+pub const LOG_BUFFER_CAPACITY: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    pub level: Level,
+    /// The module that logged it, such as `wgpu_learn::state`.
+    pub target: String,
+    pub message: String,
+    /// Since the logger was installed.
+    pub time: Duration,
+}
+
+/// The latest log entries. Once it's full, the oldest entry is dropped for every new one.
+pub struct LogBuffer {
+    entries: Mutex<VecDeque<LogEntry>>,
+    capacity: usize,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn push(&self, entry: LogEntry) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// The entries that `filter` accepts, oldest first.
+    pub fn filtered<F: FnMut(&LogEntry) -> bool>(&self, mut filter: F) -> Vec<LogEntry> {
+        self.entries.lock().unwrap().iter().filter(|entry| filter(entry)).cloned().collect()
+    }
+
+    /// The targets of the entries, sorted and without duplicates.
+    pub fn targets(&self) -> Vec<String> {
+        let mut targets = self.entries.lock().unwrap().iter().map(|entry| entry.target.clone()).collect::<Vec<_>>();
+        targets.sort_unstable();
+        targets.dedup();
+        targets
+    }
+}
+
+static START: LazyLock<Instant> = LazyLock::new(Instant::now);
+static LOG_BUFFER: LazyLock<LogBuffer> = LazyLock::new(|| LogBuffer::new(LOG_BUFFER_CAPACITY));
+
+/// The entries of the logger installed by [init].
+pub fn log_buffer() -> &'static LogBuffer {
+    &LOG_BUFFER
+}
+
+struct Logger {
+    stdout: env_logger::Logger,
+    buffer: &'static LogBuffer,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.stdout.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.stdout.matches(record) {
+            return;
+        }
+        self.stdout.log(record);
+        self.buffer.push(LogEntry {
+            level: record.level(),
+            target: record.target().to_owned(),
+            message: record.args().to_string(),
+            time: START.elapsed(),
+        });
+    }
+
+    fn flush(&self) {
+        self.stdout.flush();
+    }
+}
+
+/// Installs the logger. Call it once, before anything is logged.
+pub fn init() {
+    let stdout = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(DEFAULT_FILTER))
+        .target(env_logger::Target::Stdout)
+        .build();
+    let max_level = stdout.filter();
+    LazyLock::force(&START);
+    match log::set_boxed_logger(Box::new(Logger { stdout, buffer: &LOG_BUFFER })) {
+        Ok(()) => log::set_max_level(max_level),
+        Err(err) => eprintln!("Failed to install the logger: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(level: Level, target: &str, message: &str) -> LogEntry {
+        LogEntry {
+            level,
+            target: target.to_owned(),
+            message: message.to_owned(),
+            time: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn log_buffer_test() {
+        let buffer = LogBuffer::new(3);
+        assert!(buffer.is_empty());
+        buffer.push(entry(Level::Info, "wgpu_learn::state", "first"));
+        buffer.push(entry(Level::Warn, "wgpu_core::device", "second"));
+        buffer.push(entry(Level::Info, "wgpu_learn::assets", "third"));
+        buffer.push(entry(Level::Error, "wgpu_learn::state", "fourth"));
+        // The oldest entry was dropped.
+        assert_eq!(buffer.len(), 3);
+        let messages = buffer.filtered(|_| true).into_iter().map(|entry| entry.message).collect::<Vec<_>>();
+        assert_eq!(messages, ["second", "third", "fourth"]);
+        let warnings = buffer.filtered(|entry| entry.level <= Level::Warn);
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(buffer.targets(), ["wgpu_core::device", "wgpu_learn::assets", "wgpu_learn::state"]);
+        buffer.clear();
+        assert!(buffer.is_empty());
+    }
+}
@@ -2,59 +2,126 @@
 
 use glam::vec3;
 use pollster;
-use wgpu_learn::{framepace::AverageBuffer, modeling::modeler::Modeler, state::State, FrameInfo};
-use std::{collections::HashMap, ops::ControlFlow, time::{Duration, Instant}};
-use image::{
-    ImageBuffer, Rgba,
-};
+use wgpu_learn::{app::{App, GameSettings}, benchmark::BenchmarkSettings, logging, modeling::modeler::Modeler, state::{State, INPUT_RECORDING_PATH, USER_CONFIG_PATH}, systems::{CloudsSystem, DayNightSystem, OverlaySystem, ReticleSystem}, user_config::UserConfig, window_config::FullscreenMode};
+use std::path::PathBuf;
 
-use winit::{
-    dpi::{LogicalSize, PhysicalPosition, PhysicalSize, Size}, event::*, event_loop::{EventLoop, EventLoopWindowTarget}, keyboard::{KeyCode, PhysicalKey}, monitor::VideoMode, window::WindowBuilder
-};
+use winit::dpi::{LogicalSize, Size};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-struct Timer(Instant);
-impl Timer {
-    fn start() -> Self {
-        Self(Instant::now())
-    }
+const USAGE: &str = "\
+Usage: wgpu_learn [options]
+    --width <pixels>        Window width (logical pixels).
+    --height <pixels>       Window height (logical pixels).
+    --fullscreen            Start in borderless fullscreen.
+    --vsync <on|off>        Toggle vsync.
+    --chunk <file>          Load a chunk at startup.
+    --assets <dir>          The asset directory.
+    --record-input          Record input to the default recording file.
+    --record <file>         Record input to a file.
+    --playback <file>       Play back an input recording.
+    --bench                 Run the benchmark, save the report and exit.
+    --bench-frames <n>      The number of frames that the benchmark records.
+    --help                  Print this message.";
 
-    /// Resets the timer and returns the [Duration].
-    fn time(&mut self) -> Duration {
-        let duration = self.0.elapsed();
-        self.0 = Instant::now();
-        duration
-    }
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+enum ArgError {
+    #[error("Missing value for {0}.")]
+    MissingValue(String),
+    #[error("Invalid value for {arg}: {value:?}")]
+    InvalidValue { arg: String, value: String },
+    #[error("Unknown argument: {0}")]
+    Unknown(String),
+}
 
-    fn split_time(&mut self) -> Self {
-        let old = *self;
-        self.0 = Instant::now();
-        old
-    }
+/// Options from the command line. Unset options keep their defaults.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct LaunchOptions {
+    width: Option<u32>,
+    height: Option<u32>,
+    fullscreen: bool,
+    vsync: Option<bool>,
+    chunk: Option<PathBuf>,
+    assets: Option<PathBuf>,
+    record: Option<PathBuf>,
+    playback: Option<PathBuf>,
+    bench: bool,
+    bench_frames: Option<u32>,
+    help: bool,
+}
 
-    fn elapsed(&self) -> Duration {
-        self.0.elapsed()
+impl LaunchOptions {
+    fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, ArgError> {
+        fn value<I: Iterator<Item = String>>(arg: &str, args: &mut I) -> Result<String, ArgError> {
+            args.next().ok_or_else(|| ArgError::MissingValue(arg.to_owned()))
+        }
+        fn number<I: Iterator<Item = String>>(arg: &str, args: &mut I) -> Result<u32, ArgError> {
+            let value = value(arg, args)?;
+            match value.parse() {
+                Ok(number) if number > 0 => Ok(number),
+                _ => Err(ArgError::InvalidValue { arg: arg.to_owned(), value }),
+            }
+        }
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--width" => options.width = Some(number(&arg, &mut args)?),
+                "--height" => options.height = Some(number(&arg, &mut args)?),
+                "--fullscreen" => options.fullscreen = true,
+                "--vsync" => options.vsync = Some(match value(&arg, &mut args)?.as_str() {
+                    "on" => true,
+                    "off" => false,
+                    value => return Err(ArgError::InvalidValue { arg, value: value.to_owned() }),
+                }),
+                "--chunk" => options.chunk = Some(value(&arg, &mut args)?.into()),
+                "--assets" => options.assets = Some(value(&arg, &mut args)?.into()),
+                "--record-input" => options.record = Some(INPUT_RECORDING_PATH.into()),
+                "--record" => options.record = Some(value(&arg, &mut args)?.into()),
+                "--playback" => options.playback = Some(value(&arg, &mut args)?.into()),
+                "--bench" => options.bench = true,
+                "--bench-frames" => options.bench_frames = Some(number(&arg, &mut args)?),
+                "--help" | "-h" => options.help = true,
+                _ => return Err(ArgError::Unknown(arg)),
+            }
+        }
+        Ok(options)
     }
 
-    fn framerate(&self) -> f64 {
-        1.0 / self.0.elapsed().as_secs_f64()
+    /// The benchmark to run at startup, if any. `--bench-frames` alone doesn't start one.
+    fn benchmark(&self) -> Option<BenchmarkSettings> {
+        self.bench.then(|| {
+            let defaults = BenchmarkSettings::default();
+            BenchmarkSettings {
+                frames: self.bench_frames.unwrap_or(defaults.frames),
+                ..defaults
+            }
+        })
     }
 
-    fn wait(&mut self, duration: Duration) {
-        spin_sleep::sleep_until(self.0 + duration);
-        self.0 = Instant::now();
+    /// Applies the options that are needed before the window is created.
+    fn apply(&self, settings: &mut GameSettings) {
+        if self.width.is_some() || self.height.is_some() {
+            let default_size = settings.window.size.to_logical::<f64>(1.0);
+            settings.window.size = Size::Logical(LogicalSize::new(
+                self.width.map(f64::from).unwrap_or(default_size.width),
+                self.height.map(f64::from).unwrap_or(default_size.height),
+            ));
+        }
+        if self.fullscreen {
+            settings.window.fullscreen = FullscreenMode::Borderless;
+        }
+        if let Some(vsync) = self.vsync {
+            settings.present_mode = if vsync {
+                wgpu::PresentMode::AutoVsync
+            } else {
+                wgpu::PresentMode::AutoNoVsync
+            };
+        }
+        if let Some(assets) = &self.assets {
+            settings.assets_root = assets.clone();
+        }
     }
 }
 
-struct GameSettings {
-    present_mode: wgpu::PresentMode,
-    camera_smoothing_frame_count: Option<usize>,
-    framerate_frame_count: usize,
-    fullscreen: bool,
-    window_title: &'static str,
-    window_size: Size,
-}
-
 pub async fn run() {
     // let start_time = Instant::now();
     // let mut m = Modeler::new();
@@ -74,146 +141,53 @@ pub async fn run() {
     // println!("{:?}", &m.vertices[4..8]);
     // println!("Elapsed: {:.06}", elapsed.as_secs_f64());
     // return;
-    env_logger::init();
-    let mut event_loop = EventLoop::new().unwrap();
-    event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
-    let window = WindowBuilder::new()
-        .with_inner_size(Size::Logical(LogicalSize::new(1280.0, 720.0)))
-        .with_title("WGPU Sandbox")
-        // .with_fullscreen(Some(winit::window::Fullscreen::Borderless(None)))
-        // .with_content_protected(true)
-        .build(&event_loop).unwrap();
-    // window.set_cursor_visible(false);
-    {
-        let window_size = window.outer_size();
-        let screen_size = window.current_monitor().unwrap().size();
-        let window_half_size = PhysicalSize::new(window_size.width / 2, window_size.height / 2);
-        let screen_half_size = PhysicalSize::new(screen_size.width / 2, screen_size.height / 2);
-        let center_point = PhysicalPosition::new(
-            screen_half_size.width - window_half_size.width,
-            screen_half_size.height - window_half_size.height,
-        );
-        window.set_outer_position(center_point);
-    }
-    // window.set_cursor_visible(false);
-    let mut state = State::new(&window).await;
-    let monitor = state.window().current_monitor().unwrap();
-    let frame_time = if let Some(refresh) = monitor.refresh_rate_millihertz() {
-        println!("Refresh rate: {}", refresh / 1000);
-        Some(refresh as f64 / 1000.0)
-    } else {
-        None
-    };
-    let mut timer = Timer(Instant::now());
-    let mut wait_timer = Timer(Instant::now());
-    let mut frame_counter = 0u64;
-    let mut focused = true;
-    let mut update_timer = Timer(Instant::now());
-    let mut render_timer = Timer(Instant::now());
-    let mut avg_update_time: Option<f64> = None;
-    let mut avg_render_time: Option<f64> = None;
-    let mut fps_avgs = AverageBuffer::new(32);
-
-    let mut frame = FrameInfo {
-        index: 0,
-        fps: 0.0,
-        last_frame_time: Duration::from_secs(0),
-        delta_time: Duration::from_secs(0),
+    logging::init();
+    let options = match LaunchOptions::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            log::error!("{err}");
+            eprintln!("{USAGE}");
+            std::process::exit(2);
+        }
     };
-    let mut loop_timer = Timer(Instant::now());
-    event_loop.run(move |event, control_flow| {
-        while let Some(event) = state.gamepad.next_event() {
-            state.process_gamepad_event(&event);
+    if options.help {
+        println!("{USAGE}");
+        return;
+    }
+    let mut settings = GameSettings::default();
+    let user_config = UserConfig::load_or_default(USER_CONFIG_PATH);
+    settings.apply_user_config(&user_config);
+    options.apply(&mut settings);
+    let result = App::new(settings).run(|window, settings| {
+        let mut state = pollster::block_on(State::with_assets_root(window, settings.assets_root.clone()));
+        // Systems run in the order that they're added.
+        state.systems
+            .add(DayNightSystem)
+            .add(CloudsSystem)
+            .add(ReticleSystem)
+            .add(OverlaySystem);
+        state.apply_user_config(&user_config);
+        state.set_user_config_path(Some(PathBuf::from(USER_CONFIG_PATH)));
+        if let Some(path) = &options.chunk {
+            state.load_chunk(path.clone());
         }
-        state.process_event(&event);
-        match event {
-            Event::WindowEvent {
-                ref event,
-                window_id,
-            } if window_id == state.window.id() => if !state.process_window_event(event) {
-                match event {
-                    WindowEvent::CloseRequested
-                    | WindowEvent::KeyboardInput {
-                        event:
-                            // Escape key pressed
-                            KeyEvent {
-                                state: ElementState::Pressed,
-                                physical_key: PhysicalKey::Code(KeyCode::Escape),
-                                ..
-                            },
-                        ..
-                    } if state.close_requested() => control_flow.exit(),
-                    WindowEvent::Focused(focus) => {
-                        focused = *focus;
-                        state.focus_changed(focused);
-                    }
-                    WindowEvent::Resized(physical_size) => {
-                        state.resize(*physical_size);
-                    }
-                    WindowEvent::RedrawRequested => {
-                        // if focused {
-                        // }
-                        let frame_time = loop_timer.split_time();
-                        let fps = frame_time.framerate();
-                        fps_avgs.push(fps);
-                        let avg_fps = fps_avgs.average();
-                        frame.fps = avg_fps;
-
-                        frame.delta_time = frame_time.elapsed();
-                        state.begin_frame(&frame);
-                        // timer.wait(Duration::from_secs(1)/60);
-                        
-                        // println!("Framerate: {}", old.framerate());
-                        {
-                            let start_time = Timer::start();
-                            state.update(&frame);
-                            let end_time = start_time.elapsed();
-                            let secs = end_time.as_secs_f64();
-                            if let Some(ref mut avg) = avg_update_time {
-                                *avg = (*avg + secs) * 0.5;
-                            } else {
-                                avg_update_time.replace(secs);
-                            }
-                        }
-
-                        match state.render(&frame) {
-                            Ok(render_time) => {
-                                let secs = render_time.as_secs_f64();
-                                if let Some(ref mut avg) = avg_render_time {
-                                    *avg = (*avg + secs) * 0.5;
-                                } else {
-                                    avg_render_time.replace(secs);
-                                }
-                            },
-                            Err(wgpu::SurfaceError::Lost) => state.resize(state.size),
-                            Err(wgpu::SurfaceError::OutOfMemory) => {
-                                log::error!("OutOfMemory");
-                                control_flow.exit()
-                            },
-                            Err(wgpu::SurfaceError::Timeout) => {
-                                log::warn!("Surface timeout");
-                            }
-                            Err(e) => eprintln!("Err: {e:?}"),
-                        }
-                        
-
-                        let time = timer.time();
-                        state.end_frame(&frame);
-                        frame.last_frame_time = time;
-                        frame.index += 1;
-                    }
-                    _ => {}
-                }
-            }
-            Event::AboutToWait => {
-                // println!("Wait FPS: {}", wait.framerate());
-                if focused {
-                    state.window().request_redraw();
-                }
+        if let Some(path) = &options.record {
+            state.start_recording(path);
+        }
+        if let Some(path) = &options.playback {
+            if let Err(err) = state.start_playback(path) {
+                log::error!("Failed to load input recording {path:?}: {err}");
             }
-            _ => {}
         }
+        if let Some(settings) = options.benchmark() {
+            state.start_benchmark(settings, true);
+        }
+        state
     });
+    if let Err(err) = result {
+        log::error!("{err}");
+        std::process::exit(1);
+    }
 }
 
 #[pollster::main]
@@ -222,6 +196,38 @@ async fn main() {
 }
 
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn launch_options_test() {
+        let args = |args: &[&str]| LaunchOptions::parse(args.iter().map(|arg| arg.to_string()));
+        let options = args(&["--width", "640", "--fullscreen", "--vsync", "off", "--chunk", "a.dat", "--record-input"]).unwrap();
+        assert_eq!(options.width, Some(640));
+        assert_eq!(options.height, None);
+        assert_eq!(options.vsync, Some(false));
+        assert_eq!(options.chunk, Some(PathBuf::from("a.dat")));
+        assert_eq!(options.record, Some(PathBuf::from(INPUT_RECORDING_PATH)));
+        let mut settings = GameSettings::default();
+        options.apply(&mut settings);
+        assert_eq!(settings.window.size, Size::Logical(LogicalSize::new(640.0, 720.0)));
+        assert_eq!(settings.window.fullscreen, FullscreenMode::Borderless);
+        assert_eq!(settings.present_mode, wgpu::PresentMode::AutoNoVsync);
+
+        assert_eq!(args(&["--height"]), Err(ArgError::MissingValue("--height".into())));
+        assert_eq!(args(&["--width", "0"]), Err(ArgError::InvalidValue { arg: "--width".into(), value: "0".into() }));
+        assert_eq!(args(&["--vsync", "maybe"]), Err(ArgError::InvalidValue { arg: "--vsync".into(), value: "maybe".into() }));
+        assert_eq!(args(&["--wat"]), Err(ArgError::Unknown("--wat".into())));
+
+        assert_eq!(options.benchmark(), None);
+        assert_eq!(args(&["--bench-frames", "100"]).unwrap().benchmark(), None);
+        let bench = args(&["--bench", "--bench-frames", "100"]).unwrap().benchmark().unwrap();
+        assert_eq!(bench.frames, 100);
+        assert_eq!(bench.seed, BenchmarkSettings::default().seed);
+    }
+}
+
 #[cfg(test)]
 mod testing_sandbox {
     // TODO: Remove this sandbox when it is no longer in use.
@@ -1,139 +1,171 @@
-use std::{collections::VecDeque, time::Duration};
+use std::{collections::VecDeque, ops::{Add, Sub}, time::Duration};
 
+/// A value that can be averaged by an [AverageBuffer].
+pub trait Average: Copy + PartialOrd + Add<Output = Self> + Sub<Output = Self> {
+    const ZERO: Self;
+
+    fn div_usize(self, divisor: usize) -> Self;
+}
+
+impl Average for f32 {
+    const ZERO: Self = 0.0;
+
+    fn div_usize(self, divisor: usize) -> Self {
+        self / divisor as f32
+    }
+}
+
+impl Average for f64 {
+    const ZERO: Self = 0.0;
+
+    fn div_usize(self, divisor: usize) -> Self {
+        self / divisor as f64
+    }
+}
+
+impl Average for Duration {
+    const ZERO: Self = Duration::ZERO;
+
+    fn div_usize(self, divisor: usize) -> Self {
+        self / divisor as u32
+    }
+}
+
+/// A rolling average of the last `capacity` values.
+#[derive(Debug, Clone)]
 pub struct AverageBuffer<T> {
     buffer: VecDeque<T>,
+    capacity: usize,
     current_total: T,
 }
 
-pub trait AvgBuffer<T> {
-    fn new<I: Into<Option<T>>>(capacity: usize, initial: I) -> Self;
-    fn push(&mut self, value: T) -> T;
-    fn average(&self) -> T;
-    fn clear(&mut self);
-}
-
-impl AvgBuffer<f32> for AverageBuffer<f32> {
-    fn new<I: Into<Option<f32>>>(capacity: usize, initial: I) -> Self {
+impl<T: Average> AverageBuffer<T> {
+    /// `initial` is pushed into the buffer if it is provided.
+    pub fn new<I: Into<Option<T>>>(capacity: usize, initial: I) -> Self {
+        assert_ne!(capacity, 0, "Capacity must be greater than 0.");
+        let mut new = Self {
+            buffer: VecDeque::with_capacity(capacity),
+            capacity,
+            current_total: T::ZERO,
+        };
         if let Some(initial) = initial.into() {
-            let mut buffer = VecDeque::with_capacity(capacity);
-            buffer.push_back(initial);
-            Self {
-                buffer,
-                current_total: initial,
-            }
-        } else {
-            Self {
-                buffer: VecDeque::with_capacity(capacity),
-                current_total: 0.0,
-            }
+            new.push(initial);
         }
+        new
     }
 
-    fn push(&mut self, value: f32) -> f32 {
-        if self.buffer.len() == self.buffer.capacity() {
+    /// Pushes the value, dropping the oldest value if the buffer is full, and returns the new average.
+    pub fn push(&mut self, value: T) -> T {
+        if self.buffer.len() == self.capacity {
             if let Some(front) = self.buffer.pop_front() {
-                self.current_total -= front;
+                self.current_total = self.current_total - front;
             }
         }
         self.buffer.push_back(value);
-        self.current_total += value;
-        self.current_total / self.buffer.len() as f32
+        self.current_total = self.current_total + value;
+        self.average()
     }
 
-    fn average(&self) -> f32 {
+    /// The average of the values in the buffer, or zero if it is empty.
+    pub fn average(&self) -> T {
         if self.buffer.is_empty() {
-            self.current_total
+            T::ZERO
         } else {
-            self.current_total / self.buffer.len() as f32
+            self.current_total.div_usize(self.buffer.len())
         }
     }
 
-    fn clear(&mut self) {
-        self.buffer.clear();
-        self.current_total = 0.0;
+    pub fn min(&self) -> Option<T> {
+        self.buffer.iter().copied().reduce(|min, value| if value < min { value } else { min })
     }
-}
 
-impl AvgBuffer<f64> for AverageBuffer<f64> {
-    fn new<I: Into<Option<f64>>>(capacity: usize, initial: I) -> Self {
-        if let Some(initial) = initial.into() {
-            let mut buffer = VecDeque::with_capacity(capacity);
-            buffer.push_back(initial);
-            Self {
-                buffer,
-                current_total: initial,
-            }
-        } else {
-            Self {
-                buffer: VecDeque::with_capacity(capacity),
-                current_total: 0.0,
-            }
-        }
+    pub fn max(&self) -> Option<T> {
+        self.buffer.iter().copied().reduce(|max, value| if value > max { value } else { max })
     }
 
-    fn push(&mut self, value: f64) -> f64 {
-        if self.buffer.len() == self.buffer.capacity() {
-            if let Some(front) = self.buffer.pop_front() {
-                self.current_total -= front;
-            }
+    /// The value below which `fraction` (`0.0..=1.0`) of the values fall, using the nearest rank.
+    /// `percentile(0.5)` is the median and `percentile(0.99)` the 99th percentile.
+    pub fn percentile(&self, fraction: f64) -> Option<T> {
+        if self.buffer.is_empty() {
+            return None;
         }
-        self.buffer.push_back(value);
-        self.current_total += value;
-        self.current_total / self.buffer.len() as f64
+        let mut sorted = self.buffer.iter().copied().collect::<Vec<_>>();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let rank = (fraction.clamp(0.0, 1.0) * sorted.len() as f64).ceil() as usize;
+        Some(sorted[rank.saturating_sub(1)])
     }
 
-    fn average(&self) -> f64 {
-        if self.buffer.is_empty() {
-            self.current_total
-        } else {
-            self.current_total / self.buffer.len() as f64
-        }
+    /// The values from oldest to newest.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.buffer.iter().copied()
     }
 
-    fn clear(&mut self) {
-        self.buffer.clear();
-        self.current_total = 0.0;
+    /// The newest value.
+    pub fn last(&self) -> Option<T> {
+        self.buffer.back().copied()
     }
-}
 
-impl AvgBuffer<Duration> for AverageBuffer<Duration> {
-    fn new<I: Into<Option<Duration>>>(capacity: usize, initial: I) -> Self {
-        if let Some(initial) = initial.into() {
-            let mut buffer = VecDeque::with_capacity(capacity);
-            buffer.push_back(initial);
-            Self {
-                buffer,
-                current_total: initial,
-            }
-        } else {
-            Self {
-                buffer: VecDeque::with_capacity(capacity),
-                current_total: Duration::ZERO,
-            }
-        }
+    pub fn len(&self) -> usize {
+        self.buffer.len()
     }
 
-    fn push(&mut self, value: Duration) -> Duration {
-        if self.buffer.len() == self.buffer.capacity() {
-            if let Some(front) = self.buffer.pop_front() {
-                self.current_total -= front;
-            }
-        }
-        self.buffer.push_back(value);
-        self.current_total += value;
-        self.current_total / self.buffer.len() as u32
+    pub fn capacity(&self) -> usize {
+        self.capacity
     }
 
-    fn average(&self) -> Duration {
-        if self.buffer.is_empty() {
-            self.current_total
-        } else {
-            self.current_total / self.buffer.len() as u32
-        }
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.buffer.len() == self.capacity
     }
 
-    fn clear(&mut self) {
+    pub fn clear(&mut self) {
         self.buffer.clear();
-        self.current_total = Duration::ZERO;
+        self.current_total = T::ZERO;
+    }
+
+    /// Clears the buffer and pushes `seed`.
+    pub fn reset(&mut self, seed: T) {
+        self.clear();
+        self.push(seed);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn avg_test() {
+        let mut avgs = AverageBuffer::new(3, 5.0);
+        assert_eq!(avgs.push(10.0), 7.5);
+        assert_eq!(avgs.push(15.0), 10.0);
+        // 5.0 is dropped.
+        assert_eq!(avgs.push(20.0), 15.0);
+        assert_eq!(avgs.len(), 3);
+        assert_eq!(avgs.last(), Some(20.0));
+        assert_eq!(avgs.min(), Some(10.0));
+        assert_eq!(avgs.max(), Some(20.0));
+        avgs.reset(50.0);
+        assert_eq!(avgs.average(), 50.0);
+        avgs.clear();
+        assert_eq!(avgs.average(), 0.0);
+        assert_eq!(avgs.min(), None);
+    }
+
+    #[test]
+    fn percentile_test() {
+        let mut avgs = AverageBuffer::<Duration>::new(100, None);
+        assert_eq!(avgs.percentile(0.5), None);
+        for millis in (1..=100).rev() {
+            avgs.push(Duration::from_millis(millis));
+        }
+        assert_eq!(avgs.percentile(0.5), Some(Duration::from_millis(50)));
+        assert_eq!(avgs.percentile(0.99), Some(Duration::from_millis(99)));
+        assert_eq!(avgs.percentile(0.0), Some(Duration::from_millis(1)));
+        assert_eq!(avgs.percentile(1.0), Some(Duration::from_millis(100)));
+        assert_eq!(avgs.average(), Duration::from_micros(50_500));
+    }
+}
@@ -0,0 +1,380 @@
+use glam::*;
+
+use crate::rendering::raytrace::{Face, RayHit};
+
+use super::ray::Ray3;
+
+/*
+Voxel traversal (Amanatides & Woo) over the cells in `min..max`.
+This is the same traversal that `raytrace.wgsl` uses, so the CPU side (picking, visibility
+queries) sees exactly what the GPU renders.
+*/
+
+/// This is needed to penetrate the ray into the bounding box.
+/// Otherwise you'll get weird circles from the rays popping
+/// in and out of the next cell. This ensures that the ray
+/// will be inside the bounding box.
+const RAY_PENETRATION: f32 = 1e-5;
+
+#[inline(always)]
+fn calc_delta(mag: f32) -> f32 {
+    1.0 / mag.abs().max(<f32>::MIN_POSITIVE)
+}
+
+#[inline(always)]
+fn calc_t_max(step: i32, fract: f32, mag: f32) -> f32 {
+    if step > 0 {
+        (1.0 - fract) / mag.abs().max(<f32>::MIN_POSITIVE)
+    } else if step < 0 {
+        fract / mag.abs().max(<f32>::MIN_POSITIVE)
+    } else {
+        <f32>::INFINITY
+    }
+}
+
+/// The distances along the ray at which it enters and exits the slab `0..size` on a single axis.
+#[inline(always)]
+fn slab(step: i32, pos: f32, dir: f32, size: f32) -> (f32, f32) {
+    match step {
+        -1 => ((pos - size) / -dir, pos / -dir),
+        1 => (-pos / dir, (size - pos) / dir),
+        _ => (<f32>::NEG_INFINITY, <f32>::INFINITY),
+    }
+}
+
+/// The distance along the ray at which it exits the slab `0..size` on a single axis.
+#[inline(always)]
+fn slab_exit(step: i32, pos: f32, dir: f32, size: f32) -> f32 {
+    match step {
+        -1 => pos / -dir,
+        1 => (size - pos) / dir,
+        _ => <f32>::INFINITY,
+    }
+}
+
+/// Casts a ray through the cells in `min..max`, returning the first cell where `sample` returns
+/// a non-zero ID.
+///
+/// `sample` is only called for cells inside `min..max`. If the ray starts outside of the volume,
+/// the returned [RayHit] has the face the ray entered through, otherwise a ray that starts inside
+/// of a non-zero cell hits it with no face.
+pub fn raycast<F: Fn(IVec3) -> u32>(
+    ray: Ray3,
+    min: IVec3,
+    max: IVec3,
+    max_distance: f32,
+    sample: F,
+) -> Option<RayHit> {
+    let size = (max - min).as_vec3a();
+    // Work relative to min so that the volume is 0..size.
+    let mut pos = ray.pos - min.as_vec3a();
+    let dir = ray.dir;
+    let sign = dir.signum();
+    let step = sign.as_ivec3();
+    let lt = pos.cmplt(Vec3A::ZERO);
+    let ge = pos.cmpge(size);
+    let (delta_min, delta_max, delta_add) = if (lt | ge).any() {
+        let neg_sign = sign.cmplt(Vec3A::ZERO);
+        let pos_sign = sign.cmpgt(Vec3A::ZERO);
+        if ((lt & neg_sign) | (ge & pos_sign)).any() {
+            return None;
+        }
+        let (dx_min, dx_max) = slab(step.x, pos.x, dir.x, size.x);
+        let (dy_min, dy_max) = slab(step.y, pos.y, dir.y, size.y);
+        let (dz_min, dz_max) = slab(step.z, pos.z, dir.z, size.z);
+        let max_min = dx_min.max(dy_min.max(dz_min));
+        let min_max = dx_max.min(dy_max.min(dz_max));
+        // Early return, the ray does not hit the volume.
+        if max_min >= min_max {
+            return None;
+        }
+        let delta_add = max_min + RAY_PENETRATION;
+        if delta_add >= max_distance {
+            return None;
+        }
+        pos += dir * delta_add;
+        (
+            Some(vec3(dx_min, dy_min, dz_min)),
+            vec3(dx_max, dy_max, dz_max),
+            delta_add,
+        )
+    } else {
+        (
+            None,
+            vec3(
+                slab_exit(step.x, pos.x, dir.x, size.x),
+                slab_exit(step.y, pos.y, dir.y, size.y),
+                slab_exit(step.z, pos.z, dir.z, size.z),
+            ),
+            0.0,
+        )
+    };
+    let delta = vec3(
+        calc_delta(dir.x),
+        calc_delta(dir.y),
+        calc_delta(dir.z),
+    );
+
+    let face = (
+        if step.x >= 0 {
+            Face::NegX
+        } else {
+            Face::PosX
+        },
+        if step.y >= 0 {
+            Face::NegY
+        } else {
+            Face::PosY
+        },
+        if step.z >= 0 {
+            Face::NegZ
+        } else {
+            Face::PosZ
+        },
+    );
+
+    let fract = pos.fract();
+    let mut t_max = vec3(
+        calc_t_max(step.x, fract.x, dir.x) + delta_add,
+        calc_t_max(step.y, fract.y, dir.y) + delta_add,
+        calc_t_max(step.z, fract.z, dir.z) + delta_add,
+    );
+    let local_max = max - min;
+    let in_bounds = |cell: IVec3| cell.cmpge(IVec3::ZERO).all() && cell.cmplt(local_max).all();
+
+    // Cells outside of the volume (from floating point error at the edges) are treated as empty.
+    let sample_cell = |cell: IVec3| if in_bounds(cell) { sample(cell + min) } else { 0 };
+
+    let mut cell = pos.floor().as_ivec3();
+    let id = sample_cell(cell);
+    if id != 0 {
+        return Some(RayHit {
+            face: delta_min.map(|min| {
+                if min.x >= min.y {
+                    if min.x >= min.z {
+                        face.0
+                    } else {
+                        face.2
+                    }
+                } else if min.y >= min.z {
+                    face.1
+                } else {
+                    face.2
+                }
+            }),
+            id,
+            coord: cell + min,
+            distance: delta_add,
+        });
+    }
+    let max_d = vec3(
+        delta_max.x.min(max_distance),
+        delta_max.y.min(max_distance),
+        delta_max.z.min(max_distance),
+    );
+    loop {
+        // Pick the axis with the nearest cell boundary.
+        let (axis, hit_face) = if t_max.x <= t_max.y {
+            if t_max.x <= t_max.z {
+                (0, face.0)
+            } else {
+                (2, face.2)
+            }
+        } else if t_max.y <= t_max.z {
+            (1, face.1)
+        } else {
+            (2, face.2)
+        };
+        let distance = t_max[axis];
+        if distance >= max_d[axis] {
+            return None;
+        }
+        cell[axis] += step[axis];
+        let id = sample_cell(cell);
+        if id != 0 {
+            return Some(RayHit::hit_face(cell + min, distance, id, hit_face));
+        }
+        t_max[axis] += delta[axis];
+    }
+}
+
+/// Returns `true` if no non-zero cell in `min..max` lies between `from` and `to`.
+/// The cell containing `from` is ignored so that queries can start on a surface.
+pub fn line_of_sight<F: Fn(IVec3) -> u32>(
+    from: Vec3A,
+    to: Vec3A,
+    min: IVec3,
+    max: IVec3,
+    sample: F,
+) -> bool {
+    let offset = to - from;
+    let distance = offset.length();
+    if distance <= 0.0 {
+        return true;
+    }
+    let start = from.floor().as_ivec3();
+    let ray = Ray3::new(from, offset / distance);
+    raycast(ray, min, max, distance, |cell| {
+        if cell == start {
+            0
+        } else {
+            sample(cell)
+        }
+    }).is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: i32 = 16;
+
+    struct Rng(u64);
+
+    impl Rng {
+        fn next_u32(&mut self) -> u32 {
+            // xorshift64*
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            (self.0.wrapping_mul(0x2545F4914F6CDD1D) >> 32) as u32
+        }
+
+        fn next_f32(&mut self) -> f32 {
+            self.next_u32() as f32 / u32::MAX as f32
+        }
+
+        fn range(&mut self, min: f32, max: f32) -> f32 {
+            min + (max - min) * self.next_f32()
+        }
+
+        fn direction(&mut self) -> Vec3A {
+            loop {
+                let v = vec3a(self.range(-1.0, 1.0), self.range(-1.0, 1.0), self.range(-1.0, 1.0));
+                let len = v.length();
+                if len > 0.1 && len <= 1.0 {
+                    return v / len;
+                }
+            }
+        }
+    }
+
+    fn random_volume(rng: &mut Rng, density: f32) -> Vec<u32> {
+        (0..SIZE * SIZE * SIZE).map(|_| (rng.next_f32() < density) as u32).collect()
+    }
+
+    fn sampler(volume: &[u32]) -> impl Fn(IVec3) -> u32 + '_ {
+        move |cell: IVec3| {
+            assert!(cell.cmpge(IVec3::ZERO).all() && cell.cmplt(IVec3::splat(SIZE)).all(), "Sampled out of bounds: {cell}");
+            volume[(cell.y * SIZE * SIZE + cell.z * SIZE + cell.x) as usize]
+        }
+    }
+
+    /// Steps along the ray in tiny increments, returning the distance at which it first enters a solid cell.
+    fn brute_force(ray: Ray3, max_distance: f32, volume: &[u32]) -> Option<(IVec3, f32)> {
+        const STEP: f32 = 1e-3;
+        let sample = sampler(volume);
+        let mut t = 0.0;
+        while t < max_distance {
+            let cell = ray.point_on_ray(t).floor().as_ivec3();
+            if cell.cmpge(IVec3::ZERO).all() && cell.cmplt(IVec3::splat(SIZE)).all() && sample(cell) != 0 {
+                return Some((cell, t));
+            }
+            t += STEP;
+        }
+        None
+    }
+
+    #[test]
+    fn parity_test() {
+        const TOLERANCE: f32 = 5e-3;
+        let mut rng = Rng(0x9E3779B97F4A7C15);
+        let volume = random_volume(&mut rng, 0.02);
+        let sample = sampler(&volume);
+        let mut mismatches = 0;
+        const RAYS: usize = 2000;
+        for i in 0..RAYS {
+            // Half of the rays start outside of the volume.
+            let pos = if i % 2 == 0 {
+                vec3a(rng.range(-8.0, 24.0), rng.range(-8.0, 24.0), rng.range(-8.0, 24.0))
+            } else {
+                vec3a(rng.range(0.0, 16.0), rng.range(0.0, 16.0), rng.range(0.0, 16.0))
+            };
+            let ray = Ray3::new(pos, rng.direction());
+            let dda = raycast(ray, IVec3::ZERO, IVec3::splat(SIZE), 64.0, &sample);
+            let brute = brute_force(ray, 64.0, &volume);
+            match (&dda, brute) {
+                (Some(hit), Some((cell, distance))) => {
+                    assert_ne!(sample(hit.coord), 0);
+                    // Brute force can step over the corner of a cell, but it can never find a hit before the DDA does.
+                    assert!(hit.distance <= distance + TOLERANCE, "{ray:?}: {hit:?} vs {cell} at {distance}");
+                    if hit.coord != cell {
+                        mismatches += 1;
+                    }
+                }
+                (None, None) => (),
+                // The ray only grazed a cell.
+                (Some(_), None) => mismatches += 1,
+                (None, Some((cell, distance))) => panic!("{ray:?}: DDA missed {cell} at {distance}"),
+            }
+        }
+        assert!(mismatches < RAYS / 100, "Too many mismatches: {mismatches}");
+    }
+
+    #[test]
+    fn entry_face_test() {
+        let volume = vec![1u32; (SIZE * SIZE * SIZE) as usize];
+        let sample = sampler(&volume);
+        let cases = [
+            (vec3a(-4.0, 8.5, 8.5), Vec3A::X, Face::NegX, ivec3(0, 8, 8)),
+            (vec3a(20.0, 8.5, 8.5), Vec3A::NEG_X, Face::PosX, ivec3(15, 8, 8)),
+            (vec3a(8.5, -4.0, 8.5), Vec3A::Y, Face::NegY, ivec3(8, 0, 8)),
+            (vec3a(8.5, 20.0, 8.5), Vec3A::NEG_Y, Face::PosY, ivec3(8, 15, 8)),
+            (vec3a(8.5, 8.5, -4.0), Vec3A::Z, Face::NegZ, ivec3(8, 8, 0)),
+            (vec3a(8.5, 8.5, 20.0), Vec3A::NEG_Z, Face::PosZ, ivec3(8, 8, 15)),
+        ];
+        for (pos, dir, face, coord) in cases {
+            let hit = raycast(Ray3::new(pos, dir), IVec3::ZERO, IVec3::splat(SIZE), 64.0, &sample).unwrap();
+            assert_eq!(hit.face, Some(face));
+            assert_eq!(hit.coord, coord);
+            assert!((hit.distance - 4.0).abs() < 1e-3);
+        }
+        // Starting inside a solid cell hits it with no face.
+        let hit = raycast(Ray3::new(vec3a(4.5, 4.5, 4.5), Vec3A::X), IVec3::ZERO, IVec3::splat(SIZE), 64.0, &sample).unwrap();
+        assert_eq!(hit.face, None);
+        assert_eq!(hit.coord, ivec3(4, 4, 4));
+    }
+
+    #[test]
+    fn miss_test() {
+        let volume = vec![1u32; (SIZE * SIZE * SIZE) as usize];
+        let sample = sampler(&volume);
+        let bounds = (IVec3::ZERO, IVec3::splat(SIZE));
+        // Pointing away.
+        assert!(raycast(Ray3::new(vec3a(-1.0, 8.0, 8.0), Vec3A::NEG_X), bounds.0, bounds.1, 64.0, &sample).is_none());
+        // Passing beside.
+        assert!(raycast(Ray3::new(vec3a(-1.0, 17.0, 8.0), Vec3A::X), bounds.0, bounds.1, 64.0, &sample).is_none());
+        // Out of range.
+        assert!(raycast(Ray3::new(vec3a(-10.0, 8.0, 8.0), Vec3A::X), bounds.0, bounds.1, 5.0, &sample).is_none());
+        // Offset bounds.
+        let hit = raycast(Ray3::new(vec3a(-20.0, 8.5, 8.5), Vec3A::X), ivec3(-16, 0, 0), ivec3(0, 16, 16), 64.0, |cell| {
+            assert!(cell.x < 0);
+            (cell.x == -8) as u32
+        }).unwrap();
+        assert_eq!(hit.coord, ivec3(-8, 8, 8));
+        assert_eq!(hit.face, Some(Face::NegX));
+    }
+
+    #[test]
+    fn line_of_sight_test() {
+        let mut volume = vec![0u32; (SIZE * SIZE * SIZE) as usize];
+        let sample = |cell: IVec3| volume[(cell.y * SIZE * SIZE + cell.z * SIZE + cell.x) as usize];
+        let bounds = (IVec3::ZERO, IVec3::splat(SIZE));
+        assert!(line_of_sight(vec3a(1.5, 1.5, 1.5), vec3a(14.5, 1.5, 1.5), bounds.0, bounds.1, sample));
+        volume[(SIZE * SIZE + SIZE + 8) as usize] = 1;
+        let sample = |cell: IVec3| volume[(cell.y * SIZE * SIZE + cell.z * SIZE + cell.x) as usize];
+        assert!(!line_of_sight(vec3a(1.5, 1.5, 1.5), vec3a(14.5, 1.5, 1.5), bounds.0, bounds.1, sample));
+        // Stopping short of the blocker.
+        assert!(line_of_sight(vec3a(1.5, 1.5, 1.5), vec3a(7.5, 1.5, 1.5), bounds.0, bounds.1, sample));
+    }
+}
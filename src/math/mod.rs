@@ -1,6 +1,8 @@
 pub mod transform;
 pub mod ray;
 pub mod average;
+pub mod dda;
+pub mod morton;
 
 #[inline(always)]
 pub const fn morton6(index: u32) -> u32 {
@@ -0,0 +1,122 @@
+/*
+3D Morton (Z-order) codes. The bits of X, Y and Z are interleaved (X in the lowest bit, then Y, then Z),
+so cells that are close together in space are usually close together in memory.
+
+The `u32` versions take 10 bits per axis, the `u64` versions take 21. Higher bits are ignored.
+*/
+
+/// Spreads the lower 10 bits out so that there are two zero bits between each of them.
+#[inline(always)]
+const fn split3(value: u32) -> u32 {
+    let mut x = value & 0x3ff;
+    x = (x | (x << 16)) & 0x030000ff;
+    x = (x | (x << 8)) & 0x0300f00f;
+    x = (x | (x << 4)) & 0x030c30c3;
+    x = (x | (x << 2)) & 0x09249249;
+    x
+}
+
+/// The inverse of [split3].
+#[inline(always)]
+const fn compact3(value: u32) -> u32 {
+    let mut x = value & 0x09249249;
+    x = (x | (x >> 2)) & 0x030c30c3;
+    x = (x | (x >> 4)) & 0x0300f00f;
+    x = (x | (x >> 8)) & 0x030000ff;
+    x = (x | (x >> 16)) & 0x3ff;
+    x
+}
+
+#[inline(always)]
+const fn split3_u64(value: u64) -> u64 {
+    let mut x = value & 0x1fffff;
+    x = (x | (x << 32)) & 0x001f00000000ffff;
+    x = (x | (x << 16)) & 0x001f0000ff0000ff;
+    x = (x | (x << 8)) & 0x100f00f00f00f00f;
+    x = (x | (x << 4)) & 0x10c30c30c30c30c3;
+    x = (x | (x << 2)) & 0x1249249249249249;
+    x
+}
+
+#[inline(always)]
+const fn compact3_u64(value: u64) -> u64 {
+    let mut x = value & 0x1249249249249249;
+    x = (x | (x >> 2)) & 0x10c30c30c30c30c3;
+    x = (x | (x >> 4)) & 0x100f00f00f00f00f;
+    x = (x | (x >> 8)) & 0x001f0000ff0000ff;
+    x = (x | (x >> 16)) & 0x001f00000000ffff;
+    x = (x | (x >> 32)) & 0x1fffff;
+    x
+}
+
+/// Interleaves the lower 10 bits of each axis into a 30 bit code.
+#[inline(always)]
+pub const fn morton3_encode(x: u32, y: u32, z: u32) -> u32 {
+    split3(x) | (split3(y) << 1) | (split3(z) << 2)
+}
+
+/// The inverse of [morton3_encode], as `(x, y, z)`.
+#[inline(always)]
+pub const fn morton3_decode(code: u32) -> (u32, u32, u32) {
+    (compact3(code), compact3(code >> 1), compact3(code >> 2))
+}
+
+/// Interleaves the lower 21 bits of each axis into a 63 bit code.
+#[inline(always)]
+pub const fn morton3_encode_u64(x: u64, y: u64, z: u64) -> u64 {
+    split3_u64(x) | (split3_u64(y) << 1) | (split3_u64(z) << 2)
+}
+
+/// The inverse of [morton3_encode_u64], as `(x, y, z)`.
+#[inline(always)]
+pub const fn morton3_decode_u64(code: u64) -> (u64, u64, u64) {
+    (compact3_u64(code), compact3_u64(code >> 1), compact3_u64(code >> 2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Interleaves one bit at a time.
+    fn reference(x: u64, y: u64, z: u64, bits: u32) -> u64 {
+        (0..bits).fold(0, |code, bit| {
+            code | ((x >> bit) & 1) << (bit * 3)
+                | ((y >> bit) & 1) << (bit * 3 + 1)
+                | ((z >> bit) & 1) << (bit * 3 + 2)
+        })
+    }
+
+    #[test]
+    fn morton3_test() {
+        assert_eq!(morton3_encode(1, 0, 0), 1);
+        assert_eq!(morton3_encode(0, 1, 0), 2);
+        assert_eq!(morton3_encode(0, 0, 1), 4);
+        assert_eq!(morton3_encode(1023, 1023, 1023), (1 << 30) - 1);
+        // Bits past the 10th are ignored.
+        assert_eq!(morton3_encode(1024 | 5, 0, 0), morton3_encode(5, 0, 0));
+        assert_eq!(morton3_encode_u64(0x1fffff, 0x1fffff, 0x1fffff), (1 << 63) - 1);
+        for z in 0..64 {
+            for y in 0..64 {
+                for x in 0..64 {
+                    assert_eq!(morton3_encode(x, y, z), super::super::morton6_3(x, y, z));
+                }
+            }
+        }
+
+        let mut state = 0x2545f4914f6cdd1du64;
+        for _ in 0..10000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let (x, y, z) = (state & 0x3ff, (state >> 10) & 0x3ff, (state >> 20) & 0x3ff);
+            let code = morton3_encode(x as u32, y as u32, z as u32);
+            assert_eq!(code as u64, reference(x, y, z, 10));
+            assert_eq!(morton3_decode(code), (x as u32, y as u32, z as u32));
+
+            let (x, y, z) = (state & 0x1fffff, (state >> 21) & 0x1fffff, (state >> 42) & 0x1fffff);
+            let code = morton3_encode_u64(x, y, z);
+            assert_eq!(code, reference(x, y, z, 21));
+            assert_eq!(morton3_decode_u64(code), (x, y, z));
+        }
+    }
+}
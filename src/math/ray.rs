@@ -35,4 +35,142 @@ impl Ray3 {
     pub fn point_on_ray(&self, t: f32) -> Vec3A {
         (self.dir * t) + self.pos
     }
-}
\ No newline at end of file
+
+    /// Slab test against the axis-aligned box `min..max`.
+    ///
+    /// Returns the distances along the ray at which it enters and exits the box, or [None] if
+    /// the ray misses the box or the box is behind the ray. If the ray starts inside the box,
+    /// the entry distance is negative.
+    pub fn intersect_aabb(&self, min: Vec3A, max: Vec3A) -> Option<(f32, f32)> {
+        let inv_dir = self.dir.recip();
+        let t0 = (min - self.pos) * inv_dir;
+        let t1 = (max - self.pos) * inv_dir;
+        // NaN (0 * inf) happens when the ray lies exactly on a slab boundary, max_element/min_element ignore NaN.
+        let t_min = t0.min(t1).max_element();
+        let t_max = t0.max(t1).min_element();
+        if t_max < t_min.max(0.0) {
+            None
+        } else {
+            Some((t_min, t_max))
+        }
+    }
+
+    /// Returns the distance along the ray to the plane through `point` with the given `normal`.
+    /// Returns [None] if the ray is parallel to the plane or the plane is behind the ray.
+    pub fn intersect_plane(&self, point: Vec3A, normal: Vec3A) -> Option<f32> {
+        let denom = normal.dot(self.dir);
+        if denom.abs() <= f32::EPSILON {
+            return None;
+        }
+        let t = normal.dot(point - self.pos) / denom;
+        if t >= 0.0 {
+            Some(t)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the distances along the ray at which it enters and exits the sphere.
+    /// Like [Ray3::intersect_aabb], the entry distance is negative if the ray starts inside the sphere.
+    pub fn intersect_sphere(&self, center: Vec3A, radius: f32) -> Option<(f32, f32)> {
+        let offset = self.pos - center;
+        let a = self.dir.length_squared();
+        let half_b = offset.dot(self.dir);
+        let c = offset.length_squared() - radius * radius;
+        let discriminant = half_b * half_b - a * c;
+        if discriminant < 0.0 || a == 0.0 {
+            return None;
+        }
+        let sqrt_d = discriminant.sqrt();
+        let t0 = (-half_b - sqrt_d) / a;
+        let t1 = (-half_b + sqrt_d) / a;
+        if t1 < 0.0 {
+            None
+        } else {
+            Some((t0, t1))
+        }
+    }
+
+    /// Transforms the ray by `transform`.
+    ///
+    /// The direction is not normalized, so distances along the transformed ray match distances
+    /// along the original ray. This is what you want when transforming a ray into an object's
+    /// local space (with the inverse of its transform) for intersection tests.
+    pub fn transform(&self, transform: Mat4) -> Self {
+        Self {
+            pos: transform.transform_point3a(self.pos),
+            dir: transform.transform_vector3a(self.dir),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn aabb_test() {
+        let ray = Ray3::new(vec3a(-5.0, 0.5, 0.5), Vec3A::X);
+        let (t_min, t_max) = ray.intersect_aabb(Vec3A::ZERO, Vec3A::ONE).unwrap();
+        assert!(approx(t_min, 5.0) && approx(t_max, 6.0));
+        // Inside
+        let ray = Ray3::new(vec3a(0.5, 0.5, 0.5), Vec3A::Y);
+        let (t_min, t_max) = ray.intersect_aabb(Vec3A::ZERO, Vec3A::ONE).unwrap();
+        assert!(t_min < 0.0 && approx(t_max, 0.5));
+        // Behind
+        let ray = Ray3::new(vec3a(-5.0, 0.5, 0.5), Vec3A::NEG_X);
+        assert!(ray.intersect_aabb(Vec3A::ZERO, Vec3A::ONE).is_none());
+        // Beside
+        let ray = Ray3::new(vec3a(-5.0, 1.5, 0.5), Vec3A::X);
+        assert!(ray.intersect_aabb(Vec3A::ZERO, Vec3A::ONE).is_none());
+        // Diagonal
+        let ray = Ray3::from_target(vec3a(-1.0, -1.0, -1.0), Vec3A::ZERO);
+        let (t_min, _) = ray.intersect_aabb(Vec3A::ZERO, Vec3A::ONE).unwrap();
+        assert!(approx(t_min, 3f32.sqrt()));
+    }
+
+    #[test]
+    fn plane_test() {
+        let ray = Ray3::new(vec3a(0.0, 10.0, 0.0), Vec3A::NEG_Y);
+        assert!(approx(ray.intersect_plane(Vec3A::ZERO, Vec3A::Y).unwrap(), 10.0));
+        // The normal's direction doesn't matter.
+        assert!(approx(ray.intersect_plane(Vec3A::ZERO, Vec3A::NEG_Y).unwrap(), 10.0));
+        // Parallel
+        let ray = Ray3::new(vec3a(0.0, 10.0, 0.0), Vec3A::X);
+        assert!(ray.intersect_plane(Vec3A::ZERO, Vec3A::Y).is_none());
+        // Behind
+        let ray = Ray3::new(vec3a(0.0, 10.0, 0.0), Vec3A::Y);
+        assert!(ray.intersect_plane(Vec3A::ZERO, Vec3A::Y).is_none());
+    }
+
+    #[test]
+    fn sphere_test() {
+        let ray = Ray3::new(vec3a(0.0, 0.0, -10.0), Vec3A::Z);
+        let (t0, t1) = ray.intersect_sphere(Vec3A::ZERO, 2.0).unwrap();
+        assert!(approx(t0, 8.0) && approx(t1, 12.0));
+        let ray = Ray3::new(Vec3A::ZERO, Vec3A::Z);
+        let (t0, t1) = ray.intersect_sphere(Vec3A::ZERO, 2.0).unwrap();
+        assert!(approx(t0, -2.0) && approx(t1, 2.0));
+        let ray = Ray3::new(vec3a(0.0, 3.0, -10.0), Vec3A::Z);
+        assert!(ray.intersect_sphere(Vec3A::ZERO, 2.0).is_none());
+        let ray = Ray3::new(vec3a(0.0, 0.0, 10.0), Vec3A::Z);
+        assert!(ray.intersect_sphere(Vec3A::ZERO, 2.0).is_none());
+    }
+
+    #[test]
+    fn transform_test() {
+        let model = Mat4::from_scale_rotation_translation(Vec3::splat(2.0), Quat::from_rotation_y(1.0), vec3(5.0, 1.0, 3.0));
+        // Intersecting in local space gives the same distances as in world space.
+        let ray = Ray3::new(vec3a(5.0, 1.0, -10.0), Vec3A::Z);
+        let local = ray.transform(model.inverse());
+        let (local_t, _) = local.intersect_sphere(Vec3A::ZERO, 1.0).unwrap();
+        let (world_t, _) = ray.intersect_sphere(vec3a(5.0, 1.0, 3.0), 2.0).unwrap();
+        assert!((local_t - world_t).abs() < 1e-4);
+        let point = model.transform_point3a(local.point_on_ray(local_t));
+        assert!(point.distance(ray.point_on_ray(world_t)) < 1e-4);
+    }
+}
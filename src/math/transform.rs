@@ -1,7 +1,275 @@
 use glam::*;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Transform {
     pub translation: Vec3,
-    pub rotation: Vec3,
+    pub rotation: Quat,
     pub scale: Vec3,
-}
\ No newline at end of file
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    pub const fn new(translation: Vec3, rotation: Quat, scale: Vec3) -> Self {
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub const fn from_translation(translation: Vec3) -> Self {
+        Self {
+            translation,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub const fn from_rotation(rotation: Quat) -> Self {
+        Self {
+            rotation,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub const fn from_scale(scale: Vec3) -> Self {
+        Self {
+            scale,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Decomposes an affine matrix. Shear is lost.
+    pub fn from_mat4(matrix: Mat4) -> Self {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn to_mat4(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        self.translation + self.rotation * (self.scale * point)
+    }
+
+    pub fn transform_vector(&self, vector: Vec3) -> Vec3 {
+        self.rotation * (self.scale * vector)
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        self.rotation * Vec3::NEG_Z
+    }
+
+    pub fn right(&self) -> Vec3 {
+        self.rotation * Vec3::X
+    }
+
+    pub fn up(&self) -> Vec3 {
+        self.rotation * Vec3::Y
+    }
+
+    /// Applies `child` in the space of `self` (`self * child`).
+    /// Non-uniform scale combined with rotation can't be represented exactly, use [Transform::to_mat4] for that.
+    pub fn mul_transform(&self, child: &Transform) -> Self {
+        Self {
+            translation: self.transform_point(child.translation),
+            rotation: self.rotation * child.rotation,
+            scale: self.scale * child.scale,
+        }
+    }
+
+    /// Interpolates translation and scale linearly, and rotation with normalized lerp.
+    /// This is cheaper than [Transform::slerp] and fine for small rotations.
+    pub fn lerp(&self, other: &Transform, t: f32) -> Self {
+        Self {
+            translation: self.translation.lerp(other.translation, t),
+            rotation: self.rotation.lerp(other.rotation, t),
+            scale: self.scale.lerp(other.scale, t),
+        }
+    }
+
+    /// Interpolates translation and scale linearly, and rotation with spherical interpolation.
+    pub fn slerp(&self, other: &Transform, t: f32) -> Self {
+        Self {
+            translation: self.translation.lerp(other.translation, t),
+            rotation: self.rotation.slerp(other.rotation, t),
+            scale: self.scale.lerp(other.scale, t),
+        }
+    }
+}
+
+impl From<Transform> for Mat4 {
+    fn from(value: Transform) -> Self {
+        value.to_mat4()
+    }
+}
+
+/// A handle to a node in a [TransformHierarchy].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TransformId(usize);
+
+#[derive(Debug, Clone)]
+struct TransformNode {
+    local: Transform,
+    parent: Option<TransformId>,
+    world: Mat4,
+}
+
+/*
+Nodes are stored flat and refer to their parent by handle. World matrices are cached and
+recalculated for every node in `propagate()`, which should be called once after local transforms
+have been changed for the frame.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct TransformHierarchy {
+    nodes: Vec<TransformNode>,
+    dirty: bool,
+}
+
+impl TransformHierarchy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn insert(&mut self, local: Transform, parent: Option<TransformId>) -> TransformId {
+        if let Some(parent) = parent {
+            assert!(parent.0 < self.nodes.len(), "Invalid parent: {parent:?}");
+        }
+        let id = TransformId(self.nodes.len());
+        self.nodes.push(TransformNode {
+            local,
+            parent,
+            world: local.to_mat4(),
+        });
+        self.dirty = true;
+        id
+    }
+
+    pub fn local(&self, id: TransformId) -> &Transform {
+        &self.nodes[id.0].local
+    }
+
+    pub fn local_mut(&mut self, id: TransformId) -> &mut Transform {
+        self.dirty = true;
+        &mut self.nodes[id.0].local
+    }
+
+    pub fn set_local(&mut self, id: TransformId, local: Transform) {
+        self.nodes[id.0].local = local;
+        self.dirty = true;
+    }
+
+    pub fn parent(&self, id: TransformId) -> Option<TransformId> {
+        self.nodes[id.0].parent
+    }
+
+    /// Sets the parent of `id`. Returns `false` (and leaves the hierarchy unchanged) if that would
+    /// create a cycle.
+    pub fn set_parent(&mut self, id: TransformId, parent: Option<TransformId>) -> bool {
+        let mut ancestor = parent;
+        while let Some(current) = ancestor {
+            if current == id {
+                return false;
+            }
+            ancestor = self.nodes[current.0].parent;
+        }
+        self.nodes[id.0].parent = parent;
+        self.dirty = true;
+        true
+    }
+
+    pub fn children(&self, id: TransformId) -> impl Iterator<Item = TransformId> + '_ {
+        self.nodes.iter().enumerate()
+            .filter(move |(_, node)| node.parent == Some(id))
+            .map(|(index, _)| TransformId(index))
+    }
+
+    /// The world matrix as of the last call to [TransformHierarchy::propagate].
+    pub fn world_matrix(&self, id: TransformId) -> Mat4 {
+        self.nodes[id.0].world
+    }
+
+    /// Recalculates the world matrices of every node.
+    pub fn propagate(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        let mut done = vec![false; self.nodes.len()];
+        for index in 0..self.nodes.len() {
+            self.propagate_node(index, &mut done);
+        }
+        self.dirty = false;
+    }
+
+    fn propagate_node(&mut self, index: usize, done: &mut [bool]) -> Mat4 {
+        if done[index] {
+            return self.nodes[index].world;
+        }
+        let local = self.nodes[index].local.to_mat4();
+        let world = match self.nodes[index].parent {
+            Some(parent) => self.propagate_node(parent.0, done) * local,
+            None => local,
+        };
+        self.nodes[index].world = world;
+        done[index] = true;
+        world
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transform_test() {
+        let transform = Transform::new(vec3(1.0, 2.0, 3.0), Quat::from_rotation_y(1.0), vec3(2.0, 2.0, 2.0));
+        let point = vec3(0.5, -1.0, 4.0);
+        assert!(transform.transform_point(point).abs_diff_eq(transform.to_mat4().transform_point3(point), 1e-5));
+        let decomposed = Transform::from_mat4(transform.to_mat4());
+        assert!(decomposed.translation.abs_diff_eq(transform.translation, 1e-5));
+        assert!(decomposed.scale.abs_diff_eq(transform.scale, 1e-5));
+        let halfway = Transform::IDENTITY.slerp(&transform, 0.5);
+        assert!(halfway.rotation.abs_diff_eq(Quat::from_rotation_y(0.5), 1e-5));
+        assert!(halfway.translation.abs_diff_eq(vec3(0.5, 1.0, 1.5), 1e-5));
+    }
+
+    #[test]
+    fn hierarchy_test() {
+        let mut hierarchy = TransformHierarchy::new();
+        let root = hierarchy.insert(Transform::from_translation(vec3(10.0, 0.0, 0.0)), None);
+        let child = hierarchy.insert(Transform::from_translation(vec3(0.0, 5.0, 0.0)), Some(root));
+        let grandchild = hierarchy.insert(Transform::from_scale(Vec3::splat(2.0)), Some(child));
+        hierarchy.propagate();
+        assert!(hierarchy.world_matrix(grandchild).transform_point3(Vec3::ONE).abs_diff_eq(vec3(12.0, 7.0, 2.0), 1e-5));
+        hierarchy.local_mut(root).translation = Vec3::ZERO;
+        hierarchy.propagate();
+        assert!(hierarchy.world_matrix(child).transform_point3(Vec3::ZERO).abs_diff_eq(vec3(0.0, 5.0, 0.0), 1e-5));
+        assert_eq!(hierarchy.children(root).collect::<Vec<_>>(), vec![child]);
+        // Cycles are rejected.
+        assert!(!hierarchy.set_parent(root, Some(grandchild)));
+        assert!(hierarchy.set_parent(grandchild, None));
+    }
+}
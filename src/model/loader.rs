@@ -0,0 +1,265 @@
+use std::{collections::HashMap, path::Path};
+
+use glam::*;
+use wgpu::util::DeviceExt;
+
+use crate::voxel::vertex::Vertex;
+
+/*
+Wavefront OBJ loading into the same vertex format that the Modeler produces.
+Supported:
+- v, vt, f (v, v/vt, v//vn, v/vt/vn, negative indices, polygons are fan triangulated)
+- usemtl (mapped to texture array layers with LoadOptions::material_layers)
+Everything else (normals, groups, smoothing, mtllib) is ignored.
+glTF is not supported yet.
+*/
+
+#[derive(Debug, thiserror::Error)]
+pub enum LoadError {
+    #[error("Failed to read file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Line {line}: {message}")]
+    Parse {
+        line: usize,
+        message: String,
+    },
+    #[error("Unsupported file extension: {0:?}")]
+    UnsupportedFormat(Option<String>),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    /// Maps material names (from `usemtl`) to texture array layers.
+    pub material_layers: HashMap<String, u32>,
+    /// The texture array layer for faces with no material or an unmapped material.
+    pub default_layer: u32,
+    /// OBJ texture coordinates have V pointing up, while wgpu has V pointing down.
+    pub flip_v: bool,
+}
+
+impl LoadOptions {
+    pub fn new() -> Self {
+        Self {
+            flip_v: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_material_layer<S: Into<String>>(mut self, material: S, layer: u32) -> Self {
+        self.material_layers.insert(material.into(), layer);
+        self
+    }
+
+    /// Maps every name in `layers` (such as [crate::rendering::texture_array::TextureArray::layers])
+    /// to its layer, so materials can be named after atlas tiles.
+    pub fn with_material_layers(mut self, layers: &HashMap<String, u32>) -> Self {
+        self.material_layers.extend(layers.iter().map(|(name, &layer)| (name.clone(), layer)));
+        self
+    }
+
+    pub fn with_default_layer(mut self, layer: u32) -> Self {
+        self.default_layer = layer;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+pub struct MeshBuffers {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub num_indices: u32,
+}
+
+impl MeshData {
+    pub fn create_buffers(&self, device: &wgpu::Device) -> MeshBuffers {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mesh Vertex Buffer"),
+            contents: bytemuck::cast_slice(self.vertices.as_slice()),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mesh Index Buffer"),
+            contents: bytemuck::cast_slice(self.indices.as_slice()),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        MeshBuffers {
+            vertex_buffer,
+            index_buffer,
+            num_indices: self.indices.len() as u32,
+        }
+    }
+}
+
+/// Loads a mesh, choosing the format from the file extension.
+pub fn load_mesh<P: AsRef<Path>>(path: P, options: &LoadOptions) -> Result<MeshData, LoadError> {
+    let path = path.as_ref();
+    let extension = path.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some("obj") => load_obj(path, options),
+        _ => Err(LoadError::UnsupportedFormat(extension)),
+    }
+}
+
+pub fn load_obj<P: AsRef<Path>>(path: P, options: &LoadOptions) -> Result<MeshData, LoadError> {
+    let source = std::fs::read_to_string(path)?;
+    parse_obj(&source, options)
+}
+
+pub fn parse_obj(source: &str, options: &LoadOptions) -> Result<MeshData, LoadError> {
+    let mut positions: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<Vec2> = Vec::new();
+    let mut mesh = MeshData::default();
+    // (position, uv, layer) -> vertex index
+    let mut vertex_lookup: HashMap<(usize, Option<usize>, u32), u32> = HashMap::new();
+    let mut layer = options.default_layer;
+    let mut face: Vec<u32> = Vec::new();
+    for (line_index, line) in source.lines().enumerate() {
+        let line_number = line_index + 1;
+        let error = |message: String| LoadError::Parse {
+            line: line_number,
+            message,
+        };
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        let mut parts = line.split_whitespace();
+        let Some(keyword) = parts.next() else {
+            continue;
+        };
+        match keyword {
+            "v" => {
+                let [x, y, z] = parse_floats::<3>(&mut parts).map_err(error)?;
+                positions.push(vec3(x, y, z));
+            }
+            "vt" => {
+                let [u, v] = parse_floats::<2>(&mut parts).map_err(error)?;
+                let v = if options.flip_v { 1.0 - v } else { v };
+                uvs.push(vec2(u, v));
+            }
+            "usemtl" => {
+                let name = parts.next().unwrap_or_default();
+                layer = options.material_layers.get(name).copied().unwrap_or(options.default_layer);
+            }
+            "f" => {
+                face.clear();
+                for corner in parts {
+                    let mut indices = corner.split('/');
+                    let position = indices.next()
+                        .ok_or_else(|| error(format!("Invalid face corner: {corner:?}")))
+                        .and_then(|index| resolve_index(index, positions.len()).map_err(error))?;
+                    let uv = match indices.next() {
+                        Some(index) if !index.is_empty() => Some(resolve_index(index, uvs.len()).map_err(error)?),
+                        _ => None,
+                    };
+                    let key = (position, uv, layer);
+                    let vertex_index = match vertex_lookup.get(&key) {
+                        Some(&index) => index,
+                        None => {
+                            let index = mesh.vertices.len() as u32;
+                            mesh.vertices.push(Vertex::new(
+                                positions[position],
+                                uv.map(|uv| uvs[uv]).unwrap_or(Vec2::ZERO),
+                                layer,
+                            ));
+                            vertex_lookup.insert(key, index);
+                            index
+                        }
+                    };
+                    face.push(vertex_index);
+                }
+                if face.len() < 3 {
+                    return Err(error(format!("Face has {} vertices, expected at least 3.", face.len())));
+                }
+                // Fan triangulation
+                for i in 1..face.len() - 1 {
+                    mesh.indices.extend_from_slice(&[face[0], face[i], face[i + 1]]);
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(mesh)
+}
+
+fn parse_floats<'a, const N: usize>(parts: &mut impl Iterator<Item = &'a str>) -> Result<[f32; N], String> {
+    let mut values = [0.0; N];
+    for value in values.iter_mut() {
+        let part = parts.next().ok_or_else(|| format!("Expected {N} values."))?;
+        *value = part.parse().map_err(|_| format!("Invalid number: {part:?}"))?;
+    }
+    Ok(values)
+}
+
+/// Converts a 1-based (or negative, relative to the end) OBJ index into a 0-based index.
+fn resolve_index(index: &str, len: usize) -> Result<usize, String> {
+    let value: i64 = index.parse().map_err(|_| format!("Invalid index: {index:?}"))?;
+    let resolved = if value < 0 {
+        len as i64 + value
+    } else {
+        value - 1
+    };
+    if resolved < 0 || resolved >= len as i64 {
+        return Err(format!("Index out of range: {value}"));
+    }
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUAD: &str = "
+# A unit quad
+v 0 0 0
+v 1 0 0
+v 1 0 1
+v 0 0 1
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+usemtl dirt
+f 1/1 2/2 3/3 4/4
+";
+
+    #[test]
+    fn quad_test() {
+        let options = LoadOptions::new().with_material_layer("dirt", 3);
+        let mesh = parse_obj(QUAD, &options).unwrap();
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+        assert!(mesh.vertices.iter().all(|vertex| vertex.texindex == 3));
+        // V is flipped.
+        assert_eq!(mesh.vertices[0].uv, vec2(0.0, 1.0));
+    }
+
+    #[test]
+    fn shared_vertices_test() {
+        let source = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+f 1//1 2//1 3//1
+f -4 -2 -1
+";
+        let mesh = parse_obj(source, &LoadOptions::default()).unwrap();
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn error_test() {
+        let result = parse_obj("v 0 0 0\nf 1 2 3", &LoadOptions::default());
+        assert!(matches!(result, Err(LoadError::Parse { line: 2, .. })));
+        let result = parse_obj("v 0 zero 0", &LoadOptions::default());
+        assert!(matches!(result, Err(LoadError::Parse { line: 1, .. })));
+        assert!(matches!(load_mesh("model.fbx", &LoadOptions::default()), Err(LoadError::UnsupportedFormat(_))));
+    }
+}
@@ -1 +1,2 @@
-pub mod modeler;
\ No newline at end of file
+pub mod modeler;pub mod primitives;
+pub mod text3d;
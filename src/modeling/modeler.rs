@@ -2,6 +2,59 @@ use glam::*;
 
 use crate::voxel::vertex::Vertex;
 
+/*
+Vertices without a normal get the normal of the face they are pushed with, which points to the side that
+the indices wind counter-clockwise on (the front face of the pipelines). Vertices with a normal keep it,
+transformed along with the position, and colors are kept as they are.
+
+Indices are always `u32` ([Modeler::INDEX_FORMAT]) and count from the first vertex of the model, so
+[Modeler::vertices] and [Modeler::indices] can be drawn as they are.
+
+A model can be split into batches of at most [Modeler::batch_vertex_limit] vertices, for index buffers
+with a smaller index type (see [Modeler::with_u16_indices]) or vertex buffers that would be larger than
+the device allows. A primitive never straddles two batches: when it doesn't fit, a new batch starts.
+*/
+
+/// The most vertices that a batch can have with `u16` indices.
+pub const U16_VERTEX_LIMIT: u32 = u16::MAX as u32 + 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ModelerError {
+    #[error("A primitive with {vertices} vertices doesn't fit in a batch of {limit} vertices.")]
+    PrimitiveTooLarge { vertices: u32, limit: u32 },
+    #[error("The model has too many vertices for u32 indices.")]
+    TooManyVertices,
+    #[error("Index {index} is out of range for a mesh with {vertices} vertices.")]
+    IndexOutOfRange { index: u32, vertices: usize },
+    #[error("{indices} indices don't make whole triangles.")]
+    IncompleteTriangle { indices: usize },
+}
+
+/// The vertices and indices of one batch, see [Modeler::batches].
+#[derive(Debug, Clone, Copy)]
+pub struct ModelBatch<'a> {
+    pub vertices: &'a [Vertex],
+    /// Counted from the first vertex of the model, like [Modeler::indices].
+    pub indices: &'a [u32],
+    /// The index of the batch's first vertex in the model.
+    pub first_vertex: u32,
+}
+
+impl ModelBatch<'_> {
+    /// The indices counted from the first vertex of the batch, for when the batch has its own vertex buffer.
+    pub fn local_indices(&self) -> Vec<u32> {
+        self.indices.iter().map(|&index| index - self.first_vertex).collect()
+    }
+
+    /// Like [ModelBatch::local_indices], but as `u16`. Returns `None` if the batch has too many vertices.
+    pub fn local_indices_u16(&self) -> Option<Vec<u16>> {
+        if self.vertices.len() > U16_VERTEX_LIMIT as usize {
+            return None;
+        }
+        Some(self.indices.iter().map(|&index| (index - self.first_vertex) as u16).collect())
+    }
+}
+
 #[derive(Debug, Default, Clone, Copy)]
 pub struct PosUV {
     pub pos: Vec3,
@@ -44,6 +97,9 @@ pub struct Modeler {
     pub transform_stack: Vec<Mat4>,
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
+    batch_vertex_limit: u32,
+    /// The start of every batch after the first, as `(first_vertex, first_index)`.
+    batch_starts: Vec<(u32, u32)>,
 }
 
 pub struct TextureModeler<'a> {
@@ -52,12 +108,11 @@ pub struct TextureModeler<'a> {
 }
 
 impl Modeler {
+    /// The index format of [Modeler::indices], for `set_index_buffer`.
+    pub const INDEX_FORMAT: wgpu::IndexFormat = wgpu::IndexFormat::Uint32;
+
     pub fn new() -> Self {
-        Self {
-            transform_stack: vec![Mat4::IDENTITY],
-            vertices: Vec::new(),
-            indices: Vec::new(),
-        }
+        Self::new_transformed(Mat4::IDENTITY)
     }
 
     pub fn new_transformed(transform: Mat4) -> Self {
@@ -65,9 +120,63 @@ impl Modeler {
             transform_stack: vec![transform],
             vertices: Vec::new(),
             indices: Vec::new(),
+            batch_vertex_limit: u32::MAX,
+            batch_starts: Vec::new(),
         }
     }
 
+    /// Splits the model into batches of at most `limit` vertices (see [Modeler::batches]). Only affects
+    /// the primitives pushed afterwards.
+    pub fn with_batch_vertex_limit(mut self, limit: u32) -> Self {
+        self.batch_vertex_limit = limit;
+        self
+    }
+
+    /// Keeps every batch small enough for `u16` indices, see [ModelBatch::local_indices_u16].
+    pub fn with_u16_indices(self) -> Self {
+        self.with_batch_vertex_limit(U16_VERTEX_LIMIT)
+    }
+
+    pub fn batch_vertex_limit(&self) -> u32 {
+        self.batch_vertex_limit
+    }
+
+    pub fn batch_count(&self) -> usize {
+        self.batch_starts.len() + 1
+    }
+
+    /// The batches in order. There is always at least one, which may be empty.
+    pub fn batches(&self) -> impl Iterator<Item = ModelBatch<'_>> + '_ {
+        let ends = self.batch_starts.iter()
+            .copied()
+            .chain(std::iter::once((self.vertices.len() as u32, self.indices.len() as u32)));
+        std::iter::once((0, 0))
+            .chain(self.batch_starts.iter().copied())
+            .zip(ends)
+            .map(|((first_vertex, first_index), (end_vertex, end_index))| ModelBatch {
+                vertices: &self.vertices[first_vertex as usize..end_vertex as usize],
+                indices: &self.indices[first_index as usize..end_index as usize],
+                first_vertex,
+            })
+    }
+
+    /// Makes room for a primitive of `vertex_count` vertices, starting a new batch if it doesn't fit in
+    /// the current one. Returns the index of the primitive's first vertex.
+    fn reserve(&mut self, vertex_count: u32, index_count: usize) -> Result<u32, ModelerError> {
+        if vertex_count > self.batch_vertex_limit {
+            return Err(ModelerError::PrimitiveTooLarge { vertices: vertex_count, limit: self.batch_vertex_limit });
+        }
+        if self.vertices.len() + vertex_count as usize > u32::MAX as usize || self.indices.len() + index_count > u32::MAX as usize {
+            return Err(ModelerError::TooManyVertices);
+        }
+        let start = self.vertices.len() as u32;
+        let batch_start = self.batch_starts.last().map_or(0, |&(first_vertex, _)| first_vertex);
+        if (start - batch_start) as u64 + vertex_count as u64 > self.batch_vertex_limit as u64 {
+            self.batch_starts.push((start, self.indices.len() as u32));
+        }
+        Ok(start)
+    }
+
     pub fn get_transform(&self) -> Mat4 {
         if self.transform_stack.len() > 0 {
             self.transform_stack[self.transform_stack.len() - 1]
@@ -135,26 +244,100 @@ impl Modeler {
         self
     }
 
-    pub fn push_triangle(&mut self, vertices: &[Vertex; 3]) -> &mut Self {
+    /// Pushes `vertices` at their transformed `positions`. Vertices without a normal get their entry in
+    /// `face_normals`, the others have theirs transformed.
+    fn extend_transformed(&mut self, vertices: &[Vertex], positions: &[Vec3], face_normals: &[Vec3]) {
+        let normal_matrix = Mat3::from_mat4(self.get_transform()).inverse().transpose();
+        self.vertices.extend(vertices.iter().zip(positions).zip(face_normals).map(|((v, &position), face_normal)| {
+            let normal = if v.has_normal() {
+                (normal_matrix * v.normal).normalize_or_zero()
+            } else {
+                face_normal.normalize_or_zero()
+            };
+            Vertex { position, normal, ..*v }
+        }));
+    }
+
+    /// Like [Modeler::push_triangle], but returns an error instead of panicking when the model is full.
+    pub fn try_push_triangle(&mut self, vertices: &[Vertex; 3]) -> Result<&mut Self, ModelerError> {
         const ORDER: [u32; 3] = [0, 2, 1];
-        let start_index = self.vertices.len() as u32;
+        let start_index = self.reserve(3, 3)?;
         let transform = self.get_transform();
-        self.vertices.extend(vertices.map(|v| { Vertex::new(transform.transform_point3(v.position), v.uv, v.texindex) }));
+        let positions = vertices.map(|v| transform.transform_point3(v.position));
+        let normal = (positions[2] - positions[0]).cross(positions[1] - positions[0]);
+        self.extend_transformed(vertices, &positions, &[normal; 3]);
         self.indices.extend(ORDER.map(move |n| start_index + n));
-        self
+        Ok(self)
     }
 
-    pub fn push_quad(&mut self, vertices: &[Vertex; 4]) -> &mut Self {
+    /// Like [Modeler::push_quad], but returns an error instead of panicking when the model is full.
+    pub fn try_push_quad(&mut self, vertices: &[Vertex; 4]) -> Result<&mut Self, ModelerError> {
         /*
         0 1
         2 3
         order: 0 2 1 2 3 1
         */
         const ORDER: [u32; 6] = [0, 2, 1, 2, 3, 1];
-        let start_index = self.vertices.len() as u32;
+        let start_index = self.reserve(4, 6)?;
+        let transform = self.get_transform();
+        let positions = vertices.map(|v| transform.transform_point3(v.position));
+        // The cross product of the diagonals, which is the average normal of both triangles when the quad isn't flat.
+        let normal = (positions[3] - positions[0]).cross(positions[1] - positions[2]);
+        self.extend_transformed(vertices, &positions, &[normal; 4]);
+        self.indices.extend(ORDER.map(move |n| start_index + n));
+        Ok(self)
+    }
+
+    /// Pushes a triangle list. Unlike [Modeler::push_triangle], `indices` are used as they are (counted from
+    /// the first of `vertices`), so front faces are counter-clockwise in index order. Vertices without a
+    /// normal get the average normal of the triangles that use them, weighted by area.
+    pub fn try_push_mesh(&mut self, vertices: &[Vertex], indices: &[u32]) -> Result<&mut Self, ModelerError> {
+        if !indices.len().is_multiple_of(3) {
+            return Err(ModelerError::IncompleteTriangle { indices: indices.len() });
+        }
+        if let Some(&index) = indices.iter().find(|&&index| index as usize >= vertices.len()) {
+            return Err(ModelerError::IndexOutOfRange { index, vertices: vertices.len() });
+        }
+        let vertex_count = u32::try_from(vertices.len()).map_err(|_| ModelerError::TooManyVertices)?;
+        let start_index = self.reserve(vertex_count, indices.len())?;
         let transform = self.get_transform();
-        self.vertices.extend(vertices.map(|v| { Vertex::new(transform.transform_point3(v.position), v.uv, v.texindex) }));
-        self.indices.extend(ORDER.clone().map(move |n| start_index + n));
+        let positions = vertices.iter().map(|v| transform.transform_point3(v.position)).collect::<Vec<_>>();
+        let mut face_normals = vec![Vec3::ZERO; vertices.len()];
+        if vertices.iter().any(|v| !v.has_normal()) {
+            for triangle in indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|corner| positions[triangle[corner] as usize]);
+                let normal = (b - a).cross(c - a);
+                for &index in triangle {
+                    face_normals[index as usize] += normal;
+                }
+            }
+        }
+        self.extend_transformed(vertices, &positions, &face_normals);
+        self.indices.extend(indices.iter().map(|&index| start_index + index));
+        Ok(self)
+    }
+
+    /// Panics if the mesh is invalid or the model can't hold it, see [Modeler::try_push_mesh].
+    pub fn push_mesh(&mut self, vertices: &[Vertex], indices: &[u32]) -> &mut Self {
+        if let Err(err) = self.try_push_mesh(vertices, indices) {
+            panic!("Failed to push mesh: {err}");
+        }
+        self
+    }
+
+    /// Panics if the model can't hold another triangle, see [Modeler::try_push_triangle].
+    pub fn push_triangle(&mut self, vertices: &[Vertex; 3]) -> &mut Self {
+        if let Err(err) = self.try_push_triangle(vertices) {
+            panic!("Failed to push triangle: {err}");
+        }
+        self
+    }
+
+    /// Panics if the model can't hold another quad, see [Modeler::try_push_quad].
+    pub fn push_quad(&mut self, vertices: &[Vertex; 4]) -> &mut Self {
+        if let Err(err) = self.try_push_quad(vertices) {
+            panic!("Failed to push quad: {err}");
+        }
         self
     }
 
@@ -307,6 +490,89 @@ impl<'a> TextureModeler<'a> {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches_test() {
+        // Indices go with Modeler::INDEX_FORMAT.
+        assert_eq!(Modeler::INDEX_FORMAT.byte_size(), std::mem::size_of::<u32>());
+
+        let mut m = Modeler::new();
+        for _ in 0..3 {
+            m.push_unit_quad(0);
+        }
+        assert_eq!(m.batch_count(), 1);
+        let batch = m.batches().next().unwrap();
+        assert_eq!((batch.vertices.len(), batch.indices.len(), batch.first_vertex), (12, 18, 0));
+        assert_eq!(batch.local_indices(), m.indices);
+
+        // 10 vertices per batch fit two quads, the third starts a new batch.
+        let mut m = Modeler::new().with_batch_vertex_limit(10);
+        for _ in 0..5 {
+            m.push_unit_quad(0);
+        }
+        let triangle = [Vertex::new(Vec3::ZERO, Vec2::ZERO, 0); 3];
+        m.push_triangle(&triangle);
+        let batches = m.batches().collect::<Vec<_>>();
+        assert_eq!(batches.iter().map(|batch| batch.vertices.len()).collect::<Vec<_>>(), [8, 8, 7]);
+        assert_eq!(batches[1].first_vertex, 8);
+        // The indices stay absolute, and every batch only uses its own vertices.
+        assert_eq!(&m.indices[..6], &[0, 2, 1, 2, 3, 1]);
+        for batch in &batches {
+            let local = batch.local_indices();
+            assert!(local.iter().all(|&index| (index as usize) < batch.vertices.len()));
+            assert_eq!(batch.local_indices_u16().unwrap(), local.iter().map(|&index| index as u16).collect::<Vec<_>>());
+        }
+        assert_eq!(batches[2].local_indices(), [0, 2, 1, 2, 3, 1, 4, 6, 5]);
+
+        let mut m = Modeler::new().with_batch_vertex_limit(3);
+        assert!(m.try_push_triangle(&triangle).is_ok());
+        assert_eq!(
+            m.try_push_quad(&[Vertex::new(Vec3::ZERO, Vec2::ZERO, 0); 4]).err(),
+            Some(ModelerError::PrimitiveTooLarge { vertices: 4, limit: 3 }),
+        );
+        assert_eq!(m.vertices.len(), 3);
+        assert_eq!(Modeler::new().with_u16_indices().batch_vertex_limit(), 65536);
+    }
+
+    #[test]
+    fn normals_test() {
+        fn assert_near(a: Vec3, b: Vec3) {
+            assert!(a.distance(b) < 1e-5, "{a} != {b}");
+        }
+        // The unit quad faces up, and turns with the transform.
+        let mut m = Modeler::new();
+        m.push_unit_quad(0);
+        m.rotate(Quat::from_rotation_x(std::f32::consts::FRAC_PI_2), |m| {
+            m.push_unit_quad(0);
+        });
+        m.scale(vec3(1.0, 1.0, -1.0), |m| {
+            m.push_unit_quad(0);
+        });
+        assert!(m.vertices[..4].iter().all(|v| v.normal == Vec3::Y));
+        m.vertices[4..8].iter().for_each(|v| assert_near(v.normal, Vec3::Z));
+        // Mirroring flips the winding, so the face turns around.
+        m.vertices[8..].iter().for_each(|v| assert_near(v.normal, Vec3::NEG_Y));
+
+        // The triangle's normal comes from the index order (0, 2, 1).
+        let triangle = [vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0)]
+            .map(|position| Vertex::new(position, Vec2::ZERO, 0).with_color([255, 0, 0, 128]));
+        let mut m = Modeler::new();
+        m.push_triangle(&triangle);
+        assert!(m.vertices.iter().all(|v| v.normal == Vec3::NEG_Z && v.color == [255, 0, 0, 128]));
+
+        // Given normals are kept, and follow non-uniform scales like the surface does.
+        let sloped = Vertex::new(Vec3::ZERO, Vec2::ZERO, 0).with_normal(vec3(1.0, 1.0, 0.0).normalize());
+        let mut m = Modeler::new();
+        m.scale(vec3(2.0, 1.0, 1.0), |m| {
+            m.push_triangle(&[sloped; 3]);
+        });
+        assert_near(m.vertices[0].normal, vec3(1.0, 2.0, 0.0).normalize());
+    }
+}
+
 #[cfg(test)]
 mod testing_sandbox {
     // TODO: Remove this sandbox when it is no longer in use.
@@ -0,0 +1,326 @@
+/*
+Primitive shapes for debug scenes and gizmos. Every shape is centered on the origin (before the
+transform stack is applied), with Y as its axis, and is pushed as a single mesh (see
+[Modeler::try_push_mesh]), so it never straddles two batches.
+
+The round shapes are surfaces of revolution: a profile in the (radius, Y) plane is swept around the Y axis
+(see [Profile]). `segments` is the number of steps around the axis. Their U coordinate goes once around,
+and V goes from the top of the profile to the bottom.
+*/
+
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use glam::*;
+
+use crate::voxel::vertex::Vertex;
+
+use super::modeler::{Modeler, TextureModeler};
+
+/// The fewest segments around the axis that still enclose a volume.
+pub const MIN_SEGMENTS: u32 = 3;
+
+/// One ring of a surface of revolution.
+#[derive(Debug, Clone, Copy)]
+struct ProfilePoint {
+    /// The distance from the axis, and the height.
+    position: Vec2,
+    /// The outward normal in the (radius, Y) plane.
+    normal: Vec2,
+}
+
+/// The rings of a surface of revolution, from the top down. The surface faces the side that the normals
+/// point to, which has to be on the left of the direction that the profile goes in (in the (radius, Y)
+/// plane). Consecutive points may be at the same position to get a hard edge.
+#[derive(Debug, Default)]
+struct Profile {
+    points: Vec<ProfilePoint>,
+    /// The position of every point along the profile (`0..=1`), used for V.
+    lengths: Vec<f32>,
+}
+
+impl Profile {
+    fn push(&mut self, radius: f32, y: f32, normal: Vec2) {
+        self.points.push(ProfilePoint {
+            position: vec2(radius.max(0.0), y),
+            normal,
+        });
+    }
+
+    /// A flat disc at `y`, facing up or down.
+    fn push_disc(&mut self, radius: f32, y: f32, up: bool) {
+        if up {
+            self.push(0.0, y, Vec2::Y);
+            self.push(radius, y, Vec2::Y);
+        } else {
+            self.push(radius, y, Vec2::NEG_Y);
+            self.push(0.0, y, Vec2::NEG_Y);
+        }
+    }
+
+    /// An arc around `center` from `start` to `end` (radians, counter-clockwise from the direction away from
+    /// the axis), with normals pointing away from the center.
+    fn push_arc(&mut self, center: Vec2, radius: f32, start: f32, end: f32, steps: u32) {
+        for step in 0..=steps {
+            let angle = start + (end - start) * step as f32 / steps as f32;
+            let normal = Vec2::from_angle(angle);
+            let position = center + normal * radius;
+            self.push(position.x, position.y, normal);
+        }
+    }
+
+    /// Sweeps the profile around the Y axis.
+    fn revolve(mut self, segments: u32, texture_index: u32) -> (Vec<Vertex>, Vec<u32>) {
+        let segments = segments.max(MIN_SEGMENTS);
+        self.measure();
+        let columns = segments + 1;
+        let mut vertices = Vec::with_capacity(self.points.len() * columns as usize);
+        for (point, &v) in self.points.iter().zip(&self.lengths) {
+            for column in 0..columns {
+                let u = column as f32 / segments as f32;
+                // Around from +X towards +Z, which keeps the faces counter-clockwise from the outside.
+                let (sin, cos) = (u * TAU).sin_cos();
+                let around = vec3(cos, 0.0, sin);
+                let position = around * point.position.x + Vec3::Y * point.position.y;
+                let normal = around * point.normal.x + Vec3::Y * point.normal.y;
+                vertices.push(Vertex::new(position, vec2(u, v), texture_index).with_normal(normal));
+            }
+        }
+        let mut indices = Vec::new();
+        for (ring, pair) in self.points.windows(2).enumerate() {
+            let [upper, lower] = [pair[0], pair[1]];
+            if upper.position == lower.position {
+                continue;
+            }
+            let top = ring as u32 * columns;
+            let bottom = top + columns;
+            for column in 0..segments {
+                let (a, b) = (top + column, top + column + 1);
+                let (c, d) = (bottom + column, bottom + column + 1);
+                // Rings on the axis would make triangles without area.
+                if upper.position.x > 0.0 {
+                    indices.extend_from_slice(&[a, b, c]);
+                }
+                if lower.position.x > 0.0 {
+                    indices.extend_from_slice(&[b, d, c]);
+                }
+            }
+        }
+        (vertices, indices)
+    }
+
+    fn measure(&mut self) {
+        let mut total = 0.0;
+        self.lengths = std::iter::once(0.0)
+            .chain(self.points.windows(2).map(|pair| {
+                total += pair[0].position.distance(pair[1].position);
+                total
+            }))
+            .collect();
+        if total > 0.0 {
+            self.lengths.iter_mut().for_each(|length| *length /= total);
+        }
+    }
+}
+
+/// The outward normal of each face of a cube, and the direction that U goes in.
+const CUBE_FACES: [(Vec3, Vec3); 6] = [
+    (Vec3::X, Vec3::NEG_Z), (Vec3::NEG_X, Vec3::Z),
+    (Vec3::Y, Vec3::X), (Vec3::NEG_Y, Vec3::X),
+    (Vec3::Z, Vec3::X), (Vec3::NEG_Z, Vec3::NEG_X),
+];
+
+impl Modeler {
+    /// A box of `size` with a whole texture on every face. The sides have V pointing down.
+    pub fn push_cube(&mut self, size: Vec3, texture_index: u32) -> &mut Self {
+        let half = size * 0.5;
+        let mut vertices = Vec::with_capacity(24);
+        let mut indices = Vec::with_capacity(36);
+        for (normal, u_axis) in CUBE_FACES {
+            // With V = U x N, the quad winds counter-clockwise when seen from the outside.
+            let v_axis = u_axis.cross(normal);
+            let start = vertices.len() as u32;
+            for corner in [vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(0.0, 1.0), vec2(1.0, 1.0)] {
+                let offset = normal + u_axis * (corner.x * 2.0 - 1.0) + v_axis * (corner.y * 2.0 - 1.0);
+                vertices.push(Vertex::new(offset * half, corner, texture_index).with_normal(normal));
+            }
+            indices.extend([0, 2, 1, 2, 3, 1].map(|index| start + index));
+        }
+        self.push_mesh(&vertices, &indices)
+    }
+
+    /// A sphere with `segments` steps around and half as many from pole to pole.
+    pub fn push_uv_sphere(&mut self, radius: f32, segments: u32, texture_index: u32) -> &mut Self {
+        let rings = (segments / 2).max(2);
+        let mut profile = Profile::default();
+        profile.push_arc(Vec2::ZERO, radius, FRAC_PI_2, -FRAC_PI_2, rings);
+        let (vertices, indices) = profile.revolve(segments, texture_index);
+        self.push_mesh(&vertices, &indices)
+    }
+
+    /// A closed cylinder of `height`, with flat caps.
+    pub fn push_cylinder(&mut self, radius: f32, height: f32, segments: u32, texture_index: u32) -> &mut Self {
+        let half_height = height * 0.5;
+        let mut profile = Profile::default();
+        profile.push_disc(radius, half_height, true);
+        profile.push(radius, half_height, Vec2::X);
+        profile.push(radius, -half_height, Vec2::X);
+        profile.push_disc(radius, -half_height, false);
+        let (vertices, indices) = profile.revolve(segments, texture_index);
+        self.push_mesh(&vertices, &indices)
+    }
+
+    /// A cylinder with half spheres on both ends. `height` includes the ends, and is at least `2 * radius`
+    /// (which is a sphere).
+    pub fn push_capsule(&mut self, radius: f32, height: f32, segments: u32, texture_index: u32) -> &mut Self {
+        let half_length = (height * 0.5 - radius).max(0.0);
+        let rings = (segments / 4).max(1);
+        let mut profile = Profile::default();
+        profile.push_arc(vec2(0.0, half_length), radius, FRAC_PI_2, 0.0, rings);
+        profile.push_arc(vec2(0.0, -half_length), radius, 0.0, -FRAC_PI_2, rings);
+        let (vertices, indices) = profile.revolve(segments, texture_index);
+        self.push_mesh(&vertices, &indices)
+    }
+
+    /// A ring around the Y axis. `radius` is the distance from the center to the middle of the tube, and
+    /// `tube_segments` is the number of steps around the tube.
+    pub fn push_torus(&mut self, radius: f32, tube_radius: f32, segments: u32, tube_segments: u32, texture_index: u32) -> &mut Self {
+        let mut profile = Profile::default();
+        // Around the tube clockwise (in the (radius, Y) plane), starting at the top.
+        profile.push_arc(vec2(radius, 0.0), tube_radius, FRAC_PI_2, FRAC_PI_2 - TAU, tube_segments.max(MIN_SEGMENTS));
+        let (vertices, indices) = profile.revolve(segments, texture_index);
+        self.push_mesh(&vertices, &indices)
+    }
+}
+
+impl TextureModeler<'_> {
+    pub fn push_cube(&mut self, size: Vec3) -> &mut Self {
+        self.modeler.push_cube(size, self.texture_index);
+        self
+    }
+
+    pub fn push_uv_sphere(&mut self, radius: f32, segments: u32) -> &mut Self {
+        self.modeler.push_uv_sphere(radius, segments, self.texture_index);
+        self
+    }
+
+    pub fn push_cylinder(&mut self, radius: f32, height: f32, segments: u32) -> &mut Self {
+        self.modeler.push_cylinder(radius, height, segments, self.texture_index);
+        self
+    }
+
+    pub fn push_capsule(&mut self, radius: f32, height: f32, segments: u32) -> &mut Self {
+        self.modeler.push_capsule(radius, height, segments, self.texture_index);
+        self
+    }
+
+    pub fn push_torus(&mut self, radius: f32, tube_radius: f32, segments: u32, tube_segments: u32) -> &mut Self {
+        self.modeler.push_torus(radius, tube_radius, segments, tube_segments, self.texture_index);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::modeling::modeler::ModelerError;
+
+    use super::*;
+
+    /// Checks that every triangle winds counter-clockwise when seen from where `outward` points (for the
+    /// triangle's center), and that the vertex normals agree.
+    fn assert_outward(modeler: &Modeler, outward: impl Fn(Vec3) -> Vec3) {
+        assert!(!modeler.indices.is_empty());
+        for triangle in modeler.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|corner| modeler.vertices[triangle[corner] as usize]);
+            let face = (b.position - a.position).cross(c.position - a.position);
+            assert!(face.length() > 1e-6, "degenerate triangle {triangle:?}");
+            let center = (a.position + b.position + c.position) / 3.0;
+            assert!(face.dot(outward(center)) > 0.0, "triangle {triangle:?} at {center} faces inwards");
+            for vertex in [a, b, c] {
+                assert!((vertex.normal.length() - 1.0).abs() < 1e-4);
+                assert!(vertex.normal.dot(face) > 0.0, "normal {} disagrees with triangle {triangle:?}", vertex.normal);
+            }
+        }
+    }
+
+    #[test]
+    fn primitives_test() {
+        let from_center = |point: Vec3| point;
+        let mut m = Modeler::new();
+        m.push_cube(vec3(1.0, 2.0, 3.0), 7);
+        assert_eq!((m.vertices.len(), m.indices.len()), (24, 36));
+        assert_outward(&m, from_center);
+        let max = m.vertices.iter().fold(Vec3::ZERO, |max, v| max.max(v.position));
+        assert_eq!(max, vec3(0.5, 1.0, 1.5));
+        assert!(m.vertices.iter().all(|v| v.texindex == 7));
+
+        let mut m = Modeler::new();
+        m.push_uv_sphere(2.0, 16, 0);
+        assert_outward(&m, from_center);
+        assert!(m.vertices.iter().all(|v| (v.position.length() - 2.0).abs() < 1e-5));
+        // Sphere normals point straight out.
+        assert!(m.vertices.iter().all(|v| v.normal.distance(v.position / 2.0) < 1e-5));
+
+        let mut m = Modeler::new();
+        m.push_cylinder(1.0, 3.0, 12, 0);
+        assert_outward(&m, from_center);
+        let (min, max) = m.vertices.iter().fold((Vec3::MAX, Vec3::MIN), |(min, max), v| (min.min(v.position), max.max(v.position)));
+        assert!((min.y + 1.5).abs() < 1e-5 && (max.y - 1.5).abs() < 1e-5);
+        // The caps are flat.
+        assert!(m.vertices.iter().filter(|v| v.normal == Vec3::Y).all(|v| v.position.y == 1.5));
+
+        let mut m = Modeler::new();
+        m.push_capsule(0.5, 3.0, 12, 0);
+        assert_outward(&m, from_center);
+        let top = m.vertices.iter().map(|v| v.position.y).fold(f32::MIN, f32::max);
+        assert!((top - 1.5).abs() < 1e-5);
+
+        let mut m = Modeler::new();
+        m.push_torus(2.0, 0.5, 16, 8, 0);
+        // Away from the middle of the tube.
+        assert_outward(&m, |point| point - vec3(point.x, 0.0, point.z).normalize() * 2.0);
+        assert!(m.vertices.iter().all(|v| {
+            let tube_center = vec3(v.position.x, 0.0, v.position.z).normalize() * 2.0;
+            (v.position.distance(tube_center) - 0.5).abs() < 1e-4
+        }));
+    }
+
+    #[test]
+    fn primitives_transform_test() {
+        // Primitives follow the transform stack and the texture index.
+        let mut m = Modeler::new();
+        m.translate(vec3(10.0, 0.0, 0.0), |m| {
+            m.scale(vec3(1.0, 2.0, 1.0), |m| {
+                m.texture_index(3, |m| {
+                    m.push_uv_sphere(1.0, 8);
+                });
+            });
+        });
+        assert!(m.vertices.iter().all(|v| v.texindex == 3));
+        // The gradient of the stretched sphere.
+        assert_outward(&m, |point| (point - vec3(10.0, 0.0, 0.0)) * vec3(1.0, 0.25, 1.0));
+        // Too few segments are raised to the minimum.
+        let mut m = Modeler::new();
+        m.push_cylinder(1.0, 1.0, 0, 0);
+        assert_outward(&m, |point| point);
+        assert_eq!(m.vertices.len(), 6 * (MIN_SEGMENTS as usize + 1));
+    }
+
+    #[test]
+    fn push_mesh_test() {
+        let mut m = Modeler::new();
+        m.push_unit_quad(0);
+        // Shared vertices get the average normal of their triangles.
+        let vertices = [vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0), vec3(0.0, 0.0, 1.0)]
+            .map(|position| Vertex::new(position, Vec2::ZERO, 0));
+        m.push_mesh(&vertices, &[0, 2, 1, 0, 1, 3]);
+        assert_eq!(&m.indices[6..], &[4, 6, 5, 4, 5, 7]);
+        assert!(m.vertices[4].normal.distance(vec3(0.0, -1.0, -1.0).normalize()) < 1e-5);
+        assert_eq!(m.vertices[6].normal, Vec3::NEG_Z);
+        assert_eq!(
+            m.try_push_mesh(&vertices, &[0, 1, 4]).err(),
+            Some(ModelerError::IndexOutOfRange { index: 4, vertices: 4 }),
+        );
+        assert_eq!(m.try_push_mesh(&vertices, &[0, 1]).err(), Some(ModelerError::IncompleteTriangle { indices: 2 }));
+        assert_eq!(m.vertices.len(), 8);
+    }
+}
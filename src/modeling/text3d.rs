@@ -0,0 +1,231 @@
+/*
+Text in world space, for coordinates, chunk IDs and other debug labels that belong to a place in the scene
+rather than to the 2D overlay.
+
+Text uses a 5x7 bitmap font for printable ASCII, with one texture array layer per character (see
+[font_layer_images]). Each character is a single textured quad, so a line of text is a strip of quads. The
+font layers are found through [FONT_LAYER_NAME], which names the layer of the first character (the space).
+
+Text is laid out in the XY plane of the current transform, with +Y up, and faces +Z. Push it inside
+[billboard] to turn it towards the camera.
+*/
+
+use glam::*;
+use image::{Rgba, RgbaImage};
+
+use crate::voxel::vertex::Vertex;
+
+use super::modeler::Modeler;
+
+/// The name of the first font layer in the texture array (see [crate::rendering::texture_array::TextureArray::layer]).
+pub const FONT_LAYER_NAME: &str = "font";
+/// The character in the first font layer. The font continues in ASCII order.
+pub const FIRST_GLYPH: char = ' ';
+/// The number of font layers, which cover `' '..='~'`.
+pub const GLYPH_COUNT: u32 = 95;
+/// Characters that aren't in the font are drawn as this one.
+pub const REPLACEMENT_GLYPH: char = '?';
+
+/// A layer is 8x8 font pixels. The glyph is in the 5x7 pixels from (1, 0), and the quad covers the 6x8
+/// pixels from (1, 0), so the pixels to the right and below the glyph space out characters and lines.
+const LAYER_PIXELS: u32 = 8;
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+const CELL_OFFSET: u32 = 1;
+const CELL_WIDTH: u32 = 6;
+/// Width of a character relative to the line height.
+pub const CHARACTER_ASPECT: f32 = CELL_WIDTH as f32 / LAYER_PIXELS as f32;
+
+/// The glyphs of `' '..='~'` as 5 columns, with the top row in the lowest bit.
+const FONT: [[u8; 5]; GLYPH_COUNT as usize] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], [0x00, 0x00, 0x5F, 0x00, 0x00], [0x00, 0x07, 0x00, 0x07, 0x00], [0x14, 0x7F, 0x14, 0x7F, 0x14],
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], [0x23, 0x13, 0x08, 0x64, 0x62], [0x36, 0x49, 0x55, 0x22, 0x50], [0x00, 0x05, 0x03, 0x00, 0x00],
+    [0x00, 0x1C, 0x22, 0x41, 0x00], [0x00, 0x41, 0x22, 0x1C, 0x00], [0x14, 0x08, 0x3E, 0x08, 0x14], [0x08, 0x08, 0x3E, 0x08, 0x08],
+    [0x00, 0x50, 0x30, 0x00, 0x00], [0x08, 0x08, 0x08, 0x08, 0x08], [0x00, 0x60, 0x60, 0x00, 0x00], [0x20, 0x10, 0x08, 0x04, 0x02],
+    // 0-9
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], [0x00, 0x42, 0x7F, 0x40, 0x00], [0x42, 0x61, 0x51, 0x49, 0x46], [0x21, 0x41, 0x45, 0x4B, 0x31],
+    [0x18, 0x14, 0x12, 0x7F, 0x10], [0x27, 0x45, 0x45, 0x45, 0x39], [0x3C, 0x4A, 0x49, 0x49, 0x30], [0x01, 0x71, 0x09, 0x05, 0x03],
+    [0x36, 0x49, 0x49, 0x49, 0x36], [0x06, 0x49, 0x49, 0x29, 0x1E],
+    [0x00, 0x36, 0x36, 0x00, 0x00], [0x00, 0x56, 0x36, 0x00, 0x00], [0x08, 0x14, 0x22, 0x41, 0x00], [0x14, 0x14, 0x14, 0x14, 0x14],
+    [0x00, 0x41, 0x22, 0x14, 0x08], [0x02, 0x01, 0x51, 0x09, 0x06], [0x32, 0x49, 0x79, 0x41, 0x3E],
+    // A-Z
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], [0x7F, 0x49, 0x49, 0x49, 0x36], [0x3E, 0x41, 0x41, 0x41, 0x22], [0x7F, 0x41, 0x41, 0x22, 0x1C],
+    [0x7F, 0x49, 0x49, 0x49, 0x41], [0x7F, 0x09, 0x09, 0x09, 0x01], [0x3E, 0x41, 0x49, 0x49, 0x7A], [0x7F, 0x08, 0x08, 0x08, 0x7F],
+    [0x00, 0x41, 0x7F, 0x41, 0x00], [0x20, 0x40, 0x41, 0x3F, 0x01], [0x7F, 0x08, 0x14, 0x22, 0x41], [0x7F, 0x40, 0x40, 0x40, 0x40],
+    [0x7F, 0x02, 0x0C, 0x02, 0x7F], [0x7F, 0x04, 0x08, 0x10, 0x7F], [0x3E, 0x41, 0x41, 0x41, 0x3E], [0x7F, 0x09, 0x09, 0x09, 0x06],
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], [0x7F, 0x09, 0x19, 0x29, 0x46], [0x46, 0x49, 0x49, 0x49, 0x31], [0x01, 0x01, 0x7F, 0x01, 0x01],
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], [0x1F, 0x20, 0x40, 0x20, 0x1F], [0x3F, 0x40, 0x38, 0x40, 0x3F], [0x63, 0x14, 0x08, 0x14, 0x63],
+    [0x07, 0x08, 0x70, 0x08, 0x07], [0x61, 0x51, 0x49, 0x45, 0x43],
+    [0x00, 0x7F, 0x41, 0x41, 0x00], [0x02, 0x04, 0x08, 0x10, 0x20], [0x00, 0x41, 0x41, 0x7F, 0x00], [0x04, 0x02, 0x01, 0x02, 0x04],
+    [0x40, 0x40, 0x40, 0x40, 0x40], [0x00, 0x01, 0x02, 0x04, 0x00],
+    // a-z
+    [0x20, 0x54, 0x54, 0x54, 0x78], [0x7F, 0x48, 0x44, 0x44, 0x38], [0x38, 0x44, 0x44, 0x44, 0x20], [0x38, 0x44, 0x44, 0x48, 0x7F],
+    [0x38, 0x54, 0x54, 0x54, 0x18], [0x08, 0x7E, 0x09, 0x01, 0x02], [0x0C, 0x52, 0x52, 0x52, 0x3E], [0x7F, 0x08, 0x04, 0x04, 0x78],
+    [0x00, 0x44, 0x7D, 0x40, 0x00], [0x20, 0x40, 0x44, 0x3D, 0x00], [0x7F, 0x10, 0x28, 0x44, 0x00], [0x00, 0x41, 0x7F, 0x40, 0x00],
+    [0x7C, 0x04, 0x18, 0x04, 0x78], [0x7C, 0x08, 0x04, 0x04, 0x78], [0x38, 0x44, 0x44, 0x44, 0x38], [0x7C, 0x14, 0x14, 0x14, 0x08],
+    [0x08, 0x14, 0x14, 0x18, 0x7C], [0x7C, 0x08, 0x04, 0x04, 0x08], [0x48, 0x54, 0x54, 0x54, 0x20], [0x04, 0x3F, 0x44, 0x40, 0x20],
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], [0x1C, 0x20, 0x40, 0x20, 0x1C], [0x3C, 0x40, 0x30, 0x40, 0x3C], [0x44, 0x28, 0x10, 0x28, 0x44],
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], [0x44, 0x64, 0x54, 0x4C, 0x44],
+    [0x00, 0x08, 0x36, 0x41, 0x00], [0x00, 0x00, 0x7F, 0x00, 0x00], [0x00, 0x41, 0x36, 0x08, 0x00], [0x08, 0x04, 0x08, 0x10, 0x08],
+];
+
+/// The index of `character` in the font (and the offset of its layer from the first font layer).
+pub fn glyph_index(character: char) -> Option<u32> {
+    let index = (character as u32).checked_sub(FIRST_GLYPH as u32)?;
+    (index < GLYPH_COUNT).then_some(index)
+}
+
+/// Whether the glyph has a pixel at `x`, `y` (from the top left of its 5x7 pixels).
+fn glyph_pixel(index: u32, x: u32, y: u32) -> bool {
+    x < GLYPH_WIDTH && y < GLYPH_HEIGHT && FONT[index as usize][x as usize] & (1 << y) != 0
+}
+
+/// One white image per character, with the glyph scaled up to `width` by `height` and a transparent
+/// background. Append these to the texture array and name the first one [FONT_LAYER_NAME].
+pub fn font_layer_images(width: u32, height: u32) -> Vec<RgbaImage> {
+    (0..GLYPH_COUNT).map(|index| {
+        RgbaImage::from_fn(width, height, |x, y| {
+            let (font_x, font_y) = (x * LAYER_PIXELS / width, y * LAYER_PIXELS / height);
+            let lit = font_x >= CELL_OFFSET && glyph_pixel(index, font_x - CELL_OFFSET, font_y);
+            Rgba([255, 255, 255, if lit { 255 } else { 0 }])
+        })
+    }).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    /// The layer of [FIRST_GLYPH], see [FONT_LAYER_NAME].
+    pub font_layer: u32,
+    /// The height of a line in world units.
+    pub line_height: f32,
+    /// RGBA that the font is multiplied with.
+    pub color: [u8; 4],
+    /// The point of the text's bounds that is at the origin, from `(0, 0)` at the top left to `(1, 1)`
+    /// at the bottom right.
+    pub anchor: Vec2,
+}
+
+impl TextStyle {
+    /// White text, one unit high, that sits centered on top of the origin.
+    pub fn new(font_layer: u32) -> Self {
+        Self {
+            font_layer,
+            line_height: 1.0,
+            color: Vertex::WHITE,
+            anchor: vec2(0.5, 1.0),
+        }
+    }
+
+    pub fn with_line_height(self, line_height: f32) -> Self {
+        Self { line_height, ..self }
+    }
+
+    pub fn with_color(self, color: [u8; 4]) -> Self {
+        Self { color, ..self }
+    }
+
+    pub fn with_anchor(self, anchor: Vec2) -> Self {
+        Self { anchor, ..self }
+    }
+}
+
+/// The width and height of `text` in world units.
+pub fn text_size(text: &str, line_height: f32) -> Vec2 {
+    let columns = text.lines().map(|line| line.chars().count()).max().unwrap_or(0);
+    let lines = text.lines().count().max(1);
+    vec2(columns as f32 * CHARACTER_ASPECT, lines as f32) * line_height
+}
+
+/// Pushes a quad for every visible character of `text`. Lines are separated by `\n`.
+pub fn push_text<'a>(modeler: &'a mut Modeler, text: &str, style: &TextStyle) -> &'a mut Modeler {
+    let size = text_size(text, style.line_height);
+    let top_left = vec2(-style.anchor.x * size.x, style.anchor.y * size.y);
+    let (width, height) = (style.line_height * CHARACTER_ASPECT, style.line_height);
+    let uv_min = vec2(CELL_OFFSET as f32 / LAYER_PIXELS as f32, 0.0);
+    let uv_max = vec2((CELL_OFFSET + CELL_WIDTH) as f32 / LAYER_PIXELS as f32, 1.0);
+    for (row, line) in text.lines().enumerate() {
+        for (column, character) in line.chars().enumerate() {
+            if character.is_whitespace() {
+                continue;
+            }
+            let Some(index) = glyph_index(character).or(glyph_index(REPLACEMENT_GLYPH)) else {
+                continue;
+            };
+            let min = top_left + vec2(column as f32 * width, -(row as f32 + 1.0) * height);
+            let max = min + vec2(width, height);
+            let layer = style.font_layer + index;
+            let vertex = |x: f32, y: f32, u: f32, v: f32| {
+                Vertex::new(vec3(x, y, 0.0), vec2(u, v), layer).with_color(style.color)
+            };
+            // Top left, top right, bottom left, bottom right, which faces +Z.
+            modeler.push_quad(&[
+                vertex(min.x, max.y, uv_min.x, uv_min.y),
+                vertex(max.x, max.y, uv_max.x, uv_min.y),
+                vertex(min.x, min.y, uv_min.x, uv_max.y),
+                vertex(max.x, min.y, uv_max.x, uv_max.y),
+            ]);
+        }
+    }
+    modeler
+}
+
+/// The transform for text at `position` that faces a camera with `camera_rotation` (see
+/// [crate::camera::Camera::quat]) and stays upright on the screen.
+pub fn billboard(position: Vec3, camera_rotation: Quat) -> Mat4 {
+    Mat4::from_rotation_translation(camera_rotation, position)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn font_test() {
+        assert_eq!(glyph_index(' '), Some(0));
+        assert_eq!(glyph_index('~'), Some(GLYPH_COUNT - 1));
+        assert_eq!(glyph_index('\u{7f}'), None);
+        assert_eq!(glyph_index('é'), None);
+        assert_eq!(glyph_index('A'), Some(33));
+
+        let layers = font_layer_images(32, 32);
+        assert_eq!(layers.len(), GLYPH_COUNT as usize);
+        assert!(layers[0].pixels().all(|pixel| pixel.0[3] == 0));
+        // '!' is a line down the middle column, with a gap above the dot. Every font pixel is 4x4 here.
+        let bang = &layers[glyph_index('!').unwrap() as usize];
+        let lit = |x: u32, y: u32| bang.get_pixel(x * 4 + 1, y * 4 + 1).0[3] == 255;
+        assert!((0..5).all(|y| lit(3, y)) && !lit(3, 5) && lit(3, 6));
+        assert!(!lit(2, 0) && !lit(4, 0) && !lit(3, 7));
+        // Nothing is drawn in the spacing.
+        for layer in &layers {
+            assert!((0..32).all(|y| (0..4).chain(24..32).all(|x| layer.get_pixel(x, y).0[3] == 0)));
+        }
+    }
+
+    #[test]
+    fn push_text_test() {
+        assert_eq!(text_size("", 1.0), vec2(0.0, 1.0));
+        assert_eq!(text_size("ab\nabcd", 2.0), vec2(4.0 * 1.5, 4.0));
+
+        let style = TextStyle::new(10).with_color([255, 0, 0, 255]).with_anchor(Vec2::ZERO);
+        let mut m = Modeler::new();
+        push_text(&mut m, "A b\n\u{263A}", &style);
+        // Spaces are skipped, and unknown characters are replaced.
+        assert_eq!(m.vertices.len(), 3 * 4);
+        let layers = m.vertices.chunks(4).map(|quad| quad[0].texindex - 10).collect::<Vec<_>>();
+        assert_eq!(layers, [33, 66, 31]);
+        assert!(m.vertices.iter().all(|v| v.normal == Vec3::Z && v.color == [255, 0, 0, 255]));
+        // Anchored at the top left, the text goes right and down from the origin.
+        let (min, max) = m.vertices.iter().fold((Vec3::MAX, Vec3::MIN), |(min, max), v| (min.min(v.position), max.max(v.position)));
+        assert_eq!((min, max), (vec3(0.0, -2.0, 0.0), vec3(2.25, 0.0, 0.0)));
+
+        // The default anchor centers the text above the origin, and billboards face the camera.
+        let mut m = Modeler::new();
+        let rotation = Quat::from_rotation_y(1.0);
+        m.transform(billboard(Vec3::ONE, rotation), |m| {
+            push_text(m, "ab", &TextStyle::new(0));
+        });
+        let center = m.vertices.iter().map(|v| v.position).sum::<Vec3>() / m.vertices.len() as f32;
+        assert!(center.distance(Vec3::ONE + rotation * vec3(0.0, 0.5, 0.0)) < 1e-5);
+        assert!(m.vertices.iter().all(|v| v.normal.distance(rotation * Vec3::Z) < 1e-5));
+    }
+}
@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+use glam::{vec2, Vec2};
+use winit::dpi::PhysicalPosition;
+
+/*
+How mouse movement becomes camera rotation:
+
+    1. The frame's raw delta is smoothed by one of the [crate::livemouse::MouseFilter]s, picked by
+       [SmoothingMode] (see [crate::input::MousePosState::begin_frame]).
+    2. [AccelCurve] scales the delta by how fast the mouse is moving.
+    3. The delta is multiplied by the sensitivity, and the vertical rotation is flipped with `invert_y`.
+*/
+
+/// Radians of rotation per mouse count at a sensitivity of `1.0`.
+pub const BASE_SENSITIVITY: f64 = 0.00075 * 2.5;
+pub const MIN_SENSITIVITY: f64 = 0.1;
+pub const MAX_SENSITIVITY: f64 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SmoothingMode {
+    #[default]
+    Off,
+    /// Averages the deltas of the last few frames.
+    Average,
+    /// An exponential moving average, see [crate::livemouse::ExpMouse].
+    Exponential,
+    /// Accelerates towards the movement, see [crate::livemouse::LiveMouse].
+    Live,
+}
+
+impl SmoothingMode {
+    pub const ALL: [Self; 4] = [Self::Off, Self::Average, Self::Exponential, Self::Live];
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Average,
+            Self::Average => Self::Exponential,
+            Self::Exponential => Self::Live,
+            Self::Live => Self::Off,
+        }
+    }
+
+    /// Parses the [std::fmt::Debug] name of a mode, such as `Average`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| format!("{mode:?}") == name)
+    }
+}
+
+/// Scales mouse movement by `1 + acceleration * speed^exponent`, where the speed is in thousands of
+/// counts per second, up to `max_gain`. An acceleration of `0.0` disables it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AccelCurve {
+    pub acceleration: f64,
+    pub exponent: f64,
+    pub max_gain: f64,
+}
+
+impl AccelCurve {
+    pub const NONE: Self = Self {
+        acceleration: 0.0,
+        exponent: 1.0,
+        max_gain: 4.0,
+    };
+
+    /// The multiplier for a mouse moving at `speed` counts per second.
+    pub fn gain(&self, speed: f64) -> f64 {
+        if self.acceleration <= 0.0 {
+            return 1.0;
+        }
+        (1.0 + self.acceleration * (speed / 1000.0).powf(self.exponent)).min(self.max_gain.max(1.0))
+    }
+}
+
+impl Default for AccelCurve {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MouseSettings {
+    /// A multiplier of [BASE_SENSITIVITY].
+    pub sensitivity: f64,
+    pub invert_y: bool,
+    pub smoothing_mode: SmoothingMode,
+    /// Drops the smoothing history as soon as the mouse stops, so that the camera doesn't drift.
+    pub halting: bool,
+    pub accel_curve: AccelCurve,
+}
+
+impl Default for MouseSettings {
+    fn default() -> Self {
+        Self {
+            sensitivity: 1.0,
+            invert_y: false,
+            smoothing_mode: SmoothingMode::Off,
+            halting: false,
+            accel_curve: AccelCurve::NONE,
+        }
+    }
+}
+
+impl MouseSettings {
+    /// The camera rotation (pitch, yaw) for a frame's (smoothed) mouse delta.
+    pub fn rotation(&self, delta: PhysicalPosition<f64>, delta_time: Duration) -> Vec2 {
+        let seconds = delta_time.as_secs_f64();
+        let speed = if seconds > 0.0 {
+            (delta.x * delta.x + delta.y * delta.y).sqrt() / seconds
+        } else {
+            0.0
+        };
+        let scale = BASE_SENSITIVITY * self.sensitivity * self.accel_curve.gain(speed);
+        let pitch = -(delta.y * scale);
+        let yaw = -(delta.x * scale);
+        vec2(if self.invert_y { -pitch } else { pitch } as f32, yaw as f32)
+    }
+
+    /// Multiplies the sensitivity by `factor`, clamped to [MIN_SENSITIVITY]..=[MAX_SENSITIVITY].
+    pub fn scale_sensitivity(&mut self, factor: f64) {
+        self.sensitivity = (self.sensitivity * factor).clamp(MIN_SENSITIVITY, MAX_SENSITIVITY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mouse_settings_test() {
+        let frame = Duration::from_millis(10);
+        let delta = PhysicalPosition::new(10.0, -4.0);
+        let mut settings = MouseSettings::default();
+        let rotation = settings.rotation(delta, frame);
+        assert!((rotation.y as f64 + 10.0 * BASE_SENSITIVITY).abs() < 1e-6);
+        assert!((rotation.x as f64 - 4.0 * BASE_SENSITIVITY).abs() < 1e-6);
+
+        settings.invert_y = true;
+        settings.sensitivity = 2.0;
+        let inverted = settings.rotation(delta, frame);
+        assert!((inverted.x + rotation.x * 2.0).abs() < 1e-6);
+        assert!((inverted.y - rotation.y * 2.0).abs() < 1e-6);
+
+        settings.scale_sensitivity(100.0);
+        assert_eq!(settings.sensitivity, MAX_SENSITIVITY);
+
+        let curve = AccelCurve { acceleration: 0.5, exponent: 1.0, max_gain: 2.0 };
+        assert_eq!(AccelCurve::NONE.gain(5000.0), 1.0);
+        assert_eq!(curve.gain(0.0), 1.0);
+        assert!((curve.gain(1000.0) - 1.5).abs() < 1e-9);
+        assert_eq!(curve.gain(10000.0), 2.0);
+
+        for mode in SmoothingMode::ALL {
+            assert_eq!(SmoothingMode::from_name(&format!("{mode:?}")), Some(mode));
+        }
+    }
+}
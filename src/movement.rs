@@ -0,0 +1,235 @@
+use glam::Vec3;
+
+use crate::camera::{Camera, MoveType};
+
+/*
+Smoothed flying movement. The movement keys set a target velocity, and the camera's velocity approaches
+it exponentially like [crate::livemouse::LiveMouse] does with the mouse, with separate factors for speeding
+up and for slowing down. Walking uses the [crate::physics::CharacterController] instead, which only takes
+the preset's speed.
+
+The speeds are [SpeedPreset]s, which are read from the user config (see [crate::user_config]). The arrow
+keys step through them, and each preset starts out with its own movement type.
+*/
+
+/// Speeds in units per second of the presets that ship with the game.
+pub const DEFAULT_SPEEDS: [f32; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+pub const DEFAULT_ACCELERATION: f32 = 12.0;
+pub const DEFAULT_DECELERATION: f32 = 8.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpeedPreset {
+    /// Units per second.
+    pub speed: f32,
+    /// How quickly the velocity approaches a faster target, per second. `0.0` or less is instant.
+    pub acceleration: f32,
+    /// How quickly the velocity approaches a slower target, per second. `0.0` or less is instant.
+    pub deceleration: f32,
+    /// Either [MoveType::Planar] or [MoveType::Free].
+    pub move_type: MoveType,
+}
+
+impl SpeedPreset {
+    pub const fn new(speed: f32) -> Self {
+        Self {
+            speed,
+            acceleration: DEFAULT_ACCELERATION,
+            deceleration: DEFAULT_DECELERATION,
+            move_type: MoveType::Planar,
+        }
+    }
+
+    pub fn defaults() -> Vec<Self> {
+        DEFAULT_SPEEDS.map(Self::new).to_vec()
+    }
+}
+
+impl Default for SpeedPreset {
+    fn default() -> Self {
+        Self::new(4.0)
+    }
+}
+
+/// The name of a movement type in the user config.
+pub fn move_type_name(move_type: MoveType) -> &'static str {
+    match move_type {
+        MoveType::Absolute => "Absolute",
+        MoveType::Free => "Free",
+        MoveType::Planar => "Planar",
+    }
+}
+
+pub fn move_type_from_name(name: &str) -> Option<MoveType> {
+    [MoveType::Absolute, MoveType::Free, MoveType::Planar].into_iter().find(|&move_type| move_type_name(move_type) == name)
+}
+
+/// The fraction of the way to the target that is covered in `dt` seconds with `factor`.
+fn smooth_factor(factor: f32, dt: f32) -> f32 {
+    if factor <= 0.0 {
+        1.0
+    } else {
+        1.0 - (-factor * dt).exp()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MovementController {
+    presets: Vec<SpeedPreset>,
+    index: usize,
+    move_type: MoveType,
+    /// In world space, so that turning doesn't turn the momentum with the camera.
+    velocity: Vec3,
+}
+
+impl MovementController {
+    /// `presets` falls back to [SpeedPreset::defaults] when it's empty.
+    pub fn new(presets: Vec<SpeedPreset>, index: usize) -> Self {
+        let presets = if presets.is_empty() { SpeedPreset::defaults() } else { presets };
+        let index = index.min(presets.len() - 1);
+        Self {
+            move_type: presets[index].move_type,
+            presets,
+            index,
+            velocity: Vec3::ZERO,
+        }
+    }
+
+    pub fn presets(&self) -> &[SpeedPreset] {
+        &self.presets
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn preset(&self) -> &SpeedPreset {
+        &self.presets[self.index]
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.preset().speed
+    }
+
+    /// Switches to the preset at `index` (clamped) and to its movement type.
+    pub fn select(&mut self, index: usize) {
+        self.index = index.min(self.presets.len() - 1);
+        self.move_type = self.preset().move_type;
+    }
+
+    pub fn faster(&mut self) {
+        self.select(self.index + 1);
+    }
+
+    pub fn slower(&mut self) {
+        self.select(self.index.saturating_sub(1));
+    }
+
+    pub fn move_type(&self) -> MoveType {
+        self.move_type
+    }
+
+    /// Switches between planar and free movement until the next preset is selected.
+    pub fn toggle_move_type(&mut self) -> MoveType {
+        self.move_type = match self.move_type {
+            MoveType::Planar => MoveType::Free,
+            _ => MoveType::Planar,
+        };
+        self.move_type
+    }
+
+    pub fn velocity(&self) -> Vec3 {
+        self.velocity
+    }
+
+    /// Stops right away, such as when the camera is moved by something else.
+    pub fn stop(&mut self) {
+        self.velocity = Vec3::ZERO;
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.velocity.length_squared() > 1e-8
+    }
+
+    /// The world space velocity for moving in `direction` (relative to `camera`, with a length of up to 1)
+    /// at the preset's speed times `multiplier`.
+    pub fn target_velocity(&self, camera: &Camera, direction: Vec3, multiplier: f32) -> Vec3 {
+        let direction = match self.move_type {
+            MoveType::Absolute => direction,
+            MoveType::Free => camera.rotate_vec(direction),
+            MoveType::Planar => camera.rotate_vec_y(direction),
+        };
+        direction * self.speed() * multiplier
+    }
+
+    /// Moves the velocity towards `target` (in world space) and returns the translation for the `dt` seconds
+    /// of this frame.
+    pub fn update(&mut self, target: Vec3, dt: f32) -> Vec3 {
+        let preset = self.preset();
+        let factor = if target.length_squared() > self.velocity.length_squared() {
+            preset.acceleration
+        } else {
+            preset.deceleration
+        };
+        self.velocity += (target - self.velocity) * smooth_factor(factor, dt);
+        if target == Vec3::ZERO && !self.is_moving() {
+            self.velocity = Vec3::ZERO;
+        }
+        self.velocity * dt
+    }
+}
+
+impl Default for MovementController {
+    fn default() -> Self {
+        Self::new(SpeedPreset::defaults(), 4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoothing_test() {
+        let mut controller = MovementController::default();
+        let target = Vec3::X * controller.speed();
+        let mut previous = 0.0;
+        for _ in 0..10 {
+            let step = controller.update(target, 1.0 / 60.0).x;
+            assert!(step > previous);
+            previous = step;
+        }
+        assert!(controller.velocity().x < target.x);
+        for _ in 0..600 {
+            controller.update(target, 1.0 / 60.0);
+        }
+        assert!((controller.velocity().x - target.x).abs() < 1e-3);
+        for _ in 0..600 {
+            controller.update(Vec3::ZERO, 1.0 / 60.0);
+        }
+        assert!(!controller.is_moving());
+        assert_eq!(controller.velocity(), Vec3::ZERO);
+
+        // No smoothing at all.
+        let instant = SpeedPreset { acceleration: 0.0, deceleration: 0.0, ..SpeedPreset::new(2.0) };
+        let mut controller = MovementController::new(vec![instant], 0);
+        assert_eq!(controller.update(Vec3::Z * 2.0, 0.5), Vec3::Z);
+        assert_eq!(controller.update(Vec3::ZERO, 0.5), Vec3::ZERO);
+    }
+
+    #[test]
+    fn preset_test() {
+        let free = SpeedPreset { move_type: MoveType::Free, ..SpeedPreset::new(10.0) };
+        let mut controller = MovementController::new(vec![SpeedPreset::new(1.0), free], 5);
+        assert_eq!(controller.index(), 1);
+        assert_eq!(controller.move_type(), MoveType::Free);
+        controller.faster();
+        assert_eq!(controller.index(), 1);
+        assert_eq!(controller.toggle_move_type(), MoveType::Planar);
+        controller.slower();
+        assert_eq!(controller.speed(), 1.0);
+        assert_eq!(controller.move_type(), MoveType::Planar);
+        assert_eq!(MovementController::new(Vec::new(), 0).presets().len(), DEFAULT_SPEEDS.len());
+        assert_eq!(move_type_from_name(move_type_name(MoveType::Free)), Some(MoveType::Free));
+        assert_eq!(move_type_from_name("Sideways"), None);
+    }
+}
@@ -0,0 +1,228 @@
+use glam::*;
+
+use crate::rendering::raytrace::RaytraceChunk;
+
+/*
+Collision against voxels for a simple character controller.
+
+Movement is resolved one axis at a time (Y first, so that landing takes priority over sliding into
+walls). Along each axis, the cells that the leading face of the box sweeps through are checked in
+order and the motion stops just before the first solid one, so fast movement can't tunnel through
+thin walls.
+*/
+
+/// The gap that is left between a box and the solid it collided with.
+const SKIN: f32 = 1e-3;
+
+/// An axis aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub const fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn translated(&self, offset: Vec3) -> Self {
+        Self::new(self.min + offset, self.max + offset)
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.cmplt(other.max).all() && self.max.cmpgt(other.min).all()
+    }
+
+    /// The range of cells (inclusive) that the box overlaps on the given axis.
+    fn cell_range(&self, axis: usize) -> (i32, i32) {
+        (self.min[axis].floor() as i32, self.max[axis].ceil() as i32 - 1)
+    }
+}
+
+/// Returns `true` if any cell that `aabb` overlaps is solid.
+pub fn overlaps_solid<F: Fn(IVec3) -> bool>(aabb: Aabb, solid: F) -> bool {
+    let (min_x, max_x) = aabb.cell_range(0);
+    let (min_y, max_y) = aabb.cell_range(1);
+    let (min_z, max_z) = aabb.cell_range(2);
+    (min_y..=max_y).any(|y| {
+        (min_z..=max_z).any(|z| {
+            (min_x..=max_x).any(|x| solid(ivec3(x, y, z)))
+        })
+    })
+}
+
+/// Returns `true` if the slice of cells at `layer` on `axis`, covering `aabb` on the other two axes, contains a solid cell.
+fn layer_is_solid<F: Fn(IVec3) -> bool>(aabb: &Aabb, axis: usize, layer: i32, solid: &F) -> bool {
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+    let (min_u, max_u) = aabb.cell_range(u);
+    let (min_v, max_v) = aabb.cell_range(v);
+    (min_u..=max_u).any(|cu| {
+        (min_v..=max_v).any(|cv| {
+            let mut cell = IVec3::ZERO;
+            cell[axis] = layer;
+            cell[u] = cu;
+            cell[v] = cv;
+            solid(cell)
+        })
+    })
+}
+
+/// Returns how far `aabb` can move along `axis` (up to `distance`) before it hits a solid cell.
+/// Cells that the box already overlaps are ignored, so a box that is stuck inside of a solid can move out of it.
+fn sweep_axis<F: Fn(IVec3) -> bool>(aabb: &Aabb, axis: usize, distance: f32, solid: &F) -> (f32, bool) {
+    if distance > 0.0 {
+        let front = aabb.max[axis];
+        let first = front.ceil() as i32;
+        let last = (front + distance).floor() as i32;
+        for layer in first..=last {
+            if layer_is_solid(aabb, axis, layer, solid) {
+                return ((layer as f32 - front - SKIN).max(0.0), true);
+            }
+        }
+    } else if distance < 0.0 {
+        let front = aabb.min[axis];
+        let first = front.floor() as i32 - 1;
+        let last = (front + distance).floor() as i32;
+        for layer in (last..=first).rev() {
+            if layer_is_solid(aabb, axis, layer, solid) {
+                return (((layer + 1) as f32 - front + SKIN).min(0.0), true);
+            }
+        }
+    }
+    (distance, false)
+}
+
+/// Moves `aabb` by `motion`, stopping at solid cells. Returns the motion that was applied and the axes that collided.
+pub fn move_and_collide<F: Fn(IVec3) -> bool>(aabb: Aabb, motion: Vec3, solid: F) -> (Vec3, BVec3) {
+    let mut aabb = aabb;
+    let mut applied = Vec3::ZERO;
+    let mut collided = BVec3::FALSE;
+    for axis in [1, 0, 2] {
+        let (distance, hit) = sweep_axis(&aabb, axis, motion[axis], &solid);
+        let mut offset = Vec3::ZERO;
+        offset[axis] = distance;
+        aabb = aabb.translated(offset);
+        applied[axis] = distance;
+        collided.set(axis, hit);
+    }
+    (applied, collided)
+}
+
+/// Returns a function that treats non-zero blocks as solid. Everything below the chunk is solid as well,
+/// so that nothing can fall out of the world.
+pub fn chunk_solids(chunk: &RaytraceChunk) -> impl Fn(IVec3) -> bool + '_ {
+    |cell| cell.y < 0 || chunk.get(cell.x, cell.y, cell.z) != 0
+}
+
+/// A player box that walks on solid voxels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CharacterController {
+    /// The center of the bottom of the box.
+    pub position: Vec3,
+    pub velocity: Vec3,
+    /// The width (on both X and Z) and height of the box.
+    pub size: Vec2,
+    /// The height of the eyes above [CharacterController::position].
+    pub eye_height: f32,
+    pub gravity: f32,
+    pub jump_speed: f32,
+    /// The fastest that the controller can fall.
+    pub terminal_velocity: f32,
+    pub on_ground: bool,
+}
+
+impl CharacterController {
+    pub const DEFAULT_SIZE: Vec2 = vec2(0.6, 1.8);
+    pub const DEFAULT_EYE_HEIGHT: f32 = 1.6;
+
+    pub fn new(position: Vec3) -> Self {
+        Self {
+            position,
+            velocity: Vec3::ZERO,
+            size: Self::DEFAULT_SIZE,
+            eye_height: Self::DEFAULT_EYE_HEIGHT,
+            gravity: 28.0,
+            // Enough to jump a little over one block.
+            jump_speed: 8.5,
+            terminal_velocity: 50.0,
+            on_ground: false,
+        }
+    }
+
+    /// Creates the controller so that its eyes are at `eye`.
+    pub fn from_eye(eye: Vec3) -> Self {
+        Self::new(eye - Vec3::Y * Self::DEFAULT_EYE_HEIGHT)
+    }
+
+    pub fn eye_position(&self) -> Vec3 {
+        self.position + Vec3::Y * self.eye_height
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        let half_width = self.size.x * 0.5;
+        Aabb::new(
+            self.position - vec3(half_width, 0.0, half_width),
+            self.position + vec3(half_width, self.size.y, half_width),
+        )
+    }
+
+    /// Applies gravity, moves horizontally with `walk_velocity` (the Y component is ignored), and jumps if
+    /// `jump` is `true` and the controller is standing on something.
+    pub fn update<F: Fn(IVec3) -> bool>(&mut self, delta_time: f32, walk_velocity: Vec3, jump: bool, solid: F) {
+        self.velocity.x = walk_velocity.x;
+        self.velocity.z = walk_velocity.z;
+        if jump && self.on_ground {
+            self.velocity.y = self.jump_speed;
+        }
+        self.velocity.y = (self.velocity.y - self.gravity * delta_time).max(-self.terminal_velocity);
+        let (applied, collided) = move_and_collide(self.aabb(), self.velocity * delta_time, solid);
+        self.position += applied;
+        self.on_ground = collided.y && self.velocity.y <= 0.0;
+        if collided.y {
+            self.velocity.y = 0.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn floor_and_wall(cell: IVec3) -> bool {
+        cell.y < 0 || cell.x == 5
+    }
+
+    #[test]
+    fn sweep_stops_at_solids() {
+        let aabb = Aabb::new(vec3(0.2, 0.0, 0.2), vec3(0.8, 1.8, 0.8));
+        let (applied, collided) = move_and_collide(aabb, vec3(10.0, -3.0, 0.5), floor_and_wall);
+        assert!(collided.x && collided.y && !collided.z);
+        assert!((applied.x - (5.0 - 0.8 - SKIN)).abs() < 1e-5);
+        assert_eq!(applied.y, 0.0);
+        assert_eq!(applied.z, 0.5);
+        // Moving away from the wall isn't blocked.
+        let moved = aabb.translated(applied);
+        let (applied, collided) = move_and_collide(moved, vec3(-2.0, 0.0, 0.0), floor_and_wall);
+        assert!(!collided.x);
+        assert_eq!(applied.x, -2.0);
+    }
+
+    #[test]
+    fn controller_lands_and_jumps() {
+        let mut controller = CharacterController::new(vec3(0.5, 3.0, 0.5));
+        for _ in 0..120 {
+            controller.update(1.0 / 60.0, Vec3::ZERO, false, floor_and_wall);
+        }
+        assert!(controller.on_ground);
+        assert!(controller.position.y.abs() < 0.01);
+        controller.update(1.0 / 60.0, Vec3::ZERO, true, floor_and_wall);
+        assert!(!controller.on_ground);
+        assert!(controller.velocity.y > 0.0);
+        assert!(!overlaps_solid(controller.aabb(), floor_and_wall));
+    }
+}
@@ -0,0 +1,213 @@
+/*
+Builders for bind group layouts and bind groups.
+
+    let layout = BindGroupBuilder::new()
+        .label("Reticle Bind Group Layout")
+        .uniform(0, wgpu::ShaderStages::VERTEX)
+        .texture_2d(1, wgpu::ShaderStages::FRAGMENT)
+        .sampler(2, wgpu::ShaderStages::FRAGMENT)
+        .build(device);
+    let group = Bindings::new()
+        .buffer(0, &ortho_buffer)
+        .texture_view(1, &view)
+        .sampler(2, &sampler)
+        .build(device, Some("Reticle Bind Group"), &layout);
+*/
+
+/// Builds a [wgpu::BindGroupLayout].
+#[derive(Debug, Clone, Default)]
+pub struct BindGroupBuilder {
+    label: Option<String>,
+    entries: Vec<wgpu::BindGroupLayoutEntry>,
+}
+
+impl BindGroupBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn label<S: Into<String>>(mut self, label: S) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn entry(mut self, entry: wgpu::BindGroupLayoutEntry) -> Self {
+        self.entries.push(entry);
+        self
+    }
+
+    fn binding(self, binding: u32, visibility: wgpu::ShaderStages, ty: wgpu::BindingType) -> Self {
+        self.entry(wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty,
+            count: None,
+        })
+    }
+
+    pub fn uniform(self, binding: u32, visibility: wgpu::ShaderStages) -> Self {
+        self.binding(binding, visibility, wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        })
+    }
+
+    pub fn storage(self, binding: u32, visibility: wgpu::ShaderStages, read_only: bool) -> Self {
+        self.binding(binding, visibility, wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        })
+    }
+
+    pub fn texture(
+        self,
+        binding: u32,
+        visibility: wgpu::ShaderStages,
+        sample_type: wgpu::TextureSampleType,
+        view_dimension: wgpu::TextureViewDimension,
+    ) -> Self {
+        self.binding(binding, visibility, wgpu::BindingType::Texture {
+            sample_type,
+            view_dimension,
+            multisampled: false,
+        })
+    }
+
+    /// A filterable float 2D texture.
+    pub fn texture_2d(self, binding: u32, visibility: wgpu::ShaderStages) -> Self {
+        self.texture(binding, visibility, wgpu::TextureSampleType::Float { filterable: true }, wgpu::TextureViewDimension::D2)
+    }
+
+    /// A filterable float 2D texture array.
+    pub fn texture_2d_array(self, binding: u32, visibility: wgpu::ShaderStages) -> Self {
+        self.texture(binding, visibility, wgpu::TextureSampleType::Float { filterable: true }, wgpu::TextureViewDimension::D2Array)
+    }
+
+    /// A filterable float cube texture.
+    pub fn texture_cube(self, binding: u32, visibility: wgpu::ShaderStages) -> Self {
+        self.texture(binding, visibility, wgpu::TextureSampleType::Float { filterable: true }, wgpu::TextureViewDimension::Cube)
+    }
+
+    /// A 2D storage texture.
+    pub fn storage_texture(
+        self,
+        binding: u32,
+        visibility: wgpu::ShaderStages,
+        access: wgpu::StorageTextureAccess,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        self.binding(binding, visibility, wgpu::BindingType::StorageTexture {
+            access,
+            format,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        })
+    }
+
+    /// A 2D array storage texture (such as the faces of a cubemap).
+    pub fn storage_texture_2d_array(
+        self,
+        binding: u32,
+        visibility: wgpu::ShaderStages,
+        access: wgpu::StorageTextureAccess,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        self.binding(binding, visibility, wgpu::BindingType::StorageTexture {
+            access,
+            format,
+            view_dimension: wgpu::TextureViewDimension::D2Array,
+        })
+    }
+
+    /// A filtering sampler.
+    pub fn sampler(self, binding: u32, visibility: wgpu::ShaderStages) -> Self {
+        self.binding(binding, visibility, wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering))
+    }
+
+    pub fn entries(&self) -> &[wgpu::BindGroupLayoutEntry] {
+        &self.entries
+    }
+
+    /// The entries sorted by binding. Two builders with the same signature produce compatible layouts.
+    pub fn signature(&self) -> Vec<wgpu::BindGroupLayoutEntry> {
+        let mut entries = self.entries.clone();
+        entries.sort_by_key(|entry| entry.binding);
+        entries
+    }
+
+    pub fn build(&self, device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: self.label.as_deref(),
+            entries: &self.entries,
+        })
+    }
+}
+
+/// Builds a [wgpu::BindGroup] for a layout.
+#[derive(Debug, Clone, Default)]
+pub struct Bindings<'a> {
+    entries: Vec<wgpu::BindGroupEntry<'a>>,
+}
+
+impl<'a> Bindings<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn resource(mut self, binding: u32, resource: wgpu::BindingResource<'a>) -> Self {
+        self.entries.push(wgpu::BindGroupEntry {
+            binding,
+            resource,
+        });
+        self
+    }
+
+    pub fn buffer(self, binding: u32, buffer: &'a wgpu::Buffer) -> Self {
+        self.resource(binding, buffer.as_entire_binding())
+    }
+
+    pub fn texture_view(self, binding: u32, view: &'a wgpu::TextureView) -> Self {
+        self.resource(binding, wgpu::BindingResource::TextureView(view))
+    }
+
+    pub fn sampler(self, binding: u32, sampler: &'a wgpu::Sampler) -> Self {
+        self.resource(binding, wgpu::BindingResource::Sampler(sampler))
+    }
+
+    pub fn build(&self, device: &wgpu::Device, label: Option<&str>, layout: &wgpu::BindGroupLayout) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label,
+            layout,
+            entries: &self.entries,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_test() {
+        let a = BindGroupBuilder::new()
+            .label("A")
+            .uniform(0, wgpu::ShaderStages::VERTEX)
+            .texture_2d(1, wgpu::ShaderStages::FRAGMENT)
+            .sampler(2, wgpu::ShaderStages::FRAGMENT);
+        let b = BindGroupBuilder::new()
+            .label("B")
+            .sampler(2, wgpu::ShaderStages::FRAGMENT)
+            .uniform(0, wgpu::ShaderStages::VERTEX)
+            .texture_2d(1, wgpu::ShaderStages::FRAGMENT);
+        // Labels and order don't matter.
+        assert_eq!(a.signature(), b.signature());
+        let c = a.clone().storage(3, wgpu::ShaderStages::COMPUTE, true);
+        assert_ne!(a.signature(), c.signature());
+        assert_eq!(c.entries()[3].ty, wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: true },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        });
+    }
+}
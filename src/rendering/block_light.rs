@@ -0,0 +1,173 @@
+/*
+Light from emissive blocks (see [super::raytrace::Material::emission]).
+
+Emissive blocks light their surroundings through a light volume that is flood-filled on the CPU, the way
+block light works in Minecraft. Every cell of the chunk has a level per color channel, from `0` to
+[MAX_LIGHT_LEVEL]. An emissive block starts at a level based on its color and emission, and every step
+into a neighboring cell loses one level, so light spreads around corners but not through opaque blocks
+(translucent blocks let it through). This is a cheap stand-in for bounced light: no rays are traced, and
+the raytracer only reads the levels around the surface (see `block_light` in raytrace.wgsl).
+
+The volume is rebuilt from scratch whenever the chunk or the materials change (see
+[super::raytrace::Raytracer::write_chunk]).
+*/
+
+use std::collections::VecDeque;
+
+use super::raytrace::{Material, RaytraceChunk};
+
+/// The level of the brightest light, which reaches `MAX_LIGHT_LEVEL - 1` cells away.
+pub const MAX_LIGHT_LEVEL: u8 = 15;
+/// The fourth byte of a cell is set for cells that light can't pass through.
+const OPAQUE: u8 = 0xFF;
+const NEIGHBORS: [(i32, i32, i32); 6] = [
+    (1, 0, 0), (-1, 0, 0),
+    (0, 1, 0), (0, -1, 0),
+    (0, 0, 1), (0, 0, -1),
+];
+
+/// The levels that a block with `material` emits. Emissions of `1.0` and above are as bright as it gets.
+pub fn emitted_levels(material: &Material) -> [u8; 3] {
+    if !material.is_emissive() {
+        return [0; 3];
+    }
+    let strength = material.emission.min(1.0) * MAX_LIGHT_LEVEL as f32;
+    material.color
+        .to_array()
+        .map(|channel| (channel.clamp(0.0, 1.0) * strength).round() as u8)
+}
+
+/// The light levels of every cell in the chunk, in the same order as [RaytraceChunk::blocks]. Each cell is
+/// stored as `[red, green, blue, opaque]`, which is how the raytracer reads them.
+#[derive(Debug, Clone)]
+pub struct BlockLight {
+    cells: Box<[[u8; 4]]>,
+}
+
+impl BlockLight {
+    pub fn new() -> Self {
+        Self {
+            cells: vec![[0u8; 4]; (RaytraceChunk::SIZE * RaytraceChunk::SIZE * RaytraceChunk::SIZE) as usize].into_boxed_slice(),
+        }
+    }
+
+    /// Floods the light of every emissive block through `chunk`. `materials` is indexed by block ID, and
+    /// IDs beyond it use the last material (like [super::raytrace::GpuMaterialTable]).
+    /// Returns the number of emissive blocks.
+    pub fn rebuild(&mut self, chunk: &RaytraceChunk, materials: &[Material]) -> usize {
+        // The starting cell of every block ID, so that each block is a lookup.
+        let seeds = materials.iter()
+            .map(|material| {
+                let [red, green, blue] = emitted_levels(material);
+                [red, green, blue, if material.is_translucent() { 0 } else { OPAQUE }]
+            })
+            .collect::<Vec<_>>();
+        let mut queue = VecDeque::new();
+        for (index, (cell, &id)) in self.cells.iter_mut().zip(chunk.blocks()).enumerate() {
+            *cell = match id {
+                0 => [0; 4],
+                id => seeds.get(id as usize).or(seeds.last()).copied().unwrap_or([0, 0, 0, OPAQUE]),
+            };
+            if cell[..3] != [0; 3] {
+                queue.push_back(index);
+            }
+        }
+        let emitters = queue.len();
+        while let Some(index) = queue.pop_front() {
+            let [red, green, blue, _] = self.cells[index];
+            if red.max(green).max(blue) <= 1 {
+                continue;
+            }
+            let spread = [red, green, blue].map(|level| level.saturating_sub(1));
+            let (x, y, z) = ((index & 63) as i32, (index >> 12) as i32, ((index >> 6) & 63) as i32);
+            for (dx, dy, dz) in NEIGHBORS {
+                let (nx, ny, nz) = (x + dx, y + dy, z + dz);
+                let Some(neighbor_index) = RaytraceChunk::index(nx, ny, nz) else {
+                    continue;
+                };
+                let neighbor = &mut self.cells[neighbor_index];
+                if neighbor[3] == OPAQUE {
+                    continue;
+                }
+                let mut brighter = false;
+                for (level, &spread) in neighbor[..3].iter_mut().zip(&spread) {
+                    if spread > *level {
+                        *level = spread;
+                        brighter = true;
+                    }
+                }
+                if brighter {
+                    queue.push_back(neighbor_index);
+                }
+            }
+        }
+        emitters
+    }
+
+    /// The red, green and blue levels at `x`, `y`, `z`. Cells outside of the chunk are dark.
+    pub fn get(&self, x: i32, y: i32, z: i32) -> [u8; 3] {
+        let Some(index) = RaytraceChunk::index(x, y, z) else {
+            return [0; 3];
+        };
+        let [red, green, blue, _] = self.cells[index];
+        [red, green, blue]
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.cells)
+    }
+}
+
+impl Default for BlockLight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec3;
+
+    use super::*;
+
+    #[test]
+    fn block_light_test() {
+        const STONE: u32 = 1;
+        const LAMP: u32 = 2;
+        let lamp = Material {
+            color: vec3(1.0, 0.5, 0.0),
+            emission: 2.0,
+            ..Material::DEFAULT
+        };
+        assert_eq!(emitted_levels(&lamp), [15, 8, 0]);
+        assert_eq!(emitted_levels(&Material::DEFAULT), [0; 3]);
+        let materials = [Material::DEFAULT, Material::DEFAULT, lamp];
+
+        let mut chunk = RaytraceChunk::new();
+        chunk.set(10, 10, 10, LAMP);
+        // A wall on the +X side of the lamp, which light has to go around.
+        for y in 0..64 {
+            for z in 8..14 {
+                chunk.set(12, y, z, STONE);
+            }
+        }
+        let mut light = BlockLight::new();
+        assert_eq!(light.rebuild(&chunk, &materials), 1);
+        assert_eq!(light.get(10, 10, 10), [15, 8, 0]);
+        assert_eq!(light.get(11, 10, 10), [14, 7, 0]);
+        assert_eq!(light.get(10, 13, 10), [12, 5, 0]);
+        // Light doesn't go into opaque blocks, and has to go around the wall (in to z = 7) to get behind it.
+        assert_eq!(light.get(12, 10, 10), [0; 3]);
+        assert_eq!(light.get(13, 10, 10), [15 - 9, 0, 0]);
+        // It fades out after MAX_LIGHT_LEVEL steps, and the chunk edges are dark.
+        assert_eq!(light.get(10, 10, 24), [1, 0, 0]);
+        assert_eq!(light.get(10, 10, 25), [0; 3]);
+        assert_eq!(light.get(-1, 10, 10), [0; 3]);
+
+        // Rebuilding starts over.
+        chunk.set(10, 10, 10, 0);
+        assert_eq!(light.rebuild(&chunk, &materials), 0);
+        assert_eq!(light.get(11, 10, 10), [0; 3]);
+        assert_eq!(light.as_bytes().len(), 64 * 64 * 64 * 4);
+    }
+}
@@ -0,0 +1,275 @@
+use bytemuck::{Pod, Zeroable};
+use image::GrayImage;
+
+use super::{bindings::{BindGroupBuilder, Bindings}, buffers::UniformBuffer};
+
+/*
+Blue noise is noise without low frequencies: neighboring texels have very different values, so sampling
+patterns that are rotated or offset by it turn banding into fine grain that accumulation (and the eye)
+averages out quickly.
+
+The texture is generated with the void-and-cluster method (Ulichney 1993) on a torus, so it tiles. Every
+texel gets a unique rank, so the values are evenly distributed. In the shader, each frame offsets the
+texture by a point of the R2 sequence so that accumulated frames use different noise, and each sample
+dimension uses a different offset so that they aren't correlated either.
+*/
+
+/// The default width and height of the noise texture.
+pub const DEFAULT_BLUE_NOISE_SIZE: u32 = 64;
+/// The standard deviation of the energy filter used by void-and-cluster.
+const SIGMA: f32 = 1.5;
+/// The fraction of texels in the initial pattern.
+const INITIAL_DENSITY: f32 = 0.1;
+
+/// The plastic constant's R2 sequence (see <https://extremelearning.com.au/unreasonable-effectiveness-of-quasirandom-sequences/>).
+const R2: [f64; 2] = [0.7548776662466927, 0.5698402909980532];
+
+struct Energy {
+    size: usize,
+    /// How far the filter reaches. It's negligible beyond three standard deviations.
+    radius: isize,
+    values: Vec<f32>,
+}
+
+impl Energy {
+    fn new(size: usize) -> Self {
+        Self {
+            size,
+            radius: ((SIGMA * 3.0).ceil() as isize).min((size as isize - 1) / 2),
+            values: vec![0.0; size * size],
+        }
+    }
+
+    /// Adds (or with a `sign` of `-1.0`, removes) the filtered energy of a texel.
+    fn splat(&mut self, index: usize, sign: f32) {
+        let size = self.size as isize;
+        let (x, y) = ((index % self.size) as isize, (index / self.size) as isize);
+        for dy in -self.radius..=self.radius {
+            for dx in -self.radius..=self.radius {
+                let weight = (-((dx * dx + dy * dy) as f32) / (2.0 * SIGMA * SIGMA)).exp();
+                let target = (y + dy).rem_euclid(size) * size + (x + dx).rem_euclid(size);
+                self.values[target as usize] += sign * weight;
+            }
+        }
+    }
+
+    /// The set texel with the most energy.
+    fn tightest_cluster(&self, pattern: &[bool]) -> usize {
+        (0..pattern.len())
+            .filter(|&index| pattern[index])
+            .max_by(|&a, &b| self.values[a].total_cmp(&self.values[b]))
+            .expect("The pattern has set texels.")
+    }
+
+    /// The unset texel with the least energy.
+    fn largest_void(&self, pattern: &[bool]) -> usize {
+        (0..pattern.len())
+            .filter(|&index| !pattern[index])
+            .min_by(|&a, &b| self.values[a].total_cmp(&self.values[b]))
+            .expect("The pattern has unset texels.")
+    }
+}
+
+/// Generates a `size` x `size` tiling blue noise texture with the void-and-cluster method. Every value
+/// from 0 to 255 occurs equally often (when `size * size` is a multiple of 256).
+pub fn generate_blue_noise(size: u32, seed: u32) -> Vec<u8> {
+    let size = size.max(2) as usize;
+    let count = size * size;
+    let mut state = seed.wrapping_mul(0x9e3779b9) | 1;
+    let mut next_random = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as usize
+    };
+
+    // A random initial pattern, relaxed by moving the tightest clusters into the largest voids.
+    let initial_count = ((count as f32 * INITIAL_DENSITY) as usize).max(1);
+    let mut pattern = vec![false; count];
+    let mut energy = Energy::new(size);
+    let mut placed = 0;
+    while placed < initial_count {
+        let index = next_random() % count;
+        if !pattern[index] {
+            pattern[index] = true;
+            energy.splat(index, 1.0);
+            placed += 1;
+        }
+    }
+    for _ in 0..count {
+        let cluster = energy.tightest_cluster(&pattern);
+        pattern[cluster] = false;
+        energy.splat(cluster, -1.0);
+        let void = energy.largest_void(&pattern);
+        pattern[void] = true;
+        energy.splat(void, 1.0);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0usize; count];
+    // The initial texels are ranked by removing the tightest clusters first.
+    let mut removing = pattern.clone();
+    let mut removing_energy = Energy { values: energy.values.clone(), ..Energy::new(size) };
+    for rank in (0..initial_count).rev() {
+        let cluster = removing_energy.tightest_cluster(&removing);
+        removing[cluster] = false;
+        removing_energy.splat(cluster, -1.0);
+        ranks[cluster] = rank;
+    }
+    // The rest are ranked by filling the largest voids.
+    for rank in initial_count..count {
+        let void = energy.largest_void(&pattern);
+        pattern[void] = true;
+        energy.splat(void, 1.0);
+        ranks[void] = rank;
+    }
+    ranks.into_iter().map(|rank| (rank * 256 / count) as u8).collect()
+}
+
+/// Per-frame parameters of the noise, see [BlueNoise::write_frame].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Pod, Zeroable)]
+pub struct NoiseFrame {
+    /// Added to the texel coordinate before sampling.
+    pub offset: [u32; 2],
+    pub frame: u32,
+    pub size: u32,
+}
+
+impl NoiseFrame {
+    /// The offset for `frame`, a point of the R2 sequence scaled to the texture.
+    pub fn new(frame: u32, size: u32) -> Self {
+        let offset = R2.map(|step| ((frame as f64 * step).fract() * size as f64) as u32);
+        Self {
+            offset,
+            frame,
+            size,
+        }
+    }
+}
+
+/// A blue noise texture and the per-frame offset, bound at one group of a shader:
+///
+/// ```wgsl
+/// @binding(0) var blue_noise_texture: texture_2d<f32>;
+/// @binding(1) var<uniform> noise_frame: NoiseFrame;
+/// ```
+pub struct BlueNoise {
+    size: u32,
+    _texture: wgpu::Texture,
+    frame: UniformBuffer<NoiseFrame>,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl BlueNoise {
+    pub fn create_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        BindGroupBuilder::new()
+            .label("Blue Noise Bind Group Layout")
+            .texture_2d(0, wgpu::ShaderStages::COMPUTE)
+            .uniform(1, wgpu::ShaderStages::COMPUTE)
+            .build(device)
+    }
+
+    /// Generates the noise, see [generate_blue_noise].
+    pub fn generate(device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout, size: u32, seed: u32) -> Self {
+        let size = size.max(2);
+        Self::from_values(device, queue, layout, size, &generate_blue_noise(size, seed))
+    }
+
+    /// Uses a square grayscale image (such as a precomputed noise texture) as the noise.
+    /// Returns `None` if the image isn't square.
+    pub fn from_image(device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout, image: &GrayImage) -> Option<Self> {
+        (image.width() == image.height() && image.width() > 0)
+            .then(|| Self::from_values(device, queue, layout, image.width(), image.as_raw()))
+    }
+
+    fn from_values(device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout, size: u32, values: &[u8]) -> Self {
+        let extent = wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Blue Noise Texture"),
+            size: extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            values,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(size),
+                rows_per_image: Some(size),
+            },
+            extent,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let frame = UniformBuffer::new(device, Some("Blue Noise Frame Buffer"), NoiseFrame::new(0, size));
+        let bind_group = Bindings::new()
+            .texture_view(0, &view)
+            .buffer(1, frame.buffer())
+            .build(device, Some("Blue Noise Bind Group"), layout);
+        Self {
+            size,
+            _texture: texture,
+            frame,
+            bind_group,
+        }
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Offsets the noise for `frame`. Consecutive frames get well distributed offsets.
+    pub fn write_frame(&self, queue: &wgpu::Queue, frame: u32) {
+        self.frame.write(queue, NoiseFrame::new(frame, self.size));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blue_noise_test() {
+        let size = 16;
+        let noise = generate_blue_noise(size, 7);
+        assert_eq!(noise.len(), 256);
+        // Every rank is used once.
+        let mut counts = [0u32; 256];
+        noise.iter().for_each(|&value| counts[value as usize] += 1);
+        assert!(counts.iter().all(|&count| count == 1));
+        assert_eq!(noise, generate_blue_noise(size, 7));
+        assert_ne!(noise, generate_blue_noise(size, 8));
+
+        // The darkest texels are spread out: none of them are next to each other (on the torus).
+        let size = size as usize;
+        let dark = |x: usize, y: usize| noise[(y % size) * size + x % size] < 26;
+        for y in 0..size {
+            for x in 0..size {
+                if dark(x, y) {
+                    assert!(!dark(x + 1, y) && !dark(x, y + 1), "Adjacent dark texels at {x}, {y}");
+                }
+            }
+        }
+
+        assert_eq!(NoiseFrame::new(0, 64).offset, [0, 0]);
+        let offsets = (1..8).map(|frame| NoiseFrame::new(frame, 64).offset).collect::<Vec<_>>();
+        assert!(offsets.iter().all(|offset| offset[0] < 64 && offset[1] < 64));
+        assert!((1..offsets.len()).all(|index| offsets[index] != offsets[index - 1]));
+    }
+}
@@ -0,0 +1,211 @@
+use std::cell::{Cell, RefCell};
+
+use bytemuck::NoUninit;
+use wgpu::util::DeviceExt;
+
+/*
+Typed wrappers around uniform and storage buffers. They keep a CPU side copy of the data so that
+individual fields can be updated without having to track byte offsets by hand:
+
+    write_field!(self.buffer, queue, directional.intensity = 0.5);
+
+Writes go through `queue.write_buffer`, so they take effect with the next submission. Both types
+only need `&self` to write, the same as the GPU structs that were using `RefCell` before.
+*/
+
+/// Rounds the byte range `offset..offset + size` outwards to [wgpu::COPY_BUFFER_ALIGNMENT],
+/// clamped to `len`. `write_buffer` rejects unaligned writes (such as a single `bool`).
+const fn aligned_range(offset: usize, size: usize, len: usize) -> std::ops::Range<usize> {
+    const ALIGN: usize = wgpu::COPY_BUFFER_ALIGNMENT as usize;
+    let start = offset / ALIGN * ALIGN;
+    let end = (offset + size).div_ceil(ALIGN) * ALIGN;
+    let end = if end > len { len } else { end };
+    start..end
+}
+
+fn layout_entry(binding: u32, visibility: wgpu::ShaderStages, ty: wgpu::BufferBindingType) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+pub struct UniformBuffer<T: NoUninit> {
+    value: Cell<T>,
+    buffer: wgpu::Buffer,
+}
+
+impl<T: NoUninit> UniformBuffer<T> {
+    pub fn new(device: &wgpu::Device, label: Option<&str>, value: T) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            contents: bytemuck::bytes_of(&value),
+        });
+        Self {
+            value: Cell::new(value),
+            buffer,
+        }
+    }
+
+    /// The value as of the last write.
+    pub fn get(&self) -> T {
+        self.value.get()
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub fn write(&self, queue: &wgpu::Queue, value: T) {
+        self.value.set(value);
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&value));
+    }
+
+    /// Modifies the value and writes the whole buffer.
+    pub fn update<F: FnOnce(&mut T)>(&self, queue: &wgpu::Queue, update: F) {
+        let mut value = self.value.get();
+        update(&mut value);
+        self.write(queue, value);
+    }
+
+    /// Sets the CPU side value without writing it. Use [UniformBuffer::write_range] afterwards
+    /// to upload the part that changed. This is what [crate::write_field] uses.
+    pub fn set_unwritten(&self, value: T) {
+        self.value.set(value);
+    }
+
+    /// Writes `size` bytes of the current value starting at `offset`. The range is widened to
+    /// the copy alignment, so the neighbouring bytes are rewritten with their current value.
+    pub fn write_range(&self, queue: &wgpu::Queue, offset: usize, size: usize) {
+        let value = self.value.get();
+        let bytes = bytemuck::bytes_of(&value);
+        let range = aligned_range(offset, size, bytes.len());
+        queue.write_buffer(&self.buffer, range.start as wgpu::BufferAddress, &bytes[range]);
+    }
+
+    pub fn as_entire_binding(&self) -> wgpu::BindingResource<'_> {
+        self.buffer.as_entire_binding()
+    }
+
+    pub fn bind_group_entry(&self, binding: u32) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding,
+            resource: self.buffer.as_entire_binding(),
+        }
+    }
+
+    pub fn layout_entry(binding: u32, visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+        layout_entry(binding, visibility, wgpu::BufferBindingType::Uniform)
+    }
+}
+
+/// A fixed length array in a storage buffer.
+pub struct StorageBuffer<T: NoUninit> {
+    values: RefCell<Box<[T]>>,
+    buffer: wgpu::Buffer,
+}
+
+impl<T: NoUninit> StorageBuffer<T> {
+    pub fn new(device: &wgpu::Device, label: Option<&str>, values: &[T]) -> Self {
+        Self::with_usage(device, label, values, wgpu::BufferUsages::empty())
+    }
+
+    /// Creates the buffer with additional usages (for example [wgpu::BufferUsages::COPY_SRC]).
+    pub fn with_usage(device: &wgpu::Device, label: Option<&str>, values: &[T], usage: wgpu::BufferUsages) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | usage,
+            contents: bytemuck::cast_slice(values),
+        });
+        Self {
+            values: RefCell::new(values.into()),
+            buffer,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.borrow().is_empty()
+    }
+
+    /// The value at `index` as of the last write.
+    pub fn get(&self, index: usize) -> T {
+        self.values.borrow()[index]
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub fn set(&self, queue: &wgpu::Queue, index: usize, value: T) {
+        self.values.borrow_mut()[index] = value;
+        let offset = (index * std::mem::size_of::<T>()) as wgpu::BufferAddress;
+        queue.write_buffer(&self.buffer, offset, bytemuck::bytes_of(&value));
+    }
+
+    /// Writes `values` starting at `start`.
+    pub fn write(&self, queue: &wgpu::Queue, start: usize, values: &[T]) {
+        let mut current = self.values.borrow_mut();
+        assert!(start + values.len() <= current.len(), "Write out of bounds: {}..{} (len: {})", start, start + values.len(), current.len());
+        current[start..start + values.len()].copy_from_slice(values);
+        let offset = (start * std::mem::size_of::<T>()) as wgpu::BufferAddress;
+        queue.write_buffer(&self.buffer, offset, bytemuck::cast_slice(values));
+    }
+
+    pub fn as_entire_binding(&self) -> wgpu::BindingResource<'_> {
+        self.buffer.as_entire_binding()
+    }
+
+    pub fn bind_group_entry(&self, binding: u32) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding,
+            resource: self.buffer.as_entire_binding(),
+        }
+    }
+
+    pub fn layout_entry(binding: u32, visibility: wgpu::ShaderStages, read_only: bool) -> wgpu::BindGroupLayoutEntry {
+        layout_entry(binding, visibility, wgpu::BufferBindingType::Storage { read_only })
+    }
+}
+
+/// Sets a (possibly nested) field of a [UniformBuffer] and writes only that field.
+/// ```rust, ignore
+/// write_field!(lighting, queue, ambient.intensity = 0.5);
+/// ```
+#[macro_export]
+macro_rules! write_field {
+    ($buffer:expr, $queue:expr, $($field:ident).+ = $value:expr) => {{
+        let buffer = &$buffer;
+        let mut data = buffer.get();
+        data.$($field).+ = $value;
+        let base = &data as *const _ as usize;
+        let offset = &data.$($field).+ as *const _ as usize - base;
+        let size = ::std::mem::size_of_val(&data.$($field).+);
+        buffer.set_unwritten(data);
+        buffer.write_range($queue, offset, size);
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligned_range_test() {
+        assert_eq!(aligned_range(0, 16, 32), 0..16);
+        // A single bool.
+        assert_eq!(aligned_range(41, 1, 48), 40..44);
+        assert_eq!(aligned_range(6, 4, 48), 4..12);
+        assert_eq!(aligned_range(30, 1, 32), 28..32);
+    }
+}
@@ -0,0 +1,154 @@
+/*
+An animated layer of clouds in the raytracer's sky.
+
+The clouds are fractal value noise on a horizontal plane at [Clouds::altitude]. Rays that miss the chunk
+and point up find where they cross the plane and read the noise there, which is then thresholded by
+[Clouds::coverage], from a clear sky at `0.0` to overcast at `1.0`. The clouds fade out towards the horizon,
+where the plane is far away.
+
+The wind scrolls the noise. [Clouds::advance] moves the offset by the wind every frame (see
+[crate::systems::CloudsSystem], which also has the keys), so changing the wind's speed or direction doesn't
+make the clouds jump. The offset isn't part of what resets the raytracer's accumulation (see
+[super::raytrace::Raytracer::set_clouds]), since the clouds move every frame.
+
+The clouds are multiplied with the sky tint of the day/night cycle (see
+[crate::day_night::DayNightCycle::sky_tint]), so they turn orange in the evening and dark blue at night,
+and their thin edges light up around the directional light. With [super::raytrace::SkyMode::Transparent],
+primary misses show the clouds with their opacity as alpha, over the raster skybox.
+*/
+
+use std::time::Duration;
+
+use bytemuck::NoUninit;
+use glam::{vec2, Vec2};
+
+pub const DEFAULT_COVERAGE: f32 = 0.45;
+/// Alt+Comma and Alt+Period change the coverage by this much.
+pub const COVERAGE_STEP: f32 = 0.05;
+/// In blocks per second.
+pub const DEFAULT_WIND_SPEED: f32 = 4.0;
+pub const MAX_WIND_SPEED: f32 = 32.0;
+/// Alt+Shift+Insert and Alt+Shift+Delete change the wind speed by this much.
+pub const WIND_SPEED_STEP: f32 = 2.0;
+/// Alt+Insert and Alt+Delete turn the wind by this much (radians).
+pub const WIND_DIRECTION_STEP: f32 = std::f32::consts::PI / 12.0;
+/// The height of the cloud layer in voxel space, well above the chunk.
+pub const DEFAULT_ALTITUDE: f32 = 160.0;
+/// The size of the largest features of the noise, in blocks.
+pub const DEFAULT_SCALE: f32 = 96.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clouds {
+    pub enabled: bool,
+    /// `0.0` is a clear sky, `1.0` is overcast.
+    pub coverage: f32,
+    /// The direction that the wind blows towards, in radians around the Y axis. `0.0` is +X.
+    pub wind_direction: f32,
+    /// In blocks per second.
+    pub wind_speed: f32,
+    pub altitude: f32,
+    pub scale: f32,
+    /// How far the wind has moved the clouds, in blocks.
+    offset: Vec2,
+}
+
+impl Clouds {
+    pub const DEFAULT: Self = Self {
+        enabled: true,
+        coverage: DEFAULT_COVERAGE,
+        wind_direction: 0.0,
+        wind_speed: DEFAULT_WIND_SPEED,
+        altitude: DEFAULT_ALTITUDE,
+        scale: DEFAULT_SCALE,
+        offset: Vec2::ZERO,
+    };
+
+    /// The wind's velocity on the XZ plane, in blocks per second.
+    pub fn wind(&self) -> Vec2 {
+        vec2(self.wind_direction.cos(), self.wind_direction.sin()) * self.wind_speed
+    }
+
+    /// The wind direction in degrees, in `0..360`.
+    pub fn wind_degrees(&self) -> f32 {
+        self.wind_direction.to_degrees().rem_euclid(360.0)
+    }
+
+    pub fn offset(&self) -> Vec2 {
+        self.offset
+    }
+
+    /// Moves the clouds with the wind.
+    pub fn advance(&mut self, delta_time: Duration) {
+        self.offset += self.wind() * delta_time.as_secs_f32();
+    }
+
+    /// Sets the coverage, clamped to `0.0..=1.0`.
+    pub fn set_coverage(&mut self, coverage: f32) {
+        self.coverage = coverage.clamp(0.0, 1.0);
+    }
+
+    /// Sets the wind speed, clamped to `0.0..=MAX_WIND_SPEED`.
+    pub fn set_wind_speed(&mut self, wind_speed: f32) {
+        self.wind_speed = wind_speed.clamp(0.0, MAX_WIND_SPEED);
+    }
+
+    pub fn to_gpu(&self) -> GpuClouds {
+        GpuClouds {
+            offset: self.offset,
+            coverage: self.coverage,
+            altitude: self.altitude,
+            scale: self.scale.max(1.0),
+            on: self.enabled as u32,
+            _padding: [0; 2],
+        }
+    }
+}
+
+impl Default for Clouds {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// The `Clouds` uniform of raytrace.wgsl.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, NoUninit)]
+pub struct GpuClouds {
+    offset: Vec2,
+    coverage: f32,
+    altitude: f32,
+    scale: f32,
+    on: u32,
+    _padding: [u32; 2],
+}
+
+impl GpuClouds {
+    /// Whether the clouds look different, apart from where the wind has moved them.
+    pub fn differs_from(&self, other: &Self) -> bool {
+        Self { offset: other.offset, ..*self } != *other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clouds_test() {
+        let mut clouds = Clouds { wind_direction: std::f32::consts::FRAC_PI_2, ..Clouds::DEFAULT };
+        clouds.advance(Duration::from_secs(2));
+        assert!(clouds.offset().abs_diff_eq(vec2(0.0, DEFAULT_WIND_SPEED * 2.0), 1e-4));
+        assert!((clouds.wind_degrees() - 90.0).abs() < 1e-3);
+
+        // Only the wind moved the clouds, so they don't count as changed.
+        let before = Clouds::DEFAULT.to_gpu();
+        assert!(!clouds.to_gpu().differs_from(&before));
+        clouds.set_coverage(2.0);
+        assert_eq!(clouds.coverage, 1.0);
+        assert!(clouds.to_gpu().differs_from(&before));
+        clouds.set_wind_speed(-1.0);
+        assert_eq!(clouds.wind(), Vec2::ZERO);
+
+        assert_eq!(std::mem::size_of::<GpuClouds>(), 32);
+    }
+}
@@ -0,0 +1,170 @@
+/*
+Color spaces of the renderer. Shaders always do their math in linear space. What they read and write
+depends on the texture format:
+
+    *UnormSrgb formats      Decoded to linear when sampled, encoded to sRGB when written (by the hardware).
+    Float formats           Stored as is. These hold linear HDR colors (the scene and the post chain).
+    Other formats           Stored as is, so the shader has to know which space the values are in.
+
+The surface uses an sRGB format when the adapter has one, so a pipeline that draws to it (or to an HDR
+target) outputs linear colors. The raytrace result is stored as linear values in a float texture, and
+vello renders sRGB encoded values into an `Rgba8Unorm` texture. The pipelines that draw those textures
+pick a [ColorConversion] for their target format, so the colors come out the same whether or not the
+surface is sRGB.
+
+[ColorPath] describes one of these steps for the color audit on the overlay.
+*/
+
+/// Creates a shader module from a WGSL file that uses `convert_color` (see `shaders/color.wgsl`), with
+/// its conversion selected through the `COLOR_CONVERSION` override.
+macro_rules! color_shader {
+    ($label:literal, $path:literal) => {
+        wgpu::ShaderModuleDescriptor {
+            label: Some($label),
+            source: wgpu::ShaderSource::Wgsl(::std::borrow::Cow::Borrowed(concat!(
+                include_str!("../shaders/color.wgsl"),
+                include_str!($path),
+            ))),
+        }
+    };
+}
+pub(crate) use color_shader;
+
+/// Converts an sRGB encoded channel to linear.
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts a linear channel to sRGB.
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    Linear,
+    Srgb,
+}
+
+impl ColorSpace {
+    /// The space of the values that a texture of `format` stores. Formats that aren't sRGB are assumed
+    /// to hold linear values, which isn't true of vello's output.
+    pub fn of_format(format: wgpu::TextureFormat) -> Self {
+        if format.is_srgb() {
+            ColorSpace::Srgb
+        } else {
+            ColorSpace::Linear
+        }
+    }
+}
+
+/// What a shader does to its colors before writing them to the target. Passed to the shaders as the
+/// `COLOR_CONVERSION` override constant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorConversion {
+    #[default]
+    None = 0,
+    EncodeSrgb = 1,
+    DecodeSrgb = 2,
+}
+
+/// Whether colors that a shader writes to a target of `format` end up linear: sRGB formats are decoded
+/// again when read, and float formats store the colors as is.
+pub fn stores_linear(format: wgpu::TextureFormat) -> bool {
+    use wgpu::TextureFormat as F;
+    format.is_srgb() || matches!(format, F::Rgba16Float | F::Rgba32Float | F::Rg11b10Ufloat)
+}
+
+impl ColorConversion {
+    /// The conversion for a shader whose colors are in `source` space, drawing to a target of `format`
+    /// that ends up on the surface (see [stores_linear]).
+    pub fn for_target(source: ColorSpace, format: wgpu::TextureFormat) -> Self {
+        match (source, stores_linear(format)) {
+            // The target encodes the output, or keeps it linear for the post chain.
+            (ColorSpace::Linear, true) => ColorConversion::None,
+            (ColorSpace::Srgb, true) => ColorConversion::DecodeSrgb,
+            // The output is displayed as is, so it has to be encoded already.
+            (ColorSpace::Linear, false) => ColorConversion::EncodeSrgb,
+            (ColorSpace::Srgb, false) => ColorConversion::None,
+        }
+    }
+
+    /// The pipeline constants that select the conversion in a shader.
+    pub fn constants(self) -> std::collections::HashMap<String, f64> {
+        std::collections::HashMap::from([(String::from("COLOR_CONVERSION"), self as u32 as f64)])
+    }
+}
+
+/// How the colors of one pipeline get from its input to its target, listed by the color audit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorPath {
+    pub name: &'static str,
+    /// The space of the colors that the shader reads.
+    pub input: ColorSpace,
+    pub target: wgpu::TextureFormat,
+    /// The conversion that the shader applies.
+    pub conversion: ColorConversion,
+}
+
+impl ColorPath {
+    /// A path whose shader converts as needed for the target, see [ColorConversion::for_target].
+    pub fn converted(name: &'static str, input: ColorSpace, target: wgpu::TextureFormat) -> Self {
+        Self {
+            name,
+            input,
+            target,
+            conversion: ColorConversion::for_target(input, target),
+        }
+    }
+
+    /// A path whose shader writes its input unchanged.
+    pub fn unconverted(name: &'static str, input: ColorSpace, target: wgpu::TextureFormat) -> Self {
+        Self {
+            name,
+            input,
+            target,
+            conversion: ColorConversion::None,
+        }
+    }
+
+    /// Whether the colors are displayed correctly, that is whether the shader's conversion is the one
+    /// that the target needs.
+    pub fn is_correct(&self) -> bool {
+        self.conversion == ColorConversion::for_target(self.input, self.target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_test() {
+        for value in [0.0, 0.002, 0.01, 0.2, 0.5, 0.9, 1.0] {
+            assert!((srgb_to_linear(linear_to_srgb(value)) - value).abs() < 1e-5);
+        }
+        assert!((srgb_to_linear(0.5) - 0.21404).abs() < 1e-4);
+
+        let srgb = wgpu::TextureFormat::Bgra8UnormSrgb;
+        let unorm = wgpu::TextureFormat::Rgba8Unorm;
+        assert_eq!(ColorSpace::of_format(srgb), ColorSpace::Srgb);
+        assert_eq!(ColorSpace::of_format(unorm), ColorSpace::Linear);
+        assert_eq!(ColorConversion::for_target(ColorSpace::Linear, srgb), ColorConversion::None);
+        assert_eq!(ColorConversion::for_target(ColorSpace::Linear, unorm), ColorConversion::EncodeSrgb);
+        assert_eq!(ColorConversion::for_target(ColorSpace::Srgb, srgb), ColorConversion::DecodeSrgb);
+        assert_eq!(ColorConversion::for_target(ColorSpace::Srgb, unorm), ColorConversion::None);
+        assert_eq!(ColorConversion::for_target(ColorSpace::Linear, wgpu::TextureFormat::Rgba16Float), ColorConversion::None);
+
+        // Unconverted paths are only right for targets that store linear colors.
+        assert!(ColorPath::unconverted("Voxels", ColorSpace::Linear, srgb).is_correct());
+        assert!(!ColorPath::unconverted("Voxels", ColorSpace::Linear, unorm).is_correct());
+    }
+}
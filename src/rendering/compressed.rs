@@ -0,0 +1,662 @@
+use std::path::Path;
+
+use image::RgbaImage;
+
+/*
+Block-compressed (BCn) textures loaded from KTX2 or DDS files. The blocks are uploaded as they are when
+the device has [wgpu::Features::TEXTURE_COMPRESSION_BC]. Otherwise BC1-BC5 are decompressed into an
+RGBA8 texture on the CPU (BC6H and BC7 can't be decompressed, so they need the feature).
+
+Only uncompressed containers are read: KTX2 files with supercompression (zstd, BasisLZ) are rejected.
+
+With the `texture-conversion` feature, [encode] converts images into BC1 (opaque) or BC3 (with alpha)
+DDS files, so that startup doesn't have to decode PNGs. See the `compress_texture` binary.
+*/
+
+#[derive(Debug, thiserror::Error)]
+pub enum CompressedError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid KTX2 file: {0}")]
+    Ktx2(#[from] ktx2::ParseError),
+    #[error("Invalid DDS file: {0}")]
+    Dds(#[from] ddsfile::Error),
+    #[error("Unsupported extension: {0:?}")]
+    UnsupportedExtension(Option<String>),
+    #[error("Unsupported texture format: {0}")]
+    UnsupportedFormat(String),
+    #[error("Supercompressed KTX2 files are not supported.")]
+    Supercompressed,
+    #[error("3D textures are not supported.")]
+    Volume,
+    #[error("The dimensions ({width}x{height}) must be multiples of 4.")]
+    NotBlockAligned {
+        width: u32,
+        height: u32,
+    },
+    #[error("Layer {index} has dimensions of {dimensions:?}, expected {expected:?}.")]
+    MismatchedDimensions {
+        index: u32,
+        dimensions: (u32, u32),
+        expected: (u32, u32),
+    },
+    #[error("Mip level {level} has {actual} bytes, expected at least {expected}.")]
+    Truncated {
+        level: u32,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("{0:?} can't be decompressed on the CPU, the device needs TEXTURE_COMPRESSION_BC.")]
+    NoFallback(wgpu::TextureFormat),
+}
+
+/// A block-compressed texture with its mip levels, as read from a KTX2 or DDS file.
+#[derive(Debug, Clone)]
+pub struct CompressedImage {
+    pub format: wgpu::TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    /// Array layers (cubemaps have six).
+    pub layer_count: u32,
+    /// The blocks of each mip level, with the layers of a level one after another.
+    pub levels: Vec<Vec<u8>>,
+}
+
+fn ktx2_format(format: ktx2::Format) -> Option<wgpu::TextureFormat> {
+    use ktx2::Format as K;
+    use wgpu::TextureFormat as W;
+    Some(match format {
+        K::BC1_RGB_UNORM_BLOCK | K::BC1_RGBA_UNORM_BLOCK => W::Bc1RgbaUnorm,
+        K::BC1_RGB_SRGB_BLOCK | K::BC1_RGBA_SRGB_BLOCK => W::Bc1RgbaUnormSrgb,
+        K::BC2_UNORM_BLOCK => W::Bc2RgbaUnorm,
+        K::BC2_SRGB_BLOCK => W::Bc2RgbaUnormSrgb,
+        K::BC3_UNORM_BLOCK => W::Bc3RgbaUnorm,
+        K::BC3_SRGB_BLOCK => W::Bc3RgbaUnormSrgb,
+        K::BC4_UNORM_BLOCK => W::Bc4RUnorm,
+        K::BC4_SNORM_BLOCK => W::Bc4RSnorm,
+        K::BC5_UNORM_BLOCK => W::Bc5RgUnorm,
+        K::BC5_SNORM_BLOCK => W::Bc5RgSnorm,
+        K::BC6H_UFLOAT_BLOCK => W::Bc6hRgbUfloat,
+        K::BC6H_SFLOAT_BLOCK => W::Bc6hRgbFloat,
+        K::BC7_UNORM_BLOCK => W::Bc7RgbaUnorm,
+        K::BC7_SRGB_BLOCK => W::Bc7RgbaUnormSrgb,
+        _ => return None,
+    })
+}
+
+fn dxgi_format(format: ddsfile::DxgiFormat) -> Option<wgpu::TextureFormat> {
+    use ddsfile::DxgiFormat as D;
+    use wgpu::TextureFormat as W;
+    Some(match format {
+        D::BC1_Typeless | D::BC1_UNorm => W::Bc1RgbaUnorm,
+        D::BC1_UNorm_sRGB => W::Bc1RgbaUnormSrgb,
+        D::BC2_Typeless | D::BC2_UNorm => W::Bc2RgbaUnorm,
+        D::BC2_UNorm_sRGB => W::Bc2RgbaUnormSrgb,
+        D::BC3_Typeless | D::BC3_UNorm => W::Bc3RgbaUnorm,
+        D::BC3_UNorm_sRGB => W::Bc3RgbaUnormSrgb,
+        D::BC4_Typeless | D::BC4_UNorm => W::Bc4RUnorm,
+        D::BC4_SNorm => W::Bc4RSnorm,
+        D::BC5_Typeless | D::BC5_UNorm => W::Bc5RgUnorm,
+        D::BC5_SNorm => W::Bc5RgSnorm,
+        D::BC6H_Typeless | D::BC6H_UF16 => W::Bc6hRgbUfloat,
+        D::BC6H_SF16 => W::Bc6hRgbFloat,
+        D::BC7_Typeless | D::BC7_UNorm => W::Bc7RgbaUnorm,
+        D::BC7_UNorm_sRGB => W::Bc7RgbaUnormSrgb,
+        _ => return None,
+    })
+}
+
+fn d3d_format(format: ddsfile::D3DFormat) -> Option<wgpu::TextureFormat> {
+    Some(match format {
+        ddsfile::D3DFormat::DXT1 => wgpu::TextureFormat::Bc1RgbaUnorm,
+        ddsfile::D3DFormat::DXT2 | ddsfile::D3DFormat::DXT3 => wgpu::TextureFormat::Bc2RgbaUnorm,
+        ddsfile::D3DFormat::DXT4 | ddsfile::D3DFormat::DXT5 => wgpu::TextureFormat::Bc3RgbaUnorm,
+        _ => return None,
+    })
+}
+
+/// The size of a mip level in pixels.
+fn mip_size(width: u32, height: u32, level: u32) -> (u32, u32) {
+    ((width >> level).max(1), (height >> level).max(1))
+}
+
+/// The number of bytes in one layer of a mip level.
+fn layer_byte_len(format: wgpu::TextureFormat, width: u32, height: u32) -> usize {
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format.block_copy_size(None).expect("BCn formats have a block size.");
+    (width.div_ceil(block_width) * height.div_ceil(block_height) * block_size) as usize
+}
+
+impl CompressedImage {
+    fn new(format: wgpu::TextureFormat, width: u32, height: u32, layer_count: u32, levels: Vec<Vec<u8>>) -> Result<Self, CompressedError> {
+        if !width.is_multiple_of(4) || !height.is_multiple_of(4) {
+            return Err(CompressedError::NotBlockAligned { width, height });
+        }
+        let image = Self {
+            format,
+            width,
+            height,
+            layer_count,
+            levels,
+        };
+        for (level, data) in image.levels.iter().enumerate() {
+            let expected = image.layer_byte_len(level as u32) * layer_count as usize;
+            if data.len() < expected {
+                return Err(CompressedError::Truncated {
+                    level: level as u32,
+                    expected,
+                    actual: data.len(),
+                });
+            }
+        }
+        Ok(image)
+    }
+
+    pub fn from_ktx2(bytes: &[u8]) -> Result<Self, CompressedError> {
+        let reader = ktx2::Reader::new(bytes)?;
+        let header = reader.header();
+        if header.supercompression_scheme.is_some() {
+            return Err(CompressedError::Supercompressed);
+        }
+        if header.pixel_depth > 1 {
+            return Err(CompressedError::Volume);
+        }
+        let format = header.format
+            .and_then(ktx2_format)
+            .ok_or_else(|| CompressedError::UnsupportedFormat(format!("{:?}", header.format)))?;
+        let layer_count = header.layer_count.max(1) * header.face_count;
+        let levels = reader.levels().map(|level| level.data.to_vec()).collect();
+        Self::new(format, header.pixel_width, header.pixel_height, layer_count, levels)
+    }
+
+    pub fn from_dds(bytes: &[u8]) -> Result<Self, CompressedError> {
+        let dds = ddsfile::Dds::read(bytes)?;
+        if dds.get_depth() > 1 {
+            return Err(CompressedError::Volume);
+        }
+        let format = dds.get_dxgi_format()
+            .and_then(dxgi_format)
+            .or_else(|| dds.get_d3d_format().and_then(d3d_format))
+            .ok_or_else(|| CompressedError::UnsupportedFormat(match dds.get_dxgi_format() {
+                Some(format) => format!("{format:?}"),
+                None => format!("{:?}", dds.get_d3d_format()),
+            }))?;
+        let (width, height) = (dds.get_width(), dds.get_height());
+        let layer_count = dds.get_num_array_layers();
+        let level_count = dds.get_num_mipmap_levels();
+        // DDS stores each layer with all of its mip levels, so the levels are gathered from every layer.
+        let level_lens = (0..level_count)
+            .map(|level| {
+                let (width, height) = mip_size(width, height, level);
+                layer_byte_len(format, width, height)
+            })
+            .collect::<Vec<_>>();
+        let layer_stride = level_lens.iter().sum::<usize>();
+        let mut levels = vec![Vec::new(); level_count as usize];
+        for layer in 0..layer_count as usize {
+            let mut offset = layer * layer_stride;
+            for (level, &len) in level_lens.iter().enumerate() {
+                let Some(data) = dds.data.get(offset..offset + len) else {
+                    return Err(CompressedError::Truncated {
+                        level: level as u32,
+                        expected: offset + len,
+                        actual: dds.data.len(),
+                    });
+                };
+                levels[level].extend_from_slice(data);
+                offset += len;
+            }
+        }
+        Self::new(format, width, height, layer_count, levels)
+    }
+
+    /// Reads a `.ktx2` or `.dds` file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, CompressedError> {
+        let path = path.as_ref();
+        let ktx2 = Self::is_ktx2(path)?;
+        Self::from_bytes(&std::fs::read(path)?, ktx2)
+    }
+
+    /// Whether `path` is a KTX2 file (rather than DDS), going by its extension. Other extensions are
+    /// unsupported.
+    pub fn is_ktx2(path: &Path) -> Result<bool, CompressedError> {
+        let extension = path.extension().and_then(|extension| extension.to_str()).map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("ktx2") => Ok(true),
+            Some("dds") => Ok(false),
+            _ => Err(CompressedError::UnsupportedExtension(extension)),
+        }
+    }
+
+    /// Reads KTX2 (if `ktx2` is true) or DDS from memory.
+    pub fn from_bytes(bytes: &[u8], ktx2: bool) -> Result<Self, CompressedError> {
+        if ktx2 {
+            Self::from_ktx2(bytes)
+        } else {
+            Self::from_dds(bytes)
+        }
+    }
+
+    pub fn mip_level_count(&self) -> u32 {
+        self.levels.len() as u32
+    }
+
+    /// The size of a mip level in pixels.
+    pub fn mip_size(&self, level: u32) -> (u32, u32) {
+        mip_size(self.width, self.height, level)
+    }
+
+    fn layer_byte_len(&self, level: u32) -> usize {
+        let (width, height) = self.mip_size(level);
+        layer_byte_len(self.format, width, height)
+    }
+
+    /// The blocks of one layer of a mip level.
+    pub fn layer_data(&self, level: u32, layer: u32) -> &[u8] {
+        let len = self.layer_byte_len(level);
+        let start = layer as usize * len;
+        &self.levels[level as usize][start..start + len]
+    }
+
+    /// The format that [CompressedImage::decompress] produces.
+    pub fn decompressed_format(&self) -> wgpu::TextureFormat {
+        if self.format.is_srgb() {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        }
+    }
+
+    /// Decompresses one layer of a mip level. Single and dual channel formats (BC4 and BC5) fill the
+    /// missing color channels with zero, like sampling them on the GPU does.
+    pub fn decompress(&self, level: u32, layer: u32) -> Result<RgbaImage, CompressedError> {
+        use wgpu::TextureFormat as W;
+        let block_size = match self.format {
+            W::Bc1RgbaUnorm | W::Bc1RgbaUnormSrgb | W::Bc4RUnorm => 8,
+            W::Bc2RgbaUnorm | W::Bc2RgbaUnormSrgb | W::Bc3RgbaUnorm | W::Bc3RgbaUnormSrgb | W::Bc5RgUnorm => 16,
+            format => return Err(CompressedError::NoFallback(format)),
+        };
+        let (width, height) = self.mip_size(level);
+        let blocks_wide = width.div_ceil(4);
+        let mut image = RgbaImage::new(width, height);
+        for (index, block) in self.layer_data(level, layer).chunks_exact(block_size).enumerate() {
+            let texels = match self.format {
+                W::Bc1RgbaUnorm | W::Bc1RgbaUnormSrgb => decode_color_block(block, true),
+                W::Bc2RgbaUnorm | W::Bc2RgbaUnormSrgb => {
+                    let mut texels = decode_color_block(&block[8..], false);
+                    let alpha = u64::from_le_bytes(block[..8].try_into().unwrap());
+                    for (i, texel) in texels.iter_mut().enumerate() {
+                        texel[3] = ((alpha >> (4 * i)) & 0xF) as u8 * 17;
+                    }
+                    texels
+                }
+                W::Bc3RgbaUnorm | W::Bc3RgbaUnormSrgb => {
+                    let mut texels = decode_color_block(&block[8..], false);
+                    for (texel, alpha) in texels.iter_mut().zip(decode_alpha_block(&block[..8])) {
+                        texel[3] = alpha;
+                    }
+                    texels
+                }
+                W::Bc4RUnorm => decode_alpha_block(block).map(|red| [red, 0, 0, 255]),
+                _ => {
+                    let red = decode_alpha_block(&block[..8]);
+                    let green = decode_alpha_block(&block[8..]);
+                    std::array::from_fn(|i| [red[i], green[i], 0, 255])
+                }
+            };
+            let block_x = (index as u32 % blocks_wide) * 4;
+            let block_y = (index as u32 / blocks_wide) * 4;
+            for (i, texel) in texels.into_iter().enumerate() {
+                let (x, y) = (block_x + i as u32 % 4, block_y + i as u32 / 4);
+                // Mip levels smaller than a block only use part of it.
+                if x < width && y < height {
+                    image.put_pixel(x, y, image::Rgba(texel));
+                }
+            }
+        }
+        Ok(image)
+    }
+
+    /// Creates a texture with every layer and mip level. The blocks are uploaded directly if the device
+    /// supports BC compression, otherwise they're decompressed into a [CompressedImage::decompressed_format]
+    /// texture. The texture's format tells which one was used.
+    pub fn create_texture(&self, device: &wgpu::Device, queue: &wgpu::Queue, label: Option<&str>) -> Result<wgpu::Texture, CompressedError> {
+        let compressed = device.features().contains(wgpu::Features::TEXTURE_COMPRESSION_BC);
+        let format = if compressed { self.format } else { self.decompressed_format() };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: self.layer_count,
+            },
+            mip_level_count: self.mip_level_count(),
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        for level in 0..self.mip_level_count() {
+            let (width, height) = self.mip_size(level);
+            if compressed {
+                // Compressed copies cover whole blocks, even when the mip level is smaller than a block.
+                let (blocks_wide, blocks_high) = (width.div_ceil(4), height.div_ceil(4));
+                let block_size = self.format.block_copy_size(None).expect("BCn formats have a block size.");
+                queue.write_texture(
+                    wgpu::TexelCopyTextureInfoBase {
+                        texture: &texture,
+                        mip_level: level,
+                        origin: wgpu::Origin3d::ZERO,
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    &self.levels[level as usize],
+                    wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(blocks_wide * block_size),
+                        rows_per_image: Some(blocks_high),
+                    },
+                    wgpu::Extent3d {
+                        width: blocks_wide * 4,
+                        height: blocks_high * 4,
+                        depth_or_array_layers: self.layer_count,
+                    },
+                );
+            } else {
+                for layer in 0..self.layer_count {
+                    let image = self.decompress(level, layer)?;
+                    queue.write_texture(
+                        wgpu::TexelCopyTextureInfoBase {
+                            texture: &texture,
+                            mip_level: level,
+                            origin: wgpu::Origin3d { x: 0, y: 0, z: layer },
+                            aspect: wgpu::TextureAspect::All,
+                        },
+                        &image,
+                        wgpu::TexelCopyBufferLayout {
+                            offset: 0,
+                            bytes_per_row: Some(4 * width),
+                            rows_per_image: Some(height),
+                        },
+                        wgpu::Extent3d {
+                            width,
+                            height,
+                            depth_or_array_layers: 1,
+                        },
+                    );
+                }
+            }
+        }
+        Ok(texture)
+    }
+}
+
+fn expand_565(color: u16) -> [u8; 4] {
+    let r = ((color >> 11) & 0x1F) as u8;
+    let g = ((color >> 5) & 0x3F) as u8;
+    let b = (color & 0x1F) as u8;
+    [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2), 255]
+}
+
+/// Decodes the 8 byte color block of BC1-BC3. `bc1` allows the three color mode with transparent black,
+/// BC2 and BC3 always interpolate four colors.
+fn decode_color_block(block: &[u8], bc1: bool) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (e0, e1) = (expand_565(c0), expand_565(c1));
+    let mix = |a: u8, b: u8, wa: u16, wb: u16| ((a as u16 * wa + b as u16 * wb) / (wa + wb)) as u8;
+    let palette = if c0 > c1 || !bc1 {
+        [
+            e0,
+            e1,
+            std::array::from_fn(|i| if i == 3 { 255 } else { mix(e0[i], e1[i], 2, 1) }),
+            std::array::from_fn(|i| if i == 3 { 255 } else { mix(e0[i], e1[i], 1, 2) }),
+        ]
+    } else {
+        [
+            e0,
+            e1,
+            std::array::from_fn(|i| if i == 3 { 255 } else { mix(e0[i], e1[i], 1, 1) }),
+            [0, 0, 0, 0],
+        ]
+    };
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    std::array::from_fn(|i| palette[((indices >> (2 * i)) & 3) as usize])
+}
+
+/// Decodes the 8 byte alpha block of BC3, which BC4 and BC5 use for their channels.
+fn decode_alpha_block(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (block[0] as u16, block[1] as u16);
+    let palette: [u8; 8] = if a0 > a1 {
+        std::array::from_fn(|i| match i {
+            0 => a0 as u8,
+            1 => a1 as u8,
+            _ => (((8 - i as u16) * a0 + (i as u16 - 1) * a1) / 7) as u8,
+        })
+    } else {
+        std::array::from_fn(|i| match i {
+            0 => a0 as u8,
+            1 => a1 as u8,
+            6 => 0,
+            7 => 255,
+            _ => (((6 - i as u16) * a0 + (i as u16 - 1) * a1) / 5) as u8,
+        })
+    };
+    let mut bits = [0u8; 8];
+    bits[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bits);
+    std::array::from_fn(|i| palette[((indices >> (3 * i)) & 7) as usize])
+}
+
+#[cfg(feature = "texture-conversion")]
+pub mod encode {
+    use image::RgbaImage;
+
+    use super::{mip_size, CompressedError};
+
+    /*
+    A simple BC1/BC3 encoder for offline conversion. Each block uses the corners of its color bounding
+    box as endpoints, which is fast and good enough for the voxel textures, but lower quality than
+    dedicated encoders.
+    */
+
+    fn to_565(color: [u8; 4]) -> u16 {
+        ((color[0] as u16 >> 3) << 11) | ((color[1] as u16 >> 2) << 5) | (color[2] as u16 >> 3)
+    }
+
+    fn encode_color_block(texels: &[[u8; 4]; 16]) -> [u8; 8] {
+        let mut min = [255u8; 3];
+        let mut max = [0u8; 3];
+        for texel in texels {
+            for i in 0..3 {
+                min[i] = min[i].min(texel[i]);
+                max[i] = max[i].max(texel[i]);
+            }
+        }
+        let (mut c0, mut c1) = (to_565([max[0], max[1], max[2], 255]), to_565([min[0], min[1], min[2], 255]));
+        if c0 < c1 {
+            std::mem::swap(&mut c0, &mut c1);
+        }
+        let mut block = [0u8; 8];
+        block[..2].copy_from_slice(&c0.to_le_bytes());
+        block[2..4].copy_from_slice(&c1.to_le_bytes());
+        if c0 == c1 {
+            return block;
+        }
+        let palette = super::decode_color_block(&block, true);
+        let mut indices = 0u32;
+        for (i, texel) in texels.iter().enumerate() {
+            let distance = |color: &[u8; 4]| (0..3).map(|c| (color[c] as i32 - texel[c] as i32).pow(2)).sum::<i32>();
+            let index = (0..4).min_by_key(|&index| distance(&palette[index])).unwrap();
+            indices |= (index as u32) << (2 * i);
+        }
+        block[4..].copy_from_slice(&indices.to_le_bytes());
+        block
+    }
+
+    fn encode_alpha_block(texels: &[[u8; 4]; 16]) -> [u8; 8] {
+        let max = texels.iter().map(|texel| texel[3]).max().unwrap();
+        let min = texels.iter().map(|texel| texel[3]).min().unwrap();
+        let mut block = [max, min, 0, 0, 0, 0, 0, 0];
+        if max == min {
+            return block;
+        }
+        let palette = super::decode_alpha_block(&block);
+        let mut indices = 0u64;
+        for (i, texel) in texels.iter().enumerate() {
+            let index = (0..8).min_by_key(|&index| (palette[index] as i32 - texel[3] as i32).abs()).unwrap();
+            indices |= (index as u64) << (3 * i);
+        }
+        block[2..].copy_from_slice(&indices.to_le_bytes()[..6]);
+        block
+    }
+
+    /// Encodes an image as BC1 blocks, or BC3 if `alpha` is true. Texels outside of the image repeat the edge.
+    pub fn encode_blocks(image: &RgbaImage, alpha: bool) -> Vec<u8> {
+        let (width, height) = image.dimensions();
+        let mut data = Vec::new();
+        for block_y in (0..height).step_by(4) {
+            for block_x in (0..width).step_by(4) {
+                let texels = std::array::from_fn(|i| {
+                    let x = (block_x + i as u32 % 4).min(width - 1);
+                    let y = (block_y + i as u32 / 4).min(height - 1);
+                    image.get_pixel(x, y).0
+                });
+                if alpha {
+                    data.extend_from_slice(&encode_alpha_block(&texels));
+                }
+                data.extend_from_slice(&encode_color_block(&texels));
+            }
+        }
+        data
+    }
+
+    /// Encodes the images (the layers of a texture array) into a DDS file with a full mip chain. Uses BC3
+    /// if any texel is translucent and BC1 otherwise.
+    pub fn encode_dds(layers: &[&RgbaImage], srgb: bool) -> Result<ddsfile::Dds, CompressedError> {
+        let (width, height) = layers.first().map(|image| image.dimensions()).unwrap_or((0, 0));
+        if width == 0 || !width.is_multiple_of(4) || !height.is_multiple_of(4) {
+            return Err(CompressedError::NotBlockAligned { width, height });
+        }
+        let alpha = layers.iter().any(|image| image.pixels().any(|texel| texel.0[3] < 255));
+        let format = match (alpha, srgb) {
+            (false, false) => ddsfile::DxgiFormat::BC1_UNorm,
+            (false, true) => ddsfile::DxgiFormat::BC1_UNorm_sRGB,
+            (true, false) => ddsfile::DxgiFormat::BC3_UNorm,
+            (true, true) => ddsfile::DxgiFormat::BC3_UNorm_sRGB,
+        };
+        let level_count = crate::rendering::mipmaps::full_mip_level_count(width, height);
+        let mut dds = ddsfile::Dds::new_dxgi(ddsfile::NewDxgiParams {
+            height,
+            width,
+            depth: None,
+            format,
+            mipmap_levels: Some(level_count),
+            array_layers: Some(layers.len() as u32),
+            caps2: None,
+            is_cubemap: false,
+            resource_dimension: ddsfile::D3D10ResourceDimension::Texture2D,
+            alpha_mode: ddsfile::AlphaMode::Straight,
+        })?;
+        dds.data.clear();
+        for (index, &image) in layers.iter().enumerate() {
+            if image.dimensions() != (width, height) {
+                return Err(CompressedError::MismatchedDimensions {
+                    index: index as u32,
+                    dimensions: image.dimensions(),
+                    expected: (width, height),
+                });
+            }
+            for level in 0..level_count {
+                let (mip_width, mip_height) = mip_size(width, height, level);
+                let data = if level == 0 {
+                    encode_blocks(image, alpha)
+                } else {
+                    encode_blocks(&image::imageops::resize(image, mip_width, mip_height, image::imageops::FilterType::Triangle), alpha)
+                };
+                dds.data.extend_from_slice(&data);
+            }
+        }
+        Ok(dds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_blocks_test() {
+        // Red and blue endpoints with texels 0, 1, 2, 3 in the first row.
+        let red = 0xF800u16.to_le_bytes();
+        let blue = 0x001Fu16.to_le_bytes();
+        let block = [red[0], red[1], blue[0], blue[1], 0b11100100, 0, 0, 0];
+        let texels = decode_color_block(&block, true);
+        assert_eq!(texels[0], [255, 0, 0, 255]);
+        assert_eq!(texels[1], [0, 0, 255, 255]);
+        assert_eq!(texels[2], [170, 0, 85, 255]);
+        assert_eq!(texels[3], [85, 0, 170, 255]);
+        assert_eq!(texels[4], [255, 0, 0, 255]);
+        // Swapped endpoints use the three color mode in BC1, but not in BC3.
+        let block = [blue[0], blue[1], red[0], red[1], 0b11100100, 0, 0, 0];
+        assert_eq!(decode_color_block(&block, true)[3], [0, 0, 0, 0]);
+        assert_eq!(decode_color_block(&block, false)[3], [170, 0, 85, 255]);
+
+        // Alpha indices 0, 1 and 2 for the first texels.
+        let alpha = decode_alpha_block(&[255, 0, 0b10_001_000, 0, 0, 0, 0, 0]);
+        assert_eq!(alpha[..3], [255, 0, 218]);
+        // Indices 6 and 7 are 0 and 255 in the six value mode.
+        let alpha = decode_alpha_block(&[10, 20, 0b111_110, 0, 0, 0, 0, 0]);
+        assert_eq!(alpha[..3], [0, 255, 10]);
+    }
+
+    #[test]
+    fn dds_test() {
+        // A 4x8 BC1 texture with one mip level, built by hand.
+        let mut dds = ddsfile::Dds::new_dxgi(ddsfile::NewDxgiParams {
+            height: 8,
+            width: 4,
+            depth: None,
+            format: ddsfile::DxgiFormat::BC1_UNorm_sRGB,
+            mipmap_levels: None,
+            array_layers: None,
+            caps2: None,
+            is_cubemap: false,
+            resource_dimension: ddsfile::D3D10ResourceDimension::Texture2D,
+            alpha_mode: ddsfile::AlphaMode::Straight,
+        }).unwrap();
+        let white = 0xFFFFu16.to_le_bytes();
+        dds.data = [[0u8; 8], [white[0], white[1], 0, 0, 0, 0, 0, 0]].concat();
+        let mut bytes = Vec::new();
+        dds.write(&mut bytes).unwrap();
+
+        let image = CompressedImage::from_dds(&bytes).unwrap();
+        assert_eq!(image.format, wgpu::TextureFormat::Bc1RgbaUnormSrgb);
+        assert_eq!((image.width, image.height, image.layer_count, image.mip_level_count()), (4, 8, 1, 1));
+        assert_eq!(image.decompressed_format(), wgpu::TextureFormat::Rgba8UnormSrgb);
+        let decoded = image.decompress(0, 0).unwrap();
+        assert_eq!(decoded.get_pixel(0, 0).0, [0, 0, 0, 255]);
+        assert_eq!(decoded.get_pixel(3, 7).0, [255, 255, 255, 255]);
+
+        assert!(matches!(CompressedImage::from_ktx2(&bytes), Err(CompressedError::Ktx2(_))));
+        assert!(matches!(CompressedImage::load("texture.png"), Err(CompressedError::UnsupportedExtension(_))));
+        assert_eq!(CompressedImage::is_ktx2(Path::new("texture.KTX2")).ok(), Some(true));
+        assert_eq!(CompressedImage::is_ktx2(Path::new("texture.dds")).ok(), Some(false));
+    }
+
+    #[cfg(feature = "texture-conversion")]
+    #[test]
+    fn encode_test() {
+        let image = RgbaImage::from_fn(8, 4, |x, _| if x < 4 { image::Rgba([255, 0, 0, 255]) } else { image::Rgba([0, 0, 255, 128]) });
+        let dds = encode::encode_dds(&[&image], false).unwrap();
+        let mut bytes = Vec::new();
+        dds.write(&mut bytes).unwrap();
+        let compressed = CompressedImage::from_dds(&bytes).unwrap();
+        assert_eq!(compressed.format, wgpu::TextureFormat::Bc3RgbaUnorm);
+        assert_eq!(compressed.mip_level_count(), 4);
+        let decoded = compressed.decompress(0, 0).unwrap();
+        assert_eq!(decoded.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(decoded.get_pixel(7, 3).0, [0, 0, 255, 128]);
+        assert_eq!(compressed.decompress(3, 0).unwrap().dimensions(), (1, 1));
+    }
+}
@@ -0,0 +1,193 @@
+/*
+Frame captures for RenderDoc. When the app is launched from RenderDoc (or has its library injected), wgpu
+starts and ends captures through RenderDoc's in-application API, so a capture can cover exactly the frames
+that were asked for instead of ending at the next present like RenderDoc's own hotkey. Without RenderDoc,
+wgpu only logs a warning.
+
+    1. `request` schedules a capture of the next frames.
+    2. `begin_frame` at the start of `State::render`, before anything is encoded.
+    3. `end_frame` after the frame has been submitted and presented.
+
+The encoders and passes are split into debug groups named after the [FrameSection]s (see [debug_group]),
+so every capture has the same structure in the event browser.
+*/
+
+/// The parts of a frame, in the order they're encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameSection {
+    /// Chunk edits copied in through the staging ring.
+    Upload,
+    Raytrace,
+    /// The picture-in-picture view.
+    Inset,
+    /// The rasterized view of the chunk, see [super::raster].
+    Raster,
+    /// The vector overlay, rendered by vello.
+    VectorOverlay,
+    /// Everything drawn in the scene pass, on top of the raytrace result.
+    Scene,
+    Post,
+    /// Everything drawn in the UI pass.
+    Ui,
+}
+
+impl FrameSection {
+    /// The name of the debug group.
+    pub fn label(self) -> &'static str {
+        match self {
+            FrameSection::Upload => "Chunk Upload",
+            FrameSection::Raytrace => "Raytrace",
+            FrameSection::Inset => "Picture in Picture",
+            FrameSection::Raster => "Raster View",
+            FrameSection::VectorOverlay => "Vector Overlay",
+            FrameSection::Scene => "Scene",
+            FrameSection::Post => "Post Processing",
+            FrameSection::Ui => "UI",
+        }
+    }
+}
+
+/// Command encoders and passes, which all have debug groups.
+pub trait DebugGroups {
+    fn push_group(&mut self, label: &str);
+    fn pop_group(&mut self);
+}
+
+impl DebugGroups for wgpu::CommandEncoder {
+    fn push_group(&mut self, label: &str) {
+        self.push_debug_group(label);
+    }
+
+    fn pop_group(&mut self) {
+        self.pop_debug_group();
+    }
+}
+
+impl DebugGroups for wgpu::RenderPass<'_> {
+    fn push_group(&mut self, label: &str) {
+        self.push_debug_group(label);
+    }
+
+    fn pop_group(&mut self) {
+        self.pop_debug_group();
+    }
+}
+
+impl DebugGroups for wgpu::ComputePass<'_> {
+    fn push_group(&mut self, label: &str) {
+        self.push_debug_group(label);
+    }
+
+    fn pop_group(&mut self) {
+        self.pop_debug_group();
+    }
+}
+
+/// Runs `encode` inside a debug group, so that the group is always popped.
+pub fn debug_group<E: DebugGroups, R, F: FnOnce(&mut E) -> R>(encoder: &mut E, label: &str, encode: F) -> R {
+    encoder.push_group(label);
+    let result = encode(encoder);
+    encoder.pop_group();
+    result
+}
+
+/// Captures of whole frames, see the module comment.
+#[derive(Debug, Default)]
+pub struct FrameCapture {
+    /// Frames to capture, starting with the next one.
+    requested: u32,
+    /// Frames left in the running capture.
+    remaining: u32,
+    /// The number of captures that have been started.
+    captures: u32,
+}
+
+impl FrameCapture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Captures the next `frames` frames (at least one) in a single capture. Ignored while capturing.
+    pub fn request(&mut self, frames: u32) {
+        if !self.is_capturing() {
+            self.requested = frames.max(1);
+        }
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.remaining > 0
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.requested > 0
+    }
+
+    pub fn captures(&self) -> u32 {
+        self.captures
+    }
+
+    /// Returns `true` if a capture should start with this frame.
+    fn advance_begin(&mut self) -> bool {
+        if self.is_capturing() || !self.is_pending() {
+            return false;
+        }
+        self.remaining = std::mem::take(&mut self.requested);
+        self.captures += 1;
+        true
+    }
+
+    /// Returns `true` if the capture should end with this frame.
+    fn advance_end(&mut self) -> bool {
+        if !self.is_capturing() {
+            return false;
+        }
+        self.remaining -= 1;
+        self.remaining == 0
+    }
+
+    /// Starts the capture if one was requested. Call before anything of the frame is encoded.
+    pub fn begin_frame(&mut self, device: &wgpu::Device) {
+        if self.advance_begin() {
+            log::info!("Starting frame capture {} ({} frames).", self.captures, self.remaining);
+            device.start_capture();
+        }
+    }
+
+    /// Ends the capture after its last frame. Call after the frame has been submitted.
+    pub fn end_frame(&mut self, device: &wgpu::Device) {
+        if self.advance_end() {
+            device.stop_capture();
+            log::info!("Finished frame capture {}.", self.captures);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capture_test() {
+        let mut capture = FrameCapture::new();
+        assert!(!capture.advance_begin());
+        assert!(!capture.advance_end());
+
+        capture.request(2);
+        assert!(capture.is_pending() && !capture.is_capturing());
+        assert!(capture.advance_begin());
+        // Requests while capturing are ignored.
+        capture.request(5);
+        assert!(!capture.is_pending());
+        assert!(!capture.advance_end());
+        assert!(!capture.advance_begin());
+        assert!(capture.advance_end());
+        assert!(!capture.is_capturing());
+        assert_eq!(capture.captures(), 1);
+
+        // Zero frames still captures one.
+        capture.request(0);
+        assert!(capture.advance_begin());
+        assert!(capture.advance_end());
+        assert_eq!(capture.captures(), 2);
+    }
+}
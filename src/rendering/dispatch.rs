@@ -0,0 +1,131 @@
+use wgpu::util::DeviceExt;
+
+use super::{bindings::{BindGroupBuilder, Bindings}, buffers::UniformBuffer, raytrace::WorkgroupSize};
+
+/*
+Workgroup counts for the compute passes that cover a 2D target.
+
+[dispatch_2d] computes the counts on the CPU from the size of the target when the pass is recorded. That
+is enough as long as whoever records the pass knows the target's size. [IndirectDispatch] instead has a
+single invocation read the target's dimensions on the GPU and write the counts into a buffer, which the
+pass then dispatches with `dispatch_workgroups_indirect`, so the dispatch follows the target even when its
+size is decided somewhere else (such as by the dynamic render scale, see [super::render_scale]).
+
+Indirect dispatch needs [wgpu::DownlevelFlags::INDIRECT_EXECUTION], see [IndirectDispatch::is_supported].
+*/
+
+/// The number of `workgroup_size` workgroups that cover a `width` x `height` target.
+pub fn workgroup_count(width: u32, height: u32, workgroup_size: WorkgroupSize) -> (u32, u32) {
+    (width.div_ceil(workgroup_size.x), height.div_ceil(workgroup_size.y))
+}
+
+/// Dispatches enough `workgroup_size` workgroups to cover a `width` x `height` target.
+pub fn dispatch_2d(compute_pass: &mut wgpu::ComputePass, width: u32, height: u32, workgroup_size: WorkgroupSize) {
+    let (x, y) = workgroup_count(width, height, workgroup_size);
+    compute_pass.dispatch_workgroups(x, y, 1);
+}
+
+pub struct IndirectDispatch {
+    /// The [wgpu::util::DispatchIndirectArgs] that [IndirectDispatch::compute] writes.
+    args: wgpu::Buffer,
+    workgroup_size: UniformBuffer<[u32; 2]>,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl IndirectDispatch {
+    /// Whether the adapter can dispatch from a buffer.
+    pub fn is_supported(downlevel: &wgpu::DownlevelCapabilities) -> bool {
+        downlevel.flags.contains(wgpu::DownlevelFlags::INDIRECT_EXECUTION)
+    }
+
+    /// `target` is a view of the (float) texture that the dispatch has to cover, which must be bindable
+    /// for sampling.
+    pub fn new(device: &wgpu::Device, target: &wgpu::TextureView, workgroup_size: WorkgroupSize) -> Self {
+        let args = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Indirect Dispatch Args Buffer"),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT,
+            contents: wgpu::util::DispatchIndirectArgs { x: 0, y: 0, z: 0 }.as_bytes(),
+        });
+        let workgroup_size = UniformBuffer::new(device, Some("Indirect Dispatch Workgroup Size Buffer"), [workgroup_size.x, workgroup_size.y]);
+
+        let bind_group_layout = BindGroupBuilder::new()
+            .label("Indirect Dispatch Bind Group Layout")
+            .texture(0, wgpu::ShaderStages::COMPUTE, wgpu::TextureSampleType::Float { filterable: false }, wgpu::TextureViewDimension::D2)
+            .uniform(1, wgpu::ShaderStages::COMPUTE)
+            .storage(2, wgpu::ShaderStages::COMPUTE, false)
+            .build(device);
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, target, &workgroup_size, &args);
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../shaders/dispatch_size.wgsl"));
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Indirect Dispatch Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Indirect Dispatch Compute Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        Self {
+            args,
+            workgroup_size,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        target: &wgpu::TextureView,
+        workgroup_size: &UniformBuffer<[u32; 2]>,
+        args: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        Bindings::new()
+            .texture_view(0, target)
+            .buffer(1, workgroup_size.buffer())
+            .buffer(2, args)
+            .build(device, Some("Indirect Dispatch Bind Group"), layout)
+    }
+
+    /// Call this whenever the target texture is recreated.
+    pub fn set_target(&mut self, device: &wgpu::Device, target: &wgpu::TextureView) {
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, target, &self.workgroup_size, &self.args);
+    }
+
+    /// Writes the workgroup counts for the target's current size. Call this before [IndirectDispatch::dispatch]
+    /// in the same pass (or an earlier one). It changes the pipeline, so set the dispatched pipeline and its
+    /// bind groups afterwards.
+    pub fn compute(&self, compute_pass: &mut wgpu::ComputePass) {
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+    }
+
+    /// Dispatches the current pipeline with the counts from the last [IndirectDispatch::compute].
+    pub fn dispatch(&self, compute_pass: &mut wgpu::ComputePass) {
+        compute_pass.dispatch_workgroups_indirect(&self.args, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn workgroup_count_test() {
+        assert_eq!(workgroup_count(1920, 1080, WorkgroupSize::new(16, 16)), (120, 68));
+        assert_eq!(workgroup_count(1, 1, WorkgroupSize::new(32, 4)), (1, 1));
+        assert_eq!(workgroup_count(0, 0, WorkgroupSize::DEFAULT), (0, 0));
+    }
+}
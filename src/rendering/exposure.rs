@@ -0,0 +1,280 @@
+use std::time::Instant;
+
+use bytemuck::{Pod, Zeroable};
+
+use super::{
+    bindings::{BindGroupBuilder, Bindings},
+    buffers::{StorageBuffer, UniformBuffer},
+    post::{begin_post_pass, post_shader, PostChain, PostEffect},
+};
+
+/*
+Auto exposure meters the HDR image on the GPU and scales it so that its average luminance lands on
+`key` (middle gray by default). Nothing is read back, so metering never stalls the frame:
+
+    1. `reduce_main` samples every `SAMPLE_STRIDE`th texel and sums `log2(luminance)` per workgroup
+       into `partials`.
+    2. `adapt_main` (a single workgroup) sums the partials into the log average, computes the target
+       exposure `key / average` and moves the exposure in `state` towards it.
+    3. The fragment pass multiplies the image by the exposure in `state`.
+
+The log average keeps a few very bright texels (the sun, emissive voxels) from darkening the whole
+image. The exposure adapts in stops (log2 space), so brightening and darkening take equally long.
+[Tonemap](super::post::Tonemap) runs afterwards, and its exposure acts as compensation.
+*/
+
+/// Matches `REDUCE_WORKGROUP_SIZE` in exposure.wgsl.
+const REDUCE_WORKGROUP_SIZE: u32 = 16;
+/// Matches `SAMPLE_STRIDE` in exposure.wgsl.
+const SAMPLE_STRIDE: u32 = 4;
+/// Middle gray, the luminance that the average is exposed to.
+pub const DEFAULT_KEY: f32 = 0.18;
+/// Lower bound of the luminance that is metered, matching `MIN_LUMINANCE` in exposure.wgsl.
+const MIN_LUMINANCE: f32 = 1e-4;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+struct ExposureParams {
+    key: f32,
+    min_exposure: f32,
+    max_exposure: f32,
+    adaptation_rate: f32,
+    delta_time: f32,
+    partial_count: u32,
+    _padding: [u32; 2],
+}
+
+/// The number of workgroups of `reduce_main` for an image of `width` x `height`.
+fn reduce_workgroups(width: u32, height: u32) -> (u32, u32) {
+    let texels = REDUCE_WORKGROUP_SIZE * SAMPLE_STRIDE;
+    (width.div_ceil(texels).max(1), height.div_ceil(texels).max(1))
+}
+
+/// The exposure that maps the `average` luminance to `key`, as computed by `adapt_main`.
+pub fn target_exposure(average: f32, key: f32, min_exposure: f32, max_exposure: f32) -> f32 {
+    (key / average.max(MIN_LUMINANCE)).clamp(min_exposure, max_exposure)
+}
+
+/// Moves `current` towards `target` in log2 space, as `adapt_main` does over `delta_time` seconds.
+pub fn adapt_exposure(current: f32, target: f32, rate: f32, delta_time: f32) -> f32 {
+    if current <= 0.0 {
+        return target;
+    }
+    let blend = 1.0 - (-delta_time * rate).exp();
+    (current.log2() + (target.log2() - current.log2()) * blend).exp2()
+}
+
+/// Scales the image so that its average luminance is exposed to `key`, see the module comment.
+/// Push it before [Tonemap](super::post::Tonemap).
+pub struct AutoExposure {
+    pub enabled: bool,
+    /// The luminance that the average is mapped to.
+    pub key: f32,
+    pub min_exposure: f32,
+    pub max_exposure: f32,
+    /// How fast the exposure adapts. After `1.0 / adaptation_rate` seconds, about two thirds of the
+    /// difference (in stops) is covered.
+    pub adaptation_rate: f32,
+    size: (u32, u32),
+    compute_layout: wgpu::BindGroupLayout,
+    params: UniformBuffer<ExposureParams>,
+    partials: StorageBuffer<[f32; 2]>,
+    /// `ExposureState` in exposure.wgsl: the exposure (`0.0` until the first frame was metered) and the
+    /// average luminance.
+    state: StorageBuffer<[f32; 2]>,
+    compute_bind_group: wgpu::BindGroup,
+    apply_bind_group: wgpu::BindGroup,
+    reduce_pipeline: wgpu::ComputePipeline,
+    adapt_pipeline: wgpu::ComputePipeline,
+    apply_pipeline: wgpu::RenderPipeline,
+    last_prepare: Option<Instant>,
+}
+
+impl AutoExposure {
+    pub fn new(device: &wgpu::Device, chain: &PostChain) -> Self {
+        let (key, min_exposure, max_exposure, adaptation_rate) = (DEFAULT_KEY, 0.05, 16.0, 1.5);
+        let size = chain.size();
+        let compute_layout = BindGroupBuilder::new()
+            .label("Auto Exposure Compute Bind Group Layout")
+            .uniform(0, wgpu::ShaderStages::COMPUTE)
+            .storage(1, wgpu::ShaderStages::COMPUTE, false)
+            .storage(2, wgpu::ShaderStages::COMPUTE, false)
+            .build(device);
+        let apply_layout = BindGroupBuilder::new()
+            .label("Auto Exposure Apply Bind Group Layout")
+            .storage(0, wgpu::ShaderStages::FRAGMENT, true)
+            .build(device);
+        let params = UniformBuffer::new(device, Some("Auto Exposure Params"), ExposureParams {
+            key,
+            min_exposure,
+            max_exposure,
+            adaptation_rate,
+            delta_time: 0.0,
+            partial_count: 0,
+            _padding: [0; 2],
+        });
+        let partials = Self::create_partials(device, size);
+        let state = StorageBuffer::new(device, Some("Auto Exposure State"), &[[0.0; 2]]);
+        let compute_bind_group = Self::create_compute_bind_group(device, &compute_layout, &params, &partials, &state);
+        let apply_bind_group = Bindings::new()
+            .buffer(0, state.buffer())
+            .build(device, Some("Auto Exposure Apply Bind Group"), &apply_layout);
+
+        let shader = device.create_shader_module(post_shader!("Auto Exposure Shader", "../shaders/post/exposure.wgsl"));
+        let compute_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Auto Exposure Pipeline Layout"),
+            bind_group_layouts: &[chain.input_layout(), &compute_layout],
+            push_constant_ranges: &[],
+        });
+        let [reduce_pipeline, adapt_pipeline] = ["reduce_main", "adapt_main"].map(|entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Auto Exposure Pipeline"),
+                layout: Some(&compute_pipeline_layout),
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache: None,
+            })
+        });
+        let apply_pipeline = chain.create_pipeline(
+            device,
+            "Auto Exposure",
+            post_shader!("Auto Exposure Apply Shader", "../shaders/post/exposure_apply.wgsl"),
+            &[&apply_layout],
+        );
+        Self {
+            enabled: false,
+            key,
+            min_exposure,
+            max_exposure,
+            adaptation_rate,
+            size,
+            compute_layout,
+            params,
+            partials,
+            state,
+            compute_bind_group,
+            apply_bind_group,
+            reduce_pipeline,
+            adapt_pipeline,
+            apply_pipeline,
+            last_prepare: None,
+        }
+    }
+
+    fn create_partials(device: &wgpu::Device, (width, height): (u32, u32)) -> StorageBuffer<[f32; 2]> {
+        let (x, y) = reduce_workgroups(width, height);
+        StorageBuffer::new(device, Some("Auto Exposure Partials"), &vec![[0.0; 2]; (x * y) as usize])
+    }
+
+    fn create_compute_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        params: &UniformBuffer<ExposureParams>,
+        partials: &StorageBuffer<[f32; 2]>,
+        state: &StorageBuffer<[f32; 2]>,
+    ) -> wgpu::BindGroup {
+        Bindings::new()
+            .buffer(0, params.buffer())
+            .buffer(1, partials.buffer())
+            .buffer(2, state.buffer())
+            .build(device, Some("Auto Exposure Compute Bind Group"), layout)
+    }
+}
+
+impl PostEffect for AutoExposure {
+    fn name(&self) -> &str {
+        "Auto Exposure"
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Keeps the settings. The metered exposure starts over.
+    fn recreate(&mut self, device: &wgpu::Device, chain: &PostChain) {
+        *self = Self {
+            enabled: self.enabled,
+            key: self.key,
+            min_exposure: self.min_exposure,
+            max_exposure: self.max_exposure,
+            adaptation_rate: self.adaptation_rate,
+            ..Self::new(device, chain)
+        };
+    }
+
+    fn prepare(&mut self, queue: &wgpu::Queue) {
+        let now = Instant::now();
+        // The first frame after being enabled shouldn't adapt over the time that it was disabled.
+        let delta_time = self.last_prepare.map_or(0.0, |last| (now - last).as_secs_f32().min(0.25));
+        self.last_prepare = Some(now);
+        let min_exposure = self.min_exposure.max(1e-4);
+        let params = ExposureParams {
+            key: self.key.max(1e-4),
+            min_exposure,
+            max_exposure: self.max_exposure.max(min_exposure),
+            adaptation_rate: self.adaptation_rate.max(0.0),
+            delta_time,
+            partial_count: self.partials.len() as u32,
+            _padding: [0; 2],
+        };
+        if self.params.get() != params {
+            self.params.write(queue, params);
+        }
+    }
+
+    fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.size = (width, height);
+        self.partials = Self::create_partials(device, self.size);
+        self.compute_bind_group = Self::create_compute_bind_group(device, &self.compute_layout, &self.params, &self.partials, &self.state);
+    }
+
+    fn render(&self, render_pass: &mut wgpu::RenderPass, input: &wgpu::BindGroup) {
+        render_pass.set_pipeline(&self.apply_pipeline);
+        render_pass.set_bind_group(0, input, &[]);
+        render_pass.set_bind_group(1, &self.apply_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn encode(&self, encoder: &mut wgpu::CommandEncoder, input: &wgpu::BindGroup, target: &wgpu::TextureView) {
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Auto Exposure Metering Pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_bind_group(0, input, &[]);
+            compute_pass.set_bind_group(1, &self.compute_bind_group, &[]);
+            compute_pass.set_pipeline(&self.reduce_pipeline);
+            let (x, y) = reduce_workgroups(self.size.0, self.size.1);
+            compute_pass.dispatch_workgroups(x, y, 1);
+            compute_pass.set_pipeline(&self.adapt_pipeline);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+        }
+        let mut render_pass = begin_post_pass(encoder, "Auto Exposure Pass", target, wgpu::LoadOp::Clear(wgpu::Color::BLACK));
+        self.render(&mut render_pass, input);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exposure_test() {
+        assert_eq!(reduce_workgroups(1920, 1080), (30, 17));
+        assert_eq!(reduce_workgroups(64, 64), (1, 1));
+        assert_eq!(reduce_workgroups(0, 0), (1, 1));
+
+        assert!((target_exposure(0.36, DEFAULT_KEY, 0.01, 100.0) - 0.5).abs() < 1e-6);
+        assert_eq!(target_exposure(0.0, DEFAULT_KEY, 0.01, 100.0), 100.0);
+        assert_eq!(target_exposure(1000.0, DEFAULT_KEY, 0.01, 100.0), 0.01);
+
+        // The first metered frame snaps to the target.
+        assert_eq!(adapt_exposure(0.0, 4.0, 1.0, 0.0), 4.0);
+        assert_eq!(adapt_exposure(1.0, 4.0, 1.0, 0.0), 1.0);
+        // Halfway in stops is 2.0.
+        let half = std::f32::consts::LN_2;
+        assert!((adapt_exposure(1.0, 4.0, 1.0, half) - 2.0).abs() < 1e-5);
+        assert!((adapt_exposure(4.0, 1.0, 1.0, half) - 2.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,217 @@
+use std::{collections::VecDeque, time::Duration};
+
+use vello::{kurbo::{Affine, BezPath, Line, Rect, Stroke}, peniko::{Color, Fill}, Scene};
+
+/*
+The frame graph plots the times of the last frames on the UI layer (see [super::velvet::Velvet]), newest
+on the right:
+
+    Frame           The time between the starts of two frames, including the frame pacing wait.
+    Update          State::update and the systems.
+    Render          State::render, which includes the wait for the surface texture.
+    Raytrace (GPU)  The raytracer's compute pass, from the GPU timestamps. It lags a few frames behind,
+                    and frames without a new result don't have a point.
+
+Guide lines mark 16.6 ms (60 FPS) and 33.3 ms (30 FPS). The vertical range grows in steps of the 33.3 ms
+guide to fit the slowest frame, so the guides stay in the same place unless there are spikes.
+*/
+
+/// The times of one frame, see the module comment.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FrameSample {
+    pub frame: Duration,
+    pub update: Duration,
+    pub render: Duration,
+    pub raytrace_gpu: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Series {
+    Frame,
+    Update,
+    Render,
+    RaytraceGpu,
+}
+
+impl Series {
+    pub const ALL: [Series; 4] = [Series::Frame, Series::Update, Series::Render, Series::RaytraceGpu];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Series::Frame => "Frame",
+            Series::Update => "Update",
+            Series::Render => "Render",
+            Series::RaytraceGpu => "Raytrace (GPU)",
+        }
+    }
+
+    /// The line color as 8-bit sRGB.
+    pub fn color(self) -> [u8; 3] {
+        match self {
+            Series::Frame => [235, 235, 235],
+            Series::Update => [90, 210, 110],
+            Series::Render => [90, 160, 255],
+            Series::RaytraceGpu => [255, 160, 60],
+        }
+    }
+
+    fn time(self, sample: &FrameSample) -> Option<Duration> {
+        match self {
+            Series::Frame => Some(sample.frame),
+            Series::Update => Some(sample.update),
+            Series::Render => Some(sample.render),
+            Series::RaytraceGpu => sample.raytrace_gpu,
+        }
+    }
+}
+
+/// The times of 60 FPS and 30 FPS.
+pub const GUIDE_TIMES: [Duration; 2] = [Duration::from_micros(16_667), Duration::from_micros(33_333)];
+
+pub struct FrameGraph {
+    samples: VecDeque<FrameSample>,
+    capacity: usize,
+    pub visible: bool,
+    /// Where the graph is drawn on the UI layer, in its pixels.
+    pub rect: Rect,
+}
+
+impl FrameGraph {
+    pub const DEFAULT_CAPACITY: usize = 240;
+
+    pub fn new(capacity: usize) -> Self {
+        assert_ne!(capacity, 0, "Capacity must be greater than 0.");
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            visible: true,
+            rect: Rect::new(10.0, 570.0, 370.0, 710.0),
+        }
+    }
+
+    /// Adds the newest frame, dropping the oldest one if the graph is full.
+    pub fn push(&mut self, sample: FrameSample) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn samples(&self) -> impl Iterator<Item = &FrameSample> {
+        self.samples.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// The time at the top of the graph: the longest time of any series, rounded up to a multiple of the
+    /// 33.3 ms guide.
+    pub fn max_time(&self) -> Duration {
+        let guide = GUIDE_TIMES[1];
+        let longest = self.samples.iter()
+            .flat_map(|sample| Series::ALL.map(|series| series.time(sample)))
+            .flatten()
+            .max()
+            .unwrap_or_default();
+        guide * (longest.as_secs_f64() / guide.as_secs_f64()).ceil().max(1.0) as u32
+    }
+
+    /// The height of `time` in `rect`, clamped to the rect.
+    fn time_y(&self, rect: Rect, time: Duration, max_time: Duration) -> f64 {
+        let fraction = (time.as_secs_f64() / max_time.as_secs_f64()).min(1.0);
+        rect.y1 - fraction * rect.height()
+    }
+
+    /// The points of `series` in `rect`. The newest sample is on the right edge and each sample is one
+    /// `capacity`th of the width apart. Samples without a time are skipped.
+    pub fn points(&self, series: Series, rect: Rect) -> Vec<(f64, f64)> {
+        let max_time = self.max_time();
+        let step = rect.width() / (self.capacity.max(2) - 1) as f64;
+        let offset = self.capacity - self.samples.len();
+        self.samples.iter()
+            .enumerate()
+            .filter_map(|(index, sample)| {
+                let time = series.time(sample)?;
+                Some((rect.x0 + (offset + index) as f64 * step, self.time_y(rect, time, max_time)))
+            })
+            .collect()
+    }
+
+    /// Draws the background, the guide lines and a line for every series.
+    pub fn draw(&self, scene: &mut Scene) {
+        if !self.visible {
+            return;
+        }
+        let rect = self.rect;
+        scene.fill(Fill::NonZero, Affine::IDENTITY, Color::from_rgba8(0, 0, 0, 160), None, &rect);
+        let max_time = self.max_time();
+        let guide_stroke = Stroke::new(1.0).with_dashes(0.0, [4.0, 4.0]);
+        for time in GUIDE_TIMES {
+            let y = self.time_y(rect, time, max_time);
+            let line = Line::new((rect.x0, y), (rect.x1, y));
+            scene.stroke(&guide_stroke, Affine::IDENTITY, Color::from_rgba8(255, 255, 255, 90), None, &line);
+        }
+        let stroke = Stroke::new(1.5);
+        for series in Series::ALL {
+            let points = self.points(series, rect);
+            let Some((&first, rest)) = points.split_first() else {
+                continue;
+            };
+            let mut path = BezPath::new();
+            path.move_to(first);
+            for &point in rest {
+                path.line_to(point);
+            }
+            let [r, g, b] = series.color();
+            scene.stroke(&stroke, Affine::IDENTITY, Color::from_rgb8(r, g, b), None, &path);
+        }
+    }
+}
+
+impl Default for FrameGraph {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_graph_test() {
+        let ms = Duration::from_millis;
+        let mut graph = FrameGraph::new(3);
+        assert_eq!(graph.max_time(), GUIDE_TIMES[1]);
+        graph.push(FrameSample { frame: ms(10), update: ms(1), render: ms(2), raytrace_gpu: None });
+        graph.push(FrameSample { frame: ms(20), update: ms(1), render: ms(2), raytrace_gpu: Some(ms(5)) });
+        assert_eq!(graph.len(), 2);
+
+        // The newest sample is on the right edge, the missing raytrace time is skipped.
+        let rect = Rect::new(0.0, 0.0, 100.0, 100.0);
+        let frame = graph.points(Series::Frame, rect);
+        assert_eq!(frame.len(), 2);
+        assert!((frame[0].0 - 50.0).abs() < 1e-9 && (frame[1].0 - 100.0).abs() < 1e-9);
+        let expected = 100.0 - 20.0 / 33.333 * 100.0;
+        assert!((frame[1].1 - expected).abs() < 1e-2);
+        assert_eq!(graph.points(Series::RaytraceGpu, rect).len(), 1);
+
+        // The range grows in steps of the 33.3 ms guide, and old samples are dropped.
+        graph.push(FrameSample { frame: ms(40), ..Default::default() });
+        assert_eq!(graph.max_time(), GUIDE_TIMES[1] * 2);
+        graph.push(FrameSample::default());
+        graph.push(FrameSample::default());
+        graph.push(FrameSample::default());
+        assert_eq!(graph.len(), 3);
+        assert_eq!(graph.max_time(), GUIDE_TIMES[1]);
+    }
+}
@@ -0,0 +1,270 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::math::average::AverageBuffer;
+
+use super::{debug_capture::debug_group, timestamps::{GpuTimer, TimedFrame, DEFAULT_FRAMES_IN_FLIGHT, TIMESTAMPS_PER_SCOPE}};
+
+/*
+A thin wrapper around the command encoders of a frame, so that every pass gets the same treatment without
+repeating it where the pass is recorded:
+
+    let mut frame = FrameGraphLite::new(&device, "Frame Encoder", Some(&profiler));
+    frame.compute_pass("Raytrace", |compute_pass| raytracer.compute(compute_pass, None));
+    frame.submit(&queue, "Render Encoder");
+    frame.render_pass("UI", &[Some(attachment)], |render_pass| ...);
+    queue.submit(Some(frame.finish(Some(&mut profiler))));
+    profiler.map();
+
+Each pass is labelled, wrapped in a debug group of the same name (see [super::debug_capture]) and, with a
+[PassProfiler], timed with timestamps at the start and end of the pass. The timestamps are resolved by
+[FrameGraphLite::finish] and read back a few frames later without stalling, like [GpuTimer], which gives the
+per-pass breakdown of [PassProfiler::breakdown] without any bookkeeping where the passes are recorded.
+
+Only the passes that are begun through the wrapper are timed. Work that records its own passes on the
+encoder (the post chain, for example) can still be put in a debug group with [FrameGraphLite::group].
+*/
+
+/// The number of passes that can be timed per frame. The passes beyond it are still recorded, untimed.
+pub const MAX_TIMED_PASSES: u32 = 16;
+/// The number of frames that the times of each pass are averaged over.
+const AVERAGE_FRAMES: usize = 100;
+
+/// The passes that were timed in a frame, in the order of their scopes.
+#[derive(Debug, Clone)]
+struct PassScopes {
+    capacity: u32,
+    labels: Vec<&'static str>,
+}
+
+impl PassScopes {
+    fn new(capacity: u32) -> Self {
+        Self {
+            capacity,
+            labels: Vec::new(),
+        }
+    }
+
+    /// The scope for the pass named `label`, or `None` if every scope was taken.
+    fn push(&mut self, label: &'static str) -> Option<u32> {
+        let index = self.labels.len() as u32;
+        if index >= self.capacity {
+            return None;
+        }
+        self.labels.push(label);
+        Some(index)
+    }
+}
+
+/// The average GPU time of each pass, in the order the passes first appeared.
+#[derive(Debug, Clone, Default)]
+struct PassTimes {
+    passes: Vec<(&'static str, AverageBuffer<Duration>)>,
+}
+
+impl PassTimes {
+    /// Adds the times of a frame. Passes with the same label in one frame are added up.
+    fn record(&mut self, labels: &[&'static str], times: &[Option<Duration>]) {
+        let mut totals: Vec<(&'static str, Duration)> = Vec::new();
+        for (&label, time) in labels.iter().zip(times) {
+            let Some(time) = *time else {
+                continue;
+            };
+            match totals.iter_mut().find(|(name, _)| *name == label) {
+                Some((_, total)) => *total += time,
+                None => totals.push((label, time)),
+            }
+        }
+        for (label, total) in totals {
+            match self.passes.iter_mut().find(|(name, _)| *name == label) {
+                Some((_, average)) => {
+                    average.push(total);
+                }
+                None => self.passes.push((label, AverageBuffer::new(AVERAGE_FRAMES, total))),
+            }
+        }
+    }
+}
+
+/// Times the passes of [FrameGraphLite].
+pub struct PassProfiler {
+    timer: GpuTimer,
+    /// The labels of the frames that were resolved but not collected yet, by frame number.
+    pending: VecDeque<(u64, Vec<&'static str>)>,
+    times: PassTimes,
+}
+
+impl PassProfiler {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        Self {
+            timer: GpuTimer::with_scopes(device, queue, "Pass Profiler", MAX_TIMED_PASSES, DEFAULT_FRAMES_IN_FLIGHT),
+            pending: VecDeque::new(),
+            times: PassTimes::default(),
+        }
+    }
+
+    /// Copies the timestamps of `scopes` into a readback buffer, see [GpuTimer::resolve_scopes].
+    fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder, scopes: PassScopes) {
+        if let Some(frame) = self.timer.resolve_scopes(encoder, scopes.labels.len() as u32) {
+            self.pending.push_back((frame, scopes.labels));
+        }
+    }
+
+    /// Starts mapping the timestamps of the frame. Call this after the last encoder was submitted.
+    pub fn map(&mut self) {
+        self.timer.map();
+    }
+
+    /// Reads the times that have arrived, see [GpuTimer::collect_scopes]. Call this once per frame.
+    pub fn collect(&mut self, device: &wgpu::Device) {
+        for TimedFrame { frame, scopes } in self.timer.collect_scopes(device) {
+            // Frames that failed to map are skipped over.
+            while self.pending.front().is_some_and(|(pending, _)| *pending < frame) {
+                self.pending.pop_front();
+            }
+            if let Some((_, labels)) = self.pending.pop_front() {
+                self.times.record(&labels, &scopes);
+            }
+        }
+    }
+
+    /// The average GPU time of each pass, in the order the passes first appeared.
+    pub fn breakdown(&self) -> impl Iterator<Item = (&'static str, Duration)> + '_ {
+        self.times.passes.iter().map(|(label, average)| (*label, average.average()))
+    }
+}
+
+pub struct FrameGraphLite {
+    device: wgpu::Device,
+    encoder: wgpu::CommandEncoder,
+    /// The profiler's query set, if the passes are timed.
+    query_set: Option<wgpu::QuerySet>,
+    scopes: PassScopes,
+}
+
+impl FrameGraphLite {
+    /// Begins the first encoder of the frame. The passes are timed if there is a `profiler`, which has to
+    /// be passed to [FrameGraphLite::finish] too.
+    pub fn new(device: &wgpu::Device, label: &str, profiler: Option<&PassProfiler>) -> Self {
+        Self {
+            device: device.clone(),
+            encoder: device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some(label),
+            }),
+            query_set: profiler.map(|profiler| profiler.timer.query_set().clone()),
+            scopes: PassScopes::new(profiler.map_or(0, |profiler| profiler.timer.scope_count())),
+        }
+    }
+
+    /// The current encoder, for work that isn't a pass (such as copies).
+    pub fn encoder(&mut self) -> &mut wgpu::CommandEncoder {
+        &mut self.encoder
+    }
+
+    /// Runs `encode` inside a debug group on the encoder.
+    pub fn group<R>(&mut self, label: &str, encode: impl FnOnce(&mut wgpu::CommandEncoder) -> R) -> R {
+        debug_group(&mut self.encoder, label, encode)
+    }
+
+    /// The query set and the timestamp indices of a new scope named `label`, as `(beginning, end)`.
+    fn timestamps<'a>(query_set: Option<&'a wgpu::QuerySet>, scopes: &mut PassScopes, label: &'static str) -> Option<(&'a wgpu::QuerySet, u32, u32)> {
+        let query_set = query_set?;
+        let scope = scopes.push(label)?;
+        Some((query_set, scope * TIMESTAMPS_PER_SCOPE, scope * TIMESTAMPS_PER_SCOPE + 1))
+    }
+
+    /// Records a compute pass named `label`.
+    pub fn compute_pass<R>(&mut self, label: &'static str, encode: impl FnOnce(&mut wgpu::ComputePass) -> R) -> R {
+        self.encoder.push_debug_group(label);
+        let pass_label = format!("{label} Compute Pass");
+        let timestamp_writes = Self::timestamps(self.query_set.as_ref(), &mut self.scopes, label).map(|(query_set, beginning, end)| wgpu::ComputePassTimestampWrites {
+            query_set,
+            beginning_of_pass_write_index: Some(beginning),
+            end_of_pass_write_index: Some(end),
+        });
+        let mut compute_pass = self.encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some(&pass_label),
+            timestamp_writes,
+        });
+        let result = encode(&mut compute_pass);
+        drop(compute_pass);
+        self.encoder.pop_debug_group();
+        result
+    }
+
+    /// Records a render pass named `label` that draws to `color_attachments`.
+    pub fn render_pass<R>(
+        &mut self,
+        label: &'static str,
+        color_attachments: &[Option<wgpu::RenderPassColorAttachment>],
+        encode: impl FnOnce(&mut wgpu::RenderPass) -> R,
+    ) -> R {
+        let mut render_pass = self.begin_render_pass(label, color_attachments);
+        let result = encode(&mut render_pass);
+        self.end_render_pass(render_pass);
+        result
+    }
+
+    /// Like [FrameGraphLite::render_pass], for passes whose recording needs what the attachments borrow.
+    /// The pass has to be given back to [FrameGraphLite::end_render_pass].
+    pub fn begin_render_pass(&mut self, label: &'static str, color_attachments: &[Option<wgpu::RenderPassColorAttachment>]) -> wgpu::RenderPass<'static> {
+        self.encoder.push_debug_group(label);
+        let pass_label = format!("{label} Render Pass");
+        let timestamp_writes = Self::timestamps(self.query_set.as_ref(), &mut self.scopes, label).map(|(query_set, beginning, end)| wgpu::RenderPassTimestampWrites {
+            query_set,
+            beginning_of_pass_write_index: Some(beginning),
+            end_of_pass_write_index: Some(end),
+        });
+        self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(&pass_label),
+            color_attachments,
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes,
+        }).forget_lifetime()
+    }
+
+    pub fn end_render_pass(&mut self, render_pass: wgpu::RenderPass<'static>) {
+        drop(render_pass);
+        self.encoder.pop_debug_group();
+    }
+
+    /// Submits what was recorded so far, so that the GPU can start on it, and continues in a new encoder
+    /// named `label`.
+    pub fn submit(&mut self, queue: &wgpu::Queue, label: &str) {
+        let encoder = std::mem::replace(&mut self.encoder, self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some(label),
+        }));
+        queue.submit(Some(encoder.finish()));
+    }
+
+    /// Resolves the timestamps of the passes into the `profiler` and finishes the last encoder.
+    pub fn finish(mut self, profiler: Option<&mut PassProfiler>) -> wgpu::CommandBuffer {
+        if let Some(profiler) = profiler {
+            profiler.resolve(&mut self.encoder, self.scopes);
+        }
+        self.encoder.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pass_times_test() {
+        let mut scopes = PassScopes::new(3);
+        assert_eq!(scopes.push("Raytrace"), Some(0));
+        assert_eq!(scopes.push("Scene"), Some(1));
+        assert_eq!(scopes.push("Raytrace"), Some(2));
+        // Beyond the capacity the passes aren't timed.
+        assert_eq!(scopes.push("UI"), None);
+
+        let mut times = PassTimes::default();
+        let ms = Duration::from_millis;
+        times.record(&scopes.labels, &[Some(ms(2)), Some(ms(1)), Some(ms(3))]);
+        times.record(&["Scene", "UI"], &[Some(ms(3)), None]);
+        let breakdown = times.passes.iter().map(|(label, average)| (*label, average.average())).collect::<Vec<_>>();
+        // Passes with the same label are added up, and passes without a time are left out.
+        assert_eq!(breakdown, [("Raytrace", ms(5)), ("Scene", ms(2))]);
+    }
+}
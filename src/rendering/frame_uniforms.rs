@@ -0,0 +1,148 @@
+/*
+Values that change every frame, in one uniform buffer that every shader can read.
+
+[State](crate::state::State) owns the [FrameUniformsBindGroup] and advances it once per frame at the start
+of rendering. The same buffer is bound to the voxel pipelines and the raster view (as their own bind group),
+and to the raytracer's data bind group (see [super::raytrace::Raytracer::new]), so the effects of both
+renderers see the same time and frame index.
+
+The jitter is a sub-pixel offset from a Halton (2, 3) sequence, which covers the pixel evenly over a few
+frames. It's only set while TAA is on (see [super::taa]), when the raytracer offsets its primary rays by it,
+and zero otherwise.
+*/
+
+use std::time::Duration;
+
+use bytemuck::NoUninit;
+use glam::{vec2, UVec2, Vec2};
+
+use super::{bindings::{BindGroupBuilder, Bindings}, buffers::UniformBuffer};
+
+/// The time is wrapped to this many seconds so that it keeps its precision. Animations jump once when it
+/// wraps.
+pub const TIME_PERIOD: f32 = 3600.0;
+/// The jitter repeats after this many frames.
+pub const JITTER_PERIOD: u32 = 16;
+
+/// The `FrameUniforms` uniform of the shaders.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, NoUninit)]
+pub struct FrameUniforms {
+    /// Seconds since the first frame, wrapped to [TIME_PERIOD].
+    pub time: f32,
+    /// Seconds since the previous frame.
+    pub delta_time: f32,
+    /// Counts every frame.
+    pub frame: u32,
+    _padding: u32,
+    /// The size of the surface in pixels.
+    pub resolution: Vec2,
+    /// The camera's sub-pixel offset for this frame, in pixels in `-0.5..0.5`. Zero without jitter.
+    pub jitter: Vec2,
+}
+
+impl FrameUniforms {
+    pub const ZERO: Self = Self {
+        time: 0.0,
+        delta_time: 0.0,
+        frame: 0,
+        _padding: 0,
+        resolution: Vec2::ZERO,
+        jitter: Vec2::ZERO,
+    };
+
+    /// The uniforms of the next frame, `delta_time` later. The jitter is zero unless `jittered`.
+    pub fn next(self, delta_time: Duration, resolution: UVec2, jittered: bool) -> Self {
+        let frame = self.frame.wrapping_add(1);
+        Self {
+            time: (self.time + delta_time.as_secs_f32()).rem_euclid(TIME_PERIOD),
+            delta_time: delta_time.as_secs_f32(),
+            frame,
+            resolution: resolution.as_vec2(),
+            jitter: if jittered { jitter(frame) } else { Vec2::ZERO },
+            ..self
+        }
+    }
+}
+
+impl Default for FrameUniforms {
+    fn default() -> Self {
+        Self::ZERO
+    }
+}
+
+/// The radical inverse of `index` in `base`, in `0..1`.
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+/// The sub-pixel jitter of `frame`, in `-0.5..0.5` on both axes.
+pub fn jitter(frame: u32) -> Vec2 {
+    // The sequence starts at 1, since index 0 is the corner of the pixel.
+    let index = frame % JITTER_PERIOD + 1;
+    vec2(halton(index, 2), halton(index, 3)) - 0.5
+}
+
+pub struct FrameUniformsBindGroup {
+    pub buffer: UniformBuffer<FrameUniforms>,
+    pub bind_group: wgpu::BindGroup,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl FrameUniformsBindGroup {
+    pub fn new(device: &wgpu::Device, uniforms: FrameUniforms) -> Self {
+        let buffer = UniformBuffer::new(device, Some("Frame Uniforms Buffer"), uniforms);
+        let bind_group_layout = BindGroupBuilder::new()
+            .label("Frame Uniforms Bind Group Layout")
+            .uniform(0, wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT)
+            .build(device);
+        let bind_group = Bindings::new()
+            .buffer(0, buffer.buffer())
+            .build(device, Some("Frame Uniforms Bind Group"), &bind_group_layout);
+        Self {
+            buffer,
+            bind_group,
+            bind_group_layout,
+        }
+    }
+
+    pub fn uniforms(&self) -> FrameUniforms {
+        self.buffer.get()
+    }
+
+    /// Moves on to the next frame. Call once per frame.
+    pub fn advance(&self, queue: &wgpu::Queue, delta_time: Duration, resolution: UVec2, jittered: bool) {
+        self.buffer.write(queue, self.buffer.get().next(delta_time, resolution, jittered));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::uvec2;
+
+    use super::*;
+
+    #[test]
+    fn frame_uniforms_test() {
+        assert_eq!(std::mem::size_of::<FrameUniforms>(), 32);
+        let uniforms = FrameUniforms::ZERO.next(Duration::from_millis(500), uvec2(1920, 1080), false);
+        assert_eq!((uniforms.time, uniforms.delta_time, uniforms.frame), (0.5, 0.5, 1));
+        assert_eq!((uniforms.resolution, uniforms.jitter), (vec2(1920.0, 1080.0), Vec2::ZERO));
+        let wrapped = FrameUniforms { time: TIME_PERIOD - 0.25, ..uniforms }.next(Duration::from_millis(500), uvec2(1920, 1080), true);
+        assert_eq!(wrapped.jitter, jitter(2));
+        assert!((wrapped.time - 0.25).abs() < 1e-2);
+
+        // The jitter stays inside the pixel and doesn't repeat within its period.
+        let offsets = (0..JITTER_PERIOD).map(jitter).collect::<Vec<_>>();
+        assert!(offsets.iter().all(|offset| offset.abs().max_element() < 0.5));
+        assert!(offsets.iter().enumerate().all(|(i, a)| offsets[i + 1..].iter().all(|b| a != b)));
+        assert_eq!(jitter(JITTER_PERIOD), jitter(0));
+    }
+}
@@ -0,0 +1,122 @@
+use super::{bindings::{BindGroupBuilder, Bindings}, memory::{self, MemoryCategory, Tracked}, raytrace::RESULT_FORMAT};
+
+/*
+FXAA as a compute pass. It reads the raytrace result (see [RESULT_FORMAT]) and writes the antialiased
+result to its own output texture of the same size, which is then drawn instead of the input.
+*/
+
+const WORKGROUP_SIZE: u32 = 16;
+
+pub struct Fxaa {
+    output_texture: Tracked<wgpu::Texture>,
+    output_view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl Fxaa {
+    /// `input` must be a view of a [RESULT_FORMAT] texture with the given dimensions.
+    pub fn new(device: &wgpu::Device, input: &wgpu::TextureView, width: u32, height: u32) -> Self {
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("FXAA Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group_layout = BindGroupBuilder::new()
+            .label("FXAA Bind Group Layout")
+            .texture_2d(0, wgpu::ShaderStages::COMPUTE)
+            .sampler(1, wgpu::ShaderStages::COMPUTE)
+            .storage_texture(2, wgpu::ShaderStages::COMPUTE, wgpu::StorageTextureAccess::WriteOnly, RESULT_FORMAT)
+            .build(device);
+
+        let (output_texture, output_view) = Self::create_output(device, width, height);
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, input, &sampler, &output_view);
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../shaders/fxaa.wgsl"));
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("FXAA Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("FXAA Compute Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        Self {
+            output_texture,
+            output_view,
+            sampler,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    fn create_output(device: &wgpu::Device, width: u32, height: u32) -> (Tracked<wgpu::Texture>, wgpu::TextureView) {
+        let texture = memory::track(MemoryCategory::PostFx, device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("FXAA Output"),
+            dimension: wgpu::TextureDimension::D2,
+            format: RESULT_FORMAT,
+            mip_level_count: 1,
+            sample_count: 1,
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        }));
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (texture, view)
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        input: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+        output: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        Bindings::new()
+            .texture_view(0, input)
+            .sampler(1, sampler)
+            .texture_view(2, output)
+            .build(device, Some("FXAA Bind Group"), layout)
+    }
+
+    /// Recreates the output texture. Call this whenever the input texture is recreated.
+    pub fn resize(&mut self, device: &wgpu::Device, input: &wgpu::TextureView, width: u32, height: u32) {
+        let (output_texture, output_view) = Self::create_output(device, width, height);
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, input, &self.sampler, &output_view);
+        self.output_texture = output_texture;
+        self.output_view = output_view;
+    }
+
+    pub fn output_view(&self) -> &wgpu::TextureView {
+        &self.output_view
+    }
+
+    pub fn compute(&self, compute_pass: &mut wgpu::ComputePass) {
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+        compute_pass.dispatch_workgroups(
+            self.output_texture.width().div_ceil(WORKGROUP_SIZE),
+            self.output_texture.height().div_ceil(WORKGROUP_SIZE),
+            1,
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use wgpu::util::DeviceExt;
+
+use crate::voxel::vertex::InstanceData;
+
+/// A growable vertex buffer of [InstanceData].
+pub struct InstanceBuffer {
+    pub buffer: wgpu::Buffer,
+    len: u32,
+    capacity: u32,
+}
+
+impl InstanceBuffer {
+    pub fn new(device: &wgpu::Device, instances: &[InstanceData]) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Instance Buffer"),
+            contents: bytemuck::cast_slice(instances),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+        Self {
+            buffer,
+            len: instances.len() as u32,
+            capacity: instances.len() as u32,
+        }
+    }
+
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Replaces the instances, reallocating the buffer if it's too small.
+    pub fn write(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, instances: &[InstanceData]) {
+        let len = instances.len() as u32;
+        if len > self.capacity {
+            *self = Self::new(device, instances);
+            return;
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(instances));
+        self.len = len;
+    }
+}
+
+/// Draws every instance in `instances` of the indexed mesh in one call.
+/// The pipeline must use [crate::voxel::vertex::Vertex::desc] and [crate::voxel::vertex::Vertex::instance_desc]
+/// as vertex buffers 0 and 1, and the pipeline and bind groups must already be set.
+pub fn draw_instanced(
+    render_pass: &mut wgpu::RenderPass,
+    vertex_buffer: &wgpu::Buffer,
+    index_buffer: &wgpu::Buffer,
+    index_format: wgpu::IndexFormat,
+    num_indices: u32,
+    instances: &InstanceBuffer,
+) {
+    if instances.is_empty() {
+        return;
+    }
+    render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+    render_pass.set_vertex_buffer(1, instances.buffer.slice(..));
+    render_pass.set_index_buffer(index_buffer.slice(..), index_format);
+    render_pass.draw_indexed(0..num_indices, 0, 0..instances.len());
+}
@@ -0,0 +1,308 @@
+/*
+Lighting presets and point lights.
+
+Presets are there so that lighting experiments don't need hand-typed RGB values. Light colors are given
+as color temperatures (see [color_temperature]) and applied with
+[super::raytrace::GpuRtLighting::apply_preset]. Presets don't include the light direction, only how high
+the sun should be. The caller moves the sun (for example with [crate::sun_gizmo::SunGizmo]) so that the
+light can follow smoothly.
+
+Point lights live on the CPU in [Lights] and are uploaded to the raytracer as [GpuPointLight]s (see
+[super::raytrace::Raytracer::write_lights]), like [crate::entity::Entities]. Every surface loops over all
+of them, so there can only be a few. A light reaches as far as its radius and fades out smoothly before
+it, and can optionally cast shadows (one shadow ray per light and surface).
+*/
+
+use bytemuck::{Pod, Zeroable};
+use glam::{vec3, Vec3};
+
+use super::color::srgb_to_linear;
+
+/// The linear color of a black body at `kelvin` (clamped to `1000..=40000`), with the brightest channel at `1.0`.
+/// This is the approximation by Tanner Helland, which is close enough for light colors.
+pub fn color_temperature(kelvin: f32) -> Vec3 {
+    let t = kelvin.clamp(1000.0, 40000.0) / 100.0;
+    let red = if t <= 66.0 {
+        255.0
+    } else {
+        329.698_73 * (t - 60.0).powf(-0.133_204_76)
+    };
+    let green = if t <= 66.0 {
+        99.470_8 * t.ln() - 161.119_57
+    } else {
+        288.122_17 * (t - 60.0).powf(-0.075_514_85)
+    };
+    let blue = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.517_73 * (t - 10.0).ln() - 305.044_8
+    };
+    vec3(red, green, blue).map(|channel| srgb_to_linear((channel / 255.0).clamp(0.0, 1.0)))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightingPreset {
+    pub name: &'static str,
+    /// Color temperature of the directional light (kelvin).
+    pub sun_temperature: f32,
+    pub sun_intensity: f32,
+    /// How bright the shadowed side is (see [super::raytrace::DirectionalLight::shadow]).
+    pub shadow: f32,
+    /// Color temperature of the ambient light (kelvin).
+    pub ambient_temperature: f32,
+    pub ambient_intensity: f32,
+    /// How high the sun should be (degrees above the horizon).
+    pub sun_elevation: f32,
+}
+
+impl LightingPreset {
+    pub const NOON: Self = Self {
+        name: "Noon",
+        sun_temperature: 5800.0,
+        sun_intensity: 1.0,
+        shadow: 0.2,
+        ambient_temperature: 7500.0,
+        ambient_intensity: 0.1,
+        sun_elevation: 70.0,
+    };
+
+    pub const GOLDEN_HOUR: Self = Self {
+        name: "Golden Hour",
+        sun_temperature: 2700.0,
+        sun_intensity: 0.8,
+        shadow: 0.12,
+        ambient_temperature: 4500.0,
+        ambient_intensity: 0.08,
+        sun_elevation: 8.0,
+    };
+
+    pub const MOONLIGHT: Self = Self {
+        name: "Moonlight",
+        sun_temperature: 9500.0,
+        sun_intensity: 0.15,
+        shadow: 0.03,
+        ambient_temperature: 12000.0,
+        ambient_intensity: 0.03,
+        sun_elevation: 40.0,
+    };
+
+    /// Dim, soft light with bright shadows.
+    pub const OVERCAST: Self = Self {
+        name: "Overcast",
+        sun_temperature: 6500.0,
+        sun_intensity: 0.45,
+        shadow: 0.35,
+        ambient_temperature: 7000.0,
+        ambient_intensity: 0.25,
+        sun_elevation: 55.0,
+    };
+
+    pub const ALL: [Self; 4] = [Self::NOON, Self::GOLDEN_HOUR, Self::MOONLIGHT, Self::OVERCAST];
+
+    pub fn sun_color(&self) -> Vec3 {
+        color_temperature(self.sun_temperature)
+    }
+
+    pub fn ambient_color(&self) -> Vec3 {
+        color_temperature(self.ambient_temperature)
+    }
+}
+
+/// The number of point lights that the raytracer can draw.
+pub const MAX_POINT_LIGHTS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointLight {
+    pub position: Vec3,
+    /// A linear color.
+    pub color: Vec3,
+    /// The distance where the light has faded out completely.
+    pub radius: f32,
+    pub intensity: f32,
+    /// Whether surfaces trace a shadow ray towards the light.
+    pub shadows: bool,
+}
+
+impl PointLight {
+    pub fn new(position: Vec3, color: Vec3, radius: f32, intensity: f32) -> Self {
+        Self {
+            position,
+            color,
+            radius,
+            intensity,
+            shadows: false,
+        }
+    }
+
+    pub fn with_shadows(self, shadows: bool) -> Self {
+        Self { shadows, ..self }
+    }
+
+    pub fn to_gpu(&self) -> GpuPointLight {
+        GpuPointLight {
+            position: self.position.to_array(),
+            radius: self.radius.max(0.0),
+            color: self.color.to_array(),
+            intensity: self.intensity,
+            shadows: self.shadows as u32,
+            _padding: [0; 3],
+        }
+    }
+}
+
+/// `PointLight` in raytrace.wgsl. Empty slots have a radius of `0`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct GpuPointLight {
+    position: [f32; 3],
+    radius: f32,
+    color: [f32; 3],
+    intensity: f32,
+    shadows: u32,
+    _padding: [u32; 3],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LightId(u32);
+
+impl LightId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// The point lights of the scene. A light keeps its slot (and [LightId]) until it is removed.
+#[derive(Debug, Clone, Default)]
+pub struct Lights {
+    slots: Vec<Option<PointLight>>,
+    changed: bool,
+}
+
+impl Lights {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `None` if there are already [MAX_POINT_LIGHTS] lights.
+    pub fn add(&mut self, light: PointLight) -> Option<LightId> {
+        let index = match self.slots.iter().position(Option::is_none) {
+            Some(index) => index,
+            None if self.slots.len() < MAX_POINT_LIGHTS => {
+                self.slots.push(None);
+                self.slots.len() - 1
+            }
+            None => return None,
+        };
+        self.slots[index] = Some(light);
+        self.changed = true;
+        Some(LightId(index as u32))
+    }
+
+    /// Replaces a light. Returns `false` if there is no light with that ID.
+    pub fn update(&mut self, id: LightId, light: PointLight) -> bool {
+        match self.slots.get_mut(id.index()) {
+            Some(Some(slot)) => {
+                self.changed |= *slot != light;
+                *slot = light;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn remove(&mut self, id: LightId) -> Option<PointLight> {
+        let light = self.slots.get_mut(id.index())?.take();
+        while matches!(self.slots.last(), Some(None)) {
+            self.slots.pop();
+        }
+        self.changed |= light.is_some();
+        light
+    }
+
+    pub fn get(&self, id: LightId) -> Option<&PointLight> {
+        self.slots.get(id.index())?.as_ref()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (LightId, &PointLight)> {
+        self.slots.iter()
+            .enumerate()
+            .filter_map(|(index, light)| Some((LightId(index as u32), light.as_ref()?)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns whether anything changed since the last call.
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    /// The slots up to the last light, indexed by [LightId].
+    pub fn to_gpu(&self) -> Vec<GpuPointLight> {
+        self.slots.iter()
+            .map(|light| light.as_ref().map_or(GpuPointLight::zeroed(), PointLight::to_gpu))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_temperature_test() {
+        // Around 6600 K is white.
+        let white = color_temperature(6600.0);
+        assert!(white.min_element() > 0.95, "{white}");
+        // Low temperatures are orange, high temperatures are blue.
+        let warm = color_temperature(2700.0);
+        assert_eq!(warm.x, 1.0);
+        assert!(warm.x > warm.y && warm.y > warm.z);
+        let cool = color_temperature(12000.0);
+        assert_eq!(cool.z, 1.0);
+        assert!(cool.z > cool.x);
+        // Out of range temperatures are clamped.
+        assert_eq!(color_temperature(0.0), color_temperature(1000.0));
+        for preset in LightingPreset::ALL {
+            assert_eq!(preset.sun_color().max_element(), 1.0, "{}", preset.name);
+        }
+    }
+
+    #[test]
+    fn lights_test() {
+        let mut lights = Lights::new();
+        let light = PointLight::new(Vec3::ZERO, Vec3::ONE, 8.0, 2.0);
+        let a = lights.add(light).unwrap();
+        let b = lights.add(light.with_shadows(true)).unwrap();
+        assert!(lights.take_changed());
+        assert_eq!((a.index(), b.index(), lights.len()), (0, 1, 2));
+
+        // Updating with the same light doesn't upload again.
+        assert!(lights.update(a, light));
+        assert!(!lights.take_changed());
+        assert!(lights.update(a, PointLight { position: Vec3::Y, ..light }));
+        assert!(lights.take_changed());
+        assert_eq!(lights.get(a).unwrap().position, Vec3::Y);
+
+        // Removed slots are reused, and the trailing empty slots aren't uploaded.
+        assert!(lights.remove(a).is_some());
+        assert!(!lights.update(a, light));
+        assert_eq!(lights.to_gpu().len(), 2);
+        assert_eq!(lights.to_gpu()[0], GpuPointLight::zeroed());
+        lights.remove(b);
+        assert!(lights.to_gpu().is_empty());
+        while lights.len() < MAX_POINT_LIGHTS {
+            lights.add(light).unwrap();
+        }
+        assert_eq!(lights.add(light), None);
+
+        assert_eq!(std::mem::size_of::<GpuPointLight>(), 48);
+    }
+}
@@ -0,0 +1,267 @@
+use std::{ops::{Deref, DerefMut}, sync::atomic::{AtomicU64, Ordering}};
+
+/*
+Tracks how much GPU memory the renderer has allocated, by category, so that leaks show up on the overlay
+(such as textures that are kept alive after their pipelines are recreated on resize or hot reload).
+
+Resources are wrapped in a [Tracked] where they're created:
+
+    let texture = memory::track(MemoryCategory::PostFx, device.create_texture(&descriptor));
+
+Its size is added to the category, and removed again when the [Tracked] is dropped. [Tracked] derefs to
+the resource, so it can be used in place of it. Sizes are computed from the descriptors, so they don't
+include the padding and alignment that the driver adds, and memory that other crates allocate on their
+own (glyphon's atlas, vello's buffers) isn't counted.
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemoryCategory {
+    /// The raytracer's voxel data.
+    Chunks,
+    /// Texture arrays, assets and the skybox.
+    Textures,
+    /// Raytrace results, G-buffers and MSAA targets.
+    Targets,
+    /// The post chain's targets and the effects' intermediate textures.
+    PostFx,
+    /// Labels and the vector overlay.
+    Text,
+}
+
+impl MemoryCategory {
+    pub const ALL: [MemoryCategory; 5] = [
+        MemoryCategory::Chunks,
+        MemoryCategory::Textures,
+        MemoryCategory::Targets,
+        MemoryCategory::PostFx,
+        MemoryCategory::Text,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MemoryCategory::Chunks => "Chunks",
+            MemoryCategory::Textures => "Textures",
+            MemoryCategory::Targets => "Targets",
+            MemoryCategory::PostFx => "Post FX",
+            MemoryCategory::Text => "Text",
+        }
+    }
+
+    const fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// GPU resources whose size can be tracked.
+pub trait GpuResource {
+    /// The size in bytes.
+    fn gpu_size(&self) -> u64;
+}
+
+impl GpuResource for wgpu::Buffer {
+    fn gpu_size(&self) -> u64 {
+        self.size()
+    }
+}
+
+impl GpuResource for wgpu::Texture {
+    fn gpu_size(&self) -> u64 {
+        texture_size(self.format(), self.size(), self.dimension(), self.mip_level_count(), self.sample_count())
+    }
+}
+
+/// The size in bytes of a texture with every mip level. Formats without a single block size (combined depth
+/// and stencil) are counted as 4 bytes per texel.
+pub fn texture_size(
+    format: wgpu::TextureFormat,
+    size: wgpu::Extent3d,
+    dimension: wgpu::TextureDimension,
+    mip_level_count: u32,
+    sample_count: u32,
+) -> u64 {
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format.block_copy_size(None).unwrap_or(4) as u64;
+    (0..mip_level_count).map(|level| {
+        let mip = size.mip_level_size(level, dimension);
+        let blocks_x = mip.width.div_ceil(block_width) as u64;
+        let blocks_y = mip.height.div_ceil(block_height) as u64;
+        blocks_x * blocks_y * mip.depth_or_array_layers as u64 * block_size
+    }).sum::<u64>() * sample_count as u64
+}
+
+/// The allocations in one category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub category: MemoryCategory,
+    pub bytes: u64,
+    /// The number of resources.
+    pub count: u64,
+}
+
+/// Totals of the [Tracked] resources by category. Use [gpu_memory] for the renderer's.
+pub struct GpuMemory {
+    bytes: [AtomicU64; MemoryCategory::ALL.len()],
+    counts: [AtomicU64; MemoryCategory::ALL.len()],
+}
+
+impl GpuMemory {
+    pub const fn new() -> Self {
+        Self {
+            bytes: [const { AtomicU64::new(0) }; MemoryCategory::ALL.len()],
+            counts: [const { AtomicU64::new(0) }; MemoryCategory::ALL.len()],
+        }
+    }
+
+    /// Adds `resource` to `category` until the returned [Tracked] is dropped.
+    pub fn track<T: GpuResource>(&'static self, category: MemoryCategory, resource: T) -> Tracked<T> {
+        let size = resource.gpu_size();
+        self.bytes[category.index()].fetch_add(size, Ordering::Relaxed);
+        self.counts[category.index()].fetch_add(1, Ordering::Relaxed);
+        Tracked {
+            resource,
+            category,
+            size,
+            memory: self,
+        }
+    }
+
+    fn release(&self, category: MemoryCategory, size: u64) {
+        self.bytes[category.index()].fetch_sub(size, Ordering::Relaxed);
+        self.counts[category.index()].fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn usage(&self, category: MemoryCategory) -> MemoryUsage {
+        MemoryUsage {
+            category,
+            bytes: self.bytes[category.index()].load(Ordering::Relaxed),
+            count: self.counts[category.index()].load(Ordering::Relaxed),
+        }
+    }
+
+    /// The usage of every category, in the order of [MemoryCategory::ALL].
+    pub fn report(&self) -> [MemoryUsage; MemoryCategory::ALL.len()] {
+        MemoryCategory::ALL.map(|category| self.usage(category))
+    }
+
+    /// The bytes of every category.
+    pub fn total(&self) -> u64 {
+        self.bytes.iter().map(|bytes| bytes.load(Ordering::Relaxed)).sum()
+    }
+}
+
+impl Default for GpuMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static GPU_MEMORY: GpuMemory = GpuMemory::new();
+
+/// The tracker of the renderer's resources.
+pub fn gpu_memory() -> &'static GpuMemory {
+    &GPU_MEMORY
+}
+
+/// Tracks `resource` with [gpu_memory].
+pub fn track<T: GpuResource>(category: MemoryCategory, resource: T) -> Tracked<T> {
+    GPU_MEMORY.track(category, resource)
+}
+
+/// A resource that counts towards its category of a [GpuMemory] while it's alive.
+pub struct Tracked<T: GpuResource> {
+    resource: T,
+    category: MemoryCategory,
+    size: u64,
+    memory: &'static GpuMemory,
+}
+
+impl<T: GpuResource> Tracked<T> {
+    pub fn category(&self) -> MemoryCategory {
+        self.category
+    }
+
+    /// The size when the resource was tracked.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl<T: GpuResource> Deref for Tracked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.resource
+    }
+}
+
+impl<T: GpuResource> DerefMut for Tracked<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.resource
+    }
+}
+
+impl<T: GpuResource> Drop for Tracked<T> {
+    fn drop(&mut self) {
+        self.memory.release(self.category, self.size);
+    }
+}
+
+impl<T: GpuResource + std::fmt::Debug> std::fmt::Debug for Tracked<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tracked")
+            .field("resource", &self.resource)
+            .field("category", &self.category)
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FakeResource(u64);
+
+    impl GpuResource for FakeResource {
+        fn gpu_size(&self) -> u64 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn tracking_test() {
+        static MEMORY: GpuMemory = GpuMemory::new();
+        let chunk = MEMORY.track(MemoryCategory::Chunks, FakeResource(1024));
+        let targets = [100, 200].map(|size| MEMORY.track(MemoryCategory::Targets, FakeResource(size)));
+        assert_eq!(MEMORY.usage(MemoryCategory::Chunks), MemoryUsage { category: MemoryCategory::Chunks, bytes: 1024, count: 1 });
+        assert_eq!(MEMORY.usage(MemoryCategory::Targets).bytes, 300);
+        assert_eq!(MEMORY.usage(MemoryCategory::Targets).count, 2);
+        assert_eq!(MEMORY.total(), 1324);
+        assert_eq!(chunk.0, 1024);
+
+        // Recreating the targets releases the old ones.
+        drop(targets);
+        let _targets = [150, 250].map(|size| MEMORY.track(MemoryCategory::Targets, FakeResource(size)));
+        assert_eq!(MEMORY.usage(MemoryCategory::Targets).bytes, 400);
+        drop(chunk);
+        let report = MEMORY.report();
+        assert_eq!(report.map(|usage| usage.category), MemoryCategory::ALL);
+        assert_eq!(report[0].bytes, 0);
+        assert_eq!(report[0].count, 0);
+        assert_eq!(MEMORY.total(), 400);
+    }
+
+    #[test]
+    fn texture_size_test() {
+        let size = |width, height, layers| wgpu::Extent3d { width, height, depth_or_array_layers: layers };
+        let d2 = wgpu::TextureDimension::D2;
+        assert_eq!(texture_size(wgpu::TextureFormat::Rgba8Unorm, size(1280, 720, 1), d2, 1, 1), 1280 * 720 * 4);
+        assert_eq!(texture_size(wgpu::TextureFormat::Rgba16Float, size(100, 100, 1), d2, 1, 4), 100 * 100 * 8 * 4);
+        // Every mip level of every layer: 16x16, 8x8, 4x4, 2x2 and 1x1.
+        assert_eq!(texture_size(wgpu::TextureFormat::R8Unorm, size(16, 16, 3), d2, 5, 1), (256 + 64 + 16 + 4 + 1) * 3);
+        // The depth of 3D textures shrinks with the mip levels.
+        assert_eq!(texture_size(wgpu::TextureFormat::R8Unorm, size(4, 4, 4), wgpu::TextureDimension::D3, 3, 1), 64 + 8 + 1);
+        // 4x4 blocks of 16 bytes, rounded up.
+        assert_eq!(texture_size(wgpu::TextureFormat::Bc7RgbaUnorm, size(10, 4, 1), d2, 1, 1), 3 * 16);
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+
+use super::bindings::{BindGroupBuilder, Bindings};
+
+/*
+Generates mip levels on the GPU. Level 0 is uploaded as usual, then each level is rendered from the
+one above it with a linear sampler. This works for every layer of array textures and cubemaps.
+
+Rendering (rather than a compute pass) is used so that sRGB textures can be targets: sampling an
+sRGB view decodes to linear and writing to one encodes again, so filtering happens in linear space.
+The texture needs [wgpu::TextureUsages::RENDER_ATTACHMENT] and [wgpu::TextureUsages::TEXTURE_BINDING].
+
+    let mut mipmaps = MipmapGenerator::new(device);
+    mipmaps.generate(device, &mut encoder, &texture);
+*/
+
+/// The number of mip levels down to 1x1 for a texture of the given size.
+pub fn full_mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+pub struct MipmapGenerator {
+    shader: wgpu::ShaderModule,
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    /// Render pipelines are specific to the target format, so they're created on first use.
+    pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+}
+
+impl MipmapGenerator {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../shaders/mipmap.wgsl"));
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Mipmap Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let bind_group_layout = BindGroupBuilder::new()
+            .label("Mipmap Bind Group Layout")
+            .texture_2d(0, wgpu::ShaderStages::FRAGMENT)
+            .sampler(1, wgpu::ShaderStages::FRAGMENT)
+            .build(device);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Mipmap Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        Self {
+            shader,
+            sampler,
+            bind_group_layout,
+            pipeline_layout,
+            pipelines: HashMap::new(),
+        }
+    }
+
+    fn pipeline(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat) -> &wgpu::RenderPipeline {
+        self.pipelines.entry(format).or_insert_with(|| device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Mipmap Pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: Some("vertex_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: Some("fragment_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        }))
+    }
+
+    /// Records the passes that fill mip levels `1..` of every layer from level 0.
+    pub fn generate(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) {
+        let format = texture.format();
+        let layer_view = |mip_level: u32, layer: u32| texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Mipmap View"),
+            format: Some(format),
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_mip_level: mip_level,
+            mip_level_count: Some(1),
+            base_array_layer: layer,
+            array_layer_count: Some(1),
+            ..Default::default()
+        });
+        // Bind groups are created up front so that the pipeline can be borrowed for the passes.
+        let mut passes = Vec::new();
+        for layer in 0..texture.depth_or_array_layers() {
+            for mip_level in 1..texture.mip_level_count() {
+                let bind_group = Bindings::new()
+                    .texture_view(0, &layer_view(mip_level - 1, layer))
+                    .sampler(1, &self.sampler)
+                    .build(device, Some("Mipmap Bind Group"), &self.bind_group_layout);
+                passes.push((bind_group, layer_view(mip_level, layer)));
+            }
+        }
+        let pipeline = self.pipeline(device, format);
+        for (bind_group, target) in passes {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Mipmap Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+
+    /// Like [MipmapGenerator::generate], but submits the passes right away. Use this after uploading
+    /// level 0 with [wgpu::Queue::write_texture].
+    pub fn generate_and_submit(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) {
+        if texture.mip_level_count() <= 1 {
+            return;
+        }
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Mipmap Encoder"),
+        });
+        self.generate(device, &mut encoder, texture);
+        queue.submit(Some(encoder.finish()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mip_level_count_test() {
+        assert_eq!(full_mip_level_count(1, 1), 1);
+        assert_eq!(full_mip_level_count(32, 32), 6);
+        assert_eq!(full_mip_level_count(32, 8), 6);
+        assert_eq!(full_mip_level_count(33, 1), 6);
+        assert_eq!(full_mip_level_count(0, 0), 1);
+    }
+}
@@ -1,7 +1,37 @@
 pub mod transforms;
 pub mod texture_array;
+pub mod mipmaps;
+pub mod sampler;
+pub mod compressed;
 pub mod skybox;
 pub mod render_texture;
 pub mod raytrace;
+pub mod lighting;
+pub mod block_light;
 pub mod reticle;
-pub mod velvet;
\ No newline at end of file
+pub mod velvet;
+pub mod instancing;
+pub mod buffers;
+pub mod bindings;
+pub mod fxaa;
+pub mod post;
+pub mod staging;
+pub mod offline;
+pub mod shader_errors;
+pub mod timestamps;
+pub mod blue_noise;
+pub mod msaa;
+pub mod color;
+pub mod exposure;
+pub mod frame_graph;
+pub mod pip;
+pub mod render_scale;
+pub mod memory;
+pub mod debug_capture;
+pub mod raster;
+pub mod clouds;
+pub mod frame_uniforms;
+pub mod taa;
+pub mod dispatch;
+pub mod frame_graph_lite;
+pub mod text_style;
\ No newline at end of file
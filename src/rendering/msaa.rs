@@ -0,0 +1,236 @@
+use super::{memory::{self, MemoryCategory, Tracked}, post::post_shader, render_texture::{RenderTexture, RenderTextureBinding}};
+
+/*
+Multisampling for the raster passes. With more than one sample, each pass renders into a multisampled
+color target (HDR for the scene, the surface format for the UI) that is resolved at the end of the pass:
+
+    Scene pass (skybox, grid, raytrace result, gizmos)  -> resolved into the post chain's scene texture
+    Post chain                                          -> writes to `overlay_base` instead of the surface
+    UI pass (reticle, text, vello)                      -> starts by copying `overlay_base` into the
+                                                           target, resolved into the surface
+
+Every pipeline that draws in these passes has to be created with the same sample count (see
+[multisample_state]). With a single sample, the passes render directly into their targets as before.
+*/
+
+/// The sample counts that can be selected, if the adapter supports them.
+pub const SAMPLE_COUNTS: [u32; 4] = [1, 2, 4, 8];
+
+/// The multisample state for pipelines that draw in the multisampled passes.
+pub fn multisample_state(sample_count: u32) -> wgpu::MultisampleState {
+    wgpu::MultisampleState {
+        count: sample_count,
+        mask: !0,
+        alpha_to_coverage_enabled: false,
+    }
+}
+
+/// The entries of [SAMPLE_COUNTS] that the adapter supports for every one of `formats`. Always includes `1`.
+pub fn supported_sample_counts(adapter: &wgpu::Adapter, formats: &[wgpu::TextureFormat]) -> Vec<u32> {
+    let flags = formats.iter()
+        .map(|&format| adapter.get_texture_format_features(format).flags)
+        .collect::<Vec<_>>();
+    SAMPLE_COUNTS.into_iter()
+        .filter(|&count| count == 1 || flags.iter().all(|flags| flags.sample_count_supported(count)))
+        .collect()
+}
+
+struct MsaaTargets {
+    /// The multisampled scene and overlay textures, kept so that they count towards [memory::gpu_memory].
+    _textures: [Tracked<wgpu::Texture>; 2],
+    scene_view: wgpu::TextureView,
+    overlay_view: wgpu::TextureView,
+    /// The post chain's output, copied into the UI pass.
+    overlay_base: RenderTexture,
+}
+
+pub struct Msaa {
+    sample_count: u32,
+    scene_format: wgpu::TextureFormat,
+    format: wgpu::TextureFormat,
+    /// `None` with a single sample.
+    targets: Option<MsaaTargets>,
+    copy_layout: wgpu::BindGroupLayout,
+    copy_pipeline: Option<wgpu::RenderPipeline>,
+}
+
+impl Msaa {
+    /// `scene_format` is the format of the scene (see [super::post::PostChain::format]), `format` is the
+    /// format of the surface and of the post chain's output. The sample count must be supported (see
+    /// [supported_sample_counts]).
+    pub fn new(
+        device: &wgpu::Device,
+        scene_format: wgpu::TextureFormat,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+    ) -> Self {
+        let copy_layout = RenderTextureBinding::create_layout(device);
+        let copy_pipeline = (sample_count > 1).then(|| Self::create_copy_pipeline(device, format, &copy_layout, sample_count));
+        let targets = Self::create_targets(device, [scene_format, format], width, height, sample_count, &copy_layout);
+        Self {
+            sample_count,
+            scene_format,
+            format,
+            targets,
+            copy_layout,
+            copy_pipeline,
+        }
+    }
+
+    /// `formats` are the scene's and the overlay's.
+    fn create_targets(
+        device: &wgpu::Device,
+        formats: [wgpu::TextureFormat; 2],
+        width: u32,
+        height: u32,
+        sample_count: u32,
+        copy_layout: &wgpu::BindGroupLayout,
+    ) -> Option<MsaaTargets> {
+        if sample_count <= 1 {
+            return None;
+        }
+        let textures = formats.map(|format| {
+            memory::track(MemoryCategory::Targets, device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("MSAA Color Texture"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            }))
+        });
+        let [scene_view, overlay_view] = textures.each_ref().map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+        let format = formats[1];
+        Some(MsaaTargets {
+            _textures: textures,
+            scene_view,
+            overlay_view,
+            overlay_base: RenderTexture::with_layout(device, width, height, format, copy_layout, MemoryCategory::Targets),
+        })
+    }
+
+    fn create_copy_pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        layout: &wgpu::BindGroupLayout,
+        sample_count: u32,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(post_shader!("MSAA Copy Shader", "../shaders/post/copy.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("MSAA Copy Pipeline Layout"),
+            bind_group_layouts: &[layout],
+            push_constant_ranges: &[],
+        });
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("MSAA Copy Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vertex_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fragment_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: multisample_state(sample_count),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.targets = Self::create_targets(device, [self.scene_format, self.format], width, height, self.sample_count, &self.copy_layout);
+    }
+
+    /// A color attachment for the scene pass that renders into `target`: directly with a single sample,
+    /// otherwise into the multisampled texture, which is resolved into `target` at the end of the pass.
+    /// The multisampled contents aren't kept, so `load` should clear.
+    pub fn color_attachment<'a>(
+        &'a self,
+        target: &'a wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> wgpu::RenderPassColorAttachment<'a> {
+        Self::attachment(self.targets.as_ref().map(|targets| &targets.scene_view), target, load)
+    }
+
+    fn attachment<'a>(
+        multisampled: Option<&'a wgpu::TextureView>,
+        target: &'a wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> wgpu::RenderPassColorAttachment<'a> {
+        match multisampled {
+            Some(view) => wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: Some(target),
+                ops: wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Discard,
+                },
+            },
+            None => wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                },
+            },
+        }
+    }
+
+    /// Where the post chain should write to so that the UI pass can draw on top of it.
+    pub fn post_output<'a>(&'a self, surface_view: &'a wgpu::TextureView) -> &'a wgpu::TextureView {
+        match &self.targets {
+            Some(targets) => targets.overlay_base.view(),
+            None => surface_view,
+        }
+    }
+
+    /// The color attachment of the UI pass, which draws on top of the post chain's output. Call
+    /// [Msaa::begin_overlay] at the start of the pass.
+    pub fn overlay_attachment<'a>(&'a self, surface_view: &'a wgpu::TextureView) -> wgpu::RenderPassColorAttachment<'a> {
+        let load = if self.targets.is_some() {
+            wgpu::LoadOp::Clear(wgpu::Color::BLACK)
+        } else {
+            wgpu::LoadOp::Load
+        };
+        Self::attachment(self.targets.as_ref().map(|targets| &targets.overlay_view), surface_view, load)
+    }
+
+    /// Copies the post chain's output into the multisampled target. Does nothing with a single sample,
+    /// since the post chain wrote to the surface directly.
+    pub fn begin_overlay(&self, render_pass: &mut wgpu::RenderPass) {
+        let (Some(targets), Some(pipeline)) = (&self.targets, &self.copy_pipeline) else {
+            return;
+        };
+        render_pass.set_pipeline(pipeline);
+        targets.overlay_base.bind(0, render_pass);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,208 @@
+use std::path::{Path, PathBuf};
+
+use image::RgbaImage;
+
+use super::color;
+
+/*
+Offline rendering renders the raytracer at a resolution that is independent of the window, accumulates
+a number of samples for each frame, and reads the result back to save it as a PNG. See
+[super::raytrace::Raytracer::render_sequence].
+*/
+
+#[derive(Debug, thiserror::Error)]
+pub enum SequenceError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to save frame: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("Invalid resolution {0}x{1}.")]
+    InvalidResolution(u32, u32),
+    #[error("Failed to read the frame back: {0}")]
+    Readback(#[from] wgpu::BufferAsyncError),
+    #[error("Can't read back a {0:?} texture.")]
+    UnsupportedFormat(wgpu::TextureFormat),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SequenceSettings {
+    pub resolution: (u32, u32),
+    /// The number of frames to render along the camera path. Without a path, a single frame is rendered.
+    pub frames: u32,
+    /// How many frames are accumulated for each saved frame, up to [super::raytrace::MAX_ACCUMULATED_FRAMES].
+    pub samples_per_frame: u32,
+}
+
+impl Default for SequenceSettings {
+    fn default() -> Self {
+        Self {
+            resolution: (3840, 2160),
+            frames: 240,
+            samples_per_frame: 32,
+        }
+    }
+}
+
+/// The file for a frame of the sequence, numbered so that the frames sort in order.
+pub fn frame_path<P: AsRef<Path>>(output_dir: P, index: u32) -> PathBuf {
+    output_dir.as_ref().join(format!("frame_{index:05}.png"))
+}
+
+/// The first `result_NNNNN.png` in `dir` that doesn't exist yet, so that dumps don't overwrite each other.
+pub fn next_dump_path<P: AsRef<Path>>(dir: P) -> PathBuf {
+    (0..)
+        .map(|index| dir.as_ref().join(format!("result_{index:05}.png")))
+        .find(|path| !path.exists())
+        .expect("There is an unused index.")
+}
+
+/// The raytracer writes linear colors, which are encoded when they are drawn to the surface (see
+/// [crate::rendering::color]).
+pub fn linear_to_srgb(value: u8) -> u8 {
+    (color::linear_to_srgb(value as f32 / 255.0) * 255.0).round() as u8
+}
+
+/// Converts a half precision float, as stored in `Rgba16Float` textures.
+pub fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * (-24f32).exp2(),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * ((exponent - 15) as f32).exp2(),
+    }
+}
+
+/// Encodes a linear HDR channel as an 8-bit sRGB value. Values above `1.0` are clamped, since the frame
+/// isn't tonemapped.
+fn encode_channel(value: f32) -> u8 {
+    (color::linear_to_srgb(value.clamp(0.0, 1.0)) * 255.0).round() as u8
+}
+
+/// Copies a texture of an uncompressed color format into a buffer and waits for it to be mapped. Returns
+/// the texels row by row, without the padding of the copy.
+pub fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Result<Vec<u8>, SequenceError> {
+    let format = texture.format();
+    let pixel_bytes = match format.block_copy_size(None) {
+        Some(size) if format.block_dimensions() == (1, 1) => size,
+        _ => return Err(SequenceError::UnsupportedFormat(format)),
+    };
+    let (width, height) = (texture.width(), texture.height());
+    let row_bytes = width * pixel_bytes;
+    let padded_row_bytes = row_bytes.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Offline Readback Buffer"),
+        size: (padded_row_bytes * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Offline Readback Encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_bytes),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let slice = buffer.slice(..);
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver.recv().unwrap_or(Err(wgpu::BufferAsyncError))?;
+
+    let mut texels = Vec::with_capacity((row_bytes * height) as usize);
+    {
+        let mapped = slice.get_mapped_range();
+        for row in mapped.chunks_exact(padded_row_bytes as usize) {
+            texels.extend_from_slice(&row[..row_bytes as usize]);
+        }
+    }
+    buffer.unmap();
+    buffer.destroy();
+    Ok(texels)
+}
+
+/// The channels of an `Rgba16Float` texel, as read by [read_texture].
+pub fn decode_rgba16f(texel: &[u8]) -> [f32; 4] {
+    [0, 2, 4, 6].map(|offset| f16_to_f32(u16::from_le_bytes([texel[offset], texel[offset + 1]])))
+}
+
+/// Reads an `Rgba8Unorm` or `Rgba16Float` texture back (see [read_texture]). The colors are converted
+/// to sRGB.
+pub fn read_texture_rgba(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Result<RgbaImage, SequenceError> {
+    let format = texture.format();
+    if !matches!(format, wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba16Float) {
+        return Err(SequenceError::UnsupportedFormat(format));
+    }
+    let texels = read_texture(device, queue, texture)?;
+    let pixels = if format == wgpu::TextureFormat::Rgba16Float {
+        texels.chunks_exact(8).flat_map(|texel| {
+            let [r, g, b, a] = decode_rgba16f(texel);
+            [encode_channel(r), encode_channel(g), encode_channel(b), (a.clamp(0.0, 1.0) * 255.0).round() as u8]
+        }).collect()
+    } else {
+        texels.chunks_exact(4).flat_map(|texel| [
+            linear_to_srgb(texel[0]),
+            linear_to_srgb(texel[1]),
+            linear_to_srgb(texel[2]),
+            texel[3],
+        ]).collect()
+    };
+    Ok(RgbaImage::from_raw(texture.width(), texture.height(), pixels).expect("The readback has one pixel per texel."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offline_helpers_test() {
+        assert_eq!(frame_path("out", 7), Path::new("out").join("frame_00007.png"));
+        assert_eq!(linear_to_srgb(0), 0);
+        assert_eq!(linear_to_srgb(255), 255);
+        // Mid grey in linear space is much brighter once encoded.
+        assert_eq!(linear_to_srgb(128), 188);
+        assert!((0..=255u8).map(linear_to_srgb).collect::<Vec<_>>().windows(2).all(|pair| pair[0] <= pair[1]));
+
+        assert_eq!(f16_to_f32(0x0000), 0.0);
+        assert_eq!(f16_to_f32(0x3c00), 1.0);
+        assert_eq!(f16_to_f32(0xc000), -2.0);
+        assert_eq!(f16_to_f32(0x3555), 0.33325195);
+        assert_eq!(f16_to_f32(0x7bff), 65504.0);
+        assert_eq!(f16_to_f32(0x0001), (-24f32).exp2());
+        assert_eq!(f16_to_f32(0x7c00), f32::INFINITY);
+        let texel = [0x3c00u16, 0xc000, 0x0000, 0x7bff].map(u16::to_le_bytes).concat();
+        assert_eq!(decode_rgba16f(&texel), [1.0, -2.0, 0.0, 65504.0]);
+        assert_eq!(encode_channel(4.0), 255);
+        assert_eq!(encode_channel(-1.0), 0);
+
+        let dir = std::env::temp_dir().join(format!("wgpu_learn_dump_test_{}", std::process::id()));
+        _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(next_dump_path(&dir), dir.join("result_00000.png"));
+        std::fs::write(dir.join("result_00000.png"), []).unwrap();
+        assert_eq!(next_dump_path(&dir), dir.join("result_00001.png"));
+        _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,261 @@
+use bytemuck::{Pod, Zeroable};
+
+use crate::{camera::Camera, gizmo::Gizmo};
+
+use super::{
+    bindings::{BindGroupBuilder, Bindings},
+    buffers::UniformBuffer,
+    memory::MemoryCategory,
+    msaa::multisample_state,
+    post::{post_shader, HDR_FORMAT},
+    render_texture::{RenderTexture, RenderTextureBinding},
+    transforms::TransformsBindGroup,
+};
+
+/*
+The picture-in-picture view shows the scene from a secondary camera (see `CameraRig` in state.rs) in an
+inset in the top right corner. The scene isn't raytraced a second time. Instead, the inset shows:
+
+    - The raytracer's G-buffer as a point cloud: every `stride`th pixel of the main view at the position
+      that its primary ray hit. Holes in the cloud are what the main camera doesn't see.
+    - The lines of its own [Gizmo], such as the main camera's frustum and the chunk bounds.
+
+The inset is rendered into an HDR [RenderTexture] with a single sample before the scene pass, and drawn
+into the scene pass with a viewport, so it goes through the post chain with the rest of the scene.
+*/
+
+/// The width and height of the inset, as a fraction of the screen's.
+pub const INSET_SCALE: f32 = 0.3;
+/// The distance of the inset from the top right corner, in pixels.
+const INSET_MARGIN: f32 = 16.0;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+struct HitCloudParams {
+    inverse_view_projection: [f32; 16],
+    view_projection: [f32; 16],
+    camera_position: [f32; 3],
+    stride: u32,
+}
+
+/// The size of the inset for a screen of `width` x `height`.
+pub fn inset_size(width: u32, height: u32) -> (u32, u32) {
+    let scale = |size: u32| ((size as f32 * INSET_SCALE) as u32).max(1);
+    (scale(width), scale(height))
+}
+
+/// The number of points of the hit cloud for a G-buffer of `width` x `height`.
+pub fn hit_point_count(width: u32, height: u32, stride: u32) -> u32 {
+    let stride = stride.max(1);
+    width.div_ceil(stride) * height.div_ceil(stride)
+}
+
+pub struct PictureInPicture {
+    pub enabled: bool,
+    /// Only every `stride`th G-buffer texel (on both axes) becomes a point.
+    pub stride: u32,
+    /// Lines that are only drawn in the inset. Cleared every frame by [PictureInPicture::prepare].
+    pub gizmo: Gizmo,
+    screen_size: (u32, u32),
+    target: RenderTexture,
+    transforms: TransformsBindGroup,
+    params: UniformBuffer<HitCloudParams>,
+    params_bind_group: wgpu::BindGroup,
+    hit_cloud_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    point_count: u32,
+}
+
+impl PictureInPicture {
+    /// `sample_count` is that of the scene pass that the inset is drawn in. `hit_layout` is the layout of
+    /// the raytracer's G-buffer (see [super::raytrace::Raytracer::hit_bind_group_layout]).
+    pub fn new(device: &wgpu::Device, sample_count: u32, width: u32, height: u32, hit_layout: &wgpu::BindGroupLayout) -> Self {
+        let transforms = TransformsBindGroup::new(device);
+        let params = UniformBuffer::new(device, Some("PiP Hit Cloud Params"), HitCloudParams::zeroed());
+        let params_layout = BindGroupBuilder::new()
+            .label("PiP Hit Cloud Bind Group Layout")
+            .uniform(0, wgpu::ShaderStages::VERTEX)
+            .build(device);
+        let params_bind_group = Bindings::new()
+            .buffer(0, params.buffer())
+            .build(device, Some("PiP Hit Cloud Bind Group"), &params_layout);
+        let texture_layout = RenderTextureBinding::create_layout(device);
+        let (inset_width, inset_height) = inset_size(width, height);
+        let target = RenderTexture::with_layout(device, inset_width, inset_height, HDR_FORMAT, &texture_layout, MemoryCategory::Targets);
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../shaders/pip_hits.wgsl"));
+        let hit_cloud_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("PiP Hit Cloud Pipeline Layout"),
+            bind_group_layouts: &[&params_layout, hit_layout],
+            push_constant_ranges: &[],
+        });
+        let hit_cloud_pipeline = Self::create_pipeline(
+            device,
+            "PiP Hit Cloud Pipeline",
+            &hit_cloud_layout,
+            &shader,
+            wgpu::PrimitiveTopology::PointList,
+            1,
+        );
+        let composite_shader = device.create_shader_module(post_shader!("PiP Composite Shader", "../shaders/post/copy.wgsl"));
+        let composite_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("PiP Composite Pipeline Layout"),
+            bind_group_layouts: &[&texture_layout],
+            push_constant_ranges: &[],
+        });
+        let composite_pipeline = Self::create_pipeline(
+            device,
+            "PiP Composite Pipeline",
+            &composite_layout,
+            &composite_shader,
+            wgpu::PrimitiveTopology::TriangleList,
+            sample_count,
+        );
+        Self {
+            enabled: false,
+            stride: 2,
+            gizmo: Gizmo::new(device, HDR_FORMAT, 1, &transforms),
+            screen_size: (width, height),
+            target,
+            transforms,
+            params,
+            params_bind_group,
+            hit_cloud_pipeline,
+            composite_pipeline,
+            point_count: 0,
+        }
+    }
+
+    /// Recreates the GPU resources for a new device or sample count. Keeps the settings.
+    pub fn recreate(&mut self, device: &wgpu::Device, sample_count: u32, hit_layout: &wgpu::BindGroupLayout) {
+        let (width, height) = self.screen_size;
+        *self = Self {
+            enabled: self.enabled,
+            stride: self.stride,
+            ..Self::new(device, sample_count, width, height, hit_layout)
+        };
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        label: &str,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        topology: wgpu::PrimitiveTopology,
+        sample_count: u32,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vertex_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fragment_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: HDR_FORMAT,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: multisample_state(sample_count),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.screen_size = (width, height);
+        let (inset_width, inset_height) = inset_size(width, height);
+        let layout = self.target.binding().layout.clone();
+        self.target = RenderTexture::with_layout(device, inset_width, inset_height, HDR_FORMAT, &layout, MemoryCategory::Targets);
+    }
+
+    /// The aspect ratio that the inset's camera should have.
+    pub fn aspect_ratio(&self) -> f32 {
+        self.target.width() as f32 / self.target.height() as f32
+    }
+
+    /// Writes the transforms of `camera` (the inset's) and of `main_camera`, which the G-buffer of
+    /// `gbuffer_size` was traced from, and uploads the gizmo's lines.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, camera: &Camera, main_camera: &Camera, gbuffer_size: (u32, u32)) {
+        let view_projection = camera.projection_view_matrix();
+        self.transforms.write_view_projection(queue, &view_projection);
+        self.transforms.write_camera_position(queue, &camera.position);
+        let params = HitCloudParams {
+            inverse_view_projection: main_camera.projection_view_matrix().inverse().to_cols_array(),
+            view_projection: view_projection.to_cols_array(),
+            camera_position: main_camera.position.to_array(),
+            stride: self.stride.max(1),
+        };
+        if self.params.get() != params {
+            self.params.write(queue, params);
+        }
+        self.point_count = hit_point_count(gbuffer_size.0, gbuffer_size.1, self.stride);
+        self.gizmo.prepare(device, queue);
+    }
+
+    /// Renders the inset into its texture. `hit_bind_group` is the raytracer's G-buffer of this frame.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, hit_bind_group: &wgpu::BindGroup) {
+        if !self.enabled {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("PiP Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: self.target.view(),
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color { r: 0.02, g: 0.02, b: 0.03, a: 1.0 }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+        render_pass.set_pipeline(&self.hit_cloud_pipeline);
+        render_pass.set_bind_group(0, &self.params_bind_group, &[]);
+        render_pass.set_bind_group(1, hit_bind_group, &[]);
+        render_pass.draw(0..self.point_count, 0..1);
+        self.gizmo.render(&mut render_pass, &self.transforms);
+    }
+
+    /// Draws the inset in the top right corner of the scene pass.
+    pub fn composite(&self, render_pass: &mut wgpu::RenderPass) {
+        if !self.enabled {
+            return;
+        }
+        let (width, height) = (self.target.width() as f32, self.target.height() as f32);
+        let x = (self.screen_size.0 as f32 - width - INSET_MARGIN).max(0.0);
+        render_pass.set_viewport(x, INSET_MARGIN.min(self.screen_size.1 as f32 - height).max(0.0), width, height, 0.0, 1.0);
+        render_pass.set_pipeline(&self.composite_pipeline);
+        self.target.bind(0, render_pass);
+        render_pass.draw(0..3, 0..1);
+        let (screen_width, screen_height) = self.screen_size;
+        render_pass.set_viewport(0.0, 0.0, screen_width as f32, screen_height as f32, 0.0, 1.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pip_test() {
+        assert_eq!(inset_size(1280, 720), (384, 216));
+        assert_eq!(inset_size(1, 1), (1, 1));
+        assert_eq!(hit_point_count(1280, 720, 2), 640 * 360);
+        assert_eq!(hit_point_count(5, 5, 2), 9);
+        assert_eq!(hit_point_count(5, 5, 0), 25);
+    }
+}
@@ -0,0 +1,848 @@
+use std::any::Any;
+
+use bytemuck::{NoUninit, Pod, Zeroable};
+
+use super::{bindings::{BindGroupBuilder, Bindings}, buffers::UniformBuffer, debug_capture::debug_group, memory::MemoryCategory, render_texture::{RenderTexture, RenderTextureBinding}};
+
+/*
+A chain of fullscreen post effects. The scene is rendered into `PostChain::scene_view()`, then
+`PostChain::run` applies every enabled effect in order, ping-ponging between two render textures,
+and the result is copied to the output (usually the swapchain view).
+
+The scene and the chain's targets are HDR ([HDR_FORMAT]), so colors above `1.0` survive until
+[AutoExposure](super::exposure::AutoExposure) and [Tonemap] bring them into range. The copy to the
+output clamps whatever is left.
+
+    let tonemap = Tonemap::new(device, &post);
+    post.push(tonemap);
+    // Each frame:
+    post.get_mut::<Tonemap>().unwrap().exposure = 1.5;
+    post.run(&mut encoder, queue, &surface_view);
+*/
+
+/// Builds the shader source for a post effect by appending its fragment shader to the shared
+/// fullscreen vertex shader (which also declares the input texture at group 0).
+macro_rules! post_shader {
+    ($label:literal, $path:literal) => {
+        wgpu::ShaderModuleDescriptor {
+            label: Some($label),
+            source: wgpu::ShaderSource::Wgsl(::std::borrow::Cow::Borrowed(concat!(
+                include_str!("../shaders/post/fullscreen.wgsl"),
+                include_str!($path),
+            ))),
+        }
+    };
+}
+pub(crate) use post_shader;
+
+/// The format of the scene and of the chain's targets.
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+pub trait PostEffect: Any {
+    fn name(&self) -> &str;
+
+    /// Disabled effects are skipped.
+    fn enabled(&self) -> bool;
+
+    /// Called once per frame before the effect is rendered. Upload the effect's parameters here.
+    fn prepare(&mut self, _queue: &wgpu::Queue) {}
+
+    /// Called when the chain is resized. Effects with their own render targets recreate them here.
+    fn resize(&mut self, _device: &wgpu::Device, _width: u32, _height: u32) {}
+
+    /// Rebuilds the effect's GPU resources on a new device, keeping its settings. Called from
+    /// [PostChain::recreate] after the chain's own resources were rebuilt.
+    fn recreate(&mut self, device: &wgpu::Device, chain: &PostChain);
+
+    /// Draws the effect. `input` is the bind group of the texture that the effect reads from,
+    /// which the effect binds at group 0.
+    fn render(&self, render_pass: &mut wgpu::RenderPass, input: &wgpu::BindGroup);
+
+    /// Records the effect into `encoder`, writing to `target`. The default draws [PostEffect::render]
+    /// in a single pass. Effects that need intermediate passes override this.
+    fn encode(&self, encoder: &mut wgpu::CommandEncoder, input: &wgpu::BindGroup, target: &wgpu::TextureView) {
+        let mut render_pass = begin_post_pass(encoder, self.name(), target, wgpu::LoadOp::Clear(wgpu::Color::BLACK));
+        self.render(&mut render_pass, input);
+    }
+}
+
+/// Begins a render pass that draws to a single color target without depth.
+pub fn begin_post_pass<'a>(
+    encoder: &'a mut wgpu::CommandEncoder,
+    label: &str,
+    view: &'a wgpu::TextureView,
+    load: wgpu::LoadOp<wgpu::Color>,
+) -> wgpu::RenderPass<'a> {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        ..Default::default()
+    })
+}
+
+/// A fullscreen pipeline for a post effect with a uniform block of parameters at group 1.
+pub struct FullscreenPass<P: NoUninit> {
+    params: UniformBuffer<P>,
+    params_bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl<P: NoUninit + PartialEq> FullscreenPass<P> {
+    /// The shader must contain a `fragment_main` entry point (see `post_shader!`).
+    pub fn new(
+        device: &wgpu::Device,
+        chain: &PostChain,
+        label: &str,
+        shader: wgpu::ShaderModuleDescriptor,
+        params: P,
+    ) -> Self {
+        let params = UniformBuffer::new(device, Some(label), params);
+        let params_layout = BindGroupBuilder::new()
+            .label(label)
+            .uniform(0, wgpu::ShaderStages::FRAGMENT)
+            .build(device);
+        let params_bind_group = Bindings::new()
+            .buffer(0, params.buffer())
+            .build(device, Some(label), &params_layout);
+        let pipeline = chain.create_pipeline(device, label, shader, &[&params_layout]);
+        Self {
+            params,
+            params_bind_group,
+            pipeline,
+        }
+    }
+
+    pub fn params(&self) -> P {
+        self.params.get()
+    }
+
+    /// Writes the parameters if they changed.
+    pub fn write_params(&self, queue: &wgpu::Queue, params: P) {
+        if self.params.get() != params {
+            self.params.write(queue, params);
+        }
+    }
+
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass, input: &wgpu::BindGroup) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, input, &[]);
+        render_pass.set_bind_group(1, &self.params_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+pub struct PostChain {
+    format: wgpu::TextureFormat,
+    output_format: wgpu::TextureFormat,
+    input_layout: wgpu::BindGroupLayout,
+    // The scene is rendered into the first target.
+    targets: [RenderTexture; 2],
+    effects: Vec<Box<dyn PostEffect>>,
+    // Copies the last target to the output.
+    output_pipeline: wgpu::RenderPipeline,
+}
+
+impl PostChain {
+    /// `format` is the format of the scene and of the effects' targets (usually [HDR_FORMAT]),
+    /// `output_format` is the format of the output that the chain renders to.
+    pub fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        output_format: wgpu::TextureFormat,
+    ) -> Self {
+        let input_layout = RenderTextureBinding::create_layout(device);
+        let targets = Self::create_targets(device, width, height, format, &input_layout);
+        let output_pipeline = Self::create_pipeline_with(
+            device,
+            output_format,
+            &input_layout,
+            "Post Output",
+            post_shader!("Post Output Shader", "../shaders/post/copy.wgsl"),
+            &[],
+            None,
+        );
+        Self {
+            format,
+            output_format,
+            input_layout,
+            targets,
+            effects: Vec::new(),
+            output_pipeline,
+        }
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        layout: &wgpu::BindGroupLayout,
+    ) -> [RenderTexture; 2] {
+        [
+            RenderTexture::with_layout(device, width, height, format, layout, MemoryCategory::PostFx),
+            RenderTexture::with_layout(device, width, height, format, layout, MemoryCategory::PostFx),
+        ]
+    }
+
+    fn create_pipeline_with(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        input_layout: &wgpu::BindGroupLayout,
+        label: &str,
+        shader: wgpu::ShaderModuleDescriptor,
+        extra_layouts: &[&wgpu::BindGroupLayout],
+        blend: Option<wgpu::BlendState>,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(shader);
+        let mut bind_group_layouts = vec![input_layout];
+        bind_group_layouts.extend_from_slice(extra_layouts);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vertex_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fragment_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    /// Creates a fullscreen pipeline that reads the chain's input at group 0 and writes to the
+    /// chain's format. `extra_layouts` start at group 1.
+    pub fn create_pipeline(
+        &self,
+        device: &wgpu::Device,
+        label: &str,
+        shader: wgpu::ShaderModuleDescriptor,
+        extra_layouts: &[&wgpu::BindGroupLayout],
+    ) -> wgpu::RenderPipeline {
+        Self::create_pipeline_with(device, self.format, &self.input_layout, label, shader, extra_layouts, None)
+    }
+
+    /// Like [PostChain::create_pipeline], but blends the output with the target instead of replacing it.
+    pub fn create_blended_pipeline(
+        &self,
+        device: &wgpu::Device,
+        label: &str,
+        shader: wgpu::ShaderModuleDescriptor,
+        extra_layouts: &[&wgpu::BindGroupLayout],
+        blend: wgpu::BlendState,
+    ) -> wgpu::RenderPipeline {
+        Self::create_pipeline_with(device, self.format, &self.input_layout, label, shader, extra_layouts, Some(blend))
+    }
+
+    /// The format of the scene and of the effects' targets.
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    pub fn output_format(&self) -> wgpu::TextureFormat {
+        self.output_format
+    }
+
+    pub fn input_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.input_layout
+    }
+
+    /// The width and height of the scene and of the output.
+    pub fn size(&self) -> (u32, u32) {
+        (self.targets[0].width(), self.targets[0].height())
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.targets = Self::create_targets(device, width, height, self.format, &self.input_layout);
+        for effect in self.effects.iter_mut() {
+            effect.resize(device, width, height);
+        }
+    }
+
+    /// Rebuilds the chain and its effects on a new device (after the previous device was lost).
+    pub fn recreate(&mut self, device: &wgpu::Device) {
+        let (width, height) = self.size();
+        let mut effects = std::mem::take(&mut self.effects);
+        *self = Self::new(device, width, height, self.format, self.output_format);
+        for effect in effects.iter_mut() {
+            effect.recreate(device, self);
+        }
+        self.effects = effects;
+    }
+
+    /// The view to render the scene into.
+    pub fn scene_view(&self) -> &wgpu::TextureView {
+        self.targets[0].view()
+    }
+
+    pub fn push<E: PostEffect>(&mut self, effect: E) {
+        self.effects.push(Box::new(effect));
+    }
+
+    pub fn effects(&self) -> &[Box<dyn PostEffect>] {
+        &self.effects
+    }
+
+    /// Returns the first effect of type `E`.
+    pub fn get<E: PostEffect>(&self) -> Option<&E> {
+        self.effects.iter().find_map(|effect| (effect.as_ref() as &dyn Any).downcast_ref::<E>())
+    }
+
+    /// Returns the first effect of type `E`.
+    pub fn get_mut<E: PostEffect>(&mut self) -> Option<&mut E> {
+        self.effects.iter_mut().find_map(|effect| (effect.as_mut() as &mut dyn Any).downcast_mut::<E>())
+    }
+
+    /// Applies the enabled effects to the scene and writes the result to `output`, which must
+    /// have the chain's output format and size.
+    pub fn run(&mut self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, output: &wgpu::TextureView) {
+        for effect in self.effects.iter_mut().filter(|effect| effect.enabled()) {
+            effect.prepare(queue);
+        }
+        let mut source = 0;
+        for effect in self.effects.iter().filter(|effect| effect.enabled()) {
+            debug_group(encoder, effect.name(), |encoder| {
+                effect.encode(encoder, &self.targets[source].binding().group, self.targets[1 - source].view());
+            });
+            source = 1 - source;
+        }
+        let mut render_pass = begin_post_pass(encoder, "Post Output Pass", output, wgpu::LoadOp::Clear(wgpu::Color::BLACK));
+        render_pass.set_pipeline(&self.output_pipeline);
+        self.targets[source].bind(0, &mut render_pass);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TonemapOperator {
+    /// Only applies the exposure.
+    None,
+    Reinhard,
+    #[default]
+    Aces,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+struct TonemapParams {
+    exposure: f32,
+    operator: u32,
+    _padding: [u32; 2],
+}
+
+pub struct Tonemap {
+    pub enabled: bool,
+    /// The manual exposure. With [AutoExposure](super::exposure::AutoExposure), this is applied on top
+    /// of the metered exposure as compensation.
+    pub exposure: f32,
+    pub operator: TonemapOperator,
+    pass: FullscreenPass<TonemapParams>,
+}
+
+impl Tonemap {
+    pub fn new(device: &wgpu::Device, chain: &PostChain) -> Self {
+        let params = TonemapParams {
+            exposure: 1.0,
+            operator: TonemapOperator::default() as u32,
+            _padding: [0; 2],
+        };
+        Self {
+            enabled: true,
+            exposure: 1.0,
+            operator: TonemapOperator::default(),
+            pass: FullscreenPass::new(device, chain, "Tonemap", post_shader!("Tonemap Shader", "../shaders/post/tonemap.wgsl"), params),
+        }
+    }
+}
+
+impl PostEffect for Tonemap {
+    fn name(&self) -> &str {
+        "Tonemap"
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn recreate(&mut self, device: &wgpu::Device, chain: &PostChain) {
+        *self = Self {
+            enabled: self.enabled,
+            exposure: self.exposure,
+            operator: self.operator,
+            ..Self::new(device, chain)
+        };
+    }
+
+    fn prepare(&mut self, queue: &wgpu::Queue) {
+        self.pass.write_params(queue, TonemapParams {
+            exposure: self.exposure,
+            operator: self.operator as u32,
+            _padding: [0; 2],
+        });
+    }
+
+    fn render(&self, render_pass: &mut wgpu::RenderPass, input: &wgpu::BindGroup) {
+        self.pass.render(render_pass, input);
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+struct GammaParams {
+    gamma: f32,
+    _padding: [f32; 3],
+}
+
+/// Adjusts the gamma on top of the sRGB encoding of the output. A gamma of `1.0` leaves the image unchanged.
+pub struct Gamma {
+    pub enabled: bool,
+    pub gamma: f32,
+    pass: FullscreenPass<GammaParams>,
+}
+
+impl Gamma {
+    pub fn new(device: &wgpu::Device, chain: &PostChain) -> Self {
+        let params = GammaParams {
+            gamma: 1.0,
+            _padding: [0.0; 3],
+        };
+        Self {
+            enabled: true,
+            gamma: 1.0,
+            pass: FullscreenPass::new(device, chain, "Gamma", post_shader!("Gamma Shader", "../shaders/post/gamma.wgsl"), params),
+        }
+    }
+}
+
+impl PostEffect for Gamma {
+    fn name(&self) -> &str {
+        "Gamma"
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn recreate(&mut self, device: &wgpu::Device, chain: &PostChain) {
+        *self = Self {
+            enabled: self.enabled,
+            gamma: self.gamma,
+            ..Self::new(device, chain)
+        };
+    }
+
+    fn prepare(&mut self, queue: &wgpu::Queue) {
+        self.pass.write_params(queue, GammaParams {
+            gamma: self.gamma.max(0.01),
+            _padding: [0.0; 3],
+        });
+    }
+
+    fn render(&self, render_pass: &mut wgpu::RenderPass, input: &wgpu::BindGroup) {
+        self.pass.render(render_pass, input);
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+struct VignetteParams {
+    color: [f32; 3],
+    intensity: f32,
+    radius: f32,
+    smoothness: f32,
+    _padding: [f32; 2],
+}
+
+impl VignetteParams {
+    fn new(color: glam::Vec3, intensity: f32, radius: f32, smoothness: f32) -> Self {
+        Self {
+            color: color.to_array(),
+            intensity,
+            radius,
+            smoothness,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+/// Darkens (or tints) the edges of the screen.
+pub struct Vignette {
+    pub enabled: bool,
+    pub color: glam::Vec3,
+    /// How strongly the edges are tinted, from 0.0 to 1.0.
+    pub intensity: f32,
+    /// The distance from the center where the vignette is at full strength (1.0 is the corners).
+    pub radius: f32,
+    /// The width of the falloff towards the center.
+    pub smoothness: f32,
+    pass: FullscreenPass<VignetteParams>,
+}
+
+impl Vignette {
+    pub fn new(device: &wgpu::Device, chain: &PostChain) -> Self {
+        let color = glam::Vec3::ZERO;
+        let (intensity, radius, smoothness) = (0.6, 1.0, 0.6);
+        let params = VignetteParams::new(color, intensity, radius, smoothness);
+        Self {
+            enabled: false,
+            color,
+            intensity,
+            radius,
+            smoothness,
+            pass: FullscreenPass::new(device, chain, "Vignette", post_shader!("Vignette Shader", "../shaders/post/vignette.wgsl"), params),
+        }
+    }
+}
+
+impl PostEffect for Vignette {
+    fn name(&self) -> &str {
+        "Vignette"
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn recreate(&mut self, device: &wgpu::Device, chain: &PostChain) {
+        *self = Self {
+            enabled: self.enabled,
+            color: self.color,
+            intensity: self.intensity,
+            radius: self.radius,
+            smoothness: self.smoothness,
+            ..Self::new(device, chain)
+        };
+    }
+
+    fn prepare(&mut self, queue: &wgpu::Queue) {
+        self.pass.write_params(queue, VignetteParams::new(self.color, self.intensity, self.radius, self.smoothness));
+    }
+
+    fn render(&self, render_pass: &mut wgpu::RenderPass, input: &wgpu::BindGroup) {
+        self.pass.render(render_pass, input);
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+struct BloomParams {
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+    radius: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+struct BlurParams {
+    direction: [f32; 2],
+    _padding: [f32; 2],
+}
+
+/// The largest number of levels in the bloom mip chain.
+const MAX_BLOOM_LEVELS: usize = 5;
+/// Levels smaller than this (on either axis) are not created.
+const MIN_BLOOM_LEVEL_SIZE: u32 = 8;
+
+/// A level of the bloom mip chain. `scratch` holds the result of the horizontal blur.
+struct BloomLevel {
+    texture: RenderTexture,
+    scratch: RenderTexture,
+}
+
+/// Makes bright parts of the image (such as emissive voxels) glow.
+///
+/// The bright pixels are extracted into a half resolution texture, which is downsampled into a mip chain.
+/// Every level is blurred with a separable gaussian blur, then the levels are blended back up the chain
+/// and the result is added on top of the image.
+pub struct Bloom {
+    pub enabled: bool,
+    /// The brightness above which pixels start to bloom.
+    pub threshold: f32,
+    /// Softens the cutoff at the threshold, as a fraction of the threshold (0.0 is a hard cutoff).
+    pub knee: f32,
+    /// How strongly the bloom is added to the image.
+    pub intensity: f32,
+    /// How far the bloom spreads, from 0.0 to 1.0. This is the weight of each lower level when it
+    /// is blended into the level above it.
+    pub radius: f32,
+    format: wgpu::TextureFormat,
+    input_layout: wgpu::BindGroupLayout,
+    levels: Vec<BloomLevel>,
+    params: UniformBuffer<BloomParams>,
+    params_bind_group: wgpu::BindGroup,
+    // Horizontal, then vertical.
+    _blur_params: [UniformBuffer<BlurParams>; 2],
+    blur_bind_groups: [wgpu::BindGroup; 2],
+    prefilter_pipeline: wgpu::RenderPipeline,
+    downsample_pipeline: wgpu::RenderPipeline,
+    blur_pipeline: wgpu::RenderPipeline,
+    upsample_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+}
+
+impl Bloom {
+    pub fn new(device: &wgpu::Device, chain: &PostChain) -> Self {
+        let (threshold, knee, intensity, radius) = (0.9, 0.5, 0.6, 0.6);
+        let params = UniformBuffer::new(device, Some("Bloom Params"), BloomParams {
+            threshold,
+            knee,
+            intensity,
+            radius,
+        });
+        let params_layout = BindGroupBuilder::new()
+            .label("Bloom Params Bind Group Layout")
+            .uniform(0, wgpu::ShaderStages::FRAGMENT)
+            .build(device);
+        let params_bind_group = Bindings::new()
+            .buffer(0, params.buffer())
+            .build(device, Some("Bloom Params Bind Group"), &params_layout);
+
+        let blur_params = [[1.0, 0.0], [0.0, 1.0]].map(|direction| {
+            UniformBuffer::new(device, Some("Bloom Blur Params"), BlurParams {
+                direction,
+                _padding: [0.0; 2],
+            })
+        });
+        let blur_bind_groups = [0, 1].map(|index| {
+            Bindings::new()
+                .buffer(0, blur_params[index].buffer())
+                .build(device, Some("Bloom Blur Bind Group"), &params_layout)
+        });
+
+        let prefilter_pipeline = chain.create_pipeline(
+            device,
+            "Bloom Prefilter",
+            post_shader!("Bloom Prefilter Shader", "../shaders/post/bloom_prefilter.wgsl"),
+            &[&params_layout],
+        );
+        let downsample_pipeline = chain.create_pipeline(
+            device,
+            "Bloom Downsample",
+            post_shader!("Bloom Downsample Shader", "../shaders/post/bloom_downsample.wgsl"),
+            &[],
+        );
+        let blur_pipeline = chain.create_pipeline(
+            device,
+            "Bloom Blur",
+            post_shader!("Bloom Blur Shader", "../shaders/post/bloom_blur.wgsl"),
+            &[&params_layout],
+        );
+        // The upsampled level is blended over the level above it by the alpha that the shader outputs (the radius).
+        let upsample_pipeline = chain.create_blended_pipeline(
+            device,
+            "Bloom Upsample",
+            post_shader!("Bloom Upsample Shader", "../shaders/post/bloom_upsample.wgsl"),
+            &[&params_layout],
+            wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            },
+        );
+        let composite_pipeline = chain.create_pipeline(
+            device,
+            "Bloom Composite",
+            post_shader!("Bloom Composite Shader", "../shaders/post/bloom_composite.wgsl"),
+            &[chain.input_layout(), &params_layout],
+        );
+
+        let (width, height) = chain.size();
+        let levels = Self::create_levels(device, width, height, chain.format(), chain.input_layout());
+        Self {
+            enabled: true,
+            threshold,
+            knee,
+            intensity,
+            radius,
+            format: chain.format(),
+            input_layout: chain.input_layout().clone(),
+            levels,
+            params,
+            params_bind_group,
+            _blur_params: blur_params,
+            blur_bind_groups,
+            prefilter_pipeline,
+            downsample_pipeline,
+            blur_pipeline,
+            upsample_pipeline,
+            composite_pipeline,
+        }
+    }
+
+    fn create_levels(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        layout: &wgpu::BindGroupLayout,
+    ) -> Vec<BloomLevel> {
+        (1..=MAX_BLOOM_LEVELS as u32)
+            .map(|level| ((width >> level).max(1), (height >> level).max(1)))
+            .enumerate()
+            // The first level is always created.
+            .take_while(|&(index, (width, height))| index == 0 || width.min(height) >= MIN_BLOOM_LEVEL_SIZE)
+            .map(|(_, (width, height))| BloomLevel {
+                texture: RenderTexture::with_layout(device, width, height, format, layout, MemoryCategory::PostFx),
+                scratch: RenderTexture::with_layout(device, width, height, format, layout, MemoryCategory::PostFx),
+            })
+            .collect()
+    }
+
+    /// The number of levels in the mip chain.
+    pub fn level_count(&self) -> usize {
+        self.levels.len()
+    }
+
+    fn draw(
+        encoder: &mut wgpu::CommandEncoder,
+        label: &str,
+        target: &wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+        pipeline: &wgpu::RenderPipeline,
+        bind_groups: &[&wgpu::BindGroup],
+    ) {
+        let mut render_pass = begin_post_pass(encoder, label, target, load);
+        render_pass.set_pipeline(pipeline);
+        for (index, bind_group) in bind_groups.iter().enumerate() {
+            render_pass.set_bind_group(index as u32, *bind_group, &[]);
+        }
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+impl PostEffect for Bloom {
+    fn name(&self) -> &str {
+        "Bloom"
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn recreate(&mut self, device: &wgpu::Device, chain: &PostChain) {
+        *self = Self {
+            enabled: self.enabled,
+            threshold: self.threshold,
+            knee: self.knee,
+            intensity: self.intensity,
+            radius: self.radius,
+            ..Self::new(device, chain)
+        };
+    }
+
+    fn prepare(&mut self, queue: &wgpu::Queue) {
+        let params = BloomParams {
+            threshold: self.threshold.max(0.0),
+            knee: self.knee.clamp(0.0, 1.0),
+            intensity: self.intensity.max(0.0),
+            radius: self.radius.clamp(0.0, 1.0),
+        };
+        if self.params.get() != params {
+            self.params.write(queue, params);
+        }
+    }
+
+    fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.levels = Self::create_levels(device, width, height, self.format, &self.input_layout);
+    }
+
+    /// Adds the blurred bloom texture to `input`.
+    fn render(&self, render_pass: &mut wgpu::RenderPass, input: &wgpu::BindGroup) {
+        render_pass.set_pipeline(&self.composite_pipeline);
+        render_pass.set_bind_group(0, input, &[]);
+        self.levels[0].texture.bind(1, render_pass);
+        render_pass.set_bind_group(2, &self.params_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn encode(&self, encoder: &mut wgpu::CommandEncoder, input: &wgpu::BindGroup, target: &wgpu::TextureView) {
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+        Self::draw(
+            encoder,
+            "Bloom Prefilter Pass",
+            self.levels[0].texture.view(),
+            clear,
+            &self.prefilter_pipeline,
+            &[input, &self.params_bind_group],
+        );
+        for window in self.levels.windows(2) {
+            Self::draw(
+                encoder,
+                "Bloom Downsample Pass",
+                window[1].texture.view(),
+                clear,
+                &self.downsample_pipeline,
+                &[&window[0].texture.binding().group],
+            );
+        }
+        for level in self.levels.iter() {
+            Self::draw(
+                encoder,
+                "Bloom Horizontal Blur Pass",
+                level.scratch.view(),
+                clear,
+                &self.blur_pipeline,
+                &[&level.texture.binding().group, &self.blur_bind_groups[0]],
+            );
+            Self::draw(
+                encoder,
+                "Bloom Vertical Blur Pass",
+                level.texture.view(),
+                clear,
+                &self.blur_pipeline,
+                &[&level.scratch.binding().group, &self.blur_bind_groups[1]],
+            );
+        }
+        for window in self.levels.windows(2).rev() {
+            Self::draw(
+                encoder,
+                "Bloom Upsample Pass",
+                window[0].texture.view(),
+                wgpu::LoadOp::Load,
+                &self.upsample_pipeline,
+                &[&window[1].texture.binding().group, &self.params_bind_group],
+            );
+        }
+        let mut render_pass = begin_post_pass(encoder, "Bloom Composite Pass", target, clear);
+        self.render(&mut render_pass, input);
+    }
+}
@@ -0,0 +1,374 @@
+use glam::Vec4;
+
+use crate::{model::loader::{MeshBuffers, MeshData}, voxel::vertex::Vertex, voxel_fog::Fog};
+
+use super::{
+    bindings::{BindGroupBuilder, Bindings},
+    buffers::UniformBuffer,
+    frame_uniforms::FrameUniformsBindGroup,
+    memory::{self, MemoryCategory, Tracked},
+    msaa::multisample_state,
+    post::{post_shader, HDR_FORMAT},
+    raytrace::RtLighting,
+    render_texture::{RenderTexture, RenderTextureBinding},
+    texture_array::TextureArray,
+    transforms::TransformsBindGroup,
+};
+
+/*
+The chunk can be drawn by the compute raytracer, by rasterizing a greedy mesh of it (see
+[crate::voxel::mesh::greedy_mesh]), or by both at once, split down the middle of the screen. Ctrl+M cycles
+through the [RenderMode]s.
+
+The rasterized view is rendered with the scene's camera into an HDR [RenderTexture] with its own depth
+buffer before the scene pass, then drawn into the scene pass with a scissor rect, like the inset of
+[super::pip]. The rest of the scene pass has no depth buffer, which is why the mesh isn't drawn into it
+directly. The faces are textured from the texture array with the layers of the blocks' [super::raytrace::BlockFaces],
+then lit by the raytracer's sun and ambient light and fogged by the raytracer's fog. Apart from the
+textures, the two views only differ by what needs rays: shadows, reflections, ambient occlusion, point
+lights and block light.
+*/
+
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// A scissor rect in pixels: x, y, width and height.
+pub type ScreenRegion = (u32, u32, u32, u32);
+
+/// The left and right halves of `region`. They cover it without overlapping, even at odd widths.
+pub fn split_region(region: ScreenRegion) -> (ScreenRegion, ScreenRegion) {
+    let (x, y, width, height) = region;
+    ((x, y, width / 2, height), (x + width / 2, y, width - width / 2, height))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderMode {
+    #[default]
+    Raytraced,
+    Rasterized,
+    /// Raytraced on the left half, rasterized on the right half.
+    SplitScreen,
+}
+
+impl RenderMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Raytraced => Self::Rasterized,
+            Self::Rasterized => Self::SplitScreen,
+            Self::SplitScreen => Self::Raytraced,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Raytraced => "Raytraced",
+            Self::Rasterized => "Rasterized",
+            Self::SplitScreen => "Split Screen",
+        }
+    }
+
+    pub fn shows_raytraced(self) -> bool {
+        self != Self::Rasterized
+    }
+
+    pub fn shows_rasterized(self) -> bool {
+        self != Self::Raytraced
+    }
+
+    /// The part of a screen of `width` x `height` that shows the raytraced view.
+    pub fn raytraced_region(self, width: u32, height: u32) -> Option<ScreenRegion> {
+        match self {
+            Self::Raytraced => Some((0, 0, width, height)),
+            Self::Rasterized => None,
+            Self::SplitScreen => Some(split_region((0, 0, width, height)).0),
+        }
+    }
+
+    /// The part of a screen of `width` x `height` that shows the rasterized view.
+    pub fn rasterized_region(self, width: u32, height: u32) -> Option<ScreenRegion> {
+        match self {
+            Self::Raytraced => None,
+            Self::Rasterized => Some((0, 0, width, height)),
+            Self::SplitScreen => Some(split_region((0, 0, width, height)).1),
+        }
+    }
+}
+
+pub struct RasterView {
+    screen_size: (u32, u32),
+    target: RenderTexture,
+    _depth: Tracked<wgpu::Texture>,
+    depth_view: wgpu::TextureView,
+    lighting: UniformBuffer<RtLighting>,
+    fog: UniformBuffer<Fog>,
+    scene_bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    mesh: Option<MeshBuffers>,
+}
+
+impl RasterView {
+    /// `sample_count` is that of the scene pass that the view is drawn in. `lighting` is the raytracer's
+    /// (see [super::raytrace::GpuRtLighting::lighting]).
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        sample_count: u32,
+        width: u32,
+        height: u32,
+        transforms: &TransformsBindGroup,
+        texture_array: &TextureArray,
+        frame_uniforms: &FrameUniformsBindGroup,
+        lighting: RtLighting,
+    ) -> Self {
+        let lighting = UniformBuffer::new(device, Some("Raster Lighting Buffer"), lighting);
+        let fog = UniformBuffer::new(device, Some("Raster Fog Buffer"), Fog::new(0.0, 0.0, Vec4::ZERO));
+        let scene_layout = BindGroupBuilder::new()
+            .label("Raster Scene Bind Group Layout")
+            .uniform(0, wgpu::ShaderStages::FRAGMENT)
+            .uniform(1, wgpu::ShaderStages::FRAGMENT)
+            .build(device);
+        let scene_bind_group = Bindings::new()
+            .buffer(0, lighting.buffer())
+            .buffer(1, fog.buffer())
+            .build(device, Some("Raster Scene Bind Group"), &scene_layout);
+        let texture_layout = RenderTextureBinding::create_layout(device);
+        let target = RenderTexture::with_layout(device, width, height, HDR_FORMAT, &texture_layout, MemoryCategory::Targets);
+        let (depth, depth_view) = Self::create_depth(device, width, height);
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../shaders/voxel_raster.wgsl"));
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Raster Pipeline Layout"),
+            bind_group_layouts: &[
+                &transforms.bind_group_layout,
+                &scene_layout,
+                &texture_array.bind_group.bind_group_layout,
+                &frame_uniforms.bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Raster Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[Vertex::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: HDR_FORMAT,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: multisample_state(1),
+            multiview: None,
+            cache: None,
+        });
+
+        let composite_shader = device.create_shader_module(post_shader!("Raster Composite Shader", "../shaders/post/copy.wgsl"));
+        let composite_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Raster Composite Pipeline Layout"),
+            bind_group_layouts: &[&texture_layout],
+            push_constant_ranges: &[],
+        });
+        let composite_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Raster Composite Pipeline"),
+            layout: Some(&composite_layout),
+            vertex: wgpu::VertexState {
+                module: &composite_shader,
+                entry_point: Some("vertex_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &composite_shader,
+                entry_point: Some("fragment_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: HDR_FORMAT,
+                    // The view is cleared to transparent, so the skybox shows where there are no blocks.
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: multisample_state(sample_count),
+            multiview: None,
+            cache: None,
+        });
+        Self {
+            screen_size: (width, height),
+            target,
+            _depth: depth,
+            depth_view,
+            lighting,
+            fog,
+            scene_bind_group,
+            pipeline,
+            composite_pipeline,
+            mesh: None,
+        }
+    }
+
+    /// Recreates the GPU resources for a new device or sample count. The mesh is dropped, so it has to be
+    /// set again.
+    pub fn recreate(
+        &mut self,
+        device: &wgpu::Device,
+        sample_count: u32,
+        transforms: &TransformsBindGroup,
+        texture_array: &TextureArray,
+        frame_uniforms: &FrameUniformsBindGroup,
+    ) {
+        let (width, height) = self.screen_size;
+        *self = Self::new(device, sample_count, width, height, transforms, texture_array, frame_uniforms, self.lighting.get());
+    }
+
+    fn create_depth(device: &wgpu::Device, width: u32, height: u32) -> (Tracked<wgpu::Texture>, wgpu::TextureView) {
+        let depth = memory::track(MemoryCategory::Targets, device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Raster Depth Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        }));
+        let view = depth.create_view(&wgpu::TextureViewDescriptor::default());
+        (depth, view)
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.screen_size = (width, height);
+        let layout = self.target.binding().layout.clone();
+        self.target = RenderTexture::with_layout(device, width, height, HDR_FORMAT, &layout, MemoryCategory::Targets);
+        (self._depth, self.depth_view) = Self::create_depth(device, width, height);
+    }
+
+    /// Replaces the mesh that is drawn.
+    pub fn set_mesh(&mut self, device: &wgpu::Device, mesh: &MeshData) {
+        self.mesh = Some(mesh.create_buffers(device));
+    }
+
+    pub fn has_mesh(&self) -> bool {
+        self.mesh.is_some()
+    }
+
+    pub fn triangle_count(&self) -> u32 {
+        self.mesh.as_ref().map_or(0, |mesh| mesh.num_indices / 3)
+    }
+
+    /// Writes the raytracer's lighting and fog if they changed.
+    pub fn prepare(&self, queue: &wgpu::Queue, lighting: RtLighting, fog: &Fog) {
+        if bytemuck::bytes_of(&lighting) != bytemuck::bytes_of(&self.lighting.get()) {
+            self.lighting.write(queue, lighting);
+        }
+        if bytemuck::bytes_of(fog) != bytemuck::bytes_of(&self.fog.get()) {
+            self.fog.write(queue, *fog);
+        }
+    }
+
+    /// Renders the mesh into the view's texture with the camera of `transforms`.
+    pub fn encode(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        transforms: &TransformsBindGroup,
+        texture_array: &TextureArray,
+        frame_uniforms: &FrameUniformsBindGroup,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Raster Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: self.target.view(),
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Discard,
+                }),
+                stencil_ops: None,
+            }),
+            ..Default::default()
+        });
+        let Some(mesh) = &self.mesh else {
+            return;
+        };
+        if mesh.num_indices == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &transforms.bind_group, &[]);
+        render_pass.set_bind_group(1, &self.scene_bind_group, &[]);
+        render_pass.set_bind_group(2, &texture_array.bind_group.bind_group, &[]);
+        render_pass.set_bind_group(3, &frame_uniforms.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..mesh.num_indices, 0, 0..1);
+    }
+
+    /// Draws the view into `region` of the scene pass.
+    pub fn composite(&self, render_pass: &mut wgpu::RenderPass, region: ScreenRegion) {
+        let (x, y, width, height) = region;
+        if width == 0 || height == 0 {
+            return;
+        }
+        render_pass.set_scissor_rect(x, y, width, height);
+        render_pass.set_pipeline(&self.composite_pipeline);
+        self.target.bind(0, render_pass);
+        render_pass.draw(0..3, 0..1);
+        let (screen_width, screen_height) = self.screen_size;
+        render_pass.set_scissor_rect(0, 0, screen_width, screen_height);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_mode_test() {
+        let mut mode = RenderMode::default();
+        assert_eq!(mode.rasterized_region(1280, 720), None);
+        mode = mode.next();
+        assert_eq!(mode, RenderMode::Rasterized);
+        assert!(!mode.shows_raytraced() && mode.shows_rasterized());
+        mode = mode.next();
+        // The halves cover the screen without overlapping, even at odd widths.
+        assert_eq!(mode.raytraced_region(1281, 720), Some((0, 0, 640, 720)));
+        assert_eq!(mode.rasterized_region(1281, 720), Some((640, 0, 641, 720)));
+        assert_eq!(mode.next(), RenderMode::Raytraced);
+        // The raytraced half is split again for A/B comparisons.
+        assert_eq!(split_region((0, 0, 640, 720)), ((0, 0, 320, 720), (320, 0, 320, 720)));
+        assert_eq!(split_region((640, 10, 5, 20)), ((640, 10, 2, 20), (642, 10, 3, 20)));
+    }
+}
@@ -1,9 +1,14 @@
-use std::{cell::RefCell, fs::File, io::BufWriter, path::Path};
+use std::{collections::HashMap, fs::File, io::BufWriter, path::Path};
 
 use glam::*;
 use bytemuck::{NoUninit, Pod, Zeroable};
 use wgpu::util::DeviceExt;
-use crate::{camera::Camera, math::{ray::Ray3, *}};
+use crate::{camera::Camera, math::{morton::morton3_encode, ray::Ray3, *}, voxel_fog::Fog, write_field};
+
+use crate::animation::camera_path::CameraPath;
+use crate::entity::{Entities, GpuEntity, MAX_ENTITIES};
+
+use super::{bindings::{BindGroupBuilder, Bindings}, block_light::BlockLight, blue_noise::{BlueNoise, DEFAULT_BLUE_NOISE_SIZE}, buffers::{StorageBuffer, UniformBuffer}, clouds::{Clouds, GpuClouds}, color::{color_shader, ColorConversion, ColorSpace}, debug_capture::debug_group, dispatch::{self, IndirectDispatch}, frame_graph_lite::{FrameGraphLite, PassProfiler}, fxaa::Fxaa, lighting::{GpuPointLight, LightingPreset, Lights, MAX_POINT_LIGHTS}, memory::{self, MemoryCategory, Tracked}, msaa::multisample_state, offline::{self, SequenceError, SequenceSettings}, post::HDR_FORMAT, render_scale::{MAX_RENDER_SCALE, MIN_RENDER_SCALE}, shader_errors::{self, ShaderError}, skybox::SkyboxCubemap, staging::StagingRing, taa::Taa};
 
 #[derive(Debug, Clone, Copy)]
 pub struct RayCalc {
@@ -164,112 +169,122 @@ impl GpuRaytraceCamera {
 }
 
 pub struct RaytraceCamera {
-    pub gpu_cam: GpuRaytraceCamera,
-    pub buffer: wgpu::Buffer,
-    // pub bind_group: wgpu::BindGroup,
-    // pub bind_group_layout: wgpu::BindGroupLayout,
+    pub buffer: UniformBuffer<GpuRaytraceCamera>,
 }
 
 impl RaytraceCamera {
     pub fn new(camera: &Camera, device: &wgpu::Device) -> Self {
         let gpu_cam = GpuRaytraceCamera::new(camera, 0.1, 1000.0);
-        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Raytrace Camera Buffer"),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            contents: bytemuck::bytes_of(&gpu_cam),
-        });
-        // let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        //     label: Some("Raytrace Camera Layout"),
-        //     entries: &[wgpu::BindGroupLayoutEntry {
-        //         binding: 0,
-        //         ty: wgpu::BindingType::Buffer {
-        //             ty: wgpu::BufferBindingType::Uniform,
-        //             has_dynamic_offset: false,
-        //             min_binding_size: None,
-        //         },
-        //         visibility: wgpu::ShaderStages::COMPUTE,
-        //         count: None,
-        //     }],
-        // });
-        // let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        //     label: Some("Raytrace Camera Group"),
-        //     layout: &bind_group_layout,
-            // entries: &[wgpu::BindGroupEntry {
-            //     binding: 0,
-            //     resource: buffer.as_entire_binding(),
-            // }]
-        // });
+        let buffer = UniformBuffer::new(device, Some("Raytrace Camera Buffer"), gpu_cam);
         Self {
-            gpu_cam,
             buffer,
-            // bind_group,
-            // bind_group_layout,
         }
     }
 
-    // pub fn bind(&self, index: u32, compute_pass: &mut wgpu::ComputePass) {
-    //     compute_pass.set_bind_group(index, &self.bind_group, &[]);
-    // }
+    pub fn gpu_cam(&self) -> GpuRaytraceCamera {
+        self.buffer.get()
+    }
 
     pub fn write_transform(&mut self, transform: GpuTransform, queue: &wgpu::Queue) {
-        self.gpu_cam.transform = transform;
-        const TRANSFORM_SIZE: usize = std::mem::size_of::<GpuTransform>();
-        const TRANSFORM_OFFSET: usize = std::mem::offset_of!(GpuRaytraceCamera, transform);
-        const TRANSFORM_OFFSET_END: usize = TRANSFORM_OFFSET + TRANSFORM_SIZE;
-        const TRANSFORM_RANGE: std::ops::Range<usize> = TRANSFORM_OFFSET..TRANSFORM_OFFSET_END;
-        queue.write_buffer(
-            &self.buffer,
-            TRANSFORM_OFFSET as u64,
-            &bytemuck::bytes_of(&self.gpu_cam)[TRANSFORM_RANGE],
-        );
+        write_field!(self.buffer, queue, transform = transform);
     }
 
     pub fn write_dimensions(&mut self, width: u32, height: u32, queue: &wgpu::Queue) {
-        self.gpu_cam.dimensions = Dim::new(width, height);
-        // I know it's 8 bytes, but I'd rather use size_of just in case.
-        const DIM_SIZE: usize = std::mem::size_of::<Dim>();
-        const DIM_OFFSET: usize = std::mem::offset_of!(GpuRaytraceCamera, dimensions);
-        const DIM_OFFSET_END: usize = DIM_OFFSET + DIM_SIZE;
-        const DIM_RANGE: std::ops::Range<usize> = DIM_OFFSET..DIM_OFFSET_END;
-        queue.write_buffer(
-            &self.buffer,
-            DIM_OFFSET as u64,
-            &bytemuck::bytes_of(&self.gpu_cam)[DIM_RANGE],
-        );
+        write_field!(self.buffer, queue, dimensions = Dim::new(width, height));
     }
 
     pub fn write_range(&mut self, near: f32, far: f32, queue: &wgpu::Queue) {
-        self.gpu_cam.range = RenderRange::new(near, far);
-        const RANGE_SIZE: usize = std::mem::size_of::<RenderRange>();
-        const RANGE_OFFSET: usize = std::mem::offset_of!(GpuRaytraceCamera, range);
-        const RANGE_OFFSET_END: usize = RANGE_OFFSET + RANGE_SIZE;
-        const RANGE_RANGE: std::ops::Range<usize> = RANGE_OFFSET..RANGE_OFFSET_END;
-        queue.write_buffer(
-            &self.buffer,
-            RANGE_OFFSET as u64,
-            &bytemuck::bytes_of(&self.gpu_cam)[RANGE_RANGE],
-        );
+        write_field!(self.buffer, queue, range = RenderRange::new(near, far));
     }
 
     /// This method should generally only be called once: when first setting
     /// the camera. You should otherwise use the specific field writers.
     pub fn write_camera(&mut self, camera: &Camera, queue: &wgpu::Queue) {
-        self.gpu_cam.transform = GpuTransform::new(
-            GpuMat3::new(camera.rotation_matrix()),
-            GpuVec3::from_vec3(camera.position),
-        );
-        self.gpu_cam.dimensions = Dim::new(
-            camera.screen_size.width,
-            camera.screen_size.height
-        );
-        self.gpu_cam.range = RenderRange::new(camera.z_near, camera.z_far);
-        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&self.gpu_cam));
+        self.buffer.write(queue, GpuRaytraceCamera::new(camera, camera.z_near, camera.z_far));
+    }
+}
+
+/// Where the raytracer gets the direction of each primary ray from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RayDirectionMode {
+    /// Read from a texture that is filled by a separate pass whenever the field of view or resolution
+    /// changes. The texture is as large as the render target (32 MB at 1920x1080).
+    Precomputed,
+    /// Computed from the NDC multiplier in the raytrace shader. This costs a few ALU operations per
+    /// pixel instead of a texture read, and the directions texture isn't allocated.
+    #[default]
+    Inline,
+}
+
+impl RayDirectionMode {
+    pub const fn next(self) -> Self {
+        match self {
+            RayDirectionMode::Precomputed => RayDirectionMode::Inline,
+            RayDirectionMode::Inline => RayDirectionMode::Precomputed,
+        }
+    }
+}
+
+/// The workgroup dimensions of the raytrace shader, which are set through override constants when
+/// the pipeline is created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WorkgroupSize {
+    pub x: u32,
+    pub y: u32,
+}
+
+impl WorkgroupSize {
+    pub const DEFAULT: Self = Self::new(16, 16);
+    /// Sizes to cycle through when comparing timings.
+    pub const PRESETS: [Self; 6] = [
+        Self::new(8, 8),
+        Self::new(16, 8),
+        Self::new(8, 16),
+        Self::new(16, 16),
+        Self::new(32, 8),
+        Self::new(32, 4),
+    ];
+
+    pub const fn new(x: u32, y: u32) -> Self {
+        Self { x, y }
+    }
+
+    /// The preset after this one (or the first preset if this isn't one).
+    pub fn next_preset(self) -> Self {
+        let index = Self::PRESETS.iter().position(|&size| size == self).map_or(0, |index| index + 1);
+        Self::PRESETS[index % Self::PRESETS.len()]
+    }
+
+    /// Whether the device can run workgroups of this size.
+    pub fn is_supported(self, limits: &wgpu::Limits) -> bool {
+        self.x > 0
+            && self.y > 0
+            && self.x <= limits.max_compute_workgroup_size_x
+            && self.y <= limits.max_compute_workgroup_size_y
+            && self.x * self.y <= limits.max_compute_invocations_per_workgroup
+    }
+
+    /// The number of workgroups that cover a `width` x `height` target.
+    pub fn dispatch_size(self, width: u32, height: u32) -> (u32, u32) {
+        dispatch::workgroup_count(width, height, self)
+    }
+}
+
+impl Default for WorkgroupSize {
+    fn default() -> Self {
+        Self::DEFAULT
     }
 }
 
+/// The `@workgroup_size` of precompute_rays.wgsl.
+const PRECOMPUTE_WORKGROUP_SIZE: WorkgroupSize = WorkgroupSize::new(16, 16);
+
 pub struct PrecomputedDirections {
+    mode: RayDirectionMode,
+    /// The size of the render target. The texture is only this large with [RayDirectionMode::Precomputed].
+    size: (u32, u32),
     // This never needs to be accessed CPU side.
-    pub directions: wgpu::Texture,
+    pub directions: Tracked<wgpu::Texture>,
     pub ndc_mult: wgpu::Buffer,
     pub read_bind_group: wgpu::BindGroup,
     pub read_bind_group_layout: wgpu::BindGroupLayout,
@@ -279,23 +294,8 @@ pub struct PrecomputedDirections {
 }
 
 impl PrecomputedDirections {
-    pub fn new(device: &wgpu::Device, fov: f32) -> Self {
-        let directions = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Directions Storage"),
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba32Float,
-            mip_level_count: 1,
-            sample_count: 1,
-            size: wgpu::Extent3d {
-                width: 1920,
-                height: 1080,
-                depth_or_array_layers: 1,
-            },
-            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
-        });
-
-        let ndc_multiplier = calc_ray_mult(fov, (1920, 1080));
+    pub fn new(device: &wgpu::Device, fov: f32, width: u32, height: u32, mode: RayDirectionMode) -> Self {
+        let ndc_multiplier = calc_ray_mult(fov, BASE_RESOLUTION);
         
         let ndc_mult = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Precompute Directions NDC Multiplier Buffer"),
@@ -303,86 +303,27 @@ impl PrecomputedDirections {
             contents: bytemuck::bytes_of(&ndc_multiplier),
         });
 
-        let view = directions.create_view(&wgpu::TextureViewDescriptor {
-            label: Some("Directions View"),
-            format: Some(wgpu::TextureFormat::Rgba32Float),
-            dimension: Some(wgpu::TextureViewDimension::D2),
-            array_layer_count: None,
-            aspect: wgpu::TextureAspect::All,
-            base_array_layer: 0,
-            base_mip_level: 0,
-            mip_level_count: None,
-            usage: None,
-        });
-        let read_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Precomputed Ray Directions Read Layout"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::StorageTexture {
-                        access: wgpu::StorageTextureAccess::ReadOnly,
-                        format: wgpu::TextureFormat::Rgba32Float,
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                    },
-                    count: None,
-                }
-            ],
-        });
-        
-        let read_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Precomputed Ray Directions Read Group"),
-            layout: &read_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::TextureView(&view),
-            }]
-        });
-
-        let compute_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Precomputed Ray Directions Compute Layout"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::StorageTexture {
-                        access: wgpu::StorageTextureAccess::WriteOnly,
-                        format: wgpu::TextureFormat::Rgba32Float,
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Buffer {
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                        ty: wgpu::BufferBindingType::Uniform,
-                    },
-                    count: None,
-                }
-            ],
-        });
-
-        let compute_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Precomputed Ray Directions Write Group"),
-            layout: &compute_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                        buffer: &ndc_mult,
-                        offset: 0,
-                        size: None,
-                    }),
-                }
-            ]
-        });
+        let read_bind_group_layout = BindGroupBuilder::new()
+            .label("Precomputed Ray Directions Read Layout")
+            .storage_texture(0, wgpu::ShaderStages::COMPUTE, wgpu::StorageTextureAccess::ReadOnly, wgpu::TextureFormat::Rgba32Float)
+            .uniform(1, wgpu::ShaderStages::COMPUTE)
+            .build(device);
+
+        let compute_bind_group_layout = BindGroupBuilder::new()
+            .label("Precomputed Ray Directions Compute Layout")
+            .storage_texture(0, wgpu::ShaderStages::COMPUTE, wgpu::StorageTextureAccess::WriteOnly, wgpu::TextureFormat::Rgba32Float)
+            .uniform(1, wgpu::ShaderStages::COMPUTE)
+            .build(device);
+
+        let (texture_width, texture_height) = Self::texture_size(mode, (width, height));
+        let (directions, read_bind_group, compute_bind_group) = Self::create_directions(
+            device,
+            texture_width,
+            texture_height,
+            &ndc_mult,
+            &read_bind_group_layout,
+            &compute_bind_group_layout,
+        );
 
         let compute_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Precompute Ray Directions Compute Pipeline Layout"),
@@ -402,6 +343,8 @@ impl PrecomputedDirections {
         });
 
         Self {
+            mode,
+            size: (width, height),
             directions,
             ndc_mult,
             read_bind_group,
@@ -412,10 +355,118 @@ impl PrecomputedDirections {
         }
     }
 
+    fn create_directions(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        ndc_mult: &wgpu::Buffer,
+        read_bind_group_layout: &wgpu::BindGroupLayout,
+        compute_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> (Tracked<wgpu::Texture>, wgpu::BindGroup, wgpu::BindGroup) {
+        let directions = memory::track(MemoryCategory::Targets, device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Directions Storage"),
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            mip_level_count: 1,
+            sample_count: 1,
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        }));
+
+        let view = directions.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Directions View"),
+            format: Some(wgpu::TextureFormat::Rgba32Float),
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            array_layer_count: None,
+            aspect: wgpu::TextureAspect::All,
+            base_array_layer: 0,
+            base_mip_level: 0,
+            mip_level_count: None,
+            usage: None,
+        });
+
+        let read_bind_group = Bindings::new()
+            .texture_view(0, &view)
+            .buffer(1, ndc_mult)
+            .build(device, Some("Precomputed Ray Directions Read Group"), read_bind_group_layout);
+
+        let compute_bind_group = Bindings::new()
+            .texture_view(0, &view)
+            .buffer(1, ndc_mult)
+            .build(device, Some("Precomputed Ray Directions Write Group"), compute_bind_group_layout);
+
+        (directions, read_bind_group, compute_bind_group)
+    }
+
+    /// Inline directions don't read the texture, but the binding still needs one.
+    fn texture_size(mode: RayDirectionMode, size: (u32, u32)) -> (u32, u32) {
+        match mode {
+            RayDirectionMode::Precomputed => size,
+            RayDirectionMode::Inline => (1, 1),
+        }
+    }
+
+    pub fn mode(&self) -> RayDirectionMode {
+        self.mode
+    }
+
+    /// Switches the mode and recreates the directions texture. The raytrace pipeline has to be
+    /// recreated with the new mode's constants, and the directions need to be computed again afterwards.
+    pub fn set_mode(&mut self, device: &wgpu::Device, mode: RayDirectionMode) {
+        self.mode = mode;
+        let (width, height) = self.size;
+        self.resize(device, width, height);
+    }
+
+    /// Recreates the directions texture. The directions need to be computed again afterwards.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.size = (width, height);
+        let (width, height) = Self::texture_size(self.mode, self.size);
+        let (directions, read_bind_group, compute_bind_group) = Self::create_directions(
+            device,
+            width,
+            height,
+            &self.ndc_mult,
+            &self.read_bind_group_layout,
+            &self.compute_bind_group_layout,
+        );
+        self.directions = directions;
+        self.read_bind_group = read_bind_group;
+        self.compute_bind_group = compute_bind_group;
+    }
+
     pub fn compute(&self, compute_pass: &mut wgpu::ComputePass) {
         compute_pass.set_pipeline(&self.compute_pipeline);
         compute_pass.set_bind_group(0, &self.compute_bind_group, &[]);
-        compute_pass.dispatch_workgroups(120, 68, 1);
+        dispatch::dispatch_2d(compute_pass, self.directions.width(), self.directions.height(), PRECOMPUTE_WORKGROUP_SIZE);
+    }
+
+    /// Runs the precompute pass in its own submission. Does nothing with [RayDirectionMode::Inline].
+    pub fn submit_compute(&self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if self.mode == RayDirectionMode::Inline {
+            return;
+        }
+        // This runs outside of the frame (on resize and FOV changes), so the pass isn't timed.
+        let mut passes = FrameGraphLite::new(device, "Precompute Encoder", None);
+        passes.compute_pass("Precompute", |compute_pass| self.compute(compute_pass));
+        queue.submit(Some(passes.finish(None)));
+    }
+
+    /// Writes the NDC multiplier for `fov`. Precomputed directions are only updated once the precompute
+    /// pass has run again.
+    pub fn write_fov(&self, fov: f32, queue: &wgpu::Queue) {
+        self.write_fov_for(fov, BASE_RESOLUTION, queue);
+    }
+
+    /// Like [PrecomputedDirections::write_fov], but for an aspect ratio other than [BASE_RESOLUTION]'s.
+    pub fn write_fov_for(&self, fov: f32, resolution: (u32, u32), queue: &wgpu::Queue) {
+        let ndc_multiplier = calc_ray_mult(fov, resolution);
+        queue.write_buffer(&self.ndc_mult, 0, bytemuck::bytes_of(&ndc_multiplier));
     }
 
     pub fn bind_read(&self, index: u32, compute_pass: &mut wgpu::ComputePass) {
@@ -589,7 +640,15 @@ impl RayHit {
 }
 
 pub struct GpuRaytraceResult {
-    pub result_texture: wgpu::Texture,
+    pub result_texture: Tracked<wgpu::Texture>,
+    /// A view of the result for sampling.
+    pub result_view: wgpu::TextureView,
+    /// Running average of the result over the frames that the camera has been still.
+    pub accumulation_texture: Tracked<wgpu::Texture>,
+    /// The G-buffer's block IDs ([HIT_ID_FORMAT]), see [GBufferHit].
+    pub hit_id_texture: Tracked<wgpu::Texture>,
+    /// The G-buffer's face normals and hit distances ([HIT_NORMAL_DISTANCE_FORMAT]), see [GBufferHit].
+    pub hit_normal_distance_texture: Tracked<wgpu::Texture>,
     pub result_sampler: wgpu::Sampler,
     pub read_bind_group_layout: wgpu::BindGroupLayout,
     pub read_bind_group: wgpu::BindGroup,
@@ -597,50 +656,29 @@ pub struct GpuRaytraceResult {
     pub write_bind_group: wgpu::BindGroup,
     pub render_bind_group_layout: wgpu::BindGroupLayout,
     pub render_bind_group: wgpu::BindGroup,
+    /// Binds the G-buffer for reading in any shader stage (see [GpuRaytraceResult::hit_bind_group]).
+    pub hit_bind_group_layout: wgpu::BindGroupLayout,
+    pub hit_bind_group: wgpu::BindGroup,
     pub render_pipeline: wgpu::RenderPipeline,
+    /// Used instead of `render_pipeline` when the result is larger than [BASE_RESOLUTION]. Smaller results
+    /// are stretched by the bilinear sampler.
+    pub downsample_pipeline: wgpu::RenderPipeline,
 }
 
-impl GpuRaytraceResult {
-    pub fn new(device: &wgpu::Device) -> Self {
-        let result_texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Raytrace Result Storage"),
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
-            mip_level_count: 1,
-            sample_count: 1,
-            size: wgpu::Extent3d {
-                width: 1920,
-                height: 1080,
-                depth_or_array_layers: 1,
-            },
-            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
-            view_formats: &[],
-        });
-
-        let result_storage_view = result_texture.create_view(&wgpu::TextureViewDescriptor {
-            label: "Raytrace Result Storage Texture".into(),
-            format: Some(wgpu::TextureFormat::Rgba8Unorm),
-            dimension: Some(wgpu::TextureViewDimension::D2),
-            array_layer_count: None,
-            aspect: wgpu::TextureAspect::All,
-            base_array_layer: 0,
-            base_mip_level: 0,
-            mip_level_count: None,
-            usage: Some(wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING),
-        });
-
-        let result_render_view = result_texture.create_view(&wgpu::TextureViewDescriptor {
-            label: "Raytrace Result Storage Render Texture".into(),
-            format: Some(wgpu::TextureFormat::Rgba8Unorm),
-            dimension: Some(wgpu::TextureViewDimension::D2),
-            array_layer_count: None,
-            aspect: wgpu::TextureAspect::All,
-            base_array_layer: 0,
-            base_mip_level: 0,
-            mip_level_count: None,
-            usage: Some(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING),
-        });
+struct ResultTargets {
+    result_texture: Tracked<wgpu::Texture>,
+    result_view: wgpu::TextureView,
+    accumulation_texture: Tracked<wgpu::Texture>,
+    hit_id_texture: Tracked<wgpu::Texture>,
+    hit_normal_distance_texture: Tracked<wgpu::Texture>,
+    read_bind_group: wgpu::BindGroup,
+    write_bind_group: wgpu::BindGroup,
+    render_bind_group: wgpu::BindGroup,
+    hit_bind_group: wgpu::BindGroup,
+}
 
+impl GpuRaytraceResult {
+    pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
         let result_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             label: Some("Raytrace Result Render Sampler"),
             address_mode_u: wgpu::AddressMode::ClampToEdge,
@@ -651,96 +689,81 @@ impl GpuRaytraceResult {
             ..Default::default()
         });
 
-        let read_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Raytrace Result Read Layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                ty: wgpu::BindingType::StorageTexture {
-                    format: wgpu::TextureFormat::Rgba8Unorm,
-                    access: wgpu::StorageTextureAccess::ReadOnly,
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                },
-                visibility: wgpu::ShaderStages::COMPUTE,
-                count: None,
-            }]
-        });
+        let read_bind_group_layout = BindGroupBuilder::new()
+            .label("Raytrace Result Read Layout")
+            .storage_texture(0, wgpu::ShaderStages::COMPUTE, wgpu::StorageTextureAccess::ReadOnly, RESULT_FORMAT)
+            .build(device);
+        let write_bind_group_layout = BindGroupBuilder::new()
+            .label("Raytrace Result Write Layout")
+            .storage_texture(0, wgpu::ShaderStages::COMPUTE, wgpu::StorageTextureAccess::WriteOnly, RESULT_FORMAT)
+            .storage_texture(1, wgpu::ShaderStages::COMPUTE, wgpu::StorageTextureAccess::ReadWrite, wgpu::TextureFormat::Rgba32Float)
+            .storage_texture(2, wgpu::ShaderStages::COMPUTE, wgpu::StorageTextureAccess::WriteOnly, HIT_ID_FORMAT)
+            .storage_texture(3, wgpu::ShaderStages::COMPUTE, wgpu::StorageTextureAccess::WriteOnly, HIT_NORMAL_DISTANCE_FORMAT)
+            .build(device);
+        let render_bind_group_layout = BindGroupBuilder::new()
+            .label("Raytrace Result Render Bind Group Layout")
+            .texture_2d(0, wgpu::ShaderStages::FRAGMENT)
+            .sampler(1, wgpu::ShaderStages::FRAGMENT)
+            .build(device);
+        let hit_stages = wgpu::ShaderStages::VERTEX_FRAGMENT | wgpu::ShaderStages::COMPUTE;
+        let hit_bind_group_layout = BindGroupBuilder::new()
+            .label("Raytrace G-Buffer Bind Group Layout")
+            .texture(0, hit_stages, wgpu::TextureSampleType::Uint, wgpu::TextureViewDimension::D2)
+            .texture(1, hit_stages, wgpu::TextureSampleType::Float { filterable: false }, wgpu::TextureViewDimension::D2)
+            .build(device);
+
+        let targets = Self::create_targets(
+            device,
+            width,
+            height,
+            &result_sampler,
+            &read_bind_group_layout,
+            &write_bind_group_layout,
+            &render_bind_group_layout,
+            &hit_bind_group_layout,
+        );
 
-        let read_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Raytrace Result Read Group"),
-            layout: &read_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::TextureView(&result_storage_view),
-            }]
-        });
-        let write_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Raytrace Result Write Layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                ty: wgpu::BindingType::StorageTexture {
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                    format: wgpu::TextureFormat::Rgba8Unorm,
-                    access: wgpu::StorageTextureAccess::WriteOnly,
-                },
-                visibility: wgpu::ShaderStages::COMPUTE,
-                count: None,
-            }]
-        });
-        let write_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Raytrace Result Write Group"),
-            layout: &write_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::TextureView(&result_storage_view),
-            }]
-        });
-        let render_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Raytrace Result Render Bind Group Layout"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    count: None,
-                    ty: wgpu::BindingType::Texture {
-                        multisampled: false,
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                    },
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    count: None,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                },
-            ]
-        });
+        let (render_pipeline, downsample_pipeline) = Self::create_render_pipelines(device, &render_bind_group_layout, DEFAULT_TARGET_FORMAT, 1);
 
-        let render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Raytrace Result Render Bind Group"),
-            layout: &render_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&result_render_view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&result_sampler),
-                },
-            ]
-        });
+        Self {
+            result_texture: targets.result_texture,
+            result_view: targets.result_view,
+            accumulation_texture: targets.accumulation_texture,
+            hit_id_texture: targets.hit_id_texture,
+            hit_normal_distance_texture: targets.hit_normal_distance_texture,
+            result_sampler,
+            read_bind_group_layout,
+            read_bind_group: targets.read_bind_group,
+            write_bind_group_layout,
+            write_bind_group: targets.write_bind_group,
+            render_bind_group_layout,
+            render_bind_group: targets.render_bind_group,
+            hit_bind_group_layout,
+            hit_bind_group: targets.hit_bind_group,
+            render_pipeline,
+            downsample_pipeline,
+        }
+    }
 
-        let render_shader = device.create_shader_module(wgpu::include_wgsl!("../shaders/raytrace_result_render.wgsl"));
+    /// The pipelines that draw the result to a target of `format`, in a pass with `sample_count` samples.
+    /// The result holds linear colors, which are encoded in the shader if the target isn't sRGB.
+    fn create_render_pipelines(
+        device: &wgpu::Device,
+        render_bind_group_layout: &wgpu::BindGroupLayout,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> (wgpu::RenderPipeline, wgpu::RenderPipeline) {
+        let render_shader = device.create_shader_module(color_shader!("Raytrace Result Render Shader", "../shaders/raytrace_result_render.wgsl"));
+        let constants = ColorConversion::for_target(ColorSpace::Linear, format).constants();
 
         let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Raytrace Result Render Pipeline Layout"),
-            bind_group_layouts: &[&render_bind_group_layout],
+            bind_group_layouts: &[render_bind_group_layout],
             push_constant_ranges: &[],
         });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Raytrace Result Render Pipeline"),
+        let create_pipeline = |label: &str, fragment_entry: &str| device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
             layout: Some(&render_pipeline_layout),
             vertex: wgpu::VertexState {
                 module: &render_shader,
@@ -750,17 +773,20 @@ impl GpuRaytraceResult {
             },
             fragment: Some(wgpu::FragmentState {
                 module: &render_shader,
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                entry_point: Some("fragment_main"),
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &constants,
+                    ..Default::default()
+                },
+                entry_point: Some(fragment_entry),
                 targets: &[Some(wgpu::ColorTargetState {
                     blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                     write_mask: wgpu::ColorWrites::ALL,
-                    format: wgpu::TextureFormat::Bgra8UnormSrgb,
+                    format,
                 })],
             }),
             cache: None,
             depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
+            multisample: multisample_state(sample_count),
             multiview: None,
             primitive: wgpu::PrimitiveState {
                 cull_mode: Some(wgpu::Face::Back),
@@ -771,19 +797,173 @@ impl GpuRaytraceResult {
             },
         });
 
-        Self {
+        (
+            create_pipeline("Raytrace Result Render Pipeline", "fragment_main"),
+            create_pipeline("Raytrace Result Downsample Pipeline", "fragment_downsample"),
+        )
+    }
+
+    /// Recreates the render pipelines for a target of `format`, in a pass with `sample_count` samples.
+    pub fn set_render_target(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32) {
+        (self.render_pipeline, self.downsample_pipeline) = Self::create_render_pipelines(device, &self.render_bind_group_layout, format, sample_count);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create_targets(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        result_sampler: &wgpu::Sampler,
+        read_bind_group_layout: &wgpu::BindGroupLayout,
+        write_bind_group_layout: &wgpu::BindGroupLayout,
+        render_bind_group_layout: &wgpu::BindGroupLayout,
+        hit_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> ResultTargets {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let result_texture = memory::track(MemoryCategory::Targets, device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Raytrace Result Storage"),
+            dimension: wgpu::TextureDimension::D2,
+            format: RESULT_FORMAT,
+            mip_level_count: 1,
+            sample_count: 1,
+            size,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        }));
+
+        let accumulation_texture = memory::track(MemoryCategory::Targets, device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Raytrace Accumulation Storage"),
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            mip_level_count: 1,
+            sample_count: 1,
+            size,
+            usage: wgpu::TextureUsages::STORAGE_BINDING,
+            view_formats: &[],
+        }));
+
+        let accumulation_view = accumulation_texture.create_view(&wgpu::TextureViewDescriptor {
+            label: "Raytrace Accumulation Storage Texture".into(),
+            ..Default::default()
+        });
+
+        let [hit_id_texture, hit_normal_distance_texture] = [
+            ("Raytrace G-Buffer IDs", HIT_ID_FORMAT),
+            ("Raytrace G-Buffer Normals and Distances", HIT_NORMAL_DISTANCE_FORMAT),
+        ].map(|(label, format)| memory::track(MemoryCategory::Targets, device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            mip_level_count: 1,
+            sample_count: 1,
+            size,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })));
+        let hit_id_view = hit_id_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let hit_normal_distance_view = hit_normal_distance_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let result_storage_view = result_texture.create_view(&wgpu::TextureViewDescriptor {
+            label: "Raytrace Result Storage Texture".into(),
+            format: Some(RESULT_FORMAT),
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            array_layer_count: None,
+            aspect: wgpu::TextureAspect::All,
+            base_array_layer: 0,
+            base_mip_level: 0,
+            mip_level_count: None,
+            usage: Some(wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING),
+        });
+
+        let result_view = result_texture.create_view(&wgpu::TextureViewDescriptor {
+            label: "Raytrace Result Storage Render Texture".into(),
+            format: Some(RESULT_FORMAT),
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            array_layer_count: None,
+            aspect: wgpu::TextureAspect::All,
+            base_array_layer: 0,
+            base_mip_level: 0,
+            mip_level_count: None,
+            usage: Some(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING),
+        });
+
+        let read_bind_group = Bindings::new()
+            .texture_view(0, &result_storage_view)
+            .build(device, Some("Raytrace Result Read Group"), read_bind_group_layout);
+        let write_bind_group = Bindings::new()
+            .texture_view(0, &result_storage_view)
+            .texture_view(1, &accumulation_view)
+            .texture_view(2, &hit_id_view)
+            .texture_view(3, &hit_normal_distance_view)
+            .build(device, Some("Raytrace Result Write Group"), write_bind_group_layout);
+        let hit_bind_group = Bindings::new()
+            .texture_view(0, &hit_id_view)
+            .texture_view(1, &hit_normal_distance_view)
+            .build(device, Some("Raytrace G-Buffer Bind Group"), hit_bind_group_layout);
+        let render_bind_group = Bindings::new()
+            .texture_view(0, &result_view)
+            .sampler(1, result_sampler)
+            .build(device, Some("Raytrace Result Render Bind Group"), render_bind_group_layout);
+
+        ResultTargets {
             result_texture,
-            result_sampler,
-            read_bind_group_layout,
+            result_view,
+            accumulation_texture,
+            hit_id_texture,
+            hit_normal_distance_texture,
             read_bind_group,
-            write_bind_group_layout,
             write_bind_group,
-            render_bind_group_layout,
             render_bind_group,
-            render_pipeline,
+            hit_bind_group,
         }
     }
 
+    /// Recreates the result, accumulation and G-buffer textures. The accumulation needs to be reset afterwards.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        let targets = Self::create_targets(
+            device,
+            width,
+            height,
+            &self.result_sampler,
+            &self.read_bind_group_layout,
+            &self.write_bind_group_layout,
+            &self.render_bind_group_layout,
+            &self.hit_bind_group_layout,
+        );
+        self.result_texture = targets.result_texture;
+        self.result_view = targets.result_view;
+        self.accumulation_texture = targets.accumulation_texture;
+        self.hit_id_texture = targets.hit_id_texture;
+        self.hit_normal_distance_texture = targets.hit_normal_distance_texture;
+        self.read_bind_group = targets.read_bind_group;
+        self.write_bind_group = targets.write_bind_group;
+        self.render_bind_group = targets.render_bind_group;
+        self.hit_bind_group = targets.hit_bind_group;
+    }
+
+    /// A view of the G-buffer's normals and distances for sampling, such as for [Taa].
+    pub fn hit_normal_distance_view(&self) -> wgpu::TextureView {
+        self.hit_normal_distance_texture.create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    /// Creates bind groups for [GpuRaytraceResult::render_with] that draw the outputs of `taa`, in the order
+    /// of [Taa::output_views].
+    pub fn create_taa_render_bind_groups(&self, device: &wgpu::Device, taa: &Taa) -> [wgpu::BindGroup; 2] {
+        taa.output_views().map(|view| self.create_render_bind_group(device, view))
+    }
+
+    /// Creates a bind group for [GpuRaytraceResult::render_with] that draws `view` instead of the result.
+    pub fn create_render_bind_group(&self, device: &wgpu::Device, view: &wgpu::TextureView) -> wgpu::BindGroup {
+        Bindings::new()
+            .texture_view(0, view)
+            .sampler(1, &self.result_sampler)
+            .build(device, Some("Raytrace Result Render Bind Group"), &self.render_bind_group_layout)
+    }
+
     #[inline]
     pub fn bind_read(&self, index: u32, compute_pass: &mut wgpu::ComputePass) {
         compute_pass.set_bind_group(index, &self.read_bind_group, &[]);
@@ -800,59 +980,268 @@ impl GpuRaytraceResult {
     }
 
     pub fn render(&self, render_pass: &mut wgpu::RenderPass) {
-        render_pass.set_pipeline(&self.render_pipeline);
-        self.bind_render(0, render_pass);
+        self.render_with(render_pass, &self.render_bind_group);
+    }
+
+    /// Draws the texture of `bind_group` (see [GpuRaytraceResult::create_render_bind_group]) to the screen.
+    pub fn render_with(&self, render_pass: &mut wgpu::RenderPass, bind_group: &wgpu::BindGroup) {
+        let supersampled = self.result_texture.width() > BASE_RESOLUTION.0;
+        render_pass.set_pipeline(if supersampled { &self.downsample_pipeline } else { &self.render_pipeline });
+        render_pass.set_bind_group(0, bind_group, &[]);
         render_pass.draw(0..6, 0..1);
     }
 }
 
-pub struct RaytraceChunk {
-    blocks: Box<[u32]>,
-    needs_write: bool,
+/// The number of downsampled levels stored after the full resolution blocks. Level `n` has `64 >> n` cells per axis.
+pub const CHUNK_LOD_LEVELS: u32 = 3;
+
+/// The number of cells per axis of the LOD level.
+pub const fn lod_size(level: u32) -> i32 {
+    64 >> level
+}
+
+/// The index of the first cell of the LOD level in the combined block data (see [RaytraceChunk::lod_blocks]).
+pub const fn lod_offset(level: u32) -> usize {
+    let mut offset = 0;
+    let mut current = 0;
+    while current < level {
+        let size = lod_size(current) as usize;
+        offset += size * size * size;
+        current += 1;
+    }
+    offset
+}
+
+/// The block that represents a 2x2x2 group of blocks in the next LOD level: the most common solid block
+/// (the lowest ID on a tie), or air if all of them are air. Any solid block keeps the group solid so that
+/// thin features don't disappear in the distance.
+fn reduce_blocks(blocks: [u32; 8]) -> u32 {
+    let mut best = (0u32, 0usize);
+    for &id in blocks.iter().filter(|&&id| id != 0) {
+        let count = blocks.iter().filter(|&&other| other == id).count();
+        if count > best.1 || (count == best.1 && id < best.0) {
+            best = (id, count);
+        }
+    }
+    best.0
+}
+
+pub struct RaytraceChunk {
+    blocks: Box<[u32]>,
+    /// Every LOD level after the first (full resolution) one, one after the other.
+    lods: Box<[u32]>,
+    /// A copy of the blocks in Morton order for the CPU raycast, see [RaytraceChunk::set_morton_order].
+    morton: Option<Box<[u32]>>,
+    needs_write: bool,
+    lods_dirty: bool,
 }
 
 impl RaytraceChunk {
+    /// The number of cells per axis of the chunk.
+    pub const SIZE: i32 = 64;
+
+    /// The index of the cell at `x`, `y`, `z` in the blocks (`(y << 12) | (z << 6) | x`, like the shader),
+    /// or `None` outside of the chunk.
+    pub const fn index(x: i32, y: i32, z: i32) -> Option<usize> {
+        if ((x | y | z) as u32) >= Self::SIZE as u32 {
+            return None;
+        }
+        Some(((y << 12) | (z << 6) | x) as usize)
+    }
+
     pub fn new() -> Self {
         Self {
-            blocks: (0..64*64*64).map(|_| 0u32).collect(),
+            blocks: (0..Self::SIZE * Self::SIZE * Self::SIZE).map(|_| 0u32).collect(),
+            lods: vec![0u32; lod_offset(CHUNK_LOD_LEVELS + 1) - lod_offset(1)].into_boxed_slice(),
+            morton: None,
             needs_write: true,
+            lods_dirty: false,
         }
     }
 
     pub fn get(&self, x: i32, y: i32, z: i32) -> u32 {
-        let xyz = x | y | z;
-        if (xyz as u32) >= 64 {
-            return 0;
-        }
-
-        let index = ((y << 12) | (z << 6) | x) as usize;
-        self.blocks[index]
+        Self::index(x, y, z).map_or(0, |index| self.blocks[index])
     }
 
     pub fn set(&mut self, x: i32, y: i32, z: i32, id: u32) {
-        let xyz = x | y | z;
-        if (xyz as u32) >= 64 {
+        let Some(index) = Self::index(x, y, z) else {
             return;
+        };
+        self.blocks[index] = id;
+        if let Some(morton) = &mut self.morton {
+            morton[morton3_encode(x as u32, y as u32, z as u32) as usize] = id;
         }
+        self.needs_write = true;
+        self.lods_dirty = true;
+    }
 
-        let index = ((y << 12) | (z << 6) | x) as usize;
-        self.blocks[index] = id;
+    /// Keeps a second copy of the blocks in Morton order, which the CPU raycasts read from instead.
+    /// Rays touch nearby cells on every axis, so this keeps more of them in the same cache lines.
+    /// The GPU always uses the linear order.
+    pub fn set_morton_order(&mut self, enabled: bool) {
+        self.morton = enabled.then(|| {
+            let mut morton = vec![0u32; self.blocks.len()].into_boxed_slice();
+            for (index, &id) in self.blocks.iter().enumerate() {
+                let (x, y, z) = (index & 63, index >> 12, (index >> 6) & 63);
+                morton[morton3_encode(x as u32, y as u32, z as u32) as usize] = id;
+            }
+            morton
+        });
+    }
+
+    pub fn morton_order(&self) -> bool {
+        self.morton.is_some()
+    }
+
+    /// Moves every block to a new index, where the block at index `i` comes from `source(i)`. The transforms
+    /// work on the indices directly (`(y << 12) | (z << 6) | x`), so each block is moved with a few bit
+    /// operations instead of going through [RaytraceChunk::get] and [RaytraceChunk::set].
+    fn remap(&mut self, source: impl Fn(usize) -> usize) {
+        self.blocks = (0..self.blocks.len()).map(|index| self.blocks[source(index)]).collect();
+        if self.morton.is_some() {
+            self.set_morton_order(true);
+        }
         self.needs_write = true;
+        self.lods_dirty = true;
+    }
+
+    /// Rotates the chunk by 90 degrees clockwise around the Y axis (when viewed from above), like
+    /// [crate::voxel::edit::ClipboardVolume::rotated_y].
+    pub fn rotate_y_90(&mut self) {
+        // The block that ends up at (x, z) was at (z, 63 - x).
+        self.remap(|index| (index & !0xFFF) | ((63 - (index & 63)) << 6) | ((index >> 6) & 63));
+    }
+
+    /// Mirrors the chunk along the X axis, so that `x` becomes `63 - x`.
+    pub fn mirror_x(&mut self) {
+        self.remap(|index| index ^ 63);
+    }
+
+    /// Mirrors the chunk along the Z axis, so that `z` becomes `63 - z`.
+    pub fn mirror_z(&mut self) {
+        self.remap(|index| index ^ (63 << 6));
+    }
+
+    /// Moves every block by `offset`. Blocks that leave one side of the chunk come back in on the other.
+    pub fn translate_wrapping(&mut self, offset: IVec3) {
+        let offset = offset.rem_euclid(IVec3::splat(64)).as_uvec3();
+        let (dx, dy, dz) = (64 - offset.x as usize, 64 - offset.y as usize, 64 - offset.z as usize);
+        self.remap(|index| {
+            let (x, y, z) = (index & 63, index >> 12, (index >> 6) & 63);
+            (((y + dy) & 63) << 12) | (((z + dz) & 63) << 6) | ((x + dx) & 63)
+        });
+    }
+
+    /// The block at `x`, `y`, `z` in the cells of the LOD level. Level `0` is the full resolution chunk.
+    /// The LODs may be out of date after the chunk changes, see [RaytraceChunk::rebuild_lods].
+    pub fn get_lod(&self, level: u32, x: i32, y: i32, z: i32) -> u32 {
+        if level == 0 {
+            return self.get(x, y, z);
+        }
+        let size = lod_size(level);
+        if level > CHUNK_LOD_LEVELS || (x | y | z) as u32 >= size as u32 {
+            return 0;
+        }
+        let index = lod_offset(level) - lod_offset(1) + ((y * size + z) * size + x) as usize;
+        self.lods[index]
+    }
+
+    /// `true` if the chunk has changed since the LODs were last built.
+    pub fn lods_dirty(&self) -> bool {
+        self.lods_dirty
+    }
+
+    /// Rebuilds every LOD level from the full resolution blocks. This happens automatically before the chunk
+    /// is written to the GPU.
+    pub fn rebuild_lods(&mut self) {
+        for level in 1..=CHUNK_LOD_LEVELS {
+            let size = lod_size(level);
+            let start = lod_offset(level) - lod_offset(1);
+            for y in 0..size {
+                for z in 0..size {
+                    for x in 0..size {
+                        let mut group = [0u32; 8];
+                        for (i, id) in group.iter_mut().enumerate() {
+                            let i = i as i32;
+                            *id = self.get_lod(level - 1, x * 2 + (i & 1), y * 2 + ((i >> 1) & 1), z * 2 + (i >> 2));
+                        }
+                        self.lods[start + ((y * size + z) * size + x) as usize] = reduce_blocks(group);
+                    }
+                }
+            }
+        }
+        self.lods_dirty = false;
+    }
+
+    /// The LOD levels after the first, in the layout that they are stored in on the GPU (after the full resolution blocks).
+    pub fn lod_blocks(&self) -> &[u32] {
+        &self.lods
     }
 
     fn as_bytes(&self) -> &[u8] {
         bytemuck::cast_slice(self.blocks.as_ref())
     }
 
+    /// The blocks of the column at `x`, `z` from the bottom (`y = 0`) to the top, as `(y, id)`.
+    pub fn column(&self, x: i32, z: i32) -> impl DoubleEndedIterator<Item = (i32, u32)> + ExactSizeIterator + '_ {
+        (0..64).map(move |y| (y, self.get(x, y, z)))
+    }
+
+    /// The Y of the highest solid block in the column at `x`, `z`.
+    pub fn highest_solid_at(&self, x: i32, z: i32) -> Option<i32> {
+        self.column(x, z).rev().find(|&(_, id)| id != 0).map(|(y, _)| y)
+    }
+
+    /// The Y of the first solid block at or below `y` in the column at `x`, `z`.
+    pub fn ground_below(&self, x: i32, y: i32, z: i32) -> Option<i32> {
+        self.column(x, z).take((y.clamp(-1, 63) + 1) as usize).rev().find(|&(_, id)| id != 0).map(|(y, _)| y)
+    }
+
+    /// Finds the top of the column closest to `x`, `z` (searching outwards in rings) that has a solid block.
+    /// Returns the center of the top face of that block.
+    pub fn find_spawn_point_near(&self, x: i32, z: i32) -> Option<Vec3> {
+        (0..64i32).find_map(|radius| {
+            (-radius..=radius).find_map(|dz| {
+                (-radius..=radius)
+                    // Only the cells on the edge of the ring.
+                    .filter(|&dx| dx.abs() == radius || dz.abs() == radius)
+                    .map(|dx| (x + dx, z + dz))
+                    .filter(|&(cx, cz)| (0..64).contains(&cx) && (0..64).contains(&cz))
+                    .find_map(|(cx, cz)| {
+                        let top = self.highest_solid_at(cx, cz)?;
+                        Some(vec3(cx as f32 + 0.5, (top + 1) as f32, cz as f32 + 0.5))
+                    })
+            })
+        })
+    }
+
+    /// A point to stand on near the center of the chunk. Everything above the chunk is empty, so there is
+    /// always room above the returned point.
+    pub fn find_spawn_point(&self) -> Option<Vec3> {
+        self.find_spawn_point_near(32, 32)
+    }
+
     pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), std::io::Error> {
+        Self::save_blocks(&self.blocks, path)
+    }
+
+    /// The blocks in X, Z, Y order, as they are saved.
+    pub fn blocks(&self) -> &[u32] {
+        &self.blocks
+    }
+
+    /// Saves a copy of [RaytraceChunk::blocks] in the same format as [RaytraceChunk::save], so that
+    /// the chunk can be saved from another thread.
+    pub fn save_blocks<P: AsRef<Path>>(blocks: &[u32], path: P) -> Result<(), std::io::Error> {
         use std::{fs::File, io::{ Write, BufWriter }};
         let path = path.as_ref();
         std::fs::create_dir_all(path.parent().unwrap())?;
         let file = File::create(path)?;
         let mut buffer = BufWriter::new(file);
-        for i in 0..self.blocks.len() {
-            buffer.write_all(&self.blocks[i].to_be_bytes())?;
+        for block in blocks.iter() {
+            buffer.write_all(&block.to_be_bytes())?;
         }
+        buffer.flush()?;
         Ok(())
     }
 
@@ -867,296 +1256,59 @@ impl RaytraceChunk {
             self.blocks[i] = u32::from_be_bytes(buf);
         }
         self.needs_write = true;
+        self.lods_dirty = true;
+        if self.morton.is_some() {
+            self.set_morton_order(true);
+        }
         Ok(())
     }
 
-    pub fn raycast(&self, ray: Ray3, max_distance: f32) -> Option<RayHit> {
-        let mut ray = ray;
-        let lt = ray.pos.cmplt(Vec3A::ZERO);
-        const SIXTY_FOUR: Vec3A = Vec3A::splat(64.0);
-        let ge = ray.pos.cmpge(SIXTY_FOUR);
-        let outside = lt | ge;
-        let (step, delta_min, delta_max, delta_add) = if outside.any() {
-            // Calculate entry point (if there is one).
-            // calculate distance to cross each plane
-            let sign = ray.dir.signum();
-            let step = sign.as_ivec3();
-
-            let neg_sign = sign.cmplt(Vec3A::ZERO);
-            let pos_sign = sign.cmpgt(Vec3A::ZERO);
-
-            if ((lt & neg_sign) | (ge & pos_sign)).any() {
-                return None;
-            }
-            // if lt.test(0) && step.x < 0 // 4
-            // || lt.test(1) && step.y < 0 // 5 9
-            // || lt.test(2) && step.z < 0 // 5 14
-            // || ge.test(0) && step.x > 0 // 5 19
-            // || ge.test(1) && step.y > 0 // 5 24
-            // || ge.test(2) && step.z > 0 {// 5 29
-            //     return None;
-            // }
-            let (dx_min, dx_max) = match step.x + 1 {
-                0 => {
-                    (
-                        (ray.pos.x - 64.0) / -ray.dir.x,
-                        ray.pos.x / -ray.dir.x,
-                    )
-                }
-                1 => {
-                    (<f32>::NEG_INFINITY, <f32>::INFINITY)
-                }
-                2 => {
-                    (
-                        -ray.pos.x / ray.dir.x,
-                        (64.0 - ray.pos.x) / ray.dir.x,
-                    )
-                }
-                _ => unreachable!(),
-            };
-            let (dy_min, dy_max) = match step.y + 1 {
-                0 => {
-                    (
-                        (ray.pos.y - 64.0) / -ray.dir.y,
-                        ray.pos.y / -ray.dir.y,
-                    )
-                }
-                1 => {
-                    (<f32>::NEG_INFINITY, <f32>::INFINITY)
-                }
-                2 => {
-                    (
-                        -ray.pos.y / ray.dir.y,
-                        (64.0 - ray.pos.y) / ray.dir.y,
-                    )
-                }
-                _ => unreachable!()
-            };
-            let (dz_min, dz_max) = match step.z + 1 {
-                0 => {
-                    (
-                        (ray.pos.z - 64.0) / -ray.dir.z,
-                        ray.pos.z / -ray.dir.z,
-                    )
-                }
-                1 => {
-                    (<f32>::NEG_INFINITY, <f32>::INFINITY)
-                }
-                2 => {
-                    (
-                        -ray.pos.z / ray.dir.z,
-                        (64.0 - ray.pos.z) / ray.dir.z,
-                    )
-                }
-                _ => unreachable!()
-            };
-            let max_min = dx_min.max(dy_min.max(dz_min));
-            let min_max = dx_max.min(dy_max.min(dz_max));
-            // Early return, the ray does not hit the volume.
-            if max_min >= min_max {
-                return None;
-            }
-            // This is needed to penetrate the ray into the bounding box.
-            // Otherwise you'll get weird circles from the rays popping
-            // in and out of the next cell. This ensures that the ray
-            // will be inside the bounding box.
-            const RAY_PENETRATION: f32 = 1e-5;
-            let delta_add = max_min + RAY_PENETRATION;
-            if delta_add >= max_distance {
-                return None;
-            }
-            ray.pos = ray.pos + ray.dir * delta_add;
-            (
-                step,
-                Some(vec3(dx_min, dy_min, dz_min)),
-                vec3(dx_max, dy_max, dz_max),
-                delta_add,
-            )
-        } else {
-            let sign = ray.dir.signum();
-            let step = sign.as_ivec3();
-            let dx_max = match step.x + 1 {
-                0 => {
-                    ray.pos.x / -ray.dir.x
-                }
-                1 => {
-                    <f32>::INFINITY
-                }
-                2 => {
-                    (64.0 - ray.pos.x) / ray.dir.x
-                }
-                _ => unreachable!()
-            };
-            let dy_max = match step.y + 1 {
-                0 => {
-                    ray.pos.y / -ray.dir.y
-                }
-                1 => {
-                    <f32>::INFINITY
-                }
-                2 => {
-                    (64.0 - ray.pos.y) / ray.dir.y
-                }
-                _ => unreachable!()
-            };
-            let dz_max = match step.z + 1{
-                0 => {
-                    ray.pos.z / -ray.dir.z
-                }
-                1 => {
-                    <f32>::INFINITY
-                }
-                2 => {
-                    (64.0 - ray.pos.z) / ray.dir.z
-                }
-                _ => unreachable!()
-            };
-            (
-                step,
-                None,
-                vec3(dx_max, dy_max, dz_max),
-                0.0,
-            )
-        };
-        #[inline(always)]
-        fn calc_delta(mag: f32) -> f32 {
-            1.0 / mag.abs().max(<f32>::MIN_POSITIVE)
-        }
-        let delta = vec3(
-            calc_delta(ray.dir.x),
-            calc_delta(ray.dir.y),
-            calc_delta(ray.dir.z),
-        );
-
-        let face = (
-            if step.x >= 0 {
-                Face::NegX
-            } else {
-                Face::PosX
-            },
-            if step.y >= 0 {
-                Face::NegY
-            } else {
-                Face::PosY
-            },
-            if step.z >= 0 {
-                Face::NegZ
-            } else {
-                Face::PosZ
-            },
-        );
-
-        let fract = ray.pos.fract();
-
-        #[inline(always)]
-        fn calc_t_max(step: i32, fract: f32, mag: f32) -> f32 {
-            if step > 0 {
-                (1.0 - fract) / mag.abs().max(<f32>::MIN_POSITIVE)
-            } else if step < 0 {
-                fract / mag.abs().max(<f32>::MIN_POSITIVE)
-            } else {
-                <f32>::INFINITY
+    /// Like [RaytraceChunk::get], but reads the Morton ordered copy when there is one.
+    #[inline]
+    fn get_cell(&self, cell: IVec3) -> u32 {
+        match &self.morton {
+            Some(morton) if ((cell.x | cell.y | cell.z) as u32) < 64 => {
+                morton[morton3_encode(cell.x as u32, cell.y as u32, cell.z as u32) as usize]
             }
+            Some(_) => 0,
+            None => self.get(cell.x, cell.y, cell.z),
         }
-        let mut t_max = vec3(
-            calc_t_max(step.x, fract.x, ray.dir.x) + delta_add,
-            calc_t_max(step.y, fract.y, ray.dir.y) + delta_add,
-            calc_t_max(step.z, fract.z, ray.dir.z) + delta_add,
-        );
+    }
 
-        let mut cell = ray.pos.floor().as_ivec3();
-        let id = self.get(cell.x, cell.y, cell.z);
-        if id != 0 {
-            return Some(RayHit {
-                face: delta_min.map(|min| {
-                    if min.x >= min.y {
-                        if min.x >= min.z {
-                            face.0
-                        } else {
-                            face.2
-                        }
-                    } else {
-                        if min.y >= min.z {
-                            face.1
-                        } else {
-                            face.2
-                        }
-                    }
-                }),
-                id,
-                coord: cell,
-                distance: delta_add,
-            });
-        }
-        let max_d = vec3a(
-            delta_max.x.min(max_distance),
-            delta_max.y.min(max_distance),
-            delta_max.z.min(max_distance),
-        );
-        loop {
+    pub fn raycast(&self, ray: Ray3, max_distance: f32) -> Option<RayHit> {
+        dda::raycast(ray, IVec3::ZERO, IVec3::splat(64), max_distance, |cell| self.get_cell(cell))
+    }
 
-            if t_max.x <= t_max.y {
-                if t_max.x <= t_max.z {
-                    if t_max.x >= max_d.x {
-                        return None;
-                    }
-                    cell.x += step.x;
-                    let id = self.get(cell.x, cell.y, cell.z);
-                    if id != 0 {
-                        return Some(RayHit::hit_face(cell, t_max.x, id, face.0));
-                    }
-                    t_max.x += delta.x;
-                } else {
-                    if t_max.z >= max_d.z {
-                        return None;
-                    }
-                    cell.z += step.z;
-                    let id = self.get(cell.x, cell.y, cell.z);
-                    if id != 0 {
-                        return Some(RayHit::hit_face(cell, t_max.z, id, face.2));
-                    }
-                    t_max.z += delta.z;
-                }
-            } else {
-                if t_max.y <= t_max.z {
-                    if t_max.y >= max_d.y {
-                        return None;
-                    }
-                    cell.y += step.y;
-                    let id = self.get(cell.x, cell.y, cell.z);
-                    if id != 0 {
-                        return Some(RayHit::hit_face(cell, t_max.y, id, face.1));
-                    }
-                    t_max.y += delta.y;
-                } else {
-                    if t_max.z >= max_d.z {
-                        return None;
-                    }
-                    cell.z += step.z;
-                    let id = self.get(cell.x, cell.y, cell.z);
-                    if id != 0 {
-                        return Some(RayHit::hit_face(cell, t_max.z, id, face.2));
-                    }
-                    t_max.z += delta.z;
-                }
-            }
-        }
+    /// Like [RaytraceChunk::raycast], but blocks for which `pass_through` returns `true` are treated as air.
+    pub fn raycast_through<F: Fn(u32) -> bool>(&self, ray: Ray3, max_distance: f32, pass_through: F) -> Option<RayHit> {
+        dda::raycast(ray, IVec3::ZERO, IVec3::splat(64), max_distance, |cell| {
+            let id = self.get_cell(cell);
+            if pass_through(id) { 0 } else { id }
+        })
+    }
+
+    /// Returns `true` if there are no solid blocks between `from` and `to`.
+    pub fn line_of_sight(&self, from: Vec3A, to: Vec3A) -> bool {
+        dda::line_of_sight(from, to, IVec3::ZERO, IVec3::splat(64), |cell| self.get_cell(cell))
     }
 }
 
 pub struct GpuRaytraceChunk {
-    pub buffer: wgpu::Buffer,
+    pub buffer: Tracked<wgpu::Buffer>,
     // pub bind_group_layout: wgpu::BindGroupLayout,
     // pub bind_group: wgpu::BindGroup,
 }
 
 impl GpuRaytraceChunk {
     pub fn new(chunk: &mut RaytraceChunk, device: &wgpu::Device) -> Self {
-        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        chunk.rebuild_lods();
+        let mut contents = chunk.as_bytes().to_vec();
+        contents.extend_from_slice(bytemuck::cast_slice(chunk.lod_blocks()));
+        let buffer = memory::track(MemoryCategory::Chunks, device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Raytrace Chunk Buffer"),
             usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-            contents: chunk.as_bytes(),
-        });
+            contents: &contents,
+        }));
         chunk.needs_write = false;
         // let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         //     label: Some("Raytrace Chunk Layout"),
@@ -1190,8 +1342,24 @@ impl GpuRaytraceChunk {
     //     compute_pass.set_bind_group(index, &self.bind_group, &[]);
     // }
 
+    /// Writes the blocks and the LODs. The LODs are written as they are, so rebuild them first if they are dirty.
     pub fn write_chunk(&self, chunk: &RaytraceChunk, queue: &wgpu::Queue) {
         queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(chunk.blocks.as_ref()));
+        let lod_start = (lod_offset(1) * std::mem::size_of::<u32>()) as wgpu::BufferAddress;
+        queue.write_buffer(&self.buffer, lod_start, bytemuck::cast_slice(chunk.lod_blocks()));
+    }
+
+    /// Like [GpuRaytraceChunk::write_chunk], but the copy is recorded into `encoder` through the staging ring.
+    pub fn upload_chunk(
+        &self,
+        chunk: &RaytraceChunk,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        staging: &mut StagingRing,
+    ) {
+        staging.write(device, encoder, &self.buffer, 0, chunk.as_bytes());
+        let lod_start = (lod_offset(1) * std::mem::size_of::<u32>()) as wgpu::BufferAddress;
+        staging.write(device, encoder, &self.buffer, lod_start, bytemuck::cast_slice(chunk.lod_blocks()));
     }
 }
 
@@ -1215,7 +1383,9 @@ pub struct RtAmbientLight {
     _pad0: [u8; 4],
     intensity: f32,
     active: bool,
-    _pad1: [u8; 11],
+    _pad1: [u8; 3],
+    ao_strength: f32,
+    _pad2: [u8; 4],
 }
 
 #[repr(C)]
@@ -1226,8 +1396,7 @@ pub struct RtLighting {
 }
 
 pub struct GpuRtLighting {
-    lighting: RefCell<RtLighting>,
-    buffer: wgpu::Buffer,
+    buffer: UniformBuffer<RtLighting>,
     // bind_group_layout: wgpu::BindGroupLayout,
     // bind_group: wgpu::BindGroup,
 }
@@ -1245,6 +1414,8 @@ pub struct AmbientLight {
     pub color: Vec3,
     pub intensity: f32,
     pub active: bool,
+    /// How strongly corners and crevices are darkened. `0.0` disables ambient occlusion.
+    pub ao_strength: f32,
 }
 
 pub struct Lighting {
@@ -1269,15 +1440,13 @@ impl GpuRtLighting {
                 color: lighting.ambient.color,
                 intensity: lighting.ambient.intensity,
                 active: lighting.ambient.active,
+                ao_strength: lighting.ambient.ao_strength,
                 _pad0: padding(),
                 _pad1: padding(),
+                _pad2: padding(),
             },
         };
-        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("GPU Lighting Buffer"),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            contents: bytemuck::bytes_of(&rt_light),
-        });
+        let buffer = UniformBuffer::new(device, Some("GPU Lighting Buffer"), rt_light);
 
         // let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         //     label: Some("GPU Lighting Bind Group Layout"),
@@ -1307,7 +1476,6 @@ impl GpuRtLighting {
         // });
 
         Self {
-            lighting: RefCell::new(rt_light),
             buffer,
             // bind_group_layout,
             // bind_group,
@@ -1316,83 +1484,89 @@ impl GpuRtLighting {
     }
 
     pub fn set_directional_direction(&self, queue: &wgpu::Queue, direction: Vec3) {
-        let mut lighting = self.lighting.borrow_mut();
-        lighting.directional.direction = direction;
-        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&direction));
+        write_field!(self.buffer, queue, directional.direction = direction);
     }
 
     pub fn get_directional_direction(&self) -> Vec3 {
-        self.lighting.borrow().directional.direction
+        self.buffer.get().directional.direction
     }
 
     pub fn set_directional_color(&self, queue: &wgpu::Queue, color: Vec3) {
-        let mut lighting = self.lighting.borrow_mut();
-        lighting.directional.color = color;
-        queue.write_buffer(&self.buffer, 16, bytemuck::bytes_of(&color));
+        write_field!(self.buffer, queue, directional.color = color);
     }
 
     pub fn get_directional_color(&self) -> Vec3 {
-        self.lighting.borrow().directional.color
+        self.buffer.get().directional.color
     }
 
     pub fn set_directional_intensity(&self, queue: &wgpu::Queue, intensity: f32) {
-        let mut lighting = self.lighting.borrow_mut();
-        lighting.directional.intensity = intensity;
-        queue.write_buffer(&self.buffer, 32, bytemuck::bytes_of(&intensity));
+        write_field!(self.buffer, queue, directional.intensity = intensity);
     }
 
     pub fn get_directional_intensity(&self) -> f32 {
-        self.lighting.borrow().directional.intensity
+        self.buffer.get().directional.intensity
     }
 
     pub fn set_shadow(&self, queue: &wgpu::Queue, shadow: f32) {
-        let mut lighting = self.lighting.borrow_mut();
-        lighting.directional.shadow = shadow;
-        queue.write_buffer(&self.buffer, 36, bytemuck::bytes_of(&shadow));
+        write_field!(self.buffer, queue, directional.shadow = shadow);
     }
 
     pub fn get_shadow(&self) -> f32 {
-        self.lighting.borrow().directional.shadow
+        self.buffer.get().directional.shadow
     }
 
     pub fn set_directional_active(&self, queue: &wgpu::Queue, active: bool) {
-        let mut lighting = self.lighting.borrow_mut();
-        lighting.directional.active = active;
-        queue.write_buffer(&self.buffer, 40, bytemuck::bytes_of(&active));
+        write_field!(self.buffer, queue, directional.active = active);
     }
 
     pub fn get_directional_active(&self) -> bool {
-        self.lighting.borrow().directional.active
+        self.buffer.get().directional.active
     }
 
     pub fn set_ambient_color(&self, queue: &wgpu::Queue, color: Vec3) {
-        let mut lighting = self.lighting.borrow_mut();
-        lighting.ambient.color = color;
-        queue.write_buffer(&self.buffer, 48, bytemuck::bytes_of(&color));
+        write_field!(self.buffer, queue, ambient.color = color);
     }
 
     pub fn get_ambient_color(&self) -> Vec3 {
-        self.lighting.borrow().ambient.color
+        self.buffer.get().ambient.color
     }
 
     pub fn set_ambient_intensity(&self, queue: &wgpu::Queue, intensity: f32) {
-        let mut lighting = self.lighting.borrow_mut();
-        lighting.ambient.intensity = intensity;
-        queue.write_buffer(&self.buffer, 64, bytemuck::bytes_of(&intensity));
+        write_field!(self.buffer, queue, ambient.intensity = intensity);
     }
 
     pub fn get_ambient_intensity(&self) -> f32 {
-        self.lighting.borrow().ambient.intensity
+        self.buffer.get().ambient.intensity
     }
 
     pub fn set_ambient_active(&self, queue: &wgpu::Queue, active: bool) {
-        let mut lighting = self.lighting.borrow_mut();
-        lighting.ambient.active = active;
-        queue.write_buffer(&self.buffer, 68, bytemuck::bytes_of(&active));
+        write_field!(self.buffer, queue, ambient.active = active);
     }
 
     pub fn get_abmient_active(&self) -> bool {
-        self.lighting.borrow().ambient.active
+        self.buffer.get().ambient.active
+    }
+
+    pub fn set_ao_strength(&self, queue: &wgpu::Queue, ao_strength: f32) {
+        write_field!(self.buffer, queue, ambient.ao_strength = ao_strength);
+    }
+
+    pub fn get_ao_strength(&self) -> f32 {
+        self.buffer.get().ambient.ao_strength
+    }
+
+    /// The lighting as it's laid out on the GPU, for other pipelines that light the scene the same way.
+    pub fn lighting(&self) -> RtLighting {
+        self.buffer.get()
+    }
+
+    /// Writes the light colors, intensities and shadow of a preset. The direction is left alone.
+    pub fn apply_preset(&self, queue: &wgpu::Queue, preset: &LightingPreset) {
+        self.set_directional_color(queue, preset.sun_color());
+        self.set_directional_intensity(queue, preset.sun_intensity);
+        self.set_shadow(queue, preset.shadow);
+        self.set_ambient_color(queue, preset.ambient_color());
+        self.set_ambient_intensity(queue, preset.ambient_intensity);
     }
 
     // fn bind(&self, index: u32, compute_pass: &mut wgpu::ComputePass) {
@@ -1400,169 +1574,1704 @@ impl GpuRtLighting {
     // }
 }
 
-pub struct Raytracer {
-    // Result
-    result: GpuRaytraceResult,
-    // Chunk
-    pub chunk: RaytraceChunk,
-    gpu_chunk: GpuRaytraceChunk,
-    // Camera
-    gpu_camera: RaytraceCamera,
-    // Directions
-    gpu_precompute: PrecomputedDirections,
-    // Lighting
-    pub gpu_lighting: GpuRtLighting,
-    data_bind_group_layout: wgpu::BindGroupLayout,
-    data_bind_group: wgpu::BindGroup,
-    // Pipelines
-    raytrace_pipeline: wgpu::ComputePipeline,
+#[repr(C)]
+#[derive(Debug, Clone, Copy, NoUninit)]
+pub struct RaytraceConfig {
+    shadow_samples: u32,
+    light_angular_radius: f32,
+    max_bounces: u32,
+    accumulated_frames: u32,
+    lod_distance: f32,
+    sky_mode: u32,
+    entity_count: u32,
+    point_light_count: u32,
+    debug_view: u32,
+    heatmap_max_steps: u32,
+    volumetric_steps: u32,
+    volumetric_density: f32,
 }
 
-impl Raytracer {
-    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, camera: &Camera, chunk: Option<RaytraceChunk>, lighting: &Lighting) -> Self {
-        let result = GpuRaytraceResult::new(device);
-        let mut chunk = chunk.unwrap_or_else(|| RaytraceChunk::new());
-        let gpu_chunk = GpuRaytraceChunk::new(&mut chunk, device);
-        gpu_chunk.write_chunk(&chunk, queue);
-        let mut gpu_camera = RaytraceCamera::new(camera, device);
-        gpu_camera.write_dimensions(1920, 1080, queue);
-        let gpu_precompute = PrecomputedDirections::new(device, camera.fov);
-        let gpu_lighting = GpuRtLighting::new(device, lighting);
-
-        let data_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Raytracer Data Bind Group Layout"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Storage { read_only: true },
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 2,
-                    count: None,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Buffer {
-                        min_binding_size: None,
-                        has_dynamic_offset: false,
-                        ty: wgpu::BufferBindingType::Uniform,
-                    }
-                },
-            ]
-        });
+/// What the raytracer writes to its result instead of the shaded scene, for diagnosing it.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RaytraceDebugView {
+    #[default]
+    Off = 0,
+    /// The number of DDA steps of every ray of the pixel (primary, shadow, reflection and refraction rays),
+    /// from blue (none) through green and yellow to red at [Raytracer::heatmap_max_steps], and magenta beyond
+    /// that. The post chain still applies, so exposure and tonemapping shift the colors.
+    StepHeatmap = 1,
+}
 
-        let data_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Raytracer Data Bind Group"),
-            layout: &data_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: gpu_camera.buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: gpu_chunk.buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: gpu_lighting.buffer.as_entire_binding(),
-                },
-            ]
-        });
+impl RaytraceDebugView {
+    pub const fn next(self) -> Self {
+        match self {
+            RaytraceDebugView::Off => RaytraceDebugView::StepHeatmap,
+            RaytraceDebugView::StepHeatmap => RaytraceDebugView::Off,
+        }
+    }
+}
 
-        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+/// What rays that miss the chunk (or leave it after a reflection) see.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SkyMode {
+    /// Primary misses are left transparent (apart from the clouds) so that the raster skybox shows through.
+    /// Reflections still sample the skybox cubemap.
+    Transparent = 0,
+    /// The skybox cubemap, tinted the same way as the raster skybox.
+    Cubemap = 1,
+    /// A gradient that follows the directional light, with a disc where the light is.
+    Procedural = 2,
+}
 
-        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-            label: Some("Precompute Compute Pass"),
-            timestamp_writes: None,
-        });
+impl SkyMode {
+    pub const fn next(self) -> Self {
+        match self {
+            SkyMode::Transparent => SkyMode::Cubemap,
+            SkyMode::Cubemap => SkyMode::Procedural,
+            SkyMode::Procedural => SkyMode::Transparent,
+        }
+    }
+}
+
+/// Whether CPU raycasts (such as for editing) stop at translucent blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TranslucentHits {
+    Stop,
+    PassThrough,
+}
 
-        gpu_precompute.compute(&mut compute_pass);
-        drop(compute_pass);
-        let command_buffer = encoder.finish();
-        queue.submit(Some(command_buffer));
+/// Preset shadow settings that can be switched between at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShadowQuality {
+    /// A single shadow ray with no penumbra.
+    Hard,
+    Low,
+    Medium,
+    High,
+}
 
-        let raytrace_shader = device.create_shader_module(wgpu::include_wgsl!("../shaders/raytrace.wgsl"));
+impl ShadowQuality {
+    pub const fn samples(self) -> u32 {
+        match self {
+            ShadowQuality::Hard => 1,
+            ShadowQuality::Low => 4,
+            ShadowQuality::Medium => 8,
+            ShadowQuality::High => 16,
+        }
+    }
 
-        let raytrace_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Raytracer Compute Pipeline Layout"),
-            bind_group_layouts: &[
-                &result.write_bind_group_layout,
-                &gpu_precompute.read_bind_group_layout,
-                &data_bind_group_layout,
-            ],
-            push_constant_ranges: &[],
-        });
-        let raytrace_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("Raytracer Compute Pipeline"),
-            module: &raytrace_shader,
-            cache: None,
-            compilation_options: wgpu::PipelineCompilationOptions::default(),
-            entry_point: Some("main"),
-            layout: Some(&raytrace_pipeline_layout),
-        });
-        Self {
-            result,
-            chunk,
-            gpu_chunk,
-            gpu_camera,
-            gpu_precompute,
-            gpu_lighting,
-            data_bind_group_layout,
-            data_bind_group,
-            raytrace_pipeline,
+    pub const fn next(self) -> Self {
+        match self {
+            ShadowQuality::Hard => ShadowQuality::Low,
+            ShadowQuality::Low => ShadowQuality::Medium,
+            ShadowQuality::Medium => ShadowQuality::High,
+            ShadowQuality::High => ShadowQuality::Hard,
         }
     }
+}
 
-    pub fn write_chunk(&mut self, queue: &wgpu::Queue) {
-        if !self.chunk.needs_write {
-            return;
+/// Preset step counts of the volumetric light (light shafts through the fog) that can be switched between at
+/// runtime. Every step casts a shadow ray towards the directional light, so this is expensive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum VolumetricQuality {
+    #[default]
+    Off,
+    Low,
+    Medium,
+    High,
+}
+
+impl VolumetricQuality {
+    pub const fn steps(self) -> u32 {
+        match self {
+            VolumetricQuality::Off => 0,
+            VolumetricQuality::Low => 8,
+            VolumetricQuality::Medium => 16,
+            VolumetricQuality::High => 32,
         }
-        self.gpu_chunk.write_chunk(&self.chunk, queue);
-        self.chunk.needs_write = false;
     }
 
-    pub fn write_camera_transform(&mut self, transform: GpuTransform, queue: &wgpu::Queue) {
-        self.gpu_camera.write_transform(transform, queue);
+    pub const fn next(self) -> Self {
+        match self {
+            VolumetricQuality::Off => VolumetricQuality::Low,
+            VolumetricQuality::Low => VolumetricQuality::Medium,
+            VolumetricQuality::Medium => VolumetricQuality::High,
+            VolumetricQuality::High => VolumetricQuality::Off,
+        }
     }
+}
 
-    pub fn compute(&self, compute_pass: &mut wgpu::ComputePass, query_set: Option<&wgpu::QuerySet>) {
-        compute_pass.set_pipeline(&self.raytrace_pipeline);
-        self.result.bind_write(0, compute_pass);
-        self.gpu_precompute.bind_read(1, compute_pass);
-        compute_pass.set_bind_group(2, &self.data_bind_group, &[]);
-        // self.gpu_chunk.bind(2, compute_pass);
-        // self.gpu_camera.bind(3, compute_pass);
-        // self.gpu_lighting.bind(4, compute_pass);
-        match query_set {
-            Some(query_set) => {
-                compute_pass.write_timestamp(query_set, 0);
-                compute_pass.dispatch_workgroups(240, 135, 1);
-                compute_pass.write_timestamp(query_set, 1);
-            },
-            None => {
-                compute_pass.dispatch_workgroups(240, 135, 1);
-            },
+pub struct GpuRaytraceConfig {
+    buffer: UniformBuffer<RaytraceConfig>,
+}
+
+impl GpuRaytraceConfig {
+    pub fn new(device: &wgpu::Device, shadow_samples: u32, light_angular_radius: f32, max_bounces: u32) -> Self {
+        let config = RaytraceConfig {
+            shadow_samples,
+            light_angular_radius,
+            max_bounces,
+            accumulated_frames: 0,
+            lod_distance: DEFAULT_LOD_DISTANCE,
+            sky_mode: SkyMode::Cubemap as u32,
+            entity_count: 0,
+            point_light_count: 0,
+            debug_view: RaytraceDebugView::Off as u32,
+            heatmap_max_steps: DEFAULT_HEATMAP_MAX_STEPS,
+            volumetric_steps: VolumetricQuality::Off.steps(),
+            volumetric_density: DEFAULT_VOLUMETRIC_DENSITY,
+        };
+        Self {
+            buffer: UniformBuffer::new(device, Some("Raytrace Config Buffer"), config),
         }
-        
     }
 
-    pub fn render(&self, render_pass: &mut wgpu::RenderPass) {
-        self.result.render(render_pass);
+    pub fn set_shadow_samples(&self, queue: &wgpu::Queue, shadow_samples: u32) {
+        write_field!(self.buffer, queue, shadow_samples = shadow_samples);
+    }
+
+    pub fn get_shadow_samples(&self) -> u32 {
+        self.buffer.get().shadow_samples
+    }
+
+    /// `light_angular_radius` is in radians.
+    pub fn set_light_angular_radius(&self, queue: &wgpu::Queue, light_angular_radius: f32) {
+        write_field!(self.buffer, queue, light_angular_radius = light_angular_radius);
     }
 
-}
\ No newline at end of file
+    pub fn get_light_angular_radius(&self) -> f32 {
+        self.buffer.get().light_angular_radius
+    }
+
+    pub fn set_max_bounces(&self, queue: &wgpu::Queue, max_bounces: u32) {
+        write_field!(self.buffer, queue, max_bounces = max_bounces);
+    }
+
+    pub fn get_max_bounces(&self) -> u32 {
+        self.buffer.get().max_bounces
+    }
+
+    pub fn set_accumulated_frames(&self, queue: &wgpu::Queue, accumulated_frames: u32) {
+        write_field!(self.buffer, queue, accumulated_frames = accumulated_frames);
+    }
+
+    pub fn get_accumulated_frames(&self) -> u32 {
+        self.buffer.get().accumulated_frames
+    }
+
+    /// The distance at which primary rays switch to the first LOD level (each following level starts at
+    /// twice the distance of the previous one). `0.0` disables LODs.
+    pub fn set_lod_distance(&self, queue: &wgpu::Queue, lod_distance: f32) {
+        write_field!(self.buffer, queue, lod_distance = lod_distance);
+    }
+
+    pub fn get_lod_distance(&self) -> f32 {
+        self.buffer.get().lod_distance
+    }
+
+    pub fn set_sky_mode(&self, queue: &wgpu::Queue, sky_mode: SkyMode) {
+        write_field!(self.buffer, queue, sky_mode = sky_mode as u32);
+    }
+
+    /// The number of entity slots that the shader tests, see [Raytracer::write_entities].
+    pub fn set_entity_count(&self, queue: &wgpu::Queue, entity_count: u32) {
+        write_field!(self.buffer, queue, entity_count = entity_count);
+    }
+
+    pub fn get_entity_count(&self) -> u32 {
+        self.buffer.get().entity_count
+    }
+
+    /// The number of point light slots that the shader tests, see [Raytracer::write_lights].
+    pub fn set_point_light_count(&self, queue: &wgpu::Queue, point_light_count: u32) {
+        write_field!(self.buffer, queue, point_light_count = point_light_count);
+    }
+
+    pub fn get_point_light_count(&self) -> u32 {
+        self.buffer.get().point_light_count
+    }
+
+    pub fn set_debug_view(&self, queue: &wgpu::Queue, debug_view: RaytraceDebugView) {
+        write_field!(self.buffer, queue, debug_view = debug_view as u32);
+    }
+
+    pub fn set_heatmap_max_steps(&self, queue: &wgpu::Queue, heatmap_max_steps: u32) {
+        write_field!(self.buffer, queue, heatmap_max_steps = heatmap_max_steps.max(1));
+    }
+
+    pub fn get_heatmap_max_steps(&self) -> u32 {
+        self.buffer.get().heatmap_max_steps
+    }
+
+    /// The number of steps that the volumetric light marches along each primary ray. `0` disables it.
+    pub fn set_volumetric_steps(&self, queue: &wgpu::Queue, volumetric_steps: u32) {
+        write_field!(self.buffer, queue, volumetric_steps = volumetric_steps);
+    }
+
+    pub fn get_volumetric_steps(&self) -> u32 {
+        self.buffer.get().volumetric_steps
+    }
+
+    pub fn set_volumetric_density(&self, queue: &wgpu::Queue, volumetric_density: f32) {
+        write_field!(self.buffer, queue, volumetric_density = volumetric_density);
+    }
+
+    pub fn get_volumetric_density(&self) -> f32 {
+        self.buffer.get().volumetric_density
+    }
+}
+
+/// Number of entries in the material table. Block IDs beyond this use the last material.
+pub const MATERIAL_COUNT: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Material {
+    /// Multiplied with the surface color.
+    pub color: Vec3,
+    /// `0.0` is fully diffuse, `1.0` is a perfect mirror.
+    pub reflectivity: f32,
+    /// Light emitted by the surface, as a multiple of `color`. Unaffected by lighting and shadows.
+    pub emission: f32,
+    /// How much light passes through the block (like water). `0.0` is opaque. Rays that enter a
+    /// translucent block are bent once by `ior` and tinted by `color` as they travel through it.
+    pub translucency: f32,
+    /// The index of refraction of translucent blocks. Water is about `1.33`.
+    pub ior: f32,
+    /// How quickly light is absorbed inside translucent blocks, per block travelled.
+    pub absorption: f32,
+    /// The top faces of liquid blocks ripple with waves that move over time (see
+    /// [super::frame_uniforms::FrameUniforms::time]), which bends the light that enters them. Only has an
+    /// effect on translucent blocks.
+    pub liquid: bool,
+}
+
+impl Material {
+    pub const DEFAULT: Self = Self {
+        color: Vec3::ONE,
+        reflectivity: 0.0,
+        emission: 0.0,
+        translucency: 0.0,
+        ior: 1.0,
+        absorption: 0.0,
+        liquid: false,
+    };
+
+    pub const WATER: Self = Self {
+        color: vec3(0.35, 0.65, 0.8),
+        reflectivity: 0.0,
+        emission: 0.0,
+        translucency: 0.85,
+        ior: 1.33,
+        absorption: 0.15,
+        liquid: true,
+    };
+
+    pub fn is_reflective(&self) -> bool {
+        self.reflectivity > 0.0
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        self.emission > 0.0
+    }
+
+    pub fn is_translucent(&self) -> bool {
+        self.translucency > 0.0
+    }
+
+    pub fn is_liquid(&self) -> bool {
+        self.liquid && self.is_translucent()
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, NoUninit)]
+pub struct RtMaterial {
+    color: Vec3,
+    reflectivity: f32,
+    emission: f32,
+    translucency: f32,
+    ior: f32,
+    absorption: f32,
+    liquid: u32,
+    _padding: [u32; 3],
+}
+
+impl From<Material> for RtMaterial {
+    fn from(value: Material) -> Self {
+        Self {
+            color: value.color,
+            reflectivity: value.reflectivity,
+            emission: value.emission,
+            translucency: value.translucency,
+            ior: value.ior,
+            absorption: value.absorption,
+            liquid: value.liquid as u32,
+            _padding: [0; 3],
+        }
+    }
+}
+
+impl From<RtMaterial> for Material {
+    fn from(value: RtMaterial) -> Self {
+        Self {
+            color: value.color,
+            reflectivity: value.reflectivity,
+            emission: value.emission,
+            translucency: value.translucency,
+            ior: value.ior,
+            absorption: value.absorption,
+            liquid: value.liquid != 0,
+        }
+    }
+}
+
+/// The texture array layers of a block's faces in the rasterized view, so that blocks like grass can have
+/// a different texture on top. The raytracer doesn't sample textures, so these stay on the CPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BlockFaces {
+    /// The layer of the face that points up (+Y).
+    pub top: u32,
+    /// The layer of the face that points down (-Y).
+    pub bottom: u32,
+    /// The layer of the four faces that point sideways.
+    pub side: u32,
+}
+
+impl BlockFaces {
+    /// The same layer on every face.
+    pub const fn uniform(layer: u32) -> Self {
+        Self {
+            top: layer,
+            bottom: layer,
+            side: layer,
+        }
+    }
+
+    /// The layer of the face that points along `normal`.
+    pub fn layer(self, normal: IVec3) -> u32 {
+        match normal.y.signum() {
+            1 => self.top,
+            -1 => self.bottom,
+            _ => self.side,
+        }
+    }
+}
+
+/// Per block ID material properties, indexed by block ID.
+pub struct GpuMaterialTable {
+    buffer: StorageBuffer<RtMaterial>,
+    faces: Vec<BlockFaces>,
+}
+
+impl GpuMaterialTable {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let rt_materials = vec![RtMaterial::from(Material::DEFAULT); MATERIAL_COUNT];
+        Self {
+            buffer: StorageBuffer::new(device, Some("Material Table Buffer"), &rt_materials),
+            faces: vec![BlockFaces::default(); MATERIAL_COUNT],
+        }
+    }
+
+    pub fn set_material(&self, queue: &wgpu::Queue, id: u32, material: Material) {
+        let index = id as usize;
+        assert!(index < MATERIAL_COUNT, "Material ID out of bounds: {id}");
+        self.buffer.set(queue, index, RtMaterial::from(material));
+    }
+
+    pub fn get_material(&self, id: u32) -> Material {
+        Material::from(self.buffer.get((id as usize).min(MATERIAL_COUNT - 1)))
+    }
+
+    pub fn set_faces(&mut self, id: u32, faces: BlockFaces) {
+        let index = id as usize;
+        assert!(index < MATERIAL_COUNT, "Material ID out of bounds: {id}");
+        self.faces[index] = faces;
+    }
+
+    pub fn get_faces(&self, id: u32) -> BlockFaces {
+        self.faces[(id as usize).min(MATERIAL_COUNT - 1)]
+    }
+}
+
+/// Once this many frames have been accumulated, the accumulation becomes a moving average
+/// so that slow lighting changes (such as the day/night cycle) don't smear forever.
+pub const MAX_ACCUMULATED_FRAMES: u32 = 64;
+
+/// Roughly the angular radius of the sun (in radians), slightly exaggerated so that penumbras are visible.
+pub const DEFAULT_LIGHT_ANGULAR_RADIUS: f32 = 0.02;
+
+/// Primary rays that enter the chunk further away than this use the downsampled LODs.
+pub const DEFAULT_LOD_DISTANCE: f32 = 96.0;
+/// The step count at the top of the [RaytraceDebugView::StepHeatmap] ramp. A primary ray through the whole
+/// chunk takes up to 192 steps.
+pub const DEFAULT_HEATMAP_MAX_STEPS: u32 = 256;
+/// The extinction of the medium that scatters the volumetric light, per block. Light that travels 100 blocks
+/// through it keeps about 13% of its intensity.
+pub const DEFAULT_VOLUMETRIC_DENSITY: f32 = 0.02;
+/// The range of [Raytracer::set_volumetric_density].
+pub const MAX_VOLUMETRIC_DENSITY: f32 = 0.2;
+
+/// The resolution that the raytracer renders at without supersampling.
+pub const BASE_RESOLUTION: (u32, u32) = (1920, 1080);
+
+pub const MAX_SUPERSAMPLE_SCALE: u32 = 2;
+
+/// The format that the result is drawn to until [Raytracer::set_render_target] is called.
+pub const DEFAULT_TARGET_FORMAT: wgpu::TextureFormat = HDR_FORMAT;
+
+/// The format of the result. The colors are linear and aren't clamped, so that emissive surfaces and
+/// bright reflections keep their intensity until the exposure and tonemapping are applied.
+pub const RESULT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// The format of the G-buffer's block IDs.
+pub const HIT_ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
+/// The format of the G-buffer's face normals (`xyz`) and hit distances (`w`). Half precision is enough for
+/// outlines and edge detection, but distances lose their fraction beyond about 1000 blocks.
+pub const HIT_NORMAL_DISTANCE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// A texel of the G-buffer: the first surface along the primary ray, including translucent blocks. Misses
+/// have the ID `0`, no normal, and the camera's far distance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GBufferHit {
+    pub id: u32,
+    /// Zero for misses, and for hits from inside a block.
+    pub normal: Vec3,
+    pub distance: f32,
+}
+
+impl GBufferHit {
+    /// Decodes the texels of [HIT_ID_FORMAT] and [HIT_NORMAL_DISTANCE_FORMAT], as read by [offline::read_texture].
+    pub fn from_texels(id: &[u8], normal_distance: &[u8]) -> Self {
+        let [x, y, z, distance] = offline::decode_rgba16f(normal_distance);
+        Self {
+            id: u32::from_le_bytes([id[0], id[1], id[2], id[3]]),
+            normal: vec3(x, y, z),
+            distance,
+        }
+    }
+
+    pub fn is_hit(&self) -> bool {
+        self.id != 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RaytraceQuality {
+    /// The raytracer renders at [BASE_RESOLUTION] multiplied by this, and the result is averaged
+    /// down when it is drawn. `1` disables supersampling.
+    pub supersample_scale: u32,
+    /// Scales the resolution on top of the supersampling, in percent (see [super::render_scale]).
+    pub render_scale: u32,
+    /// The antialiasing pass that runs on the result before it is drawn.
+    pub antialiasing: AntialiasMode,
+}
+
+impl RaytraceQuality {
+    pub const DEFAULT: Self = Self {
+        supersample_scale: 1,
+        render_scale: 100,
+        antialiasing: AntialiasMode::Off,
+    };
+
+    pub const fn resolution(self) -> (u32, u32) {
+        let scale = self.supersample_scale * self.render_scale;
+        (BASE_RESOLUTION.0 * scale / 100, BASE_RESOLUTION.1 * scale / 100)
+    }
+}
+
+impl Default for RaytraceQuality {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// The antialiasing pass of the raytrace result. Supersampling is separate (see
+/// [RaytraceQuality::supersample_scale]) and can be combined with either pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AntialiasMode {
+    #[default]
+    Off,
+    /// See [Fxaa].
+    Fxaa,
+    /// See [Taa]. Jitters the primary rays.
+    Taa,
+}
+
+impl AntialiasMode {
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Fxaa,
+            Self::Fxaa => Self::Taa,
+            Self::Taa => Self::Off,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Fxaa => "FXAA",
+            Self::Taa => "TAA",
+        }
+    }
+}
+
+/// The configuration of the second ("B") side of an A/B comparison, see [Raytracer::set_comparison].
+/// Everything else is shared with the raytracer's own configuration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RaytraceVariant {
+    pub direction_mode: RayDirectionMode,
+    pub workgroup_size: WorkgroupSize,
+    /// Replaces the ambient occlusion strength of the lighting. `0.0` disables ambient occlusion.
+    pub ao_strength: f32,
+}
+
+/// The B side of an A/B comparison. It traces the same chunk, camera and config as the raytracer, but
+/// with its own pipeline, ray directions, lighting uniform and targets, so that both sides can be
+/// dispatched (and timed) one after the other in the same frame.
+struct RaytraceComparison {
+    variant: RaytraceVariant,
+    result: GpuRaytraceResult,
+    fxaa: Fxaa,
+    fxaa_render_bind_group: wgpu::BindGroup,
+    taa: Taa,
+    taa_render_bind_groups: [wgpu::BindGroup; 2],
+    gpu_precompute: PrecomputedDirections,
+    gpu_lighting: UniformBuffer<RtLighting>,
+    data_bind_group: wgpu::BindGroup,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::ComputePipeline,
+}
+
+pub struct Raytracer {
+    // Result
+    result: GpuRaytraceResult,
+    /// The format of the target that the result is rendered to.
+    target_format: wgpu::TextureFormat,
+    /// The sample count of the pass that the result is rendered in.
+    sample_count: u32,
+    quality: RaytraceQuality,
+    fxaa: Fxaa,
+    fxaa_render_bind_group: wgpu::BindGroup,
+    taa: Taa,
+    taa_render_bind_groups: [wgpu::BindGroup; 2],
+    // Chunk
+    pub chunk: RaytraceChunk,
+    gpu_chunk: GpuRaytraceChunk,
+    // Camera
+    gpu_camera: RaytraceCamera,
+    // Directions
+    gpu_precompute: PrecomputedDirections,
+    fov: f32,
+    // Lighting
+    pub gpu_lighting: GpuRtLighting,
+    // Config
+    pub gpu_config: GpuRaytraceConfig,
+    shadow_quality: ShadowQuality,
+    volumetric_quality: VolumetricQuality,
+    sky_mode: SkyMode,
+    debug_view: RaytraceDebugView,
+    // Materials
+    pub materials: GpuMaterialTable,
+    // Fog
+    gpu_fog: UniformBuffer<Fog>,
+    // Sky
+    gpu_sky_tint: UniformBuffer<Vec4>,
+    gpu_clouds: UniformBuffer<GpuClouds>,
+    // Time
+    /// The [super::frame_uniforms::FrameUniforms] buffer, which the owner advances every frame.
+    frame_uniforms: wgpu::Buffer,
+    // Entities
+    gpu_entities: StorageBuffer<GpuEntity>,
+    // Point Lights
+    gpu_point_lights: StorageBuffer<GpuPointLight>,
+    // Block Light
+    block_light: BlockLight,
+    gpu_block_light: Tracked<wgpu::Buffer>,
+    /// The materials changed, so the block light has to be rebuilt even if the chunk didn't change.
+    block_light_dirty: bool,
+    // Accumulation
+    accumulation_enabled: bool,
+    accumulated_frames: u32,
+    last_transform: Option<GpuTransform>,
+    data_bind_group_layout: wgpu::BindGroupLayout,
+    data_bind_group: wgpu::BindGroup,
+    // Noise
+    blue_noise_layout: wgpu::BindGroupLayout,
+    blue_noise: BlueNoise,
+    /// Counts every frame (unlike `accumulated_frames`) so that the noise keeps moving.
+    noise_frame: u32,
+    // Pipelines
+    raytrace_shader: wgpu::ShaderModule,
+    workgroup_size: WorkgroupSize,
+    raytrace_pipeline_layout: wgpu::PipelineLayout,
+    raytrace_pipeline: wgpu::ComputePipeline,
+    /// Dispatches the raytrace pipeline with the workgroup counts written on the GPU, if enabled (see
+    /// [Raytracer::set_indirect_dispatch]).
+    indirect_dispatch: Option<IndirectDispatch>,
+    // A/B Comparison
+    comparison: Option<RaytraceComparison>,
+}
+
+fn create_raytrace_pipeline(
+    device: &wgpu::Device,
+    module: &wgpu::ShaderModule,
+    layout: &wgpu::PipelineLayout,
+    direction_mode: RayDirectionMode,
+    workgroup_size: WorkgroupSize,
+) -> wgpu::ComputePipeline {
+    // The raytrace shader's override constants.
+    let constants = HashMap::from([
+        (String::from("INLINE_DIRECTIONS"), if direction_mode == RayDirectionMode::Inline { 1.0 } else { 0.0 }),
+        (String::from("WORKGROUP_SIZE_X"), workgroup_size.x as f64),
+        (String::from("WORKGROUP_SIZE_Y"), workgroup_size.y as f64),
+    ]);
+    device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Raytracer Compute Pipeline"),
+        module,
+        cache: None,
+        compilation_options: wgpu::PipelineCompilationOptions {
+            constants: &constants,
+            ..Default::default()
+        },
+        entry_point: Some("main"),
+        layout: Some(layout),
+    })
+}
+
+impl Raytracer {
+    /// `sky` is sampled by rays that miss the chunk, see [SkyMode]. `frame_uniforms` is the buffer of a
+    /// [super::frame_uniforms::FrameUniformsBindGroup], which animates liquid surfaces. The workgroup size
+    /// falls back to [WorkgroupSize::DEFAULT] if the device doesn't support it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        camera: &Camera,
+        chunk: Option<RaytraceChunk>,
+        lighting: &Lighting,
+        sky: &SkyboxCubemap,
+        frame_uniforms: &wgpu::Buffer,
+        workgroup_size: WorkgroupSize,
+    ) -> Self {
+        Self::with_gpu_lighting(device, queue, camera, chunk, GpuRtLighting::new(device, lighting), sky, frame_uniforms, workgroup_size)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn with_gpu_lighting(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        camera: &Camera,
+        chunk: Option<RaytraceChunk>,
+        gpu_lighting: GpuRtLighting,
+        sky: &SkyboxCubemap,
+        frame_uniforms: &wgpu::Buffer,
+        workgroup_size: WorkgroupSize,
+    ) -> Self {
+        let workgroup_size = if workgroup_size.is_supported(&device.limits()) {
+            workgroup_size
+        } else {
+            log::warn!("Unsupported raytrace workgroup size {workgroup_size:?}, using the default.");
+            WorkgroupSize::DEFAULT
+        };
+        let quality = RaytraceQuality::DEFAULT;
+        let (width, height) = quality.resolution();
+        let result = GpuRaytraceResult::new(device, width, height);
+        let fxaa = Fxaa::new(device, &result.result_view, width, height);
+        let fxaa_render_bind_group = result.create_render_bind_group(device, fxaa.output_view());
+        let taa = Taa::new(device, &result.result_view, &result.hit_normal_distance_view(), width, height);
+        let taa_render_bind_groups = result.create_taa_render_bind_groups(device, &taa);
+        let mut chunk = chunk.unwrap_or_else(|| RaytraceChunk::new());
+        let gpu_chunk = GpuRaytraceChunk::new(&mut chunk, device);
+        gpu_chunk.write_chunk(&chunk, queue);
+        let mut gpu_camera = RaytraceCamera::new(camera, device);
+        gpu_camera.write_dimensions(width, height, queue);
+        let gpu_precompute = PrecomputedDirections::new(device, camera.fov, width, height, RayDirectionMode::default());
+        let shadow_quality = ShadowQuality::Low;
+        let gpu_config = GpuRaytraceConfig::new(device, shadow_quality.samples(), DEFAULT_LIGHT_ANGULAR_RADIUS, 1);
+        let materials = GpuMaterialTable::new(device);
+        let gpu_fog = UniformBuffer::new(device, Some("Raytracer Fog Buffer"), Fog::new(0.0, 0.0, Vec4::ZERO));
+        let gpu_sky_tint = UniformBuffer::new(device, Some("Raytracer Sky Tint Buffer"), Vec4::ONE);
+        let gpu_clouds = UniformBuffer::new(device, Some("Raytracer Clouds Buffer"), Clouds::DEFAULT.to_gpu());
+        let gpu_entities = StorageBuffer::new(device, Some("Raytracer Entity Buffer"), &[GpuEntity::zeroed(); MAX_ENTITIES]);
+        let gpu_point_lights = StorageBuffer::new(device, Some("Raytracer Point Light Buffer"), &[GpuPointLight::zeroed(); MAX_POINT_LIGHTS]);
+        let block_light = BlockLight::new();
+        let gpu_block_light = memory::track(MemoryCategory::Chunks, device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Raytracer Block Light Buffer"),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            contents: block_light.as_bytes(),
+        }));
+
+        let data_bind_group_layout = BindGroupBuilder::new()
+            .label("Raytracer Data Bind Group Layout")
+            .uniform(0, wgpu::ShaderStages::COMPUTE)
+            .storage(1, wgpu::ShaderStages::COMPUTE, true)
+            .uniform(2, wgpu::ShaderStages::COMPUTE)
+            .uniform(3, wgpu::ShaderStages::COMPUTE)
+            .storage(4, wgpu::ShaderStages::COMPUTE, true)
+            .uniform(5, wgpu::ShaderStages::COMPUTE)
+            .uniform(6, wgpu::ShaderStages::COMPUTE)
+            .texture_cube(7, wgpu::ShaderStages::COMPUTE)
+            .sampler(8, wgpu::ShaderStages::COMPUTE)
+            .storage(9, wgpu::ShaderStages::COMPUTE, true)
+            .storage(10, wgpu::ShaderStages::COMPUTE, true)
+            .storage(11, wgpu::ShaderStages::COMPUTE, true)
+            .uniform(12, wgpu::ShaderStages::COMPUTE)
+            .uniform(13, wgpu::ShaderStages::COMPUTE)
+            .build(device);
+
+        let data_bind_group = Bindings::new()
+            .buffer(0, gpu_camera.buffer.buffer())
+            .buffer(1, &gpu_chunk.buffer)
+            .buffer(2, gpu_lighting.buffer.buffer())
+            .buffer(3, gpu_config.buffer.buffer())
+            .buffer(4, materials.buffer.buffer())
+            .buffer(5, gpu_fog.buffer())
+            .buffer(6, gpu_sky_tint.buffer())
+            .texture_view(7, &sky.view)
+            .sampler(8, &sky.sampler)
+            .buffer(9, gpu_entities.buffer())
+            .buffer(10, gpu_point_lights.buffer())
+            .buffer(11, &gpu_block_light)
+            .buffer(12, gpu_clouds.buffer())
+            .buffer(13, frame_uniforms)
+            .build(device, Some("Raytracer Data Bind Group"), &data_bind_group_layout);
+
+        let blue_noise_layout = BlueNoise::create_layout(device);
+        let blue_noise = BlueNoise::generate(device, queue, &blue_noise_layout, DEFAULT_BLUE_NOISE_SIZE, 0);
+
+        gpu_precompute.submit_compute(device, queue);
+
+        let raytrace_shader = device.create_shader_module(wgpu::include_wgsl!("../shaders/raytrace.wgsl"));
+
+        let raytrace_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Raytracer Compute Pipeline Layout"),
+            bind_group_layouts: &[
+                &result.write_bind_group_layout,
+                &gpu_precompute.read_bind_group_layout,
+                &data_bind_group_layout,
+                &blue_noise_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+        let raytrace_pipeline = create_raytrace_pipeline(device, &raytrace_shader, &raytrace_pipeline_layout, gpu_precompute.mode(), workgroup_size);
+        Self {
+            result,
+            target_format: DEFAULT_TARGET_FORMAT,
+            sample_count: 1,
+            quality,
+            fxaa,
+            fxaa_render_bind_group,
+            taa,
+            taa_render_bind_groups,
+            chunk,
+            gpu_chunk,
+            gpu_camera,
+            gpu_precompute,
+            fov: camera.fov,
+            gpu_lighting,
+            gpu_config,
+            shadow_quality,
+            volumetric_quality: VolumetricQuality::Off,
+            sky_mode: SkyMode::Cubemap,
+            debug_view: RaytraceDebugView::Off,
+            materials,
+            gpu_fog,
+            gpu_sky_tint,
+            gpu_clouds,
+            frame_uniforms: frame_uniforms.clone(),
+            gpu_entities,
+            gpu_point_lights,
+            block_light,
+            gpu_block_light,
+            // The chunk may already have emissive blocks.
+            block_light_dirty: true,
+            accumulation_enabled: true,
+            accumulated_frames: 0,
+            last_transform: None,
+            data_bind_group_layout,
+            data_bind_group,
+            blue_noise_layout,
+            blue_noise,
+            noise_frame: 0,
+            raytrace_shader,
+            workgroup_size,
+            raytrace_pipeline_layout,
+            raytrace_pipeline,
+            indirect_dispatch: None,
+            comparison: None,
+        }
+    }
+
+    /// Rebuilds every GPU resource on `device` (after the previous device was lost). The chunk, lighting,
+    /// materials and settings are carried over from the CPU copies, only the accumulation is lost.
+    /// `frame_uniforms` has to be on the new device too.
+    pub fn recreate(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, camera: &Camera, sky: &SkyboxCubemap, frame_uniforms: &wgpu::Buffer) {
+        let chunk = std::mem::replace(&mut self.chunk, RaytraceChunk::new());
+        let gpu_lighting = GpuRtLighting {
+            buffer: UniformBuffer::new(device, Some("GPU Lighting Buffer"), self.gpu_lighting.buffer.get()),
+        };
+        let mut raytracer = Self::with_gpu_lighting(device, queue, camera, Some(chunk), gpu_lighting, sky, frame_uniforms, self.workgroup_size);
+        raytracer.set_direction_mode(self.direction_mode(), device, queue);
+        raytracer.set_render_target(self.target_format, self.sample_count, device);
+        raytracer.set_quality(self.quality, device, queue);
+        raytracer.set_fov(self.fov, device, queue);
+        raytracer.gpu_config.buffer.write(queue, RaytraceConfig {
+            accumulated_frames: 0,
+            ..self.gpu_config.buffer.get()
+        });
+        raytracer.shadow_quality = self.shadow_quality;
+        raytracer.volumetric_quality = self.volumetric_quality;
+        raytracer.sky_mode = self.sky_mode;
+        raytracer.debug_view = self.debug_view;
+        raytracer.set_indirect_dispatch(self.indirect_dispatch(), device);
+        let materials = (0..MATERIAL_COUNT).map(|index| self.materials.buffer.get(index)).collect::<Vec<_>>();
+        raytracer.materials.buffer.write(queue, 0, &materials);
+        raytracer.materials.faces.clone_from(&self.materials.faces);
+        raytracer.gpu_fog.write(queue, self.gpu_fog.get());
+        raytracer.gpu_sky_tint.write(queue, self.gpu_sky_tint.get());
+        raytracer.gpu_clouds.write(queue, self.gpu_clouds.get());
+        let entities = (0..MAX_ENTITIES).map(|index| self.gpu_entities.get(index)).collect::<Vec<_>>();
+        raytracer.gpu_entities.write(queue, 0, &entities);
+        let point_lights = (0..MAX_POINT_LIGHTS).map(|index| self.gpu_point_lights.get(index)).collect::<Vec<_>>();
+        raytracer.gpu_point_lights.write(queue, 0, &point_lights);
+        raytracer.block_light_dirty = true;
+        raytracer.accumulation_enabled = self.accumulation_enabled;
+        if let Some(variant) = self.comparison() {
+            raytracer.set_comparison(Some(variant), device, queue, sky);
+        }
+        *self = raytracer;
+    }
+
+    /// Recompiles the raytrace shader from `path` (such as `src/shaders/raytrace.wgsl`). If the shader
+    /// has errors, the raytracer keeps running with the pipeline that it already has.
+    pub fn reload_shader<P: AsRef<Path>>(&mut self, device: &wgpu::Device, path: P) -> Result<(), ShaderError> {
+        let module = shader_errors::load_shader_module(device, path)?;
+        let pipeline = shader_errors::validation_scope(device, || {
+            create_raytrace_pipeline(device, &module, &self.raytrace_pipeline_layout, self.gpu_precompute.mode(), self.workgroup_size)
+        })?;
+        if let Some(comparison) = &mut self.comparison {
+            let variant = comparison.variant;
+            comparison.pipeline = shader_errors::validation_scope(device, || {
+                create_raytrace_pipeline(device, &module, &comparison.pipeline_layout, variant.direction_mode, variant.workgroup_size)
+            })?;
+        }
+        self.raytrace_shader = module;
+        self.raytrace_pipeline = pipeline;
+        self.reset_accumulation();
+        Ok(())
+    }
+
+    /// Uploads the chunk (if it has changed) with a copy in `encoder`, which must be submitted before the raytracer runs.
+    /// The block light is rebuilt and uploaded along with it, or on its own if the materials changed. Returns `false`
+    /// if neither the chunk nor the materials changed.
+    pub fn write_chunk(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, staging: &mut StagingRing) -> bool {
+        if !self.chunk.needs_write && !self.block_light_dirty {
+            return false;
+        }
+        if self.chunk.needs_write {
+            if self.chunk.lods_dirty() {
+                self.chunk.rebuild_lods();
+            }
+            self.gpu_chunk.upload_chunk(&self.chunk, device, encoder, staging);
+            self.chunk.needs_write = false;
+        }
+        self.rebuild_block_light();
+        staging.write(device, encoder, &self.gpu_block_light, 0, self.block_light.as_bytes());
+        self.reset_accumulation();
+        true
+    }
+
+    /// Floods the light of the emissive blocks through the chunk, see [BlockLight].
+    fn rebuild_block_light(&mut self) {
+        let materials = (0..MATERIAL_COUNT as u32).map(|id| self.materials.get_material(id)).collect::<Vec<_>>();
+        self.block_light.rebuild(&self.chunk, &materials);
+        self.block_light_dirty = false;
+    }
+
+    pub fn block_light(&self) -> &BlockLight {
+        &self.block_light
+    }
+
+    pub fn write_camera_transform(&mut self, transform: GpuTransform, queue: &wgpu::Queue) {
+        let moved = self.last_transform
+            .map(|last| bytemuck::bytes_of(&last) != bytemuck::bytes_of(&transform))
+            .unwrap_or(true);
+        if moved {
+            self.reset_accumulation();
+        }
+        self.last_transform = Some(transform);
+        self.gpu_camera.write_transform(transform, queue);
+    }
+
+    pub fn fov(&self) -> f32 {
+        self.fov
+    }
+
+    /// Rewrites the NDC multiplier and recomputes the ray directions for the new field of view.
+    /// This should be kept in sync with [Camera::fov].
+    pub fn set_fov(&mut self, fov: f32, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if fov == self.fov {
+            return;
+        }
+        self.fov = fov;
+        self.gpu_precompute.write_fov(fov, queue);
+        self.gpu_precompute.submit_compute(device, queue);
+        if let Some(comparison) = &self.comparison {
+            comparison.gpu_precompute.write_fov(fov, queue);
+            comparison.gpu_precompute.submit_compute(device, queue);
+        }
+        self.reset_accumulation();
+    }
+
+    pub fn quality(&self) -> RaytraceQuality {
+        self.quality
+    }
+
+    pub fn direction_mode(&self) -> RayDirectionMode {
+        self.gpu_precompute.mode()
+    }
+
+    /// Switches how the primary ray directions are computed, which rebuilds the raytrace pipeline.
+    /// Both modes render the same image, so the accumulation is kept.
+    pub fn set_direction_mode(&mut self, mode: RayDirectionMode, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if mode == self.gpu_precompute.mode() {
+            return;
+        }
+        self.gpu_precompute.set_mode(device, mode);
+        self.gpu_precompute.submit_compute(device, queue);
+        self.raytrace_pipeline = create_raytrace_pipeline(device, &self.raytrace_shader, &self.raytrace_pipeline_layout, mode, self.workgroup_size);
+    }
+
+    /// Recreates the pipelines that draw the result to a target of `format`, in a pass with
+    /// `sample_count` samples (see [crate::rendering::msaa::Msaa]).
+    pub fn set_render_target(&mut self, format: wgpu::TextureFormat, sample_count: u32, device: &wgpu::Device) {
+        self.target_format = format;
+        self.sample_count = sample_count;
+        self.result.set_render_target(device, format, sample_count);
+        if let Some(comparison) = &mut self.comparison {
+            comparison.result.set_render_target(device, format, sample_count);
+        }
+    }
+
+    /// The format that the result is drawn to.
+    pub fn target_format(&self) -> wgpu::TextureFormat {
+        self.target_format
+    }
+
+    pub fn workgroup_size(&self) -> WorkgroupSize {
+        self.workgroup_size
+    }
+
+    /// Rebuilds the raytrace pipeline with different workgroup dimensions. Returns `false` (and keeps
+    /// the current size) if the device doesn't support the size.
+    pub fn set_workgroup_size(&mut self, workgroup_size: WorkgroupSize, device: &wgpu::Device) -> bool {
+        if !workgroup_size.is_supported(&device.limits()) {
+            return false;
+        }
+        self.workgroup_size = workgroup_size;
+        self.raytrace_pipeline = create_raytrace_pipeline(device, &self.raytrace_shader, &self.raytrace_pipeline_layout, self.gpu_precompute.mode(), workgroup_size);
+        if self.indirect_dispatch.is_some() {
+            self.indirect_dispatch = Some(IndirectDispatch::new(device, &self.result.result_view, workgroup_size));
+        }
+        true
+    }
+
+    pub fn indirect_dispatch(&self) -> bool {
+        self.indirect_dispatch.is_some()
+    }
+
+    /// Whether the raytrace dispatch takes its workgroup counts from a buffer that is written on the GPU
+    /// from the size of the result, instead of counting them when the pass is recorded (see
+    /// [super::dispatch]). Check [IndirectDispatch::is_supported] first. The A/B comparison always
+    /// dispatches directly.
+    pub fn set_indirect_dispatch(&mut self, enabled: bool, device: &wgpu::Device) {
+        if enabled == self.indirect_dispatch.is_some() {
+            return;
+        }
+        self.indirect_dispatch = enabled.then(|| IndirectDispatch::new(device, &self.result.result_view, self.workgroup_size));
+    }
+
+    /// The variant that the raytracer is compared against, if an A/B comparison is running.
+    pub fn comparison(&self) -> Option<RaytraceVariant> {
+        self.comparison.as_ref().map(|comparison| comparison.variant)
+    }
+
+    /// Starts an A/B comparison against `variant` (or stops it with `None`). The variant gets its own targets
+    /// and pipeline, and is traced after the raytracer in [Raytracer::compute_comparison]. `sky` has to be the
+    /// cubemap that the raytracer was created with. Returns `false` (and keeps the current comparison) if the
+    /// device doesn't support the variant's workgroup size.
+    pub fn set_comparison(&mut self, variant: Option<RaytraceVariant>, device: &wgpu::Device, queue: &wgpu::Queue, sky: &SkyboxCubemap) -> bool {
+        let Some(variant) = variant else {
+            self.comparison = None;
+            return true;
+        };
+        if !variant.workgroup_size.is_supported(&device.limits()) {
+            return false;
+        }
+        let (width, height) = (self.result.result_texture.width(), self.result.result_texture.height());
+        let mut result = GpuRaytraceResult::new(device, width, height);
+        result.set_render_target(device, self.target_format, self.sample_count);
+        let fxaa = Fxaa::new(device, &result.result_view, width, height);
+        let fxaa_render_bind_group = result.create_render_bind_group(device, fxaa.output_view());
+        let taa = Taa::new(device, &result.result_view, &result.hit_normal_distance_view(), width, height);
+        let taa_render_bind_groups = result.create_taa_render_bind_groups(device, &taa);
+        let gpu_precompute = PrecomputedDirections::new(device, self.fov, width, height, variant.direction_mode);
+        gpu_precompute.submit_compute(device, queue);
+        let gpu_lighting = UniformBuffer::new(device, Some("Raytracer Comparison Lighting Buffer"), self.comparison_lighting(variant));
+        let data_bind_group = self.create_data_bind_group(device, gpu_lighting.buffer(), sky);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Raytracer Comparison Compute Pipeline Layout"),
+            bind_group_layouts: &[
+                &result.write_bind_group_layout,
+                &gpu_precompute.read_bind_group_layout,
+                &self.data_bind_group_layout,
+                &self.blue_noise_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+        let pipeline = create_raytrace_pipeline(device, &self.raytrace_shader, &pipeline_layout, variant.direction_mode, variant.workgroup_size);
+        self.comparison = Some(RaytraceComparison {
+            variant,
+            result,
+            fxaa,
+            fxaa_render_bind_group,
+            taa,
+            taa_render_bind_groups,
+            gpu_precompute,
+            gpu_lighting,
+            data_bind_group,
+            pipeline_layout,
+            pipeline,
+        });
+        self.reset_accumulation();
+        true
+    }
+
+    /// The raytracer's lighting with the ambient occlusion of `variant`.
+    fn comparison_lighting(&self, variant: RaytraceVariant) -> RtLighting {
+        let mut lighting = self.gpu_lighting.lighting();
+        lighting.ambient.ao_strength = variant.ao_strength;
+        lighting
+    }
+
+    /// The data bind group with a different lighting buffer, for the B side of a comparison.
+    fn create_data_bind_group(&self, device: &wgpu::Device, lighting: &wgpu::Buffer, sky: &SkyboxCubemap) -> wgpu::BindGroup {
+        Bindings::new()
+            .buffer(0, self.gpu_camera.buffer.buffer())
+            .buffer(1, &self.gpu_chunk.buffer)
+            .buffer(2, lighting)
+            .buffer(3, self.gpu_config.buffer.buffer())
+            .buffer(4, self.materials.buffer.buffer())
+            .buffer(5, self.gpu_fog.buffer())
+            .buffer(6, self.gpu_sky_tint.buffer())
+            .texture_view(7, &sky.view)
+            .sampler(8, &sky.sampler)
+            .buffer(9, self.gpu_entities.buffer())
+            .buffer(10, self.gpu_point_lights.buffer())
+            .buffer(11, &self.gpu_block_light)
+            .buffer(12, self.gpu_clouds.buffer())
+            .buffer(13, &self.frame_uniforms)
+            .build(device, Some("Raytracer Comparison Data Bind Group"), &self.data_bind_group_layout)
+    }
+
+    /// The resolution that the raytracer renders at, including supersampling and the render scale.
+    pub fn resolution(&self) -> (u32, u32) {
+        self.quality.resolution()
+    }
+
+    /// Changing the supersample or render scale recreates the render targets and recomputes the ray
+    /// directions. The supersample scale is clamped to `1..=MAX_SUPERSAMPLE_SCALE`, the render scale to
+    /// `MIN_RENDER_SCALE..=MAX_RENDER_SCALE`.
+    pub fn set_quality(&mut self, quality: RaytraceQuality, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let quality = RaytraceQuality {
+            supersample_scale: quality.supersample_scale.clamp(1, MAX_SUPERSAMPLE_SCALE),
+            render_scale: quality.render_scale.clamp(MIN_RENDER_SCALE, MAX_RENDER_SCALE),
+            ..quality
+        };
+        if quality.resolution() != self.quality.resolution() {
+            let (width, height) = quality.resolution();
+            self.resize_targets(device, queue, width, height);
+        }
+        if quality.antialiasing != self.quality.antialiasing {
+            // The history from the last time TAA was on is stale.
+            self.taa.reset();
+            if let Some(comparison) = &mut self.comparison {
+                comparison.taa.reset();
+            }
+        }
+        self.quality = quality;
+    }
+
+    /// Recreates the render targets and recomputes the ray directions.
+    fn resize_targets(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, width: u32, height: u32) {
+        self.result.resize(device, width, height);
+        self.fxaa.resize(device, &self.result.result_view, width, height);
+        self.fxaa_render_bind_group = self.result.create_render_bind_group(device, self.fxaa.output_view());
+        self.taa.resize(device, &self.result.result_view, &self.result.hit_normal_distance_view(), width, height);
+        self.taa_render_bind_groups = self.result.create_taa_render_bind_groups(device, &self.taa);
+        if let Some(indirect_dispatch) = &mut self.indirect_dispatch {
+            indirect_dispatch.set_target(device, &self.result.result_view);
+        }
+        self.gpu_precompute.resize(device, width, height);
+        self.gpu_precompute.submit_compute(device, queue);
+        self.gpu_camera.write_dimensions(width, height, queue);
+        if let Some(comparison) = &mut self.comparison {
+            comparison.result.resize(device, width, height);
+            comparison.fxaa.resize(device, &comparison.result.result_view, width, height);
+            comparison.fxaa_render_bind_group = comparison.result.create_render_bind_group(device, comparison.fxaa.output_view());
+            comparison.taa.resize(device, &comparison.result.result_view, &comparison.result.hit_normal_distance_view(), width, height);
+            comparison.taa_render_bind_groups = comparison.result.create_taa_render_bind_groups(device, &comparison.taa);
+            comparison.gpu_precompute.resize(device, width, height);
+            comparison.gpu_precompute.submit_compute(device, queue);
+        }
+        self.reset_accumulation();
+    }
+
+    /// Copies the raytrace result (before FXAA) back to the CPU, encoded as sRGB. This blocks until the
+    /// copy is done, so it's meant for debugging shader output.
+    pub fn read_result(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<image::RgbaImage, SequenceError> {
+        offline::read_texture_rgba(device, queue, &self.result.result_texture)
+    }
+
+    /// The layout of [Raytracer::hit_bind_group], for pipelines that read the G-buffer.
+    pub fn hit_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.result.hit_bind_group_layout
+    }
+
+    /// Binds the G-buffer of the current result. It changes when the result is resized.
+    pub fn hit_bind_group(&self) -> &wgpu::BindGroup {
+        &self.result.hit_bind_group
+    }
+
+    /// Reads the G-buffer back to the CPU, row by row. Like [Raytracer::read_result], this blocks until
+    /// the copy is done.
+    pub fn read_hits(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<Vec<GBufferHit>, SequenceError> {
+        let ids = offline::read_texture(device, queue, &self.result.hit_id_texture)?;
+        let normal_distances = offline::read_texture(device, queue, &self.result.hit_normal_distance_texture)?;
+        Ok(ids.chunks_exact(4)
+            .zip(normal_distances.chunks_exact(8))
+            .map(|(id, normal_distance)| GBufferHit::from_texels(id, normal_distance))
+            .collect())
+    }
+
+    /// Renders `settings.frames` frames along `camera_path` (or a single frame from `camera` without a path)
+    /// at `settings.resolution`, and saves them as numbered PNGs in `output_dir`. This blocks until every
+    /// frame is saved. The render targets are restored afterwards. FXAA isn't applied to the saved frames.
+    /// The passes are timed with the `profiler`, if there is one.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_sequence(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        camera: &Camera,
+        camera_path: Option<&CameraPath>,
+        output_dir: &Path,
+        settings: SequenceSettings,
+        mut profiler: Option<&mut PassProfiler>,
+    ) -> Result<(), SequenceError> {
+        let (width, height) = settings.resolution;
+        let max_size = device.limits().max_texture_dimension_2d;
+        if width == 0 || height == 0 || width > max_size || height > max_size {
+            return Err(SequenceError::InvalidResolution(width, height));
+        }
+        std::fs::create_dir_all(output_dir)?;
+        if self.chunk.needs_write || self.block_light_dirty {
+            if self.chunk.lods_dirty() {
+                self.chunk.rebuild_lods();
+            }
+            self.gpu_chunk.write_chunk(&self.chunk, queue);
+            self.chunk.needs_write = false;
+            self.rebuild_block_light();
+            queue.write_buffer(&self.gpu_block_light, 0, self.block_light.as_bytes());
+        }
+        let frames = if camera_path.is_some() { settings.frames.max(1) } else { 1 };
+        let samples = settings.samples_per_frame.clamp(1, MAX_ACCUMULATED_FRAMES);
+        let accumulation_enabled = self.accumulation_enabled;
+        self.accumulation_enabled = true;
+        self.gpu_precompute.write_fov_for(self.fov, settings.resolution, queue);
+        self.resize_targets(device, queue, width, height);
+
+        let mut result = Ok(());
+        for frame in 0..frames {
+            let mut frame_camera = camera.clone();
+            let t = if frames > 1 { frame as f32 / (frames - 1) as f32 } else { 0.0 };
+            if let Some(keyframe) = camera_path.and_then(|path| path.sample(t)) {
+                keyframe.apply(&mut frame_camera);
+            }
+            self.write_camera_transform(GpuTransform::new(
+                GpuMat3::new(frame_camera.rotation_matrix()),
+                GpuVec3::from_vec3(frame_camera.position),
+            ), queue);
+            self.reset_accumulation();
+            for _ in 0..samples {
+                self.write_accumulation(queue);
+                let mut passes = FrameGraphLite::new(device, "Offline Render Encoder", profiler.as_deref());
+                passes.compute_pass("Offline Render", |compute_pass| self.compute(compute_pass, None));
+                queue.submit(Some(passes.finish(profiler.as_deref_mut())));
+                if let Some(profiler) = profiler.as_deref_mut() {
+                    profiler.map();
+                }
+            }
+            let path = offline::frame_path(output_dir, frame);
+            result = self.read_result(device, queue)
+                .and_then(|image| Ok(image.save(&path)?));
+            if result.is_err() {
+                break;
+            }
+            log::info!("Saved frame {}/{frames} to {}", frame + 1, path.display());
+        }
+
+        self.accumulation_enabled = accumulation_enabled;
+        self.gpu_precompute.write_fov(self.fov, queue);
+        let (width, height) = self.quality.resolution();
+        self.resize_targets(device, queue, width, height);
+        self.last_transform = None;
+        result
+    }
+
+    /// Discards the accumulated frames. Call this after changing anything that affects the image
+    /// other than the camera transform or the chunk (those reset automatically).
+    pub fn reset_accumulation(&mut self) {
+        self.accumulated_frames = 0;
+    }
+
+    pub fn accumulation_enabled(&self) -> bool {
+        self.accumulation_enabled
+    }
+
+    pub fn set_accumulation_enabled(&mut self, enabled: bool) {
+        self.accumulation_enabled = enabled;
+        self.reset_accumulation();
+    }
+
+    pub fn accumulated_frames(&self) -> u32 {
+        self.accumulated_frames
+    }
+
+    /// Writes the accumulated frame count (and the lighting of the comparison, if there is one, and the
+    /// camera of TAA) for the upcoming compute pass. Call once per frame after the camera transform and chunk have been written.
+    pub fn write_accumulation(&mut self, queue: &wgpu::Queue) {
+        if !self.accumulation_enabled {
+            self.accumulated_frames = 0;
+        }
+        if self.gpu_config.get_accumulated_frames() != self.accumulated_frames {
+            self.gpu_config.set_accumulated_frames(queue, self.accumulated_frames);
+        }
+        if self.accumulation_enabled {
+            self.accumulated_frames = (self.accumulated_frames + 1).min(MAX_ACCUMULATED_FRAMES);
+        }
+        self.blue_noise.write_frame(queue, self.noise_frame);
+        self.noise_frame = self.noise_frame.wrapping_add(1);
+        if self.quality.antialiasing == AntialiasMode::Taa {
+            let transform = self.gpu_camera.gpu_cam().transform;
+            let ndc_mult = calc_ray_mult(self.fov, (self.result.result_texture.width(), self.result.result_texture.height()));
+            self.taa.prepare(queue, transform, ndc_mult);
+            if let Some(comparison) = &mut self.comparison {
+                comparison.taa.prepare(queue, transform, ndc_mult);
+            }
+        }
+        if let Some(comparison) = &self.comparison {
+            let lighting = self.comparison_lighting(comparison.variant);
+            if bytemuck::bytes_of(&lighting) != bytemuck::bytes_of(&comparison.gpu_lighting.get()) {
+                comparison.gpu_lighting.write(queue, lighting);
+            }
+        }
+    }
+
+    /// Replaces the generated blue noise with a square grayscale image, such as a precomputed
+    /// noise texture. Returns `false` (and keeps the current noise) if the image isn't square.
+    pub fn set_blue_noise(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, image: &image::GrayImage) -> bool {
+        let Some(blue_noise) = BlueNoise::from_image(device, queue, &self.blue_noise_layout, image) else {
+            return false;
+        };
+        self.blue_noise = blue_noise;
+        self.reset_accumulation();
+        true
+    }
+
+    pub fn shadow_quality(&self) -> ShadowQuality {
+        self.shadow_quality
+    }
+
+    pub fn set_shadow_quality(&mut self, quality: ShadowQuality, queue: &wgpu::Queue) {
+        self.shadow_quality = quality;
+        self.gpu_config.set_shadow_samples(queue, quality.samples());
+        self.reset_accumulation();
+    }
+
+    pub fn volumetric_quality(&self) -> VolumetricQuality {
+        self.volumetric_quality
+    }
+
+    /// Sets the step count of the volumetric light, see [VolumetricQuality].
+    pub fn set_volumetric_quality(&mut self, quality: VolumetricQuality, queue: &wgpu::Queue) {
+        self.volumetric_quality = quality;
+        self.gpu_config.set_volumetric_steps(queue, quality.steps());
+        self.reset_accumulation();
+    }
+
+    pub fn volumetric_density(&self) -> f32 {
+        self.gpu_config.get_volumetric_density()
+    }
+
+    /// The density is clamped to `0.0..=MAX_VOLUMETRIC_DENSITY`.
+    pub fn set_volumetric_density(&mut self, density: f32, queue: &wgpu::Queue) {
+        self.gpu_config.set_volumetric_density(queue, density.clamp(0.0, MAX_VOLUMETRIC_DENSITY));
+        self.reset_accumulation();
+    }
+
+    pub fn sky_mode(&self) -> SkyMode {
+        self.sky_mode
+    }
+
+    pub fn set_sky_mode(&mut self, sky_mode: SkyMode, queue: &wgpu::Queue) {
+        self.sky_mode = sky_mode;
+        self.gpu_config.set_sky_mode(queue, sky_mode);
+        self.reset_accumulation();
+    }
+
+    pub fn debug_view(&self) -> RaytraceDebugView {
+        self.debug_view
+    }
+
+    pub fn set_debug_view(&mut self, debug_view: RaytraceDebugView, queue: &wgpu::Queue) {
+        self.debug_view = debug_view;
+        self.gpu_config.set_debug_view(queue, debug_view);
+        self.reset_accumulation();
+    }
+
+    pub fn heatmap_max_steps(&self) -> u32 {
+        self.gpu_config.get_heatmap_max_steps()
+    }
+
+    /// The step count at the top of the heatmap's ramp, at least 1.
+    pub fn set_heatmap_max_steps(&mut self, max_steps: u32, queue: &wgpu::Queue) {
+        self.gpu_config.set_heatmap_max_steps(queue, max_steps);
+    }
+
+    /// Uploads the entities if they changed since the last call (see [Entities::take_changed]).
+    pub fn write_entities(&mut self, entities: &mut Entities, queue: &wgpu::Queue) {
+        if !entities.take_changed() {
+            return;
+        }
+        let gpu_entities = entities.to_gpu();
+        self.gpu_entities.write(queue, 0, &gpu_entities);
+        self.gpu_config.set_entity_count(queue, gpu_entities.len() as u32);
+        self.reset_accumulation();
+    }
+
+    /// Uploads the point lights if they changed since the last call (see [Lights::take_changed]).
+    pub fn write_lights(&mut self, lights: &mut Lights, queue: &wgpu::Queue) {
+        if !lights.take_changed() {
+            return;
+        }
+        let gpu_lights = lights.to_gpu();
+        self.gpu_point_lights.write(queue, 0, &gpu_lights);
+        self.gpu_config.set_point_light_count(queue, gpu_lights.len() as u32);
+        self.reset_accumulation();
+    }
+
+    /// Sets the color that the sky is multiplied with, such as [crate::day_night::DayNightCycle::sky_tint].
+    pub fn set_sky_tint(&mut self, tint: Vec4, queue: &wgpu::Queue) {
+        if tint == self.gpu_sky_tint.get() {
+            return;
+        }
+        self.gpu_sky_tint.write(queue, tint);
+        self.reset_accumulation();
+    }
+
+    /// Uploads the clouds if they changed. Only changes to how they look reset the accumulation, not the
+    /// wind moving them, which would keep it from ever accumulating.
+    pub fn set_clouds(&mut self, clouds: &Clouds, queue: &wgpu::Queue) {
+        let gpu_clouds = clouds.to_gpu();
+        let previous = self.gpu_clouds.get();
+        if gpu_clouds == previous {
+            return;
+        }
+        self.gpu_clouds.write(queue, gpu_clouds);
+        if gpu_clouds.differs_from(&previous) {
+            self.reset_accumulation();
+        }
+    }
+
+    pub fn max_bounces(&self) -> u32 {
+        self.gpu_config.get_max_bounces()
+    }
+
+    /// Sets how many reflection bounces reflective materials may spawn. `0` disables reflections.
+    pub fn set_max_bounces(&mut self, max_bounces: u32, queue: &wgpu::Queue) {
+        self.gpu_config.set_max_bounces(queue, max_bounces);
+        self.reset_accumulation();
+    }
+
+    pub fn lod_distance(&self) -> f32 {
+        self.gpu_config.get_lod_distance()
+    }
+
+    /// Sets the distance at which primary rays switch to the downsampled chunk LODs. `0.0` disables LODs.
+    pub fn set_lod_distance(&mut self, lod_distance: f32, queue: &wgpu::Queue) {
+        self.gpu_config.set_lod_distance(queue, lod_distance);
+        self.reset_accumulation();
+    }
+
+    pub fn fog(&self) -> Fog {
+        self.gpu_fog.get()
+    }
+
+    /// Writes the fog if it changed. The fog is applied to the primary hits, and primary rays stop marching
+    /// where the distance fog becomes opaque.
+    pub fn set_fog(&mut self, fog: &Fog, queue: &wgpu::Queue) {
+        if bytemuck::bytes_of(fog) == bytemuck::bytes_of(&self.gpu_fog.get()) {
+            return;
+        }
+        self.gpu_fog.write(queue, *fog);
+        self.reset_accumulation();
+    }
+
+    /// Raycasts against the CPU copy of the chunk, using the material table to find translucent blocks.
+    pub fn raycast(&self, ray: Ray3, max_distance: f32, translucent: TranslucentHits) -> Option<RayHit> {
+        match translucent {
+            TranslucentHits::Stop => self.chunk.raycast(ray, max_distance),
+            TranslucentHits::PassThrough => self.chunk.raycast_through(ray, max_distance, |id| {
+                id != 0 && self.materials.get_material(id).is_translucent()
+            }),
+        }
+    }
+
+    pub fn set_material(&mut self, id: u32, material: Material, queue: &wgpu::Queue) {
+        self.materials.set_material(queue, id, material);
+        self.block_light_dirty = true;
+        self.reset_accumulation();
+    }
+
+    pub fn compute(&self, compute_pass: &mut wgpu::ComputePass, query_set: Option<&wgpu::QuerySet>) {
+        if let Some(indirect_dispatch) = &self.indirect_dispatch {
+            debug_group(compute_pass, "Raytrace Dispatch Size", |compute_pass| indirect_dispatch.compute(compute_pass));
+        }
+        compute_pass.set_pipeline(&self.raytrace_pipeline);
+        self.result.bind_write(0, compute_pass);
+        self.gpu_precompute.bind_read(1, compute_pass);
+        compute_pass.set_bind_group(2, &self.data_bind_group, &[]);
+        compute_pass.set_bind_group(3, &self.blue_noise.bind_group, &[]);
+        // self.gpu_chunk.bind(2, compute_pass);
+        // self.gpu_camera.bind(3, compute_pass);
+        // self.gpu_lighting.bind(4, compute_pass);
+        // The targets can differ from the quality's resolution while rendering offline.
+        let (width, height) = (self.result.result_texture.width(), self.result.result_texture.height());
+        let (x, y) = self.workgroup_size.dispatch_size(width, height);
+        let dispatch = |compute_pass: &mut wgpu::ComputePass| match &self.indirect_dispatch {
+            Some(indirect_dispatch) => indirect_dispatch.dispatch(compute_pass),
+            None => compute_pass.dispatch_workgroups(x, y, 1),
+        };
+        debug_group(compute_pass, "Raytrace Dispatch", |compute_pass| match query_set {
+            Some(query_set) => {
+                compute_pass.write_timestamp(query_set, 0);
+                dispatch(compute_pass);
+                compute_pass.write_timestamp(query_set, 1);
+            },
+            None => {
+                dispatch(compute_pass);
+            },
+        });
+        match self.quality.antialiasing {
+            AntialiasMode::Off => {},
+            AntialiasMode::Fxaa => debug_group(compute_pass, "FXAA", |compute_pass| self.fxaa.compute(compute_pass)),
+            AntialiasMode::Taa => debug_group(compute_pass, "TAA", |compute_pass| self.taa.compute(compute_pass)),
+        }
+    }
+
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass) {
+        match self.quality.antialiasing {
+            AntialiasMode::Off => self.result.render(render_pass),
+            AntialiasMode::Fxaa => self.result.render_with(render_pass, &self.fxaa_render_bind_group),
+            AntialiasMode::Taa => self.result.render_with(render_pass, &self.taa_render_bind_groups[self.taa.output_index()]),
+        }
+    }
+
+    /// Traces the B side of the comparison (see [Raytracer::set_comparison]) into its own targets, with
+    /// the timestamps around the dispatch like [Raytracer::compute]. Does nothing without a comparison.
+    pub fn compute_comparison(&self, compute_pass: &mut wgpu::ComputePass, query_set: Option<&wgpu::QuerySet>) {
+        let Some(comparison) = &self.comparison else {
+            return;
+        };
+        compute_pass.set_pipeline(&comparison.pipeline);
+        comparison.result.bind_write(0, compute_pass);
+        comparison.gpu_precompute.bind_read(1, compute_pass);
+        compute_pass.set_bind_group(2, &comparison.data_bind_group, &[]);
+        compute_pass.set_bind_group(3, &self.blue_noise.bind_group, &[]);
+        let (width, height) = (comparison.result.result_texture.width(), comparison.result.result_texture.height());
+        let (x, y) = comparison.variant.workgroup_size.dispatch_size(width, height);
+        debug_group(compute_pass, "Raytrace Comparison Dispatch", |compute_pass| {
+            if let Some(query_set) = query_set {
+                compute_pass.write_timestamp(query_set, 0);
+            }
+            compute_pass.dispatch_workgroups(x, y, 1);
+            if let Some(query_set) = query_set {
+                compute_pass.write_timestamp(query_set, 1);
+            }
+        });
+        match self.quality.antialiasing {
+            AntialiasMode::Off => {},
+            AntialiasMode::Fxaa => debug_group(compute_pass, "Comparison FXAA", |compute_pass| comparison.fxaa.compute(compute_pass)),
+            AntialiasMode::Taa => debug_group(compute_pass, "Comparison TAA", |compute_pass| comparison.taa.compute(compute_pass)),
+        }
+    }
+
+    /// Draws the result of the comparison's B side. Does nothing without a comparison.
+    pub fn render_comparison(&self, render_pass: &mut wgpu::RenderPass) {
+        let Some(comparison) = &self.comparison else {
+            return;
+        };
+        match self.quality.antialiasing {
+            AntialiasMode::Off => comparison.result.render(render_pass),
+            AntialiasMode::Fxaa => comparison.result.render_with(render_pass, &comparison.fxaa_render_bind_group),
+            AntialiasMode::Taa => comparison.result.render_with(render_pass, &comparison.taa_render_bind_groups[comparison.taa.output_index()]),
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::voxel::worldgen::generate_hills;
+
+    use super::*;
+
+    #[test]
+    fn chunk_transform_test() {
+        let mut chunk = RaytraceChunk::new();
+        chunk.set_morton_order(true);
+        chunk.set(1, 2, 3, 1);
+        chunk.set(63, 0, 0, 2);
+        chunk.rotate_y_90();
+        // Clockwise from above: +X turns into +Z.
+        assert_eq!(chunk.get(60, 2, 1), 1);
+        assert_eq!(chunk.get(63, 0, 63), 2);
+        assert_eq!(chunk.get_cell(ivec3(60, 2, 1)), 1);
+        for _ in 0..3 {
+            chunk.rotate_y_90();
+        }
+        assert_eq!((chunk.get(1, 2, 3), chunk.get(63, 0, 0)), (1, 2));
+
+        chunk.mirror_x();
+        assert_eq!((chunk.get(62, 2, 3), chunk.get(0, 0, 0)), (1, 2));
+        chunk.mirror_z();
+        assert_eq!((chunk.get(62, 2, 60), chunk.get(0, 0, 63)), (1, 2));
+
+        chunk.translate_wrapping(ivec3(3, -1, 4));
+        assert_eq!(chunk.get(1, 1, 0), 1);
+        assert_eq!(chunk.get(3, 63, 3), 2);
+        assert_eq!(chunk.get_cell(ivec3(3, 63, 3)), 2);
+        assert_eq!(chunk.blocks().iter().filter(|&&id| id != 0).count(), 2);
+        assert!(chunk.needs_write && chunk.lods_dirty());
+    }
+
+    #[test]
+    fn spawn_point_test() {
+        let mut chunk = generate_hills(3, 1);
+        let top = chunk.highest_solid_at(32, 32).unwrap();
+        assert_eq!(chunk.get(32, top + 1, 32), 0);
+        assert_eq!(chunk.ground_below(32, 63, 32), Some(top));
+        assert_eq!(chunk.ground_below(32, -1, 32), None);
+        assert_eq!(chunk.column(32, 32).filter(|&(_, id)| id != 0).count() as i32, top + 1);
+        assert_eq!(chunk.find_spawn_point(), Some(glam::vec3(32.5, (top + 1) as f32, 32.5)));
+        // Without ground in the center, the spawn point moves to a neighboring column.
+        for y in 0..64 {
+            chunk.set(32, y, 32, 0);
+        }
+        assert_eq!(chunk.highest_solid_at(32, 32), None);
+        let spawn = chunk.find_spawn_point().unwrap();
+        assert_ne!((spawn.x, spawn.z), (32.5, 32.5));
+        assert!((spawn.x - 32.5).abs() <= 1.0 && (spawn.z - 32.5).abs() <= 1.0);
+    }
+
+    #[test]
+    fn lod_test() {
+        let mut chunk = generate_hills(3, 1);
+        chunk.set(5, 40, 5, 2);
+        assert!(chunk.lods_dirty());
+        chunk.rebuild_lods();
+        assert!(!chunk.lods_dirty());
+        // A LOD cell is solid if any of the cells it covers are solid.
+        for level in 1..=CHUNK_LOD_LEVELS {
+            let scale = 1 << level;
+            for y in 0..lod_size(level) {
+                for (x, z) in [(0, 0), (2, 1)] {
+                    let any_solid = (0..scale * scale * scale).any(|i| {
+                        chunk.get(x * scale + i % scale, y * scale + (i / scale) % scale, z * scale + i / (scale * scale)) != 0
+                    });
+                    assert_eq!(chunk.get_lod(level, x, y, z) != 0, any_solid);
+                }
+            }
+        }
+        // The lone block is kept, even though most of its group is air.
+        assert_eq!(chunk.get_lod(1, 2, 20, 2), 2);
+        assert_eq!(chunk.get_lod(3, 0, 5, 0), 2);
+        assert_eq!(chunk.get_lod(1, 0, 0, 0), 1);
+        assert_eq!(chunk.get_lod(1, 32, 0, 0), 0);
+    }
+
+    #[test]
+    fn morton_order_test() {
+        let mut chunk = generate_hills(5, 1);
+        let ray = Ray3::new(glam::vec3a(0.5, 40.0, 0.5), glam::vec3a(1.0, -0.6, 0.8).normalize());
+        let linear_hit = chunk.raycast(ray, 200.0).unwrap();
+        chunk.set_morton_order(true);
+        assert!(chunk.morton_order());
+        assert_eq!(chunk.raycast(ray, 200.0).unwrap().coord, linear_hit.coord);
+        // Edits are mirrored into the Morton copy.
+        let cell = linear_hit.coord;
+        chunk.set(cell.x, cell.y, cell.z, 0);
+        let next_hit = chunk.raycast(ray, 200.0).unwrap();
+        assert_ne!(next_hit.coord, cell);
+        chunk.set_morton_order(false);
+        assert_eq!(chunk.raycast(ray, 200.0).unwrap().coord, next_hit.coord);
+    }
+
+    #[test]
+    fn raycast_through_test() {
+        let mut chunk = generate_hills(5, 1);
+        let ray = Ray3::new(glam::vec3a(32.5, 63.5, 32.5), glam::vec3a(0.0, -1.0, 0.0));
+        let ground = chunk.raycast(ray, 200.0).unwrap();
+        // A layer of "water" above the ground.
+        chunk.set(32, ground.coord.y + 3, 32, 4);
+        assert_eq!(chunk.raycast(ray, 200.0).unwrap().id, 4);
+        let through = chunk.raycast_through(ray, 200.0, |id| id == 4).unwrap();
+        assert_eq!(through.coord, ground.coord);
+        assert_eq!(through.id, ground.id);
+    }
+
+    #[test]
+    fn volumetric_config_test() {
+        // The volumetric settings took the place of the padding, so the config still matches raytrace.wgsl.
+        assert_eq!(std::mem::size_of::<RaytraceConfig>(), 48);
+        let mut quality = VolumetricQuality::default();
+        assert_eq!(quality.steps(), 0);
+        let mut steps = Vec::new();
+        for _ in 0..3 {
+            quality = quality.next();
+            steps.push(quality.steps());
+        }
+        assert!(steps.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(quality.next(), VolumetricQuality::Off);
+    }
+
+    #[test]
+    fn liquid_test() {
+        // The liquid flag padded the material out to the size of the WGSL struct.
+        assert_eq!(std::mem::size_of::<RtMaterial>(), 48);
+        let water = Material::from(RtMaterial::from(Material::WATER));
+        assert_eq!(water, Material::WATER);
+        assert!(water.is_liquid());
+        assert!(!Material { translucency: 0.0, ..water }.is_liquid());
+    }
+}
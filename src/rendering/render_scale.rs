@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use crate::math::average::AverageBuffer;
+
+/*
+The render scale multiplies the resolution of the raytrace result (see [RaytraceQuality]) independently
+of the window size. The result is stretched over the screen when it is drawn, so a lower scale trades
+sharpness for speed.
+
+[DynamicRenderScale] picks the scale automatically to hold a target framerate. It is fed the time that
+the frame actually worked for (see [crate::state::State::record_frame_times]) rather than the time
+between frames, since the latter is stuck at the refresh rate with vsync and hides any headroom:
+
+    - Above the budget, the scale drops by a step.
+    - Below `HEADROOM` of the budget, the scale rises by a step.
+    - Every change recreates the result textures and restarts accumulation, so after a change the
+      average is cleared and the next decision waits for a full window of new samples.
+
+[RaytraceQuality]: super::raytrace::RaytraceQuality
+*/
+
+/// In percent.
+pub const MIN_RENDER_SCALE: u32 = 50;
+/// In percent.
+pub const MAX_RENDER_SCALE: u32 = 200;
+/// In percent.
+pub const RENDER_SCALE_STEP: u32 = 10;
+/// The scale only rises while the frames take less than this fraction of the budget.
+const HEADROOM: f64 = 0.75;
+/// The number of frames that are averaged before each decision.
+const SAMPLE_FRAMES: usize = 30;
+
+/// Steps `scale` by `steps` times [RENDER_SCALE_STEP], within `MIN_RENDER_SCALE..=MAX_RENDER_SCALE`.
+pub fn step_render_scale(scale: u32, steps: i32) -> u32 {
+    let scale = scale as i64 + steps as i64 * RENDER_SCALE_STEP as i64;
+    scale.clamp(MIN_RENDER_SCALE as i64, MAX_RENDER_SCALE as i64) as u32
+}
+
+#[derive(Debug, Clone)]
+pub struct DynamicRenderScale {
+    pub enabled: bool,
+    pub target_fps: f64,
+    frame_times: AverageBuffer<f64>,
+}
+
+impl DynamicRenderScale {
+    pub fn new(target_fps: f64) -> Self {
+        Self {
+            enabled: false,
+            target_fps,
+            frame_times: AverageBuffer::new(SAMPLE_FRAMES, None),
+        }
+    }
+
+    /// The time in seconds that a frame may take at the target framerate.
+    pub fn budget(&self) -> f64 {
+        1.0 / self.target_fps.max(1.0)
+    }
+
+    /// Forgets the frame times, for example after the scale was changed by hand.
+    pub fn reset(&mut self) {
+        self.frame_times.clear();
+    }
+
+    /// Adds the work time of a frame that was rendered at `scale`. Returns the new scale when it should
+    /// change.
+    pub fn update(&mut self, scale: u32, frame_time: Duration) -> Option<u32> {
+        if !self.enabled {
+            return None;
+        }
+        let average = self.frame_times.push(frame_time.as_secs_f64());
+        if !self.frame_times.is_full() {
+            return None;
+        }
+        let budget = self.budget();
+        let new_scale = if average > budget {
+            step_render_scale(scale, -1)
+        } else if average < budget * HEADROOM {
+            step_render_scale(scale, 1)
+        } else {
+            scale
+        };
+        if new_scale == scale {
+            return None;
+        }
+        self.frame_times.clear();
+        Some(new_scale)
+    }
+}
+
+impl Default for DynamicRenderScale {
+    fn default() -> Self {
+        Self::new(60.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_scale_test() {
+        assert_eq!(step_render_scale(100, 1), 110);
+        assert_eq!(step_render_scale(55, -1), MIN_RENDER_SCALE);
+        assert_eq!(step_render_scale(MAX_RENDER_SCALE, 3), MAX_RENDER_SCALE);
+
+        let mut dynamic = DynamicRenderScale::new(60.0);
+        let slow = Duration::from_millis(25);
+        assert_eq!(dynamic.update(100, slow), None);
+        dynamic.enabled = true;
+        for _ in 1..SAMPLE_FRAMES {
+            assert_eq!(dynamic.update(100, slow), None);
+        }
+        assert_eq!(dynamic.update(100, slow), Some(90));
+        // Waits for a full window after a change.
+        let fast = Duration::from_millis(5);
+        for _ in 1..SAMPLE_FRAMES {
+            assert_eq!(dynamic.update(90, fast), None);
+        }
+        assert_eq!(dynamic.update(90, fast), Some(100));
+        // Inside the band between the headroom and the budget, the scale holds.
+        let steady = Duration::from_millis(15);
+        for _ in 0..SAMPLE_FRAMES * 2 {
+            assert_eq!(dynamic.update(100, steady), None);
+        }
+    }
+}
@@ -1,18 +1,37 @@
+use super::{bindings::{BindGroupBuilder, Bindings}, memory::{self, MemoryCategory, Tracked}};
 
+/// A texture that can be rendered to and then sampled in a fragment shader.
 pub struct RenderTexture {
-    texture: wgpu::Texture,
+    texture: Tracked<wgpu::Texture>,
+    view: wgpu::TextureView,
     sampler: wgpu::Sampler,
     binding: RenderTextureBinding,
 }
 
 impl RenderTexture {
+    /// `category` is what the texture counts towards in [memory::gpu_memory].
     pub fn new(
         device: &wgpu::Device,
         width: u32,
         height: u32,
         format: wgpu::TextureFormat,
+        category: MemoryCategory,
     ) -> Self {
-        let texture = device.create_texture(&wgpu::TextureDescriptor {
+        let layout = RenderTextureBinding::create_layout(device);
+        Self::with_layout(device, width, height, format, &layout, category)
+    }
+
+    /// Creates the render texture with a shared bind group layout (see [RenderTextureBinding::create_layout]),
+    /// so that the bind groups of several render textures can be used with the same pipeline.
+    pub fn with_layout(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        layout: &wgpu::BindGroupLayout,
+        category: MemoryCategory,
+    ) -> Self {
+        let texture = memory::track(category, device.create_texture(&wgpu::TextureDescriptor {
             label: Some("Render Texture"),
             size: wgpu::Extent3d {
                 width,
@@ -25,7 +44,7 @@ impl RenderTexture {
             format,
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
             view_formats: &[],
-        });
+        }));
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             label: Some("Render Texture Sampler"),
             address_mode_u: wgpu::AddressMode::ClampToEdge,
@@ -46,64 +65,72 @@ impl RenderTexture {
             array_layer_count: None,
             ..Default::default()
         });
-        let binding = RenderTextureBinding::new(device, &view, &sampler);
+        let binding = RenderTextureBinding::new(device, layout, &view, &sampler);
         Self {
             texture,
+            view,
             sampler,
             binding,
         }
     }
+
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn width(&self) -> u32 {
+        self.texture.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.texture.height()
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.texture.format()
+    }
+
+    pub fn binding(&self) -> &RenderTextureBinding {
+        &self.binding
+    }
+
+    pub fn bind(&self, index: u32, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_bind_group(index, &self.binding.group, &[]);
+    }
 }
 
 pub struct RenderTextureBinding {
-    layout: wgpu::BindGroupLayout,
-    group: wgpu::BindGroup,
+    pub layout: wgpu::BindGroupLayout,
+    pub group: wgpu::BindGroup,
 }
 
 impl RenderTextureBinding {
+    /// The texture is at binding 0 and the sampler at binding 1. Both can also be used in compute shaders.
+    pub fn create_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        BindGroupBuilder::new()
+            .label("Render Texture Bind Group Layout")
+            .texture_2d(0, wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE)
+            .sampler(1, wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE)
+            .build(device)
+    }
+
     fn new(
         device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
         view: &wgpu::TextureView,
         sampler: &wgpu::Sampler,
     ) -> Self {
-        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Render Texture Bind Group Layout"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        multisampled: false,
-                    },
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    count: None,
-                },
-            ]
-        });
-        let group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Render Texture Bind Group"),
-            layout: &layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(sampler),
-                },
-            ]
-        });
+        let group = Bindings::new()
+            .texture_view(0, view)
+            .sampler(1, sampler)
+            .build(device, Some("Render Texture Bind Group"), layout);
         Self {
-            layout,
-            group
+            layout: layout.clone(),
+            group,
         }
     }
-}
\ No newline at end of file
+}
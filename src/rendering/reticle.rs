@@ -1,85 +1,121 @@
-use std::path::Path;
+use std::time::Duration;
 
-use image::GenericImageView;
+use bytemuck::{Pod, Zeroable};
+use glam::{vec4, Vec4};
 
-#[derive(Debug, thiserror::Error)]
-pub enum ReticleError {
-    #[error("IO Error: {0}")]
-    IoError(#[from] std::io::Error),
-    #[error("Failed to load image: {0}")]
-    FailedToLoadImage(#[from] image::ImageError),
+use crate::animation::{animator::Animator, tween};
+
+use super::{bindings::{BindGroupBuilder, Bindings}, buffers::UniformBuffer, msaa::multisample_state};
+
+/*
+The reticle is a textured quad in the center of the screen. It has a number of styles (one texture each),
+a size in logical pixels that is multiplied by the window's scale factor so that it looks the same on
+every display, and a tint that the texture is multiplied with. [Reticle::pulse] briefly scales it up as
+feedback for block edits.
+*/
+
+/// The size of the reticle in logical pixels, before it is changed with [Reticle::set_settings].
+pub const DEFAULT_RETICLE_SIZE: f32 = 72.0;
+pub const MIN_RETICLE_SIZE: f32 = 16.0;
+pub const MAX_RETICLE_SIZE: f32 = 256.0;
+/// How much larger the reticle is at the start of a pulse.
+const PULSE_SCALE: f32 = 1.3;
+const PULSE_DURATION: Duration = Duration::from_millis(180);
+
+/// Tints to cycle through, from the default (the texture's own colors) on.
+pub const RETICLE_TINTS: [Vec4; 5] = [
+    vec4(1.0, 1.0, 1.0, 1.0),
+    vec4(1.0, 0.9, 0.2, 1.0),
+    vec4(0.3, 1.0, 0.4, 1.0),
+    vec4(0.3, 0.9, 1.0, 1.0),
+    vec4(1.0, 0.3, 0.3, 1.0),
+];
+
+/// The parts of the reticle that the user can change. These carry over when the reticle is recreated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReticleSettings {
+    /// The index of the style, in the order that the textures were passed to [Reticle::new].
+    pub style: usize,
+    /// In logical pixels.
+    pub size: f32,
+    /// A linear color that the texture is multiplied with.
+    pub tint: Vec4,
 }
 
-pub struct Reticle {
-    texture: wgpu::Texture,
-    sampler: wgpu::Sampler,
-    ortho_buffer: wgpu::Buffer,
-    dimensions_buffer: wgpu::Buffer,
-    bind_group_layout: wgpu::BindGroupLayout,
+impl Default for ReticleSettings {
+    fn default() -> Self {
+        Self {
+            style: 0,
+            size: DEFAULT_RETICLE_SIZE,
+            tint: Vec4::ONE,
+        }
+    }
+}
+
+/// `ReticleParams` in reticle.wgsl.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+struct GpuReticleParams {
+    tint: [f32; 4],
+    /// In physical pixels.
+    size: f32,
+    _padding: [f32; 3],
+}
+
+/// The pulse of [Reticle::pulse], which scales the reticle's size.
+#[derive(Debug, Clone, Copy, Default)]
+struct Pulse {
+    animator: Option<Animator<f32>>,
+}
+
+impl Pulse {
+    fn start(&mut self) {
+        self.animator = Some(Animator::new(PULSE_SCALE, 1.0, PULSE_DURATION).with_easing(tween::f32::quadratic_out));
+    }
+
+    /// `1.0` outside of a pulse.
+    fn scale(&self) -> f32 {
+        self.animator.as_ref().map_or(1.0, Animator::value)
+    }
+
+    /// Advances the animation, and returns whether the scale changed.
+    fn update(&mut self, delta_time: Duration) -> bool {
+        let Some(animator) = &mut self.animator else {
+            return false;
+        };
+        animator.update(delta_time);
+        if animator.is_finished() {
+            self.animator = None;
+        }
+        true
+    }
+}
+
+struct ReticleStyle {
+    name: String,
     bind_group: wgpu::BindGroup,
+}
+
+pub struct Reticle {
+    ortho_buffer: UniformBuffer<glam::Mat4>,
+    dimensions_buffer: UniformBuffer<[f32; 2]>,
+    params_buffer: UniformBuffer<GpuReticleParams>,
+    styles: Vec<ReticleStyle>,
     render_pipeline: wgpu::RenderPipeline,
+    settings: ReticleSettings,
+    scale_factor: f32,
+    pulse: Pulse,
 }
 
 impl Reticle {
-    pub fn new<P: AsRef<Path>>(
+    /// `styles` are the names and textures of the reticle styles (for example textures from an
+    /// [crate::assets::AssetServer]). The reticle is drawn as a square in the center of the screen.
+    pub fn new(
         device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        path: P,
+        styles: &[(&str, &wgpu::TextureView)],
         surface_config: &wgpu::SurfaceConfiguration,
-    ) -> Result<Self, ReticleError> {
-        // Texture Size: 72x72
-        //   Half Width: 36x36
-        let reticle_image = image::open(path)?;
-        let (width, height) = reticle_image.dimensions();
-        let reticle_image = reticle_image.to_rgba8();
-        let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Reticle Texture"),
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            mip_level_count: 1,
-            sample_count: 1,
-            size: wgpu::Extent3d {
-                depth_or_array_layers: 1,
-                width,
-                height,
-            },
-            dimension: wgpu::TextureDimension::D2,
-            view_formats: &[],
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-        });
-
-        queue.write_texture(
-            wgpu::TexelCopyTextureInfoBase {
-                texture: &texture,
-                aspect: wgpu::TextureAspect::All,
-                mip_level: 0,
-                origin: wgpu::Origin3d {
-                    x: 0,
-                    y: 0,
-                    z: 0,
-                }
-            },
-            &reticle_image,
-            wgpu::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(width * 4),
-                rows_per_image: Some(height),
-            },
-            wgpu::Extent3d {
-                width,
-                height,
-                depth_or_array_layers: 1,
-            }
-        );
-
-        let view = texture.create_view(&wgpu::TextureViewDescriptor {
-            label: Some("Reticle Texture View"),
-            format: Some(wgpu::TextureFormat::Rgba8UnormSrgb),
-            aspect: wgpu::TextureAspect::All,
-            base_array_layer: 0,
-            base_mip_level: 0,
-            ..Default::default()
-        });
-
+        sample_count: u32,
+    ) -> Self {
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             label: Some("Reticle Texture Sampler"),
             address_mode_u: wgpu::AddressMode::ClampToEdge,
@@ -91,84 +127,32 @@ impl Reticle {
             ..Default::default()
         });
 
-        let ortho_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Reticle Ortho Matrix Buffer"),
-            mapped_at_creation: false,
-            size: 4*16, // Size of 4x4 Matrix.
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
+        let ortho_buffer = UniformBuffer::new(device, Some("Reticle Ortho Matrix Buffer"), glam::Mat4::ZERO);
 
-        let dimensions_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Reticle Dimensions Buffer"),
-            mapped_at_creation: false,
-            size: 8,
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
+        let dimensions_buffer = UniformBuffer::new(device, Some("Reticle Dimensions Buffer"), [0.0f32; 2]);
 
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Reticle Bind Group Layout"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    count: None,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                        ty: wgpu::BufferBindingType::Uniform,
-                    }
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    count: None,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        multisampled: false,
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                    }
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 2,
-                    count: None,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 3,
-                    count: None,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                        ty: wgpu::BufferBindingType::Uniform,
-                    }
-                }
-            ]
-        });
+        let settings = ReticleSettings::default();
+        let params_buffer = UniformBuffer::new(device, Some("Reticle Params Buffer"), gpu_params(&settings, 1.0));
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Reticle Bind Group"),
-            layout: &bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: ortho_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::TextureView(&view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: wgpu::BindingResource::Sampler(&sampler),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 3,
-                    resource: dimensions_buffer.as_entire_binding(),
-                }
-            ]
-        });
+        let bind_group_layout = BindGroupBuilder::new()
+            .label("Reticle Bind Group Layout")
+            .uniform(0, wgpu::ShaderStages::VERTEX)
+            .texture_2d(1, wgpu::ShaderStages::FRAGMENT)
+            .sampler(2, wgpu::ShaderStages::FRAGMENT)
+            .uniform(3, wgpu::ShaderStages::VERTEX)
+            .uniform(4, wgpu::ShaderStages::VERTEX_FRAGMENT)
+            .build(device);
+
+        let styles = styles.iter().map(|&(name, view)| ReticleStyle {
+            name: name.to_owned(),
+            bind_group: Bindings::new()
+                .buffer(0, ortho_buffer.buffer())
+                .texture_view(1, view)
+                .sampler(2, &sampler)
+                .buffer(3, dimensions_buffer.buffer())
+                .buffer(4, params_buffer.buffer())
+                .build(device, Some("Reticle Bind Group"), &bind_group_layout),
+        }).collect();
 
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Reticle Render Pipeline Layout"),
@@ -210,44 +194,141 @@ impl Reticle {
                 conservative: false,
                 unclipped_depth: false,
             },
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
+            multisample: multisample_state(sample_count),
             multiview: None,
         });
 
-        Ok(Self {
-            texture,
-            sampler,
+        Self {
             ortho_buffer,
             dimensions_buffer,
-            bind_group_layout,
-            bind_group,
+            params_buffer,
+            styles,
             render_pipeline,
-        })
+            settings,
+            scale_factor: 1.0,
+            pulse: Pulse::default(),
+        }
+    }
+
+    pub fn settings(&self) -> ReticleSettings {
+        self.settings
+    }
+
+    /// Styles past the last one wrap around, and the size is clamped to `MIN_RETICLE_SIZE..=MAX_RETICLE_SIZE`.
+    pub fn set_settings(&mut self, queue: &wgpu::Queue, settings: ReticleSettings) {
+        self.settings = ReticleSettings {
+            style: settings.style.checked_rem(self.styles.len()).unwrap_or(0),
+            size: settings.size.clamp(MIN_RETICLE_SIZE, MAX_RETICLE_SIZE),
+            tint: settings.tint,
+        };
+        self.write_params(queue);
+    }
+
+    pub fn style_count(&self) -> usize {
+        self.styles.len()
+    }
+
+    pub fn style_name(&self) -> &str {
+        self.styles.get(self.settings.style).map_or("None", |style| style.name.as_str())
+    }
+
+    /// Switches to the next style, and returns its name.
+    pub fn next_style(&mut self, queue: &wgpu::Queue) -> &str {
+        self.set_settings(queue, ReticleSettings { style: self.settings.style + 1, ..self.settings });
+        self.style_name()
+    }
+
+    /// The ratio of physical to logical pixels, such as [winit::window::Window::scale_factor].
+    pub fn set_scale_factor(&mut self, queue: &wgpu::Queue, scale_factor: f32) {
+        if self.scale_factor != scale_factor {
+            self.scale_factor = scale_factor;
+            self.write_params(queue);
+        }
+    }
+
+    /// Briefly scales the reticle up, for example when a block is placed or removed.
+    pub fn pulse(&mut self) {
+        self.pulse.start();
+    }
+
+    /// The size that the reticle is drawn at right now, in physical pixels.
+    pub fn pixel_size(&self) -> f32 {
+        self.settings.size * self.scale_factor * self.pulse.scale()
+    }
+
+    /// Advances the pulse animation.
+    pub fn update(&mut self, queue: &wgpu::Queue, delta_time: Duration) {
+        if self.pulse.update(delta_time) {
+            self.write_params(queue);
+        }
+    }
+
+    fn write_params(&self, queue: &wgpu::Queue) {
+        let params = gpu_params(&self.settings, self.pixel_size() / self.settings.size);
+        if params != self.params_buffer.get() {
+            self.params_buffer.write(queue, params);
+        }
     }
 
     #[inline]
     pub fn write_dimensions(&self, queue: &wgpu::Queue, width: u32, height: u32) {
-        let dimensions = [width as f32, height as f32];
-        queue.write_buffer(&self.dimensions_buffer, 0, bytemuck::cast_slice(&dimensions));
+        self.dimensions_buffer.write(queue, [width as f32, height as f32]);
     }
 
     #[inline]
     pub fn write_ortho(&self, queue: &wgpu::Queue, ortho: &glam::Mat4) {
-        queue.write_buffer(&self.ortho_buffer, 0, bytemuck::bytes_of(ortho));
+        self.ortho_buffer.write(queue, *ortho);
     }
 
     #[inline]
     pub fn bind(&self, index: u32, render_pass: &mut wgpu::RenderPass) {
-        render_pass.set_bind_group(index, &self.bind_group, &[]);
+        if let Some(style) = self.styles.get(self.settings.style) {
+            render_pass.set_bind_group(index, &style.bind_group, &[]);
+        }
     }
 
     pub fn render(&self, render_pass: &mut wgpu::RenderPass) {
+        if self.styles.is_empty() {
+            return;
+        }
         render_pass.set_pipeline(&self.render_pipeline);
         self.bind(0, render_pass);
         render_pass.draw(0..6, 0..1);
     }
+}
+
+/// `scale` is how much larger than `settings.size` (in physical pixels) the reticle is drawn.
+fn gpu_params(settings: &ReticleSettings, scale: f32) -> GpuReticleParams {
+    GpuReticleParams {
+        tint: settings.tint.to_array(),
+        size: settings.size * scale,
+        _padding: [0.0; 3],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pulse_test() {
+        let settings = ReticleSettings::default();
+        assert_eq!(gpu_params(&settings, 2.0).size, DEFAULT_RETICLE_SIZE * 2.0);
+        assert_eq!(std::mem::size_of::<GpuReticleParams>(), 32);
+
+        let mut pulse = Pulse::default();
+        assert_eq!(pulse.scale(), 1.0);
+        assert!(!pulse.update(PULSE_DURATION));
+        // The pulse starts large and eases back to the normal size.
+        pulse.start();
+        assert_eq!(pulse.scale(), PULSE_SCALE);
+        assert!(pulse.update(PULSE_DURATION / 2));
+        let halfway = pulse.scale();
+        // Ease out, so more than half of the way back after half of the time.
+        assert!(halfway < 1.0 + (PULSE_SCALE - 1.0) * 0.5 && halfway > 1.0);
+        // The last update still changes the scale, back to 1.0. After that the pulse is over.
+        assert!(pulse.update(PULSE_DURATION));
+        assert_eq!(pulse.scale(), 1.0);
+        assert!(!pulse.update(PULSE_DURATION));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,171 @@
+/*
+Sampler settings for texture owners that let the sampler be switched at runtime (such as
+[super::texture_array::TextureArray]). Owners keep their bind group layout when the sampler changes,
+so only the bind group has to be recreated and pipelines stay valid.
+
+Anisotropic filtering needs linear filtering for magnification, minification and mipmaps, and a
+device with [wgpu::DownlevelFlags::ANISOTROPIC_FILTERING]. [SamplerConfig::validated] falls back to
+what the device can do.
+*/
+
+/// The largest anisotropy clamp that wgpu accepts.
+pub const MAX_ANISOTROPY: u16 = 16;
+
+/// The filtering presets that can be cycled through in the settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFiltering {
+    /// Sharp texels with blended mip levels.
+    Nearest,
+    Bilinear,
+    Trilinear,
+    /// Trilinear with the given anisotropy clamp (2, 4, 8 or 16).
+    Anisotropic(u16),
+}
+
+impl TextureFiltering {
+    pub const ALL: [Self; 7] = [
+        Self::Nearest,
+        Self::Bilinear,
+        Self::Trilinear,
+        Self::Anisotropic(2),
+        Self::Anisotropic(4),
+        Self::Anisotropic(8),
+        Self::Anisotropic(16),
+    ];
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&filtering| filtering == self).map_or(0, |index| index + 1);
+        Self::ALL[index % Self::ALL.len()]
+    }
+
+    pub fn name(self) -> String {
+        match self {
+            Self::Nearest => String::from("Nearest"),
+            Self::Bilinear => String::from("Bilinear"),
+            Self::Trilinear => String::from("Trilinear"),
+            Self::Anisotropic(clamp) => format!("Anisotropic {clamp}x"),
+        }
+    }
+
+    /// Parses a [TextureFiltering::name].
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|filtering| filtering.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerConfig {
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    pub address_mode_w: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    /// `1` turns anisotropic filtering off.
+    pub anisotropy_clamp: u16,
+}
+
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        Self::NEAREST
+    }
+}
+
+impl SamplerConfig {
+    /// Sharp texels with blended mip levels, the look of the voxel textures.
+    pub const NEAREST: Self = Self {
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Nearest,
+        min_filter: wgpu::FilterMode::Nearest,
+        mipmap_filter: wgpu::FilterMode::Linear,
+        anisotropy_clamp: 1,
+    };
+
+    pub const LINEAR: Self = Self {
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        mipmap_filter: wgpu::FilterMode::Linear,
+        ..Self::NEAREST
+    };
+
+    pub const fn with_address_modes(mut self, u: wgpu::AddressMode, v: wgpu::AddressMode) -> Self {
+        self.address_mode_u = u;
+        self.address_mode_v = v;
+        self
+    }
+
+    /// Sets the filter modes and anisotropy clamp to those of the preset. Address modes are kept.
+    pub const fn with_filtering(mut self, filtering: TextureFiltering) -> Self {
+        use wgpu::FilterMode::{Linear, Nearest};
+        (self.mag_filter, self.min_filter, self.mipmap_filter, self.anisotropy_clamp) = match filtering {
+            TextureFiltering::Nearest => (Nearest, Nearest, Linear, 1),
+            TextureFiltering::Bilinear => (Linear, Linear, Nearest, 1),
+            TextureFiltering::Trilinear => (Linear, Linear, Linear, 1),
+            TextureFiltering::Anisotropic(clamp) => (Linear, Linear, Linear, clamp),
+        };
+        self
+    }
+
+    /// The preset that matches the filter modes and anisotropy clamp, if there is one.
+    pub fn filtering(&self) -> Option<TextureFiltering> {
+        TextureFiltering::ALL.into_iter().find(|&filtering| self.with_filtering(filtering) == *self)
+    }
+
+    /// Turns anisotropic filtering off if the device doesn't support it or the filter modes aren't all linear,
+    /// and clamps it to [MAX_ANISOTROPY].
+    pub fn validated(mut self, downlevel: &wgpu::DownlevelCapabilities) -> Self {
+        let all_linear = [self.mag_filter, self.min_filter, self.mipmap_filter]
+            .iter()
+            .all(|&filter| filter == wgpu::FilterMode::Linear);
+        if !all_linear || !downlevel.flags.contains(wgpu::DownlevelFlags::ANISOTROPIC_FILTERING) {
+            self.anisotropy_clamp = 1;
+        }
+        self.anisotropy_clamp = self.anisotropy_clamp.clamp(1, MAX_ANISOTROPY);
+        self
+    }
+
+    /// Creates the sampler. The config should be [SamplerConfig::validated] first.
+    pub fn create(&self, device: &wgpu::Device, label: Option<&str>) -> wgpu::Sampler {
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label,
+            address_mode_u: self.address_mode_u,
+            address_mode_v: self.address_mode_v,
+            address_mode_w: self.address_mode_w,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            anisotropy_clamp: self.anisotropy_clamp,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampler_config_test() {
+        let supported = wgpu::DownlevelCapabilities::default();
+        let unsupported = wgpu::DownlevelCapabilities {
+            flags: wgpu::DownlevelFlags::empty(),
+            ..Default::default()
+        };
+        let anisotropic = SamplerConfig::NEAREST.with_filtering(TextureFiltering::Anisotropic(16));
+        assert_eq!(anisotropic.validated(&supported).anisotropy_clamp, 16);
+        assert_eq!(anisotropic.validated(&unsupported).anisotropy_clamp, 1);
+        let nearest_mag = SamplerConfig { mag_filter: wgpu::FilterMode::Nearest, ..anisotropic };
+        assert_eq!(nearest_mag.validated(&supported).anisotropy_clamp, 1);
+        let too_high = SamplerConfig { anisotropy_clamp: 64, ..anisotropic };
+        assert_eq!(too_high.validated(&supported).anisotropy_clamp, MAX_ANISOTROPY);
+
+        assert_eq!(anisotropic.filtering(), Some(TextureFiltering::Anisotropic(16)));
+        assert_eq!(SamplerConfig::NEAREST.filtering(), Some(TextureFiltering::Nearest));
+        for filtering in TextureFiltering::ALL {
+            assert_eq!(TextureFiltering::from_name(&filtering.name()), Some(filtering));
+        }
+        assert_eq!(TextureFiltering::Anisotropic(16).next(), TextureFiltering::Nearest);
+    }
+}
@@ -0,0 +1,168 @@
+use std::{fmt, path::Path, sync::{Arc, Mutex}};
+
+/*
+wgpu panics on any error that isn't caught by an error scope. [GpuErrors::install] replaces that
+handler so that errors are collected and shown on the overlay instead of bringing the app down.
+
+Shaders that are loaded at runtime are compiled with [create_shader_module] inside of an error scope,
+so a broken shader is reported with its file and line, and the caller can keep using the pipeline
+that it already has.
+*/
+
+/// The most errors that [GpuErrors] keeps. Older errors are dropped first.
+pub const MAX_GPU_ERRORS: usize = 16;
+
+/// A compilation message for a shader.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderDiagnostic {
+    pub file: String,
+    /// 1-based.
+    pub line: Option<u32>,
+    /// 1-based, in bytes.
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+impl fmt::Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(f, "{}:{line}:{column}: {}", self.file, self.message),
+            (Some(line), None) => write!(f, "{}:{line}: {}", self.file, self.message),
+            _ => write!(f, "{}: {}", self.file, self.message),
+        }
+    }
+}
+
+fn join_diagnostics(diagnostics: &[ShaderDiagnostic]) -> String {
+    diagnostics.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n")
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ShaderError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{}", join_diagnostics(.0))]
+    Compile(Vec<ShaderDiagnostic>),
+    #[error("{0}")]
+    Validation(String),
+}
+
+impl ShaderError {
+    /// One line per message.
+    pub fn lines(&self) -> Vec<String> {
+        match self {
+            ShaderError::Compile(diagnostics) => diagnostics.iter().map(ToString::to_string).collect(),
+            err => err.to_string().lines().map(str::to_owned).collect(),
+        }
+    }
+}
+
+/// Collects uncaptured wgpu errors. Clones share the same list.
+#[derive(Debug, Clone, Default)]
+pub struct GpuErrors {
+    errors: Arc<Mutex<Vec<String>>>,
+}
+
+impl GpuErrors {
+    /// Sends the device's uncaptured errors here instead of panicking.
+    pub fn install(&self, device: &wgpu::Device) {
+        let errors = self.clone();
+        device.on_uncaptured_error(Box::new(move |err| {
+            log::error!("Uncaptured wgpu error: {err}");
+            errors.push(err.to_string());
+        }));
+    }
+
+    pub fn push<S: Into<String>>(&self, message: S) {
+        let mut errors = self.errors.lock().unwrap();
+        if errors.len() >= MAX_GPU_ERRORS {
+            errors.remove(0);
+        }
+        errors.push(message.into());
+    }
+
+    /// The errors, oldest first.
+    pub fn errors(&self) -> Vec<String> {
+        self.errors.lock().unwrap().clone()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.lock().unwrap().is_empty()
+    }
+
+    pub fn clear(&self) {
+        self.errors.lock().unwrap().clear();
+    }
+}
+
+/// Runs `f` inside of a validation error scope. Returns the error if there was one.
+pub fn validation_scope<T, F: FnOnce() -> T>(device: &wgpu::Device, f: F) -> Result<T, ShaderError> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let value = f();
+    match pollster::block_on(device.pop_error_scope()) {
+        Some(err) => Err(ShaderError::Validation(err.to_string())),
+        None => Ok(value),
+    }
+}
+
+/// Compiles WGSL, returning the compilation errors instead of an invalid module. `file` is only used
+/// for the diagnostics.
+pub fn create_shader_module(device: &wgpu::Device, file: &str, source: &str) -> Result<wgpu::ShaderModule, ShaderError> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(file),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+    let info = pollster::block_on(module.get_compilation_info());
+    let scope_error = pollster::block_on(device.pop_error_scope());
+    let diagnostics = info.messages.into_iter()
+        .filter(|message| message.message_type == wgpu::CompilationMessageType::Error)
+        .map(|message| ShaderDiagnostic {
+            file: file.to_owned(),
+            line: message.location.map(|location| location.line_number),
+            column: message.location.map(|location| location.line_position),
+            message: message.message,
+        })
+        .collect::<Vec<_>>();
+    match scope_error {
+        None if diagnostics.is_empty() => Ok(module),
+        Some(err) if diagnostics.is_empty() => Err(ShaderError::Validation(err.to_string())),
+        _ => Err(ShaderError::Compile(diagnostics)),
+    }
+}
+
+/// Reads and compiles a WGSL file, see [create_shader_module].
+pub fn load_shader_module<P: AsRef<Path>>(device: &wgpu::Device, path: P) -> Result<wgpu::ShaderModule, ShaderError> {
+    let path = path.as_ref();
+    let source = std::fs::read_to_string(path)?;
+    create_shader_module(device, &path.display().to_string(), &source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shader_errors_test() {
+        let diagnostic = ShaderDiagnostic {
+            file: String::from("shaders/raytrace.wgsl"),
+            line: Some(12),
+            column: Some(5),
+            message: String::from("expected ';'"),
+        };
+        assert_eq!(diagnostic.to_string(), "shaders/raytrace.wgsl:12:5: expected ';'");
+        let error = ShaderError::Compile(vec![diagnostic.clone(), ShaderDiagnostic { line: None, column: None, ..diagnostic }]);
+        assert_eq!(error.lines(), ["shaders/raytrace.wgsl:12:5: expected ';'", "shaders/raytrace.wgsl: expected ';'"]);
+
+        let errors = GpuErrors::default();
+        let shared = errors.clone();
+        for index in 0..MAX_GPU_ERRORS + 2 {
+            shared.push(format!("error {index}"));
+        }
+        let list = errors.errors();
+        assert_eq!(list.len(), MAX_GPU_ERRORS);
+        assert_eq!(list[0], "error 2");
+        errors.clear();
+        assert!(shared.is_empty());
+    }
+}
@@ -2,13 +2,13 @@ use std::path::Path;
 
 use std::sync::Arc;
 
-use glam::{vec2, vec3, Vec3};
-use image::GenericImageView;
+use glam::{vec2, vec3, Vec3, Vec4};
+use image::{Rgba32FImage, RgbaImage};
 use wgpu::util::DeviceExt;
 
 use crate::{modeling::modeler::{Modeler, PosUV}, voxel::vertex::Vertex};
 
-use super::transforms::TransformsBindGroup;
+use super::{bindings::{BindGroupBuilder, Bindings}, memory::{self, MemoryCategory, Tracked}, mipmaps::{full_mip_level_count, MipmapGenerator}, msaa::multisample_state, sampler::SamplerConfig, transforms::TransformsBindGroup};
 
 #[derive(Debug, thiserror::Error)]
 pub enum SkyboxErr {
@@ -21,7 +21,9 @@ pub enum SkyboxErr {
         side: &'static str,
         dimensions: (u32, u32),
         expected: (u32, u32),
-    }
+    },
+    #[error("The equirectangular image is empty.")]
+    EmptyEquirectangular,
 }
 
 #[derive(Debug, Clone)]
@@ -47,17 +49,30 @@ pub struct SkyboxTexturePaths<P: AsRef<Path>> {
     pub right: P,
 }
 
+/// Where [Skybox::new] loads the cubemap from.
+pub enum SkyboxSource<P: AsRef<Path>> {
+    /// Six face images, loaded with `format`.
+    Faces(SkyboxTexturePaths<P>),
+    /// A single equirectangular panorama (such as an `.hdr` HDRI), converted with
+    /// [SkyboxCubemap::load_equirectangular]. The `format` passed to [Skybox::new] is ignored.
+    Equirectangular(P),
+}
+
 #[derive(Debug, Clone)]
 pub struct SkyboxCubemap {
-    pub cubemap: wgpu::Texture,
+    /// Shared by the clones.
+    pub cubemap: Arc<Tracked<wgpu::Texture>>,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
+    pub sampler_config: SamplerConfig,
     pub format: wgpu::TextureFormat,
     pub dimensions: (u32, u32),
     pub binding: SkyboxCubemapBinding,
 }
 
 impl SkyboxCubemap {
+    /// The format of cubemaps converted from equirectangular images, which keeps the HDR range.
+    pub const EQUIRECTANGULAR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
 
     pub fn load<P: AsRef<std::path::Path>>(
         device: &wgpu::Device,
@@ -74,8 +89,26 @@ impl SkyboxCubemap {
             paths.front.as_ref(),
             paths.back.as_ref(),
         ];
-        let reader = image::ImageReader::open(paths[0])?;
-        let (width, height) = reader.into_dimensions()?;
+        let mut images = Vec::with_capacity(6);
+        for path in paths {
+            images.push(image::open(path)?.into_rgba8());
+        }
+        let [right, left, top, bottom, front, back] = images.as_slice() else {
+            unreachable!()
+        };
+        Self::from_images(device, queue, label, format, [right, left, top, bottom, front, back])
+    }
+
+    /// Creates the cubemap from images that have already been loaded.
+    /// The faces are in the order right, left, top, bottom, front, back.
+    pub fn from_images(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        label: Option<&str>,
+        format: wgpu::TextureFormat,
+        faces: [&RgbaImage; 6],
+    ) -> Result<Self, SkyboxErr> {
+        let (width, height) = faces[0].dimensions();
 
         let cubemap = device.create_texture(&wgpu::TextureDescriptor {
             label: Some("Skybox Cubemap Texture"),
@@ -84,11 +117,11 @@ impl SkyboxCubemap {
                 height,
                 depth_or_array_layers: 6,
             },
-            mip_level_count: 1,
+            mip_level_count: full_mip_level_count(width, height),
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::RENDER_ATTACHMENT,
             view_formats: &[],
         });
 
@@ -104,8 +137,7 @@ impl SkyboxCubemap {
         let bytes_per_row = Some(4 * width);
         let rows_per_image = Some(height);
 
-        for (i, img_path) in paths.into_iter().enumerate() {
-            let img = image::open(img_path)?;
+        for (i, img) in faces.into_iter().enumerate() {
             let (img_width, img_height) = img.dimensions();
             if (img_width, img_height) != (width, height) {
                 return Err(SkyboxErr::MismatchedDimensions {
@@ -114,7 +146,6 @@ impl SkyboxCubemap {
                     expected: (width, height)
                 });
             }
-            let img_rgba = img.to_rgba8();
 
             queue.write_texture(
                 wgpu::TexelCopyTextureInfoBase {
@@ -127,7 +158,7 @@ impl SkyboxCubemap {
                     },
                     aspect: wgpu::TextureAspect::All,
                 },
-                &img_rgba,
+                img,
                 wgpu::TexelCopyBufferLayout {
                     offset: 0,
                     bytes_per_row,
@@ -141,6 +172,162 @@ impl SkyboxCubemap {
             );
         }
 
+        MipmapGenerator::new(device).generate_and_submit(device, queue, &cubemap);
+
+        Ok(Self::from_texture(device, cubemap, label, format, (width, height)))
+    }
+
+    /// Loads an equirectangular panorama and converts it to a cubemap. HDR formats (`.hdr`, `.exr`)
+    /// keep their range. `face_size` defaults to a quarter of the image width.
+    pub fn load_equirectangular<P: AsRef<Path>>(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        label: Option<&str>,
+        path: P,
+        face_size: Option<u32>,
+    ) -> Result<Self, SkyboxErr> {
+        let image = image::open(path)?.into_rgba32f();
+        Self::from_equirectangular(device, queue, label, &image, face_size)
+    }
+
+    /// Converts an equirectangular panorama to a cubemap with [SkyboxCubemap::EQUIRECTANGULAR_FORMAT]
+    /// in a compute pass. Images larger than the device allows are downscaled first.
+    /// `face_size` defaults to a quarter of the image width.
+    pub fn from_equirectangular(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        label: Option<&str>,
+        image: &Rgba32FImage,
+        face_size: Option<u32>,
+    ) -> Result<Self, SkyboxErr> {
+        let (width, height) = image.dimensions();
+        if width == 0 || height == 0 {
+            return Err(SkyboxErr::EmptyEquirectangular);
+        }
+        let max_size = device.limits().max_texture_dimension_2d;
+        let resized;
+        let image = if width > max_size || height > max_size {
+            let scale = max_size as f32 / width.max(height) as f32;
+            let new_width = ((width as f32 * scale) as u32).max(1);
+            let new_height = ((height as f32 * scale) as u32).max(1);
+            resized = image::imageops::resize(image, new_width, new_height, image::imageops::FilterType::Triangle);
+            &resized
+        } else {
+            image
+        };
+        let (width, height) = image.dimensions();
+        let face_size = face_size.unwrap_or(width / 4).clamp(1, max_size);
+        let format = Self::EQUIRECTANGULAR_FORMAT;
+
+        let source = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Skybox Equirectangular Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            source.as_image_copy(),
+            bytemuck::cast_slice(image.as_raw()),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(16 * width),
+                rows_per_image: Some(height),
+            },
+            source.size(),
+        );
+
+        let cubemap = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Skybox Cubemap Texture"),
+            size: wgpu::Extent3d {
+                width: face_size,
+                height: face_size,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count: full_mip_level_count(face_size, face_size),
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        let source_view = source.create_view(&wgpu::TextureViewDescriptor::default());
+        // Storage views can only cover one mip level.
+        let faces_view = cubemap.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Skybox Cubemap Faces View"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            base_mip_level: 0,
+            mip_level_count: Some(1),
+            ..Default::default()
+        });
+
+        let bind_group_layout = BindGroupBuilder::new()
+            .label("Equirectangular To Cubemap Bind Group Layout")
+            .texture(
+                0,
+                wgpu::ShaderStages::COMPUTE,
+                wgpu::TextureSampleType::Float { filterable: false },
+                wgpu::TextureViewDimension::D2,
+            )
+            .storage_texture_2d_array(1, wgpu::ShaderStages::COMPUTE, wgpu::StorageTextureAccess::WriteOnly, format)
+            .build(device);
+        let bind_group = Bindings::new()
+            .texture_view(0, &source_view)
+            .texture_view(1, &faces_view)
+            .build(device, Some("Equirectangular To Cubemap Bind Group"), &bind_group_layout);
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../shaders/equirect_to_cube.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Equirectangular To Cubemap Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Equirectangular To Cubemap Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Equirectangular To Cubemap Encoder"),
+        });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Equirectangular To Cubemap Pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_pipeline(&pipeline);
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            let workgroups = face_size.div_ceil(8);
+            compute_pass.dispatch_workgroups(workgroups, workgroups, 6);
+        }
+        MipmapGenerator::new(device).generate(device, &mut encoder, &cubemap);
+        queue.submit(std::iter::once(encoder.finish()));
+
+        Ok(Self::from_texture(device, cubemap, label, format, (face_size, face_size)))
+    }
+
+    /// Creates the view, sampler and binding for a cubemap texture whose levels have been written.
+    fn from_texture(
+        device: &wgpu::Device,
+        cubemap: wgpu::Texture,
+        label: Option<&str>,
+        format: wgpu::TextureFormat,
+        dimensions: (u32, u32),
+    ) -> Self {
         let view = cubemap.create_view(&wgpu::TextureViewDescriptor {
             label,
             format: Some(format),
@@ -153,26 +340,32 @@ impl SkyboxCubemap {
             ..Default::default()
         });
 
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Linear,
-            ..Default::default()
-        });
+        let sampler_config = SamplerConfig::LINEAR;
+        let sampler = sampler_config.create(device, Some("Skybox Sampler"));
 
         let binding = SkyboxCubemapBinding::new(device, &view, &sampler);
 
-        Ok(Self {
-            cubemap,
+        Self {
+            cubemap: Arc::new(memory::track(MemoryCategory::Textures, cubemap)),
             view,
             sampler,
+            sampler_config,
             format,
-            dimensions: (width, height),
+            dimensions,
             binding,
-        })
+        }
+    }
+
+    /// Switches to a sampler created from `config` (which should be [SamplerConfig::validated]). The
+    /// layout is kept, but a [Skybox] holds its own copy of the cubemap, so it has to be recreated with
+    /// [Skybox::with_cubemap] to use the new sampler.
+    pub fn set_sampler(&mut self, device: &wgpu::Device, config: SamplerConfig) {
+        self.sampler = config.create(device, Some("Skybox Sampler"));
+        self.binding.group = Bindings::new()
+            .texture_view(0, &self.view)
+            .sampler(1, &self.sampler)
+            .build(device, Some("Skybox Cubemap Texture Bind Group"), &self.binding.layout);
+        self.sampler_config = config;
     }
 
     pub fn bind(&self, index: u32, render_pass: &mut wgpu::RenderPass) {
@@ -192,44 +385,16 @@ impl SkyboxCubemapBinding {
         view: &wgpu::TextureView,
         sampler: &wgpu::Sampler,
     ) -> Self {
-        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Skybox Cubemap Texture Bind Group Layout"),
-            entries: &[
-                // cubemap
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::Cube,
-                        multisampled: false,
-                    },
-                    count: None,
-                },
-                // sampler
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
-            ],
-        });
+        let layout = BindGroupBuilder::new()
+            .label("Skybox Cubemap Texture Bind Group Layout")
+            .texture_cube(0, wgpu::ShaderStages::FRAGMENT)
+            .sampler(1, wgpu::ShaderStages::FRAGMENT)
+            .build(device);
 
-        let group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Skybox Cubemap Texture Bind Group"),
-            layout: &layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(sampler),
-                },
-            ],
-        });
+        let group = Bindings::new()
+            .texture_view(0, view)
+            .sampler(1, sampler)
+            .build(device, Some("Skybox Cubemap Texture Bind Group"), &layout);
 
         Self {
             layout,
@@ -249,16 +414,32 @@ impl Skybox {
     pub const RIGHT_INDEX: u32 = 3;
     pub const FRONT_INDEX: u32 = 4;
     pub const BACK_INDEX: u32 = 5;
+    #[allow(clippy::too_many_arguments)]
     pub fn new<P: AsRef<Path>>(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        config: &wgpu::SurfaceConfiguration,
+        target_format: wgpu::TextureFormat,
+        sample_count: u32,
         label: Option<&str>,
         format: wgpu::TextureFormat,
         transforms: &TransformsBindGroup,
-        paths: &SkyboxTexturePaths<P>,
+        source: &SkyboxSource<P>,
     ) -> Result<Self, SkyboxErr> {
-        let cubemap = SkyboxCubemap::load(device, queue, label, format, paths)?;
+        let cubemap = match source {
+            SkyboxSource::Faces(paths) => SkyboxCubemap::load(device, queue, label, format, paths)?,
+            SkyboxSource::Equirectangular(path) => SkyboxCubemap::load_equirectangular(device, queue, label, path, None)?,
+        };
+        Ok(Self::with_cubemap(device, target_format, sample_count, transforms, cubemap))
+    }
+
+    /// `target_format` and `sample_count` are those of the pass that the skybox is rendered in.
+    pub fn with_cubemap(
+        device: &wgpu::Device,
+        target_format: wgpu::TextureFormat,
+        sample_count: u32,
+        transforms: &TransformsBindGroup,
+        cubemap: SkyboxCubemap,
+    ) -> Self {
         // top, bottom, left, right, front, back
         let mut m = Modeler::new();
         let quad = [
@@ -302,8 +483,8 @@ impl Skybox {
                 &cubemap.binding.layout,
             ],
             push_constant_ranges: &[wgpu::PushConstantRange {
-                range: 0..64,
-                stages: wgpu::ShaderStages::VERTEX,
+                range: 0..80,
+                stages: wgpu::ShaderStages::VERTEX_FRAGMENT,
             }],
         });
         let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
@@ -321,7 +502,7 @@ impl Skybox {
                 module: &shader,
                 entry_point: Some("fs_main"),
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
+                    format: target_format,
                     blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
@@ -344,16 +525,12 @@ impl Skybox {
             //     bias: wgpu::DepthBiasState::default(),
             // }),
             depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
+            multisample: multisample_state(sample_count),
             multiview: None,
             cache: None,
         });
 
-        Ok(Self {
+        Self {
             inner: Arc::new(SkyboxInner {
                 vertex_buffer,
                 index_buffer,
@@ -361,7 +538,11 @@ impl Skybox {
                 num_indices,
                 cubemap,
             })
-        })
+        }
+    }
+
+    pub fn cubemap(&self) -> &SkyboxCubemap {
+        &self.inner.cubemap
     }
 
     pub fn render(
@@ -369,15 +550,17 @@ impl Skybox {
         render_pass: &mut wgpu::RenderPass,
         transforms: &TransformsBindGroup,
         camera_position: Vec3,
+        tint: Vec4,
     ) {
         render_pass.set_pipeline(&self.inner.render_pipeline);
         render_pass.set_bind_group(0, &transforms.bind_group, &[]);
         self.inner.cubemap.bind(1, render_pass);
 
         render_pass.set_vertex_buffer(0, self.inner.vertex_buffer.slice(..));
-        render_pass.set_index_buffer(self.inner.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.set_index_buffer(self.inner.index_buffer.slice(..), Modeler::INDEX_FORMAT);
         let world = glam::Mat4::from_translation(camera_position);
-        render_pass.set_push_constants(wgpu::ShaderStages::VERTEX, 0, bytemuck::bytes_of(&world));
+        render_pass.set_push_constants(wgpu::ShaderStages::VERTEX_FRAGMENT, 0, bytemuck::bytes_of(&world));
+        render_pass.set_push_constants(wgpu::ShaderStages::VERTEX_FRAGMENT, 64, bytemuck::bytes_of(&tint));
         render_pass.draw_indexed(0..self.inner.num_indices, 0, 0..1);
     }
 }
\ No newline at end of file
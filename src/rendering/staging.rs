@@ -0,0 +1,216 @@
+use std::sync::mpsc;
+
+/*
+A ring of staging buffers for uploading data through the frame's command encoder instead of
+`queue.write_buffer`, which has to allocate (and copy into) a new staging buffer on every call.
+
+Each frame:
+    1. `write` copies data into a mapped staging buffer and records a copy into the target buffer.
+    2. `finish` unmaps the buffers that were written to. Call it before submitting the encoder.
+    3. `recall` maps them again after the submission. Once mapping finishes (when the device is polled),
+       the buffers are reused by later writes.
+
+Writes that don't fit into a free buffer allocate a new one, so the ring grows to the amount of data that
+is uploaded per frame. At most `max_free_buffers` idle buffers are kept around.
+*/
+
+/// The default size of each staging buffer, enough for a whole chunk (with its LODs).
+pub const DEFAULT_STAGING_BUFFER_SIZE: u64 = 2 * 1024 * 1024;
+pub const DEFAULT_MAX_FREE_STAGING_BUFFERS: usize = 4;
+
+struct StagingBuffer {
+    buffer: wgpu::Buffer,
+    /// The next free byte.
+    offset: u64,
+}
+
+impl StagingBuffer {
+    fn remaining(&self) -> u64 {
+        self.buffer.size() - self.offset
+    }
+}
+
+/// Upload metrics for the profiler overlay.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StagingStats {
+    /// The bytes that were written between the last two calls to [StagingRing::finish].
+    pub bytes_last_frame: u64,
+    /// Every byte that has been written.
+    pub total_bytes: u64,
+    /// Every staging buffer that currently exists, including those that are in flight.
+    pub buffer_count: usize,
+    /// Buffers that are waiting to be mapped again.
+    pub in_flight: usize,
+    /// The combined size of every staging buffer.
+    pub allocated_bytes: u64,
+}
+
+pub struct StagingRing {
+    buffer_size: u64,
+    max_free_buffers: usize,
+    /// Mapped buffers that are being written to this frame.
+    active: Vec<StagingBuffer>,
+    /// Unmapped buffers that are waiting for the submission.
+    closed: Vec<StagingBuffer>,
+    /// Mapped buffers that are ready to be written to.
+    free: Vec<StagingBuffer>,
+    sender: mpsc::Sender<StagingBuffer>,
+    receiver: mpsc::Receiver<StagingBuffer>,
+    bytes_this_frame: u64,
+    stats: StagingStats,
+}
+
+impl StagingRing {
+    /// `buffer_size` is the size of each staging buffer. Writes that are larger than that get a buffer of their own.
+    pub fn new(buffer_size: u64) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            buffer_size: buffer_size.max(wgpu::MAP_ALIGNMENT),
+            max_free_buffers: DEFAULT_MAX_FREE_STAGING_BUFFERS,
+            active: Vec::new(),
+            closed: Vec::new(),
+            free: Vec::new(),
+            sender,
+            receiver,
+            bytes_this_frame: 0,
+            stats: StagingStats::default(),
+        }
+    }
+
+    pub fn buffer_size(&self) -> u64 {
+        self.buffer_size
+    }
+
+    /// Only affects buffers that are created after this call.
+    pub fn set_buffer_size(&mut self, buffer_size: u64) {
+        self.buffer_size = buffer_size.max(wgpu::MAP_ALIGNMENT);
+    }
+
+    pub fn max_free_buffers(&self) -> usize {
+        self.max_free_buffers
+    }
+
+    /// Idle buffers beyond this are destroyed when they come back from the GPU.
+    pub fn set_max_free_buffers(&mut self, max_free_buffers: usize) {
+        self.max_free_buffers = max_free_buffers;
+        while self.free.len() > max_free_buffers {
+            if let Some(staging) = self.free.pop() {
+                self.destroy(staging);
+            }
+        }
+    }
+
+    pub fn stats(&self) -> StagingStats {
+        self.stats
+    }
+
+    /// Records a copy of `data` into `target` at `offset`. The length of `data` and `offset` must be
+    /// multiples of [wgpu::COPY_BUFFER_ALIGNMENT].
+    pub fn write(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+        data: &[u8],
+    ) {
+        let size = data.len() as u64;
+        assert_eq!(size % wgpu::COPY_BUFFER_ALIGNMENT, 0, "Staging writes must be aligned to COPY_BUFFER_ALIGNMENT.");
+        assert_eq!(offset % wgpu::COPY_BUFFER_ALIGNMENT, 0, "Staging writes must be aligned to COPY_BUFFER_ALIGNMENT.");
+        if size == 0 {
+            return;
+        }
+        let index = match self.active.iter().position(|staging| staging.remaining() >= size) {
+            Some(index) => index,
+            None => {
+                let staging = self.take_free(size).unwrap_or_else(|| self.create(device, size));
+                self.active.push(staging);
+                self.active.len() - 1
+            }
+        };
+        let staging = &mut self.active[index];
+        staging.buffer.slice(staging.offset..staging.offset + size)
+            .get_mapped_range_mut()
+            .copy_from_slice(data);
+        encoder.copy_buffer_to_buffer(&staging.buffer, staging.offset, target, offset, size);
+        staging.offset = (staging.offset + size).next_multiple_of(wgpu::MAP_ALIGNMENT);
+        self.bytes_this_frame += size;
+        self.stats.total_bytes += size;
+    }
+
+    /// Unmaps the buffers that were written to. Call this before the encoder that was written to is submitted.
+    pub fn finish(&mut self) {
+        for staging in self.active.drain(..) {
+            staging.buffer.unmap();
+            self.closed.push(staging);
+        }
+        self.stats.bytes_last_frame = std::mem::take(&mut self.bytes_this_frame);
+    }
+
+    /// Starts mapping the buffers from the last [StagingRing::finish] again. Call this after submitting.
+    pub fn recall(&mut self) {
+        self.receive();
+        for mut staging in self.closed.drain(..) {
+            staging.offset = 0;
+            let sender = self.sender.clone();
+            let buffer = staging.buffer.clone();
+            buffer.slice(..).map_async(wgpu::MapMode::Write, move |result| {
+                // If mapping fails (such as when the device is lost) the buffer is dropped.
+                if result.is_ok() {
+                    _ = sender.send(staging);
+                }
+            });
+            self.stats.in_flight += 1;
+        }
+    }
+
+    /// Collects the buffers that have finished mapping.
+    fn receive(&mut self) {
+        while let Ok(staging) = self.receiver.try_recv() {
+            self.stats.in_flight -= 1;
+            if self.free.len() < self.max_free_buffers {
+                self.free.push(staging);
+            } else {
+                self.destroy(staging);
+            }
+        }
+    }
+
+    /// Takes the smallest free buffer that can fit `size` bytes.
+    fn take_free(&mut self, size: u64) -> Option<StagingBuffer> {
+        self.receive();
+        let index = self.free.iter()
+            .enumerate()
+            .filter(|(_, staging)| staging.remaining() >= size)
+            .min_by_key(|(_, staging)| staging.buffer.size())
+            .map(|(index, _)| index)?;
+        Some(self.free.swap_remove(index))
+    }
+
+    fn create(&mut self, device: &wgpu::Device, size: u64) -> StagingBuffer {
+        let size = self.buffer_size.max(size.next_multiple_of(wgpu::MAP_ALIGNMENT));
+        self.stats.buffer_count += 1;
+        self.stats.allocated_bytes += size;
+        StagingBuffer {
+            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Staging Ring Buffer"),
+                size,
+                usage: wgpu::BufferUsages::MAP_WRITE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: true,
+            }),
+            offset: 0,
+        }
+    }
+
+    fn destroy(&mut self, staging: StagingBuffer) {
+        self.stats.buffer_count -= 1;
+        self.stats.allocated_bytes -= staging.buffer.size();
+        staging.buffer.destroy();
+    }
+}
+
+impl Default for StagingRing {
+    fn default() -> Self {
+        Self::new(DEFAULT_STAGING_BUFFER_SIZE)
+    }
+}
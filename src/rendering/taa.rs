@@ -0,0 +1,206 @@
+use bytemuck::NoUninit;
+use glam::{Mat3, Vec2, Vec3};
+
+use super::{bindings::{BindGroupBuilder, Bindings}, buffers::UniformBuffer, memory::{self, MemoryCategory, Tracked}, raytrace::{GpuMat3, GpuTransform, GpuVec3, RESULT_FORMAT}};
+
+/*
+Temporal antialiasing as a compute pass, like [super::fxaa::Fxaa]. The raytracer offsets its primary rays by
+the sub-pixel jitter of the frame (see [super::frame_uniforms::FrameUniforms::jitter]), and this pass blends
+every frame into a history, so that the edges average out over a few frames even while the camera moves.
+
+Each pixel finds where its surface was in the previous frame: the hit distance from the G-buffer puts it in
+the world with the current camera, and the previous camera projects it back onto the screen. The history is
+sampled there and clamped to the colors around the pixel in the current frame, which rejects history that
+doesn't belong to the surface anymore (disocclusions, moving lights) instead of ghosting. Pixels whose
+previous position was off screen start over from the current frame.
+
+The history is two textures that take turns being read and written, and the one that was written is drawn.
+*/
+
+const WORKGROUP_SIZE: u32 = 16;
+/// How much of the current frame is blended into the history. Lower is smoother, but slower to react.
+pub const DEFAULT_BLEND: f32 = 0.1;
+
+/// The `TaaParams` uniform of taa.wgsl.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, NoUninit)]
+struct TaaParams {
+    current: GpuTransform,
+    previous: GpuTransform,
+    ndc_mult: Vec2,
+    blend: f32,
+    reset: u32,
+}
+
+pub struct Taa {
+    history: [(Tracked<wgpu::Texture>, wgpu::TextureView); 2],
+    sampler: wgpu::Sampler,
+    params: UniformBuffer<TaaParams>,
+    bind_group_layout: wgpu::BindGroupLayout,
+    /// `bind_groups[i]` reads `history[i]` and writes the other one.
+    bind_groups: [wgpu::BindGroup; 2],
+    pipeline: wgpu::ComputePipeline,
+    /// The history that the next dispatch reads.
+    read: usize,
+    /// The camera of the previous frame, or `None` if the history has to start over.
+    previous: Option<GpuTransform>,
+}
+
+impl Taa {
+    /// `input` must be a view of a [RESULT_FORMAT] texture with the given dimensions, and `hit_normal_distance`
+    /// a view of the G-buffer's normals and distances of the same size.
+    pub fn new(device: &wgpu::Device, input: &wgpu::TextureView, hit_normal_distance: &wgpu::TextureView, width: u32, height: u32) -> Self {
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("TAA History Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let identity = GpuTransform::new(GpuMat3::new(Mat3::IDENTITY), GpuVec3::from_vec3(Vec3::ZERO));
+        let params = UniformBuffer::new(device, Some("TAA Params Buffer"), TaaParams {
+            current: identity,
+            previous: identity,
+            ndc_mult: Vec2::ONE,
+            blend: DEFAULT_BLEND,
+            reset: 1,
+        });
+
+        let bind_group_layout = BindGroupBuilder::new()
+            .label("TAA Bind Group Layout")
+            .texture_2d(0, wgpu::ShaderStages::COMPUTE)
+            .texture(1, wgpu::ShaderStages::COMPUTE, wgpu::TextureSampleType::Float { filterable: false }, wgpu::TextureViewDimension::D2)
+            .texture_2d(2, wgpu::ShaderStages::COMPUTE)
+            .sampler(3, wgpu::ShaderStages::COMPUTE)
+            .storage_texture(4, wgpu::ShaderStages::COMPUTE, wgpu::StorageTextureAccess::WriteOnly, RESULT_FORMAT)
+            .uniform(5, wgpu::ShaderStages::COMPUTE)
+            .build(device);
+
+        let history = [0, 1].map(|_| Self::create_history(device, width, height));
+        let bind_groups = Self::create_bind_groups(device, &bind_group_layout, input, hit_normal_distance, &history, &sampler, &params);
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../shaders/taa.wgsl"));
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("TAA Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("TAA Compute Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        Self {
+            history,
+            sampler,
+            params,
+            bind_group_layout,
+            bind_groups,
+            pipeline,
+            read: 0,
+            previous: None,
+        }
+    }
+
+    fn create_history(device: &wgpu::Device, width: u32, height: u32) -> (Tracked<wgpu::Texture>, wgpu::TextureView) {
+        let texture = memory::track(MemoryCategory::PostFx, device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("TAA History"),
+            dimension: wgpu::TextureDimension::D2,
+            format: RESULT_FORMAT,
+            mip_level_count: 1,
+            sample_count: 1,
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        }));
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (texture, view)
+    }
+
+    fn create_bind_groups(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        input: &wgpu::TextureView,
+        hit_normal_distance: &wgpu::TextureView,
+        history: &[(Tracked<wgpu::Texture>, wgpu::TextureView); 2],
+        sampler: &wgpu::Sampler,
+        params: &UniformBuffer<TaaParams>,
+    ) -> [wgpu::BindGroup; 2] {
+        [0, 1].map(|read| Bindings::new()
+            .texture_view(0, input)
+            .texture_view(1, hit_normal_distance)
+            .texture_view(2, &history[read].1)
+            .sampler(3, sampler)
+            .texture_view(4, &history[1 - read].1)
+            .buffer(5, params.buffer())
+            .build(device, Some("TAA Bind Group"), layout))
+    }
+
+    /// Recreates the history textures, which starts the history over. Call this whenever the input texture
+    /// is recreated.
+    pub fn resize(&mut self, device: &wgpu::Device, input: &wgpu::TextureView, hit_normal_distance: &wgpu::TextureView, width: u32, height: u32) {
+        self.history = [0, 1].map(|_| Self::create_history(device, width, height));
+        self.bind_groups = Self::create_bind_groups(device, &self.bind_group_layout, input, hit_normal_distance, &self.history, &self.sampler, &self.params);
+        self.reset();
+    }
+
+    /// Starts the history over from the next frame, such as after switching to TAA.
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+
+    /// Writes the camera of the upcoming frame (and remembers it for the next one). Call once per frame
+    /// before [Taa::compute]. `ndc_mult` is the input's [super::raytrace::calc_ray_mult].
+    pub fn prepare(&mut self, queue: &wgpu::Queue, transform: GpuTransform, ndc_mult: Vec2) {
+        self.read = 1 - self.read;
+        self.params.write(queue, TaaParams {
+            current: transform,
+            previous: self.previous.unwrap_or(transform),
+            ndc_mult,
+            blend: DEFAULT_BLEND,
+            reset: self.previous.is_none() as u32,
+        });
+        self.previous = Some(transform);
+    }
+
+    /// Both history views. The one at [Taa::output_index] holds the result of the last dispatch.
+    pub fn output_views(&self) -> [&wgpu::TextureView; 2] {
+        [&self.history[0].1, &self.history[1].1]
+    }
+
+    pub fn output_index(&self) -> usize {
+        1 - self.read
+    }
+
+    pub fn compute(&self, compute_pass: &mut wgpu::ComputePass) {
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &self.bind_groups[self.read], &[]);
+        compute_pass.dispatch_workgroups(
+            self.history[0].0.width().div_ceil(WORKGROUP_SIZE),
+            self.history[0].0.height().div_ceil(WORKGROUP_SIZE),
+            1,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn taa_params_test() {
+        // Matches the size of `TaaParams` in taa.wgsl.
+        assert_eq!(std::mem::size_of::<TaaParams>(), 144);
+    }
+}
@@ -0,0 +1,213 @@
+use glam::{vec2, Vec2};
+use glyphon::{Buffer, Color, TextArea, TextBounds};
+use vello::{kurbo::{Affine, RoundedRect}, peniko::{self, Fill}, Scene};
+
+/*
+Styles for the text of the HUD (see [crate::systems::OverlaySystem]). glyphon draws every glyph in a single
+color, so the outline and the shadow are extra copies of the text, drawn underneath it in this order:
+
+    Background  A rounded quad around the laid out text, drawn with vello (see [super::velvet::Velvet]).
+    Shadow      One copy, offset by [TextShadow::offset].
+    Outline     Eight copies, offset by [TextOutline::width] in every direction.
+    Text        The text itself.
+
+The colors of spans would override the color of the copies, so text with colored spans needs a second
+buffer with the same text but without them for its copies. Plain text draws the copies from its own buffer.
+Colors are 8-bit sRGB, which glyphon and the vello layer both decode on sRGB targets, so the copies blend
+with the scene in linear space like the text does.
+*/
+
+/// The directions of the outline's copies, around the text.
+const OUTLINE_DIRECTIONS: [Vec2; 8] = [
+    vec2(-1.0, -1.0),
+    vec2(0.0, -1.0),
+    vec2(1.0, -1.0),
+    vec2(-1.0, 0.0),
+    vec2(1.0, 0.0),
+    vec2(-1.0, 1.0),
+    vec2(0.0, 1.0),
+    vec2(1.0, 1.0),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextOutline {
+    /// Should be opaque, since the copies overlap.
+    pub color: Color,
+    /// In pixels.
+    pub width: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextShadow {
+    pub color: Color,
+    /// In pixels, down and to the right.
+    pub offset: Vec2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextBackground {
+    pub color: Color,
+    /// The space around the text in pixels.
+    pub padding: f32,
+    pub corner_radius: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    /// The color of the text that doesn't have a color of its own.
+    pub color: Color,
+    pub outline: Option<TextOutline>,
+    pub shadow: Option<TextShadow>,
+    pub background: Option<TextBackground>,
+}
+
+/// A copy of the text underneath it, see [TextStyle::layers].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextLayer {
+    pub offset: Vec2,
+    pub color: Color,
+}
+
+impl TextStyle {
+    pub const PLAIN: Self = Self {
+        color: Color::rgb(255, 255, 255),
+        outline: None,
+        shadow: None,
+        background: None,
+    };
+
+    /// The debug text, which has to stay readable over the sky as well as dark caves.
+    pub const HUD: Self = Self {
+        color: Color::rgb(220, 220, 220),
+        outline: Some(TextOutline {
+            color: Color::rgb(20, 20, 20),
+            width: 1.0,
+        }),
+        shadow: Some(TextShadow {
+            color: Color::rgba(0, 0, 0, 120),
+            offset: vec2(2.0, 2.0),
+        }),
+        background: Some(TextBackground {
+            color: Color::rgba(0, 0, 0, 90),
+            padding: 6.0,
+            corner_radius: 6.0,
+        }),
+    };
+
+    /// The log console, whose lines are colored by level.
+    pub const CONSOLE: Self = Self {
+        color: Color::rgb(220, 220, 220),
+        outline: None,
+        shadow: Some(TextShadow {
+            color: Color::rgb(20, 20, 20),
+            offset: vec2(1.0, 1.0),
+        }),
+        background: Some(TextBackground {
+            color: Color::rgba(0, 0, 0, 150),
+            padding: 4.0,
+            corner_radius: 4.0,
+        }),
+    };
+
+    /// The copies of the text that are drawn underneath it, in drawing order.
+    pub fn layers(&self) -> Vec<TextLayer> {
+        let mut layers = Vec::new();
+        if let Some(shadow) = self.shadow {
+            layers.push(TextLayer { offset: shadow.offset, color: shadow.color });
+        }
+        if let Some(outline) = self.outline {
+            layers.extend(OUTLINE_DIRECTIONS.map(|direction| TextLayer {
+                offset: direction * outline.width,
+                color: outline.color,
+            }));
+        }
+        layers
+    }
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self::PLAIN
+    }
+}
+
+/// The size of the text laid out in `buffer`, in pixels.
+pub fn text_size(buffer: &Buffer) -> Vec2 {
+    buffer.layout_runs().fold(Vec2::ZERO, |size, run| size.max(vec2(run.line_w, run.line_top + run.line_height)))
+}
+
+fn vello_color(color: Color) -> peniko::Color {
+    let [r, g, b, a] = color.as_rgba();
+    peniko::Color::from_rgba8(r, g, b, a)
+}
+
+/// Text drawn with a [TextStyle].
+pub struct StyledText<'a> {
+    /// The text.
+    pub buffer: &'a Buffer,
+    /// The same text without colored spans, for the layers. May be `buffer` if it has none.
+    pub layer_buffer: &'a Buffer,
+    /// The top left corner of the text.
+    pub position: Vec2,
+    pub bounds: TextBounds,
+    pub style: TextStyle,
+}
+
+impl<'a> StyledText<'a> {
+    /// The layers and the text, in drawing order. glyphon draws the areas in the order they're prepared in.
+    pub fn text_areas(&self) -> impl Iterator<Item = TextArea<'a>> + '_ {
+        let area = |buffer, offset: Vec2, color| TextArea {
+            buffer,
+            left: self.position.x + offset.x,
+            top: self.position.y + offset.y,
+            scale: 1.0,
+            bounds: self.bounds,
+            default_color: color,
+            custom_glyphs: &[],
+        };
+        self.style.layers().into_iter()
+            .map(move |layer| area(self.layer_buffer, layer.offset, layer.color))
+            .chain(std::iter::once(area(self.buffer, Vec2::ZERO, self.style.color)))
+    }
+
+    /// The background quad around `size` of text, if the style has a background.
+    fn background_rect(&self, size: Vec2) -> Option<(RoundedRect, Color)> {
+        let background = self.style.background?;
+        if size.cmple(Vec2::ZERO).any() {
+            return None;
+        }
+        let min = self.position - background.padding;
+        let max = self.position + size + background.padding;
+        let rect = RoundedRect::new(min.x as f64, min.y as f64, max.x as f64, max.y as f64, background.corner_radius as f64);
+        Some((rect, background.color))
+    }
+
+    /// Whether [StyledText::draw_background] draws anything.
+    pub fn has_background(&self) -> bool {
+        self.background_rect(text_size(self.buffer)).is_some()
+    }
+
+    /// Draws the background quad on a vector layer, which has to be rendered before the text.
+    pub fn draw_background(&self, scene: &mut Scene) {
+        if let Some((rect, color)) = self.background_rect(text_size(self.buffer)) {
+            scene.fill(Fill::NonZero, Affine::IDENTITY, vello_color(color), None, &rect);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_style_test() {
+        assert!(TextStyle::PLAIN.layers().is_empty());
+        let layers = TextStyle::HUD.layers();
+        // The shadow goes under the outline.
+        assert_eq!(layers.len(), 9);
+        assert_eq!(layers[0], TextLayer { offset: vec2(2.0, 2.0), color: Color::rgba(0, 0, 0, 120) });
+        assert!(layers[1..].iter().all(|layer| layer.offset.abs().max_element() == 1.0));
+        assert_eq!(layers[1..].iter().map(|layer| layer.offset).sum::<Vec2>(), Vec2::ZERO);
+        assert_eq!(vello_color(Color::rgba(1, 2, 3, 4)), peniko::Color::from_rgba8(1, 2, 3, 4));
+    }
+}
@@ -1,8 +1,10 @@
-use std::path::Path;
+use std::{collections::HashMap, path::Path};
 
-use image::GenericImageView;
+use image::RgbaImage;
 use wgpu::TextureView;
 
+use super::{compressed::{CompressedError, CompressedImage}, memory::{self, MemoryCategory, Tracked}, mipmaps::{full_mip_level_count, MipmapGenerator}, sampler::SamplerConfig};
+
 // fn log2_u32(n: u32) -> u32 {
 //     debug_assert!(n > 0);
 //     31 - n.leading_zeros()
@@ -25,17 +27,95 @@ pub enum TexArrErr {
         index: u32,
         dimensions: (u32, u32),
         expected: (u32, u32),
+    },
+    #[error("Failed to read atlas layout: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid atlas layout: {0}")]
+    Layout(#[from] ron::error::SpannedError),
+    #[error("Tile size must not be zero.")]
+    ZeroTileSize,
+    #[error("The atlas layout has {tiles} tiles, but the atlas only fits {capacity}.")]
+    TooManyTiles {
+        tiles: usize,
+        capacity: usize,
+    },
+    #[error("Tile name {0:?} is used more than once.")]
+    DuplicateName(String),
+    #[error("Failed to load compressed texture: {0}")]
+    Compressed(#[from] CompressedError),
+}
+
+/// Describes how to slice an atlas image into texture array layers. Usually read from a RON file
+/// next to the atlas:
+///
+/// ```text
+/// (
+///     tile_size: (16, 16),
+///     tiles: ["dirt", "grass_top", "", "stone"],
+/// )
+/// ```
+///
+/// Tiles are read left to right, then top to bottom. Each named tile becomes a layer in the order
+/// that it appears, and empty names skip a tile without creating a layer.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct AtlasLayout {
+    pub tile_size: (u32, u32),
+    pub tiles: Vec<String>,
+}
+
+impl AtlasLayout {
+    pub fn from_ron(source: &str) -> Result<Self, TexArrErr> {
+        Ok(ron::from_str(source)?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, TexArrErr> {
+        Self::from_ron(&std::fs::read_to_string(path)?)
+    }
+
+    /// Copies each named tile out of `atlas`. Returns the tiles along with a lookup from tile name
+    /// to layer index.
+    pub fn slice(&self, atlas: &RgbaImage) -> Result<(Vec<RgbaImage>, HashMap<String, u32>), TexArrErr> {
+        let (tile_width, tile_height) = self.tile_size;
+        if tile_width == 0 || tile_height == 0 {
+            return Err(TexArrErr::ZeroTileSize);
+        }
+        let columns = atlas.width() / tile_width;
+        let capacity = (columns * (atlas.height() / tile_height)) as usize;
+        if self.tiles.len() > capacity {
+            return Err(TexArrErr::TooManyTiles {
+                tiles: self.tiles.len(),
+                capacity,
+            });
+        }
+        let mut tiles = Vec::new();
+        let mut layers = HashMap::new();
+        for (i, name) in self.tiles.iter().enumerate() {
+            if name.is_empty() {
+                continue;
+            }
+            if layers.insert(name.clone(), tiles.len() as u32).is_some() {
+                return Err(TexArrErr::DuplicateName(name.clone()));
+            }
+            let x = (i as u32 % columns) * tile_width;
+            let y = (i as u32 / columns) * tile_height;
+            tiles.push(image::imageops::crop_imm(atlas, x, y, tile_width, tile_height).to_image());
+        }
+        Ok((tiles, layers))
     }
 }
 
 pub struct TextureArray {
-    pub texture: wgpu::Texture,
+    pub texture: Tracked<wgpu::Texture>,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
+    /// The config of `sampler`. Change it with [TextureArray::set_sampler].
+    pub sampler_config: SamplerConfig,
     pub format: wgpu::TextureFormat,
     pub dimensions: (u32, u32),
     pub layer_count: u32,
     pub bind_group: TextureArrayBindGroup,
+    /// Layer indices by tile name. Only texture arrays created from an atlas have names.
+    pub layers: HashMap<String, u32>,
 }
 
 impl TextureArray {
@@ -49,31 +129,89 @@ impl TextureArray {
         address_mode_v: wgpu::AddressMode,
         mip_level_count: u32,
     ) -> Result<Self, TexArrErr> {
-        if paths.is_empty() {
+        let images = paths.iter()
+            .map(|path| image::open(path.as_ref()).map(|img| img.into_rgba8()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let images = images.iter().collect::<Vec<_>>();
+        Self::from_images(device, queue, &images, label, format, address_mode_u, address_mode_v, mip_level_count)
+    }
+
+    /// Creates the texture array from a single atlas image, sliced into layers by `layout`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_atlas(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        atlas: &RgbaImage,
+        layout: &AtlasLayout,
+        label: Option<&str>,
+        format: wgpu::TextureFormat,
+        address_mode_u: wgpu::AddressMode,
+        address_mode_v: wgpu::AddressMode,
+        mip_level_count: u32,
+    ) -> Result<Self, TexArrErr> {
+        let (tiles, layers) = layout.slice(atlas)?;
+        let tiles = tiles.iter().collect::<Vec<_>>();
+        let mut array = Self::from_images(device, queue, &tiles, label, format, address_mode_u, address_mode_v, mip_level_count)?;
+        array.layers = layers;
+        Ok(array)
+    }
+
+    /// Loads the atlas image and its RON layout, then calls [TextureArray::from_atlas].
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_atlas_files<P: AsRef<Path>, L: AsRef<Path>>(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        atlas_path: P,
+        layout_path: L,
+        label: Option<&str>,
+        format: wgpu::TextureFormat,
+        address_mode_u: wgpu::AddressMode,
+        address_mode_v: wgpu::AddressMode,
+        mip_level_count: u32,
+    ) -> Result<Self, TexArrErr> {
+        let atlas = image::open(atlas_path.as_ref())?.into_rgba8();
+        let layout = AtlasLayout::load(layout_path)?;
+        Self::from_atlas(device, queue, &atlas, &layout, label, format, address_mode_u, address_mode_v, mip_level_count)
+    }
+
+    /// Creates the texture array from images that have already been loaded (for example by an [crate::assets::AssetServer]).
+    /// Level 0 is uploaded from the images and the other mip levels are generated on the GPU. `mip_level_count`
+    /// is clamped to the full mip chain.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_images(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        images: &[&RgbaImage],
+        label: Option<&str>,
+        format: wgpu::TextureFormat,
+        address_mode_u: wgpu::AddressMode,
+        address_mode_v: wgpu::AddressMode,
+        mip_level_count: u32,
+    ) -> Result<Self, TexArrErr> {
+        if images.is_empty() {
             return Err(TexArrErr::NoPaths);
         }
 
-        let first_img = image::open(paths[0].as_ref())?;
-        let (width, height) = first_img.dimensions();
+        let (width, height) = images[0].dimensions();
+        let mip_level_count = mip_level_count.clamp(1, full_mip_level_count(width, height));
 
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label,
             size: wgpu::Extent3d {
                 width,
                 height,
-                depth_or_array_layers: paths.len() as u32,
+                depth_or_array_layers: images.len() as u32,
             },
-            mip_level_count: mip_level_count,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            // Render attachment is needed to generate the mip levels.
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::RENDER_ATTACHMENT,
             view_formats: &[],
         });
 
-        for (i, path) in paths.iter().enumerate() {
-            let img = image::open(path.as_ref())?;
-
+        for (i, &img) in images.iter().enumerate() {
             // Ensure all images have the same dimensions
             let (img_width, img_height) = img.dimensions();
 
@@ -85,53 +223,59 @@ impl TextureArray {
                 });
             }
 
-            for mip_level in 0..mip_level_count {
-                let (mip, mip_width, mip_height) = if mip_level > 0 {
-                    let div = 2u32.pow(mip_level);
-                    let mip_width = img_width / div;
-                    let mip_height = img_width / div;
-                    let mip = img.resize(mip_width, mip_height, image::imageops::FilterType::Gaussian).into_rgba8();
-                    (
-                        mip,
-                        mip_width,
-                        mip_height,
-                    )
-                } else {
-                    (
-                        img.to_rgba8(),
-                        img_width,
-                        img_height,
-                    )
-                };
-                let bytes_per_row = Some(4 * mip_width);
-                let rows_per_image = Some(mip_height);
-
-                queue.write_texture(
-                    wgpu::TexelCopyTextureInfoBase {
-                        texture: &texture,
-                        mip_level: mip_level,
-                        origin: wgpu::Origin3d {
-                            x: 0,
-                            y: 0,
-                            z: i as u32,
-                        },
-                        aspect: wgpu::TextureAspect::All,
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfoBase {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: i as u32,
                     },
-                    &mip,
-                    wgpu::TexelCopyBufferLayout {
-                        offset: 0,
-                        bytes_per_row,
-                        rows_per_image,
-                    },
-                    wgpu::Extent3d {
-                        width: mip_width,
-                        height: mip_height,
-                        depth_or_array_layers: 1,
-                    },
-                );
-            }
+                    aspect: wgpu::TextureAspect::All,
+                },
+                img,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * img_width),
+                    rows_per_image: Some(img_height),
+                },
+                wgpu::Extent3d {
+                    width: img_width,
+                    height: img_height,
+                    depth_or_array_layers: 1,
+                },
+            );
         }
 
+        MipmapGenerator::new(device).generate_and_submit(device, queue, &texture);
+
+        Ok(Self::from_texture(device, texture, label, address_mode_u, address_mode_v))
+    }
+
+    /// Creates the texture array from a block-compressed texture, keeping its mip levels. Its layers become
+    /// the layers of the array. If the device doesn't support BC compression, the texture is decompressed
+    /// (see [CompressedImage::create_texture]), so `format` is the format that was actually used.
+    pub fn from_compressed(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: &CompressedImage,
+        label: Option<&str>,
+        address_mode_u: wgpu::AddressMode,
+        address_mode_v: wgpu::AddressMode,
+    ) -> Result<Self, TexArrErr> {
+        let texture = image.create_texture(device, queue, label)?;
+        Ok(Self::from_texture(device, texture, label, address_mode_u, address_mode_v))
+    }
+
+    fn from_texture(
+        device: &wgpu::Device,
+        texture: wgpu::Texture,
+        label: Option<&str>,
+        address_mode_u: wgpu::AddressMode,
+        address_mode_v: wgpu::AddressMode,
+    ) -> Self {
+        let format = texture.format();
         let view = texture.create_view(&wgpu::TextureViewDescriptor {
             label,
             format: Some(format),
@@ -143,21 +287,24 @@ impl TextureArray {
             array_layer_count: None,
             ..Default::default()
         });
-        let sampler = Self::create_sampler(device, address_mode_u, address_mode_v);
+        let sampler_config = SamplerConfig::NEAREST.with_address_modes(address_mode_u, address_mode_v);
+        let sampler = Self::create_sampler(device, &sampler_config);
         let bind_group = Self::bind_group(
             device,
             &view,
             &sampler,
         );
-        Ok(Self {
-            texture,
+        Self {
+            dimensions: (texture.width(), texture.height()),
+            layer_count: texture.depth_or_array_layers(),
+            texture: memory::track(MemoryCategory::Textures, texture),
             bind_group,
             view,
             format,
             sampler,
-            dimensions: (width, height),
-            layer_count: paths.len() as u32,
-        })
+            sampler_config,
+            layers: HashMap::new(),
+        }
     }
 
     pub fn bind_group(
@@ -178,7 +325,6 @@ impl TextureArray {
                     },
                     count: None,
                 },
-                // Near Sampler
                 wgpu::BindGroupLayoutEntry {
                     binding: 1,
                     visibility: wgpu::ShaderStages::FRAGMENT,
@@ -188,51 +334,54 @@ impl TextureArray {
             ],
         });
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, view, sampler);
+        TextureArrayBindGroup {
+            bind_group,
+            bind_group_layout,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        view: &TextureView,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Texture Array Bind Group"),
-            layout: &bind_group_layout,
+            layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
                     resource: wgpu::BindingResource::TextureView(view),
                 },
-                // Near Sampler
                 wgpu::BindGroupEntry {
                     binding: 1,
                     resource: wgpu::BindingResource::Sampler(sampler),
                 },
             ],
-        });
-        TextureArrayBindGroup {
-            bind_group,
-            bind_group_layout,
+        })
+    }
+
+    pub fn create_sampler(device: &wgpu::Device, config: &SamplerConfig) -> wgpu::Sampler {
+        config.create(device, Some("Texture Array Sampler"))
+    }
+
+    /// Switches to a sampler created from `config` (which should be [SamplerConfig::validated]). The bind
+    /// group layout is kept, so pipelines that use it don't have to be recreated.
+    pub fn set_sampler(&mut self, device: &wgpu::Device, config: SamplerConfig) {
+        if config == self.sampler_config {
+            return;
         }
+        self.sampler = Self::create_sampler(device, &config);
+        self.bind_group.bind_group = Self::create_bind_group(device, &self.bind_group.bind_group_layout, &self.view, &self.sampler);
+        self.sampler_config = config;
     }
 
-    pub fn create_sampler(device: &wgpu::Device, address_mode_u: wgpu::AddressMode, address_mode_v: wgpu::AddressMode) -> wgpu::Sampler {
-        // let far = device.create_sampler(&wgpu::SamplerDescriptor {
-        //     label: Some("Texture Array Far Sampler"),
-        //     address_mode_u,
-        //     address_mode_v,
-        //     address_mode_w: wgpu::AddressMode::ClampToEdge,
-        //     mag_filter: wgpu::FilterMode::Linear,
-        //     min_filter: wgpu::FilterMode::Linear,
-        //     mipmap_filter: wgpu::FilterMode::Linear,
-        //     anisotropy_clamp: 16,
-        //     ..Default::default()
-        // });
-        let near = device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some("Texture Array Sampler"),
-            address_mode_u,
-            address_mode_v,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Linear,
-            ..Default::default()
-        });
-        // TextureArraySamplers { near, far }
-        near
+    /// The layer of the tile named `name`, for use as a texture index in the [crate::modeling::modeler::Modeler]
+    /// or in [crate::model::loader::LoadOptions::with_material_layers].
+    pub fn layer(&self, name: &str) -> Option<u32> {
+        self.layers.get(name).copied()
     }
 
     pub fn texel_to_uv(&self, texpos: glam::Vec2) -> glam::Vec2 {
@@ -243,12 +392,38 @@ impl TextureArray {
     }
 }
 
-pub struct TextureArraySamplers {
-    near: wgpu::Sampler,
-    far: wgpu::Sampler,
-}
-
 pub struct TextureArrayBindGroup {
     pub bind_group_layout: wgpu::BindGroupLayout,
     pub bind_group: wgpu::BindGroup,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn atlas_slice_test() {
+        let layout = AtlasLayout::from_ron(r#"(tile_size: (2, 2), tiles: ["red", "", "blue"])"#).unwrap();
+        // A 2x2 grid of tiles where each tile is filled with its index.
+        let atlas = RgbaImage::from_fn(4, 4, |x, y| image::Rgba([(x / 2 + (y / 2) * 2) as u8, 0, 0, 255]));
+        let (tiles, layers) = layout.slice(&atlas).unwrap();
+        assert_eq!(tiles.len(), 2);
+        assert_eq!(layers.get("red"), Some(&0));
+        assert_eq!(layers.get("blue"), Some(&1));
+        assert!(tiles[0].pixels().all(|pixel| pixel.0[0] == 0));
+        assert!(tiles[1].pixels().all(|pixel| pixel.0[0] == 2));
+        assert_eq!(tiles[1].dimensions(), (2, 2));
+
+        let too_many = AtlasLayout {
+            tile_size: (2, 2),
+            tiles: vec!["a".into(); 5],
+        };
+        assert!(matches!(too_many.slice(&atlas), Err(TexArrErr::TooManyTiles { tiles: 5, capacity: 4 })));
+        let duplicate = AtlasLayout {
+            tile_size: (2, 2),
+            tiles: vec!["a".into(), "a".into()],
+        };
+        assert!(matches!(duplicate.slice(&atlas), Err(TexArrErr::DuplicateName(_))));
+        assert!(AtlasLayout::from_ron("(tile_size: 2)").is_err());
+    }
 }
\ No newline at end of file
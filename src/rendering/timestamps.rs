@@ -0,0 +1,220 @@
+use std::{sync::{Arc, Mutex}, time::Duration};
+
+/*
+GPU timestamps are resolved into one of several readback buffers. A buffer is mapped once the frame
+that wrote it has been submitted, and it's only read after the map has completed (see [GpuTimer::collect],
+which polls the device without waiting), so profiling never stalls the CPU on the GPU.
+
+While every readback buffer is still in flight, the frame isn't timed.
+
+A timer can hold several scopes (see [GpuTimer::with_scopes]), each a pair of timestamps, such as one per
+pass of the frame (see [super::frame_graph_lite]).
+*/
+
+/// How many frames of timestamps can be waiting to be read back.
+pub const DEFAULT_FRAMES_IN_FLIGHT: usize = 3;
+
+/// Timestamp `2 * i` is written before the work of scope `i` and timestamp `2 * i + 1` after it.
+pub const TIMESTAMPS_PER_SCOPE: u32 = 2;
+const TIMESTAMP_SIZE: wgpu::BufferAddress = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SlotState {
+    Free,
+    /// The timestamps were copied into the buffer, but the frame hasn't been submitted yet.
+    Written,
+    Mapping,
+    Mapped,
+    /// The map failed, such as when the device was lost. The buffer can be reused.
+    Failed,
+}
+
+struct ReadbackSlot {
+    buffer: wgpu::Buffer,
+    state: Arc<Mutex<SlotState>>,
+    /// The frame that wrote the slot, so that results are returned in order.
+    frame: u64,
+    /// The number of scopes that were written.
+    scopes: u32,
+}
+
+/// The times of the scopes of one frame, see [GpuTimer::collect_scopes].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedFrame {
+    /// The frame number that [GpuTimer::resolve_scopes] returned.
+    pub frame: u64,
+    /// `None` for the scopes whose timestamps are out of order.
+    pub scopes: Vec<Option<Duration>>,
+}
+
+/// Times GPU work with a pair of timestamps without blocking on the results.
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    slots: Vec<ReadbackSlot>,
+    /// The slot that was written this frame, which is mapped in [GpuTimer::map].
+    written: Option<usize>,
+    frame: u64,
+    period: f32,
+    scope_count: u32,
+}
+
+impl GpuTimer {
+    /// A timer with a single scope. `frames_in_flight` is the number of readback buffers (at least one).
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, label: &str, frames_in_flight: usize) -> Self {
+        Self::with_scopes(device, queue, label, 1, frames_in_flight)
+    }
+
+    /// A timer with room for `scope_count` scopes (at least one).
+    pub fn with_scopes(device: &wgpu::Device, queue: &wgpu::Queue, label: &str, scope_count: u32, frames_in_flight: usize) -> Self {
+        let scope_count = scope_count.max(1);
+        let readback_size = (scope_count * TIMESTAMPS_PER_SCOPE) as wgpu::BufferAddress * TIMESTAMP_SIZE;
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some(&format!("{label} Query Set")),
+            count: scope_count * TIMESTAMPS_PER_SCOPE,
+            ty: wgpu::QueryType::Timestamp,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("{label} Timestamp Buffer")),
+            size: readback_size,
+            mapped_at_creation: false,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+        });
+        let slots = (0..frames_in_flight.max(1)).map(|_| ReadbackSlot {
+            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(&format!("{label} Timestamp Read Buffer")),
+                size: readback_size,
+                mapped_at_creation: false,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            }),
+            state: Arc::new(Mutex::new(SlotState::Free)),
+            frame: 0,
+            scopes: 0,
+        }).collect();
+        Self {
+            query_set,
+            resolve_buffer,
+            slots,
+            written: None,
+            frame: 0,
+            period: queue.get_timestamp_period(),
+            scope_count,
+        }
+    }
+
+    /// The query set to write the timestamps to.
+    pub fn query_set(&self) -> &wgpu::QuerySet {
+        &self.query_set
+    }
+
+    pub fn scope_count(&self) -> u32 {
+        self.scope_count
+    }
+
+    /// Copies the timestamps into a free readback buffer. Call this after the timed work was recorded.
+    /// Returns `false` if every buffer is still in flight, in which case this frame isn't timed.
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) -> bool {
+        self.resolve_scopes(encoder, 1).is_some()
+    }
+
+    /// Like [GpuTimer::resolve], but for the first `scopes` scopes, which must all have been written. Returns
+    /// the frame number that [GpuTimer::collect_scopes] reports the times with, or `None` if the frame isn't
+    /// timed.
+    pub fn resolve_scopes(&mut self, encoder: &mut wgpu::CommandEncoder, scopes: u32) -> Option<u64> {
+        self.frame += 1;
+        let scopes = scopes.min(self.scope_count);
+        if scopes == 0 {
+            return None;
+        }
+        let index = self.slots.iter().position(|slot| *slot.state.lock().unwrap() == SlotState::Free)?;
+        let timestamps = scopes * TIMESTAMPS_PER_SCOPE;
+        encoder.resolve_query_set(&self.query_set, 0..timestamps, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.slots[index].buffer, 0, timestamps as wgpu::BufferAddress * TIMESTAMP_SIZE);
+        let slot = &mut self.slots[index];
+        *slot.state.lock().unwrap() = SlotState::Written;
+        slot.frame = self.frame;
+        slot.scopes = scopes;
+        self.written = Some(index);
+        Some(self.frame)
+    }
+
+    /// Starts mapping the buffer that was written by [GpuTimer::resolve]. Call this after the encoder
+    /// was submitted.
+    pub fn map(&mut self) {
+        let Some(index) = self.written.take() else {
+            return;
+        };
+        let slot = &self.slots[index];
+        *slot.state.lock().unwrap() = SlotState::Mapping;
+        let state = Arc::clone(&slot.state);
+        slot.buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            *state.lock().unwrap() = match result {
+                Ok(()) => SlotState::Mapped,
+                Err(err) => {
+                    log::error!("Failed to map the timestamp buffer: {err}");
+                    SlotState::Failed
+                }
+            };
+        });
+    }
+
+    /// Reads the buffers that have finished mapping, oldest first, and frees them. This polls the
+    /// device without waiting, so it should be called once per frame (such as at the start of it).
+    pub fn collect(&mut self, device: &wgpu::Device) -> Vec<Duration> {
+        self.collect_scopes(device).into_iter()
+            .filter_map(|frame| frame.scopes.first().copied().flatten())
+            .collect()
+    }
+
+    /// Like [GpuTimer::collect], with the times of every scope that was resolved. Frames whose buffer
+    /// failed to map are left out.
+    pub fn collect_scopes(&mut self, device: &wgpu::Device) -> Vec<TimedFrame> {
+        device.poll(wgpu::Maintain::Poll);
+        let mut ready = self.slots.iter()
+            .filter(|slot| matches!(*slot.state.lock().unwrap(), SlotState::Mapped | SlotState::Failed))
+            .collect::<Vec<_>>();
+        ready.sort_by_key(|slot| slot.frame);
+        ready.into_iter().filter_map(|slot| {
+            let mut state = slot.state.lock().unwrap();
+            let failed = *state == SlotState::Failed;
+            *state = SlotState::Free;
+            if failed {
+                return None;
+            }
+            let scopes = {
+                let data = slot.buffer.slice(..).get_mapped_range();
+                let timestamps: &[u64] = bytemuck::cast_slice(&data);
+                timestamps.chunks_exact(TIMESTAMPS_PER_SCOPE as usize)
+                    .take(slot.scopes as usize)
+                    .map(|pair| timestamp_duration(pair[0], pair[1], self.period))
+                    .collect()
+            };
+            slot.buffer.unmap();
+            Some(TimedFrame {
+                frame: slot.frame,
+                scopes,
+            })
+        }).collect()
+    }
+}
+
+/// The time between two timestamps, where `period` is the number of nanoseconds per tick (see
+/// [wgpu::Queue::get_timestamp_period]). `None` if the timestamps are out of order, which happens when
+/// the counter was reset between them.
+pub fn timestamp_duration(start: u64, end: u64, period: f32) -> Option<Duration> {
+    let ticks = end.checked_sub(start)?;
+    Some(Duration::from_nanos((ticks as f64 * period as f64) as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamp_duration_test() {
+        assert_eq!(timestamp_duration(100, 1100, 1.0), Some(Duration::from_micros(1)));
+        assert_eq!(timestamp_duration(0, 1000, 83.333), Some(Duration::from_nanos(83333)));
+        assert_eq!(timestamp_duration(5, 5, 1.0), Some(Duration::ZERO));
+        assert_eq!(timestamp_duration(1000, 100, 1.0), None);
+    }
+}
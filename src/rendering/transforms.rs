@@ -1,3 +1,5 @@
+use super::bindings::{BindGroupBuilder, Bindings};
+
 pub struct TransformsBindGroup {
     // pub world_buffer: wgpu::Buffer,
     pub view_projection_buffer: wgpu::Buffer,
@@ -20,45 +22,15 @@ impl TransformsBindGroup {
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
-        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Transforms Bind Group Layout"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                },
-            ],
-        });
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Transforms Bind Group"),
-            layout: &layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: view_projection_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: camera_position_buffer.as_entire_binding(),
-                },
-            ],
-        });
+        let layout = BindGroupBuilder::new()
+            .label("Transforms Bind Group Layout")
+            .uniform(0, wgpu::ShaderStages::VERTEX)
+            .uniform(1, wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::VERTEX)
+            .build(device);
+        let bind_group = Bindings::new()
+            .buffer(0, &view_projection_buffer)
+            .buffer(1, &camera_position_buffer)
+            .build(device, Some("Transforms Bind Group"), &layout);
         Self {
             view_projection_buffer,
             camera_position_buffer,
@@ -1,39 +1,35 @@
+use glam::{vec2, Vec2};
 use vello::{Renderer, RendererOptions};
 
-use crate::state::State;
+use super::{color::{color_shader, ColorConversion, ColorSpace}, memory::{self, MemoryCategory, Tracked}, msaa::multisample_state};
+
+/*
+Velvet is the 2D vector overlay, drawn with vello. Every frame the scene is rendered into a texture the
+size of the window, which is composited over the final image after post processing, so the vector
+shapes stay crisp regardless of the render scale. Coordinates are in physical pixels with the origin in
+the top left corner.
+*/
 
 pub struct Velvet {
     pub renderer: Renderer,
-    pub texture: wgpu::Texture,
+    pub texture: Tracked<wgpu::Texture>,
     pub view: wgpu::TextureView,
-    // sampler: wgpu::Sampler,
-    // bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
     pub bind_group: wgpu::BindGroup,
     pub draw_pipeline: wgpu::RenderPipeline,
 }
 
 impl Velvet {
-    pub fn new(device: &wgpu::Device) -> Self {
+    /// `format` and `sample_count` are those of the pass that the texture is drawn in. Vello renders sRGB
+    /// encoded colors, which are decoded when the target is sRGB. `width` and `height` are the size of the
+    /// window, see [Velvet::resize].
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32, width: u32, height: u32) -> Self {
         let renderer = Renderer::new(
             device,
             RendererOptions::default(),
         ).expect("Failed to create renderer.");
-        let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Velvet Render Texture"),
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
-            mip_level_count: 1,
-            sample_count: 1,
-            size: wgpu::Extent3d {
-                depth_or_array_layers: 1,
-                width: 1280,
-                height: 720,
-            },
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
-            view_formats: &[wgpu::TextureFormat::Rgba8Unorm],
-        });
-
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let (texture, view) = Self::create_texture(device, width, height);
 
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             label: Some("Velvet Render Texture Sampler"),
@@ -68,22 +64,10 @@ impl Velvet {
             ],
         });
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Velvet Render Bind Group"),
-            layout: &bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&sampler),
-                }
-            ],
-        });
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, &view, &sampler);
 
-        let shader = device.create_shader_module(wgpu::include_wgsl!("../shaders/stretch_texture.wgsl"));
+        let shader = device.create_shader_module(color_shader!("Velvet Shader", "../shaders/stretch_texture.wgsl"));
+        let constants = ColorConversion::for_target(ColorSpace::Srgb, format).constants();
 
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Velvet Render Pipeline Layout"),
@@ -104,17 +88,20 @@ impl Velvet {
             },
             fragment: Some(wgpu::FragmentState {
                 module: &shader,
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &constants,
+                    ..Default::default()
+                },
                 entry_point: Some("fragment_main"),
                 targets: &[Some(wgpu::ColorTargetState {
                     blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                     write_mask: wgpu::ColorWrites::all(),
-                    format: wgpu::TextureFormat::Bgra8UnormSrgb,
+                    format,
                 })]
             }),
             cache: None,
             depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
+            multisample: multisample_state(sample_count),
             multiview: None,
             primitive: wgpu::PrimitiveState {
                 cull_mode: Some(wgpu::Face::Back),
@@ -127,15 +114,67 @@ impl Velvet {
 
         Self {
             renderer,
-            // sampler,
+            sampler,
             texture,
             view,
-            // bind_group_layout,
+            bind_group_layout,
             bind_group,
             draw_pipeline,
         }
     }
 
+    fn create_texture(device: &wgpu::Device, width: u32, height: u32) -> (Tracked<wgpu::Texture>, wgpu::TextureView) {
+        let texture = memory::track(MemoryCategory::Text, device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Velvet Render Texture"),
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            mip_level_count: 1,
+            sample_count: 1,
+            size: wgpu::Extent3d {
+                depth_or_array_layers: 1,
+                width: width.max(1),
+                height: height.max(1),
+            },
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+            view_formats: &[wgpu::TextureFormat::Rgba8Unorm],
+        }));
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (texture, view)
+    }
+
+    fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, view: &wgpu::TextureView, sampler: &wgpu::Sampler) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Velvet Render Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                }
+            ],
+        })
+    }
+
+    /// Recreates the texture if the size changed.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        let (width, height) = (width.max(1), height.max(1));
+        if (width, height) == (self.texture.width(), self.texture.height()) {
+            return;
+        }
+        (self.texture, self.view) = Self::create_texture(device, width, height);
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, &self.view, &self.sampler);
+    }
+
+    /// The size of the layer in pixels.
+    pub fn size(&self) -> Vec2 {
+        vec2(self.texture.width() as f32, self.texture.height() as f32)
+    }
+
+    /// Renders the scene built by `renderer` to the texture. Whatever isn't drawn is transparent.
     pub fn draw<F: FnMut(&mut vello::Scene)>(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, mut renderer: F) {
         let mut scene = vello::Scene::new();
         renderer(&mut scene);
@@ -145,10 +184,10 @@ impl Velvet {
             &scene,
             &self.view,
             &vello::RenderParams {
-                base_color: vello::peniko::Color::from_rgb8(0, 0, 0),
+                base_color: vello::peniko::Color::TRANSPARENT,
                 antialiasing_method: vello::AaConfig::Msaa16,
-                width: 1280,
-                height: 720,
+                width: self.texture.width(),
+                height: self.texture.height(),
             }
         ).expect("Failed to render.");
     }
@@ -0,0 +1,30 @@
+// Selected with a [crate::rendering::color::ColorConversion]: 0 leaves colors unchanged, 1 encodes
+// linear colors to sRGB and 2 decodes sRGB colors to linear.
+override COLOR_CONVERSION: u32 = 0u;
+
+fn linear_to_srgb(linear: vec3<f32>) -> vec3<f32> {
+    let low = linear * 12.92;
+    let high = 1.055 * pow(max(linear, vec3<f32>(0.0)), vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, linear <= vec3<f32>(0.0031308));
+}
+
+fn srgb_to_linear(srgb: vec3<f32>) -> vec3<f32> {
+    let low = srgb / 12.92;
+    let high = pow((max(srgb, vec3<f32>(0.0)) + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, srgb <= vec3<f32>(0.04045));
+}
+
+fn convert_color(color: vec4<f32>) -> vec4<f32> {
+    switch COLOR_CONVERSION {
+        case 1u: {
+            return vec4<f32>(linear_to_srgb(color.rgb), color.a);
+        }
+        case 2u: {
+            return vec4<f32>(srgb_to_linear(color.rgb), color.a);
+        }
+        default: {
+            return color;
+        }
+    }
+}
+
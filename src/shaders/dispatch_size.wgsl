@@ -0,0 +1,17 @@
+@group(0) @binding(0) var target_texture: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> workgroup_size: vec2<u32>;
+@group(0) @binding(2) var<storage, read_write> args: DispatchArgs;
+
+// The layout that `dispatch_workgroups_indirect` reads.
+struct DispatchArgs {
+    x: u32,
+    y: u32,
+    z: u32,
+}
+
+@compute @workgroup_size(1)
+fn main() {
+    let size = textureDimensions(target_texture);
+    let count = (size + workgroup_size - 1u) / max(workgroup_size, vec2<u32>(1u));
+    args = DispatchArgs(count.x, count.y, 1u);
+}
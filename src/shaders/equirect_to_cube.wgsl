@@ -0,0 +1,61 @@
+// Projects an equirectangular panorama onto the six faces of a cubemap. Each invocation writes one
+// texel of one face (the z of the dispatch is the face). The source is a 32-bit float texture,
+// which can't be filtered, so bilinear filtering is done by hand with textureLoad.
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var faces: texture_storage_2d_array<rgba16float, write>;
+
+const PI: f32 = 3.14159265358979;
+// The largest finite 16-bit float. Bright spots (such as the sun) in HDR images can exceed it.
+const MAX_HALF: f32 = 65504.0;
+
+// The direction through `st` (from -1 to 1) on a face, in the order +X, -X, +Y, -Y, +Z, -Z.
+fn face_direction(face: u32, st: vec2<f32>) -> vec3<f32> {
+    let s = st.x;
+    let t = st.y;
+    switch face {
+        case 0u: { return vec3<f32>(1.0, -t, -s); }
+        case 1u: { return vec3<f32>(-1.0, -t, s); }
+        case 2u: { return vec3<f32>(s, 1.0, t); }
+        case 3u: { return vec3<f32>(s, -1.0, -t); }
+        case 4u: { return vec3<f32>(s, -t, 1.0); }
+        default: { return vec3<f32>(-s, -t, -1.0); }
+    }
+}
+
+// Wraps horizontally (the panorama goes all the way around) and clamps vertically.
+fn load_texel(texel: vec2<i32>, size: vec2<i32>) -> vec4<f32> {
+    let x = ((texel.x % size.x) + size.x) % size.x;
+    let y = clamp(texel.y, 0, size.y - 1);
+    return textureLoad(source, vec2<i32>(x, y), 0);
+}
+
+fn sample_bilinear(uv: vec2<f32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(source));
+    let position = uv * vec2<f32>(size) - 0.5;
+    let base = vec2<i32>(floor(position));
+    let f = fract(position);
+    let top = mix(load_texel(base, size), load_texel(base + vec2<i32>(1, 0), size), f.x);
+    let bottom = mix(load_texel(base + vec2<i32>(0, 1), size), load_texel(base + vec2<i32>(1, 1), size), f.x);
+    return mix(top, bottom, f.y);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn main(
+    @builtin(global_invocation_id) id: vec3<u32>,
+) {
+    let size = textureDimensions(faces);
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+    let st = (vec2<f32>(id.xy) + 0.5) / vec2<f32>(size) * 2.0 - 1.0;
+    let direction = normalize(face_direction(id.z, st));
+    let uv = vec2<f32>(
+        atan2(direction.z, direction.x) / (2.0 * PI) + 0.5,
+        acos(clamp(direction.y, -1.0, 1.0)) / PI,
+    );
+    let color = min(sample_bilinear(uv).rgb, vec3<f32>(MAX_HALF));
+    textureStore(faces, id.xy, id.z, vec4<f32>(color, 1.0));
+}
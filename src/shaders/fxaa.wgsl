@@ -0,0 +1,136 @@
+@group(0) @binding(0) var input_texture: texture_2d<f32>;
+@group(0) @binding(1) var input_sampler: sampler;
+@group(0) @binding(2) var output_texture: texture_storage_2d<rgba16float, write>;
+
+// Edges with less contrast than this are left alone.
+const EDGE_THRESHOLD_MIN: f32 = 0.0312;
+// Relative to the brightest neighbor.
+const EDGE_THRESHOLD_MAX: f32 = 0.125;
+// How much of the sub-pixel aliasing is removed (0.0 = none, 1.0 = softest).
+const SUBPIXEL_QUALITY: f32 = 0.75;
+const SEARCH_STEPS: i32 = 10;
+const SEARCH_STEP_SIZES: array<f32, 10> = array<f32, 10>(1.0, 1.0, 1.0, 1.0, 1.5, 2.0, 2.0, 2.0, 4.0, 8.0);
+
+// The input is HDR. Clamping keeps bright highlights from hiding the edges next to them.
+fn luma(color: vec3<f32>) -> f32 {
+    return sqrt(dot(min(color, vec3<f32>(1.0)), vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+fn load_luma(pos: vec2<i32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(input_texture));
+    return luma(textureLoad(input_texture, clamp(pos, vec2<i32>(0), size - 1), 0).rgb);
+}
+
+fn sample_luma(uv: vec2<f32>) -> f32 {
+    return luma(textureSampleLevel(input_texture, input_sampler, uv, 0.0).rgb);
+}
+
+@compute @workgroup_size(16, 16)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let size = textureDimensions(output_texture);
+    if any(global_id.xy >= size) {
+        return;
+    }
+    let inverse_size = 1.0 / vec2<f32>(size);
+    let uv = (vec2<f32>(global_id.xy) + 0.5) * inverse_size;
+    let pos = vec2<i32>(global_id.xy);
+    let center = textureLoad(input_texture, pos, 0);
+    let luma_center = luma(center.rgb);
+
+    let luma_n = load_luma(pos + vec2<i32>(0, -1));
+    let luma_s = load_luma(pos + vec2<i32>(0, 1));
+    let luma_w = load_luma(pos + vec2<i32>(-1, 0));
+    let luma_e = load_luma(pos + vec2<i32>(1, 0));
+    let luma_min = min(luma_center, min(min(luma_n, luma_s), min(luma_w, luma_e)));
+    let luma_max = max(luma_center, max(max(luma_n, luma_s), max(luma_w, luma_e)));
+    let luma_range = luma_max - luma_min;
+    if luma_range < max(EDGE_THRESHOLD_MIN, luma_max * EDGE_THRESHOLD_MAX) {
+        textureStore(output_texture, global_id.xy, center);
+        return;
+    }
+
+    let luma_nw = load_luma(pos + vec2<i32>(-1, -1));
+    let luma_ne = load_luma(pos + vec2<i32>(1, -1));
+    let luma_sw = load_luma(pos + vec2<i32>(-1, 1));
+    let luma_se = load_luma(pos + vec2<i32>(1, 1));
+    let luma_ns = luma_n + luma_s;
+    let luma_we = luma_w + luma_e;
+    let luma_n_corners = luma_nw + luma_ne;
+    let luma_s_corners = luma_sw + luma_se;
+    let luma_w_corners = luma_nw + luma_sw;
+    let luma_e_corners = luma_ne + luma_se;
+
+    // A horizontal edge separates the north and south neighbors.
+    let edge_horizontal = abs(-2.0 * luma_w + luma_w_corners) + abs(-2.0 * luma_center + luma_ns) * 2.0 + abs(-2.0 * luma_e + luma_e_corners);
+    let edge_vertical = abs(-2.0 * luma_n + luma_n_corners) + abs(-2.0 * luma_center + luma_we) * 2.0 + abs(-2.0 * luma_s + luma_s_corners);
+    let is_horizontal = edge_horizontal >= edge_vertical;
+
+    let luma_1 = select(luma_w, luma_n, is_horizontal);
+    let luma_2 = select(luma_e, luma_s, is_horizontal);
+    let gradient_1 = luma_1 - luma_center;
+    let gradient_2 = luma_2 - luma_center;
+    let is_1_steepest = abs(gradient_1) >= abs(gradient_2);
+    let gradient_scaled = 0.25 * max(abs(gradient_1), abs(gradient_2));
+
+    // Move half a texel onto the edge, towards the steeper side.
+    var step_length = select(inverse_size.x, inverse_size.y, is_horizontal);
+    var luma_local_average: f32;
+    if is_1_steepest {
+        step_length = -step_length;
+        luma_local_average = 0.5 * (luma_1 + luma_center);
+    } else {
+        luma_local_average = 0.5 * (luma_2 + luma_center);
+    }
+    var edge_uv = uv;
+    if is_horizontal {
+        edge_uv.y += step_length * 0.5;
+    } else {
+        edge_uv.x += step_length * 0.5;
+    }
+
+    // Search along the edge in both directions until the contrast changes.
+    let offset = select(vec2<f32>(0.0, inverse_size.y), vec2<f32>(inverse_size.x, 0.0), is_horizontal);
+    var uv_1 = edge_uv - offset;
+    var uv_2 = edge_uv + offset;
+    var luma_end_1 = sample_luma(uv_1) - luma_local_average;
+    var luma_end_2 = sample_luma(uv_2) - luma_local_average;
+    var reached_1 = abs(luma_end_1) >= gradient_scaled;
+    var reached_2 = abs(luma_end_2) >= gradient_scaled;
+    for (var i = 0; i < SEARCH_STEPS && !(reached_1 && reached_2); i++) {
+        if !reached_1 {
+            uv_1 -= offset * SEARCH_STEP_SIZES[i];
+            luma_end_1 = sample_luma(uv_1) - luma_local_average;
+            reached_1 = abs(luma_end_1) >= gradient_scaled;
+        }
+        if !reached_2 {
+            uv_2 += offset * SEARCH_STEP_SIZES[i];
+            luma_end_2 = sample_luma(uv_2) - luma_local_average;
+            reached_2 = abs(luma_end_2) >= gradient_scaled;
+        }
+    }
+
+    let distance_1 = select(uv.y - uv_1.y, uv.x - uv_1.x, is_horizontal);
+    let distance_2 = select(uv_2.y - uv.y, uv_2.x - uv.x, is_horizontal);
+    let is_direction_1 = distance_1 < distance_2;
+    let distance_final = min(distance_1, distance_2);
+    let edge_length = distance_1 + distance_2;
+    let pixel_offset = 0.5 - distance_final / edge_length;
+    // Only blend if the end of the edge that is closer has the expected contrast.
+    let is_luma_center_smaller = luma_center < luma_local_average;
+    let correct_variation = (select(luma_end_2, luma_end_1, is_direction_1) < 0.0) != is_luma_center_smaller;
+    var final_offset = select(0.0, pixel_offset, correct_variation);
+
+    // Sub-pixel aliasing (such as thin lines) that the edge search misses.
+    let luma_average = (1.0 / 12.0) * (2.0 * (luma_ns + luma_we) + luma_w_corners + luma_e_corners);
+    let subpixel_1 = clamp(abs(luma_average - luma_center) / luma_range, 0.0, 1.0);
+    let subpixel_2 = (-2.0 * subpixel_1 + 3.0) * subpixel_1 * subpixel_1;
+    final_offset = max(final_offset, subpixel_2 * subpixel_2 * SUBPIXEL_QUALITY);
+
+    var final_uv = uv;
+    if is_horizontal {
+        final_uv.y += final_offset * step_length;
+    } else {
+        final_uv.x += final_offset * step_length;
+    }
+    textureStore(output_texture, global_id.xy, textureSampleLevel(input_texture, input_sampler, final_uv, 0.0));
+}
@@ -0,0 +1,95 @@
+// The ground grid, see gridzmo.rs. Drawn as a fullscreen triangle after the raytrace result, each pixel
+// intersects its camera ray with the plane and is hidden if the raytracer hit something closer.
+
+struct GridParams {
+    inverse_view_projection: mat4x4<f32>,
+    camera_position: vec3<f32>,
+    // The Y coordinate of the plane.
+    height: f32,
+    // The distance between minor lines. Major lines are `MAJOR_STEP` minor lines apart.
+    spacing: f32,
+    // How far the minor lines have faded out towards the next spacing (0..1).
+    level_blend: f32,
+    // The horizontal distance at which the grid has faded out.
+    fade_distance: f32,
+    // In pixels.
+    line_width: f32,
+    minor_color: vec4<f32>,
+    major_color: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> params: GridParams;
+// The raytracer's G-buffer (see `GpuRaytraceResult::hit_bind_group`). Only the distance is used.
+@group(1) @binding(1) var hit_normal_distance: texture_2d<f32>;
+
+const MAJOR_STEP: f32 = 10.0;
+const X_AXIS_COLOR: vec3<f32> = vec3<f32>(0.9, 0.2, 0.2);
+const Z_AXIS_COLOR: vec3<f32> = vec3<f32>(0.2, 0.4, 0.9);
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// A single triangle that covers the whole screen.
+@vertex
+fn vertex_main(@builtin(vertex_index) vi: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vi << 1u) & 2u), f32(vi & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+fn unproject(ndc: vec3<f32>) -> vec3<f32> {
+    let position = params.inverse_view_projection * vec4<f32>(ndc, 1.0);
+    return position.xyz / position.w;
+}
+
+// The coverage (0..1) of the lines every `spacing` units, `line_width` pixels wide.
+fn grid_lines(coord: vec2<f32>, spacing: f32) -> f32 {
+    let cell = coord / spacing;
+    let pixels = abs(fract(cell - 0.5) - 0.5) / max(fwidth(cell), vec2<f32>(1e-6));
+    return 1.0 - saturate(min(pixels.x, pixels.y) / params.line_width);
+}
+
+// The coverage of the line along one axis, where `offset` is the distance from the axis.
+fn axis_line(offset: f32) -> f32 {
+    return 1.0 - saturate(abs(offset) / max(fwidth(offset), 1e-6) / params.line_width);
+}
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let ndc = vec2<f32>(in.uv.x * 2.0 - 1.0, 1.0 - in.uv.y * 2.0);
+    let origin = params.camera_position;
+    let dir = normalize(unproject(vec3<f32>(ndc, 1.0)) - unproject(vec3<f32>(ndc, 0.0)));
+    // The derivatives below need every pixel to get this far, so nothing is discarded until the end.
+    let facing = abs(dir.y) > 1e-5;
+    let distance = select(-1.0, (params.height - origin.y) / dir.y, facing);
+    let point = origin + dir * max(distance, 0.0);
+    let coord = point.xz;
+
+    let minor = grid_lines(coord, params.spacing) * (1.0 - params.level_blend);
+    let major = grid_lines(coord, params.spacing * MAJOR_STEP);
+    var color = mix(params.minor_color, params.major_color, major);
+    color.a = max(params.minor_color.a * minor, params.major_color.a * major);
+    // The X axis runs along Z = 0, the Z axis along X = 0.
+    let x_axis = axis_line(coord.y);
+    let z_axis = axis_line(coord.x);
+    color = vec4<f32>(mix(color.rgb, X_AXIS_COLOR, x_axis), max(color.a, x_axis));
+    color = vec4<f32>(mix(color.rgb, Z_AXIS_COLOR, z_axis), max(color.a, z_axis));
+
+    // Fade out with distance, and at grazing angles where the lines turn into noise.
+    let horizontal_distance = length(coord - origin.xz);
+    color.a *= 1.0 - smoothstep(params.fade_distance * 0.5, params.fade_distance, horizontal_distance);
+    color.a *= saturate(abs(dir.y) * 8.0);
+
+    // Hidden behind whatever the raytracer hit first. Both distances are along the normalized camera ray.
+    let size = textureDimensions(hit_normal_distance);
+    let texel = min(vec2<u32>(in.uv * vec2<f32>(size)), size - 1u);
+    let scene_distance = textureLoad(hit_normal_distance, texel, 0).w;
+    if distance <= 0.0 || distance > scene_distance || color.a <= 0.0 {
+        discard;
+    }
+    return color;
+}
@@ -0,0 +1,39 @@
+// World space text (see labels.rs). Unlit, so that labels stay readable in the dark.
+
+@group(0) @binding(0) var<uniform> view_projection: mat4x4<f32>;
+
+@group(1) @binding(0) var array_texture: texture_2d_array<f32>;
+@group(1) @binding(1) var array_texture_sampler: sampler;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) layer: u32,
+    @location(4) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) layer: u32,
+    @location(2) color: vec4<f32>,
+}
+
+@vertex
+fn vertex_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = view_projection * vec4<f32>(in.position, 1.0);
+    out.uv = in.uv;
+    out.layer = in.layer;
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let sample = textureSample(array_texture, array_texture_sampler, in.uv, in.layer) * in.color;
+    if sample.a <= 0.0 {
+        discard;
+    }
+    return sample;
+}
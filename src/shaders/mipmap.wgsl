@@ -0,0 +1,32 @@
+// Downsamples one mip level into the next. The source is sampled halfway between four texels, so
+// the linear sampler averages them. sRGB views decode before filtering and encode when writing,
+// so the average is taken in linear space.
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// A single triangle that covers the whole target.
+@vertex
+fn vertex_main(
+    @builtin(vertex_index) vi: u32
+) -> VertexOutput {
+    let uv = vec2<f32>(f32((vi << 1u) & 2u), f32(vi & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@fragment
+fn fragment_main(
+    in: VertexOutput,
+) -> @location(0) vec4<f32> {
+    return textureSample(source, source_sampler, in.uv);
+}
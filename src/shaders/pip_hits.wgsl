@@ -0,0 +1,54 @@
+// The raytracer's hits as a point cloud, seen from the picture-in-picture camera (see pip.rs). Every
+// `stride`th G-buffer texel is a point at the hit position along its primary ray, colored by the normal.
+
+struct HitCloudParams {
+    // Of the main camera, which the G-buffer was traced from.
+    inverse_view_projection: mat4x4<f32>,
+    // Of the picture-in-picture camera.
+    view_projection: mat4x4<f32>,
+    camera_position: vec3<f32>,
+    stride: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: HitCloudParams;
+@group(1) @binding(0) var hit_id: texture_2d<u32>;
+@group(1) @binding(1) var hit_normal_distance: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+fn unproject(ndc: vec3<f32>) -> vec3<f32> {
+    let position = params.inverse_view_projection * vec4<f32>(ndc, 1.0);
+    return position.xyz / position.w;
+}
+
+@vertex
+fn vertex_main(@builtin(vertex_index) vi: u32) -> VertexOutput {
+    let size = textureDimensions(hit_normal_distance);
+    let columns = (size.x + params.stride - 1u) / params.stride;
+    let texel = vec2<u32>(vi % columns, vi / columns) * params.stride;
+    var out: VertexOutput;
+    // Misses are moved outside of the clip volume.
+    out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+    out.color = vec4<f32>(0.0);
+    if any(texel >= size) || textureLoad(hit_id, texel, 0).x == 0u {
+        return out;
+    }
+    let normal_distance = textureLoad(hit_normal_distance, texel, 0);
+    let uv = (vec2<f32>(texel) + 0.5) / vec2<f32>(size);
+    let ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+    let dir = normalize(unproject(vec3<f32>(ndc, 1.0)) - unproject(vec3<f32>(ndc, 0.0)));
+    let position = params.camera_position + dir * normal_distance.w;
+    out.clip_position = params.view_projection * vec4<f32>(position, 1.0);
+    // Hits without a normal (from inside a block, or entities) are gray.
+    let normal = normal_distance.xyz;
+    out.color = vec4<f32>(select(vec3<f32>(0.5), normal * 0.5 + 0.5, dot(normal, normal) > 0.0), 1.0);
+    return out;
+}
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
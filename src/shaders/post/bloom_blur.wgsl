@@ -0,0 +1,20 @@
+struct BlurParams {
+    // (1, 0) for the horizontal pass, (0, 1) for the vertical pass.
+    direction: vec2<f32>,
+}
+
+@group(1) @binding(0) var<uniform> params: BlurParams;
+
+// A 9 tap gaussian kernel, the center weight first.
+const WEIGHTS: array<f32, 5> = array<f32, 5>(0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel_step = params.direction / vec2<f32>(textureDimensions(input_texture));
+    var color = sample_input(in.uv).rgb * WEIGHTS[0];
+    for (var i = 1; i < 5; i++) {
+        let offset = texel_step * f32(i);
+        color += (sample_input(in.uv + offset).rgb + sample_input(in.uv - offset).rgb) * WEIGHTS[i];
+    }
+    return vec4<f32>(color, 1.0);
+}
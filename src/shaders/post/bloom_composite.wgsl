@@ -0,0 +1,17 @@
+struct BloomParams {
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+    radius: f32,
+}
+
+@group(1) @binding(0) var bloom_texture: texture_2d<f32>;
+@group(1) @binding(1) var bloom_sampler: sampler;
+@group(2) @binding(0) var<uniform> params: BloomParams;
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = sample_input(in.uv);
+    let bloom = textureSample(bloom_texture, bloom_sampler, in.uv).rgb;
+    return vec4<f32>(color.rgb + bloom * params.intensity, color.a);
+}
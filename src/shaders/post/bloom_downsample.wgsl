@@ -0,0 +1,10 @@
+// Averages a 4x4 block of texels with four bilinear samples.
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(input_texture));
+    let a = sample_input(in.uv + vec2<f32>(-texel.x, -texel.y)).rgb;
+    let b = sample_input(in.uv + vec2<f32>(texel.x, -texel.y)).rgb;
+    let c = sample_input(in.uv + vec2<f32>(-texel.x, texel.y)).rgb;
+    let d = sample_input(in.uv + vec2<f32>(texel.x, texel.y)).rgb;
+    return vec4<f32>((a + b + c + d) * 0.25, 1.0);
+}
@@ -0,0 +1,30 @@
+struct BloomParams {
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+    radius: f32,
+}
+
+@group(1) @binding(0) var<uniform> params: BloomParams;
+
+// Averages a 4x4 block of texels with four bilinear samples.
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(input_texture));
+    let a = sample_input(uv + vec2<f32>(-texel.x, -texel.y)).rgb;
+    let b = sample_input(uv + vec2<f32>(texel.x, -texel.y)).rgb;
+    let c = sample_input(uv + vec2<f32>(-texel.x, texel.y)).rgb;
+    let d = sample_input(uv + vec2<f32>(texel.x, texel.y)).rgb;
+    return (a + b + c + d) * 0.25;
+}
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = downsample(in.uv);
+    let brightness = max(color.r, max(color.g, color.b));
+    // Quadratic falloff below the threshold, within the knee.
+    let knee = params.threshold * params.knee + 0.00001;
+    var soft = clamp(brightness - params.threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee);
+    let contribution = max(soft, brightness - params.threshold) / max(brightness, 0.00001);
+    return vec4<f32>(color * contribution, 1.0);
+}
@@ -0,0 +1,24 @@
+struct BloomParams {
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+    radius: f32,
+}
+
+@group(1) @binding(0) var<uniform> params: BloomParams;
+
+// A 3x3 tent filter over the smaller level. The alpha is the blend weight over the larger level.
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(input_texture));
+    var color = sample_input(in.uv).rgb * 4.0;
+    color += sample_input(in.uv + vec2<f32>(-texel.x, 0.0)).rgb * 2.0;
+    color += sample_input(in.uv + vec2<f32>(texel.x, 0.0)).rgb * 2.0;
+    color += sample_input(in.uv + vec2<f32>(0.0, -texel.y)).rgb * 2.0;
+    color += sample_input(in.uv + vec2<f32>(0.0, texel.y)).rgb * 2.0;
+    color += sample_input(in.uv + vec2<f32>(-texel.x, -texel.y)).rgb;
+    color += sample_input(in.uv + vec2<f32>(texel.x, -texel.y)).rgb;
+    color += sample_input(in.uv + vec2<f32>(-texel.x, texel.y)).rgb;
+    color += sample_input(in.uv + vec2<f32>(texel.x, texel.y)).rgb;
+    return vec4<f32>(color / 16.0, params.radius);
+}
@@ -0,0 +1,5 @@
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return sample_input(in.uv);
+}
@@ -0,0 +1,85 @@
+
+struct ExposureParams {
+    key: f32,
+    min_exposure: f32,
+    max_exposure: f32,
+    adaptation_rate: f32,
+    delta_time: f32,
+    partial_count: u32,
+}
+
+struct ExposureState {
+    exposure: f32,
+    average_luminance: f32,
+}
+
+@group(1) @binding(0) var<uniform> params: ExposureParams;
+// The log luminance sum and the texel count of each workgroup of `reduce_main`.
+@group(1) @binding(1) var<storage, read_write> partials: array<vec2<f32>>;
+@group(1) @binding(2) var<storage, read_write> state: ExposureState;
+
+// Matches `SAMPLE_STRIDE` and `REDUCE_WORKGROUP_SIZE` in exposure.rs.
+const SAMPLE_STRIDE: u32 = 4u;
+const REDUCE_WORKGROUP_SIZE: u32 = 16u;
+const ADAPT_WORKGROUP_SIZE: u32 = 256u;
+// Darker texels are counted as this, so that black areas don't pull the log average to negative infinity.
+const MIN_LUMINANCE: f32 = 1e-4;
+
+var<workgroup> sums: array<vec2<f32>, 256>;
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// Sums `sums` into `sums[0]`.
+fn reduce_workgroup(local_index: u32) {
+    for (var stride = 128u; stride > 0u; stride >>= 1u) {
+        workgroupBarrier();
+        if local_index < stride {
+            sums[local_index] += sums[local_index + stride];
+        }
+    }
+    workgroupBarrier();
+}
+
+@compute @workgroup_size(REDUCE_WORKGROUP_SIZE, REDUCE_WORKGROUP_SIZE)
+fn reduce_main(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+    @builtin(num_workgroups) num_workgroups: vec3<u32>,
+) {
+    let texel = global_id.xy * SAMPLE_STRIDE + SAMPLE_STRIDE / 2u;
+    var sum = vec2<f32>(0.0);
+    if all(texel < textureDimensions(input_texture)) {
+        let color = textureLoad(input_texture, texel, 0).rgb;
+        sum = vec2<f32>(log2(max(luminance(color), MIN_LUMINANCE)), 1.0);
+    }
+    sums[local_index] = sum;
+    reduce_workgroup(local_index);
+    if local_index == 0u {
+        partials[workgroup_id.y * num_workgroups.x + workgroup_id.x] = sums[0];
+    }
+}
+
+@compute @workgroup_size(ADAPT_WORKGROUP_SIZE)
+fn adapt_main(@builtin(local_invocation_index) local_index: u32) {
+    var sum = vec2<f32>(0.0);
+    for (var index = local_index; index < params.partial_count; index += ADAPT_WORKGROUP_SIZE) {
+        sum += partials[index];
+    }
+    sums[local_index] = sum;
+    reduce_workgroup(local_index);
+    if local_index == 0u {
+        let average = exp2(sums[0].x / max(sums[0].y, 1.0));
+        let target_exposure = clamp(params.key / average, params.min_exposure, params.max_exposure);
+        var exposure = target_exposure;
+        // The first frame starts at the target. After that, the exposure adapts in stops.
+        if state.exposure > 0.0 {
+            let blend = 1.0 - exp(-params.delta_time * params.adaptation_rate);
+            exposure = exp2(mix(log2(state.exposure), log2(target_exposure), blend));
+        }
+        state.exposure = exposure;
+        state.average_luminance = average;
+    }
+}
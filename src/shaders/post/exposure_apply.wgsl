@@ -0,0 +1,14 @@
+
+// Written by `adapt_main` in exposure.wgsl.
+struct ExposureState {
+    exposure: f32,
+    average_luminance: f32,
+}
+
+@group(1) @binding(0) var<storage, read> state: ExposureState;
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = sample_input(in.uv);
+    return vec4<f32>(color.rgb * state.exposure, color.a);
+}
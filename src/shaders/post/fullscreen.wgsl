@@ -0,0 +1,23 @@
+// Shared by every post effect. The effect's fragment shader is appended to this file.
+
+@group(0) @binding(0) var input_texture: texture_2d<f32>;
+@group(0) @binding(1) var input_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// A single triangle that covers the whole screen.
+@vertex
+fn vertex_main(@builtin(vertex_index) vi: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vi << 1u) & 2u), f32(vi & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+fn sample_input(uv: vec2<f32>) -> vec4<f32> {
+    return textureSample(input_texture, input_sampler, uv);
+}
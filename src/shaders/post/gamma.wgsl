@@ -0,0 +1,12 @@
+
+struct GammaParams {
+    gamma: f32,
+}
+
+@group(1) @binding(0) var<uniform> params: GammaParams;
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = sample_input(in.uv);
+    return vec4<f32>(pow(max(color.rgb, vec3<f32>(0.0)), vec3<f32>(1.0 / params.gamma)), color.a);
+}
@@ -0,0 +1,40 @@
+
+struct TonemapParams {
+    exposure: f32,
+    tonemap_operator: u32,
+}
+
+@group(1) @binding(0) var<uniform> params: TonemapParams;
+
+const OPERATOR_NONE: u32 = 0;
+const OPERATOR_REINHARD: u32 = 1;
+const OPERATOR_ACES: u32 = 2;
+
+// Narkowicz's fit of the ACES filmic curve.
+fn aces(x: vec3<f32>) -> vec3<f32> {
+    const A: f32 = 2.51;
+    const B: f32 = 0.03;
+    const C: f32 = 2.43;
+    const D: f32 = 0.59;
+    const E: f32 = 0.14;
+    return clamp((x * (A * x + B)) / (x * (C * x + D) + E), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = sample_input(in.uv);
+    let exposed = color.rgb * params.exposure;
+    var mapped: vec3<f32>;
+    switch params.tonemap_operator {
+        case OPERATOR_REINHARD: {
+            mapped = exposed / (exposed + vec3<f32>(1.0));
+        }
+        case OPERATOR_ACES: {
+            mapped = aces(exposed);
+        }
+        default: {
+            mapped = exposed;
+        }
+    }
+    return vec4<f32>(mapped, color.a);
+}
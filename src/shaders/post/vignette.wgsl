@@ -0,0 +1,18 @@
+
+struct VignetteParams {
+    color: vec3<f32>,
+    intensity: f32,
+    radius: f32,
+    smoothness: f32,
+}
+
+@group(1) @binding(0) var<uniform> params: VignetteParams;
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = sample_input(in.uv);
+    // 0.0 in the center, 1.0 in the corners.
+    let distance = length(in.uv - 0.5) * sqrt(2.0);
+    let amount = smoothstep(params.radius - params.smoothness, params.radius, distance) * params.intensity;
+    return vec4<f32>(mix(color.rgb, params.color, amount), color.a);
+}
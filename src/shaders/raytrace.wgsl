@@ -1,11 +1,134 @@
 // 64x64x64 = 262144
 // 1mib
 
-@group(0) @binding(0) var raycast_result: texture_storage_2d<rgba8unorm, write>;
+@group(0) @binding(0) var raycast_result: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(1) var accumulation: texture_storage_2d<rgba32float, read_write>;
+// The G-buffer: the block ID, and the face normal and distance of the first surface along the primary ray.
+@group(0) @binding(2) var hit_id: texture_storage_2d<r32uint, write>;
+@group(0) @binding(3) var hit_normal_distance: texture_storage_2d<rgba16float, write>;
 @group(1) @binding(0) var directions: texture_storage_2d<rgba32float, read>;
+@group(1) @binding(1) var<uniform> ndc_mult: vec2<f32>;
 @group(2) @binding(0) var<uniform> camera: Camera;
 @group(2) @binding(1) var<storage, read> voxel_chunk: array<u32>;
 @group(2) @binding(2) var<uniform> lighting: Lighting;
+@group(2) @binding(3) var<uniform> config: RaytraceConfig;
+@group(2) @binding(4) var<storage, read> materials: array<Material>;
+@group(2) @binding(5) var<uniform> fog: Fog;
+@group(2) @binding(6) var<uniform> sky_tint: vec4<f32>;
+@group(2) @binding(7) var sky_cubemap: texture_cube<f32>;
+@group(2) @binding(8) var sky_sampler: sampler;
+// The first `config.entity_count` slots are used, see `trace_entities`.
+@group(2) @binding(9) var<storage, read> entities: array<Entity>;
+// The first `config.point_light_count` slots are used, see `point_light`.
+@group(2) @binding(10) var<storage, read> point_lights: array<PointLight>;
+// One packed [red, green, blue, opaque] cell per block, see block_light.rs.
+@group(2) @binding(11) var<storage, read> block_light_volume: array<u32>;
+@group(2) @binding(12) var<uniform> clouds: Clouds;
+// See frame_uniforms.rs.
+@group(2) @binding(13) var<uniform> frame_uniforms: FrameUniforms;
+@group(3) @binding(0) var blue_noise_texture: texture_2d<f32>;
+@group(3) @binding(1) var<uniform> noise_frame: NoiseFrame;
+
+// Size: 16
+struct NoiseFrame {
+    // Moves the noise every frame so that accumulated frames aren't correlated.
+    offset: vec2<u32>, // 0..8
+    frame: u32,        // 8..12
+    size: u32,         // 12..16
+}
+
+// The pixel being traced, for sampling the blue noise.
+var<private> current_texel: vec2<u32>;
+// The first surface along the primary ray, set by `trace_color` for the G-buffer.
+var<private> primary_hit: RayHit;
+// Set when the pixel sees an animated liquid surface, which would smear if it were accumulated.
+var<private> animated: bool;
+
+// Size: 48
+struct RaytraceConfig {
+    shadow_samples: u32,       // 0..4
+    light_angular_radius: f32, // 4..8
+    max_bounces: u32,          // 8..12
+    // The number of previous frames blended into the accumulation texture.
+    accumulated_frames: u32,   // 12..16
+    // Primary rays that enter the chunk beyond this distance use the LODs. 0.0 disables them.
+    lod_distance: f32,         // 16..20
+    // One of the SKY_* constants.
+    sky_mode: u32,             // 20..24
+    entity_count: u32,         // 24..28
+    point_light_count: u32,    // 28..32
+    // One of the DEBUG_VIEW_* constants.
+    debug_view: u32,           // 32..36
+    // The step count at the top of the heatmap's ramp.
+    heatmap_max_steps: u32,    // 36..40
+    // The number of steps of the volumetric light's march. 0 disables it.
+    volumetric_steps: u32,     // 40..44
+    // The extinction of the medium that scatters the volumetric light, per block.
+    volumetric_density: f32,   // 44..48
+}
+
+const DEBUG_VIEW_OFF: u32 = 0u;
+// The DDA steps of every ray of the pixel (primary, shadow, reflection and refraction rays) as a color ramp.
+const DEBUG_VIEW_STEP_HEATMAP: u32 = 1u;
+
+// The DDA steps taken by the rays of the current pixel.
+var<private> dda_steps: u32;
+
+const SKY_TRANSPARENT: u32 = 0u;
+const SKY_CUBEMAP: u32 = 1u;
+const SKY_PROCEDURAL: u32 = 2u;
+
+// Size: 32
+struct Clouds {
+    // How far the wind has moved the clouds, in blocks.
+    offset: vec2<f32>,   // 0..8
+    // 0.0 is a clear sky, 1.0 is overcast.
+    coverage: f32,       // 8..12
+    // The height of the cloud layer.
+    altitude: f32,       // 12..16
+    // The size of the largest features of the noise, in blocks.
+    scale: f32,          // 16..20
+    on: u32,             // 20..24
+    _padding: vec2<u32>, // 24..32
+}
+
+// Size: 32
+struct FrameUniforms {
+    // Seconds since the first frame, wrapped every hour.
+    time: f32,             // 0..4
+    delta_time: f32,       // 4..8
+    frame: u32,            // 8..12
+    _padding: u32,         // 12..16
+    resolution: vec2<f32>, // 16..24
+    // The camera's sub-pixel offset, in pixels.
+    jitter: vec2<f32>,     // 24..32
+}
+
+// Size: 32
+struct Fog {
+    color: vec4<f32>,     // 0..16
+    start: f32,           // 16..20
+    end: f32,             // 20..24
+    height_density: f32,  // 24..28
+    height_falloff: f32,  // 28..32
+}
+
+// Size: 48
+struct Material {
+    color: vec3<f32>,  // 0..12
+    reflectivity: f32, // 12..16
+    emission: f32,     // 16..20
+    // 0.0 is opaque.
+    translucency: f32, // 20..24
+    ior: f32,          // 24..28
+    absorption: f32,   // 28..32
+    // The top faces ripple with waves, see `liquid_normal`.
+    liquid: u32,       // 32..36
+    // 12 bytes padding
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+}
 
 // Size: 48
 struct DirectionalLight {
@@ -28,8 +151,23 @@ struct AmbientLight {
     _pad0: u32,
     intensity: f32,           // 16..20
     on: u32,             // 20..24
-    // 8 bytes padding
-    _pad1: vec2<u32>,
+    ao_strength: f32,    // 24..28
+    // 4 bytes padding
+    _pad1: u32,
+}
+
+// Size: 48
+struct PointLight {
+    position: vec3<f32>, //  0..12
+    // 0.0 for empty slots.
+    radius: f32,         // 12..16
+    color: vec3<f32>,    // 16..28
+    intensity: f32,      // 28..32
+    shadows: u32,        // 32..36
+    // 12 bytes padding
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
 }
 
 // Size: 80
@@ -55,15 +193,63 @@ fn detect_edge(hit_fract: vec2<f32>) -> bool {
 const SMIDGEN: vec3<f32> = vec3<f32>(1e-4);
 const UNSMIDGEN: vec3<f32> = vec3<f32>(1.0 - 1e-4);
 
-@compute @workgroup_size(16, 16)
+// Set by the pipeline (see `WorkgroupSize`).
+override WORKGROUP_SIZE_X: u32 = 16u;
+override WORKGROUP_SIZE_Y: u32 = 16u;
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y)
 fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     // (n << 11) == (n * 2048)
     // let index = (y << 11) + x;
-    if any(global_id.xy > SCREENSIZE) {
+    if any(global_id.xy >= textureDimensions(raycast_result)) {
         return;
     }
-    let color = trace_color(global_id.xy);
+    current_texel = global_id.xy;
+    dda_steps = 0u;
+    animated = false;
+    var color = trace_color(global_id.xy);
+    // Entities in front of the first surface are drawn over it.
+    let ray = get_ray(global_id.xy);
+    var distance = select(camera.far, primary_hit.distance, primary_hit.hit);
+    let entity_hit = trace_entities(ray, distance);
+    if entity_hit.hit {
+        color = apply_fog(vec4<f32>(shade_entity(ray, entity_hit), 1.0), ray, entity_hit.distance);
+        primary_hit = RayHit(vec3<i32>(floor(ray.pos + ray.dir * entity_hit.distance)), entity_hit.distance, ENTITY_ID_FLAG | entity_hit.index, NoFace, true);
+        distance = entity_hit.distance;
+    }
+    if config.volumetric_steps > 0u {
+        color = apply_volumetric(color, ray, distance);
+    }
+    if config.debug_view == DEBUG_VIEW_STEP_HEATMAP {
+        color = vec4<f32>(heatmap_color(f32(dda_steps) / f32(max(config.heatmap_max_steps, 1u))), 1.0);
+    } else if config.accumulated_frames > 0u && !animated {
+        let previous = textureLoad(accumulation, global_id.xy);
+        color = mix(previous, color, 1.0 / f32(config.accumulated_frames + 1u));
+    }
+    textureStore(accumulation, global_id.xy, color);
     textureStore(raycast_result, global_id.xy, color);
+    // Misses have the ID 0, no normal, and the far distance.
+    let hit = primary_hit.hit;
+    textureStore(hit_id, global_id.xy, vec4<u32>(select(0u, primary_hit.id, hit), 0u, 0u, 1u));
+    var normal = select(vec3<f32>(0.0), face_normal(primary_hit.face), hit);
+    if entity_hit.hit {
+        normal = entity_hit.normal;
+    }
+    textureStore(hit_normal_distance, global_id.xy, vec4<f32>(normal, select(camera.far, primary_hit.distance, hit)));
+}
+
+// Blue, cyan, green, yellow and red from 0.0 to 1.0. Beyond 1.0 is magenta, so that the pixels that go over
+// the budget stand out.
+fn heatmap_color(t: f32) -> vec3<f32> {
+    if t > 1.0 {
+        return vec3<f32>(1.0, 0.0, 1.0);
+    }
+    let x = max(t, 0.0) * 4.0;
+    return vec3<f32>(
+        clamp(x - 2.0, 0.0, 1.0),
+        clamp(x, 0.0, 1.0) - clamp(x - 3.0, 0.0, 1.0),
+        1.0 - clamp(x - 1.0, 0.0, 1.0),
+    );
 }
 
 fn trace_color(texel: vec2<u32>) -> vec4<f32> {
@@ -80,13 +266,25 @@ fn trace_color(texel: vec2<u32>) -> vec4<f32> {
     var ray = get_ray(texel);
     let coord = vec3<i32>(floor(ray.pos));
     let id = get_block(coord);
+    if id != 0u && get_material(id).translucency > 0.0 {
+        return trace_underwater(ray, id);
+    }
     let solid_block = id == 0;
-    let transparent_color = vec4<f32>(0.0);
     if solid_block {
-        let hit = raycast(ray, camera.near, camera.far, true);
+        let entry = chunk_entry_distance(ray);
+        let exit = chunk_exit_distance(ray);
+        // Rays that only reach the chunk in the opaque fog skip the LODs and the march.
+        if entry < exit && entry >= fog_opaque_distance() {
+            return fog_color(ray);
+        }
+        let hit = raycast_lod(ray, camera.near, primary_max_distance(), true, select_lod(entry));
         if hit.hit {
-            var hit_point = ray.pos + ray.dir * hit.distance;
-            return vec4<f32>(calculate_surf_color(hit.coord, hit_point, hit.face, hit.distance), 1.0);
+            primary_hit = hit;
+            return apply_fog(vec4<f32>(shade_hit(ray, hit), 1.0), ray, hit.distance);
+        }
+        // Stopped by the fog rather than leaving the chunk.
+        if exit > fog_opaque_distance() {
+            return fog_color(ray);
         }
     } else {
         let in_hit = raycast(ray, camera.near, camera.far, false);
@@ -130,18 +328,24 @@ fn trace_color(texel: vec2<u32>) -> vec4<f32> {
                     return vec4<f32>(0.0);
                 }
             }
+            primary_hit = RayHit(hit_coord, in_hit.distance, get_block(hit_coord), hit_face, true);
             let surf_color = calculate_surf_color(hit_coord, hit_point, hit_face, in_hit.distance);
             ray.pos = hit_point;
             let out_hit = raycast(ray, camera.near, camera.far, true);
             if out_hit.hit {
-                let solid_color = calculate_surf_color(out_hit.coord, ray.pos + ray.dir * out_hit.distance, out_hit.face, out_hit.distance);
+                let solid_color = shade_hit(ray, out_hit);
                 let result_rgb = mix(solid_color, surf_color, 0.8);
-                return vec4<f32>(result_rgb, 1.0);
+                return apply_fog(vec4<f32>(result_rgb, 1.0), ray, in_hit.distance);
             } else {
-                return vec4<f32>(surf_color, 0.8);
+                if config.sky_mode == SKY_TRANSPARENT {
+                    return apply_fog(vec4<f32>(surf_color, 0.8), ray, in_hit.distance);
+                }
+                let result_rgb = mix(sky_color(ray.dir), surf_color, 0.8);
+                return apply_fog(vec4<f32>(result_rgb, 1.0), ray, in_hit.distance);
             }
         }
     }
+    let transparent_color = miss_color(ray);
     // if blend_transparent {
     //     let hit = raycast(ray, camera.near, camera.far, false);
     //     var color: vec3<f32>;
@@ -204,7 +408,291 @@ fn trace_color(texel: vec2<u32>) -> vec4<f32> {
     //         ray.pos = ray.pos + ray.dir * hit.distance;
     //     }
     // }
-    return vec4<f32>(0.0);
+    return transparent_color;
+}
+
+// The amount of fog (0..1) between the ray origin and `distance` along the ray.
+fn fog_amount(ray: Ray, distance: f32) -> f32 {
+    var distance_fog = 0.0;
+    if fog.end > fog.start {
+        let fog_interp = saturate((distance - fog.start) / (fog.end - fog.start));
+        distance_fog = smoothstep(0.0, 1.0, circular_in(fog_interp));
+    }
+    var height_fog = 0.0;
+    if fog.height_density > 0.0 {
+        // Exponential height fog integrated along the ray.
+        let falloff = fog.height_falloff;
+        let origin_density = fog.height_density * exp(-falloff * ray.pos.y);
+        let dir_y = ray.dir.y * falloff;
+        var optical_depth = origin_density * distance;
+        if abs(dir_y) > 1e-5 {
+            optical_depth = origin_density * (1.0 - exp(-dir_y * distance)) / dir_y;
+        }
+        height_fog = 1.0 - exp(-max(optical_depth, 0.0));
+    }
+    return 1.0 - (1.0 - distance_fog) * (1.0 - height_fog);
+}
+
+// With an opaque sky, the fog fades into the sky instead. The fog's alpha is how much of its color is
+// mixed into the sky.
+fn fog_color(ray: Ray) -> vec4<f32> {
+    if config.sky_mode == SKY_TRANSPARENT {
+        return fog.color;
+    }
+    return vec4<f32>(mix(sky_color(ray.dir), fog.color.rgb, fog.color.a), 1.0);
+}
+
+fn apply_fog(color: vec4<f32>, ray: Ray, distance: f32) -> vec4<f32> {
+    return mix(color, fog_color(ray), fog_amount(ray, distance));
+}
+
+fn procedural_sky(dir: vec3<f32>) -> vec3<f32> {
+    const ZENITH: vec3<f32> = vec3<f32>(0.2, 0.4, 0.85);
+    const HORIZON: vec3<f32> = vec3<f32>(0.7, 0.8, 0.95);
+    const GROUND: vec3<f32> = vec3<f32>(0.3, 0.28, 0.26);
+    var color: vec3<f32>;
+    if dir.y >= 0.0 {
+        color = mix(HORIZON, ZENITH, sqrt(dir.y));
+    } else {
+        color = mix(HORIZON, GROUND, sqrt(-dir.y));
+    }
+    if lighting.directional.on != 0 {
+        let light = lighting.directional;
+        let to_light = -normalize(light.direction);
+        let cos_angle = dot(dir, to_light);
+        // Glow around the light, strongest near the horizon.
+        let glow = pow(max(cos_angle, 0.0), 8.0) * (1.0 - abs(dir.y));
+        color += light.color * glow * 0.4;
+        let radius = max(config.light_angular_radius, 0.005);
+        let disc = smoothstep(cos(radius * 1.5), cos(radius), cos_angle);
+        color += light.color * light.intensity * disc * 4.0;
+    }
+    return color;
+}
+
+// The clouds fade out between these distances along the cloud layer, towards the horizon.
+const CLOUD_FADE_START: f32 = 600.0;
+const CLOUD_FADE_END: f32 = 2400.0;
+// The range of the noise over which a cloud goes from transparent to opaque, which softens the edges.
+const CLOUD_SOFTNESS: f32 = 0.25;
+
+fn cloud_hash(cell: vec2<i32>) -> f32 {
+    return f32(hash_u32(bitcast<u32>(cell.x) ^ hash_u32(bitcast<u32>(cell.y)))) / 4294967295.0;
+}
+
+// Value noise in 0..1.
+fn cloud_noise(point: vec2<f32>) -> f32 {
+    let cell = vec2<i32>(floor(point));
+    let t = fract(point);
+    let smooth_t = t * t * (3.0 - 2.0 * t);
+    let top = mix(cloud_hash(cell), cloud_hash(cell + vec2<i32>(1, 0)), smooth_t.x);
+    let bottom = mix(cloud_hash(cell + vec2<i32>(0, 1)), cloud_hash(cell + vec2<i32>(1, 1)), smooth_t.x);
+    return mix(top, bottom, smooth_t.y);
+}
+
+// Four octaves of `cloud_noise`, in 0..1.
+fn cloud_density(point: vec2<f32>) -> f32 {
+    var density = 0.0;
+    var amplitude = 0.5;
+    var octave_point = point;
+    for (var i = 0u; i < 4u; i++) {
+        density += cloud_noise(octave_point) * amplitude;
+        // The offset keeps the octaves' lattices from lining up at the origin.
+        octave_point = octave_point * 2.03 + vec2<f32>(17.0, 31.0);
+        amplitude *= 0.5;
+    }
+    return density / 0.9375;
+}
+
+// The cloud layer in the direction `dir` from the camera, as a color and an opacity. See clouds.rs.
+fn cloud_layer(dir: vec3<f32>) -> vec4<f32> {
+    let height = clouds.altitude - camera.position.y;
+    if clouds.on == 0u || dir.y <= 0.0 || height <= 0.0 {
+        return vec4<f32>(0.0);
+    }
+    let distance = height / dir.y;
+    let point = camera.position.xz + dir.xz * distance - clouds.offset;
+    let density = cloud_density(point / clouds.scale);
+    // The more coverage, the less dense the noise has to be to make a cloud.
+    let threshold = 1.0 - clouds.coverage;
+    let opacity = smoothstep(threshold - CLOUD_SOFTNESS * 0.5, threshold + CLOUD_SOFTNESS * 0.5, density);
+    let fade = 1.0 - smoothstep(CLOUD_FADE_START, CLOUD_FADE_END, distance);
+    // Thick clouds are darker underneath.
+    var color = vec3<f32>(mix(1.0, 0.65, saturate((density - threshold) * 2.0)));
+    if lighting.directional.on != 0 {
+        // The thin edges light up around the light.
+        let to_light = -normalize(lighting.directional.direction);
+        let glow = pow(max(dot(dir, to_light), 0.0), 6.0) * (1.0 - opacity * 0.5);
+        color += lighting.directional.color * glow * 0.5;
+    }
+    return vec4<f32>(color * sky_tint.rgb, opacity * fade);
+}
+
+// The sky in the ray's direction, used by every sky mode except for primary misses with SKY_TRANSPARENT.
+fn sky_color(dir: vec3<f32>) -> vec3<f32> {
+    var sky: vec3<f32>;
+    if config.sky_mode == SKY_PROCEDURAL {
+        sky = procedural_sky(dir) * sky_tint.rgb;
+    } else {
+        sky = textureSampleLevel(sky_cubemap, sky_sampler, dir, 0.0).rgb * sky_tint.rgb;
+    }
+    let cloud = cloud_layer(dir);
+    return mix(sky, cloud.rgb, cloud.a);
+}
+
+// The color of a primary ray that doesn't hit anything.
+fn miss_color(ray: Ray) -> vec4<f32> {
+    // Only the clouds are drawn over the raster skybox.
+    if config.sky_mode == SKY_TRANSPARENT {
+        return cloud_layer(ray.dir);
+    }
+    return vec4<f32>(sky_color(ray.dir), 1.0);
+}
+
+fn get_material(id: u32) -> Material {
+    return materials[min(id, arrayLength(&materials) - 1u)];
+}
+
+// How many translucent blocks a ray may pass through before they are treated as opaque.
+const MAX_TRANSLUCENT_LAYERS: u32 = 4u;
+
+// The color that survives `distance` blocks through a translucent material.
+fn transmittance(material: Material, distance: f32) -> vec3<f32> {
+    return exp(-(vec3<f32>(1.0) - material.color) * material.absorption * distance);
+}
+
+// Refracts `dir` into a block with the index of refraction `ior`, from air.
+fn refract_into(dir: vec3<f32>, normal: vec3<f32>, ior: f32) -> vec3<f32> {
+    let refracted = refract(dir, normal, 1.0 / max(ior, 1.0));
+    if all(refracted == vec3<f32>(0.0)) {
+        return dir;
+    }
+    return normalize(refracted);
+}
+
+const LIQUID_WAVE_AMPLITUDE: f32 = 0.03;
+// How fast the waves move, in radians per second.
+const LIQUID_WAVE_SPEED: f32 = 1.5;
+
+// The slope of a wave that travels along `dir` with `frequency` (in radians per block).
+fn wave_slope(point: vec2<f32>, dir: vec2<f32>, frequency: f32, phase: f32) -> vec2<f32> {
+    return dir * (cos(dot(point, dir) * frequency - phase) * frequency * LIQUID_WAVE_AMPLITUDE);
+}
+
+// The normal of a liquid's top face at `point`. A few waves in different directions and at different speeds
+// are summed, so that the ripples don't line up.
+fn liquid_normal(point: vec3<f32>) -> vec3<f32> {
+    let phase = frame_uniforms.time * LIQUID_WAVE_SPEED;
+    var slope = wave_slope(point.xz, vec2<f32>(0.8, 0.6), 2.1, phase);
+    slope += wave_slope(point.xz, vec2<f32>(-0.6, 0.8), 3.3, phase * 1.3);
+    slope += wave_slope(point.xz, vec2<f32>(0.28, -0.96), 5.7, phase * 1.7);
+    return normalize(vec3<f32>(-slope.x, 1.0, -slope.y));
+}
+
+// The color of a primary ray that starts inside a translucent block.
+fn trace_underwater(ray: Ray, medium: u32) -> vec4<f32> {
+    let material = get_material(medium);
+    let exit = raycast_medium(ray, camera.near, camera.far, medium);
+    if !exit.hit {
+        return vec4<f32>(material.color * sky_color(ray.dir), 1.0);
+    }
+    let absorbed = transmittance(material, exit.distance) * material.color;
+    if exit.id != 0u {
+        primary_hit = exit;
+        return apply_fog(vec4<f32>(shade_hit(ray, exit) * absorbed, 1.0), ray, exit.distance);
+    }
+    let exit_cell = vec3<f32>(exit.coord);
+    let exit_point = ray.pos + ray.dir * exit.distance;
+    let out_ray = Ray(clamp(exit_point, exit_cell + SMIDGEN, exit_cell + UNSMIDGEN), ray.dir);
+    let hit = raycast(out_ray, 0.0, camera.far, true);
+    if !hit.hit {
+        return vec4<f32>(sky_color(ray.dir) * absorbed, 1.0);
+    }
+    primary_hit = hit;
+    primary_hit.distance += exit.distance;
+    return apply_fog(vec4<f32>(shade_hit(out_ray, hit) * absorbed, 1.0), ray, exit.distance + hit.distance);
+}
+
+fn face_normal(face: u32) -> vec3<f32> {
+    switch face {
+        case PosX: { return vec3<f32>(1.0, 0.0, 0.0); }
+        case NegX: { return vec3<f32>(-1.0, 0.0, 0.0); }
+        case PosY: { return vec3<f32>(0.0, 1.0, 0.0); }
+        case NegY: { return vec3<f32>(0.0, -1.0, 0.0); }
+        case PosZ: { return vec3<f32>(0.0, 0.0, 1.0); }
+        case NegZ: { return vec3<f32>(0.0, 0.0, -1.0); }
+        default: { return vec3<f32>(0.0); }
+    }
+}
+
+// Shades a solid hit, following reflections for reflective materials up to `config.max_bounces`.
+fn shade_hit(ray: Ray, first_hit: RayHit) -> vec3<f32> {
+    var current_ray = ray;
+    var hit = first_hit;
+    var color = vec3<f32>(0.0);
+    var throughput = vec3<f32>(1.0);
+    var bounce = 0u;
+    var layers = 0u;
+    loop {
+        let hit_point = current_ray.pos + current_ray.dir * hit.distance;
+        let material = get_material(hit.id);
+        let surf_color = calculate_surf_color(hit.coord, hit_point, hit.face, hit.distance) * material.color;
+        if material.translucency > 0.0 && layers < MAX_TRANSLUCENT_LAYERS && hit.face != NoFace {
+            layers += 1u;
+            color += throughput * surf_color * (1.0 - material.translucency);
+            throughput *= material.translucency;
+            // Bend once on the way in, then travel straight through the block (and out of it).
+            var normal = face_normal(hit.face);
+            if material.liquid != 0u && hit.face == PosY {
+                normal = liquid_normal(hit_point);
+                animated = true;
+            }
+            let cell = vec3<f32>(hit.coord);
+            let origin = clamp(hit_point, cell + SMIDGEN, cell + UNSMIDGEN);
+            current_ray = Ray(origin, refract_into(current_ray.dir, normal, material.ior));
+            let exit = raycast_medium(current_ray, 0.0, camera.far, hit.id);
+            if !exit.hit {
+                color += throughput * material.color * sky_color(current_ray.dir);
+                break;
+            }
+            throughput *= transmittance(material, exit.distance);
+            if exit.id != 0u {
+                hit = exit;
+                continue;
+            }
+            let exit_cell = vec3<f32>(exit.coord);
+            let exit_point = current_ray.pos + current_ray.dir * exit.distance;
+            current_ray = Ray(clamp(exit_point, exit_cell + SMIDGEN, exit_cell + UNSMIDGEN), current_ray.dir);
+            hit = raycast(current_ray, 0.0, camera.far, true);
+            if !hit.hit {
+                color += throughput * sky_color(current_ray.dir);
+                break;
+            }
+            continue;
+        }
+        var reflectivity = material.reflectivity;
+        if bounce >= config.max_bounces || hit.face == NoFace {
+            reflectivity = 0.0;
+        }
+        color += throughput * surf_color * (1.0 - reflectivity);
+        color += throughput * material.color * material.emission;
+        if reflectivity <= 0.0 {
+            break;
+        }
+        throughput *= material.color * reflectivity;
+        // Start the reflection ray from inside the empty cell in front of the face.
+        let normal = face_normal(hit.face);
+        let neighbor_cell = vec3<f32>(hit.coord + vec3<i32>(normal));
+        let origin = clamp(hit_point, neighbor_cell + SMIDGEN, neighbor_cell + UNSMIDGEN);
+        current_ray = Ray(origin, reflect(current_ray.dir, normal));
+        hit = raycast(current_ray, 0.0, camera.far, true);
+        if !hit.hit {
+            color += throughput * sky_color(current_ray.dir);
+            break;
+        }
+        bounce += 1u;
+    }
+    return color;
 }
 
 fn calculate_surf_color(
@@ -218,6 +706,9 @@ fn calculate_surf_color(
     var neighbor = coord;
     var hit_point = point;
     var face_fract = vec2<f32>(0.0);
+    // The axes that face_fract is measured along.
+    var tangent = vec3<i32>(0);
+    var bitangent = vec3<i32>(0);
     switch face {
         case PosX: {
             hit_normal = vec3<f32>(1.0, 0.0, 0.0);
@@ -225,6 +716,8 @@ fn calculate_surf_color(
             let neighbor_cell = vec3<f32>(neighbor);
             hit_point = clamp(hit_point, neighbor_cell + SMIDGEN, neighbor_cell + UNSMIDGEN);
             face_fract = fract(hit_point.yz);
+            tangent = vec3<i32>(0, 1, 0);
+            bitangent = vec3<i32>(0, 0, 1);
             color = vec3<f32>(1.0, 0.0, 0.0);
         }
         case NegX: {
@@ -233,6 +726,8 @@ fn calculate_surf_color(
             let neighbor_cell = vec3<f32>(neighbor);
             hit_point = clamp(hit_point, neighbor_cell + SMIDGEN, neighbor_cell + UNSMIDGEN);
             face_fract = fract(hit_point.yz);
+            tangent = vec3<i32>(0, 1, 0);
+            bitangent = vec3<i32>(0, 0, 1);
             color = vec3<f32>(1.0, 1.0, 0.0);
         }
         case PosY: {
@@ -241,6 +736,8 @@ fn calculate_surf_color(
             let neighbor_cell = vec3<f32>(neighbor);
             hit_point = clamp(hit_point, neighbor_cell + SMIDGEN, neighbor_cell + UNSMIDGEN);
             face_fract = fract(hit_point.xz);
+            tangent = vec3<i32>(1, 0, 0);
+            bitangent = vec3<i32>(0, 0, 1);
             color = vec3<f32>(0.0, 1.0, 0.0);
             
             // const CHANMAX: f32 = 31.0;
@@ -258,6 +755,8 @@ fn calculate_surf_color(
             let neighbor_cell = vec3<f32>(neighbor);
             hit_point = clamp(hit_point, neighbor_cell + SMIDGEN, neighbor_cell + UNSMIDGEN);
             face_fract = fract(hit_point.xz);
+            tangent = vec3<i32>(1, 0, 0);
+            bitangent = vec3<i32>(0, 0, 1);
             color = vec3<f32>(0.0, 1.0, 1.0);
         }
         case PosZ: {
@@ -266,6 +765,8 @@ fn calculate_surf_color(
             let neighbor_cell = vec3<f32>(neighbor);
             hit_point = clamp(hit_point, neighbor_cell + SMIDGEN, neighbor_cell + UNSMIDGEN);
             face_fract = fract(hit_point.xy);
+            tangent = vec3<i32>(1, 0, 0);
+            bitangent = vec3<i32>(0, 1, 0);
             color = vec3<f32>(0.0, 0.0, 1.0);
         }
         case NegZ: {
@@ -274,6 +775,8 @@ fn calculate_surf_color(
             let neighbor_cell = vec3<f32>(neighbor);
             hit_point = clamp(hit_point, neighbor_cell + SMIDGEN, neighbor_cell + UNSMIDGEN);
             face_fract = fract(hit_point.xy);
+            tangent = vec3<i32>(1, 0, 0);
+            bitangent = vec3<i32>(0, 1, 0);
             color = vec3<f32>(1.0, 0.0, 1.0);
         }
         case NoFace: {
@@ -290,11 +793,89 @@ fn calculate_surf_color(
     if detect_edge(face_fract) {
         color *= mix(0.1, 1.0, edge_scalar);
     }
+    color *= surface_light(hit_point, hit_normal);
+    if lighting.ambient.ao_strength > 0.0 {
+        let occlusion = voxel_ao(neighbor, tangent, bitangent, face_fract);
+        color *= mix(1.0, occlusion, lighting.ambient.ao_strength);
+    }
+    return color;
+}
+
+// The light that reaches a surface at `point`.
+fn surface_light(point: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    var light = sun_and_ambient_light(point, normal);
+    for (var i = 0u; i < min(config.point_light_count, arrayLength(&point_lights)); i++) {
+        light += point_light(point_lights[i], point, normal);
+    }
+    return light + block_light(point);
+}
+
+// The light from a point light, which fades out smoothly before its radius.
+fn point_light(light: PointLight, point: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    let to_light = light.position - point;
+    let distance = length(to_light);
+    if distance >= light.radius || distance <= 0.0 {
+        return vec3<f32>(0.0);
+    }
+    let dir = to_light / distance;
+    let light_dot = dot(dir, normal);
+    if light_dot <= 0.0 {
+        return vec3<f32>(0.0);
+    }
+    if light.shadows != 0u {
+        let ray = Ray(point, dir);
+        if raycast(ray, 0.0, distance, true).hit || trace_entities(ray, distance).hit {
+            return vec3<f32>(0.0);
+        }
+    }
+    let ratio = distance / light.radius;
+    let window = saturate(1.0 - ratio * ratio * ratio * ratio);
+    let attenuation = window * window / (1.0 + distance * distance);
+    return light.color * light.intensity * attenuation * light_dot;
+}
+
+// How bright the light from emissive blocks is at the highest level.
+const BLOCK_LIGHT_INTENSITY: f32 = 1.5;
+const MAX_LIGHT_LEVEL: f32 = 15.0;
+
+// The light from emissive blocks at `point`, blended between the centers of the cells around it.
+// Opaque cells are left out of the blend (they are always dark), so surfaces aren't darkened by the
+// block that they belong to.
+fn block_light(point: vec3<f32>) -> vec3<f32> {
+    let position = point - 0.5;
+    let base = vec3<i32>(floor(position));
+    let f = fract(position);
+    var light = vec3<f32>(0.0);
+    var total_weight = 0.0;
+    for (var i = 0; i < 8; i++) {
+        let corner = vec3<i32>(i & 1, (i >> 1) & 1, i >> 2);
+        let cell = base + corner;
+        if u32(cell.x | cell.y | cell.z) >= 64u {
+            continue;
+        }
+        let levels = unpack4x8unorm(block_light_volume[u32(cell.y * 4096 + cell.z * 64 + cell.x)]);
+        if levels.a > 0.0 {
+            continue;
+        }
+        let weights = mix(1.0 - f, f, vec3<f32>(corner));
+        let weight = weights.x * weights.y * weights.z;
+        light += levels.rgb * weight;
+        total_weight += weight;
+    }
+    if total_weight <= 0.0 {
+        return vec3<f32>(0.0);
+    }
+    // The levels are stored from 0 to MAX_LIGHT_LEVEL, which unpacks to 0..MAX_LIGHT_LEVEL / 255.
+    let level = light / total_weight * (255.0 / MAX_LIGHT_LEVEL);
+    return level * level * BLOCK_LIGHT_INTENSITY;
+}
+
+// The directional and ambient light that reaches a surface at `point`.
+fn sun_and_ambient_light(point: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
     if lighting.directional.on != 0 {
         let inv_light = -normalize(lighting.directional.direction);
-        let light_ray = Ray(hit_point, inv_light);
-        let light_hit = raycast(light_ray, 0.0, 112.0, true);
-        let light_dot = max(0.0, dot(inv_light, hit_normal));
+        let visibility = shadow_visibility(point, inv_light);
+        let light_dot = max(0.0, dot(inv_light, normal));
         let day_dot = max(0.0, dot(inv_light, UP));
         // let directional_intensity = mix(lighting.directional.evening_intensity, lighting.directional.intensity, circular_out(day_dot));
         let directional_intensity = lighting.directional.intensity;
@@ -302,24 +883,266 @@ fn calculate_surf_color(
         var light: vec3<f32>;
         if bool(lighting.ambient.on) {
             let ambient = lighting.ambient.color * lighting.ambient.intensity;
-            if light_hit.hit {
-                light = ambient;
-            } else {
-                light = mix(ambient, directional_color, circular_out(light_dot));
-            }
+            let lit = mix(ambient, directional_color, circular_out(light_dot));
+            light = mix(ambient, lit, visibility);
         } else {
-            if light_hit.hit {
-                light = vec3<f32>(lighting.directional.shadow);
-            } else {
-                light = directional_color * light_dot;
-                light = mix(vec3<f32>(lighting.directional.shadow), light, circular_out(light_dot));
-            }
+            let shadow = vec3<f32>(lighting.directional.shadow);
+            let lit = mix(shadow, directional_color * light_dot, circular_out(light_dot));
+            light = mix(shadow, lit, visibility);
         }
-        color *= light;
+        return light;
     } else if bool(lighting.ambient.on) {
-        color *= lighting.ambient.color * lighting.ambient.intensity;
+        return lighting.ambient.color * lighting.ambient.intensity;
     }
-    return color;
+    return vec3<f32>(1.0);
+}
+
+const MAX_SHADOW_SAMPLES: u32 = 32u;
+const GOLDEN_ANGLE: f32 = 2.39996323;
+const SHADOW_DISTANCE: f32 = 112.0;
+
+// The `dimension` arguments of `blue_noise`, one for each use of the noise.
+const NOISE_SHADOW_ROTATION: u32 = 0u;
+const NOISE_VOLUMETRIC_JITTER: u32 = 1u;
+
+fn hash_u32(value: u32) -> u32 {
+    // PCG hash
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// A blue noise value in 0..1 for the current pixel and frame. Each `dimension` reads the texture at a
+// different offset, so that values used for different purposes aren't correlated.
+fn blue_noise(dimension: u32) -> f32 {
+    let hash = hash_u32(dimension);
+    let offset = noise_frame.offset + vec2<u32>(hash, hash >> 16u);
+    let texel = (current_texel + offset) % noise_frame.size;
+    return textureLoad(blue_noise_texture, texel, 0).r;
+}
+
+// The fraction of shadow rays toward the light (a cone of `config.light_angular_radius`) that
+// escape the chunk. With a single sample or a zero radius, this is a hard shadow test.
+fn shadow_visibility(origin: vec3<f32>, inv_light: vec3<f32>) -> f32 {
+    let samples = clamp(config.shadow_samples, 1u, MAX_SHADOW_SAMPLES);
+    if samples == 1u || config.light_angular_radius <= 0.0 {
+        let ray = Ray(origin, inv_light);
+        let hit = raycast(ray, 0.0, SHADOW_DISTANCE, true);
+        return select(1.0, 0.0, hit.hit || trace_entities(ray, SHADOW_DISTANCE).hit);
+    }
+    // Orthonormal basis around the light direction.
+    var helper = vec3<f32>(0.0, 1.0, 0.0);
+    if abs(inv_light.y) > 0.99 {
+        helper = vec3<f32>(1.0, 0.0, 0.0);
+    }
+    let tangent = normalize(cross(helper, inv_light));
+    let bitangent = cross(inv_light, tangent);
+    let cone_radius = tan(config.light_angular_radius);
+    // Rotate the sample spiral per pixel (and per frame, for accumulation) to trade banding for noise.
+    let rotation = blue_noise(NOISE_SHADOW_ROTATION) * 6.28318531;
+    var unblocked = 0u;
+    for (var i = 0u; i < samples; i++) {
+        // Vogel disk sampling
+        let r = sqrt((f32(i) + 0.5) / f32(samples)) * cone_radius;
+        let theta = f32(i) * GOLDEN_ANGLE + rotation;
+        let dir = normalize(inv_light + (tangent * cos(theta) + bitangent * sin(theta)) * r);
+        let ray = Ray(origin, dir);
+        let hit = raycast(ray, 0.0, SHADOW_DISTANCE, true);
+        if !hit.hit && !trace_entities(ray, SHADOW_DISTANCE).hit {
+            unblocked += 1u;
+        }
+    }
+    return f32(unblocked) / f32(samples);
+}
+
+// How strongly the medium scatters the volumetric light forward (the Henyey-Greenstein `g`), so the light
+// shafts are brightest when looking towards the light.
+const VOLUMETRIC_ANISOTROPY: f32 = 0.6;
+// The volumetric light's march ends here. Further away, the shafts are lost in the fog anyway.
+const VOLUMETRIC_DISTANCE: f32 = 128.0;
+const PI: f32 = 3.14159265;
+
+fn henyey_greenstein(cos_theta: f32, g: f32) -> f32 {
+    let g2 = g * g;
+    return (1.0 - g2) / (4.0 * PI * pow(1.0 + g2 - 2.0 * g * cos_theta, 1.5));
+}
+
+// Marches the primary `ray` through the medium up to `distance`, summing the directional light that each
+// step scatters towards the camera. Steps in the shadow of the chunk don't scatter any light, which is what
+// makes the light shafts. The march starts at a blue noise offset, so that the banding of a few steps turns
+// into noise that the accumulation smooths out. Returns the scattered light and the transmittance.
+fn volumetric_light(ray: Ray, distance: f32) -> vec4<f32> {
+    let steps = config.volumetric_steps;
+    if lighting.directional.on == 0u || config.volumetric_density <= 0.0 {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    let step_length = min(distance, VOLUMETRIC_DISTANCE) / f32(steps);
+    let inv_light = -normalize(lighting.directional.direction);
+    let light = lighting.directional.color * lighting.directional.intensity;
+    let phase = henyey_greenstein(dot(ray.dir, inv_light), VOLUMETRIC_ANISOTROPY);
+    let step_transmittance = exp(-config.volumetric_density * step_length);
+    let jitter = blue_noise(NOISE_VOLUMETRIC_JITTER);
+    var transmittance = 1.0;
+    var scattered = 0.0;
+    for (var i = 0u; i < steps; i++) {
+        let point = ray.pos + ray.dir * ((f32(i) + jitter) * step_length);
+        if !raycast(Ray(point, inv_light), 0.0, SHADOW_DISTANCE, true).hit {
+            // The light scattered over the whole step, rather than at its point.
+            scattered += transmittance * (1.0 - step_transmittance);
+        }
+        transmittance *= step_transmittance;
+    }
+    return vec4<f32>(light * scattered * phase, transmittance);
+}
+
+// Composites the volumetric light in front of `color` (straight alpha), as a layer with the opacity of the
+// medium, so that it also shows over a transparent sky.
+fn apply_volumetric(color: vec4<f32>, ray: Ray, distance: f32) -> vec4<f32> {
+    let volumetric = volumetric_light(ray, distance);
+    let transmittance = volumetric.a;
+    let alpha = 1.0 - transmittance * (1.0 - color.a);
+    if alpha <= 0.0 {
+        return color;
+    }
+    return vec4<f32>((volumetric.rgb + color.rgb * color.a * transmittance) / alpha, alpha);
+}
+
+// Size: 96
+struct Entity {
+    center: vec3<f32>,       //  0..12
+    // One of the ENTITY_* constants.
+    shape: u32,              // 12..16
+    half_extents: vec3<f32>, // 16..28
+    emission: f32,           // 28..32
+    color: vec3<f32>,        // 32..44
+    // 4 bytes padding
+    _pad0: u32,
+    // Rotates world space directions into the entity's space.
+    to_local: mat3x3<f32>,   // 48..96
+}
+
+const ENTITY_NONE: u32 = 0u;
+const ENTITY_BOX: u32 = 1u;
+const ENTITY_BILLBOARD: u32 = 2u;
+// Set on the G-buffer ID of entities, the rest is the entity's index.
+const ENTITY_ID_FLAG: u32 = 0x80000000u;
+
+struct EntityHit {
+    hit: bool,
+    distance: f32,
+    index: u32,
+    normal: vec3<f32>,
+}
+
+// An oriented box, intersected with the slab method in the entity's space.
+fn intersect_entity_box(ray: Ray, entity: Entity) -> EntityHit {
+    var result = EntityHit(false, 0.0, 0u, vec3<f32>(0.0));
+    let origin = entity.to_local * (ray.pos - entity.center);
+    let dir = entity.to_local * ray.dir;
+    let inv_dir = 1.0 / dir;
+    let t0 = (-entity.half_extents - origin) * inv_dir;
+    let t1 = (entity.half_extents - origin) * inv_dir;
+    let t_min = min(t0, t1);
+    let t_max = max(t0, t1);
+    let near = max(max(t_min.x, t_min.y), t_min.z);
+    let far = min(min(t_max.x, t_max.y), t_max.z);
+    if near > far || far < 0.0 || near < 0.0 {
+        return result;
+    }
+    // The normal of the face that the ray entered through, back in world space.
+    var local_normal = vec3<f32>(0.0);
+    if near == t_min.x {
+        local_normal.x = -sign(dir.x);
+    } else if near == t_min.y {
+        local_normal.y = -sign(dir.y);
+    } else {
+        local_normal.z = -sign(dir.z);
+    }
+    result.hit = true;
+    result.distance = near;
+    result.normal = transpose(entity.to_local) * local_normal;
+    return result;
+}
+
+// A round sprite that always faces the ray's origin.
+fn intersect_entity_billboard(ray: Ray, entity: Entity) -> EntityHit {
+    var result = EntityHit(false, 0.0, 0u, vec3<f32>(0.0));
+    let normal = normalize(ray.pos - entity.center);
+    let facing = dot(ray.dir, normal);
+    if facing >= 0.0 {
+        return result;
+    }
+    let distance = dot(entity.center - ray.pos, normal) / facing;
+    if distance < 0.0 {
+        return result;
+    }
+    var helper = UP;
+    if abs(normal.y) > 0.99 {
+        helper = vec3<f32>(1.0, 0.0, 0.0);
+    }
+    let right = normalize(cross(helper, normal));
+    let up = cross(normal, right);
+    let offset = ray.pos + ray.dir * distance - entity.center;
+    let uv = vec2<f32>(dot(offset, right), dot(offset, up)) / entity.half_extents.xy;
+    if dot(uv, uv) > 1.0 {
+        return result;
+    }
+    result.hit = true;
+    result.distance = distance;
+    result.normal = normal;
+    return result;
+}
+
+// The closest entity along the ray, up to `max_distance`.
+fn trace_entities(ray: Ray, max_distance: f32) -> EntityHit {
+    var closest = EntityHit(false, max_distance, 0u, vec3<f32>(0.0));
+    for (var index = 0u; index < config.entity_count; index++) {
+        let entity = entities[index];
+        var hit = EntityHit(false, 0.0, 0u, vec3<f32>(0.0));
+        if entity.shape == ENTITY_BOX {
+            hit = intersect_entity_box(ray, entity);
+        } else if entity.shape == ENTITY_BILLBOARD {
+            hit = intersect_entity_billboard(ray, entity);
+        }
+        if hit.hit && hit.distance < closest.distance {
+            closest = hit;
+            closest.index = index;
+        }
+    }
+    return closest;
+}
+
+fn shade_entity(ray: Ray, hit: EntityHit) -> vec3<f32> {
+    let entity = entities[hit.index];
+    // Lift the point off the surface so that the shadow rays don't hit the entity itself.
+    let point = ray.pos + ray.dir * hit.distance + hit.normal * 1e-3;
+    return entity.color * surface_light(point, hit.normal) + entity.color * entity.emission;
+}
+
+fn is_solid(coord: vec3<i32>) -> f32 {
+    return select(0.0, 1.0, get_block(coord) != 0u);
+}
+
+fn corner_ao(side0: f32, side1: f32, corner: f32) -> f32 {
+    if side0 + side1 == 2.0 {
+        return 0.0;
+    }
+    return (3.0 - (side0 + side1 + corner)) / 3.0;
+}
+
+// Smooth per-corner voxel ambient occlusion.
+// `neighbor` is the (empty) cell in front of the face, and `uv` is the position on the face
+// along `tangent` and `bitangent`.
+fn voxel_ao(neighbor: vec3<i32>, tangent: vec3<i32>, bitangent: vec3<i32>, uv: vec2<f32>) -> f32 {
+    let t0 = is_solid(neighbor - tangent);
+    let t1 = is_solid(neighbor + tangent);
+    let b0 = is_solid(neighbor - bitangent);
+    let b1 = is_solid(neighbor + bitangent);
+    let ao00 = corner_ao(t0, b0, is_solid(neighbor - tangent - bitangent));
+    let ao10 = corner_ao(t1, b0, is_solid(neighbor + tangent - bitangent));
+    let ao01 = corner_ao(t0, b1, is_solid(neighbor - tangent + bitangent));
+    let ao11 = corner_ao(t1, b1, is_solid(neighbor + tangent + bitangent));
+    return mix(mix(ao00, ao10, uv.x), mix(ao01, ao11, uv.x), uv.y);
 }
 
 struct Camera {
@@ -354,7 +1177,18 @@ struct Ray {
 //     return (low | high) != u32(0);
 // }
 
+// Set by the pipeline (see `RayDirectionMode`). When true, the directions are computed here the same
+// way as precompute_rays.wgsl instead of being read from `directions`.
+override INLINE_DIRECTIONS: bool = false;
+
+// The jitter is only set while TAA is on (see taa.rs). The precomputed directions go through the pixel
+// centers, so jittered directions are always computed here.
 fn get_dir(coord: vec2<u32>) -> vec3<f32> {
+    let jitter = frame_uniforms.jitter;
+    if INLINE_DIRECTIONS || any(jitter != vec2<f32>(0.0)) {
+        let ndc = ((vec2<f32>(coord) + 0.5 + jitter) / vec2<f32>(textureDimensions(raycast_result))) * 2.0 - 1.0;
+        return normalize(vec3<f32>(ndc * ndc_mult, -1.0));
+    }
     return textureLoad(directions, coord).xyz;
 }
 
@@ -430,6 +1264,71 @@ fn get_block(coord: vec3<i32>) -> u32 {
     return id;
 }
 
+// The LODs are stored after the full resolution blocks, see `lod_offset` in raytrace.rs.
+const LOD_LEVELS: u32 = 3u;
+
+fn lod_offset(lod: u32) -> u32 {
+    switch lod {
+        case 1u: { return 262144u; }
+        case 2u: { return 294912u; }
+        case 3u: { return 299008u; }
+        default: { return 0u; }
+    }
+}
+
+// `coord` is in the cells of the LOD level, which has 64 >> lod cells per axis.
+fn get_block_lod(coord: vec3<i32>, lod: u32) -> u32 {
+    if lod == 0u {
+        return get_block(coord);
+    }
+    let size = 64u >> lod;
+    let ucoord = vec3<u32>(coord);
+    if any(ucoord >= vec3<u32>(size)) {
+        return 0u;
+    }
+    return voxel_chunk[lod_offset(lod) + (ucoord.y * size + ucoord.z) * size + ucoord.x];
+}
+
+// The distance along the ray to where it enters the chunk, 0.0 if it starts inside.
+fn chunk_entry_distance(ray: Ray) -> f32 {
+    let inv_dir = 1.0 / select(ray.dir, vec3<f32>(MINPOS), ray.dir == ZERO);
+    let t0 = (ZERO - ray.pos) * inv_dir;
+    let t1 = (SIXTYFOUR - ray.pos) * inv_dir;
+    let near = min(t0, t1);
+    return max(0.0, max(near.x, max(near.y, near.z)));
+}
+
+// The distance along the ray to where it leaves the chunk. Not greater than the entry distance if the ray
+// misses the chunk.
+fn chunk_exit_distance(ray: Ray) -> f32 {
+    let inv_dir = 1.0 / select(ray.dir, vec3<f32>(MINPOS), ray.dir == ZERO);
+    let t0 = (ZERO - ray.pos) * inv_dir;
+    let t1 = (SIXTYFOUR - ray.pos) * inv_dir;
+    let far = max(t0, t1);
+    return max(0.0, min(far.x, min(far.y, far.z)));
+}
+
+// How far primary rays march. Beyond the end of the distance fog every hit would be the fog color, so the
+// rays that get there stop and the pixel is fogged without shading anything.
+fn primary_max_distance() -> f32 {
+    return min(camera.far, fog_opaque_distance());
+}
+
+// Where the distance fog hides everything, or F32MAX without distance fog. The LODs don't change it, since
+// they only change the size of the steps along the ray.
+fn fog_opaque_distance() -> f32 {
+    return select(F32MAX, fog.end, fog.end > fog.start);
+}
+
+// Each LOD level starts at twice the distance of the previous one.
+fn select_lod(distance: f32) -> u32 {
+    if config.lod_distance <= 0.0 || distance < config.lod_distance {
+        return 0u;
+    }
+    let level = floor(log2(distance / config.lod_distance)) + 1.0;
+    return u32(min(level, f32(LOD_LEVELS)));
+}
+
 fn calc_delta(mag: f32) -> f32 {
     return 1.0 / max(abs(mag), MINPOS);
 }
@@ -451,6 +1350,44 @@ const NEGFACE: vec3<u32> = vec3<u32>(NegX, NegY, NegZ);
 const POSFACE: vec3<u32> = vec3<u32>(PosX, PosY, PosZ);
 
 fn raycast(ray: Ray, near: f32, far: f32, solid: bool) -> RayHit {
+    return raycast_grid(ray, near, far, solid, 0u, 0u);
+}
+
+// Raycasts through the cells with the `medium` block ID, stopping at the first cell that isn't `medium`.
+fn raycast_medium(ray: Ray, near: f32, far: f32, medium: u32) -> RayHit {
+    return raycast_grid(ray, near, far, true, 0u, medium);
+}
+
+// Solid rays stop at any block other than the medium they travel through (air unless it's
+// raycast_medium), non-solid rays stop at air.
+fn stops_at(id: u32, solid: bool, medium: u32) -> bool {
+    if solid {
+        return id != medium;
+    }
+    return id == 0u;
+}
+
+// Raycasts against the LOD level. The returned coord is the full resolution cell on the surface of the
+// LOD cell that was hit, so that it can be shaded like a regular hit.
+fn raycast_lod(ray: Ray, near: f32, far: f32, solid: bool, lod: u32) -> RayHit {
+    if lod == 0u {
+        return raycast_grid(ray, near, far, solid, 0u, 0u);
+    }
+    let scale = f32(1u << lod);
+    var hit = raycast_grid(Ray(ray.pos / scale, ray.dir), near / scale, far / scale, solid, lod, 0u);
+    if hit.hit {
+        hit.distance *= scale;
+        let cell_min = vec3<f32>(hit.coord) * scale;
+        let point = ray.pos + ray.dir * hit.distance;
+        hit.coord = vec3<i32>(floor(clamp(point, cell_min + SMIDGEN, cell_min + vec3<f32>(scale) - SMIDGEN)));
+    }
+    return hit;
+}
+
+// DDA through the cells of the LOD level. `ray` is in the space of the level's cells.
+fn raycast_grid(ray: Ray, near: f32, far: f32, solid: bool, lod: u32, medium: u32) -> RayHit {
+    let size = f32(64u >> lod);
+    let grid_max = vec3<f32>(size);
     // No hit:
     // RayHit(
     //     vec3<i32>(0, 0, 0), // coord
@@ -470,7 +1407,7 @@ fn raycast(ray: Ray, near: f32, far: f32, solid: bool) -> RayHit {
     var enter_face = NoFace;
 
     let lt = pos < ZERO;
-    let gt = pos >= SIXTYFOUR;
+    let gt = pos >= grid_max;
     let ltgt = lt | gt;
     if any(ltgt) {
         switch step.x + 1 {
@@ -484,7 +1421,7 @@ fn raycast(ray: Ray, near: f32, far: f32, solid: bool) -> RayHit {
                         false,
                     );
                 }
-                delta_min.x = (pos.x - 64.0) / -dir.x;
+                delta_min.x = (pos.x - size) / -dir.x;
                 delta_max.x = pos.x / -dir.x;
             }
             case 1: {}
@@ -499,7 +1436,7 @@ fn raycast(ray: Ray, near: f32, far: f32, solid: bool) -> RayHit {
                     );
                 }
                 delta_min.x = -pos.x / dir.x;
-                delta_max.x = (64.0 - pos.x) / dir.x;
+                delta_max.x = (size - pos.x) / dir.x;
             }
             default: {}
         }
@@ -515,7 +1452,7 @@ fn raycast(ray: Ray, near: f32, far: f32, solid: bool) -> RayHit {
                         false,
                     );
                 }
-                delta_min.y = (pos.y - 64.0) / -dir.y;
+                delta_min.y = (pos.y - size) / -dir.y;
                 delta_max.y = pos.y / -dir.y;
             }
             case 1:{}
@@ -530,7 +1467,7 @@ fn raycast(ray: Ray, near: f32, far: f32, solid: bool) -> RayHit {
                     );
                 }
                 delta_min.y = -pos.y / dir.y;
-                delta_max.y = (64.0 - pos.y) / dir.y;
+                delta_max.y = (size - pos.y) / dir.y;
             }
             default: {}
         }
@@ -546,7 +1483,7 @@ fn raycast(ray: Ray, near: f32, far: f32, solid: bool) -> RayHit {
                         false,
                     );
                 }
-                delta_min.z = (pos.z - 64.0) / -dir.z;
+                delta_min.z = (pos.z - size) / -dir.z;
                 delta_max.z = pos.z / -dir.z;
             }
             case 1:{}
@@ -561,7 +1498,7 @@ fn raycast(ray: Ray, near: f32, far: f32, solid: bool) -> RayHit {
                     );
                 }
                 delta_min.z = -pos.z / dir.z;
-                delta_max.z = (64.0 - pos.z) / dir.z;
+                delta_max.z = (size - pos.z) / dir.z;
             }
             default: {}
         }
@@ -597,7 +1534,7 @@ fn raycast(ray: Ray, near: f32, far: f32, solid: bool) -> RayHit {
             }
             case 1:{}
             case 2: {
-                delta_max.x = (64.0 - pos.x) / dir.x;
+                delta_max.x = (size - pos.x) / dir.x;
             }
             default: {}
         }
@@ -608,7 +1545,7 @@ fn raycast(ray: Ray, near: f32, far: f32, solid: bool) -> RayHit {
             }
             case 1:{}
             case 2: {
-                delta_max.y = (64.0 - pos.y) / dir.y;
+                delta_max.y = (size - pos.y) / dir.y;
             }
             default: {}
         }
@@ -619,7 +1556,7 @@ fn raycast(ray: Ray, near: f32, far: f32, solid: bool) -> RayHit {
             }
             case 1:{}
             case 2: {
-                delta_max.z = (64.0 - pos.z) / dir.z;
+                delta_max.z = (size - pos.z) / dir.z;
             }
             default: {}
         }
@@ -631,8 +1568,8 @@ fn raycast(ray: Ray, near: f32, far: f32, solid: bool) -> RayHit {
     // );
 
     var cell = vec3<i32>(floor(pos));
-    let hit_id = get_block(cell);
-    if (hit_id != 0) == solid {
+    let hit_id = get_block_lod(cell, lod);
+    if stops_at(hit_id, solid, medium) {
         var hit_face = enter_face;
         // if t_max_add == delta_min.x {
         //     hit_face = face.x;
@@ -666,6 +1603,7 @@ fn raycast(ray: Ray, near: f32, far: f32, solid: bool) -> RayHit {
         min(delta_max.z, far),
     );
     loop {
+        dda_steps += 1u;
         if t_max.x <= t_max.y {
             if t_max.x <= t_max.z {
                 if t_max.x >= max_dist.x {
@@ -678,8 +1616,8 @@ fn raycast(ray: Ray, near: f32, far: f32, solid: bool) -> RayHit {
                     );
                 }
                 cell.x = cell.x + step.x;
-                let hit_id = get_block(cell);
-                if (hit_id != 0) == solid {
+                let hit_id = get_block_lod(cell, lod);
+                if stops_at(hit_id, solid, medium) {
                     return RayHit(
                         cell,
                         t_max.x,
@@ -700,8 +1638,8 @@ fn raycast(ray: Ray, near: f32, far: f32, solid: bool) -> RayHit {
                     );
                 }
                 cell.z = cell.z + step.z;
-                let hit_id = get_block(cell);
-                if (hit_id != 0) == solid {
+                let hit_id = get_block_lod(cell, lod);
+                if stops_at(hit_id, solid, medium) {
                     return RayHit(
                         cell,
                         t_max.z,
@@ -724,8 +1662,8 @@ fn raycast(ray: Ray, near: f32, far: f32, solid: bool) -> RayHit {
                     );
                 }
                 cell.y = cell.y + step.y;
-                let hit_id = get_block(cell);
-                if (hit_id != 0) == solid {
+                let hit_id = get_block_lod(cell, lod);
+                if stops_at(hit_id, solid, medium) {
                     return RayHit(
                         cell,
                         t_max.y,
@@ -746,8 +1684,8 @@ fn raycast(ray: Ray, near: f32, far: f32, solid: bool) -> RayHit {
                     );
                 }
                 cell.z = cell.z + step.z;
-                let hit_id = get_block(cell);
-                if (hit_id != 0) == solid {
+                let hit_id = get_block_lod(cell, lod);
+                if stops_at(hit_id, solid, medium) {
                     return RayHit(
                         cell,
                         t_max.z,
@@ -1,3 +1,4 @@
+// The result holds linear colors, see [crate::rendering::color].
 @group(0) @binding(0)
 var render_texture: texture_2d<f32>;
 @group(0) @binding(1)
@@ -37,5 +38,18 @@ fn vertex_main(
 fn fragment_main(
     in: VertexOutput,
 ) -> @location(0) vec4<f32> {
-    return textureSample(render_texture, render_texture_sampler, in.uv);
-}
\ No newline at end of file
+    return convert_color(textureSample(render_texture, render_texture_sampler, in.uv));
+}
+// Used when the texture is supersampled. Averages four bilinear taps spread over the area that the
+// screen pixel covers in the texture, so that every texel contributes instead of only the nearest four.
+@fragment
+fn fragment_downsample(
+    in: VertexOutput,
+) -> @location(0) vec4<f32> {
+    let offset = fwidth(in.uv) * 0.25;
+    var color = textureSample(render_texture, render_texture_sampler, in.uv + vec2<f32>(-offset.x, -offset.y));
+    color += textureSample(render_texture, render_texture_sampler, in.uv + vec2<f32>(offset.x, -offset.y));
+    color += textureSample(render_texture, render_texture_sampler, in.uv + vec2<f32>(-offset.x, offset.y));
+    color += textureSample(render_texture, render_texture_sampler, in.uv + vec2<f32>(offset.x, offset.y));
+    return convert_color(color * 0.25);
+}
@@ -2,6 +2,14 @@
 @group(0) @binding(1) var reticle_texture: texture_2d<f32>;
 @group(0) @binding(2) var reticle_sampler: sampler;
 @group(0) @binding(3) var<uniform> dimensions: vec2<f32>;
+@group(0) @binding(4) var<uniform> params: ReticleParams;
+
+struct ReticleParams {
+    // Linear color that the texture is multiplied with.
+    tint: vec4<f32>,
+    // Width and height in physical pixels.
+    size: f32,
+}
 
 struct Vertex {
     pos: vec2<f32>,
@@ -13,15 +21,16 @@ struct VertexOut {
     @location(1) uv: vec2<f32>,
 }
 
+// A unit quad, scaled by `params.size`.
 const VERTICES: array<Vertex, 4> = array<Vertex, 4>(
     // Top-Left
-    Vertex(vec2<f32>(-36.0, -36), vec2<f32>(0.0, 0.0)),
+    Vertex(vec2<f32>(-0.5, -0.5), vec2<f32>(0.0, 0.0)),
     // Top-Right
-    Vertex(vec2<f32>(36.0, -36), vec2<f32>(1.0, 0.0)),
+    Vertex(vec2<f32>(0.5, -0.5), vec2<f32>(1.0, 0.0)),
     // Bottom-Left
-    Vertex(vec2<f32>(-36.0, 36), vec2<f32>(0.0, 1.0)),
+    Vertex(vec2<f32>(-0.5, 0.5), vec2<f32>(0.0, 1.0)),
     // Bottom-Right
-    Vertex(vec2<f32>(36.0, 36), vec2<f32>(1.0, 1.0)),
+    Vertex(vec2<f32>(0.5, 0.5), vec2<f32>(1.0, 1.0)),
 );
 
 const INDICES: array<u32, 6> = array<u32, 6>(
@@ -34,7 +43,7 @@ fn vs_main(
 ) -> VertexOut {
     var out: VertexOut;
     let vertex = VERTICES[INDICES[index]];
-    let pos = ortho_matrix * vec4<f32>(vertex.pos + dimensions / 2.0, 0.0, 1.0);
+    let pos = ortho_matrix * vec4<f32>(vertex.pos * params.size + dimensions / 2.0, 0.0, 1.0);
     out.clip_position = pos;
     out.uv = vertex.uv;
     return out;
@@ -45,5 +54,5 @@ fn fs_main(
     in: VertexOut
 ) -> @location(0) vec4<f32> {
     let sample = textureSample(reticle_texture, reticle_sampler, in.uv);
-    return sample;
+    return sample * params.tint;
 }
\ No newline at end of file
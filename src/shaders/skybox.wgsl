@@ -1,6 +1,12 @@
 
 
-var<push_constant> world: mat4x4<f32>;
+struct PushData {
+    world: mat4x4<f32>,
+    // Multiplied with the cubemap sample (used for day/night blending).
+    tint: vec4<f32>,
+}
+
+var<push_constant> push: PushData;
 @group(0) @binding(0) var<uniform> view_projection: mat4x4<f32>;
 @group(0) @binding(1) var<uniform> camera_position: vec3<f32>;
 
@@ -8,11 +14,11 @@ var<push_constant> world: mat4x4<f32>;
 @group(1) @binding(1) var cubemap_sampler: sampler;
 
 fn local_to_clip(pos: vec3<f32>) -> vec4<f32> {
-    return view_projection * (world * vec4<f32>(pos, 1.0));
+    return view_projection * (push.world * vec4<f32>(pos, 1.0));
 }
 
 fn local_to_world(pos: vec3<f32>) -> vec3<f32> {
-    return (world * vec4<f32>(pos, 1.0)).xyz;
+    return (push.world * vec4<f32>(pos, 1.0)).xyz;
 }
 
 struct VertexInput {
@@ -98,5 +104,5 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     //     return vec4<f32>(1.0, 0.0, 0.0, 1.0);
     // }
     let far_sample = textureSample(cubemap, cubemap_sampler, dir);
-    return far_sample;
+    return far_sample * push.tint;
 }
\ No newline at end of file
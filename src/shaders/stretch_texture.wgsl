@@ -37,5 +37,5 @@ fn vertex_main(
 fn fragment_main(
     in: VertexOutput,
 ) -> @location(0) vec4<f32> {
-    return textureSample(render_texture, render_texture_sampler, in.uv);
+    return convert_color(textureSample(render_texture, render_texture_sampler, in.uv));
 }
\ No newline at end of file
@@ -0,0 +1,76 @@
+@group(0) @binding(0) var input_texture: texture_2d<f32>;
+// The raytracer's G-buffer, only the distance (w) is used.
+@group(0) @binding(1) var hit_normal_distance: texture_2d<f32>;
+@group(0) @binding(2) var history_texture: texture_2d<f32>;
+@group(0) @binding(3) var history_sampler: sampler;
+@group(0) @binding(4) var output_texture: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(5) var<uniform> params: TaaParams;
+
+// Size: 64
+struct Transform {
+    rotation: mat3x3<f32>, //  0..48
+    position: vec3<f32>,   // 48..60
+}
+
+// Size: 144
+struct TaaParams {
+    current: Transform,    //   0..64
+    previous: Transform,   //  64..128
+    ndc_mult: vec2<f32>,   // 128..136
+    // How much of the current frame is blended into the history.
+    blend: f32,            // 136..140
+    // 1 when there is no history to blend with.
+    reset: u32,            // 140..144
+}
+
+fn load_input(pos: vec2<i32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(input_texture));
+    return textureLoad(input_texture, clamp(pos, vec2<i32>(0), size - 1), 0);
+}
+
+// Where the surface seen through `texel` was on the screen in the previous frame, as a UV.
+fn reproject(texel: vec2<u32>, size: vec2<u32>) -> vec2<f32> {
+    let distance = textureLoad(hit_normal_distance, texel, 0).w;
+    // The same direction as `get_dir` in raytrace.wgsl, without the jitter.
+    let ndc = ((vec2<f32>(texel) + 0.5) / vec2<f32>(size)) * 2.0 - 1.0;
+    let dir = normalize(vec3<f32>(ndc * params.ndc_mult, -1.0));
+    let point = params.current.position + params.current.rotation * dir * distance;
+    let local = transpose(params.previous.rotation) * (point - params.previous.position);
+    if local.z >= 0.0 {
+        // Behind the previous camera.
+        return vec2<f32>(-1.0);
+    }
+    let previous_ndc = (local.xy / -local.z) / params.ndc_mult;
+    return (previous_ndc + 1.0) * 0.5;
+}
+
+@compute @workgroup_size(16, 16)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let size = textureDimensions(output_texture);
+    if any(global_id.xy >= size) {
+        return;
+    }
+    let pos = vec2<i32>(global_id.xy);
+    let current = textureLoad(input_texture, pos, 0);
+    if params.reset != 0u {
+        textureStore(output_texture, global_id.xy, current);
+        return;
+    }
+    let uv = reproject(global_id.xy, size);
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) {
+        textureStore(output_texture, global_id.xy, current);
+        return;
+    }
+    // The history is only trusted as far as it looks like the neighborhood in the current frame.
+    var low = current;
+    var high = current;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let neighbor = load_input(pos + vec2<i32>(x, y));
+            low = min(low, neighbor);
+            high = max(high, neighbor);
+        }
+    }
+    let history = clamp(textureSampleLevel(history_texture, history_sampler, uv, 0.0), low, high);
+    textureStore(output_texture, global_id.xy, mix(history, current, params.blend));
+}
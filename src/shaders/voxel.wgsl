@@ -50,6 +50,20 @@ struct Fog {
     color: vec4<f32>,
     start: f32,
     end: f32,
+    height_density: f32,
+    height_falloff: f32,
+}
+
+// Size: 32
+struct FrameUniforms {
+    // Seconds since the first frame, wrapped every hour.
+    time: f32,             // 0..4
+    delta_time: f32,       // 4..8
+    frame: u32,            // 8..12
+    _padding: u32,         // 12..16
+    resolution: vec2<f32>, // 16..24
+    // The camera's sub-pixel offset, in pixels.
+    jitter: vec2<f32>,     // 24..32
 }
 
 // @group(0) @binding(0) var<uniform> world: mat4x4<f32>;
@@ -63,6 +77,9 @@ var<push_constant> world: mat4x4<f32>;
 
 @group(2) @binding(0) var<uniform> fog: Fog;
 
+// See frame_uniforms.rs.
+@group(3) @binding(0) var<uniform> frame_uniforms: FrameUniforms;
+
 fn local_to_clip(pos: vec3<f32>) -> vec4<f32> {
     return view_projection * (world * vec4<f32>(pos, 1.0));
 }
@@ -75,6 +92,16 @@ struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) uv: vec2<f32>,
     @location(2) layer: u32,
+    // Zero if the vertex has no normal.
+    @location(3) normal: vec3<f32>,
+    @location(4) color: vec4<f32>,
+}
+
+struct InstanceInput {
+    @location(5) model_0: vec4<f32>,
+    @location(6) model_1: vec4<f32>,
+    @location(7) model_2: vec4<f32>,
+    @location(8) model_3: vec4<f32>,
 }
 
 struct VertexOutput {
@@ -82,6 +109,8 @@ struct VertexOutput {
     @location(0) uv: vec2<f32>,
     @location(1) layer: u32,
     @location(2) world_pos: vec3<f32>,
+    @location(3) normal: vec3<f32>,
+    @location(4) color: vec4<f32>,
 };
 
 @vertex
@@ -96,6 +125,31 @@ fn vs_main(
     out.clip_position = local_to_clip(in.position);
     out.uv = in.uv;
     out.layer = in.layer;
+    // The world transforms are rotations, translations and uniform scales, so the normal can be transformed like a direction.
+    out.normal = (world * vec4<f32>(in.normal, 0.0)).xyz;
+    out.color = in.color;
+    return out;
+}
+
+@vertex
+fn vs_instanced(
+    in: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model = mat4x4<f32>(
+        instance.model_0,
+        instance.model_1,
+        instance.model_2,
+        instance.model_3,
+    );
+    let world_pos = model * vec4<f32>(in.position, 1.0);
+    var out: VertexOutput;
+    out.world_pos = world_pos.xyz;
+    out.clip_position = view_projection * world_pos;
+    out.uv = in.uv;
+    out.layer = in.layer;
+    out.normal = (model * vec4<f32>(in.normal, 0.0)).xyz;
+    out.color = in.color;
     return out;
 }
 
@@ -113,6 +167,7 @@ const PLAYER_LIGHT_INTENSITY: f32 = 1.0;
 const LIGHT_COLOR: vec3<f32> = vec3<f32>(0.0, 0.5, 1.0);
 const AMBIENT_COLOR: vec3<f32> = vec3<f32>(1.0, 1.0, 1.0);
 const AMBIENT_INTENSITY: f32 = 0.1;
+// For vertices without a normal.
 const NORMAL: vec3<f32> = vec3<f32>(0.0, 1.0, 0.0);
 
 const MIN_POS: f32 = 1.175494351e-38;
@@ -149,7 +204,7 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     // if low && high {
     //     return vec4<f32>(1.0, 1.0, 1.0, 1.0);
     // }
-    let sample = textureSample(array_texture, array_texture_sampler, in.uv, in.layer);
+    let sample = textureSample(array_texture, array_texture_sampler, in.uv, in.layer) * in.color;
     let view_distance = length(in.world_pos - camera_position);
     if view_distance >= fog.start {
         if fog.color.a <= 0.00001 && view_distance >= FOG_END {
@@ -162,7 +217,8 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
         // var atten = clamp(1.0 - (view_distance / PLAYER_LIGHT_RANGE), 0.0, 1.0);
         var atten = 1.0 - smoothstep(0.0, PLAYER_LIGHT_RANGE, view_distance);
         // atten = atten * atten;
-        let diffuse = max(dot(NORMAL, light_dir), 0.0);
+        let normal = select(NORMAL, normalize(in.normal), dot(in.normal, in.normal) > MIN_POS);
+        let diffuse = max(dot(normal, light_dir), 0.0);
         let point_light = LIGHT_COLOR * PLAYER_LIGHT_INTENSITY * diffuse * atten;
         let ambient = AMBIENT_COLOR * AMBIENT_INTENSITY;
         let final_color = sample.rgb * (ambient + point_light);
@@ -0,0 +1,155 @@
+// The rasterized view of the chunk, see raster.rs. The faces are textured like in voxel.wgsl, with the layer
+// of the block's face from the mesh. The sun and ambient light follow `sun_and_ambient_light` in
+// raytrace.wgsl, without the shadows, reflections, ambient occlusion, point lights and block light that need
+// rays.
+
+struct Fog {
+    color: vec4<f32>,
+    start: f32,
+    end: f32,
+    height_density: f32,
+    height_falloff: f32,
+}
+
+// Size: 48
+struct DirectionalLight {
+    direction: vec3<f32>,   // 0..12
+    // 4 bytes padding
+    _pad0: u32,
+    color: vec3<f32>,       // 16..28
+    evening_intensity: f32, // 28..32
+    intensity: f32,         // 32..36
+    shadow: f32,            // 36..40
+    on: u32,                // 40..44
+    // 4 bytes padding
+    _pad2: u32,
+}
+
+// Size: 32
+struct AmbientLight {
+    color: vec3<f32>, // 0..12
+    // 4 bytes padding
+    _pad0: u32,
+    intensity: f32,      // 16..20
+    on: u32,             // 20..24
+    ao_strength: f32,    // 24..28
+    // 4 bytes padding
+    _pad1: u32,
+}
+
+// Size: 80
+struct Lighting {
+    directional: DirectionalLight, //  0..48
+    ambient: AmbientLight,         // 48..80
+}
+
+// Size: 32
+struct FrameUniforms {
+    // Seconds since the first frame, wrapped every hour.
+    time: f32,             // 0..4
+    delta_time: f32,       // 4..8
+    frame: u32,            // 8..12
+    _padding: u32,         // 12..16
+    resolution: vec2<f32>, // 16..24
+    // The camera's sub-pixel offset, in pixels.
+    jitter: vec2<f32>,     // 24..32
+}
+
+@group(0) @binding(0) var<uniform> view_projection: mat4x4<f32>;
+@group(0) @binding(1) var<uniform> camera_position: vec3<f32>;
+
+@group(1) @binding(0) var<uniform> lighting: Lighting;
+@group(1) @binding(1) var<uniform> fog: Fog;
+
+@group(2) @binding(0) var array_texture: texture_2d_array<f32>;
+@group(2) @binding(1) var array_texture_sampler: sampler;
+
+// See frame_uniforms.rs.
+@group(3) @binding(0) var<uniform> frame_uniforms: FrameUniforms;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) layer: u32,
+    @location(3) normal: vec3<f32>,
+    @location(4) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_pos: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) layer: u32,
+    @location(3) normal: vec3<f32>,
+    @location(4) color: vec4<f32>,
+}
+
+// The mesh is in voxel space, so there's no world transform.
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = view_projection * vec4<f32>(in.position, 1.0);
+    out.world_pos = in.position;
+    out.uv = in.uv;
+    out.layer = in.layer;
+    out.normal = in.normal;
+    out.color = in.color;
+    return out;
+}
+
+fn circular_in(t: f32) -> f32 {
+    return 1.0 - sqrt(1.0 - pow(t, 2.0));
+}
+
+fn circular_out(t: f32) -> f32 {
+    return sqrt(1.0 - pow(1.0 - t, 2.0));
+}
+
+// `sun_and_ambient_light` with every surface in full view of the sun.
+fn sun_and_ambient_light(normal: vec3<f32>) -> vec3<f32> {
+    if lighting.directional.on != 0 {
+        let inv_light = -normalize(lighting.directional.direction);
+        let light_dot = max(0.0, dot(inv_light, normal));
+        let directional_color = lighting.directional.color * lighting.directional.intensity;
+        if bool(lighting.ambient.on) {
+            let ambient = lighting.ambient.color * lighting.ambient.intensity;
+            return mix(ambient, directional_color, circular_out(light_dot));
+        }
+        let shadow = vec3<f32>(lighting.directional.shadow);
+        return mix(shadow, directional_color * light_dot, circular_out(light_dot));
+    } else if bool(lighting.ambient.on) {
+        return lighting.ambient.color * lighting.ambient.intensity;
+    }
+    return vec3<f32>(1.0);
+}
+
+// `fog_amount` in raytrace.wgsl, for the ray from the camera to `point`.
+fn fog_amount(point: vec3<f32>) -> f32 {
+    let offset = point - camera_position;
+    let distance = length(offset);
+    var distance_fog = 0.0;
+    if fog.end > fog.start {
+        let fog_interp = saturate((distance - fog.start) / (fog.end - fog.start));
+        distance_fog = smoothstep(0.0, 1.0, circular_in(fog_interp));
+    }
+    var height_fog = 0.0;
+    if fog.height_density > 0.0 && distance > 0.0 {
+        let falloff = fog.height_falloff;
+        let origin_density = fog.height_density * exp(-falloff * camera_position.y);
+        let dir_y = offset.y / distance * falloff;
+        var optical_depth = origin_density * distance;
+        if abs(dir_y) > 1e-5 {
+            optical_depth = origin_density * (1.0 - exp(-dir_y * distance)) / dir_y;
+        }
+        height_fog = 1.0 - exp(-max(optical_depth, 0.0));
+    }
+    return 1.0 - (1.0 - distance_fog) * (1.0 - height_fog);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = normalize(in.normal);
+    let sample = textureSample(array_texture, array_texture_sampler, in.uv, in.layer) * in.color;
+    let color = sample.rgb * sun_and_ambient_light(normal);
+    return mix(vec4<f32>(color, sample.a), fog.color, fog_amount(in.world_pos));
+}
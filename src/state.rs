@@ -1,93 +1,587 @@
 #![allow(unused)]
 use std::sync::atomic::AtomicBool;
+use std::borrow::Cow;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use std::fmt::Write;
+use std::path::{Path, PathBuf};
 
 use gilrs::Gilrs;
-use glam::{vec2, vec3, vec4, Vec3};
-use wgpu::{MemoryHints, MultisampleState, ShaderStages, TextureFormat};
+use glam::{uvec2, vec2, vec3, vec4, Vec2, Vec3};
+use wgpu::{MemoryHints, ShaderStages, TextureFormat};
 use wgpu::{self, util::DeviceExt};
 use winit::dpi::{PhysicalPosition, PhysicalSize};
 use winit::event::{DeviceEvent, Event, MouseButton};
 use winit::keyboard::{KeyCode, PhysicalKey};
-use winit::{event::WindowEvent, window::Window};
-
-use crate::animation::animtimer::AnimTimer;
-use crate::camera::Camera;
-use crate::input::Input;
-use crate::math::average::{AverageBuffer, AvgBuffer};
+use winit::{event::WindowEvent, window::{CursorGrabMode, Window}};
+
+use crate::animation::animator::{Animations, Animator, CameraTrack};
+use crate::animation::camera_path::{CameraKeyframe, CameraPath};
+use crate::rendering::offline::{self, SequenceError, SequenceSettings};
+use crate::user_config::{Keybinds, UserConfig};
+use crate::assets::{placeholder_image, AssetServer};
+use crate::animation::tween;
+use crate::bookmarks::{slot_key, Bookmarks, BOOKMARK_SLOTS};
+use crate::camera::{Camera, MAX_FOV, MIN_FOV};
+use crate::chunk_debug::{self, ChunkDebugMode};
+use crate::console::{Console, CONSOLE_FONT_SIZE, CONSOLE_LINES, CONSOLE_LINE_HEIGHT};
+use crate::logging::log_buffer;
+use crate::benchmark::{Benchmark, BenchmarkSample, BenchmarkSettings, BENCHMARK_DIR};
+use crate::day_night::DayNightCycle;
+use crate::rendering::clouds::Clouds;
+use crate::sun_gizmo::{SunAngles, SunGizmo, NUDGE_SPEED};
+use crate::input::{Input, InputPlayback, InputRecorder, InputRecordingError};
+use crate::math::average::AverageBuffer;
+use crate::model::loader::MeshData;
+use crate::voxel::mesh::greedy_mesh;
 use crate::modeling::modeler::Modeler;
-use crate::rendering::raytrace::{AmbientLight, DirectionalLight, GpuMat3, GpuTransform, GpuVec3, Lighting, PrecomputedDirections, RaytraceChunk, Raytracer};
-use crate::rendering::reticle::Reticle;
-use crate::rendering::skybox::{Skybox, SkyboxTexturePaths};
+use crate::modeling::text3d::{self, TextStyle};
+use crate::physics::{chunk_solids, CharacterController};
+use crate::entity::{Entities, Entity, EntityId, Orbit};
+use crate::rendering::raytrace::{AmbientLight, AntialiasMode, BlockFaces, DirectionalLight, Material, GpuMat3, GpuTransform, GpuVec3, Lighting, PrecomputedDirections, RaytraceChunk, RaytraceQuality, Raytracer, RaytraceVariant, TranslucentHits, WorkgroupSize, MATERIAL_COUNT, MAX_SUPERSAMPLE_SCALE};
+use crate::rendering::exposure::AutoExposure;
+use crate::rendering::post::{Bloom, Gamma, PostChain, Tonemap, TonemapOperator, Vignette, HDR_FORMAT};
+use crate::rendering::msaa::{self, Msaa};
+use crate::rendering::color::{ColorPath, ColorSpace};
+use crate::rendering::debug_capture::{debug_group, FrameCapture, FrameSection};
+use crate::rendering::memory::gpu_memory;
+use crate::rendering::reticle::{Reticle, ReticleSettings, RETICLE_TINTS};
+use crate::rendering::staging::StagingRing;
+use crate::rendering::skybox::{Skybox, SkyboxCubemap};
 use crate::rendering::texture_array::TextureArrayBindGroup;
 use crate::rendering::velvet::Velvet;
-use crate::voxel::vertex::Vertex;
+use crate::rendering::frame_graph::{FrameGraph, FrameSample};
+use crate::tasks::{TaskId, TaskPool};
+use crate::voxel::vertex::{InstanceData, Vertex};
+use crate::voxel::worldgen;
+use crate::rendering::instancing::{draw_instanced, InstanceBuffer};
 use crate::rendering::{
+    frame_uniforms::{FrameUniforms, FrameUniformsBindGroup},
     texture_array::TextureArray,
     transforms::TransformsBindGroup,
 };
 use crate::voxel_fog::{Fog, FogBindGroup};
+use crate::voxel::edit::{Brush, ClipboardVolume, Selection};
+use crate::voxel::sculpt::Sculptor;
+use crate::voxel::schematic;
+use crate::voxel::picker::{PickResult, Picker};
+use crate::rendering::shader_errors::GpuErrors;
+use crate::mouse_settings::{MouseSettings, MAX_SENSITIVITY, MIN_SENSITIVITY};
+use crate::movement::MovementController;
+use crate::rendering::timestamps::{GpuTimer, DEFAULT_FRAMES_IN_FLIGHT};
+use crate::voxel::saves::{SaveSlot, WorldSaves};
+use crate::gizmo::Gizmo;
+use crate::labels::Labels;
+use crate::gridzmo::Gridzmo;
+use crate::rendering::pip::PictureInPicture;
+use crate::rendering::raster::{split_region, RasterView, RenderMode};
+use crate::rendering::render_scale::{step_render_scale, DynamicRenderScale};
+use crate::rendering::sampler::TextureFiltering;
+use crate::rendering::lighting::{color_temperature, LightId, LightingPreset, Lights, PointLight};
+use crate::rendering::dispatch::IndirectDispatch;
+use crate::rendering::frame_graph_lite::{FrameGraphLite, PassProfiler};
+use crate::systems::{RenderCtx, SystemCtx, Systems};
+use crate::window_config::{FullscreenMode, WindowConfig};
 use crate::FrameInfo;
 
-use glyphon::{Attrs, Buffer, Cache, Color, FontSystem, Metrics, Resolution, SwashCache, TextArea, TextAtlas, TextRenderer, Viewport, Weight};
+use glyphon::{Attrs, Buffer, Cache, Color, ColorMode, FontSystem, Metrics, Resolution, SwashCache, TextArea, TextAtlas, TextRenderer, Viewport, Weight};
 
 pub struct Settings {
-    pub mouse_smoothing: bool,
-    pub mouse_halting: bool,
+    pub mouse: MouseSettings,
+    pub ambient_occlusion: bool,
+    pub draw_instanced_grid: bool,
+    /// Block coordinates and chunk IDs in the world (Ctrl+L).
+    pub draw_labels: bool,
+    /// The 2D vector overlay, see [Velvet] (Ctrl+O).
+    pub draw_overlay: bool,
+    /// Chunk borders and the origin's axes, see [crate::chunk_debug] (Ctrl+Quote).
+    pub chunk_debug: ChunkDebugMode,
+    /// Whether the chunk is raytraced, rasterized or both, see [crate::rendering::raster] (Ctrl+M).
+    pub render_mode: RenderMode,
 }
 
+/// Draws on the vector overlay every frame, see [State::set_overlay]. The second argument is the size of the
+/// overlay in pixels.
+pub type OverlayDraw = Box<dyn FnMut(&mut vello::Scene, Vec2)>;
+
 pub struct TextRend {
-    font_system: FontSystem,
-    text_atlas: TextAtlas,
-    text_renderer: TextRenderer,
-    front_buffer: Buffer,
-    back_buffer: Buffer,
-    cache: Cache,
-    swash_cache: SwashCache,
+    pub font_system: FontSystem,
+    pub text_atlas: TextAtlas,
+    pub text_renderer: TextRenderer,
+    pub front_buffer: Buffer,
+    pub console_front_buffer: Buffer,
+    pub console_back_buffer: Buffer,
+    pub cache: Cache,
+    pub swash_cache: SwashCache,
+    /// The backgrounds of the styled text, see [crate::rendering::text_style].
+    pub backgrounds: Velvet,
 }
 
-pub struct StateAnimator {
-    timer: AnimTimer,
-    callback: Box<dyn Fn(&mut State<'_>, &AnimTimer) + 'static>,
+impl TextRend {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, surface_format: wgpu::TextureFormat, sample_count: u32, size: PhysicalSize<u32>) -> Self {
+        let mut font_system = FontSystem::new();
+        let (cache, text_atlas, text_renderer) = Self::create_renderer(device, queue, surface_format, sample_count);
+
+        let mut front_buffer = Buffer::new(&mut font_system, Metrics::new(48.0, 48.0));
+        front_buffer.set_size(&mut font_system, Some(size.width as f32), Some(size.height as f32));
+        let console_front_buffer = Buffer::new(&mut font_system, Metrics::new(CONSOLE_FONT_SIZE, CONSOLE_LINE_HEIGHT));
+        let console_back_buffer = Buffer::new(&mut font_system, Metrics::new(CONSOLE_FONT_SIZE, CONSOLE_LINE_HEIGHT));
+
+        TextRend {
+            font_system,
+            cache,
+            text_atlas,
+            text_renderer,
+            front_buffer,
+            console_front_buffer,
+            console_back_buffer,
+            swash_cache: SwashCache::new(),
+            backgrounds: Velvet::new(device, surface_format, sample_count, size.width, size.height),
+        }
+    }
+
+    fn create_renderer(device: &wgpu::Device, queue: &wgpu::Queue, surface_format: wgpu::TextureFormat, sample_count: u32) -> (Cache, TextAtlas, TextRenderer) {
+        let cache = glyphon::Cache::new(device);
+        // glyphon's accurate mode decodes the (sRGB) text colors, which is only right for sRGB targets.
+        let color_mode = if surface_format.is_srgb() {
+            ColorMode::Accurate
+        } else {
+            ColorMode::Web
+        };
+        let mut text_atlas = TextAtlas::with_color_mode(device, queue, &cache, surface_format, color_mode);
+        let text_renderer = TextRenderer::new(
+            &mut text_atlas,
+            device,
+            msaa::multisample_state(sample_count),
+            None,
+        );
+        (cache, text_atlas, text_renderer)
+    }
+
+    /// Rebuilds the atlas and renderer on a new device (or for a new sample count). The fonts and text
+    /// buffers are kept.
+    pub fn recreate(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, surface_format: wgpu::TextureFormat, sample_count: u32) {
+        (self.cache, self.text_atlas, self.text_renderer) = Self::create_renderer(device, queue, surface_format, sample_count);
+        let size = self.backgrounds.size();
+        self.backgrounds = Velvet::new(device, surface_format, sample_count, size.x as u32, size.y as u32);
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, size: PhysicalSize<u32>) {
+        self.front_buffer.set_size(&mut self.font_system, Some(size.width as f32), Some(size.height as f32));
+        self.backgrounds.resize(device, size.width, size.height);
+    }
 }
 
-impl StateAnimator {
-    pub fn start<F: Fn(&mut State<'_>, &AnimTimer) + 'static>(duration: Duration, callback: F) -> Self {
-        Self {
-            timer: AnimTimer::start(duration),
-            callback: Box::new(callback),
-        }
-    }
-
-    /// Update the animation and return true when it is finished.
-    /// 
-    /// This function will call the inner callback regardless of whether or not the animation is finished.
-    /// You should use the result of this function to determine when to stop calling this function.
-    /// Think of it like this:
-    /// ```rust, no_run
-    /// let mut state: State<'_> = ...;
-    /// let animator: StateAnimator = ...;
-    /// let mut should_stop_animating = animator.is_finished();
-    /// // ... later in loop
-    /// if !should_stop_animating {
-    ///     should_stop_animating = animator.update(&mut state);
-    /// }
-    /// 
-    /// ```
-    pub fn update(&self, state: &mut State<'_>) -> bool {
-        (self.callback)(state, &self.timer);
-        self.timer.is_finished()
+/// The results of the jobs that [State] runs on its [TaskPool].
+pub enum TaskOutput {
+    Chunk(Result<RaytraceChunk, std::io::Error>),
+    Mesh(MeshData),
+    /// The greedy mesh of the chunk, see [State::update_chunk_mesh].
+    ChunkMesh(MeshData),
+    Saved(SaveSlot, Result<(), std::io::Error>),
+}
+
+/// F6 records input to this file and F7 plays it back.
+pub const INPUT_RECORDING_PATH: &str = "./input.rec";
+/// Ctrl+E exports the selection to this file and Ctrl+I imports it (while selecting).
+pub const SCHEMATIC_PATH: &str = "./sandbox_files/structure.schem";
+/// F10 saves the camera path to this file and Shift+F10 loads it.
+pub const CAMERA_PATH_PATH: &str = "./sandbox_files/camera.path";
+/// User settings are loaded from and saved to this file.
+pub const USER_CONFIG_PATH: &str = "./sandbox_files/user_config.toml";
+/// Changed user settings are saved once they've stayed the same for this long, so that resizing
+/// the window doesn't save on every frame.
+const USER_CONFIG_SAVE_DELAY: Duration = Duration::from_secs(1);
+pub const DEFAULT_CAMERA_PATH_DURATION: Duration = Duration::from_secs(10);
+/// Ctrl+S quicksaves into this directory and L loads the most recent save from it.
+pub const SAVES_DIR: &str = "./sandbox_files/saves";
+/// Ctrl+F5 recompiles the raytrace shader from this file.
+pub const RAYTRACE_SHADER_PATH: &str = "./src/shaders/raytrace.wgsl";
+/// How long messages from [State::notify] stay on screen.
+const NOTIFICATION_DURATION: Duration = Duration::from_secs(3);
+/// F12 renders the camera path (or the current view) into this directory.
+pub const SEQUENCE_PATH: &str = "./sandbox_files/sequence";
+/// Ctrl+F12 saves the raytrace result into this directory.
+pub const RESULT_DUMP_DIR: &str = "./sandbox_files/dumps";
+const AO_STRENGTH: f32 = 0.75;
+/// How far the main camera's frustum reaches in the picture-in-picture view.
+const PIP_FRUSTUM_DISTANCE: f32 = 96.0;
+const MAX_REFLECTION_BOUNCES: u32 = 3;
+pub const BLOCK_ID: u32 = 1;
+pub const MIRROR_ID: u32 = 2;
+pub const LAMP_ID: u32 = 3;
+pub const WATER_ID: u32 = 4;
+/// The walking speed at a move speed of `1.0`, in blocks per second.
+const WALK_SPEED: f32 = 4.5;
+/// Shift+`[` and Shift+`]` divide and multiply the mouse sensitivity by this (the brackets alone change
+/// the day/night speed).
+const SENSITIVITY_STEP: f64 = 1.1;
+const FOV_STEP: f32 = 5.0 * (std::f32::consts::PI / 180.0);
+/// The field of view is multiplied by this when fully zoomed in.
+const ZOOM_FOV_SCALE: f32 = 0.35;
+const ZOOM_DURATION: Duration = Duration::from_millis(200);
+/// Holding the right mouse button for at least this long zooms, a shorter click removes a block.
+const ZOOM_HOLD_DELAY: Duration = Duration::from_millis(150);
+/// Exposure steps are in stops (powers of two).
+const EXPOSURE_STEP: f32 = 0.25;
+const MIN_EXPOSURE: f32 = 0.125;
+const MAX_EXPOSURE: f32 = 8.0;
+const GAMMA_STEP: f32 = 0.1;
+/// Ctrl+Comma and Ctrl+Period change the reticle size by this many logical pixels.
+const RETICLE_SIZE_STEP: f32 = 8.0;
+/// Alt+Minus and Alt+Equal change the density of the volumetric light by this much.
+const VOLUMETRIC_DENSITY_STEP: f32 = 0.005;
+const MIN_GAMMA: f32 = 0.5;
+const MAX_GAMMA: f32 = 2.5;
+const BLOOM_THRESHOLD_STEP: f32 = 0.05;
+const BLOOM_INTENSITY_STEP: f32 = 0.1;
+const MAX_BLOOM_INTENSITY: f32 = 4.0;
+/// Asset paths are relative to this directory.
+pub const ASSETS_ROOT: &str = "./assets";
+const SKYBOX_DIR: &str = "textures/skyboxes/complex";
+/// An equirectangular panorama that is used instead of the skybox faces if it exists.
+const SKYBOX_HDRI: &str = "textures/skyboxes/sky.hdr";
+
+/// Loads the skybox from [SKYBOX_HDRI] if it exists, otherwise from the faces, falling back to a
+/// placeholder cubemap if any of them can't be loaded.
+/// The faces are read without caching since they are large and only needed once.
+fn load_skybox_cubemap(device: &wgpu::Device, queue: &wgpu::Queue, assets: &AssetServer) -> SkyboxCubemap {
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+    let hdri = assets.resolve(SKYBOX_HDRI);
+    if hdri.exists() {
+        match SkyboxCubemap::load_equirectangular(device, queue, Some("Skybox"), &hdri, None) {
+            Ok(cubemap) => return cubemap,
+            Err(err) => log::error!("Failed to load skybox {hdri:?}: {err}"),
+        }
+    }
+    let faces = ["right", "left", "top", "bottom", "front", "back"]
+        .map(|side| assets.read_image(std::path::Path::new(SKYBOX_DIR).join(format!("purp_{side}.png"))));
+    let result = match faces {
+        [Ok(right), Ok(left), Ok(top), Ok(bottom), Ok(front), Ok(back)] => {
+            SkyboxCubemap::from_images(device, queue, Some("Skybox"), FORMAT, [&right, &left, &top, &bottom, &front, &back])
+                .map_err(|err| log::error!("Failed to create skybox: {err}"))
+        }
+        faces => {
+            faces.into_iter()
+                .filter_map(Result::err)
+                .for_each(|err| log::error!("Failed to load skybox: {err}"));
+            Err(())
+        }
+    };
+    result.unwrap_or_else(|()| {
+        let placeholder = placeholder_image(64, 64);
+        SkyboxCubemap::from_images(device, queue, Some("Skybox"), FORMAT, [&placeholder; 6])
+            .expect("Placeholder faces have the same dimensions.")
+    })
+}
+
+/// Requests the device with the features and limits that the renderer needs.
+async fn request_device(adapter: &wgpu::Adapter) -> Result<(wgpu::Device, wgpu::Queue), wgpu::RequestDeviceError> {
+    let mut limits = wgpu::Limits {
+        max_push_constant_size: 128,
+        ..Default::default()
+    };
+    limits.max_push_constant_size = 256;
+    // The raytrace pass writes the result, the accumulation and the two G-buffer textures, and reads the
+    // ray directions.
+    limits.max_storage_textures_per_shader_stage = 8;
+    // Device and Queue
+    adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            required_features: wgpu::Features::PUSH_CONSTANTS
+            | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+            | wgpu::Features::TIMESTAMP_QUERY
+            | wgpu::Features::TIMESTAMP_QUERY_INSIDE_PASSES
+            // Optional: compressed textures are decompressed on the CPU without it.
+            | (adapter.features() & wgpu::Features::TEXTURE_COMPRESSION_BC),
+            required_limits: limits,
+            label: None,
+            memory_hints: MemoryHints::Performance,
+        },
+        None
+    ).await
+}
+
+/// Sets `flag` once `device` is lost.
+fn watch_device_lost(device: &wgpu::Device, flag: &Arc<AtomicBool>) {
+    let flag = Arc::clone(flag);
+    device.set_device_lost_callback(move |reason, message| {
+        log::error!("The device was lost ({reason:?}): {message}");
+        flag.store(true, std::sync::atomic::Ordering::Relaxed);
+    });
+}
+
+/// The block textures at the start of the texture array, as `(layer name, path)`. The layer of a texture is
+/// its index, which the [BlockFaces] of the blocks refer to.
+const BLOCK_TEXTURES: [(&str, &str); 2] = [
+    ("dirt", "textures/cube_sides/packed_dirt3.png"),
+    ("grass_top", "textures/cube_sides/grass_001.png"),
+];
+const DIRT_LAYER: u32 = 0;
+const GRASS_TOP_LAYER: u32 = 1;
+/// Every layer of the texture array has this size, so larger block textures are scaled down.
+const BLOCK_TEXTURE_SIZE: (u32, u32) = (32, 32);
+
+/// The textures of the voxel meshes. The images are cached by the [AssetServer], so this is cheap
+/// to call again when the texture array has to be recreated.
+fn load_texture_array(device: &wgpu::Device, queue: &wgpu::Queue, assets: &mut AssetServer) -> TextureArray {
+    let (width, height) = BLOCK_TEXTURE_SIZE;
+    let block_textures = BLOCK_TEXTURES.map(|(_, path)| assets.load_image_or_placeholder(path, BLOCK_TEXTURE_SIZE));
+    let block_textures = block_textures.map(|handle| {
+        let image = assets.image(handle);
+        if image.dimensions() == BLOCK_TEXTURE_SIZE {
+            Cow::Borrowed(image)
+        } else {
+            Cow::Owned(image::imageops::resize(image, width, height, image::imageops::FilterType::Triangle))
+        }
+    });
+    // The font for world space text follows the tiles (see text3d).
+    let font = text3d::font_layer_images(width, height);
+    let images = block_textures.iter().map(|image| image.as_ref()).chain(&font).collect::<Vec<_>>();
+    let mut texture_array = TextureArray::from_images(
+        device,
+        queue,
+        &images,
+        Some("Debug Texture Array"),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+        wgpu::AddressMode::Repeat,
+        wgpu::AddressMode::Repeat,
+        5,
+    ).unwrap_or_else(|err| {
+        log::error!("Failed to create texture array: {err}");
+        let placeholder = placeholder_image(width, height);
+        let font = text3d::font_layer_images(width, height);
+        let images = [&placeholder; BLOCK_TEXTURES.len()].into_iter().chain(&font).collect::<Vec<_>>();
+        TextureArray::from_images(
+            device,
+            queue,
+            &images,
+            Some("Debug Texture Array"),
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            wgpu::AddressMode::Repeat,
+            wgpu::AddressMode::Repeat,
+            5,
+        ).expect("Placeholder images have the same dimensions.")
+    });
+    for (layer, (name, _)) in BLOCK_TEXTURES.iter().enumerate() {
+        texture_array.layers.insert(name.to_string(), layer as u32);
     }
+    texture_array.layers.insert(text3d::FONT_LAYER_NAME.to_string(), BLOCK_TEXTURES.len() as u32);
+    texture_array
+}
+
+/// The voxel render pipeline and its instanced variant, which takes the model matrix from an instance buffer.
+fn create_voxel_pipelines(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    transforms: &TransformsBindGroup,
+    texture_array: &TextureArray,
+    fog_bind_group: &FogBindGroup,
+    frame_uniforms: &FrameUniformsBindGroup,
+) -> (wgpu::RenderPipeline, wgpu::RenderPipeline) {
+    // Include Shader
+    let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/voxel.wgsl"));
+    // Render Pipeline Layout
+    let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Render Pipeline Layout"),
+        bind_group_layouts: &[
+            &transforms.bind_group_layout,
+            &texture_array.bind_group.bind_group_layout,
+            &fog_bind_group.bind_group_layout,
+            &frame_uniforms.bind_group_layout,
+        ],
+        push_constant_ranges: &[wgpu::PushConstantRange {
+            range: 0..64,
+            stages: wgpu::ShaderStages::VERTEX,
+        }],
+    });
+    // Render Pipeline
+    let create_voxel_pipeline = |label: &str, entry_point: &str, buffers: &[wgpu::VertexBufferLayout]| device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(&render_pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some(entry_point),
+            buffers,
+            compilation_options: wgpu::PipelineCompilationOptions {
+                ..Default::default()
+            },
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions {
+                ..Default::default()
+            },
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: msaa::multisample_state(sample_count),
+        multiview: None,
+        cache: None,
+    });
+    let render_pipeline = create_voxel_pipeline("Render Pipeline", "vs_main", &[Vertex::desc()]);
+    // Same as the render pipeline, but with the model matrix coming from an instance buffer.
+    let instanced_pipeline = create_voxel_pipeline("Instanced Render Pipeline", "vs_instanced", &[Vertex::desc(), Vertex::instance_desc()]);
+    (render_pipeline, instanced_pipeline)
+}
+
+/// Builds the debug grid mesh on a worker. The result arrives as [TaskOutput::Mesh].
+fn spawn_grid_mesh(tasks: &mut TaskPool<TaskOutput>) -> TaskId {
+    tasks.spawn(|| {
+        let mut m = Modeler::new();
+        m.texture_index(DIRT_LAYER, move |m| {
+            for y in 0..16 {
+                for x in 0..16 {
+                    let xf = x as f32;
+                    let yf = y as f32;
+                    m.translate(vec3(xf, 0.0, yf), move |m| {
+                        m.push_unit_quad();
+                    });
+                }
+            }
+        });
+        TaskOutput::Mesh(MeshData {
+            vertices: m.vertices,
+            indices: m.indices,
+        })
+    })
+}
+
+/// A grid of 16x16 planes, one per chunk column.
+fn create_grid_instances(device: &wgpu::Device) -> InstanceBuffer {
+    let instances = (-64..64).flat_map(|z| (-64..64).map(move |x| {
+        InstanceData::from_translation(vec3(x as f32 * 16.0, 0.0, z as f32 * 16.0))
+    })).collect::<Vec<_>>();
+    InstanceBuffer::new(device, &instances)
+}
+
+/// The reticle styles, in the order that Ctrl+K cycles through them.
+const RETICLE_STYLES: [(&str, &str); 4] = [
+    ("Crosshair", "textures/reticles/crosshair118.png"),
+    ("Dot", "textures/reticles/dot.png"),
+    ("Cross", "textures/reticles/cross.png"),
+    ("Ring", "textures/reticles/ring.png"),
+];
+
+fn create_reticle(device: &wgpu::Device, queue: &wgpu::Queue, assets: &mut AssetServer, config: &wgpu::SurfaceConfiguration, sample_count: u32) -> Reticle {
+    let textures = RETICLE_STYLES.map(|(name, path)| {
+        (name, assets.load_texture_or_placeholder(
+            device,
+            queue,
+            path,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            (72, 72),
+        ))
+    });
+    let styles = textures.iter().map(|&(name, texture)| (name, &assets.texture(texture).view)).collect::<Vec<_>>();
+    Reticle::new(device, &styles, config, sample_count)
+}
+
+/// How a camera of the [CameraRig] follows the main camera.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RigMode {
+    /// Looks straight down from `height` above the main camera, with the main camera's forward direction
+    /// at the top.
+    TopDown { height: f32 },
+    /// Looks at the main camera from `distance` behind and `height` above it.
+    ThirdPerson { distance: f32, height: f32 },
+    /// Stays where it was put.
+    Fixed,
+}
+
+pub struct RigCamera {
+    pub name: &'static str,
+    pub mode: RigMode,
+    pub camera: Camera,
+}
+
+/// The secondary cameras. The selected one is shown in the picture-in-picture inset (Backquote cycles
+/// through them), see [crate::rendering::pip].
+#[derive(Default)]
+pub struct CameraRig {
+    cameras: Vec<RigCamera>,
+    selected: Option<usize>,
 }
 
-// pub struct Animation
+impl CameraRig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the index of the camera.
+    pub fn add(&mut self, name: &'static str, mode: RigMode, camera: Camera) -> usize {
+        self.cameras.push(RigCamera { name, mode, camera });
+        self.cameras.len() - 1
+    }
+
+    pub fn get(&self, index: usize) -> Option<&RigCamera> {
+        self.cameras.get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut RigCamera> {
+        self.cameras.get_mut(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.cameras.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cameras.is_empty()
+    }
+
+    pub fn selected(&self) -> Option<&RigCamera> {
+        self.cameras.get(self.selected?)
+    }
+
+    pub fn select(&mut self, index: Option<usize>) {
+        self.selected = index.filter(|&index| index < self.cameras.len());
+    }
+
+    /// Selects the next camera, and none after the last one.
+    pub fn select_next(&mut self) -> Option<&RigCamera> {
+        let next = self.selected.map_or(0, |index| index + 1);
+        self.select(Some(next));
+        self.selected()
+    }
 
-const MOVE_SPEEDS: [f32; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+    /// Moves the cameras that follow `main`. `aspect_ratio` is that of the view that they're shown in.
+    pub fn update(&mut self, main: &Camera, aspect_ratio: f32) {
+        for rig in self.cameras.iter_mut() {
+            rig.camera.aspect_ratio = aspect_ratio;
+            match rig.mode {
+                RigMode::TopDown { height } => {
+                    rig.camera.position = main.position + Vec3::Y * height;
+                    rig.camera.rotation = glam::vec2(-std::f32::consts::FRAC_PI_2, main.rotation.y);
+                }
+                RigMode::ThirdPerson { distance, height } => {
+                    rig.camera.position = main.position + main.pan_backward() * distance + Vec3::Y * height;
+                    rig.camera.look_at(main.position);
+                }
+                RigMode::Fixed => (),
+            }
+        }
+    }
+}
 
 pub struct State<'a> {
     pub surface: wgpu::Surface<'a>,
+    pub adapter: wgpu::Adapter,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
@@ -100,6 +594,8 @@ pub struct State<'a> {
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
     pub num_indices: u32,
+    pub instanced_pipeline: wgpu::RenderPipeline,
+    pub instance_buffer: InstanceBuffer,
     pub last_time: std::time::Instant,
     // Texture Array
     pub texture_array: TextureArray,
@@ -108,32 +604,154 @@ pub struct State<'a> {
     // Fog
     pub fog_bind_group: FogBindGroup,
     pub fog: Fog,
+    /// The time, frame index, resolution and jitter of the frame, advanced in [State::begin_render].
+    pub frame_uniforms: FrameUniformsBindGroup,
     // Camera
     pub camera: Camera,
-    pub move_speed_index: usize,
+    /// The flying speed presets and momentum (Left/Right select the preset, Shift+P toggles free movement).
+    pub movement: MovementController,
+    /// Camera poses saved with Ctrl+1..9, see [crate::bookmarks].
+    pub bookmarks: Bookmarks,
     // Input State
     pub input: Input,
     pub gamepad: Gilrs,
     pub settings: Settings,
     pub text_rend: TextRend,
+    /// Mouse look. While locked, the cursor is hidden and grabbed (see [State::set_locked]).
     pub locked: bool,
-    pub animation: Option<StateAnimator>,
+    /// How the cursor is actually grabbed. [CursorGrabMode::None] while locked means that the platform
+    /// doesn't support grabbing, so the cursor is warped back to the center every frame instead.
+    pub cursor_grab: CursorGrabMode,
+    pub focused: bool,
+    /// The config that was last applied with [State::apply_window_config].
+    pub window_config: WindowConfig,
+    pub place_id: u32,
+    /// Finds the block under the reticle for editing.
+    pub picker: Picker,
+    /// The field of view without zoom.
+    pub base_fov: f32,
+    /// Zoom progress from 0.0 (not zoomed) to 1.0 (fully zoomed).
+    pub zoom: f32,
+    pub zoom_pressed_at: Option<Instant>,
+    pub animations: Animations<Camera>,
+    /// F8 adds the camera pose to the path and F9 plays it back.
+    pub camera_path: CameraPath,
+    pub camera_path_duration: Duration,
+    pub sequence_settings: SequenceSettings,
+    /// The running benchmark (Ctrl+F9 starts one), see [State::start_benchmark].
+    pub benchmark: Option<Benchmark>,
+    /// Exit once the running benchmark is saved (for `--bench`).
+    exit_after_benchmark: bool,
+    exit_requested: bool,
+    pub keybinds: Keybinds,
+    /// Where the user config is saved, see [State::set_user_config_path].
+    user_config_path: Option<PathBuf>,
+    saved_user_config: UserConfig,
+    user_config_changed_at: Option<(Instant, UserConfig)>,
+    pub tasks: TaskPool<TaskOutput>,
+    // Only the most recently requested chunk/mesh is used, older results are discarded.
+    pub pending_chunk: Option<TaskId>,
+    pub pending_mesh: Option<TaskId>,
+    pub pending_chunk_mesh: Option<TaskId>,
+    /// The chunk changed since the last mesh of it was started.
+    chunk_mesh_dirty: bool,
+    pub pending_save: Option<TaskId>,
+    /// Errors from wgpu and from reloading shaders, shown on the overlay.
+    pub gpu_errors: GpuErrors,
+    /// Set by the device lost callback, see [State::recover_lost_device].
+    device_lost: Arc<AtomicBool>,
+    pub saves: WorldSaves,
+    notification: Option<(String, Instant)>,
+    pub world_seed: u32,
     // pub depth_stencil: wgpu::Texture,
     // pub depth_texture_view: wgpu::TextureView,
     // pub glyphon_pipeline: wgpu::RenderPipeline,
     pub raytracer: Raytracer,
+    pub day_night: DayNightCycle,
+    /// The clouds in the raytracer's sky, moved by [crate::systems::CloudsSystem].
+    pub clouds: Clouds,
+    /// Places the directional light by hand (Ctrl+Q toggles it). It follows the day/night cycle while that's running.
+    pub sun_gizmo: SunGizmo,
+    /// The index in [LightingPreset::ALL] of the last preset applied (Ctrl+F1 cycles them).
+    pub lighting_preset: Option<usize>,
     pub raytrace_timer: AverageBuffer<Duration>,
-    pub rt_query_buffer: wgpu::Buffer,
-    pub rt_query_read_buffer: wgpu::Buffer,
-    pub rt_query_set: wgpu::QuerySet,
+    /// Times the raytracer's compute pass. The results are collected in [State::begin_frame].
+    pub raytrace_gpu_timer: GpuTimer,
+    /// The newest raytrace time collected this frame, for the frame graph.
+    new_raytrace_time: Option<Duration>,
+    pub comparison_timer: AverageBuffer<Duration>,
+    /// Times the B side of the raytracer's A/B comparison (Alt+F3), see [Raytracer::compute_comparison].
+    pub comparison_gpu_timer: GpuTimer,
+    /// Times every pass of [State::render] that is recorded through [FrameGraphLite].
+    pub pass_profiler: PassProfiler,
+    /// Plots the frame times on the UI layer (Ctrl+F4 toggles it).
+    pub frame_graph: FrameGraph,
+    /// Picks the raytracer's render scale from the frame times (Ctrl+Backslash toggles it).
+    pub dynamic_render_scale: DynamicRenderScale,
     pub reticle: Reticle,
     pub ortho: glam::Mat4,
-    // vello
+    pub assets: AssetServer,
+    /// The scene is rendered into the post chain, the UI is drawn on top of its output.
+    pub post: PostChain,
+    /// The multisampled target of the scene and UI passes. Every raster pipeline that draws in them
+    /// uses its sample count.
+    pub msaa: Msaa,
+    /// Lists the color spaces of the pipelines on the overlay (Ctrl+F3), see [State::color_paths].
+    pub color_audit: bool,
+    /// RenderDoc captures of single frames (Ctrl+F8).
+    pub debug_capture: FrameCapture,
+    /// The log console (Ctrl+Backquote).
+    pub console: Console,
+    /// The vector overlay, with the frame graph and the sun gizmo on it.
     pub velvet: Velvet,
+    overlay: Option<OverlayDraw>,
+    /// Present while walking (Digit1 toggles between walking and flying).
+    pub player: Option<CharacterController>,
+    pub recorder: Option<InputRecorder>,
+    /// While playing back, recorded input replaces the live input.
+    pub playback: Option<InputPlayback>,
+    pub gizmo: Gizmo,
+    pub labels: Labels,
+    /// The ground grid (Quote toggles it).
+    pub gridzmo: Gridzmo,
+    pub camera_rig: CameraRig,
+    /// Shows the selected camera of the rig.
+    pub pip: PictureInPicture,
+    /// The rasterized view of the chunk, drawn in the [RenderMode]s that show it.
+    pub raster: RasterView,
+    /// While selecting (Digit3 toggles), left click drags out a box instead of placing blocks.
+    pub selection_mode: bool,
+    pub selection: Option<Selection>,
+    /// The cell where the current selection drag started.
+    pub selection_anchor: Option<glam::IVec3>,
+    pub clipboard: Option<ClipboardVolume>,
+    /// The size of the area edit tools (Ctrl+BracketLeft/Ctrl+BracketRight).
+    pub brush: Brush,
+    /// Reshapes the terrain while left click is held, instead of placing blocks (Ctrl+H cycles the brushes).
+    pub sculptor: Sculptor,
+    /// Uploads GPU data through the frame's command encoder.
+    pub staging: StagingRing,
+    /// Registered from main.rs, see [crate::systems].
+    pub systems: Systems,
+    /// Uploaded to the raytracer in [State::begin_render].
+    pub entities: Entities,
+    /// The demo entity that circles the chunk, see [State::update_entities].
+    pub orbiter: Option<(EntityId, Orbit)>,
+    /// How far the orbiter has moved. Semicolon pauses it, so that the raytracer can accumulate.
+    pub orbit_time: Duration,
+    pub orbit_paused: bool,
+    /// Uploaded to the raytracer in [State::begin_render].
+    pub lights: Lights,
+    /// The point light that follows the camera (Ctrl+B toggles it), see [State::update_torch].
+    pub torch: Option<LightId>,
 }
 
 impl<'a> State<'a> {
     pub async fn new(window: &'a Window) -> State<'a> {
+        Self::with_assets_root(window, ASSETS_ROOT).await
+    }
+
+    pub async fn with_assets_root<P: Into<PathBuf>>(window: &'a Window, assets_root: P) -> State<'a> {
         let size = window.inner_size();
         let aspect_ratio = size.width as f32 / size.height as f32;
         // Instance
@@ -151,24 +769,11 @@ impl<'a> State<'a> {
                 force_fallback_adapter: false,
             },
         ).await.unwrap();
-        let mut limits = wgpu::Limits {
-            max_push_constant_size: 128,
-            ..Default::default()
-        };
-        limits.max_push_constant_size = 256;
-        // Device and Queue
-        let (device, queue) = adapter.request_device(
-            &wgpu::DeviceDescriptor {
-                required_features: wgpu::Features::PUSH_CONSTANTS
-                | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
-                | wgpu::Features::TIMESTAMP_QUERY
-                | wgpu::Features::TIMESTAMP_QUERY_INSIDE_PASSES,
-                required_limits: limits,
-                label: None,
-                memory_hints: MemoryHints::Performance,
-            },
-            None
-        ).await.unwrap();
+        let (device, queue) = request_device(&adapter).await.unwrap();
+        let gpu_errors = GpuErrors::default();
+        gpu_errors.install(&device);
+        let device_lost = Arc::new(AtomicBool::new(false));
+        watch_device_lost(&device, &device_lost);
         // adapter.request_device(
         //     &DeviceDescriptor {
         //         features: Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
@@ -176,25 +781,14 @@ impl<'a> State<'a> {
         //     },
         //     None,
         // ).await
-        #[cfg(debug_assertions)]
-        {
-            match adapter.get_info().backend {
-                wgpu::Backend::Vulkan => println!("Vulkan backend."),
-                wgpu::Backend::Metal => println!("Metal backend. (wtf? This is running on a mac?)"),
-                wgpu::Backend::Dx12 => println!("Dx12 backend."),
-                wgpu::Backend::Gl => println!("Gl backend."),
-                wgpu::Backend::BrowserWebGpu => println!("BrowserWebGpu backend."),
-                _ => {}
-            }
-            let features = adapter.features();
-            if features.contains(wgpu::Features::PUSH_CONSTANTS) {
-                println!("Push constants are supported.");
-            } else {
-                println!("Push constants are not supported.");
-            }
-            let limits = device.limits();
-            println!("Push constant size limit: {}", limits.max_push_constant_size);
-        }
+        let info = adapter.get_info();
+        log::info!("Using {} ({:?} backend).", info.name, info.backend);
+        let push_constants = adapter.features().contains(wgpu::Features::PUSH_CONSTANTS);
+        log::debug!(
+            "Push constants are {}supported (size limit: {}).",
+            if push_constants { "" } else { "not " },
+            device.limits().max_push_constant_size,
+        );
         // Surface Caps/Format
         let surface_caps = surface.get_capabilities(&adapter);
         let surface_format = surface_caps.formats.iter()
@@ -212,52 +806,27 @@ impl<'a> State<'a> {
             view_formats: vec![],
             desired_maximum_frame_latency: 2,
         };
+        let mut assets = AssetServer::new(assets_root);
         // Texture Array
-        let cube_sides_dir = std::path::PathBuf::from("./assets/textures/cube_sides/");
-        let texture_array = TextureArray::from_files(
-            &device,
-            &queue,
-            &[
-                cube_sides_dir.join("packed_dirt3.png"),
-                cube_sides_dir.join("packed_dirt3.png"),
-                cube_sides_dir.join("packed_dirt3.png"),
-                cube_sides_dir.join("packed_dirt3.png"),
-                cube_sides_dir.join("packed_dirt3.png"),
-                cube_sides_dir.join("packed_dirt3.png"),
-                // cube_sides_dir.join("pos_y.png"),
-            ],
-            Some("Debug Texture Array"),
-            wgpu::TextureFormat::Rgba8UnormSrgb,
-            wgpu::AddressMode::Repeat,
-            wgpu::AddressMode::Repeat,
-            5,
-        ).expect("Failed to load texture array.");
+        let texture_array = load_texture_array(&device, &queue, &mut assets);
         // Texture Array Bind Group
         // let texture_array_bind_group = texture_array.bind_group(&device);
         // Transforms
         let transforms = TransformsBindGroup::new(&device);
 
-        let skybox_dir = std::path::PathBuf::from("./assets/textures/skyboxes/complex/");
-        let skybox = Skybox::new(
+        // The sample count from the user config is applied in State::apply_user_config.
+        let msaa = Msaa::new(&device, HDR_FORMAT, config.format, size.width, size.height, 1);
+        let sky_cubemap = load_skybox_cubemap(&device, &queue, &assets);
+        let skybox = Skybox::with_cubemap(
             &device,
-            &queue,
-            &config,
-            Some("Skybox"),
-            wgpu::TextureFormat::Rgba8UnormSrgb,
-            // surface_format,
+            HDR_FORMAT,
+            msaa.sample_count(),
             &transforms,
-            &SkyboxTexturePaths {
-                top: skybox_dir.join("purp_top.png"),
-                bottom: skybox_dir.join("purp_bottom.png"),
-                left: skybox_dir.join("purp_left.png"),
-                right: skybox_dir.join("purp_right.png"),
-                front: skybox_dir.join("purp_front.png"),
-                back: skybox_dir.join("purp_back.png"),
-            }
-        ).expect("Failed to load skybox.");
+            sky_cubemap.clone(),
+        );
         
         // Camera
-        let camera = Camera::from_look_to(
+        let mut camera = Camera::from_look_to(
             Vec3::new(0.0, 16.0, 0.0),
             vec3(-1.0, 0.0, 1.0).normalize(),
             60f32.to_radians(),
@@ -273,137 +842,24 @@ impl<'a> State<'a> {
 
         
 
-        let fog = Fog::new(40000.0, 50000.0, vec4(60.0, 60.0, 60.0, 0.0));
+        // Fades into the skybox. The scroll wheel moves the start of the fog.
+        let fog = Fog::new(160.0, 480.0, vec4(0.6, 0.7, 0.8, 0.0)).with_height_fog(0.004, 0.08);
         let fog_bind_group = FogBindGroup::new(&device);
         fog_bind_group.write_fog(&queue, &fog);
+        let frame_uniforms = FrameUniformsBindGroup::new(&device, FrameUniforms::ZERO);
 
 
-        // Include Shader
-        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/voxel.wgsl"));
-        // Render Pipeline Layout
-        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[
-                &transforms.bind_group_layout,
-                &texture_array.bind_group.bind_group_layout,
-                &fog_bind_group.bind_group_layout,
-            ],
-            push_constant_ranges: &[wgpu::PushConstantRange {
-                range: 0..64,
-                stages: wgpu::ShaderStages::VERTEX,
-            }],
-        });
-        // Render Pipeline
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[
-                    Vertex::desc()
-                ],
-                compilation_options: wgpu::PipelineCompilationOptions {
-                    ..Default::default()
-                },
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions {
-                    ..Default::default()
-                },
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-            cache: None,
-        });
-
-        let mut m = Modeler::new();
-        m.texture_index(4, move |m| {
-            // m.push_unit_quad();
-            for y in 0..16 {
-                for x in 0..16 {
-                    let xf = x as f32;
-                    let yf = y as f32;
-                    m.translate(vec3(xf, 0.0, yf), move |m| {
-                        m.push_unit_quad();
-                    });
-                }
-            }
-        });
-
-        // println!("{:?}", &m.vertices);
-        // println!("{:?}", &m.indices);
-        // std::thread::sleep(Duration::from_secs(5));
-
-        // m.vertices.extend_from_slice(Vertex::PLANE_VERTICES);
-        // m.indices.extend_from_slice(Vertex::PLANE_INDICES);
+        let (render_pipeline, instanced_pipeline) = create_voxel_pipelines(&device, HDR_FORMAT, msaa.sample_count(), &transforms, &texture_array, &fog_bind_group, &frame_uniforms);
 
-        // Vertex Buffer
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(m.vertices.as_slice()),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-        // Index Buffer
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(m.indices.as_slice()),
-            usage: wgpu::BufferUsages::INDEX,
-        });
+        let base_fov = camera.fov;
+        let mut tasks = TaskPool::with_available_parallelism();
+        // The grid mesh is built on a worker, the buffers are replaced in update() once it's done.
+        let pending_mesh = Some(spawn_grid_mesh(&mut tasks));
+        let mesh_buffers = MeshData::default().create_buffers(&device);
 
-        let text_rend = {
-            let mut font_system = FontSystem::new();
-            let cache = glyphon::Cache::new(&device);
-            let mut text_atlas = TextAtlas::new(&device, &queue, &cache, surface_format);
-            let text_renderer = TextRenderer::new(
-                &mut text_atlas,
-                &device,
-                MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false
-                },
-                None,
-            );
-
-            let mut front_buffer = Buffer::new(&mut font_system, Metrics::new(48.0, 48.0));
-            front_buffer.set_size(&mut font_system, Some(size.width as f32), Some(size.height as f32));
-            let mut back_buffer = Buffer::new(&mut font_system, Metrics::new(48.0, 48.0));
-            front_buffer.set_size(&mut font_system, Some(size.width as f32), Some(size.height as f32));
-
-            TextRend {
-                font_system,
-                cache,
-                text_atlas,
-                text_renderer,
-                front_buffer,
-                back_buffer,
-                swash_cache: SwashCache::new(),
-            }
-        };
+        let instance_buffer = create_grid_instances(&device);
 
-        
+        let text_rend = TextRend::new(&device, &queue, surface_format, msaa.sample_count(), size);
 
         // Depth texture
         // let (depth_stencil, depth_texture_view) = {
@@ -467,7 +923,12 @@ impl<'a> State<'a> {
         //         }
         //     }
         // }
-        let mut raytracer = Raytracer::new(&device, &queue, &camera, Some(chunk), &Lighting {
+        // Start on top of the terrain.
+        if let Some(spawn) = chunk.find_spawn_point() {
+            camera.position = spawn + Vec3::Y * CharacterController::DEFAULT_EYE_HEIGHT;
+            transforms.write_view_projection(&queue, &camera.projection_view_matrix());
+        }
+        let lighting = Lighting {
             directional: DirectionalLight {
                 // color: vec3(0.9568627450980393, 0.9137254901960784, 0.6078431372549019),
                 color: vec3(1.0, 1.0, 1.0),
@@ -481,76 +942,189 @@ impl<'a> State<'a> {
                 color: Vec3::ONE,
                 intensity: 0.1,
                 active: true,
+                ao_strength: AO_STRENGTH,
             }
+        };
+        let day_night = DayNightCycle::new(&lighting.directional, 0.35, Duration::from_secs(60 * 4));
+        let mut raytracer = Raytracer::new(&device, &queue, &camera, Some(chunk), &lighting, &sky_cubemap, frame_uniforms.buffer.buffer(), WorkgroupSize::DEFAULT);
+        raytracer.set_render_target(HDR_FORMAT, msaa.sample_count(), &device);
+        day_night.apply(&raytracer.gpu_lighting, &queue);
+        let sun_gizmo = SunGizmo::new(day_night.light_direction());
+        raytracer.set_material(MIRROR_ID, Material {
+            color: vec3(0.9, 0.95, 1.0),
+            reflectivity: 0.8,
+            emission: 0.0,
+            ..Material::DEFAULT
+        }, &queue);
+        raytracer.set_material(LAMP_ID, Material {
+            color: vec3(1.0, 0.85, 0.6),
+            reflectivity: 0.0,
+            emission: 1.5,
+            ..Material::DEFAULT
+        }, &queue);
+        raytracer.set_material(WATER_ID, Material::WATER, &queue);
+        raytracer.materials.set_faces(BLOCK_ID, BlockFaces {
+            top: GRASS_TOP_LAYER,
+            bottom: DIRT_LAYER,
+            side: DIRT_LAYER,
         });
         let raytrace_timer = AverageBuffer::<Duration>::new(100, None);
-        let reticle = match Reticle::new(&device, &queue, "assets/textures/reticles/crosshair118.png", &config) {
-            Ok(reticle) => reticle,
-            Err(err) => panic!("Error Creating Reticle: {err}"),
-        };
+        let mut reticle = create_reticle(&device, &queue, &mut assets, &config, msaa.sample_count());
+        reticle.set_scale_factor(&queue, window.scale_factor() as f32);
+        let mut post = PostChain::new(&device, size.width, size.height, HDR_FORMAT, config.format);
+        // Metered first, so that bloom and tonemapping see the exposed image.
+        post.push(AutoExposure::new(&device, &post));
+        // Bloom is applied before tonemapping so that it is tonemapped with the rest of the image.
+        post.push(Bloom::new(&device, &post));
+        let mut tonemap = Tonemap::new(&device, &post);
+        tonemap.enabled = false;
+        post.push(tonemap);
+        let mut gamma = Gamma::new(&device, &post);
+        gamma.enabled = false;
+        post.push(gamma);
+        post.push(Vignette::new(&device, &post));
 
         let ortho = glam::Mat4::orthographic_rh(0.0, size.width as f32, size.height as f32, 0.0, 0.0, 100.0);
 
-        let rt_query_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Raytrace Timestamp Buffer"),
-            size: 16,
-            mapped_at_creation: false,
-            usage:  wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
-        });
-
-        let rt_query_read_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Raytrace Timestamp Read Buffer"),
-            size: 16,
-            mapped_at_creation: false,
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
-        });
-
-        let rt_query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
-            label: Some("Raytrace Query Set"),
-            count: 2,
-            ty: wgpu::QueryType::Timestamp,
-        });
-
-        let velvet = Velvet::new(&device);
+        let raytrace_gpu_timer = GpuTimer::new(&device, &queue, "Raytrace", DEFAULT_FRAMES_IN_FLIGHT);
+        let comparison_gpu_timer = GpuTimer::new(&device, &queue, "Raytrace Comparison", DEFAULT_FRAMES_IN_FLIGHT);
+        let pass_profiler = PassProfiler::new(&device, &queue);
+
+        let velvet = Velvet::new(&device, config.format, msaa.sample_count(), size.width, size.height);
+        let gizmo = Gizmo::new(&device, HDR_FORMAT, msaa.sample_count(), &transforms);
+        let labels = Labels::new(&device, HDR_FORMAT, msaa.sample_count(), &transforms, &texture_array);
+        let gridzmo = Gridzmo::new(&device, HDR_FORMAT, msaa.sample_count(), raytracer.hit_bind_group_layout());
+        let mut camera_rig = CameraRig::new();
+        let rig_camera = || Camera::at(Vec3::ZERO, 60f32.to_radians(), 0.1, 1000.0, size, None::<Skybox>);
+        camera_rig.add("Top Down", RigMode::TopDown { height: 96.0 }, rig_camera());
+        camera_rig.add("Third Person", RigMode::ThirdPerson { distance: 24.0, height: 12.0 }, rig_camera());
+        let pip = PictureInPicture::new(&device, msaa.sample_count(), size.width, size.height, raytracer.hit_bind_group_layout());
+        let raster = RasterView::new(&device, msaa.sample_count(), size.width, size.height, &transforms, &texture_array, &frame_uniforms, raytracer.gpu_lighting.lighting());
+        let mut entities = Entities::new();
+        let orbit = Orbit {
+            center: vec3(32.0, 40.0, 32.0),
+            radius: 40.0,
+            period: Duration::from_secs(20),
+        };
+        let orbiter = entities.spawn(Entity::cube(orbit.position(Duration::ZERO), 3.0, vec3(0.9, 0.2, 0.15)))
+            .map(|id| (id, orbit));
+        entities.spawn(Entity::billboard(orbit.center + Vec3::Y * 12.0, 2.0, vec3(1.0, 0.8, 0.4)).with_emission(4.0));
 
         // return
         Self {
             window,
             surface,
+            adapter,
             device,
             queue,
             config,
             size,
             render_pipeline,
-            vertex_buffer,
-            index_buffer,
-            num_indices: m.indices.len() as u32,
+            vertex_buffer: mesh_buffers.vertex_buffer,
+            index_buffer: mesh_buffers.index_buffer,
+            num_indices: mesh_buffers.num_indices,
+            instanced_pipeline,
+            instance_buffer,
             texture_array,
             camera,
-            move_speed_index: 4,
+            movement: MovementController::default(),
+            bookmarks: Bookmarks::new(),
             transforms,
             fog_bind_group,
             fog,
+            frame_uniforms,
             last_time: std::time::Instant::now(),
             input: Input::default(),
             gamepad: Gilrs::new().expect("Failed to create gamepad."),
             settings: Settings {
-                mouse_smoothing: false,
-                mouse_halting: false,
+                mouse: MouseSettings::default(),
+                ambient_occlusion: true,
+                draw_instanced_grid: false,
+                draw_labels: false,
+                draw_overlay: true,
+                chunk_debug: ChunkDebugMode::Off,
+                render_mode: RenderMode::Raytraced,
             },
             text_rend,
             locked: false,
-            animation: None,
+            cursor_grab: CursorGrabMode::None,
+            focused: true,
+            window_config: WindowConfig::default(),
+            place_id: BLOCK_ID,
+            picker: Picker::new(200.0),
+            base_fov,
+            zoom: 0.0,
+            zoom_pressed_at: None,
+            animations: Animations::new(),
+            camera_path: CameraPath::new(),
+            camera_path_duration: DEFAULT_CAMERA_PATH_DURATION,
+            sequence_settings: SequenceSettings::default(),
+            benchmark: None,
+            exit_after_benchmark: false,
+            exit_requested: false,
+            keybinds: Keybinds::default(),
+            user_config_path: None,
+            saved_user_config: UserConfig::default(),
+            user_config_changed_at: None,
+            tasks,
+            pending_chunk: None,
+            pending_mesh,
+            pending_chunk_mesh: None,
+            chunk_mesh_dirty: true,
+            pending_save: None,
+            gpu_errors,
+            device_lost,
+            saves: WorldSaves::new(SAVES_DIR),
+            notification: None,
+            world_seed: 0,
             // depth_stencil,
             // depth_texture_view,
             raytracer,
+            day_night,
+            clouds: Clouds::default(),
+            sun_gizmo,
+            lighting_preset: None,
             raytrace_timer,
-            rt_query_buffer,
-            rt_query_read_buffer,
-            rt_query_set,
+            raytrace_gpu_timer,
+            new_raytrace_time: None,
+            comparison_timer: AverageBuffer::new(100, None),
+            comparison_gpu_timer,
+            pass_profiler,
+            frame_graph: FrameGraph::default(),
+            dynamic_render_scale: DynamicRenderScale::default(),
             reticle,
+            assets,
+            post,
+            msaa,
+            color_audit: false,
+            debug_capture: FrameCapture::new(),
+            console: Console::new(),
             ortho,
             velvet,
+            overlay: None,
+            player: None,
+            recorder: None,
+            playback: None,
+            gizmo,
+            labels,
+            gridzmo,
+            camera_rig,
+            pip,
+            raster,
+            selection_mode: false,
+            entities,
+            orbiter,
+            orbit_time: Duration::ZERO,
+            orbit_paused: false,
+            lights: Lights::new(),
+            torch: None,
+            selection: None,
+            selection_anchor: None,
+            clipboard: None,
+            brush: Brush::DEFAULT,
+            sculptor: Sculptor::new(),
+            staging: StagingRing::default(),
+            systems: Systems::new(),
         }
     }
 
@@ -576,16 +1150,229 @@ impl<'a> State<'a> {
             self.ortho = glam::Mat4::orthographic_rh(0.0, new_size.width as f32, new_size.height as f32, 0.0, 0.0, 100.0);
             self.reticle.write_dimensions(&self.queue, new_size.width, new_size.height);
             self.reticle.write_ortho(&self.queue, &self.ortho);
-            // self.text_rend.buffer.set_size(&mut self.text_rend.font_system, Some(new_size.width as f32), Some(new_size.height as f32));
+            // Resizes also follow changes to the scale factor, such as moving the window to another display.
+            self.reticle.set_scale_factor(&self.queue, self.window.scale_factor() as f32);
+            self.post.resize(&self.device, new_size.width, new_size.height);
+            self.msaa.resize(&self.device, new_size.width, new_size.height);
+            self.pip.resize(&self.device, new_size.width, new_size.height);
+            self.raster.resize(&self.device, new_size.width, new_size.height);
+            self.velvet.resize(&self.device, new_size.width, new_size.height);
+            self.text_rend.resize(&self.device, new_size);
+        }
+    }
+
+    /// Reconfigures the surface with the given present mode.
+    /// Returns `false` (and leaves the surface unchanged) if the surface doesn't support the mode.
+    pub fn set_present_mode(&mut self, mode: wgpu::PresentMode) -> bool {
+        let caps = self.surface.get_capabilities(&self.adapter);
+        if !caps.present_modes.contains(&mode) {
+            log::warn!("Present mode {mode:?} is not supported by the surface.");
+            return false;
+        }
+        self.config.present_mode = mode;
+        self.surface.configure(&self.device, &self.config);
+        true
+    }
+
+    /// Switches to the next supported present mode in the order Fifo -> Mailbox -> Immediate.
+    pub fn cycle_present_mode(&mut self) {
+        const MODES: [wgpu::PresentMode; 3] = [
+            wgpu::PresentMode::Fifo,
+            wgpu::PresentMode::Mailbox,
+            wgpu::PresentMode::Immediate,
+        ];
+        let current = MODES.iter().position(|&mode| mode == self.config.present_mode).unwrap_or(0);
+        for offset in 1..MODES.len() {
+            if self.set_present_mode(MODES[(current + offset) % MODES.len()]) {
+                return;
+            }
+        }
+    }
+
+    /// Releases the cursor while the window is unfocused, and grabs it again once focus returns.
+    pub fn focus_changed(&mut self, focus: bool) {
+        self.focused = focus;
+        if !self.locked {
+            return;
+        }
+        if focus {
+            // Motion from while the window was unfocused shouldn't turn the camera.
+            self.input.mouse_pos.delta = PhysicalPosition::new(0.0, 0.0);
+            self.grab_cursor();
+        } else {
+            self.release_cursor();
+        }
+    }
+
+    /// Applies the title, icon, minimum size and fullscreen state. The windowed size is only applied if it
+    /// changed, so that applying a config at runtime doesn't undo resizing.
+    pub fn apply_window_config(&mut self, config: WindowConfig) {
+        if config.title != self.window_config.title {
+            self.window.set_title(&config.title);
+        }
+        self.window.set_min_inner_size(config.min_size);
+        match config.load_icon(&self.assets) {
+            Ok(icon) => self.window.set_window_icon(icon),
+            Err(err) => log::error!("Failed to load the window icon: {err}"),
+        }
+        self.window.set_fullscreen(config.fullscreen(self.window));
+        if config.fullscreen == FullscreenMode::Windowed && config.size != self.window_config.size {
+            _ = self.window.request_inner_size(config.size);
+        }
+        self.window_config = config;
+    }
+
+    /// The current user settings.
+    pub fn user_config(&self) -> UserConfig {
+        let window_size = if self.window_config.fullscreen == FullscreenMode::Windowed {
+            self.window.inner_size()
+        } else {
+            self.window_config.size.to_physical(self.window.scale_factor())
+        };
+        UserConfig {
+            mouse: self.settings.mouse,
+            move_speed_index: self.movement.index(),
+            speed_presets: self.movement.presets().to_vec(),
+            bookmarks: self.bookmarks,
+            keybinds: self.keybinds,
+            fov: self.base_fov,
+            present_mode: self.config.present_mode,
+            msaa_samples: self.msaa.sample_count(),
+            texture_filtering: self.texture_filtering(),
+            window_size: (window_size.width, window_size.height),
+            fullscreen: self.window_config.fullscreen != FullscreenMode::Windowed,
+        }
+    }
+
+    /// Applies the input, camera, MSAA and texture filtering settings. The window and present mode are left alone, since they're
+    /// set up through [crate::window_config::WindowConfig] and [State::set_present_mode] at startup
+    /// (where command line options can override them).
+    pub fn apply_user_config(&mut self, config: &UserConfig) {
+        self.settings.mouse = config.mouse;
+        self.settings.mouse.sensitivity = config.mouse.sensitivity.clamp(MIN_SENSITIVITY, MAX_SENSITIVITY);
+        self.movement = MovementController::new(config.speed_presets.clone(), config.move_speed_index);
+        self.bookmarks = config.bookmarks;
+        self.keybinds = config.keybinds;
+        self.base_fov = config.fov.clamp(MIN_FOV, MAX_FOV);
+        self.set_msaa_samples(config.msaa_samples);
+        self.set_texture_filtering(config.texture_filtering);
+    }
+
+    /// Sets the file that the user config is saved to when it changes. The current settings are
+    /// treated as saved. `None` turns saving off.
+    pub fn set_user_config_path(&mut self, path: Option<PathBuf>) {
+        self.user_config_path = path;
+        self.saved_user_config = self.user_config();
+        self.user_config_changed_at = None;
+    }
+
+    /// Saves the user config now, if there's a path to save it to.
+    pub fn save_user_config(&mut self) {
+        let Some(path) = &self.user_config_path else {
+            return;
+        };
+        let config = self.user_config();
+        match config.save(path) {
+            Ok(()) => self.saved_user_config = config,
+            Err(err) => log::error!("Failed to save user config: {err}"),
+        }
+        self.user_config_changed_at = None;
+    }
+
+    /// Saves the user config once it has changed and then stayed the same for [USER_CONFIG_SAVE_DELAY].
+    fn autosave_user_config(&mut self) {
+        if self.user_config_path.is_none() {
+            return;
+        }
+        let config = self.user_config();
+        if config == self.saved_user_config {
+            self.user_config_changed_at = None;
+            return;
+        }
+        match &self.user_config_changed_at {
+            Some((changed_at, pending)) if *pending == config => {
+                if changed_at.elapsed() >= USER_CONFIG_SAVE_DELAY {
+                    self.save_user_config();
+                }
+            }
+            _ => self.user_config_changed_at = Some((Instant::now(), config)),
+        }
+    }
+
+    /// Applies one of [LightingPreset::ALL] and moves the sun to the preset's elevation. This pauses the
+    /// day/night cycle, which would overwrite the light otherwise.
+    pub fn apply_lighting_preset(&mut self, index: usize) {
+        let preset = &LightingPreset::ALL[index];
+        self.day_night.paused = true;
+        self.raytracer.gpu_lighting.apply_preset(&self.queue, preset);
+        let azimuth = self.sun_gizmo.target().azimuth;
+        self.sun_gizmo.set_position(SunAngles::new(azimuth, preset.sun_elevation.to_radians()).position());
+        self.raytracer.reset_accumulation();
+        self.lighting_preset = Some(index);
+    }
+
+    /// The variant of the A/B comparison, or the raytracer's configuration with the other ray direction mode
+    /// if there's no comparison yet.
+    fn comparison_variant(&self) -> RaytraceVariant {
+        self.raytracer.comparison().unwrap_or(RaytraceVariant {
+            direction_mode: self.raytracer.direction_mode().next(),
+            workgroup_size: self.raytracer.workgroup_size(),
+            ao_strength: self.raytracer.gpu_lighting.get_ao_strength(),
+        })
+    }
+
+    /// Starts, changes or stops (with `None`) the raytracer's A/B comparison, see [Raytracer::set_comparison].
+    /// Returns `false` if the device doesn't support the variant.
+    pub fn set_raytrace_comparison(&mut self, variant: Option<RaytraceVariant>) -> bool {
+        let Some(sky) = self.camera.skybox().map(|skybox| skybox.cubemap().clone()) else {
+            log::warn!("The A/B comparison needs the skybox.");
+            return false;
+        };
+        if !self.raytracer.set_comparison(variant, &self.device, &self.queue, &sky) {
+            return false;
+        }
+        // Start both averages over so that the sides are timed over the same frames.
+        self.raytrace_timer.clear();
+        self.comparison_timer.clear();
+        true
+    }
+
+    /// Turns mouse look on or off.
+    pub fn set_locked(&mut self, locked: bool) {
+        self.locked = locked;
+        if locked {
+            self.grab_cursor();
+        } else {
+            self.release_cursor();
         }
     }
 
-    pub fn focus_changed(&mut self, _focus: bool) {
+    /// Locks the cursor in place, falling back to confining it to the window, and then to warping it
+    /// back to the center every frame if neither is supported.
+    fn grab_cursor(&mut self) {
+        self.cursor_grab = [CursorGrabMode::Locked, CursorGrabMode::Confined].into_iter()
+            .find(|&mode| self.window.set_cursor_grab(mode).is_ok())
+            .unwrap_or(CursorGrabMode::None);
+        if self.cursor_grab == CursorGrabMode::None {
+            log::warn!("Cursor grabbing isn't supported, falling back to warping the cursor.");
+        }
+        self.window.set_cursor_visible(false);
+    }
 
+    fn release_cursor(&mut self) {
+        if self.cursor_grab != CursorGrabMode::None {
+            if let Err(err) = self.window.set_cursor_grab(CursorGrabMode::None) {
+                log::error!("Failed to release the cursor: {err}");
+            }
+            self.cursor_grab = CursorGrabMode::None;
+        }
+        self.window.set_cursor_visible(true);
     }
 
     pub fn close_requested(&mut self) -> bool {
-        
+        if let Err(err) = self.stop_recording() {
+            log::error!("Failed to save input recording: {err}");
+        }
         true
     }
 
@@ -605,19 +1392,19 @@ impl<'a> State<'a> {
                         
                     },
                     gilrs::Button::LeftTrigger2 => {
-                        
+                        self.input.gamepad.left_trigger = t;
                     },
                     gilrs::Button::RightTrigger => {
                     },
                     gilrs::Button::RightTrigger2 => {
-                        
-                        println!("{t:.4}")
+                        self.input.gamepad.right_trigger = t;
+                        log::trace!("Right trigger: {t:.4}");
                     },
                     _ => (),
                 }
             },
             gilrs::EventType::AxisChanged(axis, t, code) => {
-                
+                self.input.gamepad.set_axis(axis, t);
             },
             gilrs::EventType::Connected => {
                 
@@ -637,10 +1424,11 @@ impl<'a> State<'a> {
 
     pub fn process_event(&mut self, event: &Event<()>) {
         match event {
-            Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } => {
+            // Raw motion keeps coming while the cursor is grabbed (it isn't limited by the edges of the
+            // window), but it's also sent while the window is in the background.
+            Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } if self.focused => {
                 self.input.mouse_pos.delta.x += delta.0;
                 self.input.mouse_pos.delta.y += delta.1;
-                self.input.mouse_pos.live_mouse.set_target(delta.0, delta.1);
                 // self.window.set_cursor_position(self.window_center()).unwrap();
                 // const MOUSE_SENSITIVITY: f64 = 0.00075;
                 // let rot_y = -(delta.0 * MOUSE_SENSITIVITY);
@@ -664,7 +1452,7 @@ impl<'a> State<'a> {
                 match delta {
                     winit::event::MouseScrollDelta::LineDelta(_, y) => {
                         let diff = *y;
-                        self.fog.start = self.fog.start + diff * 3.0;
+                        self.fog.start = (self.fog.start + diff * 3.0).clamp(0.0, self.fog.end);
                     },
                     winit::event::MouseScrollDelta::PixelDelta(physical_position) => todo!(),
                 }
@@ -673,6 +1461,13 @@ impl<'a> State<'a> {
                 if !event.repeat {
                     match event.physical_key {
                         PhysicalKey::Code(key) => {
+                            // Handled here rather than in update so that they work during playback.
+                            if event.state.is_pressed() && key == KeyCode::F6 {
+                                self.toggle_recording();
+                            }
+                            if event.state.is_pressed() && key == KeyCode::F7 {
+                                self.toggle_playback();
+                            }
                             self.input.set_key_state(key, event.state.is_pressed());
                         }
                         _ => (),
@@ -684,8 +1479,106 @@ impl<'a> State<'a> {
         false
     }
 
-    pub fn begin_frame(&mut self, frame: &FrameInfo) {
+    /// During playback, the recorded input (and delta time) replaces the live input of the frame.
+    pub fn begin_frame(&mut self, frame: &mut FrameInfo) {
+        if let Some(playback) = &mut self.playback {
+            if !playback.apply_next(frame, &mut self.input) {
+                self.stop_playback();
+            }
+        } else if let Some(recorder) = &mut self.recorder {
+            recorder.record(frame, &self.input);
+        }
         self.input.begin_frame(&self.settings, frame);
+        for time in self.raytrace_gpu_timer.collect(&self.device) {
+            self.raytrace_timer.push(time);
+            self.new_raytrace_time = Some(time);
+        }
+        for time in self.comparison_gpu_timer.collect(&self.device) {
+            self.comparison_timer.push(time);
+        }
+        self.pass_profiler.collect(&self.device);
+    }
+
+    /// Adds the times of the frame to the frame graph. `frame` is the time since the previous frame
+    /// started, `update` and `render` are the times of [State::update] and [State::render].
+    pub fn record_frame_times(&mut self, frame: Duration, update: Duration, render: Duration) {
+        let raytrace_gpu = self.new_raytrace_time.take();
+        self.frame_graph.push(FrameSample {
+            frame,
+            update,
+            render,
+            raytrace_gpu,
+        });
+        // The time between frames includes frame pacing, so the render scale follows the work instead.
+        let work = (update + render).max(raytrace_gpu.unwrap_or_default());
+        let quality = self.raytracer.quality();
+        if let Some(benchmark) = &mut self.benchmark {
+            benchmark.record(BenchmarkSample {
+                frame,
+                update,
+                render,
+                raytrace_gpu,
+                render_scale: quality.render_scale,
+            });
+            if benchmark.is_finished() {
+                self.finish_benchmark();
+            }
+        }
+        if let Some(render_scale) = self.dynamic_render_scale.update(quality.render_scale, work) {
+            self.raytracer.set_quality(RaytraceQuality { render_scale, ..quality }, &self.device, &self.queue);
+        }
+    }
+
+    pub fn start_recording<P: AsRef<std::path::Path>>(&mut self, path: P) {
+        log::info!("Recording input to {:?}.", path.as_ref());
+        self.recorder = Some(InputRecorder::new(path));
+    }
+
+    /// Saves the recording, if there is one.
+    pub fn stop_recording(&mut self) -> Result<(), InputRecordingError> {
+        let Some(recorder) = self.recorder.take() else {
+            return Ok(());
+        };
+        let path = recorder.path().to_owned();
+        let frames = recorder.finish()?;
+        log::info!("Recorded {frames} frames of input to {path:?}.");
+        Ok(())
+    }
+
+    pub fn start_playback<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<(), InputRecordingError> {
+        let playback = InputPlayback::load(path.as_ref())?;
+        log::info!("Playing back {} frames of input from {:?}.", playback.frames().len(), path.as_ref());
+        self.playback = Some(playback);
+        Ok(())
+    }
+
+    pub fn stop_playback(&mut self) {
+        if let Some(playback) = self.playback.take() {
+            log::info!("Stopped input playback after {} frames.", playback.position());
+            // Release everything that the recording was holding.
+            self.input.key_states.clear();
+            self.input.mouse_states.clear();
+        }
+    }
+
+    fn toggle_recording(&mut self) {
+        if self.recorder.is_some() {
+            if let Err(err) = self.stop_recording() {
+                log::error!("Failed to save input recording: {err}");
+            }
+        } else if self.playback.is_none() {
+            self.start_recording(INPUT_RECORDING_PATH);
+        }
+    }
+
+    fn toggle_playback(&mut self) {
+        if self.playback.is_some() {
+            self.stop_playback();
+        } else if self.recorder.is_none() {
+            if let Err(err) = self.start_playback(INPUT_RECORDING_PATH) {
+                log::error!("Failed to load input recording: {err}");
+            }
+        }
     }
 
     pub fn end_frame(&mut self, frame: &FrameInfo) {
@@ -696,6 +1589,7 @@ impl<'a> State<'a> {
         // let mid_pos = PhysicalPosition::new(mid_x, mid_y);
         // self.input.mouse_pos.current = mid_pos;
         // self.window.set_cursor_position(mid_pos).unwrap();
+        self.autosave_user_config();
         self.input.end_frame();
     }
 
@@ -712,43 +1606,86 @@ impl<'a> State<'a> {
         let elapsed = self.last_time.elapsed();
         let t = frame.delta_time.as_secs_f32();
 
-        if self.input.key_just_pressed(KeyCode::F11) {
-            // self.window.set_fullscreen(Some(winit::window::Fullscreen::Borderless(None)));
-            if let Some(_) = self.window.fullscreen() {
-                self.window.set_fullscreen(None);
-            } else {
-                self.window.set_fullscreen(Some(winit::window::Fullscreen::Borderless(None)));
+        let ctrl = self.input.key_pressed(KeyCode::ControlLeft) || self.input.key_pressed(KeyCode::ControlRight);
+        let alt = self.input.key_pressed(KeyCode::AltLeft) || self.input.key_pressed(KeyCode::AltRight);
+        let shift = self.input.key_pressed(KeyCode::ShiftLeft) || self.input.key_pressed(KeyCode::ShiftRight);
+
+        if self.input.key_just_pressed(KeyCode::F11) && ctrl {
+            let debug_view = self.raytracer.debug_view().next();
+            self.raytracer.set_debug_view(debug_view, &self.queue);
+            self.notify(format!("Raytrace Debug View: {debug_view:?}"));
+        } else if self.input.key_just_pressed(KeyCode::F11) {
+            let mut config = self.window_config.clone();
+            config.fullscreen = match config.fullscreen {
+                FullscreenMode::Windowed => FullscreenMode::Borderless,
+                _ => FullscreenMode::Windowed,
+            };
+            self.apply_window_config(config);
+        }
+
+        if self.input.key_just_pressed(KeyCode::F5) && ctrl {
+            self.reload_shaders();
+        } else if self.input.key_just_pressed(KeyCode::F5) {
+            // Textures are rewritten in place, so the bind groups that use them stay valid.
+            let errors = self.assets.reload_textures(&self.queue);
+            for err in &errors {
+                log::error!("Failed to reload texture: {err}");
             }
+            log::info!("Reloaded textures ({} failed).", errors.len());
+        }
+
+        // The number keys are bookmark slots while holding Ctrl or Alt.
+        let digits = !ctrl && !alt;
+        if self.input.key_just_pressed(KeyCode::KeyV) && !ctrl {
+            self.cycle_present_mode();
+        }
+        if !digits {
+            self.update_bookmarks(ctrl);
         }
 
+        if self.input.key_just_pressed(KeyCode::Digit1) && digits {
+            self.player = match self.player {
+                Some(_) => None,
+                None => Some(CharacterController::from_eye(self.camera.position)),
+            };
+        }
+        let walking = self.player.is_some();
+
         let mut total_movement = Vec3::ZERO;
         let mut moved = false;
-        let ctrl = self.input.key_pressed(KeyCode::ControlLeft) || self.input.key_pressed(KeyCode::ControlRight);
         let alt_l = self.input.key_pressed(KeyCode::AltLeft);
-        let w = self.input.key_pressed(KeyCode::KeyW);
-        let s = self.input.key_pressed(KeyCode::KeyS);
+        let keybinds = self.keybinds;
+        let w = self.input.key_pressed(keybinds.forward);
+        let s = self.input.key_pressed(keybinds.backward);
 
-        let a = self.input.key_pressed(KeyCode::KeyA);
-        let d = self.input.key_pressed(KeyCode::KeyD);
+        let a = self.input.key_pressed(keybinds.left);
+        let d = self.input.key_pressed(keybinds.right);
 
-        let r = self.input.key_pressed(KeyCode::KeyR);
-        let f = self.input.key_pressed(KeyCode::KeyF);
+        // Vertical and free movement are only available while flying.
+        let r = !walking && !ctrl && self.input.key_pressed(keybinds.up);
+        let f = !walking && self.input.key_pressed(keybinds.down);
 
         let tk = self.input.key_pressed(KeyCode::KeyT);
         let g = self.input.key_pressed(KeyCode::KeyG);
 
-        let d2 = self.input.key_pressed(KeyCode::Digit2);
-        let x = self.input.key_pressed(KeyCode::KeyX);
+        // P alone pauses the day/night cycle.
+        if self.input.key_just_pressed(KeyCode::KeyP) && shift {
+            let move_type = self.movement.toggle_move_type();
+            self.notify(format!("Movement: {move_type:?}"));
+        }
+        let d2 = !walking && digits && self.input.key_pressed(KeyCode::Digit2);
+        let x = !walking && !ctrl && self.input.key_pressed(KeyCode::KeyX);
         
-        let move_speed = MOVE_SPEEDS[self.move_speed_index];
-
-        let move_multiplier = if self.input.key_pressed(KeyCode::ShiftLeft) {
-            4.0 * move_speed
+        let speed_multiplier = if self.input.key_pressed(KeyCode::ShiftLeft) {
+            4.0
         } else if alt_l {
-            0.25 * move_speed
+            0.25
         } else {
-            MOVE_SPEEDS[self.move_speed_index]
+            1.0
         };
+        let move_multiplier = self.movement.speed() * speed_multiplier;
+        // Along the camera's view, whatever the movement type.
+        let mut free_velocity = Vec3::ZERO;
 
         // Forward (Planar)
         if w && !s {
@@ -763,11 +1700,11 @@ impl<'a> State<'a> {
 
         // Forward (Free)
         if d2 && !x {
-            self.camera.position += self.camera.forward() * t * move_multiplier;
+            free_velocity = self.camera.forward() * move_multiplier;
             moved = true;
             // self.camera.translate_rotated(Vec3::Y * t);
         } else if x && !d2 { // Backward (Free)
-            self.camera.position += self.camera.backward() * t * move_multiplier;
+            free_velocity = self.camera.backward() * move_multiplier;
             moved = true;
             // self.camera.translate_rotated(Vec3::NEG_Y * t);
         }
@@ -795,10 +1732,33 @@ impl<'a> State<'a> {
         }
         
 
-        if moved {
-            let movement = total_movement.normalize() * t * move_multiplier;
-            self.camera.translate_planar(movement);
-            self.animation.take();
+        if let Some(player) = &mut self.player {
+            self.movement.stop();
+            let walk_velocity = if moved {
+                self.camera.rotate_vec_y(total_movement.normalize()) * WALK_SPEED * move_multiplier
+            } else {
+                Vec3::ZERO
+            };
+            let jump = self.input.key_pressed(keybinds.jump);
+            // Large steps (such as after a hitch) would make the jump height inconsistent.
+            player.update(t.min(0.05), walk_velocity, jump, chunk_solids(&self.raytracer.chunk));
+            self.camera.position = player.eye_position();
+            if moved || jump {
+                self.animations.cancel_all();
+            }
+        } else {
+            let direction = total_movement.normalize_or_zero();
+            let target = self.movement.target_velocity(&self.camera, direction, speed_multiplier) + free_velocity;
+            let translation = self.movement.update(target, t);
+            if moved {
+                self.animations.cancel_all();
+            }
+            // Animations that were started while coasting take over the camera.
+            if !self.animations.is_empty() {
+                self.movement.stop();
+            } else if self.movement.is_moving() {
+                self.camera.translate(translation);
+            }
         }
         
         let mouse_pos = self.input.mouse_pos.current;
@@ -808,122 +1768,501 @@ impl<'a> State<'a> {
         );
         let ray = self.camera.normalized_screen_to_ray(screen_pos);
 
-        if self.input.key_just_pressed(KeyCode::KeyB) {
-            println!("{:.5}, {:.5}", ray.dir.length(), ray.invert_dir().dir.length());
+        if self.input.key_just_pressed(KeyCode::KeyB) && ctrl {
+            self.toggle_torch();
+        } else if self.input.key_just_pressed(KeyCode::KeyB) {
+            log::debug!("Ray direction length: {:.5}, inverted: {:.5}", ray.dir.length(), ray.invert_dir().dir.length());
         }
 
-        if self.input.key_pressed(KeyCode::KeyQ) {
-            self.raytracer.gpu_lighting.set_directional_direction(&self.queue, ray.dir.into());
+        // The ray points back towards the camera, so the sky under the cursor is the other way.
+        let sky_position = -Vec3::from(ray.dir);
+        if !self.day_night.paused {
+            self.sun_gizmo.snap_to(-self.day_night.light_direction());
         }
-
-        if self.input.mouse_just_pressed(MouseButton::Left) {
-            // let new_pos = ray.point_on_ray(t);
-            // self.camera.position = new_pos;
+        if self.input.key_just_pressed(KeyCode::KeyQ) && ctrl {
+            self.sun_gizmo.visible = !self.sun_gizmo.visible;
+        }
+        let mut sun_moved = false;
+        if self.input.key_pressed(KeyCode::KeyQ) && !ctrl {
+            self.sun_gizmo.set_position(sky_position);
+            sun_moved = true;
+        }
+        let sun_grabbed = !self.locked && self.sun_gizmo.drag(
+            &self.camera,
+            self.velvet.size(),
+            screen_pos,
+            sky_position,
+            self.input.mouse_pressed(MouseButton::Left),
+            self.input.mouse_just_pressed(MouseButton::Left),
+        );
+        if alt_l {
+            let nudge = NUDGE_SPEED * t;
+            let axis = |positive: bool, negative: bool| (positive as i32 - negative as i32) as f32 * nudge;
+            let azimuth = axis(self.input.key_pressed(KeyCode::ArrowRight), self.input.key_pressed(KeyCode::ArrowLeft));
+            let elevation = axis(self.input.key_pressed(KeyCode::ArrowUp), self.input.key_pressed(KeyCode::ArrowDown));
+            if azimuth != 0.0 || elevation != 0.0 {
+                self.sun_gizmo.nudge(azimuth, elevation);
+                sun_moved = true;
+            }
+        }
+        if sun_moved || sun_grabbed {
+            // Manually placing the light stops the day/night cycle from overwriting it.
+            self.day_night.paused = true;
+        }
+        if self.sun_gizmo.update(frame.delta_time) {
+            self.raytracer.gpu_lighting.set_directional_direction(&self.queue, self.sun_gizmo.light_direction());
+            self.raytracer.reset_accumulation();
+        }
+
+        if self.input.key_just_pressed(KeyCode::Digit3) && digits {
+            self.selection_mode = !self.selection_mode;
+            self.selection_anchor = None;
+        }
+        let target = self.picker.pick(&self.raytracer, ray);
+        if self.selection_mode {
+            if self.input.mouse_just_pressed(MouseButton::Left) && !sun_grabbed {
+                self.selection_anchor = target.map(|pick| pick.hit_cell);
+            }
+            if let (Some(anchor), Some(pick)) = (self.selection_anchor, target) {
+                if self.input.mouse_pressed(MouseButton::Left) {
+                    self.selection = Some(Selection::from_corners(anchor, pick.hit_cell));
+                }
+            }
+            if self.input.mouse_just_released(MouseButton::Left) {
+                self.selection_anchor = None;
+            }
+            if ctrl {
+                self.update_clipboard(target.and_then(|pick| pick.place_cell));
+            }
+            self.update_chunk_transforms();
+        }
+        if let Some(selection) = self.selection {
+            let (min, max) = selection.bounds();
+            self.gizmo.aabb(min, max, vec4(1.0, 0.9, 0.2, 1.0));
+        }
+        // Where the clipboard would be pasted.
+        if let (Some(clipboard), Some(place_cell)) = (&self.clipboard, target.and_then(|pick| pick.place_cell)) {
+            if self.selection_mode {
+                let min = place_cell.as_vec3();
+                self.gizmo.aabb(min, min + clipboard.size().as_vec3(), vec4(0.2, 0.9, 1.0, 1.0));
+            }
+        }
+        // The block that would be removed.
+        if let Some(pick) = target {
+            if !self.selection_mode && self.locked {
+                let min = pick.hit_cell.as_vec3();
+                self.gizmo.aabb(min, min + Vec3::ONE, vec4(1.0, 1.0, 1.0, 0.5));
+            }
+        }
+        if self.settings.draw_labels {
+            self.add_labels(target);
+        }
+        chunk_debug::draw(&mut self.gizmo, self.settings.chunk_debug, self.camera.position);
+
+        let sculpting = self.sculptor.mode.is_active() && !self.selection_mode && !sun_grabbed;
+        let strokes = self.sculptor.strokes(
+            frame.delta_time,
+            sculpting && self.input.mouse_pressed(MouseButton::Left),
+            self.input.mouse_just_pressed(MouseButton::Left),
+        );
+        if let Some(pick) = target.filter(|_| strokes > 0) {
+            for _ in 0..strokes {
+                self.sculptor.stroke(&mut self.raytracer.chunk, pick.hit_cell, self.brush, pick.id);
+            }
+        }
+        if sculpting {
+            if let Some(pick) = target {
+                let radius = self.brush.radius;
+                let min = (pick.hit_cell - glam::ivec3(radius, 0, radius)).as_vec3();
+                let max = (pick.hit_cell + glam::ivec3(radius + 1, 1, radius + 1)).as_vec3();
+                self.gizmo.aabb(min, max, vec4(0.4, 1.0, 0.4, 0.75));
+            }
+        }
+        if self.input.mouse_just_pressed(MouseButton::Left) && !self.selection_mode && !sun_grabbed && !sculpting {
+            // let new_pos = ray.point_on_ray(t);
+            // self.camera.position = new_pos;
             // self.camera.position = ray.point_on_ray(t * 0.25).into();
-            if let Some(hit) = self.raytracer.chunk.raycast(ray, 200.0) {
-                let cell = hit.get_hit_cell();
-                self.raytracer.chunk.set(cell.x, cell.y, cell.z, 1);
+            if let Some(cell) = target.and_then(|pick| pick.place_cell) {
+                self.raytracer.chunk.set(cell.x, cell.y, cell.z, self.place_id);
+                self.reticle.pulse();
             }
         }
         if self.input.mouse_just_pressed(MouseButton::Right) {
+            self.zoom_pressed_at = Some(Instant::now());
+        }
+        if self.input.mouse_just_released(MouseButton::Right) {
+            let clicked = self.zoom_pressed_at.take()
+                .map(|pressed_at| pressed_at.elapsed() < ZOOM_HOLD_DELAY)
+                .unwrap_or(false);
             // let ray = ray.invert_dir();
             // let new_pos = ray.point_on_ray(t);
-            if let Some(hit) = self.raytracer.chunk.raycast(ray, 200.0) {
-                let cell = hit.coord;
-                self.raytracer.chunk.set(cell.x, cell.y, cell.z, 0);
+            if clicked {
+                if let Some(pick) = target {
+                    let cell = pick.hit_cell;
+                    self.raytracer.chunk.set(cell.x, cell.y, cell.z, 0);
+                    self.reticle.pulse();
+                }
             }
         }
-        let chunk_path = "./sandbox_files/chunk.dat";
+        if self.input.key_just_pressed(KeyCode::Minus) && digits {
+            self.base_fov = (self.base_fov - FOV_STEP).clamp(MIN_FOV, MAX_FOV);
+        }
+        if self.input.key_just_pressed(KeyCode::Equal) && digits {
+            self.base_fov = (self.base_fov + FOV_STEP).clamp(MIN_FOV, MAX_FOV);
+        }
+        if alt && !ctrl && (self.input.key_just_pressed(KeyCode::Minus) || self.input.key_just_pressed(KeyCode::Equal)) {
+            let step = if self.input.key_just_pressed(KeyCode::Minus) { -VOLUMETRIC_DENSITY_STEP } else { VOLUMETRIC_DENSITY_STEP };
+            let density = self.raytracer.volumetric_density() + step;
+            self.raytracer.set_volumetric_density(density, &self.queue);
+            self.notify(format!("Volumetric Density: {:.3}", self.raytracer.volumetric_density()));
+        }
+        // Stepping the render scale by hand turns the dynamic render scale off.
+        if ctrl && (self.input.key_just_pressed(KeyCode::Minus) || self.input.key_just_pressed(KeyCode::Equal)) {
+            let steps = if self.input.key_just_pressed(KeyCode::Minus) { -1 } else { 1 };
+            let quality = self.raytracer.quality();
+            let render_scale = step_render_scale(quality.render_scale, steps);
+            self.raytracer.set_quality(RaytraceQuality { render_scale, ..quality }, &self.device, &self.queue);
+            self.dynamic_render_scale.enabled = false;
+            self.notify(format!("Render Scale: {render_scale}%"));
+        }
+        if self.input.key_just_pressed(KeyCode::Backslash) && ctrl {
+            self.dynamic_render_scale.enabled = !self.dynamic_render_scale.enabled;
+            self.dynamic_render_scale.reset();
+            let target_fps = self.dynamic_render_scale.target_fps;
+            self.notify(if self.dynamic_render_scale.enabled {
+                format!("Dynamic Render Scale: {target_fps} FPS")
+            } else {
+                String::from("Dynamic Render Scale: Off")
+            });
+        }
+        self.update_fov(frame.delta_time);
+        self.reticle.update(&self.queue, frame.delta_time);
+        self.update_entities(frame.delta_time);
         // self.texture_array.texel_to_uv(vec2(32.0, 32.0));
         if self.input.key_just_pressed(KeyCode::KeyS) && ctrl {
-            self.raytracer.chunk.save(chunk_path).expect("Failed to save chunk.");
-            println!("Saved chunk to file \"{chunk_path}\".");
+            match self.saves.next_quicksave() {
+                Ok(slot) => self.save_world(slot),
+                Err(err) => self.notify(format!("Failed to quicksave: {err}")),
+            }
         }
-        if self.input.key_just_pressed(KeyCode::KeyL) {
-            let load_start = Instant::now();
-            match self.raytracer.chunk.load(chunk_path) {
-                Ok(()) => {
-                    let load_elapsed = load_start.elapsed();
-                    println!("Loaded chunk from file \"{chunk_path}\" in {load_elapsed:.2?}");
-                }
-                Err(err) => {
-                    eprintln!("Failed to load file: \"{chunk_path}\"");
-                    eprintln!("Error: {err:?}");
-                }
+        if self.input.key_just_pressed(KeyCode::KeyL) && ctrl {
+            self.settings.draw_labels = !self.settings.draw_labels;
+            self.notify(if self.settings.draw_labels { "Labels: On" } else { "Labels: Off" });
+        } else if self.input.key_just_pressed(KeyCode::KeyL) {
+            match self.saves.latest() {
+                Ok(Some(save)) => self.load_world(save.slot),
+                Ok(None) => self.notify("There are no saves to load."),
+                Err(err) => self.notify(format!("Failed to list saves: {err}")),
             }
         }
+        if self.saves.update(frame.delta_time) && self.pending_save.is_none() {
+            self.save_world(SaveSlot::Autosave);
+        }
+        if self.input.key_just_pressed(KeyCode::KeyG) {
+            self.world_seed = self.world_seed.wrapping_add(1);
+            let seed = self.world_seed;
+            self.pending_chunk = Some(self.tasks.spawn(move || {
+                TaskOutput::Chunk(Ok(worldgen::generate_hills(seed, BLOCK_ID)))
+            }));
+        }
 
         if self.input.key_just_pressed(KeyCode::Tab) {
-            self.locked = !self.locked;
-            if self.locked {
-                self.window.set_cursor_visible(false);
-            } else {
-                self.window.set_cursor_visible(true);
-            }
+            self.set_locked(!self.locked);
         }
 
-        if self.input.key_pressed(KeyCode::KeyE) {
+        if self.input.key_pressed(KeyCode::KeyE) && !ctrl {
             self.camera.position += self.camera.forward() * t * move_multiplier;
         }
 
-        if self.input.key_just_pressed(KeyCode::ArrowRight) {
-            // self.move_speed_index = (self.move_speed_index + 1) % MOVE_SPEEDS.len();
-            self.move_speed_index = (self.move_speed_index + 1).min(MOVE_SPEEDS.len() - 1);
+        if self.input.key_just_pressed(KeyCode::ArrowRight) && !alt_l {
+            self.movement.faster();
             // let start = self.camera.position;
-            // let mut end = self.camera.position + self.camera.right() * 4.0;
-            // self.animation.replace(StateAnimator::start(Duration::from_secs(1), move |state, anim| {
-            //     use crate::animation::tween;
-            //     let pos = start.lerp(end, tween::f32::quartic_in_out(anim.alpha_f32()));
-            //     state.camera.position = pos;
-            // }));
-        } else if self.input.key_just_pressed(KeyCode::ArrowLeft) {
-            // self.move_speed_index = (self.move_speed_index + MOVE_SPEEDS.len() - 1) % MOVE_SPEEDS.len();
-            self.move_speed_index = self.move_speed_index.saturating_sub(1);
+            // let end = self.camera.position + self.camera.right() * 4.0;
+            // self.animations.start(CameraTrack::Position(
+            //     Animator::new(start, end, Duration::from_secs(1)).with_easing(tween::f32::quartic_in_out)
+            // ));
+        } else if self.input.key_just_pressed(KeyCode::ArrowLeft) && !alt_l {
+            self.movement.slower();
             // let start = self.camera.position;
-            // let mut end = self.camera.position + self.camera.left() * 4.0;
-            // self.animation.replace(StateAnimator::start(Duration::from_secs(1), move |state, anim| {
-            //     use crate::animation::tween;
-            //     let pos = start.lerp(end, tween::f32::quartic_in_out(anim.alpha_f32()));
-            //     state.camera.position = pos;
-            // }));
+            // let end = self.camera.position + self.camera.left() * 4.0;
+            // self.animations.start(CameraTrack::Position(
+            //     Animator::new(start, end, Duration::from_secs(1)).with_easing(tween::f32::quartic_in_out)
+            // ));
         }
 
         if self.input.key_just_pressed(KeyCode::KeyY) {
             let start = self.camera.position;
             let mut end = vec3(64.0*16.0, 1.0, 64.0*16.0);
-            self.animation.replace(StateAnimator::start(Duration::from_secs(10), move |state, anim| {
-                use crate::animation::tween;
-                let pos = start.lerp(end, tween::f32::quartic_in_out(anim.alpha_f32()));
-                state.camera.position = pos;
-            }));
+            self.animations.cancel_all();
+            self.animations.start(CameraTrack::Position(
+                Animator::new(start, end, Duration::from_secs(10)).with_easing(tween::f32::quartic_in_out)
+            ));
         }
 
+        self.update_camera_path();
+
         // Mouse Move
 
-        // Toggle Mouse Smoothing
-        if self.input.key_just_pressed(KeyCode::KeyH) {
-            self.settings.mouse_smoothing = !self.settings.mouse_smoothing;
+        // Mouse Settings
+        if self.input.key_just_pressed(KeyCode::KeyH) && ctrl {
+            self.sculptor.mode = self.sculptor.mode.next();
+            self.notify(format!("Sculpt Brush: {}", self.sculptor.mode.name()));
+        } else if self.input.key_just_pressed(KeyCode::KeyH) {
+            self.settings.mouse.smoothing_mode = self.settings.mouse.smoothing_mode.next();
+        }
+        if self.input.key_just_pressed(KeyCode::KeyJ) && ctrl {
+            self.sculptor.noise = !self.sculptor.noise;
+            self.notify(if self.sculptor.noise { "Sculpt Noise: On" } else { "Sculpt Noise: Off" });
+        } else if self.input.key_just_pressed(KeyCode::KeyJ) {
+            self.settings.mouse.halting = !self.settings.mouse.halting;
+        }
+        if self.input.key_just_pressed(KeyCode::BracketLeft) && ctrl {
+            self.brush = self.brush.shrunk();
+            self.notify(format!("Brush Radius: {}", self.brush.radius));
+        } else if self.input.key_just_pressed(KeyCode::BracketLeft) && shift {
+            self.settings.mouse.scale_sensitivity(1.0 / SENSITIVITY_STEP);
+        }
+        if self.input.key_just_pressed(KeyCode::BracketRight) && ctrl {
+            self.brush = self.brush.grown();
+            self.notify(format!("Brush Radius: {}", self.brush.radius));
+        } else if self.input.key_just_pressed(KeyCode::BracketRight) && shift {
+            self.settings.mouse.scale_sensitivity(SENSITIVITY_STEP);
+        }
+        if self.input.key_just_pressed(KeyCode::Backslash) && !ctrl {
+            self.settings.mouse.invert_y = !self.settings.mouse.invert_y;
+        }
+        if self.input.key_just_pressed(KeyCode::KeyK) && ctrl && shift {
+            let settings = self.reticle.settings();
+            let index = RETICLE_TINTS.iter().position(|&tint| tint == settings.tint).map_or(0, |index| (index + 1) % RETICLE_TINTS.len());
+            self.reticle.set_settings(&self.queue, ReticleSettings { tint: RETICLE_TINTS[index], ..settings });
+        } else if self.input.key_just_pressed(KeyCode::KeyK) && ctrl {
+            let style = self.reticle.next_style(&self.queue).to_owned();
+            self.notify(format!("Reticle: {style}"));
+        } else if self.input.key_just_pressed(KeyCode::KeyK) && alt {
+            let quality = self.raytracer.volumetric_quality().next();
+            self.raytracer.set_volumetric_quality(quality, &self.queue);
+            self.notify(format!("Volumetric Light: {quality:?} ({} Steps)", quality.steps()));
+        } else if self.input.key_just_pressed(KeyCode::KeyK) {
+            let quality = self.raytracer.shadow_quality().next();
+            self.raytracer.set_shadow_quality(quality, &self.queue);
+        }
+        if self.input.key_just_pressed(KeyCode::F1) && ctrl {
+            let index = self.lighting_preset.map_or(0, |index| (index + 1) % LightingPreset::ALL.len());
+            self.apply_lighting_preset(index);
+            self.notify(format!("Lighting: {}", LightingPreset::ALL[index].name));
+        } else if self.input.key_just_pressed(KeyCode::F1) && alt {
+            let variant = self.comparison_variant();
+            let direction_mode = if self.raytracer.comparison().is_some() { variant.direction_mode.next() } else { variant.direction_mode };
+            self.set_raytrace_comparison(Some(RaytraceVariant { direction_mode, ..variant }));
+            self.notify(format!("A/B Comparison: {direction_mode:?} Directions"));
+        } else if self.input.key_just_pressed(KeyCode::F1) {
+            let mode = self.raytracer.direction_mode().next();
+            self.raytracer.set_direction_mode(mode, &self.device, &self.queue);
+            // Start the average over so that the modes' raytrace times can be compared.
+            self.raytrace_timer.clear();
         }
-        if self.input.key_just_pressed(KeyCode::KeyJ) {
-            self.settings.mouse_halting = !self.settings.mouse_halting;
+        if self.input.key_just_pressed(KeyCode::F2) && ctrl {
+            let filtering = self.set_texture_filtering(self.texture_filtering().next());
+            self.notify(format!("Texture Filtering: {}", filtering.name()));
+        } else if self.input.key_just_pressed(KeyCode::F2) && alt {
+            let variant = self.comparison_variant();
+            let mut workgroup_size = variant.workgroup_size.next_preset();
+            // Skip the sizes that the device can't run.
+            while !workgroup_size.is_supported(&self.device.limits()) {
+                workgroup_size = workgroup_size.next_preset();
+            }
+            self.set_raytrace_comparison(Some(RaytraceVariant { workgroup_size, ..variant }));
+            self.notify(format!("A/B Comparison: {}x{} Workgroups", workgroup_size.x, workgroup_size.y));
+        } else if self.input.key_just_pressed(KeyCode::F2) && shift {
+            let enabled = !self.raytracer.indirect_dispatch();
+            if enabled && !IndirectDispatch::is_supported(&self.adapter.get_downlevel_capabilities()) {
+                self.notify("Indirect Dispatch: Unsupported");
+            } else {
+                self.raytracer.set_indirect_dispatch(enabled, &self.device);
+                self.raytrace_timer.clear();
+                self.notify(if enabled { "Indirect Dispatch: On" } else { "Indirect Dispatch: Off" });
+            }
+        } else if self.input.key_just_pressed(KeyCode::F2) {
+            let mut workgroup_size = self.raytracer.workgroup_size().next_preset();
+            // Skip the sizes that the device can't run.
+            while !self.raytracer.set_workgroup_size(workgroup_size, &self.device) {
+                workgroup_size = workgroup_size.next_preset();
+            }
+            self.raytrace_timer.clear();
+        }
+        if self.input.key_just_pressed(KeyCode::F3) && ctrl {
+            self.color_audit = !self.color_audit;
+        } else if self.input.key_just_pressed(KeyCode::F3) && alt {
+            let variant = match self.raytracer.comparison() {
+                Some(_) => None,
+                None => Some(self.comparison_variant()),
+            };
+            if self.set_raytrace_comparison(variant) {
+                self.notify(if variant.is_some() { "A/B Comparison: On" } else { "A/B Comparison: Off" });
+            }
+        } else if self.input.key_just_pressed(KeyCode::F3) {
+            let supported = self.supported_msaa_samples();
+            let index = supported.iter().position(|&count| count == self.msaa.sample_count()).map_or(0, |index| index + 1);
+            let sample_count = supported[index % supported.len()];
+            self.set_msaa_samples(sample_count);
+            self.notify(format!("MSAA: {sample_count}x"));
+        }
+        if self.input.key_just_pressed(KeyCode::F4) && ctrl {
+            self.frame_graph.visible = !self.frame_graph.visible;
+        } else if self.input.key_just_pressed(KeyCode::F4) {
+            let sky_mode = self.raytracer.sky_mode().next();
+            self.raytracer.set_sky_mode(sky_mode, &self.queue);
+        }
+        if self.input.key_just_pressed(KeyCode::KeyU) {
+            let bounces = (self.raytracer.max_bounces() + 1) % (MAX_REFLECTION_BOUNCES + 1);
+            self.raytracer.set_max_bounces(bounces, &self.queue);
+        }
+        if self.input.key_just_pressed(KeyCode::KeyM) && ctrl {
+            self.settings.render_mode = self.settings.render_mode.next();
+            self.notify(format!("Render Mode: {}", self.settings.render_mode.name()));
+        } else if self.input.key_just_pressed(KeyCode::KeyM) {
+            self.place_id = match self.place_id {
+                BLOCK_ID => MIRROR_ID,
+                MIRROR_ID => LAMP_ID,
+                LAMP_ID => WATER_ID,
+                _ => BLOCK_ID,
+            };
+        }
+        if self.input.key_just_pressed(KeyCode::Digit8) && digits {
+            self.picker.translucent = match self.picker.translucent {
+                TranslucentHits::Stop => TranslucentHits::PassThrough,
+                TranslucentHits::PassThrough => TranslucentHits::Stop,
+            };
+        }
+        if self.input.key_just_pressed(KeyCode::Backquote) && ctrl {
+            self.console.toggle();
+        } else if self.input.key_just_pressed(KeyCode::Backquote) {
+            let name = self.camera_rig.select_next().map_or("Off", |rig| rig.name);
+            self.notify(format!("Picture in Picture: {name}"));
+        }
+        if self.console.visible {
+            if self.input.key_just_pressed(KeyCode::Home) {
+                self.console.cycle_level();
+            }
+            if self.input.key_just_pressed(KeyCode::End) {
+                self.console.cycle_module(&log_buffer().targets());
+            }
+            if self.input.key_just_pressed(KeyCode::PageUp) {
+                self.console.scroll(CONSOLE_LINES as isize);
+            }
+            if self.input.key_just_pressed(KeyCode::PageDown) {
+                self.console.scroll(-(CONSOLE_LINES as isize));
+            }
+        }
+        if self.input.key_just_pressed(KeyCode::Quote) && ctrl {
+            self.settings.chunk_debug = self.settings.chunk_debug.next();
+            self.notify(format!("Chunk Debug: {}", self.settings.chunk_debug.name()));
+        } else if self.input.key_just_pressed(KeyCode::Quote) {
+            self.gridzmo.visible = !self.gridzmo.visible;
+        }
+        if self.input.key_just_pressed(KeyCode::KeyO) && ctrl {
+            self.settings.draw_overlay = !self.settings.draw_overlay;
+            self.notify(if self.settings.draw_overlay { "Overlay: On" } else { "Overlay: Off" });
+        } else if self.input.key_just_pressed(KeyCode::KeyO) && alt {
+            let variant = self.comparison_variant();
+            let ao_strength = if variant.ao_strength > 0.0 { 0.0 } else { AO_STRENGTH };
+            self.set_raytrace_comparison(Some(RaytraceVariant { ao_strength, ..variant }));
+            self.notify(if ao_strength > 0.0 { "A/B Comparison: Ambient Occlusion On" } else { "A/B Comparison: Ambient Occlusion Off" });
+        } else if self.input.key_just_pressed(KeyCode::KeyO) {
+            self.settings.ambient_occlusion = !self.settings.ambient_occlusion;
+            let strength = if self.settings.ambient_occlusion { AO_STRENGTH } else { 0.0 };
+            self.raytracer.gpu_lighting.set_ao_strength(&self.queue, strength);
+            self.raytracer.reset_accumulation();
+        }
+        if self.input.key_just_pressed(KeyCode::KeyI) && !ctrl {
+            self.settings.draw_instanced_grid = !self.settings.draw_instanced_grid;
+        }
+        if self.input.key_just_pressed(KeyCode::KeyT) {
+            let enabled = !self.raytracer.accumulation_enabled();
+            self.raytracer.set_accumulation_enabled(enabled);
+        }
+        // Cycle tonemapping: Off -> Reinhard -> ACES -> Off
+        if self.input.key_just_pressed(KeyCode::KeyC) && !ctrl && !alt {
+            if let Some(tonemap) = self.post.get_mut::<Tonemap>() {
+                (tonemap.enabled, tonemap.operator) = match (tonemap.enabled, tonemap.operator) {
+                    (false, _) => (true, TonemapOperator::Reinhard),
+                    (true, TonemapOperator::Reinhard) => (true, TonemapOperator::Aces),
+                    (true, _) => (false, TonemapOperator::Reinhard),
+                };
+            }
+        }
+        if ctrl && (self.input.key_just_pressed(KeyCode::Comma) || self.input.key_just_pressed(KeyCode::Period)) {
+            let step = if self.input.key_just_pressed(KeyCode::Comma) { -RETICLE_SIZE_STEP } else { RETICLE_SIZE_STEP };
+            let settings = self.reticle.settings();
+            self.reticle.set_settings(&self.queue, ReticleSettings { size: settings.size + step, ..settings });
+        } else if !alt && (self.input.key_just_pressed(KeyCode::Comma) || self.input.key_just_pressed(KeyCode::Period)) {
+            let stops = if self.input.key_just_pressed(KeyCode::Comma) { -EXPOSURE_STEP } else { EXPOSURE_STEP };
+            if let Some(tonemap) = self.post.get_mut::<Tonemap>() {
+                tonemap.exposure = (tonemap.exposure * stops.exp2()).clamp(MIN_EXPOSURE, MAX_EXPOSURE);
+            }
+        }
+        // Slash toggles auto exposure, which the tonemap's exposure then compensates.
+        if self.input.key_just_pressed(KeyCode::Slash) {
+            if let Some(auto_exposure) = self.post.get_mut::<AutoExposure>() {
+                auto_exposure.enabled = !auto_exposure.enabled;
+            }
+        }
+        if digits && (self.input.key_just_pressed(KeyCode::Digit9) || self.input.key_just_pressed(KeyCode::Digit0)) {
+            let step = if self.input.key_just_pressed(KeyCode::Digit9) { -GAMMA_STEP } else { GAMMA_STEP };
+            if let Some(gamma) = self.post.get_mut::<Gamma>() {
+                gamma.gamma = ((gamma.gamma + step) / GAMMA_STEP).round() * GAMMA_STEP;
+                gamma.gamma = gamma.gamma.clamp(MIN_GAMMA, MAX_GAMMA);
+                // A gamma of 1.0 doesn't change anything.
+                gamma.enabled = (gamma.gamma - 1.0).abs() > GAMMA_STEP * 0.5;
+            }
+        }
+        if self.input.key_just_pressed(KeyCode::Digit5) && digits {
+            if let Some(bloom) = self.post.get_mut::<Bloom>() {
+                bloom.enabled = !bloom.enabled;
+            }
+        }
+        // Digit6/Digit7 adjust the bloom threshold, or the intensity while holding shift.
+        if digits && (self.input.key_just_pressed(KeyCode::Digit6) || self.input.key_just_pressed(KeyCode::Digit7)) {
+            let sign = if self.input.key_just_pressed(KeyCode::Digit6) { -1.0 } else { 1.0 };
+            if let Some(bloom) = self.post.get_mut::<Bloom>() {
+                if shift {
+                    bloom.intensity = (bloom.intensity + sign * BLOOM_INTENSITY_STEP).clamp(0.0, MAX_BLOOM_INTENSITY);
+                } else {
+                    bloom.threshold = (bloom.threshold + sign * BLOOM_THRESHOLD_STEP).clamp(0.0, 1.0);
+                }
+            }
+        }
+        if self.input.key_just_pressed(KeyCode::KeyZ) {
+            if let Some(vignette) = self.post.get_mut::<Vignette>() {
+                vignette.enabled = !vignette.enabled;
+            }
+        }
+        // Cycle antialiasing: Off -> FXAA -> TAA -> SSAA -> SSAA + FXAA -> SSAA + TAA
+        if self.input.key_just_pressed(KeyCode::KeyN) {
+            let quality = self.raytracer.quality();
+            let antialiasing = quality.antialiasing.next();
+            let quality = match (quality.supersample_scale, antialiasing) {
+                (1, AntialiasMode::Off) => RaytraceQuality { supersample_scale: MAX_SUPERSAMPLE_SCALE, antialiasing, ..quality },
+                (_, AntialiasMode::Off) => RaytraceQuality { render_scale: quality.render_scale, ..RaytraceQuality::DEFAULT },
+                _ => RaytraceQuality { antialiasing, ..quality },
+            };
+            self.raytracer.set_quality(quality, &self.device, &self.queue);
         }
 
         // Change Smoothing Frame Count
-        if self.input.key_just_pressed(KeyCode::ArrowUp) {
+        if self.input.key_just_pressed(KeyCode::ArrowUp) && !alt_l {
             let capacity = self.input.mouse_pos.delta_avg.capacity();
             if capacity < 30 {
                 self.input.mouse_pos.delta_avg.set_capacity(capacity + 1);
             }
         }
-        if self.input.key_just_pressed(KeyCode::ArrowDown) {
+        if self.input.key_just_pressed(KeyCode::ArrowDown) && !alt_l {
             let capacity = self.input.mouse_pos.delta_avg.capacity();
             if capacity > 1 {
                 self.input.mouse_pos.delta_avg.set_capacity(capacity - 1);
             }
         }
 
-        const MOUSE_SENSITIVITY: f64 = 0.00075 * 2.5;
-
         // if !self.locked && self.input.mouse_just_pressed(MouseButton::Middle) {
         //     self.window.set_cursor_visible(true);
         // }
@@ -931,25 +2270,34 @@ impl<'a> State<'a> {
         //     self.window.set_cursor_visible(false);
         // }
 
-        if self.input.key_just_pressed(KeyCode::Digit4) {
-            println!("{:?}", self.input.mouse_pos.live_mouse.velocity());
+        if self.input.key_just_pressed(KeyCode::Digit4) && digits {
+            log::debug!("Mouse velocity: {:?}", self.input.mouse_pos.live_mouse.velocity());
         }
         let middle_pressed = self.input.mouse_pressed(MouseButton::Middle);
         if self.locked || middle_pressed {
             // let rot_y = -(self.input.mouse_pos.live_mouse.velocity().0 * MOUSE_SENSITIVITY);
             // let rot_x = -(self.input.mouse_pos.live_mouse.velocity().1 * MOUSE_SENSITIVITY);
-            let rot_y = -(self.input.mouse_pos.delta.x * MOUSE_SENSITIVITY);
-            let rot_x = -(self.input.mouse_pos.delta.y * MOUSE_SENSITIVITY);
-            
-            self.camera.rotate(vec2(rot_x as f32, rot_y as f32));
-            if !middle_pressed {
-                self.window.set_cursor_position(self.window_center()).unwrap();
+            let rotation = self.settings.mouse.rotation(self.input.mouse_pos.delta, frame.delta_time);
+            self.camera.rotate(rotation);
+            // Only needed when the cursor couldn't be grabbed.
+            if self.locked && !middle_pressed && self.cursor_grab == CursorGrabMode::None && self.focused {
+                if let Err(err) = self.window.set_cursor_position(self.window_center()) {
+                    log::error!("Failed to center the cursor: {err}");
+                }
             }
         }
 
-        if let Some(mut anim) = self.animation.take() {
-            if !anim.update(self) {
-                self.animation = Some(anim);
+        if let Some(benchmark) = &self.benchmark {
+            benchmark.apply_camera(&mut self.camera);
+        }
+
+        if !self.animations.is_empty() {
+            let fov = self.camera.fov;
+            self.animations.update(&mut self.camera, frame.delta_time);
+            // Field of view animations change the base field of view, which zooming scales.
+            if self.camera.fov != fov {
+                self.base_fov = self.camera.fov;
+                self.raytracer.set_fov(self.camera.fov, &self.device, &self.queue);
             }
         }
 
@@ -972,241 +2320,795 @@ impl<'a> State<'a> {
             GpuMat3::new(self.camera.rotation_matrix()),
             GpuVec3::from_vec3(self.camera.position),
         ), &self.queue);
-        self.raytracer.write_chunk(&self.queue);
+        self.receive_tasks();
+
+        let mut systems = std::mem::take(&mut self.systems);
+        systems.update(&mut SystemCtx {
+            state: self,
+            frame,
+        });
+        self.systems = systems;
 
         self.last_time = std::time::Instant::now();
     }
 
+    /// Labels the chunk and the block under the crosshair.
+    fn add_labels(&mut self, target: Option<PickResult>) {
+        let Some(font_layer) = self.texture_array.layer(text3d::FONT_LAYER_NAME) else {
+            return;
+        };
+        let style = TextStyle::new(font_layer);
+        self.labels.label(vec3(32.0, 66.0, 32.0), "Chunk 0 (0, 0, 0)", style.with_line_height(1.5).with_color([255, 230, 120, 255]));
+        if let Some(pick) = target {
+            let cell = pick.hit_cell;
+            let text = format!("{}, {}, {}\nID {}", cell.x, cell.y, cell.z, pick.id);
+            self.labels.label(cell.as_vec3() + vec3(0.5, 1.1, 0.5), text, style.with_line_height(0.25));
+        }
+    }
+
+    /// Ctrl+C copies the selection, Ctrl+X cuts it, Ctrl+V pastes at `paste_cell`, and Ctrl+R rotates the clipboard.
+    /// Ctrl+E exports the selection to [SCHEMATIC_PATH] and Ctrl+I imports it into the clipboard, ready to be pasted.
+    fn update_clipboard(&mut self, paste_cell: Option<glam::IVec3>) {
+        if let Some(selection) = self.selection {
+            if self.input.key_just_pressed(KeyCode::KeyC) {
+                self.clipboard = Some(ClipboardVolume::copy(&self.raytracer.chunk, selection));
+            }
+            if self.input.key_just_pressed(KeyCode::KeyX) {
+                self.clipboard = Some(ClipboardVolume::cut(&mut self.raytracer.chunk, selection));
+            }
+            if self.input.key_just_pressed(KeyCode::KeyE) {
+                let volume = ClipboardVolume::copy(&self.raytracer.chunk, selection);
+                match schematic::save_schematic(&volume, SCHEMATIC_PATH) {
+                    Ok(()) => log::info!("Exported schematic to \"{SCHEMATIC_PATH}\"."),
+                    Err(err) => log::error!("Failed to export schematic: {err}"),
+                }
+            }
+        }
+        if self.input.key_just_pressed(KeyCode::KeyI) {
+            match schematic::load_schematic(SCHEMATIC_PATH) {
+                Ok(volume) => {
+                    log::info!("Imported schematic from \"{SCHEMATIC_PATH}\".");
+                    self.clipboard = Some(volume);
+                }
+                Err(err) => log::error!("Failed to import schematic: {err}"),
+            }
+        }
+        if let Some(clipboard) = &mut self.clipboard {
+            if self.input.key_just_pressed(KeyCode::KeyR) {
+                *clipboard = clipboard.rotated_y();
+            }
+            if let Some(cell) = paste_cell.filter(|_| self.input.key_just_pressed(KeyCode::KeyV)) {
+                clipboard.paste(&mut self.raytracer.chunk, cell, false);
+            }
+        }
+    }
+
+    /// Reorients the whole chunk in selection mode: Numpad5 rotates it, Numpad7 and Numpad9 mirror it along X and Z,
+    /// and Numpad4/6, Numpad8/2 and NumpadSubtract/NumpadAdd move it by one block along X, Z and Y, wrapping around.
+    fn update_chunk_transforms(&mut self) {
+        if self.input.key_just_pressed(KeyCode::Numpad5) {
+            self.raytracer.chunk.rotate_y_90();
+            self.notify("Rotated the chunk by 90 degrees.");
+        }
+        if self.input.key_just_pressed(KeyCode::Numpad7) {
+            self.raytracer.chunk.mirror_x();
+            self.notify("Mirrored the chunk along X.");
+        }
+        if self.input.key_just_pressed(KeyCode::Numpad9) {
+            self.raytracer.chunk.mirror_z();
+            self.notify("Mirrored the chunk along Z.");
+        }
+        let offsets = [
+            (KeyCode::Numpad4, glam::IVec3::NEG_X),
+            (KeyCode::Numpad6, glam::IVec3::X),
+            (KeyCode::Numpad8, glam::IVec3::NEG_Z),
+            (KeyCode::Numpad2, glam::IVec3::Z),
+            (KeyCode::NumpadSubtract, glam::IVec3::NEG_Y),
+            (KeyCode::NumpadAdd, glam::IVec3::Y),
+        ];
+        for (key, offset) in offsets {
+            if self.input.key_just_pressed(key) {
+                self.raytracer.chunk.translate_wrapping(offset);
+            }
+        }
+    }
+
+    /// Saves a copy of the chunk in the background. A message is shown once it's saved.
+    pub fn save_world(&mut self, slot: SaveSlot) {
+        let path = self.saves.path(&slot);
+        let blocks = self.raytracer.chunk.blocks().to_vec();
+        self.pending_save = Some(self.tasks.spawn(move || {
+            let result = WorldSaves::write(&path, &blocks);
+            TaskOutput::Saved(slot, result)
+        }));
+    }
+
+    pub fn load_world(&mut self, slot: SaveSlot) {
+        self.notify(format!("Loading {slot}..."));
+        self.load_chunk(self.saves.path(&slot));
+    }
+
+    /// Whether the device was lost. Call [State::recover_lost_device] before rendering again.
+    pub fn device_lost(&self) -> bool {
+        self.device_lost.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Requests a new device from the adapter and rebuilds the GPU resources on it.
+    pub fn recover_lost_device(&mut self) -> Result<(), wgpu::RequestDeviceError> {
+        let (device, queue) = pollster::block_on(request_device(&self.adapter))?;
+        self.recreate_gpu_resources(device, queue);
+        self.notify("The GPU device was lost and has been recreated.");
+        Ok(())
+    }
+
+    /// Replaces the device and rebuilds every GPU resource on it: textures, pipelines, buffers and bind
+    /// groups. CPU-side state such as the chunk, the camera and the settings is kept, and the raytracer
+    /// and post effects carry their settings over.
+    pub fn recreate_gpu_resources(&mut self, device: wgpu::Device, queue: wgpu::Queue) {
+        self.device = device;
+        self.queue = queue;
+        let (device, queue) = (&self.device, &self.queue);
+        self.gpu_errors.install(device);
+        self.device_lost.store(false, std::sync::atomic::Ordering::Relaxed);
+        watch_device_lost(device, &self.device_lost);
+        self.surface.configure(device, &self.config);
+
+        self.assets.recreate_textures(device, queue);
+        let sampler_config = self.texture_array.sampler_config;
+        self.texture_array = load_texture_array(device, queue, &mut self.assets);
+        self.texture_array.set_sampler(device, sampler_config);
+        self.transforms = TransformsBindGroup::new(device);
+        self.fog_bind_group = FogBindGroup::new(device);
+        self.frame_uniforms = FrameUniformsBindGroup::new(device, self.frame_uniforms.uniforms());
+        let sample_count = self.msaa.sample_count();
+        self.msaa = Msaa::new(device, HDR_FORMAT, self.config.format, self.size.width, self.size.height, sample_count);
+        let sky_cubemap = load_skybox_cubemap(device, queue, &self.assets);
+        self.camera.set_skybox(Skybox::with_cubemap(device, HDR_FORMAT, sample_count, &self.transforms, sky_cubemap.clone()));
+        (self.render_pipeline, self.instanced_pipeline) = create_voxel_pipelines(device, HDR_FORMAT, sample_count, &self.transforms, &self.texture_array, &self.fog_bind_group, &self.frame_uniforms);
+        // The grid mesh only existed on the GPU, so it is built again.
+        let mesh_buffers = MeshData::default().create_buffers(device);
+        self.vertex_buffer = mesh_buffers.vertex_buffer;
+        self.index_buffer = mesh_buffers.index_buffer;
+        self.num_indices = mesh_buffers.num_indices;
+        self.pending_mesh = Some(spawn_grid_mesh(&mut self.tasks));
+        self.instance_buffer = create_grid_instances(device);
+        self.text_rend.recreate(device, queue, self.config.format, sample_count);
+        self.raytracer.recreate(device, queue, &self.camera, &sky_cubemap, self.frame_uniforms.buffer.buffer());
+        self.raytrace_gpu_timer = GpuTimer::new(device, queue, "Raytrace", DEFAULT_FRAMES_IN_FLIGHT);
+        self.comparison_gpu_timer = GpuTimer::new(device, queue, "Raytrace Comparison", DEFAULT_FRAMES_IN_FLIGHT);
+        self.pass_profiler = PassProfiler::new(device, queue);
+        let reticle_settings = self.reticle.settings();
+        self.reticle = create_reticle(device, queue, &mut self.assets, &self.config, sample_count);
+        self.reticle.set_settings(queue, reticle_settings);
+        self.reticle.set_scale_factor(queue, self.window.scale_factor() as f32);
+        self.reticle.write_dimensions(queue, self.size.width, self.size.height);
+        self.reticle.write_ortho(queue, &self.ortho);
+        self.post.recreate(device);
+        self.velvet = Velvet::new(device, self.config.format, sample_count, self.size.width, self.size.height);
+        self.gizmo = Gizmo::new(device, HDR_FORMAT, sample_count, &self.transforms);
+        self.labels = Labels::new(device, HDR_FORMAT, sample_count, &self.transforms, &self.texture_array);
+        self.gridzmo.recreate(device, HDR_FORMAT, sample_count, self.raytracer.hit_bind_group_layout());
+        self.pip.recreate(device, sample_count, self.raytracer.hit_bind_group_layout());
+        self.raster.recreate(device, sample_count, &self.transforms, &self.texture_array, &self.frame_uniforms);
+        self.pending_chunk_mesh = None;
+        self.chunk_mesh_dirty = true;
+        self.staging = StagingRing::default();
+        // The old resources have been replaced, so this should match the usage before the device was lost.
+        log::info!("Recreated the GPU resources ({} MiB tracked).", gpu_memory().total() / (1024 * 1024));
+    }
+
+    /// The filtering of the voxel textures.
+    pub fn texture_filtering(&self) -> TextureFiltering {
+        self.texture_array.sampler_config.filtering().unwrap_or(TextureFiltering::Nearest)
+    }
+
+    /// Switches the filtering of the voxel textures. Returns the filtering that was applied, which has
+    /// no anisotropy if the device doesn't support it.
+    pub fn set_texture_filtering(&mut self, filtering: TextureFiltering) -> TextureFiltering {
+        let config = self.texture_array.sampler_config
+            .with_filtering(filtering)
+            .validated(&self.adapter.get_downlevel_capabilities());
+        self.texture_array.set_sampler(&self.device, config);
+        config.filtering().unwrap_or(filtering)
+    }
+
+    /// The MSAA sample counts that the adapter supports for both the scene and the surface format.
+    pub fn supported_msaa_samples(&self) -> Vec<u32> {
+        msaa::supported_sample_counts(&self.adapter, &[HDR_FORMAT, self.config.format])
+    }
+
+    /// Switches the sample count of the scene and UI passes, which rebuilds every raster pipeline.
+    /// Returns `false` (and keeps the current sample count) if the adapter doesn't support it.
+    pub fn set_msaa_samples(&mut self, sample_count: u32) -> bool {
+        if sample_count == self.msaa.sample_count() {
+            return true;
+        }
+        if !self.supported_msaa_samples().contains(&sample_count) {
+            log::warn!("{sample_count}x MSAA is not supported.");
+            return false;
+        }
+        let device = &self.device;
+        self.msaa = Msaa::new(device, HDR_FORMAT, self.config.format, self.size.width, self.size.height, sample_count);
+        if let Some(cubemap) = self.camera.skybox().map(|skybox| skybox.cubemap().clone()) {
+            self.camera.set_skybox(Skybox::with_cubemap(device, HDR_FORMAT, sample_count, &self.transforms, cubemap));
+        }
+        (self.render_pipeline, self.instanced_pipeline) = create_voxel_pipelines(device, HDR_FORMAT, sample_count, &self.transforms, &self.texture_array, &self.fog_bind_group, &self.frame_uniforms);
+        self.text_rend.recreate(device, &self.queue, self.config.format, sample_count);
+        self.raytracer.set_render_target(HDR_FORMAT, sample_count, device);
+        let reticle_settings = self.reticle.settings();
+        self.reticle = create_reticle(device, &self.queue, &mut self.assets, &self.config, sample_count);
+        self.reticle.set_settings(&self.queue, reticle_settings);
+        self.reticle.set_scale_factor(&self.queue, self.window.scale_factor() as f32);
+        self.reticle.write_dimensions(&self.queue, self.size.width, self.size.height);
+        self.reticle.write_ortho(&self.queue, &self.ortho);
+        self.velvet = Velvet::new(device, self.config.format, sample_count, self.size.width, self.size.height);
+        self.gizmo = Gizmo::new(device, HDR_FORMAT, sample_count, &self.transforms);
+        self.labels = Labels::new(device, HDR_FORMAT, sample_count, &self.transforms, &self.texture_array);
+        self.gridzmo.recreate(device, HDR_FORMAT, sample_count, self.raytracer.hit_bind_group_layout());
+        self.pip.recreate(device, sample_count, self.raytracer.hit_bind_group_layout());
+        self.raster.recreate(device, sample_count, &self.transforms, &self.texture_array, &self.frame_uniforms);
+        self.pending_chunk_mesh = None;
+        self.chunk_mesh_dirty = true;
+        log::info!("Switched to {sample_count}x MSAA ({} MiB tracked).", gpu_memory().total() / (1024 * 1024));
+        true
+    }
+
+    /// How each pipeline gets its colors to the screen. Textures with sRGB formats are decoded when
+    /// sampled, so most pipelines read linear colors and rely on the target to encode them.
+    pub fn color_paths(&self) -> Vec<ColorPath> {
+        let format = self.config.format;
+        let scene_format = self.post.format();
+        vec![
+            ColorPath::unconverted("Voxels", ColorSpace::Linear, scene_format),
+            ColorPath::unconverted("Skybox", ColorSpace::Linear, scene_format),
+            ColorPath::converted("Raytrace Result", ColorSpace::Linear, self.raytracer.target_format()),
+            ColorPath::unconverted("Post Chain", ColorSpace::Linear, self.post.output_format()),
+            ColorPath::unconverted("Reticle", ColorSpace::Linear, format),
+            ColorPath::converted("Text", ColorSpace::Srgb, format),
+            ColorPath::converted("Vello", ColorSpace::Srgb, format),
+        ]
+    }
+
+    /// Recompiles the shaders that can be reloaded at runtime from [RAYTRACE_SHADER_PATH]. Errors are shown
+    /// on the overlay, and the previous pipelines keep running.
+    pub fn reload_shaders(&mut self) {
+        match self.raytracer.reload_shader(&self.device, RAYTRACE_SHADER_PATH) {
+            Ok(()) => {
+                self.gpu_errors.clear();
+                self.notify("Reloaded the raytrace shader.");
+            }
+            Err(err) => {
+                log::error!("Failed to reload the raytrace shader:\n{err}");
+                for line in err.lines() {
+                    self.gpu_errors.push(line);
+                }
+                self.notify("Failed to reload the raytrace shader, keeping the previous one.");
+            }
+        }
+    }
+
+    /// Sets the callback that draws on the vector overlay every frame, on top of the frame graph and the sun
+    /// gizmo. Coordinates are in pixels from the top left corner of the window.
+    pub fn set_overlay<F: FnMut(&mut vello::Scene, Vec2) + 'static>(&mut self, draw: F) {
+        self.overlay = Some(Box::new(draw));
+    }
+
+    /// Removes the callback set with [State::set_overlay].
+    pub fn clear_overlay(&mut self) {
+        self.overlay = None;
+    }
+
+    /// Shows a message on the overlay for a few seconds.
+    pub fn notify<S: Into<String>>(&mut self, message: S) {
+        let message = message.into();
+        log::info!("{message}");
+        self.notification = Some((message, Instant::now()));
+    }
+
+    /// The message from [State::notify], until it expires.
+    pub fn notification(&self) -> Option<&str> {
+        self.notification.as_ref()
+            .filter(|(_, shown_at)| shown_at.elapsed() < NOTIFICATION_DURATION)
+            .map(|(message, _)| message.as_str())
+    }
+
+    /// Loads a chunk in the background. It replaces the current chunk once it's loaded.
+    pub fn load_chunk<P: Into<PathBuf>>(&mut self, path: P) {
+        let path = path.into();
+        self.pending_chunk = Some(self.tasks.spawn(move || {
+            let load_start = Instant::now();
+            let mut chunk = RaytraceChunk::new();
+            let result = chunk.load(&path).map(|_| chunk);
+            if result.is_ok() {
+                let load_elapsed = load_start.elapsed();
+                log::info!("Loaded chunk from file {path:?} in {load_elapsed:.2?}");
+            }
+            TaskOutput::Chunk(result)
+        }));
+    }
+
+    /// Renders the camera path (if it has at least two keyframes) or the current view offline.
+    /// Saves the raytrace result to the next free file in [RESULT_DUMP_DIR], see [Raytracer::read_result].
+    pub fn dump_raytrace_result(&mut self) {
+        let result = std::fs::create_dir_all(RESULT_DUMP_DIR)
+            .map_err(SequenceError::from)
+            .and_then(|()| {
+                let path = offline::next_dump_path(RESULT_DUMP_DIR);
+                self.raytracer.read_result(&self.device, &self.queue)?.save(&path)?;
+                Ok(path)
+            });
+        match result {
+            Ok(path) => self.notify(format!("Saved the raytrace result to {}", path.display())),
+            Err(err) => {
+                log::error!("Failed to save the raytrace result: {err}");
+                self.notify("Failed to save the raytrace result.");
+            }
+        }
+    }
+
+    /// See [Raytracer::render_sequence].
+    pub fn render_sequence<P: AsRef<Path>>(&mut self, output_dir: P, settings: SequenceSettings) -> Result<(), SequenceError> {
+        let camera_path = (self.camera_path.len() >= 2).then_some(&self.camera_path);
+        self.raytracer.render_sequence(&self.device, &self.queue, &self.camera, camera_path, output_dir.as_ref(), settings, Some(&mut self.pass_profiler))
+    }
+
+    /// Ctrl+1..9 saves the camera to a bookmark, Alt+1..9 flies to one and Alt+Shift+1..9 teleports.
+    fn update_bookmarks(&mut self, save: bool) {
+        let Some(slot) = (1..=BOOKMARK_SLOTS).find(|&slot| slot_key(slot).is_some_and(|key| self.input.key_just_pressed(key))) else {
+            return;
+        };
+        let pose = CameraKeyframe::from_camera(&self.camera);
+        if save {
+            self.bookmarks.set(slot, pose);
+            self.notify(format!("Saved Bookmark {slot}"));
+            return;
+        }
+        let Some(bookmark) = self.bookmarks.get(slot) else {
+            self.notify(format!("Bookmark {slot} is empty"));
+            return;
+        };
+        self.animations.cancel_all();
+        self.movement.stop();
+        // Walking would pull the camera back to the player.
+        self.player = None;
+        if self.input.key_pressed(KeyCode::ShiftLeft) || self.input.key_pressed(KeyCode::ShiftRight) {
+            bookmark.apply(&mut self.camera);
+            self.notify(format!("Teleported to Bookmark {slot}"));
+        } else if let Some(flight) = self.bookmarks.flight(slot, pose) {
+            self.animations.start(flight);
+            self.notify(format!("Flying to Bookmark {slot}"));
+        }
+    }
+
+    /// F8 adds a keyframe (Shift+F8 clears the path, and Ctrl+F8 starts a [FrameCapture]), F9 plays the path back, F10 saves it
+    /// and Shift+F10 loads it. Moving stops the playback. F12 renders the path offline, and Ctrl+F12
+    /// dumps the raytrace result (see [State::dump_raytrace_result]). Ctrl+F9 starts (or cancels) a benchmark.
+    fn update_camera_path(&mut self) {
+        let shift = self.input.key_pressed(KeyCode::ShiftLeft) || self.input.key_pressed(KeyCode::ShiftRight);
+        let ctrl = self.input.key_pressed(KeyCode::ControlLeft) || self.input.key_pressed(KeyCode::ControlRight);
+        if self.input.key_just_pressed(KeyCode::F8) && ctrl {
+            self.debug_capture.request(1);
+            self.notify("Capturing the next frame with RenderDoc.");
+        } else if self.input.key_just_pressed(KeyCode::F8) {
+            if shift {
+                self.camera_path.clear();
+            } else {
+                self.camera_path.push(CameraKeyframe::from_camera(&self.camera));
+            }
+        }
+        if self.input.key_just_pressed(KeyCode::F9) && ctrl {
+            if self.benchmark.take().is_some() {
+                self.notify("Cancelled the benchmark.");
+            } else {
+                self.start_benchmark(BenchmarkSettings::default(), false);
+            }
+        } else if self.input.key_just_pressed(KeyCode::F9) {
+            if self.camera_path.len() < 2 {
+                log::warn!("A camera path needs at least two keyframes.");
+            } else {
+                self.animations.cancel_all();
+                self.animations.start(self.camera_path.playback(self.camera_path_duration));
+            }
+        }
+        if self.input.key_just_pressed(KeyCode::F12) && ctrl {
+            self.dump_raytrace_result();
+        } else if self.input.key_just_pressed(KeyCode::F12) {
+            if let Err(err) = self.render_sequence(SEQUENCE_PATH, self.sequence_settings) {
+                log::error!("Failed to render sequence: {err}");
+            }
+        }
+        if self.input.key_just_pressed(KeyCode::F10) {
+            if shift {
+                match CameraPath::load(CAMERA_PATH_PATH) {
+                    Ok(path) => self.camera_path = path,
+                    Err(err) => log::error!("Failed to load camera path: {err}"),
+                }
+            } else if let Err(err) = self.camera_path.save(CAMERA_PATH_PATH) {
+                log::error!("Failed to save camera path: {err}");
+            }
+        }
+    }
+
+    /// Replaces the chunk with the benchmark scene and flies the camera around it (see [Benchmark]). The
+    /// report is saved to [BENCHMARK_DIR] when it's done. The day/night cycle is paused at noon so that
+    /// every run has the same lighting. With `exit_when_done`, the app exits after saving the report.
+    pub fn start_benchmark(&mut self, settings: BenchmarkSettings, exit_when_done: bool) {
+        let chunk = settings.generate_scene(BLOCK_ID);
+        self.pending_chunk = None;
+        self.animations.cancel_all();
+        self.day_night.time_of_day = 0.5;
+        self.day_night.paused = true;
+        self.day_night.apply(&self.raytracer.gpu_lighting, &self.queue);
+        self.sun_gizmo.snap_to(-self.day_night.light_direction());
+        self.lighting_preset = None;
+        self.benchmark = Some(Benchmark::new(settings, &chunk));
+        self.raytracer.chunk = chunk;
+        self.raytracer.reset_accumulation();
+        self.exit_after_benchmark = exit_when_done;
+        self.notify(format!("Benchmarking {} frames...", settings.frames));
+    }
+
+    fn finish_benchmark(&mut self) {
+        let Some(benchmark) = self.benchmark.take() else {
+            return;
+        };
+        let report = benchmark.finish();
+        log::info!("{}", report.summary());
+        match report.save_csv(BENCHMARK_DIR) {
+            Ok(path) => self.notify(format!("Saved the benchmark to {}", path.display())),
+            Err(err) => self.notify(format!("Failed to save the benchmark: {err}")),
+        }
+        if std::mem::take(&mut self.exit_after_benchmark) {
+            self.exit_requested = true;
+        }
+    }
+
+    /// `true` once the app should exit on its own (after a `--bench` run).
+    pub fn exit_requested(&self) -> bool {
+        self.exit_requested
+    }
+
+    /// A warm light that casts shadows, at the camera.
+    fn torch_light(&self) -> PointLight {
+        PointLight::new(self.camera.position, color_temperature(3200.0), 24.0, 6.0).with_shadows(true)
+    }
+
+    pub fn toggle_torch(&mut self) {
+        if let Some(id) = self.torch.take() {
+            self.lights.remove(id);
+            self.notify("Torch: Off");
+        } else {
+            self.torch = self.lights.add(self.torch_light());
+            self.notify(if self.torch.is_some() { "Torch: On" } else { "Torch: There are too many lights." });
+        }
+    }
+
+    /// Keeps the torch at the camera.
+    fn update_torch(&mut self) {
+        if let Some(id) = self.torch {
+            let light = self.torch_light();
+            self.lights.update(id, light);
+        }
+    }
+
+    /// Moves the orbiter along its orbit.
+    fn update_entities(&mut self, delta_time: Duration) {
+        if self.input.key_just_pressed(KeyCode::Semicolon) {
+            self.orbit_paused = !self.orbit_paused;
+        }
+        let Some((id, orbit)) = self.orbiter else {
+            return;
+        };
+        if self.orbit_paused {
+            return;
+        }
+        self.orbit_time += delta_time;
+        if let Some(entity) = self.entities.get_mut(id) {
+            entity.position = orbit.position(self.orbit_time);
+            entity.rotation = orbit.rotation(self.orbit_time);
+        }
+    }
+
+    /// Moves the zoom towards its target and applies the resulting field of view to the camera and
+    /// the raytracer.
+    fn update_fov(&mut self, delta_time: Duration) {
+        let zooming = self.zoom_pressed_at
+            .map(|pressed_at| pressed_at.elapsed() >= ZOOM_HOLD_DELAY)
+            .unwrap_or(false);
+        let step = delta_time.as_secs_f32() / ZOOM_DURATION.as_secs_f32();
+        self.zoom = if zooming {
+            (self.zoom + step).min(1.0)
+        } else {
+            (self.zoom - step).max(0.0)
+        };
+        let scale = 1.0 + (ZOOM_FOV_SCALE - 1.0) * tween::f32::quadratic_in_out(self.zoom);
+        if self.camera.set_fov(self.base_fov * scale) {
+            self.raytracer.set_fov(self.camera.fov, &self.device, &self.queue);
+        }
+    }
+
+    /// Takes the results of finished background jobs and applies them. Chunks are uploaded by the
+    /// following `write_chunk`, meshes are uploaded here.
+    fn receive_tasks(&mut self) {
+        for (id, output) in self.tasks.poll() {
+            match output {
+                TaskOutput::Chunk(result) => {
+                    if self.pending_chunk != Some(id) {
+                        continue;
+                    }
+                    self.pending_chunk = None;
+                    match result {
+                        Ok(chunk) => {
+                            // Keep walking on the new terrain instead of getting stuck inside of it.
+                            if let Some(player) = &mut self.player {
+                                if let Some(spawn) = chunk.find_spawn_point_near(player.position.x as i32, player.position.z as i32) {
+                                    *player = CharacterController::new(spawn);
+                                    self.camera.position = player.eye_position();
+                                }
+                            }
+                            self.raytracer.chunk = chunk;
+                        }
+                        Err(err) => {
+                            log::error!("Failed to load chunk: {err:?}");
+                            self.notify(format!("Failed to load chunk: {err}"));
+                        }
+                    }
+                }
+                TaskOutput::Saved(slot, result) => {
+                    if self.pending_save == Some(id) {
+                        self.pending_save = None;
+                    }
+                    match result {
+                        Ok(()) => self.notify(format!("Saved {slot}.")),
+                        Err(err) => self.notify(format!("Failed to save {slot}: {err}")),
+                    }
+                }
+                TaskOutput::Mesh(mesh) => {
+                    if self.pending_mesh != Some(id) {
+                        continue;
+                    }
+                    self.pending_mesh = None;
+                    let buffers = mesh.create_buffers(&self.device);
+                    self.vertex_buffer = buffers.vertex_buffer;
+                    self.index_buffer = buffers.index_buffer;
+                    self.num_indices = buffers.num_indices;
+                }
+                TaskOutput::ChunkMesh(mesh) => {
+                    if self.pending_chunk_mesh != Some(id) {
+                        continue;
+                    }
+                    self.pending_chunk_mesh = None;
+                    self.raster.set_mesh(&self.device, &mesh);
+                }
+            }
+        }
+    }
+
+    /// Meshes the chunk in the background when it changed and the rasterized view is shown. Edits that
+    /// land while a mesh is being built are picked up by the next one.
+    fn update_chunk_mesh(&mut self) {
+        if !self.settings.render_mode.shows_rasterized() || !self.chunk_mesh_dirty || self.pending_chunk_mesh.is_some() {
+            return;
+        }
+        self.chunk_mesh_dirty = false;
+        let blocks = self.raytracer.chunk.blocks().to_vec();
+        let materials = (0..MATERIAL_COUNT as u32).map(|id| self.raytracer.materials.get_material(id)).collect::<Vec<_>>();
+        let faces = (0..MATERIAL_COUNT as u32).map(|id| self.raytracer.materials.get_faces(id)).collect::<Vec<_>>();
+        self.pending_chunk_mesh = Some(self.tasks.spawn(move || TaskOutput::ChunkMesh(greedy_mesh(&blocks, &materials, &faces))));
+    }
+
     /// Called at the start of render() so that render resources can be initialized.
-    fn begin_render(&mut self) {
+    fn begin_render(&mut self, frame: &FrameInfo) {
+        let jittered = self.raytracer.quality().antialiasing == AntialiasMode::Taa;
+        self.frame_uniforms.advance(&self.queue, frame.delta_time, uvec2(self.config.width, self.config.height), jittered);
         // Update the view/projection matrix in the transform bind group buffer.
         self.transforms.write_view_projection(&self.queue, &self.camera.projection_view_matrix());
         self.transforms.write_camera_position(&self.queue, &self.camera.position);
         self.fog_bind_group.write_fog(&self.queue, &self.fog);
+        self.raytracer.set_fog(&self.fog, &self.queue);
+        self.raytracer.set_sky_tint(self.day_night.sky_tint(), &self.queue);
+        self.raytracer.set_clouds(&self.clouds, &self.queue);
+        self.raytracer.write_entities(&mut self.entities, &self.queue);
+        self.update_torch();
+        self.raytracer.write_lights(&mut self.lights, &self.queue);
+        self.raster.prepare(&self.queue, self.raytracer.gpu_lighting.lighting(), &self.raytracer.fog());
+        self.gizmo.prepare(&self.device, &self.queue);
+        self.labels.prepare(&self.device, &self.queue, self.camera.quat());
+        self.gridzmo.prepare(&self.queue, &self.camera);
+        self.camera_rig.update(&self.camera, self.pip.aspect_ratio());
+        let rig_camera = self.camera_rig.selected().map(|rig| &rig.camera);
+        self.pip.enabled = rig_camera.is_some();
+        if let Some(rig_camera) = rig_camera {
+            self.pip.gizmo.frustum(&self.camera, PIP_FRUSTUM_DISTANCE, vec4(1.0, 0.9, 0.2, 1.0));
+            self.pip.gizmo.aabb(Vec3::ZERO, Vec3::splat(64.0), vec4(0.5, 0.5, 0.5, 1.0));
+            self.pip.prepare(&self.device, &self.queue, rig_camera, &self.camera, self.raytracer.resolution());
+        }
     }
 
     pub fn render(&mut self, frame: &FrameInfo) -> Result<Duration, wgpu::SurfaceError> {
         let start_time = Instant::now();
-        self.begin_render();
+        self.debug_capture.begin_frame(&self.device);
+        self.begin_render(frame);
 
         let output = self.surface.get_current_texture()?;
         let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
         
         // let raytrace_start = Instant::now();
-        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Compute Encoder"),
+        let mut passes = FrameGraphLite::new(&self.device, "Compute Encoder", Some(&self.pass_profiler));
+        // Chunk edits are copied in through the staging ring before the raytracer runs.
+        let chunk_changed = passes.group(FrameSection::Upload.label(), |encoder| {
+            self.raytracer.write_chunk(&self.device, encoder, &mut self.staging)
         });
-
-        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-            label: Some("Render Compute Pass"),
-            timestamp_writes: None,
+        self.chunk_mesh_dirty |= chunk_changed;
+        self.update_chunk_mesh();
+        self.raytracer.write_accumulation(&self.queue);
+        self.staging.finish();
+
+        passes.compute_pass(FrameSection::Raytrace.label(), |compute_pass| {
+            self.raytracer.compute(compute_pass, Some(self.raytrace_gpu_timer.query_set()));
+            self.raytracer.compute_comparison(compute_pass, Some(self.comparison_gpu_timer.query_set()));
         });
-        
-        self.raytracer.compute(&mut compute_pass, Some(&self.rt_query_set));
-        
-        drop(compute_pass);
-        encoder.resolve_query_set(&self.rt_query_set, 0..2, &self.rt_query_buffer, 0);
-        encoder.copy_buffer_to_buffer(&self.rt_query_buffer, 0, &self.rt_query_read_buffer, 0, 16);
-        self.queue.submit(Some(encoder.finish()));
+        self.raytrace_gpu_timer.resolve(passes.encoder());
+        if self.raytracer.comparison().is_some() {
+            self.comparison_gpu_timer.resolve(passes.encoder());
+        }
+        passes.submit(&self.queue, "Render Encoder");
+        self.raytrace_gpu_timer.map();
+        self.comparison_gpu_timer.map();
+        self.staging.recall();
         // let raytrace_elapsed = raytrace_start.elapsed();
         // self.raytrace_timer.push(raytrace_elapsed);
 
-        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Render Encoder")
-        });
-
-        let mut clear_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Render Clear Pass"),
-            color_attachments: &[
-                Some(wgpu::RenderPassColorAttachment {
-                    view: &self.velvet.view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0, g: 0.0, b: 0.0, a: 0.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    }
-                })
-            ],
-            ..Default::default()
+        passes.group(FrameSection::Inset.label(), |encoder| {
+            self.pip.encode(encoder, self.raytracer.hit_bind_group());
         });
+        if self.settings.render_mode.shows_rasterized() {
+            passes.group(FrameSection::Raster.label(), |encoder| self.raster.encode(encoder, &self.transforms, &self.texture_array, &self.frame_uniforms));
+        }
 
-        drop(clear_pass);
-
-        self.velvet.draw(&self.device, &self.queue, |scene| {
-            scene.fill(
-                vello::peniko::Fill::NonZero,
-                vello::kurbo::Affine::IDENTITY,
-                vello::peniko::BrushRef::Solid(vello::peniko::Color::WHITE),
-                None,
-                &vello::kurbo::Circle::new((10.0, 10.0), 64.0),
-            );
-        });
+        // Vello records and submits its own encoders, so its work can't be grouped with the others.
+        if self.settings.draw_overlay {
+            let size = self.velvet.size();
+            let mut overlay = self.overlay.take();
+            self.velvet.draw(&self.device, &self.queue, |scene| {
+                self.frame_graph.draw(scene);
+                self.sun_gizmo.draw(scene, &self.camera, size);
+                if let Some(draw) = &mut overlay {
+                    draw(scene, size);
+                }
+            });
+            self.overlay = overlay;
+        }
 
-        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Render Pass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0, g: 0.0, b: 0.0, a: 1.0
-                        }),
-                        store: wgpu::StoreOp::Store
+        let scene_attachment = self.msaa.color_attachment(
+            self.post.scene_view(),
+            wgpu::LoadOp::Clear(wgpu::Color {
+                r: 0.0, g: 0.0, b: 0.0, a: 1.0
+            }),
+        );
+        // depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+        //     view: &self.depth_texture_view,
+        //     depth_ops: Some(wgpu::Operations {
+        //         load: wgpu::LoadOp::Clear(1.0),
+        //         store: wgpu::StoreOp::Store,
+        //     }),
+        //     stencil_ops: None,
+        // }),
+        passes.render_pass(FrameSection::Scene.label(), &[Some(scene_attachment)], |render_pass| {
+            // increment
+            // decrement
+            // render_pass.set_pipeline(&self.render_pipeline);
+            // render_pass.set_bind_group(0, &self.transforms.bind_group, &[]);
+            // render_pass.set_bind_group(1, &self.texture_array.bind_group.bind_group, &[]);
+            // render_pass.set_bind_group(2, &self.fog_bind_group.bind_group, &[]);
+
+            // const LOCS: &'static [Vec3] = &[
+            //     vec3(-1., 0., -1.), vec3(0., 0., -1.), vec3(1., 0., -1.),
+            //     vec3(-1., 0., 0.), vec3(0., 0., 0.), vec3(1., 0., 0.),
+            //     vec3(-1., 0., 1.), vec3(0., 0., 1.), vec3(1., 0., 1.),
+            // ];
+
+            // let mat1 = glam::Mat4::IDENTITY;
+            // let mat2 = glam::Mat4::from_scale_rotation_translation(Vec3::ONE, Quat::IDENTITY, Vec3::X);
+            // let mat2 = glam::Mat4::from_translation(Vec3::X);
+            // render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            // render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            // for &loc in LOCS.iter() {
+            //     let world = glam::Mat4::from_translation(loc);
+            //     render_pass.set_push_constants(ShaderStages::VERTEX, 0, bytemuck::bytes_of(&world));
+            //     render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+            // }
+            // for z in -64..64 {
+            //     for x in -64..64 {
+            //         let world = glam::Mat4::from_translation(Vec3::new(x as f32 * 16.0, 0.0, z as f32 * 16.0));
+            //         render_pass.set_push_constants(ShaderStages::VERTEX, 0, bytemuck::bytes_of(&world));
+            //         render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+            //     }
+            // }
+
+            debug_group(render_pass, "Skybox", |render_pass| {
+                self.camera.render(render_pass, &self.transforms, self.day_night.sky_tint());
+            });
+            if self.settings.draw_instanced_grid && self.num_indices > 0 {
+                render_pass.insert_debug_marker("Instanced Grid");
+                render_pass.set_pipeline(&self.instanced_pipeline);
+                render_pass.set_bind_group(0, &self.transforms.bind_group, &[]);
+                render_pass.set_bind_group(1, &self.texture_array.bind_group.bind_group, &[]);
+                render_pass.set_bind_group(2, &self.fog_bind_group.bind_group, &[]);
+                render_pass.set_bind_group(3, &self.frame_uniforms.bind_group, &[]);
+                draw_instanced(
+                    render_pass,
+                    &self.vertex_buffer,
+                    &self.index_buffer,
+                    Modeler::INDEX_FORMAT,
+                    self.num_indices,
+                    &self.instance_buffer,
+                );
+            }
+            let (width, height) = self.post.size();
+            if let Some(region) = self.settings.render_mode.raytraced_region(width, height) {
+                // An A/B comparison shows the raytracer on the left half of the region and the variant on the right.
+                let (region, comparison_region) = match self.raytracer.comparison() {
+                    Some(_) => {
+                        let (left, right) = split_region(region);
+                        (left, Some(right))
                     }
-            })],
-            // depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-            //     view: &self.depth_texture_view,
-            //     depth_ops: Some(wgpu::Operations {
-            //         load: wgpu::LoadOp::Clear(1.0),
-            //         store: wgpu::StoreOp::Store,
-            //     }),
-            //     stencil_ops: None,
-            // }),
-            depth_stencil_attachment: None,
-            occlusion_query_set: None,
-            timestamp_writes: None
+                    None => (region, None),
+                };
+                let (x, y, region_width, region_height) = region;
+                debug_group(render_pass, "Raytrace Result", |render_pass| {
+                    render_pass.set_scissor_rect(x, y, region_width, region_height);
+                    self.raytracer.render(render_pass);
+                    render_pass.set_scissor_rect(0, 0, width, height);
+                });
+                if let Some((x, y, region_width, region_height)) = comparison_region {
+                    debug_group(render_pass, "Raytrace Comparison", |render_pass| {
+                        render_pass.set_scissor_rect(x, y, region_width, region_height);
+                        self.raytracer.render_comparison(render_pass);
+                        render_pass.set_scissor_rect(0, 0, width, height);
+                    });
+                }
+            }
+            if let Some(region) = self.settings.render_mode.rasterized_region(width, height) {
+                debug_group(render_pass, "Raster View", |render_pass| self.raster.composite(render_pass, region));
+            }
+            debug_group(render_pass, "Grid", |render_pass| self.gridzmo.render(render_pass, self.raytracer.hit_bind_group()));
+            debug_group(render_pass, "Gizmo", |render_pass| self.gizmo.render(render_pass, &self.transforms));
+            debug_group(render_pass, "Labels", |render_pass| self.labels.render(render_pass, &self.transforms, &self.texture_array));
+            debug_group(render_pass, "Picture in Picture", |render_pass| self.pip.composite(render_pass));
         });
 
-        // increment
-        // decrement
-        // render_pass.set_pipeline(&self.render_pipeline);
-        // render_pass.set_bind_group(0, &self.transforms.bind_group, &[]);
-        // render_pass.set_bind_group(1, &self.texture_array.bind_group.bind_group, &[]);
-        // render_pass.set_bind_group(2, &self.fog_bind_group.bind_group, &[]);
-
-        // const LOCS: &'static [Vec3] = &[
-        //     vec3(-1., 0., -1.), vec3(0., 0., -1.), vec3(1., 0., -1.),
-        //     vec3(-1., 0., 0.), vec3(0., 0., 0.), vec3(1., 0., 0.),
-        //     vec3(-1., 0., 1.), vec3(0., 0., 1.), vec3(1., 0., 1.),
-        // ];
-
-        // let mat1 = glam::Mat4::IDENTITY;
-        // let mat2 = glam::Mat4::from_scale_rotation_translation(Vec3::ONE, Quat::IDENTITY, Vec3::X);
-        // let mat2 = glam::Mat4::from_translation(Vec3::X);
-        // render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        // render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-        // for &loc in LOCS.iter() {
-        //     let world = glam::Mat4::from_translation(loc);
-        //     render_pass.set_push_constants(ShaderStages::VERTEX, 0, bytemuck::bytes_of(&world));
-        //     render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
-        // }
-        // for z in -64..64 {
-        //     for x in -64..64 {
-        //         let world = glam::Mat4::from_translation(Vec3::new(x as f32 * 16.0, 0.0, z as f32 * 16.0));
-        //         render_pass.set_push_constants(ShaderStages::VERTEX, 0, bytemuck::bytes_of(&world));
-        //         render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
-        //     }
-        // }
-
-        self.camera.render(&mut render_pass, &self.transforms);
-        self.raytracer.render(&mut render_pass);
-
-        let avg_rt_time = self.raytrace_timer.average();
-    
-
-        if self.locked {
-            self.reticle.render(&mut render_pass);
-        }
-        
-        // ██████████████████
-        // █                █
-        // █ Text Rendering █
-        // █                █
-        // ██████████████████
-        {
-
-            let mut viewport = Viewport::new(&self.device, &self.text_rend.cache);
-            viewport.update(&self.queue, Resolution { width: self.size.width, height: self.size.height });
-
-            let mut render_text = String::new();
-
-            writeln!(render_text, "Frame Index: {}", frame.index);
-            writeln!(render_text, "FPS: {:.0}", frame.fps);
-            writeln!(render_text, "Raytrace Time: {avg_rt_time:.3?}");
-            if self.settings.mouse_smoothing {
-                writeln!(render_text, "Mouse Smoothing: {}", self.input.mouse_pos.delta_avg.capacity());
-                writeln!(render_text, "Mouse Halting: {}", self.settings.mouse_halting);
-            } else {
-                writeln!(render_text, "Mouse Smoothing: Off");
-            }
-            writeln!(render_text, "Animating: {}", self.animation.is_some());
-            writeln!(render_text, "Move Speed: {:.2}", MOVE_SPEEDS[self.move_speed_index]);
-
-            self.text_rend.back_buffer.set_text(
-                &mut self.text_rend.font_system,
-                &render_text,
-                Attrs::new()
-                    // .color(Color::rgb(255, 255, 255))
-                    ,
-                glyphon::Shaping::Advanced,
-            );
-            let mut back_text = TextArea {
-                bounds: glyphon::TextBounds { left: 0, top: 0, right: self.size.width as i32, bottom: self.size.height as i32 },
-                buffer: &self.text_rend.back_buffer,
-                left: 10.0,
-                top: 10.0,
-                scale: 1.0,
-                default_color: Color::rgb(50, 50, 50),
-                custom_glyphs: &[]
-            };
+        passes.group(FrameSection::Post.label(), |encoder| {
+            self.post.run(encoder, &self.queue, self.msaa.post_output(&view));
+        });
 
-            self.text_rend.front_buffer.set_text(
-                &mut self.text_rend.font_system,
-                &render_text,
-                Attrs::new()
-                    .color(Color::rgb(200, 200, 200))
-                    ,
-                glyphon::Shaping::Advanced,
-            );
-            let mut front_text = TextArea {
-                bounds: glyphon::TextBounds { left: 0, top: 0, right: self.size.width as i32, bottom: self.size.height as i32 },
-                buffer: &self.text_rend.front_buffer,
-                left: 8.0,
-                top: 9.0,
-                scale: 1.0,
-                default_color: Color::rgb(0, 0, 0),
-                custom_glyphs: &[]
-            };
+        // The systems need all of the state, so the pass can't be recorded in a closure.
+        let mut render_pass = passes.begin_render_pass(FrameSection::Ui.label(), &[Some(self.msaa.overlay_attachment(&view))]);
+        self.msaa.begin_overlay(&mut render_pass);
 
-            self.text_rend.text_renderer.prepare(&self.device, &self.queue, &mut self.text_rend.font_system, &mut self.text_rend.text_atlas, &viewport, [front_text, back_text], &mut self.text_rend.swash_cache).expect("Failed.");
-            self.text_rend.text_renderer.render(&self.text_rend.text_atlas, &viewport, &mut render_pass).expect("Failed to render text.");
-        }
+        let mut systems = std::mem::take(&mut self.systems);
+        systems.render(&mut RenderCtx {
+            state: self,
+            frame,
+            render_pass: &mut render_pass,
+        });
+        self.systems = systems;
 
         // render_pass.set_push_constants(ShaderStages::VERTEX, 0, bytemuck::bytes_of(&mat2));
         // render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
         // render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
         // render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
 
-        self.velvet.render(&mut render_pass);
+        // After the systems, so that the vector HUD is on top of the reticle and the text.
+        if self.settings.draw_overlay {
+            debug_group(&mut render_pass, FrameSection::VectorOverlay.label(), |render_pass| self.velvet.render(render_pass));
+        }
 
-        drop(render_pass);
-        self.queue.submit(std::iter::once(encoder.finish()));
+        passes.end_render_pass(render_pass);
+        self.queue.submit(std::iter::once(passes.finish(Some(&mut self.pass_profiler))));
+        self.pass_profiler.map();
         output.present();
-        let rt_ts_slice = self.rt_query_read_buffer.slice(..);
-        let finished = Arc::new(AtomicBool::new(false));
-        let finished_clone = Arc::clone(&finished);
-        rt_ts_slice.map_async(wgpu::MapMode::Read, move |result| {
-            if let Err(e) = result {
-                panic!("Failed to map buffer: {e:?}");
-            } else {
-                finished_clone.store(true, std::sync::atomic::Ordering::Relaxed);
-            }
-        });
-
-        while !finished.load(std::sync::atomic::Ordering::Relaxed) {
-            self.device.poll(wgpu::Maintain::Wait);
-        }
-        {
-            let rt_ts_data = rt_ts_slice.get_mapped_range();
-            let timestamps: &[u64] = bytemuck::cast_slice(&rt_ts_data);
-            let ticks = timestamps[1] - timestamps[0];
-            let time_ns = ticks as f64 * self.queue.get_timestamp_period() as f64;
-            let rt_compute_time = Duration::from_nanos(time_ns as u64);
-            self.raytrace_timer.push(rt_compute_time);
-        }
-        self.rt_query_read_buffer.unmap();
+        self.debug_capture.end_frame(&self.device);
         let time = start_time.elapsed();
         Ok(time)
     }
@@ -0,0 +1,244 @@
+/*
+The sun gizmo places the directional light by hand. The light source (the sun, or the moon at night) is
+drawn on the UI layer (see [crate::rendering::velvet::Velvet]) where it is in the sky, and can be dragged
+with the mouse while the cursor is unlocked. Alt and the arrow keys nudge it.
+
+The position is stored as an azimuth and an elevation. Moving the gizmo only changes the target angles;
+the light follows them smoothly (see [SunGizmo::update]), so that dragging and nudging don't make the
+lighting jump. The elevation is kept above the horizon so that faces are never lit from below.
+
+While the day/night cycle is running, the gizmo follows it instead (see [SunGizmo::snap_to]).
+*/
+
+use std::f32::consts::{FRAC_PI_2, TAU};
+use std::time::Duration;
+
+use glam::{vec2, vec3, Vec2, Vec3};
+use vello::{kurbo::{Affine, Circle, Line, Point, Stroke}, peniko::{Color, Fill}, Scene};
+
+use crate::camera::Camera;
+
+/// How fast the light catches up to the target angles (per second).
+const FOLLOW_RATE: f32 = 12.0;
+/// Angles closer than this (radians) to the target snap to it.
+const SNAP_ANGLE: f32 = 1e-4;
+/// How fast the arrow keys nudge the angles (radians per second).
+pub const NUDGE_SPEED: f32 = std::f32::consts::FRAC_PI_4;
+/// The radius of the sun disc on the UI layer (in its pixels).
+const DISC_RADIUS: f64 = 12.0;
+/// How far from the disc the mouse can grab it.
+const GRAB_RADIUS: f32 = 20.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SunAngles {
+    /// Around the Y axis from +X towards +Z (radians, `0..TAU`).
+    pub azimuth: f32,
+    /// Above the horizon (radians, `0..=FRAC_PI_2`).
+    pub elevation: f32,
+}
+
+impl SunAngles {
+    pub fn new(azimuth: f32, elevation: f32) -> Self {
+        Self {
+            azimuth: azimuth.rem_euclid(TAU),
+            elevation: elevation.clamp(0.0, FRAC_PI_2),
+        }
+    }
+
+    /// The angles of a position on the sky. Positions below the horizon are moved up to it.
+    pub fn from_position(position: Vec3) -> Self {
+        let position = position.normalize_or(Vec3::Y);
+        Self::new(position.z.atan2(position.x), position.y.clamp(-1.0, 1.0).asin())
+    }
+
+    /// The position on the unit sphere.
+    pub fn position(self) -> Vec3 {
+        let (sin_el, cos_el) = self.elevation.sin_cos();
+        let (sin_az, cos_az) = self.azimuth.sin_cos();
+        vec3(cos_el * cos_az, sin_el, cos_el * sin_az)
+    }
+
+    /// Moves towards `target` by `alpha` (`0..=1`), taking the short way around for the azimuth.
+    fn approach(self, target: Self, alpha: f32) -> Self {
+        let azimuth_delta = (target.azimuth - self.azimuth + TAU / 2.0).rem_euclid(TAU) - TAU / 2.0;
+        if azimuth_delta.abs() < SNAP_ANGLE && (target.elevation - self.elevation).abs() < SNAP_ANGLE {
+            return target;
+        }
+        Self::new(
+            self.azimuth + azimuth_delta * alpha,
+            self.elevation + (target.elevation - self.elevation) * alpha,
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SunGizmo {
+    pub visible: bool,
+    current: SunAngles,
+    target: SunAngles,
+    dragging: bool,
+}
+
+impl SunGizmo {
+    /// Starts with the light at the source of `light_direction` (the direction that the light travels).
+    pub fn new(light_direction: Vec3) -> Self {
+        let angles = SunAngles::from_position(-light_direction);
+        Self {
+            visible: true,
+            current: angles,
+            target: angles,
+            dragging: false,
+        }
+    }
+
+    /// The angles that the light is at right now.
+    pub fn angles(&self) -> SunAngles {
+        self.current
+    }
+
+    /// The angles that the light is moving towards.
+    pub fn target(&self) -> SunAngles {
+        self.target
+    }
+
+    /// The direction that the light travels.
+    pub fn light_direction(&self) -> Vec3 {
+        -self.current.position()
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.dragging
+    }
+
+    /// Moves the light towards a position on the sky.
+    pub fn set_position(&mut self, position: Vec3) {
+        self.target = SunAngles::from_position(position);
+    }
+
+    /// Moves the target angles by the given amounts (radians).
+    pub fn nudge(&mut self, azimuth: f32, elevation: f32) {
+        self.target = SunAngles::new(self.target.azimuth + azimuth, self.target.elevation + elevation);
+    }
+
+    /// Jumps straight to a position on the sky without following. Used while the day/night cycle moves the light.
+    pub fn snap_to(&mut self, position: Vec3) {
+        self.target = SunAngles::from_position(position);
+        self.current = self.target;
+        self.dragging = false;
+    }
+
+    /// Moves the light towards the target angles. Returns `true` if it moved.
+    pub fn update(&mut self, delta_time: Duration) -> bool {
+        if self.current == self.target {
+            return false;
+        }
+        let alpha = 1.0 - (-FOLLOW_RATE * delta_time.as_secs_f32()).exp();
+        self.current = self.current.approach(self.target, alpha);
+        true
+    }
+
+    /// Where the light is on the UI layer (of `layer_size` pixels), and whether it's in view.
+    /// Lights that are out of view are clamped to the edge, in the direction that the camera would have to turn.
+    pub fn layer_position(&self, camera: &Camera, layer_size: Vec2) -> (Vec2, bool) {
+        let clip = camera.world_to_clip(camera.position + self.current.position());
+        let (ndc, in_front) = if clip.w > 0.0 {
+            (vec2(clip.x, clip.y) / clip.w, true)
+        } else {
+            (-vec2(clip.x, clip.y), false)
+        };
+        let extent = ndc.abs().max_element();
+        let in_view = in_front && extent <= 1.0;
+        let ndc = if in_view || extent == 0.0 { ndc } else { ndc / extent };
+        (ndc_to_layer(ndc, layer_size), in_view)
+    }
+
+    /// Handles the mouse for this frame. `screen_pos` is the cursor in normalized screen coordinates
+    /// (`-1..1`, Y down) and `sky_position` is the point on the sky under it.
+    /// Returns `true` while the gizmo has the mouse, so that clicks don't go through to the world.
+    pub fn drag(&mut self, camera: &Camera, layer_size: Vec2, screen_pos: Vec2, sky_position: Vec3, pressed: bool, just_pressed: bool) -> bool {
+        if !pressed {
+            self.dragging = false;
+            return false;
+        }
+        if just_pressed && self.visible {
+            let (position, in_view) = self.layer_position(camera, layer_size);
+            let cursor = ndc_to_layer(vec2(screen_pos.x, -screen_pos.y), layer_size);
+            self.dragging = in_view && position.distance(cursor) <= GRAB_RADIUS;
+        }
+        if self.dragging {
+            self.set_position(sky_position);
+        }
+        self.dragging
+    }
+
+    /// Draws the light where it is in the sky, or a ring at the edge of the screen if it's out of view.
+    pub fn draw(&self, scene: &mut Scene, camera: &Camera, layer_size: Vec2) {
+        if !self.visible {
+            return;
+        }
+        let (position, in_view) = self.layer_position(camera, layer_size);
+        let center = Point::new(position.x as f64, position.y as f64);
+        let outline = Color::from_rgba8(40, 30, 10, 200);
+        if !in_view {
+            let ring = Circle::new(center, DISC_RADIUS * 0.6);
+            scene.stroke(&Stroke::new(3.0), Affine::IDENTITY, Color::from_rgba8(255, 210, 90, 200), None, &ring);
+            return;
+        }
+        let color = if self.dragging {
+            Color::from_rgb8(255, 240, 160)
+        } else {
+            Color::from_rgb8(255, 210, 90)
+        };
+        let ray_stroke = Stroke::new(2.0);
+        for index in 0..8 {
+            let (sin, cos) = (index as f64 * std::f64::consts::FRAC_PI_4).sin_cos();
+            let ray = Line::new(
+                (center.x + cos * DISC_RADIUS * 1.4, center.y + sin * DISC_RADIUS * 1.4),
+                (center.x + cos * DISC_RADIUS * 2.0, center.y + sin * DISC_RADIUS * 2.0),
+            );
+            scene.stroke(&ray_stroke, Affine::IDENTITY, color, None, &ray);
+        }
+        let disc = Circle::new(center, DISC_RADIUS);
+        scene.fill(Fill::NonZero, Affine::IDENTITY, color, None, &disc);
+        scene.stroke(&Stroke::new(1.5), Affine::IDENTITY, outline, None, &disc);
+    }
+}
+
+/// Converts normalized device coordinates (Y up) to pixels on the UI layer (Y down).
+fn ndc_to_layer(ndc: Vec2, layer_size: Vec2) -> Vec2 {
+    vec2(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5) * layer_size
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sun_angles_test() {
+        let angles = SunAngles::new(1.0, 0.5);
+        let round_trip = SunAngles::from_position(angles.position());
+        assert!((round_trip.azimuth - 1.0).abs() < 1e-5 && (round_trip.elevation - 0.5).abs() < 1e-5);
+        // Below the horizon is moved up to it.
+        assert_eq!(SunAngles::from_position(vec3(1.0, -1.0, 0.0)).elevation, 0.0);
+        assert_eq!(SunAngles::new(-0.5, 2.0).elevation, FRAC_PI_2);
+        assert!((SunAngles::new(-0.5, 0.0).azimuth - (TAU - 0.5)).abs() < 1e-5);
+
+        // The azimuth takes the short way around.
+        let halfway = SunAngles::new(TAU - 0.1, 0.0).approach(SunAngles::new(0.1, 0.0), 0.5);
+        assert!(halfway.azimuth < 1e-5 || halfway.azimuth > TAU - 1e-5);
+    }
+
+    #[test]
+    fn sun_gizmo_follow_test() {
+        let mut gizmo = SunGizmo::new(Vec3::NEG_Y);
+        assert!(!gizmo.update(Duration::from_millis(16)));
+        gizmo.nudge(0.0, -0.5);
+        assert!(gizmo.update(Duration::from_millis(16)));
+        assert!(gizmo.angles().elevation > gizmo.target().elevation);
+        for _ in 0..200 {
+            gizmo.update(Duration::from_millis(16));
+        }
+        assert_eq!(gizmo.angles(), gizmo.target());
+        assert!((gizmo.light_direction() + gizmo.target().position()).length() < 1e-5);
+    }
+}
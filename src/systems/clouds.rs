@@ -0,0 +1,44 @@
+use winit::keyboard::KeyCode;
+
+use crate::rendering::clouds::{COVERAGE_STEP, WIND_DIRECTION_STEP, WIND_SPEED_STEP};
+
+use super::{System, SystemCtx};
+
+/// Moves [crate::rendering::clouds::Clouds] with the wind. Alt+C toggles the clouds, Alt+Comma/Alt+Period
+/// change the coverage, Alt+Insert/Alt+Delete turn the wind and Alt+Shift+Insert/Alt+Shift+Delete change its
+/// speed. (Alt and the number keys fly to bookmarks.)
+pub struct CloudsSystem;
+
+impl System for CloudsSystem {
+    fn name(&self) -> &'static str {
+        "Clouds"
+    }
+
+    fn update(&mut self, ctx: &mut SystemCtx) {
+        let state = &mut *ctx.state;
+        let ctrl = state.input.key_pressed(KeyCode::ControlLeft) || state.input.key_pressed(KeyCode::ControlRight);
+        let alt = state.input.key_pressed(KeyCode::AltLeft) || state.input.key_pressed(KeyCode::AltRight);
+        let shift = state.input.key_pressed(KeyCode::ShiftLeft) || state.input.key_pressed(KeyCode::ShiftRight);
+        let wind_keys = state.input.key_just_pressed(KeyCode::Insert) || state.input.key_just_pressed(KeyCode::Delete);
+        if alt && state.input.key_just_pressed(KeyCode::KeyC) {
+            state.clouds.enabled = !state.clouds.enabled;
+            state.notify(if state.clouds.enabled { "Clouds: On" } else { "Clouds: Off" });
+        }
+        if alt && !ctrl && (state.input.key_just_pressed(KeyCode::Comma) || state.input.key_just_pressed(KeyCode::Period)) {
+            let step = if state.input.key_just_pressed(KeyCode::Comma) { -COVERAGE_STEP } else { COVERAGE_STEP };
+            state.clouds.set_coverage(state.clouds.coverage + step);
+            state.notify(format!("Cloud Coverage: {:.0}%", state.clouds.coverage * 100.0));
+        }
+        if alt && !shift && wind_keys {
+            let step = if state.input.key_just_pressed(KeyCode::Insert) { -WIND_DIRECTION_STEP } else { WIND_DIRECTION_STEP };
+            state.clouds.wind_direction = (state.clouds.wind_direction + step).rem_euclid(std::f32::consts::TAU);
+            state.notify(format!("Wind Direction: {:.0}°", state.clouds.wind_degrees()));
+        }
+        if alt && shift && wind_keys {
+            let step = if state.input.key_just_pressed(KeyCode::Insert) { -WIND_SPEED_STEP } else { WIND_SPEED_STEP };
+            state.clouds.set_wind_speed(state.clouds.wind_speed + step);
+            state.notify(format!("Wind Speed: {:.0} Blocks/s", state.clouds.wind_speed));
+        }
+        state.clouds.advance(ctx.frame.delta_time);
+    }
+}
@@ -0,0 +1,35 @@
+use winit::keyboard::KeyCode;
+
+use super::{System, SystemCtx};
+
+/// Advances [crate::day_night::DayNightCycle] and applies it to the raytracer's lighting.
+/// P pauses the cycle and the brackets change its speed, without modifiers (the keys do other things with them).
+pub struct DayNightSystem;
+
+impl System for DayNightSystem {
+    fn name(&self) -> &'static str {
+        "Day/Night"
+    }
+
+    fn update(&mut self, ctx: &mut SystemCtx) {
+        let state = &mut *ctx.state;
+        let input = &state.input;
+        let modified = [
+            KeyCode::ControlLeft, KeyCode::ControlRight,
+            KeyCode::AltLeft, KeyCode::AltRight,
+            KeyCode::ShiftLeft, KeyCode::ShiftRight,
+        ].into_iter().any(|key| input.key_pressed(key));
+        if input.key_just_pressed(KeyCode::KeyP) && !modified {
+            state.day_night.toggle_pause();
+        }
+        if input.key_just_pressed(KeyCode::BracketRight) && !modified {
+            state.day_night.speed_up();
+        }
+        if input.key_just_pressed(KeyCode::BracketLeft) && !modified {
+            state.day_night.slow_down();
+        }
+        if state.day_night.update(ctx.frame.delta_time) {
+            state.day_night.apply(&state.raytracer.gpu_lighting, &state.queue);
+        }
+    }
+}
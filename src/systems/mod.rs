@@ -0,0 +1,90 @@
+use crate::state::State;
+use crate::FrameInfo;
+
+pub mod clouds;
+pub mod day_night;
+pub mod overlay;
+pub mod reticle;
+
+pub use clouds::CloudsSystem;
+pub use day_night::DayNightSystem;
+pub use overlay::OverlaySystem;
+pub use reticle::ReticleSystem;
+
+/*
+Systems are features that hook into the State lifecycle without State having to know about them.
+They're registered (in order) from main.rs:
+
+    state.systems
+        .add(DayNightSystem)
+        .add(CloudsSystem)
+        .add(ReticleSystem)
+        .add(OverlaySystem);
+
+Every frame, `update` is called on each system (in registration order) at the end of State::update, and
+`render` is called during the UI pass, after post processing, so systems draw on top of the scene.
+*/
+
+pub struct SystemCtx<'a, 'w> {
+    pub state: &'a mut State<'w>,
+    pub frame: &'a FrameInfo,
+}
+
+pub struct RenderCtx<'a, 'w, 'p> {
+    pub state: &'a mut State<'w>,
+    pub frame: &'a FrameInfo,
+    /// The UI pass, which draws directly to the surface.
+    pub render_pass: &'a mut wgpu::RenderPass<'p>,
+}
+
+pub trait System {
+    fn name(&self) -> &'static str;
+
+    fn update(&mut self, _ctx: &mut SystemCtx) {}
+
+    fn render(&mut self, _ctx: &mut RenderCtx) {}
+}
+
+/// The registered systems, in the order that they run.
+#[derive(Default)]
+pub struct Systems {
+    systems: Vec<Box<dyn System>>,
+}
+
+impl Systems {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add<S: System + 'static>(&mut self, system: S) -> &mut Self {
+        self.systems.push(Box::new(system));
+        self
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.systems.iter().map(|system| system.name())
+    }
+
+    pub fn len(&self) -> usize {
+        self.systems.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.systems.is_empty()
+    }
+
+    pub fn update(&mut self, ctx: &mut SystemCtx) {
+        for system in self.systems.iter_mut() {
+            system.update(ctx);
+        }
+    }
+
+    /// Renders each system in a debug group named after it.
+    pub fn render(&mut self, ctx: &mut RenderCtx) {
+        for system in self.systems.iter_mut() {
+            ctx.render_pass.push_debug_group(system.name());
+            system.render(ctx);
+            ctx.render_pass.pop_debug_group();
+        }
+    }
+}
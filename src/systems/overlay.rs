@@ -0,0 +1,341 @@
+use std::fmt::Write;
+
+use glam::vec2;
+use glyphon::{Attrs, Color, Resolution, Viewport};
+use winit::window::CursorGrabMode;
+
+use crate::chunk_debug::{chunk_coord, local_coord};
+use crate::console::{format_entry, level_color, CONSOLE_MARGIN};
+use crate::logging::log_buffer;
+use crate::rendering::exposure::AutoExposure;
+use crate::rendering::lighting::LightingPreset;
+use crate::rendering::memory::gpu_memory;
+use crate::rendering::post::{Bloom, Gamma, Tonemap};
+use crate::gridzmo::grid_spacing;
+use crate::mouse_settings::SmoothingMode;
+use crate::rendering::raytrace::{RaytraceDebugView, TranslucentHits, VolumetricQuality};
+use crate::rendering::text_style::{StyledText, TextStyle};
+use crate::state::{State, LAMP_ID, MIRROR_ID, WATER_ID};
+use crate::FrameInfo;
+
+use super::{RenderCtx, System};
+
+/// The distance of the debug text from the corner of the window.
+const HUD_MARGIN: f32 = 10.0;
+
+/// The debug text in the top left corner of the window, and the log console at the bottom.
+pub struct OverlaySystem;
+
+impl System for OverlaySystem {
+    fn name(&self) -> &'static str {
+        "Overlay"
+    }
+
+    fn render(&mut self, ctx: &mut RenderCtx) {
+        let state = &mut *ctx.state;
+        let mut viewport = Viewport::new(&state.device, &state.text_rend.cache);
+        viewport.update(&state.queue, Resolution { width: state.size.width, height: state.size.height });
+
+        let console_visible = prepare_console(state);
+
+        let mut render_text = String::new();
+        // Writing to a String can't fail.
+        _ = write_text(state, ctx.frame, &mut render_text);
+
+        let text_rend = &mut state.text_rend;
+        text_rend.front_buffer.set_text(&mut text_rend.font_system, &render_text, Attrs::new(), glyphon::Shaping::Advanced);
+
+        let bounds = glyphon::TextBounds { left: 0, top: 0, right: state.size.width as i32, bottom: state.size.height as i32 };
+        let mut texts = vec![StyledText {
+            buffer: &text_rend.front_buffer,
+            // The debug text has no colored spans, so the layers are drawn from the same buffer.
+            layer_buffer: &text_rend.front_buffer,
+            position: vec2(HUD_MARGIN, HUD_MARGIN),
+            bounds,
+            style: TextStyle::HUD,
+        }];
+        if console_visible {
+            let top = state.size.height as f32 - state.console.height() - CONSOLE_MARGIN;
+            texts.push(StyledText {
+                buffer: &text_rend.console_front_buffer,
+                layer_buffer: &text_rend.console_back_buffer,
+                position: vec2(CONSOLE_MARGIN, top),
+                bounds: glyphon::TextBounds { top: top as i32, ..bounds },
+                style: TextStyle::CONSOLE,
+            });
+        }
+
+        // The backgrounds are a vector layer of their own, since the one with the frame graph is drawn on top
+        // of the text.
+        if texts.iter().any(StyledText::has_background) {
+            text_rend.backgrounds.draw(&state.device, &state.queue, |scene| {
+                for text in texts.iter() {
+                    text.draw_background(scene);
+                }
+            });
+            text_rend.backgrounds.render(ctx.render_pass);
+        }
+
+        let text_areas = texts.iter().flat_map(StyledText::text_areas).collect::<Vec<_>>();
+        text_rend.text_renderer.prepare(&state.device, &state.queue, &mut text_rend.font_system, &mut text_rend.text_atlas, &viewport, text_areas, &mut text_rend.swash_cache).expect("Failed.");
+        text_rend.text_renderer.render(&text_rend.text_atlas, &viewport, ctx.render_pass).expect("Failed to render text.");
+    }
+}
+
+/// Sets the text of the console's buffers. Returns `false` if the console is hidden.
+fn prepare_console(state: &mut State) -> bool {
+    if !state.console.visible {
+        return false;
+    }
+    let entries = state.console.visible_entries(log_buffer());
+    let mut lines = vec![(state.console.title(), Color::rgb(255, 255, 255))];
+    lines.extend(entries.iter().map(|entry| {
+        let [r, g, b] = level_color(entry.level);
+        (format!("\n{}", format_entry(entry)), Color::rgb(r, g, b))
+    }));
+    let text_rend = &mut state.text_rend;
+    let width = Some(state.size.width as f32 - CONSOLE_MARGIN * 2.0);
+    text_rend.console_front_buffer.set_size(&mut text_rend.font_system, width, None);
+    text_rend.console_back_buffer.set_size(&mut text_rend.font_system, width, None);
+    text_rend.console_front_buffer.set_rich_text(
+        &mut text_rend.font_system,
+        lines.iter().map(|(line, color)| (line.as_str(), Attrs::new().color(*color))),
+        Attrs::new(),
+        glyphon::Shaping::Advanced,
+    );
+    let plain = lines.iter().map(|(line, _)| line.as_str()).collect::<String>();
+    text_rend.console_back_buffer.set_text(&mut text_rend.font_system, &plain, Attrs::new(), glyphon::Shaping::Advanced);
+    true
+}
+
+fn write_text(state: &State, frame: &FrameInfo, text: &mut String) -> std::fmt::Result {
+    if let Some(message) = state.notification() {
+        writeln!(text, "{message}")?;
+    }
+    writeln!(text, "Frame Index: {}", frame.index)?;
+    writeln!(text, "FPS: {:.0}", frame.fps)?;
+    if state.frame_graph.visible {
+        writeln!(text, "Frame Graph (up to {:.1?}): Frame (White), Update (Green), Render (Blue), Raytrace GPU (Orange)", state.frame_graph.max_time())?;
+    }
+    let avg_rt_time = state.raytrace_timer.average();
+    let max_rt_time = state.raytrace_timer.percentile(0.99).unwrap_or_default();
+    let workgroup_size = state.raytracer.workgroup_size();
+    writeln!(text, "Raytrace Time: {avg_rt_time:.3?} (99%: {max_rt_time:.3?}), {:?} Directions, {}x{} Workgroups{}",
+        state.raytracer.direction_mode(),
+        workgroup_size.x,
+        workgroup_size.y,
+        if state.raytracer.indirect_dispatch() { " (Indirect)" } else { "" },
+    )?;
+    let passes = state.pass_profiler.breakdown()
+        .map(|(label, time)| format!("{label} {time:.3?}"))
+        .collect::<Vec<_>>();
+    if !passes.is_empty() {
+        writeln!(text, "GPU Passes: {}", passes.join(", "))?;
+    }
+    if let Some(variant) = state.raytracer.comparison() {
+        let avg_time = state.comparison_timer.average();
+        let max_time = state.comparison_timer.percentile(0.99).unwrap_or_default();
+        writeln!(text, "A/B Comparison (Right Half): {avg_time:.3?} (99%: {max_time:.3?}), {:?} Directions, {}x{} Workgroups, AO {:.2}",
+            variant.direction_mode,
+            variant.workgroup_size.x,
+            variant.workgroup_size.y,
+            variant.ao_strength,
+        )?;
+    }
+    let staging = state.staging.stats();
+    writeln!(text, "Staging: {} KiB last frame, {} buffers ({} in flight, {:.1} MiB)",
+        staging.bytes_last_frame / 1024,
+        staging.buffer_count,
+        staging.in_flight,
+        mebibytes(staging.allocated_bytes),
+    )?;
+    let memory = gpu_memory();
+    let categories = memory.report().iter()
+        .map(|usage| format!("{} {:.1}", usage.category.name(), mebibytes(usage.bytes)))
+        .collect::<Vec<_>>();
+    writeln!(text, "GPU Memory: {:.1} MiB ({})", mebibytes(memory.total()), categories.join(", "))?;
+    let mouse = &state.settings.mouse;
+    writeln!(text, "Mouse Sensitivity: {:.2}{}", mouse.sensitivity, if mouse.invert_y { " (Inverted Y)" } else { "" })?;
+    match mouse.smoothing_mode {
+        SmoothingMode::Off => writeln!(text, "Mouse Smoothing: Off")?,
+        SmoothingMode::Average => writeln!(text, "Mouse Smoothing: Average of {}", state.input.mouse_pos.delta_avg.capacity())?,
+        mode => writeln!(text, "Mouse Smoothing: {mode:?}")?,
+    }
+    if mouse.smoothing_mode != SmoothingMode::Off {
+        writeln!(text, "Mouse Halting: {}", mouse.halting)?;
+    }
+    writeln!(text, "Animations: {}", state.animations.len())?;
+    writeln!(text, "Camera Path: {} keyframes", state.camera_path.len())?;
+    if !state.bookmarks.is_empty() {
+        let bookmarks = state.bookmarks.iter()
+            .map(|(slot, pose)| format!("{slot} ({:.0}, {:.0}, {:.0})", pose.position.x, pose.position.y, pose.position.z))
+            .collect::<Vec<_>>();
+        writeln!(text, "Bookmarks: {}", bookmarks.join(", "))?;
+    }
+    if let Some(benchmark) = &state.benchmark {
+        writeln!(text, "Benchmark: {}/{} frames ({:.0}%)", benchmark.frame(), benchmark.total_frames(), benchmark.progress() * 100.0)?;
+    }
+    writeln!(text, "Move Speed: {:.2} ({:?})", state.movement.speed(), state.movement.move_type())?;
+    if state.zoom > 0.0 {
+        writeln!(text, "FOV: {:.0} (Zoom)", state.camera.fov.to_degrees())?;
+    } else {
+        writeln!(text, "FOV: {:.0}", state.camera.fov.to_degrees())?;
+    }
+    writeln!(text, "Present Mode: {:?}", state.config.present_mode)?;
+    if state.locked {
+        let cursor = match state.cursor_grab {
+            CursorGrabMode::Locked => "Locked",
+            CursorGrabMode::Confined => "Confined",
+            CursorGrabMode::None => "Warped",
+        };
+        writeln!(text, "Cursor: {cursor}")?;
+    }
+    writeln!(text, "Shadows: {:?}", state.raytracer.shadow_quality())?;
+    let volumetric_quality = state.raytracer.volumetric_quality();
+    if volumetric_quality != VolumetricQuality::Off {
+        writeln!(text, "Volumetric Light: {volumetric_quality:?} (Density {:.3})", state.raytracer.volumetric_density())?;
+    }
+    writeln!(text, "Reflection Bounces: {}", state.raytracer.max_bounces())?;
+    writeln!(text, "Sky: {:?}", state.raytracer.sky_mode())?;
+    if state.clouds.enabled {
+        let clouds = &state.clouds;
+        writeln!(text, "Clouds: {:.0}% Coverage, Wind {:.0} Blocks/s at {:.0}°", clouds.coverage * 100.0, clouds.wind_speed, clouds.wind_degrees())?;
+    }
+    if state.raytracer.debug_view() == RaytraceDebugView::StepHeatmap {
+        writeln!(text, "Step Heatmap: Blue (0) to Red ({} steps), Magenta Beyond", state.raytracer.heatmap_max_steps())?;
+    }
+    writeln!(text, "MSAA: {}x", state.msaa.sample_count())?;
+    let render_mode = state.settings.render_mode;
+    if render_mode.shows_rasterized() {
+        writeln!(text, "Render Mode: {} ({} Triangles)", render_mode.name(), state.raster.triangle_count())?;
+    } else {
+        writeln!(text, "Render Mode: {}", render_mode.name())?;
+    }
+    if state.settings.chunk_debug.shows_borders() {
+        let chunk = chunk_coord(state.camera.position);
+        let local = local_coord(state.camera.position);
+        writeln!(text, "Chunk: ({}, {}, {}), Voxel in Chunk: ({}, {}, {}){}",
+            chunk.x, chunk.y, chunk.z,
+            local.x, local.y, local.z,
+            if state.settings.chunk_debug.shows_axes() { ", Axes at Origin" } else { "" },
+        )?;
+    }
+    if state.gridzmo.visible {
+        let (spacing, _) = grid_spacing(state.camera.position.y - state.gridzmo.height);
+        writeln!(text, "Grid: {spacing} Spacing at Y = {}", state.gridzmo.height)?;
+    }
+    let quality = state.raytracer.quality();
+    let (width, height) = state.raytracer.resolution();
+    writeln!(text, "Antialiasing: {}x SSAA ({width}x{height}), {}", quality.supersample_scale, quality.antialiasing.name())?;
+    let dynamic = if state.dynamic_render_scale.enabled { " (Dynamic)" } else { "" };
+    writeln!(text, "Render Scale: {}%{dynamic}", quality.render_scale)?;
+    if state.raytracer.accumulation_enabled() {
+        writeln!(text, "Accumulated Frames: {}", state.raytracer.accumulated_frames())?;
+    } else {
+        writeln!(text, "Accumulation: Off")?;
+    }
+    let placing = match state.place_id {
+        MIRROR_ID => "Mirror",
+        LAMP_ID => "Lamp",
+        WATER_ID => "Water",
+        _ => "Block",
+    };
+    if state.sculptor.mode.is_active() {
+        let noise = if state.sculptor.noise { ", Noise" } else { "" };
+        writeln!(text, "Sculpting: {} (Radius {}{noise})", state.sculptor.mode.name(), state.brush.radius)?;
+    } else {
+        writeln!(text, "Placing: {placing}")?;
+    }
+    if state.picker.translucent == TranslucentHits::PassThrough {
+        writeln!(text, "Editing through translucent blocks")?;
+    }
+    if state.selection_mode {
+        let selected = state.selection
+            .map(|selection| { let size = selection.size(); format!("{}x{}x{}", size.x, size.y, size.z) })
+            .unwrap_or_else(|| String::from("None"));
+        let clipboard = state.clipboard.as_ref()
+            .map(|clipboard| { let size = clipboard.size(); format!("{}x{}x{}", size.x, size.y, size.z) })
+            .unwrap_or_else(|| String::from("Empty"));
+        writeln!(text, "Selecting: {selected}, Clipboard: {clipboard}")?;
+    }
+    writeln!(text, "Ambient Occlusion: {}", if state.settings.ambient_occlusion { "On" } else { "Off" })?;
+    writeln!(text, "Texture Filtering: {}", state.texture_filtering().name())?;
+    if state.day_night.paused {
+        writeln!(text, "Time of Day: {} (Paused)", state.day_night.clock_time())?;
+    } else {
+        writeln!(text, "Time of Day: {} (x{:.2})", state.day_night.clock_time(), state.day_night.speed())?;
+    }
+    if let Some(index) = state.lighting_preset.filter(|_| state.day_night.paused) {
+        writeln!(text, "Lighting: {}", LightingPreset::ALL[index].name)?;
+    }
+    if !state.lights.is_empty() {
+        writeln!(text, "Point Lights: {}{}", state.lights.len(), if state.torch.is_some() { " (Torch On)" } else { "" })?;
+    }
+    if state.sun_gizmo.visible {
+        let angles = state.sun_gizmo.angles();
+        writeln!(text, "Sun: Azimuth {:.1}, Elevation {:.1}{}",
+            angles.azimuth.to_degrees(),
+            angles.elevation.to_degrees(),
+            if state.sun_gizmo.is_dragging() { " (Dragging)" } else { "" },
+        )?;
+    }
+    let post_effects = state.post.effects().iter()
+        .filter(|effect| effect.enabled())
+        .map(|effect| effect.name())
+        .collect::<Vec<_>>();
+    if !post_effects.is_empty() {
+        writeln!(text, "Post Effects: {}", post_effects.join(", "))?;
+    }
+    if let Some(bloom) = state.post.get::<Bloom>().filter(|bloom| bloom.enabled) {
+        writeln!(text, "Bloom: Threshold {:.2}, Intensity {:.1}", bloom.threshold, bloom.intensity)?;
+    }
+    if let Some(auto_exposure) = state.post.get::<AutoExposure>().filter(|auto_exposure| auto_exposure.enabled) {
+        writeln!(
+            text,
+            "Auto Exposure: Key {:.2}, Range {:.2}..{:.1}",
+            auto_exposure.key, auto_exposure.min_exposure, auto_exposure.max_exposure,
+        )?;
+    }
+    if let Some(tonemap) = state.post.get::<Tonemap>().filter(|tonemap| tonemap.enabled) {
+        writeln!(text, "Tonemap: {:?} (Exposure: {:.2})", tonemap.operator, tonemap.exposure)?;
+    }
+    if let Some(gamma) = state.post.get::<Gamma>().filter(|gamma| gamma.enabled) {
+        writeln!(text, "Gamma: {:.1}", gamma.gamma)?;
+    }
+    if state.color_audit {
+        writeln!(text, "Color Audit (Surface: {:?}):", state.config.format)?;
+        for path in state.color_paths() {
+            writeln!(text, "  {}: {:?} -> {:?} ({:?}){}",
+                path.name,
+                path.input,
+                path.target,
+                path.conversion,
+                if path.is_correct() { "" } else { " WRONG" },
+            )?;
+        }
+    }
+    if let Some(player) = &state.player {
+        writeln!(text, "Walking ({})", if player.on_ground { "On Ground" } else { "Airborne" })?;
+    }
+    if let Some(recorder) = &state.recorder {
+        writeln!(text, "Recording Input: {} frames", recorder.frames().len())?;
+    }
+    if let Some(playback) = &state.playback {
+        writeln!(text, "Playing Input: {}/{}", playback.position(), playback.frames().len())?;
+    }
+    if !state.tasks.is_idle() {
+        writeln!(text, "Background Tasks: {}", state.tasks.pending())?;
+    }
+    let gpu_errors = state.gpu_errors.errors();
+    if !gpu_errors.is_empty() {
+        writeln!(text, "GPU Errors:")?;
+        for error in gpu_errors.iter() {
+            writeln!(text, "  {error}")?;
+        }
+    }
+    Ok(())
+}
+
+fn mebibytes(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
@@ -0,0 +1,16 @@
+use super::{RenderCtx, System};
+
+/// Draws the reticle while the cursor is locked.
+pub struct ReticleSystem;
+
+impl System for ReticleSystem {
+    fn name(&self) -> &'static str {
+        "Reticle"
+    }
+
+    fn render(&mut self, ctx: &mut RenderCtx) {
+        if ctx.state.locked {
+            ctx.state.reticle.render(ctx.render_pass);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::{panic::AssertUnwindSafe, sync::{mpsc::{self, Receiver, Sender}, Arc, Mutex}, thread::JoinHandle};
+
+/*
+A small worker pool for work that would otherwise hitch the frame loop (chunk generation, loading,
+meshing). Jobs are sent to the workers over a channel, and the results come back over another
+channel that the main thread drains with `poll()` once per update. Nothing ever blocks the main
+thread unless `wait()` is called.
+*/
+
+type Job<T> = Box<dyn FnOnce() -> T + Send + 'static>;
+
+/// A handle to a job spawned on a [TaskPool].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaskId(u64);
+
+pub struct TaskPool<T: Send + 'static> {
+    job_sender: Option<Sender<(TaskId, Job<T>)>>,
+    // `None` means that the job panicked.
+    result_receiver: Receiver<(TaskId, Option<T>)>,
+    workers: Vec<JoinHandle<()>>,
+    next_id: u64,
+    pending: usize,
+}
+
+impl<T: Send + 'static> TaskPool<T> {
+    pub fn new(thread_count: usize) -> Self {
+        let (job_sender, job_receiver) = mpsc::channel::<(TaskId, Job<T>)>();
+        let (result_sender, result_receiver) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let workers = (0..thread_count.max(1)).map(|index| {
+            let job_receiver = job_receiver.clone();
+            let result_sender = result_sender.clone();
+            std::thread::Builder::new()
+                .name(format!("task-worker-{index}"))
+                .spawn(move || loop {
+                    // The lock is released as soon as a job has been received.
+                    let received = job_receiver.lock().unwrap().recv();
+                    let Ok((id, job)) = received else {
+                        // The pool was dropped.
+                        break;
+                    };
+                    let result = std::panic::catch_unwind(AssertUnwindSafe(job)).ok();
+                    if result.is_none() {
+                        log::error!("Task {id:?} panicked.");
+                    }
+                    if result_sender.send((id, result)).is_err() {
+                        break;
+                    }
+                })
+                .expect("Failed to spawn worker thread.")
+        }).collect();
+        Self {
+            job_sender: Some(job_sender),
+            result_receiver,
+            workers,
+            next_id: 0,
+            pending: 0,
+        }
+    }
+
+    /// Creates a pool with one worker per available core, leaving one for the main thread.
+    pub fn with_available_parallelism() -> Self {
+        let threads = std::thread::available_parallelism()
+            .map(|count| count.get().saturating_sub(1))
+            .unwrap_or(1);
+        Self::new(threads)
+    }
+
+    pub fn thread_count(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// The number of jobs that have been spawned but not yet returned by [TaskPool::poll] or [TaskPool::wait].
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.pending == 0
+    }
+
+    pub fn spawn<F: FnOnce() -> T + Send + 'static>(&mut self, job: F) -> TaskId {
+        let id = TaskId(self.next_id);
+        self.next_id += 1;
+        self.job_sender.as_ref()
+            .expect("Task pool has been shut down.")
+            .send((id, Box::new(job)))
+            .expect("All worker threads have exited.");
+        self.pending += 1;
+        id
+    }
+
+    /// Returns the results of all jobs that have finished since the last call without blocking.
+    /// Jobs that panicked are logged and skipped.
+    pub fn poll(&mut self) -> Vec<(TaskId, T)> {
+        let mut finished = Vec::new();
+        while let Ok((id, result)) = self.result_receiver.try_recv() {
+            self.pending -= 1;
+            if let Some(result) = result {
+                finished.push((id, result));
+            }
+        }
+        finished
+    }
+
+    /// Blocks until a job finishes. Returns `None` if there are no pending jobs.
+    /// Jobs that panicked are logged and skipped.
+    pub fn wait(&mut self) -> Option<(TaskId, T)> {
+        while self.pending > 0 {
+            let (id, result) = self.result_receiver.recv().ok()?;
+            self.pending -= 1;
+            if let Some(result) = result {
+                return Some((id, result));
+            }
+        }
+        None
+    }
+}
+
+impl<T: Send + 'static> Drop for TaskPool<T> {
+    fn drop(&mut self) {
+        // Closing the job channel makes the workers exit once the queue is empty.
+        self.job_sender.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn task_pool_test() {
+        let mut pool = TaskPool::new(4);
+        let ids = (0..16u64).map(|i| (pool.spawn(move || i * i), i * i)).collect::<Vec<_>>();
+        assert_eq!(pool.pending(), 16);
+        let mut results = Vec::new();
+        while let Some(result) = pool.wait() {
+            results.push(result);
+        }
+        assert!(pool.is_idle());
+        results.sort();
+        assert_eq!(results, ids);
+        assert!(pool.poll().is_empty());
+    }
+
+    #[test]
+    fn panic_test() {
+        let mut pool = TaskPool::new(1);
+        pool.spawn(|| panic!("Intentional panic."));
+        let id = pool.spawn(|| 7);
+        // The panicking job is skipped, and the worker survives it.
+        assert_eq!(pool.wait(), Some((id, 7)));
+        assert_eq!(pool.wait(), None);
+    }
+}
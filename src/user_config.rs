@@ -0,0 +1,474 @@
+use std::{collections::{BTreeMap, HashMap}, fmt::Write as _, path::Path};
+
+use winit::keyboard::KeyCode;
+
+use glam::{Vec2, Vec3};
+
+use crate::animation::camera_path::CameraKeyframe;
+use crate::bookmarks::{Bookmarks, BOOKMARK_SLOTS};
+use crate::mouse_settings::{MouseSettings, SmoothingMode};
+use crate::movement::{move_type_from_name, move_type_name, SpeedPreset};
+use crate::rendering::sampler::TextureFiltering;
+
+/*
+User settings that persist between launches. The file is a small subset of TOML:
+
+    # Comment
+    [section]
+    key = true
+    key = 1.5
+    key = "string"
+
+Keys are read as `section.key`. Missing keys keep their defaults and unknown keys are ignored, so
+older files keep working when settings are added. `input.mouse_smoothing` and `input.mouse_halting`
+from before the `[mouse]` section are still read, but the `[mouse]` keys take precedence over them.
+
+The flying speeds are numbered sections, in order:
+
+    [movement.0]
+    speed = 0.25
+    acceleration = 12
+    deceleration = 8
+    mode = "Planar"
+
+If the file has any, they replace the default presets. Missing keys of a preset keep the defaults of
+[SpeedPreset]. Camera bookmarks are sections by slot, with the rotation in degrees:
+
+    [bookmark.1]
+    x = 12.5
+    y = 40
+    z = -3
+    pitch = -15
+    yaw = 90
+*/
+
+#[derive(Debug, thiserror::Error)]
+pub enum UserConfigError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Syntax error on line {0}.")]
+    Syntax(usize),
+    #[error("Invalid value for {key}: {value}")]
+    InvalidValue { key: String, value: String },
+}
+
+/// Keys that can be bound in [Keybinds].
+const BINDABLE_KEYS: &[KeyCode] = &[
+    KeyCode::KeyA, KeyCode::KeyB, KeyCode::KeyC, KeyCode::KeyD, KeyCode::KeyE, KeyCode::KeyF, KeyCode::KeyG,
+    KeyCode::KeyH, KeyCode::KeyI, KeyCode::KeyJ, KeyCode::KeyK, KeyCode::KeyL, KeyCode::KeyM, KeyCode::KeyN,
+    KeyCode::KeyO, KeyCode::KeyP, KeyCode::KeyQ, KeyCode::KeyR, KeyCode::KeyS, KeyCode::KeyT, KeyCode::KeyU,
+    KeyCode::KeyV, KeyCode::KeyW, KeyCode::KeyX, KeyCode::KeyY, KeyCode::KeyZ,
+    KeyCode::Digit0, KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4,
+    KeyCode::Digit5, KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+    KeyCode::ArrowUp, KeyCode::ArrowDown, KeyCode::ArrowLeft, KeyCode::ArrowRight,
+    KeyCode::Space, KeyCode::ShiftRight, KeyCode::ControlRight, KeyCode::Enter, KeyCode::Backspace,
+    KeyCode::Numpad0, KeyCode::Numpad1, KeyCode::Numpad2, KeyCode::Numpad3, KeyCode::Numpad4,
+    KeyCode::Numpad5, KeyCode::Numpad6, KeyCode::Numpad7, KeyCode::Numpad8, KeyCode::Numpad9,
+];
+
+const PRESENT_MODES: &[wgpu::PresentMode] = &[
+    wgpu::PresentMode::AutoVsync,
+    wgpu::PresentMode::AutoNoVsync,
+    wgpu::PresentMode::Fifo,
+    wgpu::PresentMode::FifoRelaxed,
+    wgpu::PresentMode::Immediate,
+    wgpu::PresentMode::Mailbox,
+];
+
+/// Parses the [std::fmt::Debug] name of a bindable key, such as `KeyW`.
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS.iter().copied().find(|key| format!("{key:?}") == name)
+}
+
+/// The movement keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keybinds {
+    pub forward: KeyCode,
+    pub backward: KeyCode,
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub up: KeyCode,
+    pub down: KeyCode,
+    pub jump: KeyCode,
+}
+
+impl Default for Keybinds {
+    fn default() -> Self {
+        Self {
+            forward: KeyCode::KeyW,
+            backward: KeyCode::KeyS,
+            left: KeyCode::KeyA,
+            right: KeyCode::KeyD,
+            up: KeyCode::KeyR,
+            down: KeyCode::KeyF,
+            jump: KeyCode::Space,
+        }
+    }
+}
+
+impl Keybinds {
+    fn entries(&self) -> [(&'static str, KeyCode); 7] {
+        [
+            ("forward", self.forward),
+            ("backward", self.backward),
+            ("left", self.left),
+            ("right", self.right),
+            ("up", self.up),
+            ("down", self.down),
+            ("jump", self.jump),
+        ]
+    }
+
+    fn entry_mut(&mut self, name: &str) -> Option<&mut KeyCode> {
+        Some(match name {
+            "forward" => &mut self.forward,
+            "backward" => &mut self.backward,
+            "left" => &mut self.left,
+            "right" => &mut self.right,
+            "up" => &mut self.up,
+            "down" => &mut self.down,
+            "jump" => &mut self.jump,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserConfig {
+    pub mouse: MouseSettings,
+    /// An index into `speed_presets`.
+    pub move_speed_index: usize,
+    pub speed_presets: Vec<SpeedPreset>,
+    pub bookmarks: Bookmarks,
+    pub keybinds: Keybinds,
+    /// The base vertical field of view, in radians.
+    pub fov: f32,
+    pub present_mode: wgpu::PresentMode,
+    /// The MSAA sample count (1, 2, 4 or 8). Unsupported counts are ignored.
+    pub msaa_samples: u32,
+    /// The filtering of the voxel textures. Anisotropy is turned off on devices that don't support it.
+    pub texture_filtering: TextureFiltering,
+    /// The physical inner size of the window while windowed.
+    pub window_size: (u32, u32),
+    pub fullscreen: bool,
+}
+
+impl Default for UserConfig {
+    fn default() -> Self {
+        Self {
+            mouse: MouseSettings::default(),
+            move_speed_index: 4,
+            speed_presets: SpeedPreset::defaults(),
+            bookmarks: Bookmarks::new(),
+            keybinds: Keybinds::default(),
+            fov: 60f32.to_radians(),
+            present_mode: wgpu::PresentMode::Fifo,
+            msaa_samples: 1,
+            texture_filtering: TextureFiltering::Nearest,
+            window_size: (1280, 720),
+            fullscreen: false,
+        }
+    }
+}
+
+fn invalid(key: &str, value: &str) -> UserConfigError {
+    UserConfigError::InvalidValue { key: key.to_owned(), value: value.to_owned() }
+}
+
+fn parse_value<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, UserConfigError> {
+    value.parse().map_err(|_| invalid(key, value))
+}
+
+fn parse_string<'a>(key: &str, value: &'a str) -> Result<&'a str, UserConfigError> {
+    value.strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .ok_or_else(|| invalid(key, value))
+}
+
+/// Reads a `movement.<index>.<field>` key into the preset at `index`.
+fn parse_speed_preset(presets: &mut BTreeMap<usize, SpeedPreset>, key: &str, value: &str) -> Result<(), UserConfigError> {
+    let (index, field) = key.strip_prefix("movement.")
+        .and_then(|rest| rest.split_once('.'))
+        .and_then(|(index, field)| Some((index.parse::<usize>().ok()?, field)))
+        .ok_or_else(|| invalid(key, value))?;
+    let preset = presets.entry(index).or_default();
+    match field {
+        "speed" => preset.speed = parse_value(key, value)?,
+        "acceleration" => preset.acceleration = parse_value(key, value)?,
+        "deceleration" => preset.deceleration = parse_value(key, value)?,
+        "mode" => preset.move_type = move_type_from_name(parse_string(key, value)?).ok_or_else(|| invalid(key, value))?,
+        _ => log::warn!("Unknown user config key: {key}"),
+    }
+    Ok(())
+}
+
+/// Reads a `bookmark.<slot>.<field>` key into the bookmark of `slot`.
+fn parse_bookmark(bookmarks: &mut BTreeMap<usize, CameraKeyframe>, key: &str, value: &str) -> Result<(), UserConfigError> {
+    let (slot, field) = key.strip_prefix("bookmark.")
+        .and_then(|rest| rest.split_once('.'))
+        .and_then(|(slot, field)| Some((slot.parse::<usize>().ok()?, field)))
+        .filter(|&(slot, _)| (1..=BOOKMARK_SLOTS).contains(&slot))
+        .ok_or_else(|| invalid(key, value))?;
+    let pose = bookmarks.entry(slot).or_insert(CameraKeyframe { position: Vec3::ZERO, rotation: Vec2::ZERO });
+    match field {
+        "x" => pose.position.x = parse_value(key, value)?,
+        "y" => pose.position.y = parse_value(key, value)?,
+        "z" => pose.position.z = parse_value(key, value)?,
+        "pitch" => pose.rotation.x = parse_value::<f32>(key, value)?.to_radians(),
+        "yaw" => pose.rotation.y = parse_value::<f32>(key, value)?.to_radians(),
+        _ => log::warn!("Unknown user config key: {key}"),
+    }
+    Ok(())
+}
+
+impl UserConfig {
+    pub fn to_toml(&self) -> String {
+        let mut text = String::new();
+        // Writing to a String can't fail.
+        _ = self.write_toml(&mut text);
+        text
+    }
+
+    fn write_toml(&self, text: &mut String) -> std::fmt::Result {
+        writeln!(text, "# WGPU Sandbox user settings. This file is rewritten when the settings change.")?;
+        writeln!(text)?;
+        writeln!(text, "[input]")?;
+        writeln!(text, "move_speed_index = {}", self.move_speed_index)?;
+        writeln!(text)?;
+        writeln!(text, "[mouse]")?;
+        writeln!(text, "sensitivity = {}", self.mouse.sensitivity)?;
+        writeln!(text, "invert_y = {}", self.mouse.invert_y)?;
+        writeln!(text, "smoothing = \"{:?}\"", self.mouse.smoothing_mode)?;
+        writeln!(text, "halting = {}", self.mouse.halting)?;
+        writeln!(text, "acceleration = {}", self.mouse.accel_curve.acceleration)?;
+        writeln!(text, "acceleration_exponent = {}", self.mouse.accel_curve.exponent)?;
+        writeln!(text, "max_acceleration_gain = {}", self.mouse.accel_curve.max_gain)?;
+        writeln!(text)?;
+        writeln!(text, "[keybinds]")?;
+        for (name, key) in self.keybinds.entries() {
+            writeln!(text, "{name} = \"{key:?}\"")?;
+        }
+        writeln!(text)?;
+        writeln!(text, "[camera]")?;
+        writeln!(text, "fov = {}", self.fov.to_degrees())?;
+        writeln!(text)?;
+        writeln!(text, "[video]")?;
+        writeln!(text, "present_mode = \"{:?}\"", self.present_mode)?;
+        writeln!(text, "msaa_samples = {}", self.msaa_samples)?;
+        writeln!(text, "texture_filtering = \"{}\"", self.texture_filtering.name())?;
+        writeln!(text, "width = {}", self.window_size.0)?;
+        writeln!(text, "height = {}", self.window_size.1)?;
+        writeln!(text, "fullscreen = {}", self.fullscreen)?;
+        for (index, preset) in self.speed_presets.iter().enumerate() {
+            writeln!(text)?;
+            writeln!(text, "[movement.{index}]")?;
+            writeln!(text, "speed = {}", preset.speed)?;
+            writeln!(text, "acceleration = {}", preset.acceleration)?;
+            writeln!(text, "deceleration = {}", preset.deceleration)?;
+            writeln!(text, "mode = \"{}\"", move_type_name(preset.move_type))?;
+        }
+        for (slot, pose) in self.bookmarks.iter() {
+            writeln!(text)?;
+            writeln!(text, "[bookmark.{slot}]")?;
+            writeln!(text, "x = {}", pose.position.x)?;
+            writeln!(text, "y = {}", pose.position.y)?;
+            writeln!(text, "z = {}", pose.position.z)?;
+            writeln!(text, "pitch = {}", pose.rotation.x.to_degrees())?;
+            writeln!(text, "yaw = {}", pose.rotation.y.to_degrees())?;
+        }
+        Ok(())
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, UserConfigError> {
+        let mut values = HashMap::new();
+        let mut section = String::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                section = name.trim().to_owned();
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or(UserConfigError::Syntax(index + 1))?;
+            values.insert(format!("{section}.{}", key.trim()), value.trim().to_owned());
+        }
+
+        let mut config = Self::default();
+        let mut speed_presets = BTreeMap::new();
+        let mut bookmarks = BTreeMap::new();
+        // The legacy `input.*` keys are applied first, so that the `mouse.*` keys that replaced them win.
+        let mut values = values.into_iter().collect::<Vec<_>>();
+        values.sort_by(|(a, _), (b, _)| (!a.starts_with("input."), a).cmp(&(!b.starts_with("input."), b)));
+        for (key, value) in values.iter() {
+            let value = value.as_str();
+            match key.as_str() {
+                "input.mouse_smoothing" => {
+                    config.mouse.smoothing_mode = if parse_value(key, value)? { SmoothingMode::Average } else { SmoothingMode::Off };
+                }
+                "input.mouse_halting" | "mouse.halting" => config.mouse.halting = parse_value(key, value)?,
+                "mouse.sensitivity" => config.mouse.sensitivity = parse_value(key, value)?,
+                "mouse.invert_y" => config.mouse.invert_y = parse_value(key, value)?,
+                "mouse.smoothing" => {
+                    config.mouse.smoothing_mode = SmoothingMode::from_name(parse_string(key, value)?)
+                        .ok_or_else(|| invalid(key, value))?;
+                }
+                "mouse.acceleration" => config.mouse.accel_curve.acceleration = parse_value(key, value)?,
+                "mouse.acceleration_exponent" => config.mouse.accel_curve.exponent = parse_value(key, value)?,
+                "mouse.max_acceleration_gain" => config.mouse.accel_curve.max_gain = parse_value(key, value)?,
+                "input.move_speed_index" => config.move_speed_index = parse_value(key, value)?,
+                "camera.fov" => config.fov = parse_value::<f32>(key, value)?.to_radians(),
+                "video.present_mode" => {
+                    let name = parse_string(key, value)?;
+                    config.present_mode = PRESENT_MODES.iter()
+                        .copied()
+                        .find(|mode| format!("{mode:?}") == name)
+                        .ok_or_else(|| invalid(key, value))?;
+                }
+                "video.msaa_samples" => config.msaa_samples = parse_value(key, value)?,
+                "video.texture_filtering" => {
+                    config.texture_filtering = TextureFiltering::from_name(parse_string(key, value)?)
+                        .ok_or_else(|| invalid(key, value))?;
+                }
+                "video.width" => config.window_size.0 = parse_value(key, value)?,
+                "video.height" => config.window_size.1 = parse_value(key, value)?,
+                "video.fullscreen" => config.fullscreen = parse_value(key, value)?,
+                _ if key.starts_with("movement.") => parse_speed_preset(&mut speed_presets, key, value)?,
+                _ if key.starts_with("bookmark.") => parse_bookmark(&mut bookmarks, key, value)?,
+                _ => match key.strip_prefix("keybinds.").and_then(|name| config.keybinds.entry_mut(name)) {
+                    Some(binding) => *binding = parse_key(parse_string(key, value)?).ok_or_else(|| invalid(key, value))?,
+                    None => log::warn!("Unknown user config key: {key}"),
+                },
+            }
+        }
+        if !speed_presets.is_empty() {
+            config.speed_presets = speed_presets.into_values().collect();
+        }
+        for (slot, pose) in bookmarks {
+            config.bookmarks.set(slot, pose);
+        }
+        Ok(config)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), UserConfigError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, self.to_toml())?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, UserConfigError> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    /// Loads the config, or returns the defaults if the file doesn't exist or can't be read.
+    pub fn load_or_default<P: AsRef<Path>>(path: P) -> Self {
+        match Self::load(&path) {
+            Ok(config) => config,
+            Err(UserConfigError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(err) => {
+                log::error!("Failed to load user config {:?}, using the defaults: {err}", path.as_ref());
+                Self::default()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec2;
+
+    use crate::camera::MoveType;
+    use crate::mouse_settings::AccelCurve;
+
+    use super::*;
+
+    #[test]
+    fn user_config_round_trip() {
+        let config = UserConfig {
+            mouse: MouseSettings {
+                sensitivity: 1.5,
+                invert_y: true,
+                smoothing_mode: SmoothingMode::Average,
+                accel_curve: AccelCurve { acceleration: 0.25, exponent: 1.5, max_gain: 3.0 },
+                ..Default::default()
+            },
+            move_speed_index: 2,
+            keybinds: Keybinds { forward: KeyCode::ArrowUp, ..Default::default() },
+            fov: 90f32.to_radians(),
+            present_mode: wgpu::PresentMode::Mailbox,
+            msaa_samples: 4,
+            texture_filtering: TextureFiltering::Anisotropic(8),
+            window_size: (1920, 1080),
+            fullscreen: true,
+            ..Default::default()
+        };
+        let read = UserConfig::from_toml(&config.to_toml()).unwrap();
+        assert_eq!(read.keybinds, config.keybinds);
+        assert!((read.fov - config.fov).abs() < 1e-5);
+        assert_eq!(UserConfig { fov: config.fov, ..read }, config);
+
+        // Missing keys keep their defaults, unknown ones are ignored.
+        let partial = UserConfig::from_toml("# comment\n[input]\nmouse_halting = true\nunknown = 5\n").unwrap();
+        let halting = MouseSettings { halting: true, ..Default::default() };
+        assert_eq!(partial, UserConfig { mouse: halting, ..Default::default() });
+        let old_smoothing = UserConfig::from_toml("[input]\nmouse_smoothing = true\n").unwrap();
+        assert_eq!(old_smoothing.mouse.smoothing_mode, SmoothingMode::Average);
+        // The `[mouse]` keys override the legacy ones, wherever they are in the file.
+        for text in [
+            "[mouse]\nsmoothing = \"Live\"\nhalting = false\n[input]\nmouse_smoothing = true\nmouse_halting = true\n",
+            "[input]\nmouse_smoothing = true\nmouse_halting = true\n[mouse]\nsmoothing = \"Live\"\nhalting = false\n",
+        ] {
+            let both = UserConfig::from_toml(text).unwrap();
+            assert_eq!((both.mouse.smoothing_mode, both.mouse.halting), (SmoothingMode::Live, false));
+        }
+        assert!(matches!(UserConfig::from_toml("[mouse]\nsmoothing = \"Jittery\""), Err(UserConfigError::InvalidValue { .. })));
+        assert!(matches!(UserConfig::from_toml("[input]\nmouse_halting"), Err(UserConfigError::Syntax(2))));
+        assert!(matches!(UserConfig::from_toml("[keybinds]\njump = \"Nope\""), Err(UserConfigError::InvalidValue { .. })));
+        assert!(matches!(UserConfig::from_toml("[video]\nwidth = -3"), Err(UserConfigError::InvalidValue { .. })));
+    }
+
+    #[test]
+    fn speed_presets_test() {
+        let config = UserConfig {
+            speed_presets: vec![
+                SpeedPreset::new(1.0),
+                SpeedPreset { acceleration: 0.0, move_type: MoveType::Free, ..SpeedPreset::new(30.0) },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(UserConfig::from_toml(&config.to_toml()).unwrap().speed_presets, config.speed_presets);
+
+        // Sorted by index, with the missing keys left at the defaults.
+        let read = UserConfig::from_toml("[movement.5]\nspeed = 20\n[movement.1]\nmode = \"Free\"\n").unwrap();
+        assert_eq!(read.speed_presets, [
+            SpeedPreset { move_type: MoveType::Free, ..Default::default() },
+            SpeedPreset { speed: 20.0, ..Default::default() },
+        ]);
+        assert_eq!(UserConfig::from_toml("").unwrap().speed_presets, SpeedPreset::defaults());
+        assert!(matches!(UserConfig::from_toml("[movement.fast]\nspeed = 2"), Err(UserConfigError::InvalidValue { .. })));
+        assert!(matches!(UserConfig::from_toml("[movement.0]\nmode = \"Sideways\""), Err(UserConfigError::InvalidValue { .. })));
+    }
+
+    #[test]
+    fn bookmarks_test() {
+        let mut config = UserConfig::default();
+        let pose = CameraKeyframe { position: Vec3::new(12.5, 40.0, -3.0), rotation: vec2(-0.25, 1.5) };
+        config.bookmarks.set(2, pose);
+        config.bookmarks.set(9, CameraKeyframe { position: Vec3::ONE, rotation: vec2(0.0, 0.0) });
+        let read = UserConfig::from_toml(&config.to_toml()).unwrap();
+        assert_eq!(read.bookmarks.iter().map(|(slot, _)| slot).collect::<Vec<_>>(), [2, 9]);
+        let read_pose = read.bookmarks.get(2).unwrap();
+        assert_eq!(read_pose.position, pose.position);
+        assert!(read_pose.rotation.abs_diff_eq(pose.rotation, 1e-5));
+
+        // Missing keys are zero.
+        let partial = UserConfig::from_toml("[bookmark.4]\ny = 10\n").unwrap();
+        assert_eq!(partial.bookmarks.get(4).unwrap().position, Vec3::new(0.0, 10.0, 0.0));
+        assert!(matches!(UserConfig::from_toml("[bookmark.10]\nx = 1"), Err(UserConfigError::InvalidValue { .. })));
+        assert!(matches!(UserConfig::from_toml("[bookmark.1]\nx = far"), Err(UserConfigError::InvalidValue { .. })));
+    }
+}
@@ -0,0 +1,194 @@
+use glam::*;
+
+use crate::rendering::raytrace::RaytraceChunk;
+
+/// An inclusive box of voxel cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Selection {
+    pub min: IVec3,
+    pub max: IVec3,
+}
+
+impl Selection {
+    /// The selection between two opposite corners, in any order.
+    pub fn from_corners(a: IVec3, b: IVec3) -> Self {
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    /// The number of cells on each axis.
+    pub fn size(&self) -> IVec3 {
+        self.max - self.min + IVec3::ONE
+    }
+
+    pub fn contains(&self, cell: IVec3) -> bool {
+        cell.cmpge(self.min).all() && cell.cmple(self.max).all()
+    }
+
+    /// Every cell in the selection, X first, then Z, then Y.
+    pub fn cells(&self) -> impl Iterator<Item = IVec3> {
+        let Self { min, max } = *self;
+        (min.y..=max.y).flat_map(move |y| {
+            (min.z..=max.z).flat_map(move |z| {
+                (min.x..=max.x).map(move |x| ivec3(x, y, z))
+            })
+        })
+    }
+
+    /// The world space bounds of the selected cells.
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        (self.min.as_vec3(), (self.max + IVec3::ONE).as_vec3())
+    }
+}
+
+/// The size of the edit tools that work on an area, such as the sculpt brushes (see [super::sculpt]).
+/// Ctrl+BracketLeft and Ctrl+BracketRight resize it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Brush {
+    /// The distance from the center to the edge, in blocks.
+    pub radius: i32,
+}
+
+impl Brush {
+    pub const MIN_RADIUS: i32 = 1;
+    pub const MAX_RADIUS: i32 = 16;
+    pub const DEFAULT: Self = Self { radius: 4 };
+
+    pub fn grown(self) -> Self {
+        Self { radius: (self.radius + 1).min(Self::MAX_RADIUS) }
+    }
+
+    pub fn shrunk(self) -> Self {
+        Self { radius: (self.radius - 1).max(Self::MIN_RADIUS) }
+    }
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// A copied box of block IDs that can be pasted elsewhere.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClipboardVolume {
+    size: IVec3,
+    blocks: Box<[u32]>,
+}
+
+impl ClipboardVolume {
+    /// An empty (all air) volume.
+    pub fn new(size: IVec3) -> Self {
+        assert!(size.cmpgt(IVec3::ZERO).all(), "Volume size must be positive.");
+        Self {
+            size,
+            blocks: vec![0; (size.x * size.y * size.z) as usize].into_boxed_slice(),
+        }
+    }
+
+    pub fn size(&self) -> IVec3 {
+        self.size
+    }
+
+    fn index(&self, x: i32, y: i32, z: i32) -> Option<usize> {
+        let pos = ivec3(x, y, z);
+        if pos.cmplt(IVec3::ZERO).any() || pos.cmpge(self.size).any() {
+            return None;
+        }
+        Some(((y * self.size.z + z) * self.size.x + x) as usize)
+    }
+
+    /// Returns `0` outside of the volume.
+    pub fn get(&self, x: i32, y: i32, z: i32) -> u32 {
+        self.index(x, y, z).map(|index| self.blocks[index]).unwrap_or(0)
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, z: i32, id: u32) {
+        if let Some(index) = self.index(x, y, z) {
+            self.blocks[index] = id;
+        }
+    }
+
+    /// The blocks in the volume, X first, then Z, then Y.
+    pub fn blocks(&self) -> &[u32] {
+        &self.blocks
+    }
+
+    /// Copies the selected blocks. Cells outside of the chunk are copied as air.
+    pub fn copy(chunk: &RaytraceChunk, selection: Selection) -> Self {
+        let mut volume = Self::new(selection.size());
+        for cell in selection.cells() {
+            let local = cell - selection.min;
+            volume.set(local.x, local.y, local.z, chunk.get(cell.x, cell.y, cell.z));
+        }
+        volume
+    }
+
+    /// Copies the selected blocks and then removes them from the chunk.
+    pub fn cut(chunk: &mut RaytraceChunk, selection: Selection) -> Self {
+        let volume = Self::copy(chunk, selection);
+        for cell in selection.cells() {
+            chunk.set(cell.x, cell.y, cell.z, 0);
+        }
+        volume
+    }
+
+    /// Writes the volume into the chunk with its minimum corner at `origin`. Air is only written when
+    /// `include_air` is `true`, otherwise the existing blocks show through. Cells outside of the chunk are skipped.
+    pub fn paste(&self, chunk: &mut RaytraceChunk, origin: IVec3, include_air: bool) {
+        for local in Selection::from_corners(IVec3::ZERO, self.size - IVec3::ONE).cells() {
+            let id = self.get(local.x, local.y, local.z);
+            if id != 0 || include_air {
+                let cell = origin + local;
+                chunk.set(cell.x, cell.y, cell.z, id);
+            }
+        }
+    }
+
+    /// Rotates the volume by 90 degrees clockwise around the Y axis (when viewed from above).
+    pub fn rotated_y(&self) -> Self {
+        let mut rotated = Self::new(ivec3(self.size.z, self.size.y, self.size.x));
+        for local in Selection::from_corners(IVec3::ZERO, self.size - IVec3::ONE).cells() {
+            rotated.set(self.size.z - 1 - local.z, local.y, local.x, self.get(local.x, local.y, local.z));
+        }
+        rotated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copy_rotate_paste_test() {
+        let mut chunk = RaytraceChunk::new();
+        // An L shape: a 3 long bar along X with a block on top of one end.
+        chunk.set(10, 0, 10, 1);
+        chunk.set(11, 0, 10, 2);
+        chunk.set(12, 0, 10, 3);
+        chunk.set(12, 1, 10, 4);
+        let selection = Selection::from_corners(ivec3(12, 1, 10), ivec3(10, 0, 10));
+        assert_eq!(selection.size(), ivec3(3, 2, 1));
+        let volume = ClipboardVolume::cut(&mut chunk, selection);
+        assert!(selection.cells().all(|cell| chunk.get(cell.x, cell.y, cell.z) == 0));
+        assert_eq!(volume.get(2, 1, 0), 4);
+
+        let rotated = volume.rotated_y();
+        assert_eq!(rotated.size(), ivec3(1, 2, 3));
+        // The bar now runs along Z.
+        assert_eq!((0..3).map(|z| rotated.get(0, 0, z)).collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(rotated.rotated_y().rotated_y().rotated_y(), volume);
+
+        rotated.paste(&mut chunk, ivec3(20, 5, 20), false);
+        assert_eq!(chunk.get(20, 5, 22), 3);
+        assert_eq!(chunk.get(20, 6, 22), 4);
+        // Air isn't pasted unless asked to.
+        chunk.set(20, 6, 20, 9);
+        rotated.paste(&mut chunk, ivec3(20, 5, 20), false);
+        assert_eq!(chunk.get(20, 6, 20), 9);
+        rotated.paste(&mut chunk, ivec3(20, 5, 20), true);
+        assert_eq!(chunk.get(20, 6, 20), 0);
+    }
+}
@@ -1,6 +1,238 @@
+use glam::*;
+
+use crate::model::loader::MeshData;
+use crate::rendering::raytrace::{BlockFaces, Material, RaytraceChunk};
+
+use super::vertex::Vertex;
+
+/*
+Greedy meshing of the raytracer's chunk for the rasterized view (see [crate::rendering::raster]).
+
+Every face between a block and a cell that doesn't hide it becomes part of a 2D mask, one mask per slice
+of the chunk and face direction. The masks are then covered with as few rectangles as possible: starting
+at the first uncovered face, a rectangle grows along the first axis while the block stays the same, then
+along the second axis while every face of the next row matches. Each rectangle becomes one quad.
+
+The vertices are in voxel space, the same space that the raytracer traces, so the mesh is drawn without a
+world transform. The vertex color is the color of the block's material, and the texture array layer comes
+from the block's [BlockFaces], picked by the direction that the quad faces. The texture coordinates are the
+voxel space position on the quad's plane, so the texture repeats once per block and lines up across quads.
+The quads of translucent blocks come after all of the opaque ones, so that they are blended over what's
+behind them.
+*/
 
 pub struct VoxelMesh {
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
     num_indices: u32,
-}
\ No newline at end of file
+}
+
+/// The block at `cell` in blocks laid out like [RaytraceChunk::blocks]. Cells outside of the chunk are air.
+fn block_at(blocks: &[u32], cell: IVec3) -> u32 {
+    RaytraceChunk::index(cell.x, cell.y, cell.z).map_or(0, |index| blocks[index])
+}
+
+fn material(materials: &[Material], id: u32) -> Material {
+    materials.get(id as usize).or(materials.last()).copied().unwrap_or(Material::DEFAULT)
+}
+
+fn block_faces(faces: &[BlockFaces], id: u32) -> BlockFaces {
+    faces.get(id as usize).or(faces.last()).copied().unwrap_or_default()
+}
+
+/// Whether the face of block `id` towards `neighbor` is visible. Translucent blocks (like water) only
+/// hide the faces of the same block, so the ground under them is still meshed.
+fn face_visible(materials: &[Material], id: u32, neighbor: u32) -> bool {
+    id != 0 && neighbor != id && (neighbor == 0 || material(materials, neighbor).is_translucent())
+}
+
+/// The vertex color of block `id`. Translucent blocks are as opaque as they are in the raytracer.
+fn block_color(materials: &[Material], id: u32) -> [u8; 4] {
+    let material = material(materials, id);
+    let color = material.color.clamp(Vec3::ZERO, Vec3::ONE) * 255.0;
+    let alpha = (1.0 - material.translucency).clamp(0.0, 1.0) * 255.0;
+    [color.x as u8, color.y as u8, color.z as u8, alpha as u8]
+}
+
+/// Meshes the faces of `blocks` (in the layout of [crate::rendering::raytrace::RaytraceChunk::blocks])
+/// with one quad per rectangle of matching faces. `materials` and `faces` are indexed by block ID, IDs
+/// beyond them use the last entry.
+pub fn greedy_mesh(blocks: &[u32], materials: &[Material], faces: &[BlockFaces]) -> MeshData {
+    let mut mesh = MeshData::default();
+    let mut translucent = MeshData::default();
+    let size = RaytraceChunk::SIZE as usize;
+    let mut mask = vec![0u32; size * size];
+    for axis in 0..3 {
+        // The other two axes, in the order that makes (u x v) point along +axis.
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        for positive in [false, true] {
+            let mut normal = IVec3::ZERO;
+            normal[axis] = if positive { 1 } else { -1 };
+            for layer in 0..RaytraceChunk::SIZE {
+                for b in 0..RaytraceChunk::SIZE {
+                    for a in 0..RaytraceChunk::SIZE {
+                        let mut cell = IVec3::ZERO;
+                        cell[axis] = layer;
+                        cell[u] = a;
+                        cell[v] = b;
+                        let id = block_at(blocks, cell);
+                        let visible = face_visible(materials, id, block_at(blocks, cell + normal));
+                        mask[(b * RaytraceChunk::SIZE + a) as usize] = if visible { id } else { 0 };
+                    }
+                }
+                // The plane that the faces of this layer are on.
+                let depth = (layer + positive as i32) as f32;
+                for b in 0..size {
+                    let mut a = 0;
+                    while a < size {
+                        let id = mask[b * size + a];
+                        if id == 0 {
+                            a += 1;
+                            continue;
+                        }
+                        let width = mask[b * size + a..(b + 1) * size].iter().take_while(|&&other| other == id).count();
+                        let height = (b..size)
+                            .take_while(|&row| mask[row * size + a..row * size + a + width].iter().all(|&other| other == id))
+                            .count();
+                        for row in b..b + height {
+                            mask[row * size + a..row * size + a + width].fill(0);
+                        }
+                        let target = if material(materials, id).is_translucent() { &mut translucent } else { &mut mesh };
+                        let texture = block_faces(faces, id).layer(normal);
+                        push_quad(target, axis, normal, depth, (a, b), (width, height), texture, block_color(materials, id));
+                        a += width;
+                    }
+                }
+            }
+        }
+    }
+    let base = mesh.vertices.len() as u32;
+    mesh.vertices.append(&mut translucent.vertices);
+    mesh.indices.extend(translucent.indices.iter().map(|index| base + index));
+    mesh
+}
+
+/// The texture coordinates of `position` on a face along `axis`. Faces on the sides are upright, with the
+/// top of the texture towards +Y.
+fn face_uv(position: Vec3, axis: usize) -> Vec2 {
+    match axis {
+        0 => vec2(position.z, -position.y),
+        1 => vec2(position.x, position.z),
+        _ => vec2(position.x, -position.y),
+    }
+}
+
+/// Pushes the quad of the rectangle at `start` with `size` (on the two axes after `axis`), counter-clockwise
+/// when seen from the side that `normal` points to. `texture` is the layer in the texture array.
+#[allow(clippy::too_many_arguments)]
+fn push_quad(
+    mesh: &mut MeshData,
+    axis: usize,
+    normal: IVec3,
+    depth: f32,
+    start: (usize, usize),
+    size: (usize, usize),
+    texture: u32,
+    color: [u8; 4],
+) {
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+    let corner = |a: usize, b: usize| {
+        let mut position = Vec3::ZERO;
+        position[axis] = depth;
+        position[u] = a as f32;
+        position[v] = b as f32;
+        Vertex::new(position, face_uv(position, axis), texture)
+            .with_normal(normal.as_vec3())
+            .with_color(color)
+    };
+    let (a0, b0) = start;
+    let (a1, b1) = (start.0 + size.0, start.1 + size.1);
+    let base = mesh.vertices.len() as u32;
+    mesh.vertices.extend([corner(a0, b0), corner(a1, b0), corner(a1, b1), corner(a0, b1)]);
+    let indices = if normal[axis] > 0 {
+        [0, 1, 2, 0, 2, 3]
+    } else {
+        [0, 2, 1, 0, 3, 2]
+    };
+    mesh.indices.extend(indices.map(|index| base + index));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocks_with(cells: &[(IVec3, u32)]) -> Vec<u32> {
+        let mut blocks = vec![0u32; (RaytraceChunk::SIZE * RaytraceChunk::SIZE * RaytraceChunk::SIZE) as usize];
+        for &(cell, id) in cells {
+            blocks[RaytraceChunk::index(cell.x, cell.y, cell.z).unwrap()] = id;
+        }
+        blocks
+    }
+
+    fn quad_count(mesh: &MeshData) -> usize {
+        assert_eq!(mesh.vertices.len() * 6, mesh.indices.len() * 4);
+        mesh.vertices.len() / 4
+    }
+
+    #[test]
+    fn greedy_mesh_test() {
+        let materials = [Material::DEFAULT; 4];
+        assert_eq!(quad_count(&greedy_mesh(&blocks_with(&[]), &materials, &[])), 0);
+        // A single block has one quad per side, facing outwards.
+        let mesh = greedy_mesh(&blocks_with(&[(ivec3(1, 2, 3), 1)]), &materials, &[]);
+        assert_eq!(quad_count(&mesh), 6);
+        let center = vec3(1.5, 2.5, 3.5);
+        for triangle in mesh.indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize].position);
+            let winding = (b - a).cross(c - a).normalize();
+            assert_eq!(winding, mesh.vertices[triangle[0] as usize].normal);
+            assert!(winding.dot(a - center) > 0.0);
+        }
+        // A row of the same block is merged, different blocks aren't.
+        let row = (0..8).map(|x| (ivec3(x, 0, 0), 1)).collect::<Vec<_>>();
+        assert_eq!(quad_count(&greedy_mesh(&blocks_with(&row), &materials, &[])), 6);
+        let mixed = (0..8).map(|x| (ivec3(x, 0, 0), 1 + (x as u32 & 1))).collect::<Vec<_>>();
+        assert_eq!(quad_count(&greedy_mesh(&blocks_with(&mixed), &materials, &[])), 8 * 4 + 2);
+        // A full layer is one quad on top and one below, plus the four sides.
+        let layer = (0..RaytraceChunk::SIZE).flat_map(|z| (0..RaytraceChunk::SIZE).map(move |x| (ivec3(x, 5, z), 3))).collect::<Vec<_>>();
+        assert_eq!(quad_count(&greedy_mesh(&blocks_with(&layer), &materials, &[])), 6);
+        // The ground under water is meshed, the faces between water blocks aren't.
+        let mut materials = materials;
+        materials[2] = Material::WATER;
+        let pond = [(ivec3(0, 0, 0), 1), (ivec3(0, 1, 0), 2), (ivec3(0, 2, 0), 2)];
+        let mesh = greedy_mesh(&blocks_with(&pond), &materials, &[]);
+        assert_eq!(quad_count(&mesh), 6 + 5);
+        assert!(mesh.vertices[..24].iter().all(|vertex| vertex.color[3] == 255));
+        assert!(mesh.vertices[24..].iter().all(|vertex| vertex.color[3] < 255));
+    }
+
+    #[test]
+    fn block_faces_test() {
+        let materials = [Material::DEFAULT; 3];
+        let grass = BlockFaces { top: 1, bottom: 2, side: 3 };
+        let faces = [BlockFaces::default(), grass, BlockFaces::uniform(4)];
+        let mesh = greedy_mesh(&blocks_with(&[(ivec3(1, 2, 3), 1), (ivec3(5, 5, 5), 2)]), &materials, &faces);
+        assert_eq!(quad_count(&mesh), 12);
+        for vertex in &mesh.vertices {
+            let expected = if vertex.position.x < 4.0 {
+                match vertex.normal.y {
+                    y if y > 0.5 => 1,
+                    y if y < -0.5 => 2,
+                    _ => 3,
+                }
+            } else {
+                4
+            };
+            assert_eq!(vertex.texindex, expected);
+        }
+        // The texture repeats once per block and is upright on the sides.
+        let row = (0..3).map(|x| (ivec3(x, 0, 0), 1)).collect::<Vec<_>>();
+        let mesh = greedy_mesh(&blocks_with(&row), &materials, &faces);
+        let uvs = |normal: Vec3| mesh.vertices.iter().filter(|vertex| vertex.normal == normal).map(|vertex| vertex.uv).collect::<Vec<_>>();
+        let front = uvs(Vec3::NEG_Z);
+        assert_eq!(front.iter().map(|uv| uv.x).fold(f32::MIN, f32::max), 3.0);
+        assert!(front.iter().all(|uv| uv.y == 0.0 || uv.y == -1.0));
+        let top = uvs(Vec3::Y);
+        assert!(top.iter().all(|uv| uv.y == 0.0 || uv.y == 1.0));
+    }
+}
@@ -1,2 +1,11 @@
 pub mod vertex;
-pub mod mesh;
\ No newline at end of file
+pub mod mesh;
+pub mod voxelize;
+pub mod worldgen;
+pub mod edit;
+pub mod schematic;
+pub mod picker;
+pub mod saves;
+pub mod sculpt;
+
+pub use voxelize::voxelize;
\ No newline at end of file
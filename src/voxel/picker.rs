@@ -0,0 +1,123 @@
+use glam::*;
+
+use crate::{math::ray::Ray3, rendering::raytrace::{RayHit, RaytraceChunk, Raytracer, TranslucentHits}};
+
+/// What a [Picker] ray hit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PickResult {
+    /// The block that was hit, which is where a block would be removed.
+    pub hit_cell: IVec3,
+    pub id: u32,
+    /// The cell in front of the hit face, which is where a block would be placed. `None` if that cell
+    /// is outside of the chunk, or if the ray started inside of the block.
+    pub place_cell: Option<IVec3>,
+    /// The normal of the face that was hit, or zero if the ray started inside of the block.
+    pub face_normal: IVec3,
+    pub distance: f32,
+    /// Where the ray hit the block, on the surface of `hit_cell`.
+    pub world_point: Vec3,
+}
+
+impl PickResult {
+    pub fn from_hit(ray: Ray3, hit: &RayHit) -> Self {
+        let face_normal = hit.face.map(|face| face.normal().as_ivec3()).unwrap_or(IVec3::ZERO);
+        let place_cell = hit.face
+            .map(|_| hit.coord + face_normal)
+            .filter(|cell| RaytraceChunk::index(cell.x, cell.y, cell.z).is_some());
+        let cell_min = hit.coord.as_vec3a();
+        let world_point = ray.point_on_ray(hit.distance).clamp(cell_min, cell_min + Vec3A::ONE);
+        Self {
+            hit_cell: hit.coord,
+            id: hit.id,
+            place_cell,
+            face_normal,
+            distance: hit.distance,
+            world_point: world_point.into(),
+        }
+    }
+}
+
+/// Finds the block under a ray for editing and highlighting.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Picker {
+    pub max_distance: f32,
+    /// Whether translucent blocks are picked, or what's behind them.
+    pub translucent: TranslucentHits,
+}
+
+impl Picker {
+    pub const fn new(max_distance: f32) -> Self {
+        Self {
+            max_distance,
+            translucent: TranslucentHits::Stop,
+        }
+    }
+
+    pub const fn with_translucent(mut self, translucent: TranslucentHits) -> Self {
+        self.translucent = translucent;
+        self
+    }
+
+    /// Picks against the raytracer's chunk, using its materials to find translucent blocks.
+    pub fn pick(&self, raytracer: &Raytracer, ray: Ray3) -> Option<PickResult> {
+        raytracer.raycast(ray, self.max_distance, self.translucent)
+            .map(|hit| PickResult::from_hit(ray, &hit))
+    }
+
+    /// Picks against a chunk without materials, so every block is treated as opaque.
+    pub fn pick_chunk(&self, chunk: &RaytraceChunk, ray: Ray3) -> Option<PickResult> {
+        chunk.raycast(ray, self.max_distance)
+            .map(|hit| PickResult::from_hit(ray, &hit))
+    }
+}
+
+impl Default for Picker {
+    fn default() -> Self {
+        Self::new(200.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picker_test() {
+        let mut chunk = RaytraceChunk::new();
+        chunk.set(10, 5, 10, 1);
+        let picker = Picker::default();
+
+        // Looking down onto the top of a block.
+        let ray = Ray3::new(vec3a(10.5, 20.0, 10.5), Vec3A::NEG_Y);
+        let pick = picker.pick_chunk(&chunk, ray).unwrap();
+        assert_eq!(pick.hit_cell, ivec3(10, 5, 10));
+        assert_eq!(pick.face_normal, IVec3::Y);
+        assert_eq!(pick.place_cell, Some(ivec3(10, 6, 10)));
+        assert!((pick.distance - 14.0).abs() < 1e-3);
+        assert!(pick.world_point.abs_diff_eq(vec3(10.5, 6.0, 10.5), 1e-3));
+
+        // A block on the edge of the chunk hit from outside has nowhere to place into.
+        chunk.set(0, 30, 30, 1);
+        let ray = Ray3::new(vec3a(-5.0, 30.5, 30.5), Vec3A::X);
+        let pick = picker.pick_chunk(&chunk, ray).unwrap();
+        assert_eq!(pick.hit_cell, ivec3(0, 30, 30));
+        assert_eq!(pick.face_normal, IVec3::NEG_X);
+        assert_eq!(pick.place_cell, None);
+        assert!(pick.world_point.abs_diff_eq(vec3(0.0, 30.5, 30.5), 1e-3));
+
+        // The same block hit from inside the chunk.
+        let ray = Ray3::new(vec3a(5.5, 30.5, 30.5), Vec3A::NEG_X);
+        assert_eq!(picker.pick_chunk(&chunk, ray).unwrap().place_cell, Some(ivec3(1, 30, 30)));
+
+        // Starting inside of a block hits it without a face.
+        let ray = Ray3::new(vec3a(10.5, 5.5, 10.5), Vec3A::X);
+        let pick = picker.pick_chunk(&chunk, ray).unwrap();
+        assert_eq!(pick.hit_cell, ivec3(10, 5, 10));
+        assert_eq!(pick.face_normal, IVec3::ZERO);
+        assert_eq!(pick.place_cell, None);
+
+        // Out of range.
+        let ray = Ray3::new(vec3a(10.5, 20.0, 10.5), Vec3A::NEG_Y);
+        assert_eq!(Picker::new(10.0).pick_chunk(&chunk, ray), None);
+    }
+}
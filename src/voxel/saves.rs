@@ -0,0 +1,222 @@
+use std::{fmt, path::{Path, PathBuf}, time::{Duration, SystemTime}};
+
+use crate::rendering::raytrace::RaytraceChunk;
+
+/*
+World saves are chunk files (see [RaytraceChunk::save]) in a saves directory. There are three kinds of slots:
+
+    <name>.chunk         Named slots.
+    quicksave_<n>.chunk  Numbered quicksaves. New quicksaves replace the oldest one once there are
+                         `quicksave_count` of them.
+    autosave.chunk       Written every `autosave_interval`.
+
+Saves are written to a temporary file that is renamed over the slot once it's complete, so that a
+crash during a save doesn't destroy the previous one. The blocks are copied before saving so that the
+file can be written on a background thread.
+*/
+
+const EXTENSION: &str = "chunk";
+const QUICKSAVE_PREFIX: &str = "quicksave_";
+const AUTOSAVE_NAME: &str = "autosave";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SaveSlot {
+    Named(String),
+    Quicksave(u32),
+    Autosave,
+}
+
+impl SaveSlot {
+    /// A named slot. Names may only contain ASCII letters, digits, `-` and `_`, and can't be
+    /// mistaken for a quicksave or the autosave.
+    pub fn named<S: Into<String>>(name: S) -> Option<Self> {
+        let name = name.into();
+        let valid = !name.is_empty()
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            && name != AUTOSAVE_NAME
+            && !name.starts_with(QUICKSAVE_PREFIX);
+        valid.then_some(SaveSlot::Named(name))
+    }
+
+    pub fn file_name(&self) -> String {
+        match self {
+            SaveSlot::Named(name) => format!("{name}.{EXTENSION}"),
+            SaveSlot::Quicksave(number) => format!("{QUICKSAVE_PREFIX}{number}.{EXTENSION}"),
+            SaveSlot::Autosave => format!("{AUTOSAVE_NAME}.{EXTENSION}"),
+        }
+    }
+
+    /// The inverse of [SaveSlot::file_name].
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        let stem = file_name.strip_suffix(EXTENSION)?.strip_suffix('.')?;
+        if stem == AUTOSAVE_NAME {
+            return Some(SaveSlot::Autosave);
+        }
+        if let Some(number) = stem.strip_prefix(QUICKSAVE_PREFIX) {
+            return number.parse().ok().map(SaveSlot::Quicksave);
+        }
+        SaveSlot::named(stem)
+    }
+}
+
+impl fmt::Display for SaveSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveSlot::Named(name) => write!(f, "\"{name}\""),
+            SaveSlot::Quicksave(number) => write!(f, "Quicksave {number}"),
+            SaveSlot::Autosave => write!(f, "Autosave"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveInfo {
+    pub slot: SaveSlot,
+    pub path: PathBuf,
+    pub modified: SystemTime,
+}
+
+pub struct WorldSaves {
+    dir: PathBuf,
+    pub quicksave_count: u32,
+    /// `None` disables autosaving.
+    pub autosave_interval: Option<Duration>,
+    since_autosave: Duration,
+}
+
+impl WorldSaves {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            quicksave_count: 5,
+            autosave_interval: Some(Duration::from_secs(5 * 60)),
+            since_autosave: Duration::ZERO,
+        }
+    }
+
+    pub fn with_autosave_interval(mut self, interval: Option<Duration>) -> Self {
+        self.autosave_interval = interval;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn path(&self, slot: &SaveSlot) -> PathBuf {
+        self.dir.join(slot.file_name())
+    }
+
+    /// Every save in the directory, newest first. A missing directory has no saves.
+    pub fn list(&self) -> std::io::Result<Vec<SaveInfo>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        let mut saves = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let Some(slot) = entry.file_name().to_str().and_then(SaveSlot::from_file_name) else {
+                continue;
+            };
+            saves.push(SaveInfo {
+                slot,
+                path: entry.path(),
+                modified: entry.metadata()?.modified()?,
+            });
+        }
+        saves.sort_by_key(|save| std::cmp::Reverse(save.modified));
+        Ok(saves)
+    }
+
+    /// The most recent save of any kind.
+    pub fn latest(&self) -> std::io::Result<Option<SaveInfo>> {
+        Ok(self.list()?.into_iter().next())
+    }
+
+    /// The first unused quicksave slot, or the oldest quicksave once they are all used.
+    pub fn next_quicksave(&self) -> std::io::Result<SaveSlot> {
+        let count = self.quicksave_count.max(1);
+        let quicksaves = self.list()?.into_iter()
+            .filter(|save| matches!(save.slot, SaveSlot::Quicksave(number) if (1..=count).contains(&number)))
+            .collect::<Vec<_>>();
+        let unused = (1..=count).find(|&number| !quicksaves.iter().any(|save| save.slot == SaveSlot::Quicksave(number)));
+        Ok(match (unused, quicksaves.last()) {
+            (Some(number), _) => SaveSlot::Quicksave(number),
+            (None, Some(oldest)) => oldest.slot.clone(),
+            (None, None) => SaveSlot::Quicksave(1),
+        })
+    }
+
+    /// Advances the autosave timer. Returns `true` when it's time to autosave.
+    pub fn update(&mut self, delta_time: Duration) -> bool {
+        let Some(interval) = self.autosave_interval else {
+            return false;
+        };
+        self.since_autosave += delta_time;
+        if self.since_autosave >= interval {
+            self.since_autosave = Duration::ZERO;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Writes a copy of the chunk's blocks to the slot. This can be called from any thread.
+    pub fn write(path: &Path, blocks: &[u32]) -> std::io::Result<()> {
+        let temp_path = path.with_extension("tmp");
+        RaytraceChunk::save_blocks(blocks, &temp_path)?;
+        std::fs::rename(&temp_path, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_slot_test() {
+        for slot in [SaveSlot::named("my_world-2").unwrap(), SaveSlot::Quicksave(3), SaveSlot::Autosave] {
+            assert_eq!(SaveSlot::from_file_name(&slot.file_name()), Some(slot));
+        }
+        assert_eq!(SaveSlot::named("../escape"), None);
+        assert_eq!(SaveSlot::named("autosave"), None);
+        assert_eq!(SaveSlot::named("quicksave_1"), None);
+        assert_eq!(SaveSlot::from_file_name("notes.txt"), None);
+        assert_eq!(SaveSlot::from_file_name("quicksave_x.chunk"), None);
+    }
+
+    #[test]
+    fn world_saves_test() {
+        let dir = std::env::temp_dir().join(format!("wgpu_learn_saves_test_{}", std::process::id()));
+        _ = std::fs::remove_dir_all(&dir);
+        let mut saves = WorldSaves::new(&dir).with_autosave_interval(Some(Duration::from_secs(60)));
+        saves.quicksave_count = 2;
+        assert_eq!(saves.list().unwrap(), Vec::new());
+        assert_eq!(saves.next_quicksave().unwrap(), SaveSlot::Quicksave(1));
+
+        let mut chunk = RaytraceChunk::new();
+        chunk.set(1, 2, 3, 7);
+        // Quicksaves rotate through their slots, replacing the oldest one.
+        for expected in [1, 2, 1] {
+            let slot = saves.next_quicksave().unwrap();
+            assert_eq!(slot, SaveSlot::Quicksave(expected));
+            WorldSaves::write(&saves.path(&slot), chunk.blocks()).unwrap();
+            // Make sure that the modification times differ.
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        let latest = saves.latest().unwrap().unwrap();
+        assert_eq!(latest.slot, SaveSlot::Quicksave(1));
+        assert_eq!(saves.list().unwrap().len(), 2);
+
+        let mut loaded = RaytraceChunk::new();
+        loaded.load(&latest.path).unwrap();
+        assert_eq!(loaded.get(1, 2, 3), 7);
+
+        assert!(!saves.update(Duration::from_secs(59)));
+        assert!(saves.update(Duration::from_secs(1)));
+        assert!(!saves.update(Duration::from_secs(1)));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+use std::{io::{BufReader, BufWriter, Read, Write}, path::Path};
+
+use glam::*;
+
+use super::edit::ClipboardVolume;
+
+/*
+Schematic files store a copied volume so that it can be pasted into other chunks.
+All values are big endian.
+
+    magic: b"WLSC", version: u32,
+    size: (u32, u32, u32),
+    palette_len: u16, palette: [u32] (block IDs),
+    cells: [u16] (palette indices, X first, then Z, then Y)
+*/
+
+const SCHEMATIC_MAGIC: [u8; 4] = *b"WLSC";
+const SCHEMATIC_VERSION: u32 = 1;
+/// The largest schematic (on each axis) that can be read, to reject corrupt files before allocating.
+pub const MAX_SCHEMATIC_SIZE: u32 = 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SchematicError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Not a schematic.")]
+    InvalidMagic,
+    #[error("Unsupported schematic version {0}, expected {SCHEMATIC_VERSION}.")]
+    UnsupportedVersion(u32),
+    #[error("Invalid schematic size {0}x{1}x{2}.")]
+    InvalidSize(u32, u32, u32),
+    #[error("Too many distinct blocks for a schematic ({0}).")]
+    PaletteTooLarge(usize),
+    #[error("Palette index {0} is out of range.")]
+    InvalidPaletteIndex(u16),
+}
+
+/// The distinct block IDs in the volume, in the order that they first appear.
+pub fn palette(volume: &ClipboardVolume) -> Vec<u32> {
+    let mut palette = Vec::new();
+    for &id in volume.blocks() {
+        if !palette.contains(&id) {
+            palette.push(id);
+        }
+    }
+    palette
+}
+
+pub fn write_schematic<W: Write>(volume: &ClipboardVolume, writer: &mut W) -> Result<(), SchematicError> {
+    let palette = palette(volume);
+    if palette.len() > u16::MAX as usize {
+        return Err(SchematicError::PaletteTooLarge(palette.len()));
+    }
+    writer.write_all(&SCHEMATIC_MAGIC)?;
+    writer.write_all(&SCHEMATIC_VERSION.to_be_bytes())?;
+    for axis in volume.size().to_array() {
+        writer.write_all(&(axis as u32).to_be_bytes())?;
+    }
+    writer.write_all(&(palette.len() as u16).to_be_bytes())?;
+    for id in palette.iter() {
+        writer.write_all(&id.to_be_bytes())?;
+    }
+    for id in volume.blocks() {
+        let index = palette.iter().position(|entry| entry == id).unwrap_or_default() as u16;
+        writer.write_all(&index.to_be_bytes())?;
+    }
+    Ok(())
+}
+
+pub fn read_schematic<R: Read>(reader: &mut R) -> Result<ClipboardVolume, SchematicError> {
+    if read_bytes::<_, 4>(reader)? != SCHEMATIC_MAGIC {
+        return Err(SchematicError::InvalidMagic);
+    }
+    let version = read_u32(reader)?;
+    if version != SCHEMATIC_VERSION {
+        return Err(SchematicError::UnsupportedVersion(version));
+    }
+    let (x, y, z) = (read_u32(reader)?, read_u32(reader)?, read_u32(reader)?);
+    if [x, y, z].iter().any(|&axis| axis == 0 || axis > MAX_SCHEMATIC_SIZE) {
+        return Err(SchematicError::InvalidSize(x, y, z));
+    }
+    let palette_len = read_u16(reader)?;
+    let palette = (0..palette_len)
+        .map(|_| read_u32(reader))
+        .collect::<Result<Vec<_>, _>>()?;
+    let size = uvec3(x, y, z).as_ivec3();
+    let mut volume = ClipboardVolume::new(size);
+    for y in 0..size.y {
+        for z in 0..size.z {
+            for x in 0..size.x {
+                let index = read_u16(reader)?;
+                let id = palette.get(index as usize).copied().ok_or(SchematicError::InvalidPaletteIndex(index))?;
+                volume.set(x, y, z, id);
+            }
+        }
+    }
+    Ok(volume)
+}
+
+pub fn save_schematic<P: AsRef<Path>>(volume: &ClipboardVolume, path: P) -> Result<(), SchematicError> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut writer = BufWriter::new(std::fs::File::create(path)?);
+    write_schematic(volume, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn load_schematic<P: AsRef<Path>>(path: P) -> Result<ClipboardVolume, SchematicError> {
+    let mut reader = BufReader::new(std::fs::File::open(path)?);
+    read_schematic(&mut reader)
+}
+
+fn read_bytes<R: Read, const N: usize>(reader: &mut R) -> std::io::Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> std::io::Result<u16> {
+    read_bytes(reader).map(u16::from_be_bytes)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> std::io::Result<u32> {
+    read_bytes(reader).map(u32::from_be_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schematic_round_trip() {
+        let mut volume = ClipboardVolume::new(ivec3(3, 2, 4));
+        volume.set(0, 0, 0, 7);
+        volume.set(2, 1, 3, 1_000_000);
+        volume.set(1, 1, 1, 7);
+        assert_eq!(palette(&volume), [7, 0, 1_000_000]);
+        let mut bytes = Vec::new();
+        write_schematic(&volume, &mut bytes).unwrap();
+        let read = read_schematic(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read, volume);
+
+        assert!(matches!(read_schematic(&mut &b"nope"[..]), Err(SchematicError::InvalidMagic)));
+        // Point the first cell past the end of the palette.
+        let first_cell = bytes.len() - volume.blocks().len() * 2;
+        bytes[first_cell..first_cell + 2].copy_from_slice(&3u16.to_be_bytes());
+        assert!(matches!(read_schematic(&mut bytes.as_slice()), Err(SchematicError::InvalidPaletteIndex(3))));
+    }
+}
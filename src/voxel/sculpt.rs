@@ -0,0 +1,286 @@
+use std::time::Duration;
+
+use glam::IVec3;
+
+use crate::rendering::raytrace::RaytraceChunk;
+
+use super::edit::Brush;
+
+/*
+The sculpt brushes (Ctrl+H) reshape the terrain under the crosshair while the left mouse button is held.
+Every column within the [Brush] radius of the hit cell gets a weight from a smooth falloff kernel, which the
+noise (Ctrl+J) roughens. Each stroke then changes a column by at most one block, with a chance equal to its
+weight:
+- Raise adds a block on top of the column.
+- Lower removes the top block of the column.
+- Smooth moves the column towards the average height of its neighbors.
+Columns near the center change more often than those at the edge, so holding a brush grows (or digs) a
+rounded hill. The chances are rolled from a hash of the column and the stroke number, so a stroke is
+deterministic.
+
+Strokes are rate limited to one per [STROKE_INTERVAL] while the button is held, so the brushes work at the
+same speed at any frame rate.
+*/
+
+pub const STROKE_INTERVAL: Duration = Duration::from_millis(50);
+/// The most strokes that are applied in one frame, so that a long frame doesn't dig a pit.
+pub const MAX_STROKES_PER_FRAME: u32 = 4;
+/// How much the noise scales the weights, up or down.
+pub const NOISE_AMOUNT: f32 = 0.75;
+/// The distance between the noise's lattice points, in blocks.
+const NOISE_SCALE: f32 = 3.0;
+const NOISE_SEED: u32 = 0x51ED27;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SculptMode {
+    #[default]
+    Off,
+    Raise,
+    Lower,
+    Smooth,
+}
+
+impl SculptMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Raise,
+            Self::Raise => Self::Lower,
+            Self::Lower => Self::Smooth,
+            Self::Smooth => Self::Off,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Raise => "Raise",
+            Self::Lower => "Lower",
+            Self::Smooth => "Smooth",
+        }
+    }
+
+    pub fn is_active(self) -> bool {
+        self != Self::Off
+    }
+}
+
+/// A hash of the column and `seed` in `0..1`.
+fn hash(x: i32, z: i32, seed: u32) -> f32 {
+    let hash = (x as u32).wrapping_mul(0x9E3779B9) ^ (z as u32).wrapping_mul(0x85EBCA6B) ^ seed.wrapping_mul(0xC2B2AE35);
+    let hash = (hash ^ (hash >> 15)).wrapping_mul(0x2C1B3C6D);
+    (hash ^ (hash >> 12)) as f32 / u32::MAX as f32
+}
+
+/// Value noise in `-1..1`: the hashes of the surrounding lattice points, smoothly interpolated.
+fn value_noise(x: f32, z: f32) -> f32 {
+    let (x, z) = (x / NOISE_SCALE, z / NOISE_SCALE);
+    let (x0, z0) = (x.floor(), z.floor());
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (tx, tz) = (smooth(x - x0), smooth(z - z0));
+    let (x0, z0) = (x0 as i32, z0 as i32);
+    let corner = |dx: i32, dz: i32| hash(x0 + dx, z0 + dz, NOISE_SEED);
+    let top = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * tx;
+    let bottom = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * tx;
+    (top + (bottom - top) * tz) * 2.0 - 1.0
+}
+
+/// The weight of a column `distance` away from the center of a brush with `radius`, from `1.0` in the
+/// center to `0.0` at the edge.
+pub fn falloff(distance: f32, radius: f32) -> f32 {
+    let t = (distance / radius).clamp(0.0, 1.0);
+    (1.0 - t * t).powi(2)
+}
+
+/// The number of blocks in the column up to and including its highest solid block.
+fn column_height(chunk: &RaytraceChunk, x: i32, z: i32) -> i32 {
+    chunk.highest_solid_at(x, z).map_or(0, |top| top + 1)
+}
+
+pub struct Sculptor {
+    pub mode: SculptMode,
+    /// Roughens the brush with value noise.
+    pub noise: bool,
+    /// How long the brush has been held since the last stroke.
+    elapsed: Duration,
+    /// Counts the strokes, so that each one rolls different chances.
+    stroke: u32,
+}
+
+impl Sculptor {
+    pub fn new() -> Self {
+        Self {
+            mode: SculptMode::Off,
+            noise: false,
+            elapsed: Duration::ZERO,
+            stroke: 0,
+        }
+    }
+
+    /// The number of strokes to apply this frame. The first stroke happens as soon as the button is
+    /// `just_pressed`, and then one every [STROKE_INTERVAL] while it's `held`.
+    pub fn strokes(&mut self, delta_time: Duration, held: bool, just_pressed: bool) -> u32 {
+        if !self.mode.is_active() || !held {
+            self.elapsed = Duration::ZERO;
+            return 0;
+        }
+        if just_pressed {
+            self.elapsed = Duration::ZERO;
+            return 1;
+        }
+        self.elapsed += delta_time;
+        let strokes = (self.elapsed.as_nanos() / STROKE_INTERVAL.as_nanos()) as u32;
+        self.elapsed -= STROKE_INTERVAL * strokes;
+        strokes.min(MAX_STROKES_PER_FRAME)
+    }
+
+    /// The weight of every column of the chunk under a `brush` centered on the column of `center`, as
+    /// `(x, z, weight)`. Columns with no weight are left out.
+    pub fn weights(&self, center: IVec3, brush: Brush) -> Vec<(i32, i32, f32)> {
+        let radius = brush.radius as f32 + 0.5;
+        let mut weights = Vec::new();
+        for z in center.z - brush.radius..=center.z + brush.radius {
+            for x in center.x - brush.radius..=center.x + brush.radius {
+                if !(0..RaytraceChunk::SIZE).contains(&x) || !(0..RaytraceChunk::SIZE).contains(&z) {
+                    continue;
+                }
+                let distance = ((x - center.x) as f32).hypot((z - center.z) as f32);
+                let mut weight = falloff(distance, radius);
+                if self.noise {
+                    weight *= 1.0 + value_noise(x as f32, z as f32) * NOISE_AMOUNT;
+                }
+                if weight > 0.0 {
+                    weights.push((x, z, weight.min(1.0)));
+                }
+            }
+        }
+        weights
+    }
+
+    /// Applies one stroke of the brush on the columns around `center` (the cell that was hit). Raised columns
+    /// are topped with `block_id`. Returns `true` if any block changed.
+    pub fn stroke(&mut self, chunk: &mut RaytraceChunk, center: IVec3, brush: Brush, block_id: u32) -> bool {
+        let stroke = self.stroke;
+        self.stroke = self.stroke.wrapping_add(1);
+        let weights = self.weights(center, brush);
+        // Every column is compared with the heights from before the stroke, including the neighbors that
+        // smoothing averages (one column past the brush).
+        let reach = brush.radius + 1;
+        let side = (reach * 2 + 1) as usize;
+        let heights = (0..side * side)
+            .map(|index| {
+                let x = center.x - reach + (index % side) as i32;
+                let z = center.z - reach + (index / side) as i32;
+                column_height(chunk, x, z)
+            })
+            .collect::<Vec<_>>();
+        let height_at = |x: i32, z: i32| heights[((z - center.z + reach) as usize) * side + (x - center.x + reach) as usize];
+        let mut changed = false;
+        for &(x, z, weight) in &weights {
+            if hash(x, z, stroke) >= weight {
+                continue;
+            }
+            let height = height_at(x, z);
+            let target = match self.mode {
+                SculptMode::Off => height,
+                SculptMode::Raise => height + 1,
+                SculptMode::Lower => height - 1,
+                SculptMode::Smooth => {
+                    let neighbors = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                        .map(|(dx, dz)| (x + dx, z + dz))
+                        .into_iter()
+                        .filter(|&(x, z)| (0..RaytraceChunk::SIZE).contains(&x) && (0..RaytraceChunk::SIZE).contains(&z))
+                        .map(|(x, z)| height_at(x, z))
+                        .collect::<Vec<_>>();
+                    let average = neighbors.iter().sum::<i32>() as f32 / neighbors.len() as f32;
+                    height + (average.round() as i32 - height).signum()
+                }
+            };
+            if target > height && target <= RaytraceChunk::SIZE {
+                // Smoothing grows the column with its own top block.
+                let id = if height > 0 && self.mode == SculptMode::Smooth { chunk.get(x, height - 1, z) } else { block_id };
+                chunk.set(x, height, z, id);
+                changed = true;
+            } else if target < height && target >= 0 {
+                chunk.set(x, height - 1, z, 0);
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+impl Default for Sculptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::ivec3;
+
+    use super::*;
+
+    fn flat_chunk(height: i32) -> RaytraceChunk {
+        let mut chunk = RaytraceChunk::new();
+        for z in 0..RaytraceChunk::SIZE {
+            for x in 0..RaytraceChunk::SIZE {
+                for y in 0..height {
+                    chunk.set(x, y, z, 1);
+                }
+            }
+        }
+        chunk
+    }
+
+    #[test]
+    fn sculpt_test() {
+        let brush = Brush { radius: 4 };
+        let center = ivec3(32, 9, 32);
+        let mut sculptor = Sculptor { mode: SculptMode::Raise, ..Sculptor::new() };
+        let mut chunk = flat_chunk(10);
+        for _ in 0..20 {
+            sculptor.stroke(&mut chunk, center, brush, 2);
+        }
+        // A hill: the center always rises, the edges only sometimes, and nothing outside the brush.
+        assert_eq!(column_height(&chunk, 32, 32), 30);
+        assert!(column_height(&chunk, 35, 32) < 30);
+        assert_eq!(column_height(&chunk, 37, 32), 10);
+        assert_eq!(chunk.get(32, 29, 32), 2);
+
+        sculptor.mode = SculptMode::Smooth;
+        let spike = column_height(&chunk, 32, 32) - column_height(&chunk, 33, 32);
+        for _ in 0..20 {
+            sculptor.stroke(&mut chunk, center, brush, 2);
+        }
+        assert!(column_height(&chunk, 32, 32) - column_height(&chunk, 33, 32) < spike.max(1));
+
+        sculptor.mode = SculptMode::Lower;
+        let mut chunk = flat_chunk(3);
+        for _ in 0..10 {
+            sculptor.stroke(&mut chunk, center, brush, 2);
+        }
+        assert_eq!(column_height(&chunk, 32, 32), 0);
+
+        // The noise keeps the weights in range.
+        sculptor.noise = true;
+        assert!(sculptor.weights(ivec3(0, 0, 0), brush).iter().all(|&(x, z, weight)| {
+            (0..RaytraceChunk::SIZE).contains(&x) && (0..RaytraceChunk::SIZE).contains(&z) && weight > 0.0 && weight <= 1.0
+        }));
+        assert_eq!(Brush { radius: Brush::MAX_RADIUS }.grown().radius, Brush::MAX_RADIUS);
+        assert_eq!(Brush { radius: Brush::MIN_RADIUS }.shrunk().radius, Brush::MIN_RADIUS);
+    }
+
+    #[test]
+    fn stroke_rate_test() {
+        let mut sculptor = Sculptor { mode: SculptMode::Lower, ..Sculptor::new() };
+        assert_eq!(sculptor.strokes(Duration::from_millis(16), true, true), 1);
+        assert_eq!(sculptor.strokes(Duration::from_millis(40), true, false), 0);
+        assert_eq!(sculptor.strokes(Duration::from_millis(40), true, false), 1);
+        // A long frame is capped.
+        assert_eq!(sculptor.strokes(Duration::from_secs(1), true, false), MAX_STROKES_PER_FRAME);
+        assert_eq!(sculptor.strokes(Duration::from_millis(16), false, false), 0);
+        sculptor.mode = SculptMode::Off;
+        assert_eq!(sculptor.strokes(Duration::from_millis(16), true, true), 0);
+    }
+}
@@ -1,11 +1,41 @@
 use glam::*;
 
 #[repr(C)]
-#[derive(Debug, Default, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
     pub position: glam::Vec3,
     pub uv: glam::Vec2,
     pub texindex: u32,
+    /// A zero normal means that the vertex doesn't have one. The [crate::modeling::modeler::Modeler] fills
+    /// those in from the winding of the face.
+    pub normal: glam::Vec3,
+    /// RGBA that the texture is multiplied with (`Unorm8x4`, white by default).
+    pub color: [u8; 4],
+}
+
+impl Default for Vertex {
+    fn default() -> Self {
+        Self::new(Vec3::ZERO, Vec2::ZERO, 0)
+    }
+}
+
+/// Per-instance data for instanced draws (see [Vertex::instance_desc]).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceData {
+    pub model: glam::Mat4,
+}
+
+impl InstanceData {
+    pub const fn new(model: Mat4) -> Self {
+        Self {
+            model,
+        }
+    }
+
+    pub fn from_translation(translation: Vec3) -> Self {
+        Self::new(Mat4::from_translation(translation))
+    }
 }
 
 pub const fn pos(x: f32, y: f32, z: f32) -> Vec3 {
@@ -21,38 +51,61 @@ pub const fn index(i: u32) -> u32 {
 }
 
 pub const fn vert(position: glam::Vec3, uv: glam::Vec2, texindex: u32) -> Vertex {
-    Vertex {
-        position,
-        uv,
-        texindex,
-    }
+    Vertex::new(position, uv, texindex)
 }
 
 impl Vertex {
     pub const ATTRIBS: &'static [wgpu::VertexAttribute] = &wgpu::vertex_attr_array![
         0 => Float32x3,
         1 => Float32x2,
-        2 => Uint32
+        2 => Uint32,
+        3 => Float32x3,
+        4 => Unorm8x4
+    ];
+
+    /// The model matrix is passed as four column vectors following the vertex attributes.
+    pub const INSTANCE_ATTRIBS: &'static [wgpu::VertexAttribute] = &wgpu::vertex_attr_array![
+        5 => Float32x4,
+        6 => Float32x4,
+        7 => Float32x4,
+        8 => Float32x4
     ];
 
+    pub const WHITE: [u8; 4] = [255; 4];
+
     pub const PLANE_VERTICES: &'static [Self] = &[
-        vert(pos(-0.5, 0.0, -0.5), uv(0.0, 0.0), index(4)), vert(pos(0.5, 0.0, -0.5), uv(1.0, 0.0), index(4)),
-        vert(pos(-0.5, 0.0, 0.5), uv(0.0, 1.0), index(4)), vert(pos(0.5, 0.0, 0.5), uv(1.0, 1.0), index(4)),
-    ]; 
+        vert(pos(-0.5, 0.0, -0.5), uv(0.0, 0.0), index(4)).with_normal(Vec3::Y), vert(pos(0.5, 0.0, -0.5), uv(1.0, 0.0), index(4)).with_normal(Vec3::Y),
+        vert(pos(-0.5, 0.0, 0.5), uv(0.0, 1.0), index(4)).with_normal(Vec3::Y), vert(pos(0.5, 0.0, 0.5), uv(1.0, 1.0), index(4)).with_normal(Vec3::Y),
+    ];
 
     pub const PLANE_INDICES: &'static [u16] = &[
         0, 2, 1,
         2, 3, 1,
     ];
 
+    /// A white vertex without a normal.
     pub const fn new(position: Vec3, uv: Vec2, texindex: u32) -> Self {
         Self {
             position,
             uv,
             texindex,
+            normal: Vec3::ZERO,
+            color: Self::WHITE,
         }
     }
 
+    pub const fn with_normal(self, normal: Vec3) -> Self {
+        Self { normal, ..self }
+    }
+
+    pub const fn with_color(self, color: [u8; 4]) -> Self {
+        Self { color, ..self }
+    }
+
+    pub fn has_normal(&self) -> bool {
+        self.normal != Vec3::ZERO
+    }
+
     pub const fn vertex_buffer_layout() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
@@ -68,9 +121,31 @@ impl Vertex {
             attributes: Self::ATTRIBS,
         }
     }
+
+    /// Layout of the [InstanceData] buffer. Use this as the second vertex buffer.
+    pub const fn instance_desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<InstanceData>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: Self::INSTANCE_ATTRIBS,
+        }
+    }
 }
 
 #[test]
 fn glam_test() {
     // glam::Mat4::look_to_rh()
+}
+
+#[test]
+fn layout_test() {
+    // The attributes are packed without padding, and the instance attributes come after them.
+    assert_eq!(std::mem::size_of::<Vertex>(), 40);
+    let layout = Vertex::desc();
+    let end = layout.attributes.iter().map(|attribute| attribute.offset + attribute.format.size()).max();
+    assert_eq!(end, Some(layout.array_stride));
+    let last_location = Vertex::ATTRIBS.iter().map(|attribute| attribute.shader_location).max().unwrap();
+    assert!(Vertex::INSTANCE_ATTRIBS.iter().all(|attribute| attribute.shader_location > last_location));
+    assert_eq!(Vertex::default().color, Vertex::WHITE);
+    assert!(!Vertex::default().has_normal());
 }
\ No newline at end of file
@@ -0,0 +1,269 @@
+use std::collections::{HashMap, VecDeque};
+
+use glam::*;
+
+use crate::{model::loader::MeshData, modeling::modeler::Modeler, rendering::raytrace::RaytraceChunk};
+
+use super::vertex::Vertex;
+
+/// The largest resolution that fits in a [RaytraceChunk].
+pub const MAX_RESOLUTION: u32 = 64;
+
+/// Anything made of indexed triangles.
+pub trait TriangleMesh {
+    fn vertices(&self) -> &[Vertex];
+    fn indices(&self) -> &[u32];
+}
+
+impl TriangleMesh for Modeler {
+    fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+}
+
+impl TriangleMesh for MeshData {
+    fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Fill {
+    /// Only the voxels that the surface passes through.
+    #[default]
+    Shell,
+    /// The surface plus everything enclosed by it (filled with [VoxelizeOptions::fill_id]).
+    /// The mesh should be closed, otherwise the interior leaks and nothing is filled.
+    Solid,
+}
+
+#[derive(Debug, Clone)]
+pub struct VoxelizeOptions {
+    pub fill: Fill,
+    /// Maps a triangle's texture index (of its first vertex) to a block ID.
+    pub material_ids: HashMap<u32, u32>,
+    /// The block ID for triangles with an unmapped texture index.
+    pub default_id: u32,
+    /// The block ID for the interior when using [Fill::Solid].
+    pub fill_id: u32,
+}
+
+impl Default for VoxelizeOptions {
+    fn default() -> Self {
+        Self {
+            fill: Fill::Shell,
+            material_ids: HashMap::new(),
+            default_id: 1,
+            fill_id: 1,
+        }
+    }
+}
+
+impl VoxelizeOptions {
+    pub fn solid(mut self) -> Self {
+        self.fill = Fill::Solid;
+        self
+    }
+
+    pub fn with_material_id(mut self, texindex: u32, id: u32) -> Self {
+        self.material_ids.insert(texindex, id);
+        self
+    }
+}
+
+/// Rasterizes a triangle mesh into a chunk. The mesh is scaled uniformly so that its longest side
+/// spans `resolution` voxels (at most [MAX_RESOLUTION]) and placed at the chunk's origin.
+pub fn voxelize<M: TriangleMesh>(mesh: &M, resolution: u32, options: &VoxelizeOptions) -> RaytraceChunk {
+    let mut chunk = RaytraceChunk::new();
+    let vertices = mesh.vertices();
+    let indices = mesh.indices();
+    let resolution = resolution.clamp(1, MAX_RESOLUTION) as i32;
+    if vertices.is_empty() || indices.len() < 3 {
+        return chunk;
+    }
+    let (min, max) = vertices.iter().fold((Vec3::MAX, Vec3::MIN), |(min, max), vertex| {
+        (min.min(vertex.position), max.max(vertex.position))
+    });
+    let extent = (max - min).max_element();
+    let scale = if extent > 0.0 { resolution as f32 / extent } else { 1.0 };
+    let to_voxel = |position: Vec3| (position - min) * scale;
+    let last = IVec3::splat(resolution - 1);
+
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| vertices[index as usize]);
+        let id = options.material_ids.get(&a.texindex).copied().unwrap_or(options.default_id);
+        let tri = [to_voxel(a.position), to_voxel(b.position), to_voxel(c.position)];
+        let tri_min = tri[0].min(tri[1]).min(tri[2]).floor().as_ivec3().clamp(IVec3::ZERO, last);
+        let tri_max = tri[0].max(tri[1]).max(tri[2]).floor().as_ivec3().clamp(IVec3::ZERO, last);
+        for y in tri_min.y..=tri_max.y {
+            for z in tri_min.z..=tri_max.z {
+                for x in tri_min.x..=tri_max.x {
+                    let center = vec3(x as f32, y as f32, z as f32) + 0.5;
+                    if triangle_box_overlap(center, Vec3::splat(0.5), tri) {
+                        chunk.set(x, y, z, id);
+                    }
+                }
+            }
+        }
+    }
+
+    if options.fill == Fill::Solid {
+        fill_interior(&mut chunk, resolution, options.fill_id);
+    }
+    chunk
+}
+
+/// Flood fills the outside of the shell from the border of the (padded) volume, then fills
+/// everything that wasn't reached.
+fn fill_interior(chunk: &mut RaytraceChunk, resolution: i32, fill_id: u32) {
+    // Padded by one voxel on every side so that the flood can get around the shell.
+    let size = resolution + 2;
+    let index = |cell: IVec3| ((cell.y * size + cell.z) * size + cell.x) as usize;
+    let is_shell = |chunk: &RaytraceChunk, cell: IVec3| {
+        let inner = cell - 1;
+        inner.cmpge(IVec3::ZERO).all() && inner.cmplt(IVec3::splat(resolution)).all() && chunk.get(inner.x, inner.y, inner.z) != 0
+    };
+    let mut outside = vec![false; (size * size * size) as usize];
+    let mut queue = VecDeque::from([IVec3::ZERO]);
+    outside[0] = true;
+    const NEIGHBORS: [IVec3; 6] = [IVec3::X, IVec3::NEG_X, IVec3::Y, IVec3::NEG_Y, IVec3::Z, IVec3::NEG_Z];
+    while let Some(cell) = queue.pop_front() {
+        for offset in NEIGHBORS {
+            let next = cell + offset;
+            if next.cmplt(IVec3::ZERO).any() || next.cmpge(IVec3::splat(size)).any() {
+                continue;
+            }
+            let next_index = index(next);
+            if outside[next_index] || is_shell(chunk, next) {
+                continue;
+            }
+            outside[next_index] = true;
+            queue.push_back(next);
+        }
+    }
+    for y in 0..resolution {
+        for z in 0..resolution {
+            for x in 0..resolution {
+                let padded = ivec3(x, y, z) + 1;
+                if !outside[index(padded)] && chunk.get(x, y, z) == 0 {
+                    chunk.set(x, y, z, fill_id);
+                }
+            }
+        }
+    }
+}
+
+/// Separating axis test between a triangle and an axis aligned box (Akenine-Möller).
+/// Touching counts as overlapping.
+pub fn triangle_box_overlap(center: Vec3, half_size: Vec3, triangle: [Vec3; 3]) -> bool {
+    let v = triangle.map(|point| point - center);
+    let edges = [v[1] - v[0], v[2] - v[1], v[0] - v[2]];
+
+    // Returns true if `axis` separates the triangle from the box.
+    let separated = |axis: Vec3| {
+        if axis.length_squared() <= f32::EPSILON {
+            return false;
+        }
+        let p = v.map(|vertex| vertex.dot(axis));
+        let min = p[0].min(p[1]).min(p[2]);
+        let max = p[0].max(p[1]).max(p[2]);
+        let radius = half_size.dot(axis.abs());
+        min > radius || max < -radius
+    };
+
+    // The 9 cross products of the box axes and triangle edges.
+    for box_axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+        for edge in edges {
+            if separated(box_axis.cross(edge)) {
+                return false;
+            }
+        }
+    }
+    // The box's face normals.
+    for axis in 0..3 {
+        let min = v[0][axis].min(v[1][axis]).min(v[2][axis]);
+        let max = v[0][axis].max(v[1][axis]).max(v[2][axis]);
+        if min > half_size[axis] || max < -half_size[axis] {
+            return false;
+        }
+    }
+    // The triangle's normal.
+    !separated(edges[0].cross(edges[1]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A closed cube from (0, 0, 0) to (1, 1, 1).
+    fn cube() -> MeshData {
+        let mut mesh = MeshData::default();
+        for corner in 0..8u32 {
+            let position = vec3((corner & 1) as f32, ((corner >> 1) & 1) as f32, ((corner >> 2) & 1) as f32);
+            mesh.vertices.push(Vertex::new(position, Vec2::ZERO, 0));
+        }
+        // Two triangles per face (winding doesn't matter for voxelization).
+        const QUADS: [[u32; 4]; 6] = [
+            [0, 1, 3, 2], [4, 5, 7, 6], // -Z, +Z
+            [0, 1, 5, 4], [2, 3, 7, 6], // -Y, +Y
+            [0, 2, 6, 4], [1, 3, 7, 5], // -X, +X
+        ];
+        for [a, b, c, d] in QUADS {
+            mesh.indices.extend_from_slice(&[a, b, c, a, c, d]);
+        }
+        mesh
+    }
+
+    fn count(chunk: &RaytraceChunk, id: u32) -> usize {
+        (0..64).flat_map(|y| (0..64).flat_map(move |z| (0..64).map(move |x| (x, y, z))))
+            .filter(|&(x, y, z)| chunk.get(x, y, z) == id)
+            .count()
+    }
+
+    #[test]
+    fn shell_test() {
+        let chunk = voxelize(&cube(), 8, &VoxelizeOptions::default());
+        assert_eq!(count(&chunk, 1), 8 * 8 * 8 - 6 * 6 * 6);
+        assert_eq!(chunk.get(0, 0, 0), 1);
+        assert_eq!(chunk.get(3, 3, 3), 0);
+        assert_eq!(chunk.get(8, 0, 0), 0);
+    }
+
+    #[test]
+    fn solid_test() {
+        let options = VoxelizeOptions {
+            fill_id: 2,
+            ..VoxelizeOptions::default().solid()
+        };
+        let chunk = voxelize(&cube(), 8, &options);
+        assert_eq!(count(&chunk, 1), 8 * 8 * 8 - 6 * 6 * 6);
+        assert_eq!(count(&chunk, 2), 6 * 6 * 6);
+    }
+
+    #[test]
+    fn material_test() {
+        let mut mesh = cube();
+        for vertex in mesh.vertices.iter_mut() {
+            vertex.texindex = 5;
+        }
+        let chunk = voxelize(&mesh, 4, &VoxelizeOptions::default().with_material_id(5, 7));
+        assert_eq!(count(&chunk, 7), 4 * 4 * 4 - 2 * 2 * 2);
+    }
+
+    #[test]
+    fn overlap_test() {
+        let triangle = [vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0)];
+        assert!(triangle_box_overlap(vec3(0.25, 0.25, 0.0), Vec3::splat(0.1), triangle));
+        assert!(!triangle_box_overlap(vec3(0.25, 0.25, 0.5), Vec3::splat(0.1), triangle));
+        // Beyond the hypotenuse.
+        assert!(!triangle_box_overlap(vec3(0.8, 0.8, 0.0), Vec3::splat(0.1), triangle));
+    }
+}
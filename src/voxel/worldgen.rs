@@ -0,0 +1,52 @@
+use crate::rendering::raytrace::RaytraceChunk;
+
+/// Generates rolling hills from a few layered sine waves. Different seeds shift and rotate the waves.
+/// This is slow enough that it should be run on a [crate::tasks::TaskPool].
+pub fn generate_hills(seed: u32, block_id: u32) -> RaytraceChunk {
+    let mut chunk = RaytraceChunk::new();
+    // Cheap per-seed parameters in 0..1
+    let param = |index: u32| {
+        let hash = seed.wrapping_mul(0x9E3779B9).wrapping_add(index.wrapping_mul(0x85EBCA6B));
+        let hash = (hash ^ (hash >> 15)).wrapping_mul(0x2C1B3C6D);
+        (hash ^ (hash >> 12)) as f32 / u32::MAX as f32
+    };
+    let angle = param(0) * std::f32::consts::TAU;
+    let (sin, cos) = angle.sin_cos();
+    let phase_a = param(1) * 100.0;
+    let phase_b = param(2) * 100.0;
+    for z in 0..64 {
+        for x in 0..64 {
+            let (xf, zf) = (x as f32, z as f32);
+            let u = xf * cos - zf * sin;
+            let v = xf * sin + zf * cos;
+            let height = 12.0
+                + (u * 0.09 + phase_a).sin() * 6.0
+                + (v * 0.07 + phase_b).sin() * 5.0
+                + ((u + v) * 0.21).sin() * 2.0;
+            let height = (height.round() as i32).clamp(1, 63);
+            for y in 0..height {
+                chunk.set(x, y, z, block_id);
+            }
+        }
+    }
+    chunk
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hills_test() {
+        let chunk = generate_hills(3, 1);
+        for z in 0..64 {
+            for x in 0..64 {
+                assert_eq!(chunk.get(x, 0, z), 1);
+                assert_eq!(chunk.get(x, 63, z), 0);
+            }
+        }
+        // Deterministic per seed.
+        let again = generate_hills(3, 1);
+        assert!((0..64).all(|y| chunk.get(17, y, 40) == again.get(17, y, 40)));
+    }
+}
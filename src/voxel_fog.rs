@@ -1,7 +1,11 @@
 use bytemuck::NoUninit;
 use glam::Vec4;
 
+use crate::rendering::{bindings::{BindGroupBuilder, Bindings}, buffers::UniformBuffer};
 
+
+/// Distance fog, with optional height fog. Shared by the voxel pipeline and the raytracer.
+/// A fog color with an alpha of `0.0` fades into whatever is behind (the skybox).
 #[repr(C)]
 #[repr(align(16))]
 #[derive(Debug, Clone, Copy, NoUninit)]
@@ -9,7 +13,10 @@ pub struct Fog {
     pub color: [f32; 4],
     pub start: f32,
     pub end: f32,
-    pub padding: [u8; 8],
+    /// The density of the height fog at `y = 0`. `0.0` disables height fog. Only the raytracer uses height fog.
+    pub height_density: f32,
+    /// How quickly the height fog thins out going up.
+    pub height_falloff: f32,
 }
 
 impl Fog {
@@ -18,10 +25,17 @@ impl Fog {
             start,
             end,
             color: color.to_array(),
-            padding: [0; 8],
+            height_density: 0.0,
+            height_falloff: 0.0,
         }
     }
 
+    /// Fog that thins out exponentially with height. A `density` of `0.0` disables it.
+    pub fn with_height_fog(mut self, density: f32, falloff: f32) -> Self {
+        self.set_height_fog(density, falloff);
+        self
+    }
+
     pub fn set_start(&mut self, start: f32) {
         self.start = start;
     }
@@ -33,47 +47,29 @@ impl Fog {
     pub fn set_color(&mut self, color: Vec4) {
         self.color = color.to_array();
     }
+
+    pub fn set_height_fog(&mut self, density: f32, falloff: f32) {
+        self.height_density = density.max(0.0);
+        self.height_falloff = falloff.max(1e-4);
+    }
 }
 
 pub struct FogBindGroup {
-    pub buffer: wgpu::Buffer,
+    pub buffer: UniformBuffer<Fog>,
     pub bind_group: wgpu::BindGroup,
     pub bind_group_layout: wgpu::BindGroupLayout,
 }
 
 impl FogBindGroup {
     pub fn new(device: &wgpu::Device) -> Self {
-        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Fog Buffer"),
-            size: std::mem::size_of::<Fog>() as wgpu::BufferAddress,
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Fog Bind Group Layout"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }
-            ],
-        });
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Fog Bind Group"),
-            layout: &bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: buffer.as_entire_binding(),
-                },
-            ],
-        });
+        let buffer = UniformBuffer::new(device, Some("Fog Buffer"), Fog::new(0.0, 0.0, Vec4::ZERO));
+        let bind_group_layout = BindGroupBuilder::new()
+            .label("Fog Bind Group Layout")
+            .uniform(0, wgpu::ShaderStages::FRAGMENT)
+            .build(device);
+        let bind_group = Bindings::new()
+            .buffer(0, buffer.buffer())
+            .build(device, Some("Fog Bind Group"), &bind_group_layout);
         Self {
             buffer,
             bind_group,
@@ -82,10 +78,6 @@ impl FogBindGroup {
     }
 
     pub fn write_fog(&self, queue: &wgpu::Queue, fog: &Fog) {
-        queue.write_buffer(
-            &self.buffer,
-            0,
-            bytemuck::bytes_of(fog),
-        );
+        self.buffer.write(queue, *fog);
     }
 }
\ No newline at end of file
@@ -0,0 +1,117 @@
+use std::path::PathBuf;
+
+use winit::dpi::{LogicalSize, PhysicalPosition, Size};
+use winit::monitor::{MonitorHandle, VideoMode};
+use winit::window::{Fullscreen, Icon, Window};
+
+use crate::assets::{AssetError, AssetServer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FullscreenMode {
+    Windowed,
+    /// A borderless window that covers the monitor.
+    Borderless,
+    /// Exclusive fullscreen, using the monitor's current video mode.
+    Exclusive,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MonitorSelection {
+    /// The monitor that the window is currently on.
+    Current,
+    Primary,
+    /// An index into the available monitors. Falls back to the current monitor if it's out of range.
+    Index(usize),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum WindowIconError {
+    #[error(transparent)]
+    Asset(#[from] AssetError),
+    #[error("Invalid window icon: {0}")]
+    BadIcon(#[from] winit::window::BadIcon),
+}
+
+/// Window settings, applied with [crate::state::State::apply_window_config].
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowConfig {
+    pub title: String,
+    /// The inner size while windowed.
+    pub size: Size,
+    pub min_size: Option<Size>,
+    pub fullscreen: FullscreenMode,
+    /// The monitor used for fullscreen.
+    pub monitor: MonitorSelection,
+    /// Relative to the asset root.
+    pub icon: Option<PathBuf>,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: String::from("WGPU Sandbox"),
+            size: Size::Logical(LogicalSize::new(1280.0, 720.0)),
+            min_size: Some(Size::Logical(LogicalSize::new(320.0, 180.0))),
+            fullscreen: FullscreenMode::Windowed,
+            monitor: MonitorSelection::Current,
+            icon: Some(PathBuf::from("textures/cube_sides/grass_001.png")),
+        }
+    }
+}
+
+impl WindowConfig {
+    pub fn select_monitor(&self, window: &Window) -> Option<MonitorHandle> {
+        match self.monitor {
+            MonitorSelection::Current => window.current_monitor(),
+            MonitorSelection::Primary => window.primary_monitor().or_else(|| window.current_monitor()),
+            MonitorSelection::Index(index) => window.available_monitors().nth(index).or_else(|| window.current_monitor()),
+        }
+    }
+
+    /// The fullscreen state for [Window::set_fullscreen]. Exclusive fullscreen falls back to borderless if the
+    /// monitor's current video mode can't be found.
+    pub fn fullscreen(&self, window: &Window) -> Option<Fullscreen> {
+        let monitor = self.select_monitor(window);
+        match self.fullscreen {
+            FullscreenMode::Windowed => None,
+            FullscreenMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+            FullscreenMode::Exclusive => match monitor.as_ref().and_then(current_video_mode) {
+                Some(mode) => Some(Fullscreen::Exclusive(mode)),
+                None => {
+                    log::warn!("Couldn't find the current video mode, using borderless fullscreen instead.");
+                    Some(Fullscreen::Borderless(monitor))
+                }
+            },
+        }
+    }
+
+    pub fn load_icon(&self, assets: &AssetServer) -> Result<Option<Icon>, WindowIconError> {
+        let Some(path) = &self.icon else {
+            return Ok(None);
+        };
+        let image = assets.read_image(path)?;
+        let (width, height) = image.dimensions();
+        Ok(Some(Icon::from_rgba(image.into_raw(), width, height)?))
+    }
+}
+
+/// The video mode that matches the monitor's current resolution and refresh rate, with the highest bit depth.
+pub fn current_video_mode(monitor: &MonitorHandle) -> Option<VideoMode> {
+    let size = monitor.size();
+    let refresh_rate = monitor.refresh_rate_millihertz();
+    monitor.video_modes()
+        .filter(|mode| mode.size() == size)
+        .filter(|mode| refresh_rate.is_none() || Some(mode.refresh_rate_millihertz()) == refresh_rate)
+        .max_by_key(|mode| mode.bit_depth())
+}
+
+/// Moves the window to the center of the monitor.
+pub fn center_window(window: &Window, monitor: &MonitorHandle) {
+    let window_size = window.outer_size();
+    let monitor_size = monitor.size();
+    let monitor_position = monitor.position();
+    window.set_outer_position(PhysicalPosition::new(
+        monitor_position.x + (monitor_size.width as i32 - window_size.width as i32) / 2,
+        monitor_position.y + (monitor_size.height as i32 - window_size.height as i32) / 2,
+    ));
+}